@@ -93,8 +93,7 @@ static int push_get_sent_nbytes(const int sockqd, size_t data_size)
 
     assert(sga.sga_segs != 0);
     memset(sga.sga_segs[0].sgaseg_buf, 1, data_size);
-    // ToDo: demi_pushto() also must work for TCP on all LibOSes.
-    // FIXME: https://github.com/microsoft/demikernel/issues/137
+    // demi_pushto() fails with EISCONN on connected TCP sockets, so use demi_push().
     assert(demi_push(&tok, sockqd, &sga) == 0);
     assert(demi_wait(&res, tok, NULL) == 0);
     assert(res.qr_opcode == DEMI_OPC_PUSH);
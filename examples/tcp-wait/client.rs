@@ -102,7 +102,7 @@ impl TcpClient {
 
             // Wait for pop().
             match self.libos.wait(pop_qt, Some(TIMEOUT_SECONDS)) {
-                Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_POP && qr.qr_ret == 0 => {
+                Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_POP => {
                     let sga: demi_sgarray_t = unsafe { qr.qr_value.sga };
                     let sgaseg_len: u32 = sga.sga_segs[0].sgaseg_len;
                     self.libos.sgafree(sga)?;
@@ -167,7 +167,7 @@ impl TcpClient {
 
             // Wait for pop().
             match self.libos.wait(pop_qt, Some(TIMEOUT_SECONDS)) {
-                Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_POP && qr.qr_ret == 0 => {
+                Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_POP => {
                     let sga: demi_sgarray_t = unsafe { qr.qr_value.sga };
                     let sgaseg_len: u32 = sga.sga_segs[0].sgaseg_len;
                     self.libos.sgafree(sga)?;
@@ -228,7 +228,7 @@ impl TcpClient {
 
             // Wait for pop().
             match self.libos.wait(pop_qt, Some(TIMEOUT_SECONDS)) {
-                Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_POP && qr.qr_ret == 0 => {
+                Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_POP => {
                     let sga: demi_sgarray_t = unsafe { qr.qr_value.sga };
                     let sgaseg_len: u32 = sga.sga_segs[0].sgaseg_len;
                     self.libos.sgafree(sga)?;
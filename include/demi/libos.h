@@ -78,6 +78,31 @@ extern "C"
     ATTR_NONNULL(1)
     extern int demi_accept(_Out_ demi_qtoken_t *qt_out, _In_ int sockqd);
 
+    /**
+     * @brief Checks whether there are connection requests waiting to be accepted on a socket I/O queue. This does not
+     * accept any connection.
+     *
+     * @param sockqd      I/O queue descriptor of the target socket.
+     * @param pending_out Store location for the result: non-zero if a call to demi_accept() would complete right away.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(2)
+    extern int demi_has_pending_connections(_In_ int sockqd, _Out_ int *pending_out);
+
+    /**
+     * @brief Asynchronously waits until a socket I/O queue can take at least @p min_bytes without queueing them behind
+     * flow or congestion control. The operation completes with DEMI_OPC_WRITABLE.
+     *
+     * @param qt_out    Store location for I/O queue token.
+     * @param sockqd    I/O queue descriptor of the target socket.
+     * @param min_bytes Minimum number of bytes that the socket should be able to take.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1)
+    extern int demi_writable(_Out_ demi_qtoken_t *qt_out, _In_ int sockqd, _In_ size_t min_bytes);
+
     /**
      * @brief Asynchronously initiates a connection on a socket I/O queue.
      *
@@ -101,6 +126,48 @@ extern "C"
      */
     extern int demi_close(_In_ int qd);
 
+    /**
+     * @brief Asynchronously closes an I/O queue descriptor.
+     *
+     * @details The queue token completes once the connection of a socket I/O queue is shut down, or reset, and
+     * everything that the I/O queue held is released. Closing an I/O queue that is already closing fails with
+     * EALREADY.
+     *
+     * @param qt_out Store location for I/O queue token.
+     * @param qd     Target I/O queue descriptor.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1)
+    extern int demi_async_close(_Out_ demi_qtoken_t *qt_out, _In_ int qd);
+
+    /**
+     * @brief Shuts down one or both directions of the connection on a socket I/O queue, without closing it.
+     *
+     * @details Shutting down the send direction (SHUT_WR) sends a FIN once the data that was pushed before went out,
+     * while data can still be popped until the remote closes its side. Shutting down the receive direction (SHUT_RD)
+     * makes pops return end-of-file. The queue descriptor must still be closed with demi_close().
+     *
+     * @param sockqd I/O queue descriptor of the target socket.
+     * @param how    Direction to shut down, either SHUT_RD, SHUT_WR or SHUT_RDWR.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    extern int demi_shutdown(_In_ int sockqd, _In_ int how);
+
+    /**
+     * @brief Closes an I/O queue right away.
+     *
+     * @details Unlike demi_close(), the connection of a socket I/O queue is reset instead of being shut down
+     * gracefully, so the remote learns right away that the connection is gone. Operations that are pending on the
+     * queue fail.
+     *
+     * @param qd Target I/O queue descriptor.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    extern int demi_abort(_In_ int qd);
+
     /**
      * @brief Asynchronously pushes a scatter-gather array to an I/O queue.
      *
@@ -139,6 +206,50 @@ extern "C"
     ATTR_NONNULL(1)
     extern int demi_pop(_Out_ demi_qtoken_t *qt_out, _In_ int qd);
 
+    /**
+     * @brief Asynchronously pops a scatter-gather array from an I/O queue, as flags say.
+     *
+     * @param qt_out Store location for I/O queue token.
+     * @param qd     Target I/O queue descriptor.
+     * @param flags  Either zero or MSG_PEEK, which leaves the data in the I/O queue for the next pop.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1)
+    extern int demi_pop_flags(_Out_ demi_qtoken_t *qt_out, _In_ int qd, _In_ int flags);
+
+    /**
+     * @brief Asynchronously forwards data from one I/O queue to another, without handing it to the application.
+     *
+     * @param qt_out Store location for I/O queue token.
+     * @param src_qd I/O queue descriptor to pop data from.
+     * @param dst_qd I/O queue descriptor to push data to.
+     *
+     * @details Once @p src_qd reaches the end of its stream, the send direction of @p dst_qd is shut down and the
+     * operation completes as a push on @p dst_qd. The operation fails as soon as either queue does.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1)
+    extern int demi_splice(_Out_ demi_qtoken_t *qt_out, _In_ int src_qd, _In_ int dst_qd);
+
+    /**
+     * @brief Asynchronously runs a chain of operations, each of which starts as soon as the one before it completes.
+     *
+     * @param qts_out Store location for one I/O queue token per operation.
+     * @param ops     Operations to run.
+     * @param num_ops Number of operations.
+     *
+     * @details An operation whose queue descriptor is DEMI_LINKED_QD runs on the I/O queue of the operation before it,
+     * which is the accepted connection if that one was an accept. Once an operation fails, the rest of the chain fails
+     * with ECANCELED.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1, 2)
+    extern int demi_submit_linked(_Out_writes_(num_ops) demi_qtoken_t *qts_out,
+                                  _In_reads_(num_ops) const demi_linked_op_t *ops, _In_ int num_ops);
+
     /**
      * @brief Sets socket options.
      *
@@ -33,6 +33,17 @@ extern "C"
      */
     extern int demi_sgafree(_In_ demi_sgarray_t *sga);
 
+    /**
+     * @brief Appends the segments of a scatter-gather array to another one, without copying their data.
+     *
+     * @param sga  Target scatter-gather array.
+     * @param tail Scatter-gather array whose segments are appended to @p sga.
+     *
+     * @return On successful completion, zero is returned and @p tail must not be released on its own anymore. On
+     * failure, a positive error code is returned instead and neither scatter-gather array is changed.
+     */
+    extern int demi_sgaappend(_Inout_ demi_sgarray_t *sga, _In_ demi_sgarray_t *tail);
+
 #ifdef __cplusplus
 }
 #endif
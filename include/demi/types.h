@@ -24,8 +24,16 @@ extern "C"
 
 /**
  * @brief Maximum number of segments in a scatter-gather array.
+ *
+ * @note This was 1 before version 2.0.0, which changes the size and layout of demi_sgarray_t. Code built against older
+ * headers must be rebuilt.
  */
-#define DEMI_SGARRAY_MAXSIZE 1
+#define DEMI_SGARRAY_MAXSIZE 16
+
+/**
+ * @brief Queue descriptor of a linked operation that runs on the queue of the operation before it.
+ */
+#define DEMI_LINKED_QD -1
 
     /**
      * @brief An I/O queue token.
@@ -82,6 +90,7 @@ extern "C"
         DEMI_OPC_CONNECT,     /**< Connect operation. */
         DEMI_OPC_CLOSE,       /**< Close operation. */
         DEMI_OPC_FAILED,      /**< Operation failed.  */
+        DEMI_OPC_WRITABLE,    /**< Writable operation. */
     } demi_opcode_t;
 
 /**
@@ -95,8 +104,9 @@ extern "C"
         typedef struct __attribute__((__packed__)) demi_accept_result
 #endif
     {
-        int32_t qd;              /**< Socket I/O queue descriptor of accepted connection. */
-        struct sockaddr_in addr; /**< Remote address of accepted connection.              */
+        int32_t qd;               /**< Socket I/O queue descriptor of accepted connection. */
+        struct sockaddr_in addr;  /**< Remote address of accepted connection.              */
+        struct sockaddr_in local; /**< Local address of accepted connection.               */
     } demi_accept_result_t;
 #ifdef _WIN32
 #pragma pack(pop)
@@ -104,19 +114,16 @@ extern "C"
 
 /**
  * @brief Result value for an asynchronous I/O operation.
+ *
+ * @note Unlike the structures that it holds, this is not packed, so it ends with padding up to the alignment of its
+ * 64-bit fields. Arrays of results (e.g. for demi_wait_any()) rely on this.
  */
-#ifdef _WIN32
-#pragma pack(push, 1)
     typedef struct demi_qresult
-#endif
-#ifdef __linux__
-        typedef struct __attribute__((__packed__)) demi_qresult
-#endif
     {
         enum demi_opcode qr_opcode; /**< Opcode of completed operation.                              */
         int32_t qr_qd;              /**< I/O queue descriptor associated to the completed operation. */
         demi_qtoken_t qr_qt;        /**< I/O queue token of the completed operation.                 */
-        int64_t qr_ret;             /**< Return code.                                                */
+        int64_t qr_ret;             /**< Return code. Bytes still buffered for pops.                 */
 
         /**
          * @brief Result value.
@@ -127,10 +134,56 @@ extern "C"
             demi_accept_result_t ares; /**< Accept result.                      */
         } qr_value;
     } demi_qresult_t;
+
+/**
+ * @brief Operation of a chain that is submitted with demi_submit_linked().
+ */
+#ifdef _WIN32
+#pragma pack(push, 1)
+    typedef struct demi_linked_op
+#endif
+#ifdef __linux__
+        typedef struct __attribute__((__packed__)) demi_linked_op
+#endif
+    {
+        enum demi_opcode op_opcode; /**< DEMI_OPC_ACCEPT, DEMI_OPC_POP or DEMI_OPC_PUSH.                    */
+        int32_t op_qd;              /**< Target I/O queue descriptor, or DEMI_LINKED_QD.                   */
+        demi_sgarray_t op_sga;      /**< Scatter-gather array to push, only used by DEMI_OPC_PUSH.         */
+    } demi_linked_op_t;
+#ifdef _WIN32
+#pragma pack(pop)
+#endif
+
+/**
+ * @brief Statistics of a TCP connection, as returned by demi_getsockopt() for TCP_INFO.
+ */
+#ifdef _WIN32
+#pragma pack(push, 1)
+    typedef struct demi_tcp_info
+#endif
+#ifdef __linux__
+        typedef struct __attribute__((__packed__)) demi_tcp_info
+#endif
+    {
+        uint8_t ti_state;            /**< State of the connection, numbered like the TCP_* states of Linux.    */
+        uint32_t ti_srtt_us;         /**< Smoothed round-trip time in microseconds, zero if not measured yet.  */
+        uint32_t ti_rto_us;          /**< Retransmission timeout in microseconds.                              */
+        uint32_t ti_snd_cwnd;        /**< Congestion window in bytes.                                          */
+        uint32_t ti_snd_ssthresh;    /**< Slow start threshold in bytes.                                       */
+        uint64_t ti_total_retrans;   /**< Number of retransmitted segments.                                    */
+        uint32_t ti_bytes_in_flight; /**< Number of bytes that were sent but not acknowledged yet.             */
+        uint32_t ti_rcv_wnd;         /**< Receive window that is advertised to the peer, in bytes.             */
+        uint32_t ti_snd_mss;         /**< Maximum segment size that is sent.                                   */
+    } demi_tcp_info_t;
 #ifdef _WIN32
 #pragma pack(pop)
 #endif
 
+    /**
+     * @brief Ring that hands off completions from the thread that runs Demikernel to an application thread.
+     */
+    typedef struct demi_completion_ring demi_completion_ring_t;
+
     // Callback Function.
     typedef void (*demi_callback_t)(const char *, uint32_t, uint64_t);
 
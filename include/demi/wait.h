@@ -57,6 +57,54 @@ extern "C"
     extern int demi_wait_next_n(_Out_writes_to_(num_qrs, *ready_offset) demi_qresult_t *qr_out, _In_ int num_qrs,
                                 _Out_ int *num_qrs_out, _In_opt_ const struct timespec *timeout);
 
+    /**
+     * @brief Creates a ring that hands off completions from the thread that runs Demikernel to an application thread.
+     *
+     * @param ring_out Store location for the new ring.
+     * @param capacity Number of completions that the ring holds, which has to be a power of two.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1)
+    extern int demi_completion_ring_create(_Out_ demi_completion_ring_t **ring_out, _In_ size_t capacity);
+
+    /**
+     * @brief Destroys a completion ring once neither thread uses it anymore.
+     *
+     * @param ring Target completion ring.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1)
+    extern int demi_completion_ring_destroy(_In_ demi_completion_ring_t *ring);
+
+    /**
+     * @brief Waits for asynchronous I/O operations to complete and hands their results off to a completion ring. This
+     * must be called on the thread that runs Demikernel.
+     *
+     * @param ring        Target completion ring.
+     * @param num_qrs_out The number of results that were handed off.
+     * @param timeout     Timeout interval in seconds and nanoseconds.
+     *
+     * @return On successful completion, zero is returned. On failure, a positive error code is returned instead.
+     */
+    ATTR_NONNULL(1, 2)
+    extern int demi_wait_into_ring(_In_ demi_completion_ring_t *ring, _Out_ int *num_qrs_out,
+                                   _In_opt_ const struct timespec *timeout);
+
+    /**
+     * @brief Takes the oldest result out of a completion ring without blocking. This may be called on a thread that does
+     * not run Demikernel.
+     *
+     * @param qr_out Store location for the result of the completed I/O operation.
+     * @param ring   Target completion ring.
+     *
+     * @return On successful completion, zero is returned. If the ring is empty, EAGAIN is returned. On failure, a
+     * positive error code is returned instead.
+     */
+    ATTR_NONNULL(1, 2)
+    extern int demi_completion_ring_pop(_Out_ demi_qresult_t *qr_out, _In_ demi_completion_ring_t *ring);
+
 #ifdef __cplusplus
 }
 #endif
@@ -15,6 +15,9 @@ export LD_LIBRARY_PATH ?= $(CURDIR)/lib:$(shell find $(PREFIX)/lib/ -name '*x86_
 #=======================================================================================================================
 
 export BUILD := release
+ifeq ($(CONTAIN_PANICS),yes)
+export BUILD := release-unwind
+endif
 ifeq ($(DEBUG),yes)
 export RUST_LOG ?= trace
 export BUILD := dev
@@ -29,6 +32,9 @@ export INCDIR := $(CURDIR)/include
 export LIBDIR ?= $(CURDIR)/lib
 export SRCDIR = $(CURDIR)/src
 export BUILD_DIR := $(CURDIR)/target/release
+ifeq ($(BUILD),release-unwind)
+export BUILD_DIR := $(CURDIR)/target/release-unwind
+endif
 ifeq ($(BUILD),dev)
 export BUILD_DIR := $(CURDIR)/target/debug
 endif
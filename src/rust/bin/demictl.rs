@@ -0,0 +1,110 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+#![deny(clippy::all)]
+
+//! Client for the control server of a running Demikernel libOS. It sends a single command and prints the response.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::anyhow::Result;
+use ::clap::{Arg, ArgMatches, Command};
+#[cfg(target_os = "linux")]
+use ::std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+const DEFAULT_CONTROL_SOCKET: &str = "/tmp/demikernel.sock";
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+#[derive(Debug)]
+pub struct ProgramArguments {
+    socket: String,
+    command: String,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl ProgramArguments {
+    pub fn new() -> Result<Self> {
+        let matches: ArgMatches = Command::new("demictl")
+            .about("Inspects a running Demikernel libOS through its control socket")
+            .arg(
+                Arg::new("socket")
+                    .long("socket")
+                    .value_parser(clap::value_parser!(String))
+                    .required(false)
+                    .value_name("PATH")
+                    .help("Sets the path of the control socket"),
+            )
+            .arg(
+                Arg::new("command")
+                    .value_parser(clap::value_parser!(String))
+                    .num_args(1..)
+                    .required(false)
+                    .value_name("COMMAND")
+                    .help("Sets the command to run (e.g. sockets, log debug, stages on)"),
+            )
+            .get_matches();
+
+        let socket: String = matches
+            .get_one::<String>("socket")
+            .cloned()
+            .unwrap_or(DEFAULT_CONTROL_SOCKET.to_string());
+        let command: String = match matches.get_many::<String>("command") {
+            Some(words) => words.cloned().collect::<Vec<String>>().join(" "),
+            None => "help".to_string(),
+        };
+
+        Ok(Self { socket, command })
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+#[cfg(target_os = "linux")]
+fn main() -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new()?;
+
+    let mut stream: UnixStream = match UnixStream::connect(&args.socket) {
+        Ok(stream) => stream,
+        Err(e) => anyhow::bail!("cannot connect to {:?}: {:?}", args.socket, e),
+    };
+    stream.write_all(format!("{}\n", args.command).as_bytes())?;
+
+    // The server ends each response with an empty line.
+    let mut failed: bool = false;
+    for line in BufReader::new(stream).lines() {
+        let line: String = line?;
+        if line.is_empty() {
+            break;
+        }
+        failed |= line.starts_with("error:");
+        println!("{}", line);
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() -> Result<()> {
+    let _args: ProgramArguments = ProgramArguments::new()?;
+    anyhow::bail!("demictl is only supported on Linux")
+}
@@ -0,0 +1,266 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+#![deny(clippy::all)]
+
+//! Diagnostics tool for a running catnip libOS. It attaches to the libOS as a secondary DPDK process, so it can read
+//! counters and capture packets without going through the libOS or interfering with its data path. The libOS has to
+//! run with `dpdk.diagnostics` enabled.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::anyhow::Result;
+use ::clap::{Arg, ArgMatches, Command};
+use ::demikernel::{
+    demikernel::config::Config, CaptureDirection, CaptureRecord, DiagnosticsClient, PortStats, MAX_CAPTURE_RECORD_SIZE,
+    MAX_SNAPLEN,
+};
+use ::std::{
+    fs::File,
+    io::{BufWriter, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// How long to wait before looking at the capture ring again once it is empty.
+const CAPTURE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Magic number of pcap files with nanosecond timestamps.
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+
+/// Link type of Ethernet frames in pcap files.
+const PCAP_LINKTYPE_ETHERNET: u32 = 1;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+#[derive(Debug)]
+pub struct ProgramArguments {
+    config: String,
+    command: DiagnosticsCommand,
+}
+
+#[derive(Debug)]
+enum DiagnosticsCommand {
+    /// Prints the counters every `interval`, or once if there is none.
+    Stats { interval: Option<Duration> },
+    /// Captures `count` packets that went in `direction` (or both ways if there is none) into a pcap file.
+    Capture {
+        output: String,
+        count: Option<usize>,
+        snaplen: usize,
+        direction: Option<CaptureDirection>,
+    },
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl ProgramArguments {
+    pub fn new() -> Result<Self> {
+        let matches: ArgMatches = Command::new("demidiag")
+            .about("Reads counters and captures packets of a running catnip libOS")
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .value_parser(clap::value_parser!(String))
+                    .required(true)
+                    .value_name("PATH")
+                    .help("Sets the configuration file of the libOS, whose EAL arguments are reused"),
+            )
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("stats").about("Prints counters").arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .value_parser(clap::value_parser!(u64))
+                        .required(false)
+                        .value_name("MILLISECONDS")
+                        .help("Prints the counters periodically instead of once"),
+                ),
+            )
+            .subcommand(
+                Command::new("capture")
+                    .about("Captures packets into a pcap file")
+                    .arg(
+                        Arg::new("output")
+                            .long("output")
+                            .value_parser(clap::value_parser!(String))
+                            .required(true)
+                            .value_name("PATH")
+                            .help("Sets the pcap file to write"),
+                    )
+                    .arg(
+                        Arg::new("count")
+                            .long("count")
+                            .value_parser(clap::value_parser!(usize))
+                            .required(false)
+                            .value_name("PACKETS")
+                            .help("Stops after this many packets"),
+                    )
+                    .arg(
+                        Arg::new("snaplen")
+                            .long("snaplen")
+                            .value_parser(clap::value_parser!(usize))
+                            .required(false)
+                            .value_name("BYTES")
+                            .help("Sets the number of bytes to capture of each packet"),
+                    )
+                    .arg(
+                        Arg::new("direction")
+                            .long("direction")
+                            .value_parser(["rx", "tx"])
+                            .required(false)
+                            .value_name("rx|tx")
+                            .help("Only captures packets that went this way"),
+                    ),
+            )
+            .get_matches();
+
+        let config: String = matches
+            .get_one::<String>("config")
+            .cloned()
+            .expect("config is a required argument");
+        let command: DiagnosticsCommand = match matches.subcommand() {
+            Some(("stats", matches)) => DiagnosticsCommand::Stats {
+                interval: matches.get_one::<u64>("interval").map(|ms| Duration::from_millis(*ms)),
+            },
+            Some(("capture", matches)) => DiagnosticsCommand::Capture {
+                output: matches
+                    .get_one::<String>("output")
+                    .cloned()
+                    .expect("output is a required argument"),
+                count: matches.get_one::<usize>("count").copied(),
+                snaplen: matches.get_one::<usize>("snaplen").copied().unwrap_or(MAX_SNAPLEN),
+                direction: match matches.get_one::<String>("direction").map(|d| d.as_str()) {
+                    Some("rx") => Some(CaptureDirection::Rx),
+                    Some("tx") => Some(CaptureDirection::Tx),
+                    _ => None,
+                },
+            },
+            _ => anyhow::bail!("unknown command"),
+        };
+
+        Ok(Self { config, command })
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+fn print_stats(stats: &PortStats) {
+    let json: ::serde_json::Value = ::serde_json::json!({
+        "rx_packets": stats.rx_packets,
+        "rx_bytes": stats.rx_bytes,
+        "tx_packets": stats.tx_packets,
+        "tx_bytes": stats.tx_bytes,
+        "tx_drops": stats.tx_drops,
+        "capture_drops": stats.capture_drops,
+    });
+    println!("{}", json);
+}
+
+fn write_pcap_header(out: &mut impl Write, snaplen: usize) -> Result<()> {
+    out.write_all(&PCAP_MAGIC_NANOS.to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&4u16.to_le_bytes())?;
+    // Timezone offset and timestamp accuracy, which are always zero.
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&(snaplen as u32).to_le_bytes())?;
+    out.write_all(&PCAP_LINKTYPE_ETHERNET.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_pcap_record(out: &mut impl Write, record: &CaptureRecord) -> Result<()> {
+    out.write_all(&((record.timestamp_ns / 1_000_000_000) as u32).to_le_bytes())?;
+    out.write_all(&((record.timestamp_ns % 1_000_000_000) as u32).to_le_bytes())?;
+    out.write_all(&(record.data.len() as u32).to_le_bytes())?;
+    out.write_all(&(record.orig_len as u32).to_le_bytes())?;
+    out.write_all(record.data)?;
+    Ok(())
+}
+
+fn capture(
+    client: &DiagnosticsClient,
+    output: &str,
+    count: Option<usize>,
+    snaplen: usize,
+    direction: Option<CaptureDirection>,
+) -> Result<()> {
+    let mut out: BufWriter<File> = BufWriter::new(File::create(output)?);
+    write_pcap_header(&mut out, snaplen)?;
+
+    if let Err(e) = client.start_capture(snaplen) {
+        anyhow::bail!("cannot start capture: {:?}", e.cause);
+    }
+    let drops_before: u64 = client.get_stats().capture_drops;
+    let mut captured: usize = 0;
+    let mut buf: [u8; MAX_CAPTURE_RECORD_SIZE] = [0; MAX_CAPTURE_RECORD_SIZE];
+    let mut last_flush: Instant = Instant::now();
+    while count.map_or(true, |count| captured < count) {
+        match client.try_pop_capture(&mut buf) {
+            Some(record) if direction.map_or(true, |direction| direction == record.direction) => {
+                write_pcap_record(&mut out, &record)?;
+                captured += 1;
+            },
+            Some(_) => (),
+            None => {
+                // Flush every now and then, so that the file can be followed while the capture runs.
+                if last_flush.elapsed() > Duration::from_secs(1) {
+                    out.flush()?;
+                    last_flush = Instant::now();
+                }
+                thread::sleep(CAPTURE_POLL_INTERVAL);
+            },
+        }
+    }
+    client.stop_capture();
+    out.flush()?;
+
+    let dropped: u64 = client.get_stats().capture_drops - drops_before;
+    eprintln!("captured {} packets, {} dropped by the capture ring", captured, dropped);
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args: ProgramArguments = ProgramArguments::new()?;
+    let config: Config = match Config::new(args.config.clone()) {
+        Ok(config) => config,
+        Err(e) => anyhow::bail!("cannot read {:?}: {:?}", args.config, e.cause),
+    };
+    let eal_init_args = match config.eal_init_args() {
+        Ok(eal_init_args) => eal_init_args,
+        Err(e) => anyhow::bail!("cannot read EAL arguments: {:?}", e.cause),
+    };
+    let client: DiagnosticsClient = match DiagnosticsClient::attach(&eal_init_args) {
+        Ok(client) => client,
+        Err(e) => anyhow::bail!("cannot attach to the primary process: {:?}", e.cause),
+    };
+
+    match args.command {
+        DiagnosticsCommand::Stats { interval: None } => print_stats(&client.get_stats()),
+        DiagnosticsCommand::Stats {
+            interval: Some(interval),
+        } => loop {
+            print_stats(&client.get_stats());
+            thread::sleep(interval);
+        },
+        DiagnosticsCommand::Capture {
+            output,
+            count,
+            snaplen,
+            direction,
+        } => capture(&client, &output, count, snaplen, direction)?,
+    }
+    Ok(())
+}
@@ -9,10 +9,15 @@ use crate::{
     catnap::transport::get_libc_err,
     collections::{async_queue::AsyncQueue, async_value::SharedAsyncValue},
     expect_ok,
-    runtime::{fail::Fail, limits, memory::DemiBuffer, DemiRuntime},
+    runtime::{fail::Fail, limits, memory::DemiBuffer, network::socket::PopFlags, DemiRuntime},
 };
 use ::socket2::Socket;
-use ::std::{cmp::min, io, mem::MaybeUninit, net::SocketAddr};
+use ::std::{
+    cmp::min,
+    io::{self, IoSlice},
+    mem::MaybeUninit,
+    net::SocketAddr,
+};
 
 //======================================================================================================================
 // Structures
@@ -63,10 +68,11 @@ impl ActiveSocketData {
                 result.set(Some(Ok(())));
                 return;
             }
-            // Try to send the buffer.
+            // Try to send the buffer, gathering all of its segments if it is a chain.
+            let segments: Vec<IoSlice> = buf.segments().map(IoSlice::new).collect();
             let io_result: Result<usize, io::Error> = match addr {
-                Some(addr) => self.socket.send_to(&buf, &addr.clone().into()),
-                None => self.socket.send(&buf),
+                Some(addr) => self.socket.send_to_vectored(&segments, &addr.clone().into()),
+                None => self.socket.send_vectored(&segments),
             };
             match io_result {
                 // Operation completed.
@@ -153,8 +159,36 @@ impl ActiveSocketData {
         }
     }
 
-    /// Pops data from the socket. Blocks until some data is found but does not wait until the buf has reached [size].
-    pub async fn pop(&mut self, size: usize) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
+    /// Returns the number of bytes that were read from the socket but not popped yet.
+    pub fn get_buffered_bytes(&self) -> usize {
+        self.recv_queue
+            .get_values()
+            .filter_map(|item| item.as_ref().ok())
+            .map(|(_, buf)| buf.len())
+            .sum()
+    }
+
+    /// Pops data from the socket. Blocks until some data is found but does not wait until the buf has reached [size],
+    /// unless [flags] ask to wait for all of it. A peek leaves what it finds in the queue, errors included, so that the
+    /// next pop finds it again.
+    pub async fn pop(&mut self, size: usize, flags: PopFlags) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
+        if flags.wait_all && !flags.peek {
+            return self.pop_all(size).await;
+        }
+        if flags.peek {
+            let item: Result<(Option<SocketAddr>, DemiBuffer), Fail> = self.recv_queue.pop(None).await?;
+            self.recv_queue.push_front(item.clone());
+            let (addr, mut incoming): (Option<SocketAddr>, DemiBuffer) = item?;
+            if incoming.len() > size {
+                incoming.trim(incoming.len() - size)?;
+            }
+            return Ok((addr, incoming));
+        }
+        self.pop_some(size).await
+    }
+
+    /// Pops whatever data the first received buffer holds, up to [size] bytes, and leaves the rest for the next pop.
+    async fn pop_some(&mut self, size: usize) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
         let (addr, mut incoming): (Option<SocketAddr>, DemiBuffer) = self.recv_queue.pop(None).await??;
         // Figure out how much data we got.
         let bytes_read: usize = min(incoming.len(), size);
@@ -168,6 +202,42 @@ impl ActiveSocketData {
         Ok((addr, incoming))
     }
 
+    /// Pops exactly [size] bytes, coalescing as many received buffers as it takes into a single one. Returns fewer bytes
+    /// only if the connection is closed or fails first, leaving the end of the connection or the failure in the queue
+    /// for the next pop.
+    async fn pop_all(&mut self, size: usize) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
+        if size > u16::MAX as usize {
+            let cause: String = format!("cannot wait for more bytes than a buffer can hold (size={:?})", size);
+            error!("pop_all(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        let (addr, first): (Option<SocketAddr>, DemiBuffer) = self.pop_some(size).await?;
+        if first.len() == size || first.is_empty() {
+            return Ok((addr, first));
+        }
+
+        let mut buf: DemiBuffer = DemiBuffer::new(size as u16);
+        buf[..first.len()].copy_from_slice(&first);
+        let mut filled: usize = first.len();
+        while filled < size {
+            let mut next: DemiBuffer = match self.recv_queue.pop(None).await? {
+                Ok((_, next)) if !next.is_empty() => next,
+                item => {
+                    self.recv_queue.push_front(item);
+                    break;
+                },
+            };
+            if next.len() > size - filled {
+                let remainder: DemiBuffer = next.split_back(size - filled)?;
+                self.recv_queue.push_front(Ok((addr.clone(), remainder)));
+            }
+            buf[filled..filled + next.len()].copy_from_slice(&next);
+            filled += next.len();
+        }
+        buf.trim(size - filled)?;
+        Ok((addr, buf))
+    }
+
     pub fn get_socket(&self) -> &Socket {
         &self.socket
     }
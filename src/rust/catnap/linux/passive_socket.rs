@@ -58,6 +58,11 @@ impl PassiveSocketData {
         }
     }
 
+    /// Returns whether there are incoming connections that have not been accepted yet.
+    pub fn has_pending_connections(&self) -> bool {
+        !self.accept_queue.is_empty()
+    }
+
     /// Block until a new connection arrives.
     pub async fn accept(&mut self) -> Result<(Socket, SocketAddr), Fail> {
         self.accept_queue.pop(None).await?
@@ -8,7 +8,7 @@
 use crate::{
     catnap::transport::{active_socket::ActiveSocketData, passive_socket::PassiveSocketData},
     expect_some,
-    runtime::{fail::Fail, memory::DemiBuffer, SharedObject},
+    runtime::{fail::Fail, memory::DemiBuffer, network::socket::PopFlags, SharedObject},
 };
 use ::socket2::Socket;
 use ::std::{
@@ -114,15 +114,32 @@ impl SharedSocketData {
         }
     }
 
+    /// Check for incoming connections on a passive listening socket.
+    pub fn has_pending_connections(&self) -> bool {
+        match self.deref() {
+            SocketData::Inactive(_) => unreachable!("Cannot check for connections on an inactive socket"),
+            SocketData::Active(_) => unreachable!("Cannot check for connections on an active socket"),
+            SocketData::Passive(data) => data.has_pending_connections(),
+        }
+    }
+
     /// Pop some data on an active established connection.
-    pub async fn pop(&mut self, size: usize) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
+    pub async fn pop(&mut self, size: usize, flags: PopFlags) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
         match self.deref_mut() {
             SocketData::Inactive(_) => unreachable!("Cannot read on an inactive socket"),
-            SocketData::Active(data) => data.pop(size).await,
+            SocketData::Active(data) => data.pop(size, flags).await,
             SocketData::Passive(_) => unreachable!("Cannot read on a passive socket"),
         }
     }
 
+    /// Returns the number of bytes that were read from an active socket but not popped yet.
+    pub fn get_buffered_bytes(&self) -> usize {
+        match self.deref() {
+            SocketData::Active(data) => data.get_buffered_bytes(),
+            SocketData::Inactive(_) | SocketData::Passive(_) => 0,
+        }
+    }
+
     /// Handle incoming data event.
     pub fn poll_in(&mut self) {
         match self.deref_mut() {
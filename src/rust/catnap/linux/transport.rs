@@ -21,7 +21,10 @@ use crate::{
         fail::Fail,
         memory::{DemiBuffer, MemoryRuntime},
         network::{
-            socket::option::{SocketOption, TcpSocketOptions},
+            socket::{
+                option::{PriorityClass, QuickAck, SocketOption, TcpInfo, TcpSocketOptions, TcpState},
+                PopFlags,
+            },
             transport::NetworkTransport,
         },
         poll_yield, DemiRuntime, SharedDemiRuntime, SharedObject,
@@ -32,10 +35,11 @@ use ::futures::FutureExt;
 use ::slab::Slab;
 use ::socket2::{Domain, Protocol, Socket, Type};
 use ::std::{
-    io,
+    io, mem,
     net::{Shutdown, SocketAddr, SocketAddrV4},
     ops::{Deref, DerefMut},
     os::fd::{AsRawFd, RawFd},
+    time::Duration,
 };
 
 //======================================================================================================================
@@ -224,6 +228,118 @@ impl SharedCatnapTransport {
 // Standalone functions
 //======================================================================================================================
 
+/// Sets an integer option at the IPPROTO_TCP level that socket2 does not cover.
+fn set_tcp_int_option(socket: &Socket, name: libc::c_int, value: libc::c_int) -> Result<(), Fail> {
+    set_int_option(socket, libc::IPPROTO_TCP, name, value)
+}
+
+/// Sets an integer option at the `level` level that socket2 does not cover.
+fn set_int_option(socket: &Socket, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> Result<(), Fail> {
+    let ret: libc::c_int = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        let errno: i32 = get_libc_err(io::Error::last_os_error());
+        let cause: String = format!("setsockopt({:?}) failed: {:?}", name, errno);
+        error!("set_int_option(): {}", cause);
+        return Err(Fail::new(errno, &cause));
+    }
+    Ok(())
+}
+
+/// Gets an integer option at the IPPROTO_TCP level that socket2 does not cover.
+fn get_tcp_int_option(socket: &Socket, name: libc::c_int) -> Result<libc::c_int, Fail> {
+    get_int_option(socket, libc::IPPROTO_TCP, name)
+}
+
+/// Gets an integer option at the `level` level that socket2 does not cover.
+fn get_int_option(socket: &Socket, level: libc::c_int, name: libc::c_int) -> Result<libc::c_int, Fail> {
+    let mut value: libc::c_int = 0;
+    let mut len: libc::socklen_t = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret: libc::c_int = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        let errno: i32 = get_libc_err(io::Error::last_os_error());
+        let cause: String = format!("getsockopt({:?}) failed: {:?}", name, errno);
+        error!("get_int_option(): {}", cause);
+        return Err(Fail::new(errno, &cause));
+    }
+    Ok(value)
+}
+
+/// Reads the connection statistics of the kernel (TCP_INFO). The kernel counts the congestion window, the slow start
+/// threshold and the data in flight in segments, so these are converted to bytes with the send MSS.
+fn get_tcp_info(socket: &Socket) -> Result<TcpInfo, Fail> {
+    // Slow start threshold of the kernel before the first congestion event.
+    const TCP_INFINITE_SSTHRESH: u32 = 0x7fffffff;
+
+    let mut info: libc::tcp_info = unsafe { mem::zeroed() };
+    let mut len: libc::socklen_t = mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let ret: libc::c_int = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut libc::tcp_info as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        let errno: i32 = get_libc_err(io::Error::last_os_error());
+        let cause: String = format!("getsockopt(TCP_INFO) failed: {:?}", errno);
+        error!("get_tcp_info(): {}", cause);
+        return Err(Fail::new(errno, &cause));
+    }
+
+    // States as numbered in include/net/tcp_states.h of the kernel.
+    let state: TcpState = match info.tcpi_state {
+        1 => TcpState::Established,
+        4 => TcpState::FinWait1,
+        5 => TcpState::FinWait2,
+        6 => TcpState::TimeWait,
+        7 => TcpState::Closed,
+        8 => TcpState::CloseWait,
+        9 => TcpState::LastAck,
+        11 => TcpState::Closing,
+        _ => {
+            let cause: String = format!("socket is not connected");
+            error!("get_tcp_info(): {}", cause);
+            return Err(Fail::new(libc::ENOTCONN, &cause));
+        },
+    };
+    let mss: u32 = info.tcpi_snd_mss;
+    Ok(TcpInfo {
+        state,
+        smoothed_rtt: match info.tcpi_rtt {
+            0 => None,
+            rtt => Some(Duration::from_micros(rtt as u64)),
+        },
+        rto: Duration::from_micros(info.tcpi_rto as u64),
+        congestion_window: info.tcpi_snd_cwnd.saturating_mul(mss),
+        slow_start_threshold: match info.tcpi_snd_ssthresh {
+            ssthresh if ssthresh >= TCP_INFINITE_SSTHRESH => u32::MAX,
+            ssthresh => ssthresh.saturating_mul(mss),
+        },
+        retransmitted_segments: info.tcpi_total_retrans as u64,
+        bytes_in_flight: info.tcpi_unacked.saturating_mul(mss),
+        receive_window: info.tcpi_rcv_space,
+        mss: mss as usize,
+    })
+}
+
 /// Internal function to extract the raw OS error code.
 fn get_libc_err(e: io::Error) -> i32 {
     expect_some!(e.raw_os_error(), "should have an os error code")
@@ -354,6 +470,11 @@ impl NetworkTransport for SharedCatnapTransport {
                     Ok(())
                 }
             },
+            SocketOption::KeepAliveIdle(idle) => set_tcp_int_option(socket, libc::TCP_KEEPIDLE, idle.as_secs() as i32),
+            SocketOption::KeepAliveInterval(interval) => {
+                set_tcp_int_option(socket, libc::TCP_KEEPINTVL, interval.as_secs() as i32)
+            },
+            SocketOption::KeepAliveCount(count) => set_tcp_int_option(socket, libc::TCP_KEEPCNT, count as i32),
             SocketOption::NoDelay(nagle_off) => {
                 if let Err(e) = socket.set_nodelay(nagle_off) {
                     let errno: i32 = get_libc_err(e);
@@ -364,6 +485,78 @@ impl NetworkTransport for SharedCatnapTransport {
                     Ok(())
                 }
             },
+            SocketOption::Cork(cork) => set_tcp_int_option(socket, libc::TCP_CORK, cork as i32),
+            SocketOption::Pacing(_) => {
+                let cause: String = format!("pacing is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            // The kernel marks outgoing packets with the DSCP code point, which sits above the two ECN bits.
+            SocketOption::Priority(priority) => {
+                if let Err(e) = socket.set_tos((priority.dscp() as u32) << 2) {
+                    let errno: i32 = get_libc_err(e);
+                    let cause: String = format!("IP_TOS failed: {:?}", errno);
+                    error!("set_socket_option(): {}", cause);
+                    Err(Fail::new(errno, &cause))
+                } else {
+                    Ok(())
+                }
+            },
+            // The kernel decides on its own how many segments to acknowledge right away after the connection was idle.
+            SocketOption::QuickAck(QuickAck::AfterIdle(_)) => {
+                let cause: String = format!("quick ACKs after idle are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::QuickAck(quick_ack) => {
+                set_tcp_int_option(socket, libc::TCP_QUICKACK, (quick_ack == QuickAck::Always) as i32)
+            },
+            // The kernel does not let sockets pick their own delayed ACK policy.
+            SocketOption::DelayedAck(_) => {
+                let cause: String = format!("delayed ACK policies are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ReceiveLowWatermark(bytes) => set_int_option(
+                socket,
+                libc::SOL_SOCKET,
+                libc::SO_RCVLOWAT,
+                bytes.min(i32::MAX as usize) as i32,
+            ),
+            SocketOption::TcpInfo(_) => {
+                let cause: String = format!("connection statistics can only be read");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOPROTOOPT, &cause))
+            },
+            SocketOption::CongestionControl(_) => {
+                let cause: String = format!("selecting the congestion control algorithm is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::SynRetries(retries) => set_tcp_int_option(socket, libc::TCP_SYNCNT, retries as i32),
+            SocketOption::SynBackoff(_) => {
+                let cause: String = format!("backing off SYN retransmissions is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ConnectTimeout(_) => {
+                let cause: String = format!("connect timeouts are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Transparent(transparent) => {
+                set_int_option(socket, libc::SOL_IP, libc::IP_TRANSPARENT, transparent as i32)
+            },
+            SocketOption::ListenPorts(_) => {
+                let cause: String = format!("listening on port ranges is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::KeepCwndAfterIdleWhenPaced(_) => {
+                let cause: String = format!("keeping the congestion window after idle is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
         }
     }
 
@@ -395,6 +588,18 @@ impl NetworkTransport for SharedCatnapTransport {
                     Err(Fail::new(errno, &cause))
                 },
             },
+            SocketOption::KeepAliveIdle(_) => {
+                Ok(SocketOption::KeepAliveIdle(Duration::from_secs(
+                    get_tcp_int_option(socket, libc::TCP_KEEPIDLE)? as u64,
+                )))
+            },
+            SocketOption::KeepAliveInterval(_) => Ok(SocketOption::KeepAliveInterval(Duration::from_secs(
+                get_tcp_int_option(socket, libc::TCP_KEEPINTVL)? as u64,
+            ))),
+            SocketOption::KeepAliveCount(_) => Ok(SocketOption::KeepAliveCount(get_tcp_int_option(
+                socket,
+                libc::TCP_KEEPCNT,
+            )? as u32)),
             SocketOption::NoDelay(_) => match socket.nodelay() {
                 Ok(nagle_off) => Ok(SocketOption::NoDelay(nagle_off)),
                 Err(e) => {
@@ -404,6 +609,67 @@ impl NetworkTransport for SharedCatnapTransport {
                     Err(Fail::new(errno, &cause))
                 },
             },
+            SocketOption::Cork(_) => Ok(SocketOption::Cork(get_tcp_int_option(socket, libc::TCP_CORK)? != 0)),
+            SocketOption::Pacing(_) => {
+                let cause: String = format!("pacing is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Priority(_) => match socket.tos() {
+                Ok(tos) => Ok(SocketOption::Priority(PriorityClass::from_dscp((tos >> 2) as u8))),
+                Err(e) => {
+                    let errno: i32 = get_libc_err(e);
+                    let cause: String = format!("IP_TOS failed: {:?}", errno);
+                    error!("get_socket_option(): {}", cause);
+                    Err(Fail::new(errno, &cause))
+                },
+            },
+            SocketOption::QuickAck(_) => match get_tcp_int_option(socket, libc::TCP_QUICKACK)? {
+                0 => Ok(SocketOption::QuickAck(QuickAck::Off)),
+                _ => Ok(SocketOption::QuickAck(QuickAck::Always)),
+            },
+            SocketOption::DelayedAck(_) => {
+                let cause: String = format!("delayed ACK policies are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ReceiveLowWatermark(_) => {
+                Ok(SocketOption::ReceiveLowWatermark(
+                    get_int_option(socket, libc::SOL_SOCKET, libc::SO_RCVLOWAT)? as usize,
+                ))
+            },
+            SocketOption::TcpInfo(_) => Ok(SocketOption::TcpInfo(get_tcp_info(socket)?)),
+            SocketOption::CongestionControl(_) => {
+                let cause: String = format!("selecting the congestion control algorithm is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::SynRetries(_) => Ok(SocketOption::SynRetries(
+                get_tcp_int_option(socket, libc::TCP_SYNCNT)? as u32
+            )),
+            SocketOption::SynBackoff(_) => {
+                let cause: String = format!("backing off SYN retransmissions is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ConnectTimeout(_) => {
+                let cause: String = format!("connect timeouts are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Transparent(_) => Ok(SocketOption::Transparent(
+                get_int_option(socket, libc::SOL_IP, libc::IP_TRANSPARENT)? != 0,
+            )),
+            SocketOption::ListenPorts(_) => {
+                let cause: String = format!("listening on port ranges is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::KeepCwndAfterIdleWhenPaced(_) => {
+                let cause: String = format!("keeping the congestion window after idle is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
         }
     }
 
@@ -428,6 +694,27 @@ impl NetworkTransport for SharedCatnapTransport {
         }
     }
 
+    // Gets the local name of a socket.
+    fn getsockname(&mut self, sd: &mut Self::SocketDescriptor) -> Result<SocketAddrV4, Fail> {
+        let socket: &mut Socket = self.socket_from_sd(sd);
+        match socket.local_addr() {
+            Ok(addr) => match addr.as_socket_ipv4() {
+                Some(ipv4_addr) => Ok(ipv4_addr),
+                None => {
+                    let cause: String = format!("invalid IPv4 address");
+                    error!("getsockname(): {}", cause);
+                    Err(Fail::new(libc::EINVAL, &cause))
+                },
+            },
+            Err(e) => {
+                let errno: i32 = get_libc_err(e);
+                let cause: String = format!("failed to get socket name (errno={:?})", errno);
+                error!("getsockname(): {}", cause);
+                Err(Fail::new(errno, &cause))
+            },
+        }
+    }
+
     /// Binds a socket to [local] on the underlying network transport.
     fn bind(&mut self, sd: &mut Self::SocketDescriptor, local: SocketAddr) -> Result<(), Fail> {
         timer!("catnap::linux::transport::bind");
@@ -480,6 +767,34 @@ impl NetworkTransport for SharedCatnapTransport {
         Ok(())
     }
 
+    /// Checks whether there are incoming connections waiting to be accepted. Connections are moved into the accept
+    /// queue when epoll reports the listening socket as readable.
+    fn has_pending_connections(&mut self, sd: &mut Self::SocketDescriptor) -> Result<bool, Fail> {
+        Ok(self.data_from_sd(sd).has_pending_connections())
+    }
+
+    /// Returns the pending error of a socket and clears it, which is what SO_ERROR does.
+    fn get_last_error(&mut self, sd: &mut Self::SocketDescriptor) -> Result<Option<Fail>, Fail> {
+        match self.socket_from_sd(sd).take_error() {
+            Ok(None) => Ok(None),
+            Ok(Some(e)) => {
+                let cause: String = format!("{:?}", e);
+                Ok(Some(Fail::new(get_libc_err(e), &cause)))
+            },
+            Err(e) => {
+                let cause: String = format!("failed to get SO_ERROR: {:?}", e);
+                error!("get_last_error(): {}", cause);
+                Err(Fail::new(get_libc_err(e), &cause))
+            },
+        }
+    }
+
+    /// Returns the number of bytes that were already read from the socket but not popped yet. Data that the kernel still
+    /// holds is left out, as asking for it would take a system call on every pop.
+    fn get_buffered_bytes(&mut self, sd: &mut Self::SocketDescriptor) -> Result<usize, Fail> {
+        Ok(self.data_from_sd(sd).get_buffered_bytes())
+    }
+
     /// Accept the next incoming connection. This function blocks until a new connection arrives from the underlying
     /// transport.
     async fn accept(&mut self, sd: &mut Self::SocketDescriptor) -> Result<(Self::SocketDescriptor, SocketAddr), Fail> {
@@ -536,6 +851,20 @@ impl NetworkTransport for SharedCatnapTransport {
         }
     }
 
+    /// Shut down one or both directions of the socket. The kernel sends the FIN for us.
+    fn shutdown(&mut self, sd: &mut Self::SocketDescriptor, how: Shutdown) -> Result<(), Fail> {
+        let socket: &mut Socket = self.socket_from_sd(sd);
+        match socket.shutdown(how) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let errno: i32 = get_libc_err(e);
+                let cause: String = format!("failed to shut down socket (errno={:?})", errno);
+                error!("shutdown(): {}", cause);
+                Err(Fail::new(errno, &cause))
+            },
+        }
+    }
+
     /// Close the socket and block until close completes.
     async fn close(&mut self, sd: &mut Self::SocketDescriptor) -> Result<(), Fail> {
         timer!("catnap::linux::transport::close");
@@ -551,7 +880,7 @@ impl NetworkTransport for SharedCatnapTransport {
                         libc::ENOTCONN => break,
                         errno if DemiRuntime::should_retry(errno) => {
                             // Wait for a new incoming event.
-                            data.pop(0).await?;
+                            data.pop(0, PopFlags::default()).await?;
                             continue;
                         },
                         errno => return Err(Fail::new(errno, "operation failed")),
@@ -586,6 +915,12 @@ impl NetworkTransport for SharedCatnapTransport {
         }
     }
 
+    /// The kernel does flow and congestion control for us and a push completes once the kernel took the data, so we
+    /// consider sockets to always be writable.
+    async fn wait_for_writable(&mut self, _sd: &mut Self::SocketDescriptor, _min_bytes: usize) -> Result<(), Fail> {
+        Ok(())
+    }
+
     /// Pop a [buf] of at most [size] from the underlying transport. This function blocks until the socket has data to
     /// be read. For connected (i.e., TCP) sockets, this function returns Ok(None). For datagram (i.e., UDP) sockets,
     /// this function returns the remote address that is the source of the incoming data.
@@ -593,9 +928,29 @@ impl NetworkTransport for SharedCatnapTransport {
         &mut self,
         sd: &mut Self::SocketDescriptor,
         size: usize,
+        flags: PopFlags,
     ) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
         timer!("catnap::linux::transport::pop");
-        self.data_from_sd(sd).pop(size).await
+        self.data_from_sd(sd).pop(size, flags).await
+    }
+
+    /// Close the socket right away. Lingering is turned off first, so that the kernel resets the connection instead of
+    /// shutting it down gracefully. Also unregisters the socket with epoll.
+    fn abort(&mut self, sd: &mut Self::SocketDescriptor) -> Result<(), Fail> {
+        let data: &mut SharedSocketData = self.data_from_sd(sd);
+        if let Err(e) = data.get_socket().set_linger(Some(Duration::ZERO)) {
+            let errno: i32 = get_libc_err(e);
+            let cause: String = format!("failed to turn off lingering (errno={:?})", errno);
+            error!("abort(): {}", cause);
+            return Err(Fail::new(errno, &cause));
+        }
+        match data.deref_mut() {
+            SocketData::Active(_) => self.unregister_epoll(sd, (libc::EPOLLIN | libc::EPOLLOUT) as u32)?,
+            SocketData::Passive(_) => self.unregister_epoll(sd, libc::EPOLLIN as u32)?,
+            _ => (),
+        };
+        self.socket_table.remove(*sd);
+        Ok(())
     }
 
     /// Close the socket on the underlying transport. Also unregisters the socket with epoll.
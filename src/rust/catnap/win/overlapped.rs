@@ -475,7 +475,9 @@ mod tests {
         })
         .fuse();
 
-        let server_task: QToken = runtime.insert_io_coroutine("ioc_server", Box::pin(server)).unwrap();
+        let server_task: QToken = runtime
+            .insert_io_coroutine("ioc_server", QDesc::from(0), Box::pin(server))
+            .unwrap();
         ensure!(runtime.run_any(&[server_task], Duration::ZERO).is_none());
         post_completion(&iocp, overlapped.as_mut().marshal(), COMPLETION_KEY)?;
 
@@ -585,7 +587,9 @@ mod tests {
         );
 
         let mut runtime: SharedDemiRuntime = SharedDemiRuntime::default();
-        let server_task: QToken = runtime.insert_io_coroutine("ioc_server", server).unwrap();
+        let server_task: QToken = runtime
+            .insert_io_coroutine("ioc_server", QDesc::from(0), server)
+            .unwrap();
 
         let mut wait_for_state = |state| -> Result<(), Fail> {
             while server_state_view.load(Ordering::Relaxed) < state {
@@ -691,7 +695,9 @@ mod tests {
         .fuse();
 
         let mut runtime: SharedDemiRuntime = SharedDemiRuntime::default();
-        let server_task: QToken = runtime.insert_io_coroutine("ioc_server", Box::pin(server)).unwrap();
+        let server_task: QToken = runtime
+            .insert_io_coroutine("ioc_server", QDesc::from(0), Box::pin(server))
+            .unwrap();
 
         ensure!(
             server_state_view.load(Ordering::Relaxed) < 1,
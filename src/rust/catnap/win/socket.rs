@@ -16,7 +16,7 @@ use crate::{
 use std::{
     fmt::Debug,
     mem::MaybeUninit,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6},
     pin::Pin,
     rc::Rc,
     time::Duration,
@@ -24,13 +24,14 @@ use std::{
 use windows::{
     core::PSTR,
     Win32::{
-        Foundation::{BOOL, ERROR_NOT_FOUND, FALSE, HANDLE, TRUE},
+        Foundation::{BOOL, ERROR_NOT_FOUND, FALSE, HANDLE, TRUE, WIN32_ERROR},
         Networking::WinSock::{
-            bind, closesocket, listen, shutdown, tcp_keepalive, WSAGetLastError, WSARecvFrom, WSASendTo,
-            FROM_PROTOCOL_INFO, INVALID_SOCKET, IPPROTO_TCP, LINGER, SD_BOTH, SIO_KEEPALIVE_VALS, SOCKADDR,
-            SOCKADDR_IN, SOCKADDR_IN6, SOCKADDR_INET, SOCKADDR_STORAGE, SOCKET, SOCKET_ERROR, SOL_SOCKET, SO_KEEPALIVE,
-            SO_LINGER, SO_PROTOCOL_INFOW, SO_UPDATE_ACCEPT_CONTEXT, SO_UPDATE_CONNECT_CONTEXT, TCP_NODELAY, WSABUF,
-            WSAEINVAL, WSAPROTOCOL_INFOW, WSA_FLAG_OVERLAPPED,
+            bind, closesocket, listen, shutdown, tcp_keepalive, WSAGetLastError, WSAPoll, WSARecvFrom, WSASendTo,
+            FROM_PROTOCOL_INFO, INVALID_SOCKET, IPPROTO_TCP, LINGER, POLLRDNORM, SD_BOTH, SD_RECEIVE, SD_SEND,
+            SIO_KEEPALIVE_VALS, SOCKADDR, SOCKADDR_IN, SOCKADDR_IN6, SOCKADDR_INET, SOCKADDR_STORAGE, SOCKET,
+            SOCKET_ERROR, SOL_SOCKET, SO_ERROR, SO_KEEPALIVE, SO_LINGER, SO_PROTOCOL_INFOW, SO_UPDATE_ACCEPT_CONTEXT,
+            SO_UPDATE_CONNECT_CONTEXT, TCP_NODELAY, WINSOCK_SHUTDOWN_HOW, WSABUF, WSAEINVAL, WSAPOLLFD,
+            WSAPOLL_EVENT_FLAGS, WSAPROTOCOL_INFOW, WSA_FLAG_OVERLAPPED,
         },
         System::IO::{CancelIoEx, OVERLAPPED},
     },
@@ -165,6 +166,34 @@ impl Socket {
         addr
     }
 
+    /// Get local address of socket
+    pub fn getsockname(&self) -> Result<SocketAddrV4, Fail> {
+        WinsockRuntime::getsockname(self.s)
+    }
+
+    /// Check, without blocking, whether a connection is waiting to be accepted on a listening socket.
+    pub fn has_pending_connections(&self) -> Result<bool, Fail> {
+        let mut fd: WSAPOLLFD = WSAPOLLFD {
+            fd: self.s,
+            events: POLLRDNORM,
+            revents: WSAPOLL_EVENT_FLAGS(0),
+        };
+        if unsafe { WSAPoll(&mut fd, 1, 0) } == SOCKET_ERROR {
+            Err(expect_last_wsa_error())
+        } else {
+            Ok((fd.revents.0 & POLLRDNORM.0) != 0)
+        }
+    }
+
+    /// Get and clear the pending error of the socket (SO_ERROR).
+    pub fn take_error(&self) -> Result<Option<Fail>, Fail> {
+        let error: i32 = unsafe { WinsockRuntime::do_getsockopt(self.s, SOL_SOCKET, SO_ERROR) }?;
+        match error {
+            0 => Ok(None),
+            error => Ok(Some(Fail::from(WIN32_ERROR(error as u32)))),
+        }
+    }
+
     /// Set TCP keepalive socket options.
     pub fn set_tcp_keepalive(&self, keepalive_params: &tcp_keepalive) -> Result<(), Fail> {
         unsafe { WinsockRuntime::do_setsockopt(self.s, SOL_SOCKET, SO_KEEPALIVE, Some(&keepalive_params.onoff)) }?;
@@ -240,7 +269,17 @@ impl Socket {
     /// `start_disconnect` will start an asynchronous disconnect operation. If the socket is not disconnected prior to
     /// this call, this call may block for socket teardown, depending on the linger settings.
     pub fn shutdown(&self) -> Result<(), Fail> {
-        if unsafe { shutdown(self.s, SD_BOTH) } == 0 {
+        self.shutdown_direction(Shutdown::Both)
+    }
+
+    /// Shutdown communication in one or both directions on the socket, leaving the other direction open.
+    pub fn shutdown_direction(&self, how: Shutdown) -> Result<(), Fail> {
+        let how: WINSOCK_SHUTDOWN_HOW = match how {
+            Shutdown::Read => SD_RECEIVE,
+            Shutdown::Write => SD_SEND,
+            Shutdown::Both => SD_BOTH,
+        };
+        if unsafe { shutdown(self.s, how) } == 0 {
             Ok(())
         } else {
             Err(expect_last_wsa_error().into())
@@ -528,21 +567,25 @@ impl Socket {
 
         let mut bytes_transferred: u32 = 0;
         let success: bool = unsafe {
-            let wsa_buffer: WSABUF = WSABUF {
-                len: buffer.len() as u32,
-                // Safety: loading the buffer pointer won't violate pinning invariants.
-                buf: PSTR::from_raw(buffer.as_mut_ptr()),
-            };
+            // Gather all segments of the buffer, so that a buffer chain goes out in a single call.
+            let wsa_buffers: Vec<WSABUF> = buffer
+                .segments()
+                .map(|segment: &[u8]| WSABUF {
+                    len: segment.len() as u32,
+                    // Safety: loading the buffer pointer won't violate pinning invariants.
+                    buf: PSTR::from_raw(segment.as_ptr() as *mut u8),
+                })
+                .collect();
 
             let addr: Option<socket2::SockAddr> = addr.map(socket2::SockAddr::from);
 
             // NB winsock service providers are required to capture the entire WSABUF array inline with the call, so
-            // wsa_buffer and the derivative slice can safely drop after the call.
+            // wsa_buffers can safely drop after the call.
             // Per Windows documentation, WSASendTo ignores the destination address for connection oriented sockets and
             // functions equivalently to WSASend.
             let result: i32 = WSASendTo(
                 self.s,
-                std::slice::from_ref(&wsa_buffer),
+                &wsa_buffers,
                 Some(&mut bytes_transferred),
                 0,
                 addr.as_ref()
@@ -22,7 +22,10 @@ use crate::{
         fail::Fail,
         memory::{DemiBuffer, MemoryRuntime},
         network::{
-            socket::option::{SocketOption, TcpSocketOptions},
+            socket::{
+                option::{SocketOption, TcpSocketOptions},
+                PopFlags,
+            },
             transport::NetworkTransport,
         },
         poll_yield, DemiRuntime, SharedDemiRuntime, SharedObject,
@@ -30,8 +33,9 @@ use crate::{
 };
 use ::futures::FutureExt;
 use std::{
-    net::{SocketAddr, SocketAddrV4},
+    net::{Shutdown, SocketAddr, SocketAddrV4},
     pin::Pin,
+    time::Duration,
 };
 use windows::Win32::{
     Networking::WinSock::{WSAGetLastError, IPPROTO, IPPROTO_TCP, IPPROTO_UDP},
@@ -134,6 +138,82 @@ impl NetworkTransport for SharedCatnapTransport {
             SocketOption::Linger(linger) => socket.set_linger(linger),
             SocketOption::KeepAlive(tcp_keepalive) => socket.set_tcp_keepalive(&tcp_keepalive),
             SocketOption::NoDelay(nagle_enabled) => socket.set_nagle(nagle_enabled),
+            SocketOption::Cork(_) => {
+                let cause: String = format!("corking is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Pacing(_) => {
+                let cause: String = format!("pacing is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Priority(_) => {
+                let cause: String = format!("priority is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            // Winsock takes the keepalive timers together with the on/off switch.
+            SocketOption::KeepAliveIdle(_) | SocketOption::KeepAliveInterval(_) | SocketOption::KeepAliveCount(_) => {
+                let cause: String = format!("keepalive timers are only supported through the keepalive option");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::QuickAck(_) => {
+                let cause: String = format!("quick ACKs are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::DelayedAck(_) => {
+                let cause: String = format!("delayed ACK policies are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ReceiveLowWatermark(_) => {
+                let cause: String = format!("receive low watermarks are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::TcpInfo(_) => {
+                let cause: String = format!("connection statistics can only be read");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOPROTOOPT, &cause))
+            },
+            SocketOption::CongestionControl(_) => {
+                let cause: String = format!("selecting the congestion control algorithm is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::SynRetries(_) => {
+                let cause: String = format!("configuring SYN retransmissions is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::SynBackoff(_) => {
+                let cause: String = format!("backing off SYN retransmissions is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ConnectTimeout(_) => {
+                let cause: String = format!("connect timeouts are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Transparent(_) => {
+                let cause: String = format!("transparent sockets are not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ListenPorts(_) => {
+                let cause: String = format!("listening on port ranges is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::KeepCwndAfterIdleWhenPaced(_) => {
+                let cause: String = format!("keeping the congestion window after idle is not supported");
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
         }
     }
 
@@ -149,6 +229,81 @@ impl NetworkTransport for SharedCatnapTransport {
             SocketOption::Linger(_) => Ok(SocketOption::Linger(socket.get_linger()?)),
             SocketOption::KeepAlive(_) => Ok(SocketOption::KeepAlive(socket.get_tcp_keepalive()?)),
             SocketOption::NoDelay(_) => Ok(SocketOption::NoDelay(socket.get_nagle()?)),
+            SocketOption::Cork(_) => {
+                let cause: String = format!("corking is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Pacing(_) => {
+                let cause: String = format!("pacing is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Priority(_) => {
+                let cause: String = format!("priority is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::KeepAliveIdle(_) | SocketOption::KeepAliveInterval(_) | SocketOption::KeepAliveCount(_) => {
+                let cause: String = format!("keepalive timers are only supported through the keepalive option");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::QuickAck(_) => {
+                let cause: String = format!("quick ACKs are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::DelayedAck(_) => {
+                let cause: String = format!("delayed ACK policies are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ReceiveLowWatermark(_) => {
+                let cause: String = format!("receive low watermarks are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::TcpInfo(_) => {
+                let cause: String = format!("connection statistics are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::CongestionControl(_) => {
+                let cause: String = format!("selecting the congestion control algorithm is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::SynRetries(_) => {
+                let cause: String = format!("configuring SYN retransmissions is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::SynBackoff(_) => {
+                let cause: String = format!("backing off SYN retransmissions is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ConnectTimeout(_) => {
+                let cause: String = format!("connect timeouts are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Transparent(_) => {
+                let cause: String = format!("transparent sockets are not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::ListenPorts(_) => {
+                let cause: String = format!("listening on port ranges is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::KeepCwndAfterIdleWhenPaced(_) => {
+                let cause: String = format!("keeping the congestion window after idle is not supported");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
         }
     }
 
@@ -165,6 +320,29 @@ impl NetworkTransport for SharedCatnapTransport {
         }
     }
 
+    /// Gets the local address of the specified socket.
+    fn getsockname(&mut self, socket: &mut Self::SocketDescriptor) -> Result<SocketAddrV4, Fail> {
+        match socket.getsockname() {
+            Ok(addr) => Ok(addr),
+            Err(_) => {
+                let cause: String = format!("failed to get local address (errno={:?})", unsafe { WSAGetLastError() });
+                error!("getsockname(): {:?}", cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+
+    /// Shut down one or both directions of the specified socket.
+    fn shutdown(&mut self, socket: &mut Self::SocketDescriptor, how: Shutdown) -> Result<(), Fail> {
+        socket.shutdown_direction(how)
+    }
+
+    /// Close the specified socket right away. Lingering is turned off, so that the connection is reset once the socket
+    /// is closed.
+    fn abort(&mut self, socket: &mut Self::SocketDescriptor) -> Result<(), Fail> {
+        socket.set_linger(Some(Duration::ZERO))
+    }
+
     /// Synchronously shut down the specified socket.
     fn hard_close(&mut self, socket: &mut Self::SocketDescriptor) -> Result<(), Fail> {
         socket.shutdown()
@@ -200,6 +378,22 @@ impl NetworkTransport for SharedCatnapTransport {
         socket.listen(backlog)
     }
 
+    /// Check whether a connection is waiting to be accepted on the specified socket.
+    fn has_pending_connections(&mut self, socket: &mut Self::SocketDescriptor) -> Result<bool, Fail> {
+        socket.has_pending_connections()
+    }
+
+    /// Get and clear the pending error of the specified socket.
+    fn get_last_error(&mut self, socket: &mut Self::SocketDescriptor) -> Result<Option<Fail>, Fail> {
+        socket.take_error()
+    }
+
+    /// Get the number of bytes that can be popped from the specified socket without waiting. Pops read straight from
+    /// the socket, so nothing is buffered here, and asking the kernel would take a system call on every pop.
+    fn get_buffered_bytes(&mut self, _socket: &mut Self::SocketDescriptor) -> Result<usize, Fail> {
+        Ok(0)
+    }
+
     /// Accept a connection on the specified socket. The coroutine will not finish until a connection is successfully
     /// accepted or `yielder` is cancelled.
     async fn accept(&mut self, socket: &mut Self::SocketDescriptor) -> Result<(Socket, SocketAddr), Fail> {
@@ -239,12 +433,30 @@ impl NetworkTransport for SharedCatnapTransport {
         .await
     }
 
+    /// The kernel does flow and congestion control for us and a push completes once the kernel took the data, so we
+    /// consider sockets to always be writable.
+    async fn wait_for_writable(&mut self, _sd: &mut Self::SocketDescriptor, _min_bytes: usize) -> Result<(), Fail> {
+        Ok(())
+    }
+
     /// Pop data from the socket into `buf`. This method will return the remote address iff the socket is not connected.
     async fn pop(
         &mut self,
         socket: &mut Self::SocketDescriptor,
         size: usize,
+        flags: PopFlags,
     ) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
+        // Each pop receives straight into a buffer of its own, so there is no queue of received data to peek into.
+        if flags.peek {
+            let cause: String = format!("peeking is not supported");
+            error!("pop(): {}", cause);
+            return Err(Fail::new(libc::ENOTSUP, &cause));
+        }
+        if flags.wait_all {
+            let cause: String = format!("waiting for all of the data is not supported");
+            error!("pop(): {}", cause);
+            return Err(Fail::new(libc::ENOTSUP, &cause));
+        }
         let mut buf: DemiBuffer = DemiBuffer::new(size as u16);
         unsafe {
             self.0.iocp.do_io(
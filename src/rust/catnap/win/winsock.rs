@@ -23,8 +23,8 @@ use std::{
 use windows::{
     core::{GUID, PSTR},
     Win32::Networking::WinSock::{
-        closesocket, getpeername, getsockopt, setsockopt, WSACleanup, WSAIoctl, WSASocketW, WSAStartup, INVALID_SOCKET,
-        IN_ADDR_0_0, LPFN_ACCEPTEX, LPFN_CONNECTEX, LPFN_DISCONNECTEX, LPFN_GETACCEPTEXSOCKADDRS,
+        closesocket, getpeername, getsockname, getsockopt, setsockopt, WSACleanup, WSAIoctl, WSASocketW, WSAStartup,
+        INVALID_SOCKET, IN_ADDR_0_0, LPFN_ACCEPTEX, LPFN_CONNECTEX, LPFN_DISCONNECTEX, LPFN_GETACCEPTEXSOCKADDRS,
         RIO_EXTENSION_FUNCTION_TABLE, SIO_GET_EXTENSION_FUNCTION_POINTER, SIO_GET_MULTIPLE_EXTENSION_FUNCTION_POINTER,
         SOCKADDR, SOCKADDR_IN, SOCKET, SOL_SOCKET, SO_PROTOCOL_INFOW, WSADATA, WSAID_ACCEPTEX, WSAID_CONNECTEX,
         WSAID_DISCONNECTEX, WSAID_GETACCEPTEXSOCKADDRS, WSAPROTOCOL_INFOW, WSA_FLAG_OVERLAPPED,
@@ -281,6 +281,24 @@ impl WinsockRuntime {
         }
     }
 
+    /// Gets the local ip and port of a socket from SOCKADDR_IN and converts them to SocketAddrV4
+    pub fn getsockname(s: SOCKET) -> Result<SocketAddrV4, Fail> {
+        let mut sockaddr_in: SOCKADDR_IN = SOCKADDR_IN::default();
+        let sockaddr_ptr: &mut SOCKADDR = &mut unsafe { mem::transmute::<SOCKADDR_IN, SOCKADDR>(sockaddr_in) };
+        let mut namelen: i32 = std::mem::size_of::<SOCKADDR>() as i32;
+
+        if unsafe { getsockname(s, sockaddr_ptr, &mut namelen) } == 0 {
+            sockaddr_in = unsafe { mem::transmute::<SOCKADDR, SOCKADDR_IN>(*sockaddr_ptr) };
+            let port: u16 = u16::from_be(sockaddr_in.sin_port);
+            let addr: IN_ADDR_0_0 = unsafe { sockaddr_in.sin_addr.S_un.S_un_b };
+            let addrv4: SocketAddrV4 =
+                SocketAddrV4::new(Ipv4Addr::new(addr.s_b1, addr.s_b2, addr.s_b3, addr.s_b4), port);
+            Ok(addrv4)
+        } else {
+            Err(expect_last_wsa_error())
+        }
+    }
+
     /// Get or initialize a new `SocketExtensions` instance for a  socket. Extensions are stored by socket provider,
     /// which may be shared by multiple sockets.
     fn get_or_init_extensions(&mut self, s: SOCKET) -> Result<Rc<SocketExtensions>, Fail> {
@@ -0,0 +1,405 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::{concurrent_ring::ConcurrentRingBuffer, ring::Ring},
+    expect_ok,
+    runtime::{
+        fail::Fail,
+        libdpdk::{rte_eal_init, rte_errno, rte_memzone, rte_memzone_lookup, rte_memzone_reserve, rte_socket_id},
+        memory::DemiBuffer,
+    },
+};
+use ::std::{
+    ffi::CString,
+    mem,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Name of the DPDK memory zone that the primary process shares its diagnostics through.
+const DIAGNOSTICS_MEMZONE_NAME: &str = "demikernel_diagnostics";
+
+/// Identifies a diagnostics area, along with the version of its layout. Bump the version whenever the layout changes,
+/// so that tools built against another layout refuse to attach instead of misreading it.
+const DIAGNOSTICS_MAGIC: u64 = 0x4445_4d49_4449_0001;
+
+/// Size of the ring that holds captured packets, including the offsets that the ring keeps at its front.
+const CAPTURE_RING_SIZE: usize = 4 << 20;
+
+/// Maximum number of bytes that are captured of each packet.
+pub const MAX_SNAPLEN: usize = 2048;
+
+/// Size of the header of a capture record: direction (1 byte), original length (2 bytes) and timestamp in nanoseconds
+/// since the Unix epoch (8 bytes).
+const CAPTURE_HEADER_SIZE: usize = 11;
+
+/// Size of the largest capture record.
+pub const MAX_CAPTURE_RECORD_SIZE: usize = CAPTURE_HEADER_SIZE + MAX_SNAPLEN;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Diagnostics area that the primary process shares with secondary processes. It lives at the front of a DPDK memory
+/// zone and is followed by the capture ring. The primary process is the only writer of the counters.
+#[repr(C)]
+struct DiagnosticsArea {
+    magic: AtomicU64,
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_drops: AtomicU64,
+    capture_drops: AtomicU64,
+    /// Number of bytes to capture of each packet. Zero means that capturing is off.
+    snaplen: AtomicU32,
+}
+
+/// Snapshot of the counters of the primary process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PortStats {
+    /// Number of packets that were received.
+    pub rx_packets: u64,
+    /// Number of bytes that were received.
+    pub rx_bytes: u64,
+    /// Number of packets that were handed to the device for transmission.
+    pub tx_packets: u64,
+    /// Number of bytes that were handed to the device for transmission.
+    pub tx_bytes: u64,
+    /// Number of packets that could not be transmitted because there were no buffers or room in the transmit backlog.
+    pub tx_drops: u64,
+    /// Number of packets that were not captured because the capture ring was full.
+    pub capture_drops: u64,
+}
+
+/// Direction in which a captured packet went.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureDirection {
+    Rx,
+    Tx,
+}
+
+/// A captured packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureRecord<'a> {
+    pub direction: CaptureDirection,
+    /// Length of the packet on the wire, which may exceed the number of bytes that were captured.
+    pub orig_len: usize,
+    /// Time at which the primary process saw the packet, in nanoseconds since the Unix epoch.
+    pub timestamp_ns: u64,
+    /// Captured bytes of the packet.
+    pub data: &'a [u8],
+}
+
+/// Exports the counters of the primary process and the packets that it sends and receives to secondary processes. The
+/// data path only pays for a few counter updates, unless a secondary process turned capturing on.
+pub struct DiagnosticsExporter {
+    area: *const DiagnosticsArea,
+    capture_ring: ConcurrentRingBuffer,
+}
+
+/// Diagnostics tools run as a secondary DPDK process and use this to attach to the diagnostics area of the primary
+/// process. They never touch the device or the memory pools of the primary process.
+pub struct DiagnosticsClient {
+    area: *const DiagnosticsArea,
+    capture_ring: ConcurrentRingBuffer,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl DiagnosticsExporter {
+    /// Reserves the diagnostics area. This must be called by the primary process, after DPDK was initialized.
+    pub fn new() -> Result<Self, Fail> {
+        let name: CString = expect_ok!(CString::new(DIAGNOSTICS_MEMZONE_NAME), "memzone name has no null bytes");
+        let memzone: *const rte_memzone = unsafe {
+            rte_memzone_reserve(
+                name.as_ptr(),
+                mem::size_of::<DiagnosticsArea>() + CAPTURE_RING_SIZE,
+                rte_socket_id() as i32,
+                0,
+            )
+        };
+        if memzone.is_null() {
+            let rte_errno: libc::c_int = unsafe { rte_errno() };
+            let cause: String = format!("failed to reserve diagnostics memzone (rte_errno={:?})", rte_errno);
+            error!("new(): {}", cause);
+            return Err(Fail::new(libc::EIO, &cause));
+        }
+
+        // Safety: the memzone is large enough for the area and the capture ring, and memzones are cache line aligned.
+        let area: *mut DiagnosticsArea = unsafe { (*memzone).__bindgen_anon_1.addr as *mut DiagnosticsArea };
+        unsafe {
+            area.write(DiagnosticsArea {
+                magic: AtomicU64::new(0),
+                rx_packets: AtomicU64::new(0),
+                rx_bytes: AtomicU64::new(0),
+                tx_packets: AtomicU64::new(0),
+                tx_bytes: AtomicU64::new(0),
+                tx_drops: AtomicU64::new(0),
+                capture_drops: AtomicU64::new(0),
+                snaplen: AtomicU32::new(0),
+            })
+        };
+        let capture_ring: ConcurrentRingBuffer =
+            ConcurrentRingBuffer::from_raw_parts(true, unsafe { area.add(1) as *mut u8 }, CAPTURE_RING_SIZE)?;
+        // Secondary processes only attach once the area is complete.
+        unsafe { (*area).magic.store(DIAGNOSTICS_MAGIC, Ordering::Release) };
+        info!(
+            "new(): exporting diagnostics through memzone {:?}",
+            DIAGNOSTICS_MEMZONE_NAME
+        );
+
+        Ok(Self { area, capture_ring })
+    }
+
+    /// Accounts for a received packet.
+    pub fn on_receive(&self, pkt: &[u8]) {
+        let area: &DiagnosticsArea = self.area();
+        bump(&area.rx_packets, 1);
+        bump(&area.rx_bytes, pkt.len() as u64);
+        self.capture(CaptureDirection::Rx, pkt);
+    }
+
+    /// Accounts for a packet that was handed to the device for transmission.
+    pub fn on_transmit(&self, pkt: &DemiBuffer) {
+        let area: &DiagnosticsArea = self.area();
+        bump(&area.tx_packets, 1);
+        bump(&area.tx_bytes, pkt.len() as u64);
+        // Only the first segment of a chain is captured, which holds the headers of the packet.
+        self.capture(CaptureDirection::Tx, pkt.first_segment());
+    }
+
+    /// Accounts for a packet that could not be transmitted.
+    pub fn on_transmit_drop(&self) {
+        bump(&self.area().tx_drops, 1);
+    }
+
+    /// Copies `pkt` into the capture ring, if a secondary process turned capturing on. Packets that do not fit are
+    /// dropped from the capture, so that a slow reader never holds up the data path.
+    fn capture(&self, direction: CaptureDirection, pkt: &[u8]) {
+        let snaplen: usize = self.area().snaplen.load(Ordering::Relaxed) as usize;
+        if snaplen == 0 {
+            return;
+        }
+        let mut record: [u8; MAX_CAPTURE_RECORD_SIZE] = [0; MAX_CAPTURE_RECORD_SIZE];
+        let timestamp_ns: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_nanos() as u64)
+            .unwrap_or(0);
+        let len: usize = encode_capture_record(&mut record, direction, pkt, snaplen, timestamp_ns);
+        if self.capture_ring.try_push(&record[..len]).is_err() {
+            bump(&self.area().capture_drops, 1);
+        }
+    }
+
+    fn area(&self) -> &DiagnosticsArea {
+        // Safety: the memzone is never freed while the process runs.
+        unsafe { &*self.area }
+    }
+}
+
+impl DiagnosticsClient {
+    /// Initializes DPDK as a secondary process with the EAL arguments of the primary process, which must run with the
+    /// same file prefix, and attaches to its diagnostics area.
+    pub fn attach(eal_init_args: &[CString]) -> Result<Self, Fail> {
+        // Whatever process type the primary asked for, we must be a secondary.
+        let proc_type: CString = expect_ok!(CString::new("--proc-type=secondary"), "argument has no null bytes");
+        let mut args: Vec<CString> = eal_init_args
+            .iter()
+            .filter(|arg| !arg.to_bytes().starts_with(b"--proc-type"))
+            .cloned()
+            .collect();
+        args.push(proc_type);
+        let arg_refs = args.iter().map(|s| s.as_ptr() as *mut u8).collect::<Vec<_>>();
+        let ret: libc::c_int = unsafe { rte_eal_init(arg_refs.len() as i32, arg_refs.as_ptr() as *mut _) };
+        if ret < 0 {
+            let rte_errno: libc::c_int = unsafe { rte_errno() };
+            let cause: String = format!("EAL initialization failed (rte_errno={:?})", rte_errno);
+            error!("attach(): {}", cause);
+            return Err(Fail::new(libc::EIO, &cause));
+        }
+
+        let name: CString = expect_ok!(CString::new(DIAGNOSTICS_MEMZONE_NAME), "memzone name has no null bytes");
+        let memzone: *const rte_memzone = unsafe { rte_memzone_lookup(name.as_ptr()) };
+        if memzone.is_null() {
+            let cause: String = format!("primary process does not export diagnostics");
+            error!("attach(): {}", cause);
+            return Err(Fail::new(libc::ENOENT, &cause));
+        }
+        let area: *mut DiagnosticsArea = unsafe { (*memzone).__bindgen_anon_1.addr as *mut DiagnosticsArea };
+        let magic: u64 = unsafe { (*area).magic.load(Ordering::Acquire) };
+        if magic != DIAGNOSTICS_MAGIC {
+            let cause: String = format!("unknown diagnostics layout (magic={:#x})", magic);
+            error!("attach(): {}", cause);
+            return Err(Fail::new(libc::EPROTO, &cause));
+        }
+        let capture_ring: ConcurrentRingBuffer =
+            ConcurrentRingBuffer::from_raw_parts(false, unsafe { area.add(1) as *mut u8 }, CAPTURE_RING_SIZE)?;
+
+        Ok(Self { area, capture_ring })
+    }
+
+    /// Takes a snapshot of the counters of the primary process.
+    pub fn get_stats(&self) -> PortStats {
+        let area: &DiagnosticsArea = self.area();
+        PortStats {
+            rx_packets: area.rx_packets.load(Ordering::Relaxed),
+            rx_bytes: area.rx_bytes.load(Ordering::Relaxed),
+            tx_packets: area.tx_packets.load(Ordering::Relaxed),
+            tx_bytes: area.tx_bytes.load(Ordering::Relaxed),
+            tx_drops: area.tx_drops.load(Ordering::Relaxed),
+            capture_drops: area.capture_drops.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Asks the primary process to capture the first `snaplen` bytes of every packet that it sends and receives. Packets
+    /// that were left in the capture ring by an earlier capture are discarded.
+    pub fn start_capture(&self, snaplen: usize) -> Result<(), Fail> {
+        if snaplen == 0 || snaplen > MAX_SNAPLEN {
+            let cause: String = format!("invalid snaplen (snaplen={:?}, max={:?})", snaplen, MAX_SNAPLEN);
+            error!("start_capture(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        let mut record: [u8; MAX_CAPTURE_RECORD_SIZE] = [0; MAX_CAPTURE_RECORD_SIZE];
+        while self.capture_ring.try_pop(&mut record).is_ok() {}
+        self.area().snaplen.store(snaplen as u32, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Asks the primary process to stop capturing. Packets that are still in the capture ring can be read afterwards.
+    pub fn stop_capture(&self) {
+        self.area().snaplen.store(0, Ordering::Relaxed);
+    }
+
+    /// Takes the next captured packet out of the capture ring, if there is one. `buf` must have room for
+    /// [MAX_CAPTURE_RECORD_SIZE] bytes.
+    pub fn try_pop_capture<'a>(&self, buf: &'a mut [u8]) -> Option<CaptureRecord<'a>> {
+        match self.capture_ring.try_pop(buf) {
+            Ok(len) => decode_capture_record(&buf[..len]),
+            Err(_) => None,
+        }
+    }
+
+    fn area(&self) -> &DiagnosticsArea {
+        // Safety: the memzone is never freed while the primary process runs.
+        unsafe { &*self.area }
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Adds `by` to `counter`. The primary process is the only writer of its counters, so this needs no atomic
+/// read-modify-write on the data path.
+fn bump(counter: &AtomicU64, by: u64) {
+    counter.store(counter.load(Ordering::Relaxed).wrapping_add(by), Ordering::Relaxed);
+}
+
+/// Writes the capture record of the first `snaplen` bytes of `pkt` into `record`. Returns the length of the record.
+fn encode_capture_record(
+    record: &mut [u8; MAX_CAPTURE_RECORD_SIZE],
+    direction: CaptureDirection,
+    pkt: &[u8],
+    snaplen: usize,
+    timestamp_ns: u64,
+) -> usize {
+    let captured_len: usize = pkt.len().min(snaplen).min(MAX_SNAPLEN);
+    record[0] = match direction {
+        CaptureDirection::Rx => 0,
+        CaptureDirection::Tx => 1,
+    };
+    record[1..3].copy_from_slice(&(pkt.len().min(u16::MAX as usize) as u16).to_le_bytes());
+    record[3..CAPTURE_HEADER_SIZE].copy_from_slice(&timestamp_ns.to_le_bytes());
+    record[CAPTURE_HEADER_SIZE..CAPTURE_HEADER_SIZE + captured_len].copy_from_slice(&pkt[..captured_len]);
+    CAPTURE_HEADER_SIZE + captured_len
+}
+
+/// Parses a capture record that was written by [encode_capture_record].
+fn decode_capture_record(record: &[u8]) -> Option<CaptureRecord> {
+    if record.len() < CAPTURE_HEADER_SIZE {
+        warn!("decode_capture_record(): record is too short (len={:?})", record.len());
+        return None;
+    }
+    let direction: CaptureDirection = match record[0] {
+        0 => CaptureDirection::Rx,
+        1 => CaptureDirection::Tx,
+        direction => {
+            warn!("decode_capture_record(): unknown direction (direction={:?})", direction);
+            return None;
+        },
+    };
+    let orig_len: usize = u16::from_le_bytes([record[1], record[2]]) as usize;
+    let mut timestamp_ns: [u8; 8] = [0; 8];
+    timestamp_ns.copy_from_slice(&record[3..CAPTURE_HEADER_SIZE]);
+    Some(CaptureRecord {
+        direction,
+        orig_len,
+        timestamp_ns: u64::from_le_bytes(timestamp_ns),
+        data: &record[CAPTURE_HEADER_SIZE..],
+    })
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+/// The diagnostics area is shared with other processes, which only ever access it through atomics.
+unsafe impl Send for DiagnosticsExporter {}
+
+unsafe impl Send for DiagnosticsClient {}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_capture_record, encode_capture_record, CaptureDirection, CaptureRecord, MAX_CAPTURE_RECORD_SIZE,
+        MAX_SNAPLEN,
+    };
+    use ::anyhow::Result;
+
+    #[test]
+    fn test_capture_record_round_trip() -> Result<()> {
+        let pkt: Vec<u8> = (0..100).collect();
+        let mut record: [u8; MAX_CAPTURE_RECORD_SIZE] = [0; MAX_CAPTURE_RECORD_SIZE];
+
+        // Packets are cut at the snaplen, but keep their original length.
+        let len: usize = encode_capture_record(&mut record, CaptureDirection::Tx, &pkt, 64, 42);
+        let decoded: CaptureRecord = match decode_capture_record(&record[..len]) {
+            Some(decoded) => decoded,
+            None => anyhow::bail!("capture record should decode"),
+        };
+        crate::ensure_eq!(decoded.direction, CaptureDirection::Tx);
+        crate::ensure_eq!(decoded.orig_len, 100);
+        crate::ensure_eq!(decoded.timestamp_ns, 42);
+        crate::ensure_eq!(decoded.data, &pkt[..64]);
+
+        // Packets that are shorter than the snaplen are captured in full.
+        let len: usize = encode_capture_record(&mut record, CaptureDirection::Rx, &pkt, MAX_SNAPLEN, 7);
+        let decoded: CaptureRecord = match decode_capture_record(&record[..len]) {
+            Some(decoded) => decoded,
+            None => anyhow::bail!("capture record should decode"),
+        };
+        crate::ensure_eq!(decoded.direction, CaptureDirection::Rx);
+        crate::ensure_eq!(decoded.data, &pkt[..]);
+
+        // Truncated records are rejected.
+        crate::ensure_eq!(decode_capture_record(&record[..3]).is_none(), true);
+
+        Ok(())
+    }
+}
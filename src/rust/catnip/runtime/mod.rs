@@ -1,13 +1,17 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+pub mod diagnostics;
 pub mod memory;
 
 //======================================================================================================================
 // Imports
 //======================================================================================================================
 
-use self::memory::{consts::DEFAULT_MAX_BODY_SIZE, MemoryManager};
+use self::{
+    diagnostics::DiagnosticsExporter,
+    memory::{consts::DEFAULT_MAX_BODY_SIZE, MemoryManager},
+};
 use crate::{
     demikernel::config::Config,
     expect_some,
@@ -27,13 +31,14 @@ use crate::{
             RTE_ETH_LINK_UP, RTE_PKTMBUF_HEADROOM,
         },
         memory::DemiBuffer,
-        network::consts::RECEIVE_BATCH_SIZE,
+        network::{consts::RECEIVE_BATCH_SIZE, socket::option::PriorityClass},
         SharedObject,
     },
     timer,
 };
 use ::arrayvec::ArrayVec;
 use ::std::{
+    collections::VecDeque,
     ffi::CString,
     mem,
     mem::MaybeUninit,
@@ -41,6 +46,16 @@ use ::std::{
     time::Duration,
 };
 
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Maximum number of packets of each priority class that may wait for room in their transmit queue.
+const TX_BACKLOG_MAX_SIZE: usize = 4096;
+
+const ETHERNET2_HEADER_SIZE: usize = 14;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+
 //======================================================================================================================
 // Structures
 //======================================================================================================================
@@ -48,6 +63,14 @@ use ::std::{
 pub struct DPDKRuntime {
     mm: MemoryManager,
     port_id: u16,
+    /// Transmit queue of each priority class, from the lowest priority to the highest one. All classes share queue 0
+    /// if the device cannot give each of them a queue of its own.
+    tx_queues: [u16; PriorityClass::COUNT],
+    /// Packets of each priority class that did not fit into their transmit queue yet. Whenever there is room again,
+    /// higher priority classes go first, so latency-critical packets skip ahead even if they all share a queue.
+    tx_backlog: [VecDeque<DemiBuffer>; PriorityClass::COUNT],
+    /// Counters and packet capture for diagnostics tools that attach as a secondary DPDK process, if enabled.
+    diagnostics: Option<DiagnosticsExporter>,
 }
 
 #[derive(Clone)]
@@ -75,15 +98,36 @@ impl SharedDPDKRuntime {
             },
         };
 
-        let (mm, port_id): (MemoryManager, u16) = Self::initialize_dpdk(
+        let (mm, port_id, tx_rings): (MemoryManager, u16, u16) = Self::initialize_dpdk(
             &config.eal_init_args()?,
             config.enable_jumbo_frames()?,
             config.mtu()?,
             tcp_offload.unwrap_or(false),
             udp_offload.unwrap_or(false),
+            config.tx_priority_queues().unwrap_or(false),
         )?;
 
-        Ok(Self(SharedObject::<DPDKRuntime>::new(DPDKRuntime { mm, port_id })))
+        // Each priority class gets the queue with the same index, so that devices that map transmit queues onto
+        // traffic classes in order serve latency-critical traffic from the highest one.
+        let mut tx_queues: [u16; PriorityClass::COUNT] = [0; PriorityClass::COUNT];
+        if tx_rings as usize == PriorityClass::COUNT {
+            for (class, queue) in tx_queues.iter_mut().enumerate() {
+                *queue = class as u16;
+            }
+        }
+
+        let diagnostics: Option<DiagnosticsExporter> = match config.dpdk_diagnostics() {
+            Ok(true) => Some(DiagnosticsExporter::new()?),
+            _ => None,
+        };
+
+        Ok(Self(SharedObject::<DPDKRuntime>::new(DPDKRuntime {
+            mm,
+            port_id,
+            tx_queues,
+            tx_backlog: Default::default(),
+            diagnostics,
+        })))
     }
 
     fn initialize_dpdk(
@@ -92,7 +136,8 @@ impl SharedDPDKRuntime {
         mtu: u16,
         tcp_checksum_offload: bool,
         udp_checksum_offload: bool,
-    ) -> Result<(MemoryManager, u16), Fail> {
+        tx_priority_queues: bool,
+    ) -> Result<(MemoryManager, u16, u16), Fail> {
         std::env::set_var("MLX5_SHUT_UP_BF", "1");
         std::env::set_var("MLX5_SINGLE_THREADED", "1");
         std::env::set_var("MLX4_SINGLE_THREADED", "1");
@@ -127,13 +172,14 @@ impl SharedDPDKRuntime {
 
         let owner: u64 = RTE_ETH_DEV_NO_OWNER as u64;
         let port_id: u16 = unsafe { rte_eth_find_next_owned_by(0, owner) as u16 };
-        Self::initialize_dpdk_port(
+        let tx_rings: u16 = Self::initialize_dpdk_port(
             port_id,
             &memory_manager,
             use_jumbo_frames,
             mtu,
             tcp_checksum_offload,
             udp_checksum_offload,
+            tx_priority_queues,
         )?;
 
         // TODO: Where is this function?
@@ -141,7 +187,7 @@ impl SharedDPDKRuntime {
         //     eprintln!("WARNING: Too many lcores enabled. Only 1 used.");
         // }
 
-        Ok((memory_manager, port_id))
+        Ok((memory_manager, port_id, tx_rings))
     }
 
     fn initialize_dpdk_port(
@@ -151,9 +197,9 @@ impl SharedDPDKRuntime {
         mtu: u16,
         tcp_checksum_offload: bool,
         udp_checksum_offload: bool,
-    ) -> Result<(), Fail> {
+        tx_priority_queues: bool,
+    ) -> Result<u16, Fail> {
         let rx_rings: u16 = 1;
-        let tx_rings: u16 = 1;
         let rx_ring_size: u16 = 2048;
         let tx_ring_size: u16 = 2048;
         let nb_rxd: u16 = rx_ring_size;
@@ -174,6 +220,26 @@ impl SharedDPDKRuntime {
         };
 
         println!("dev_info: {:?}", dev_info);
+
+        // Give each priority class a transmit queue of its own, if the device has enough of them. Otherwise, all
+        // classes share a single queue and are only prioritized in software.
+        let tx_rings: u16 = if tx_priority_queues && (dev_info.max_tx_queues as usize) >= PriorityClass::COUNT {
+            info!(
+                "initialize_dpdk_port(): using one transmit queue per priority class (max_tx_queues={:?})",
+                dev_info.max_tx_queues
+            );
+            PriorityClass::COUNT as u16
+        } else {
+            if tx_priority_queues {
+                warn!(
+                    "initialize_dpdk_port(): not enough transmit queues for priority classes, prioritizing in software \
+                     (max_tx_queues={:?})",
+                    dev_info.max_tx_queues
+                );
+            }
+            1
+        };
+
         let mut port_conf: rte_eth_conf = unsafe { MaybeUninit::zeroed().assume_init() };
         port_conf.rxmode.max_lro_pkt_size = if use_jumbo_frames {
             RTE_ETHER_MAX_JUMBO_FRAME_LEN
@@ -304,7 +370,32 @@ impl SharedDPDKRuntime {
             retry_count -= 1;
         }
 
-        Ok(())
+        Ok(tx_rings)
+    }
+}
+
+impl DPDKRuntime {
+    /// Hands as many packets as possible from the backlog over to the device, from the highest priority class to the
+    /// lowest one. Once a queue is full, classes that share it wait, so that they cannot overtake a higher priority
+    /// class.
+    fn flush_tx_backlog(&mut self) {
+        let mut full_queues: ArrayVec<u16, { PriorityClass::COUNT }> = ArrayVec::new();
+        for class in (0..PriorityClass::COUNT).rev() {
+            let queue_id: u16 = self.tx_queues[class];
+            if full_queues.contains(&queue_id) {
+                continue;
+            }
+            while let Some(pkt) = self.tx_backlog[class].pop_front() {
+                let mut mbuf_ptr: *mut rte_mbuf = expect_some!(pkt.into_mbuf(), "mbuf cannot be empty");
+                let num_sent: u16 = unsafe { rte_eth_tx_burst(self.port_id, queue_id, &mut mbuf_ptr, 1) };
+                if num_sent == 0 {
+                    // Safety: the device did not take the mbuf, so we still own it.
+                    self.tx_backlog[class].push_front(unsafe { DemiBuffer::from_mbuf(mbuf_ptr) });
+                    full_queues.push(queue_id);
+                    break;
+                }
+            }
+        }
     }
 }
 
@@ -330,24 +421,57 @@ impl PhysicalLayer for SharedDPDKRuntime {
         let outgoing_pkt: DemiBuffer = match pkt {
             buf if buf.is_dpdk_allocated() => buf,
             buf => {
-                let mut mbuf: DemiBuffer = self.mm.alloc_body_mbuf().expect("should be able to allocate mbuf");
+                // The pool may run dry under load. Report it as ENOBUFS, so that the caller can retry later.
+                let mut mbuf: DemiBuffer = match self.mm.alloc_body_mbuf() {
+                    Ok(mbuf) => mbuf,
+                    Err(e) => {
+                        let cause: String = format!("cannot allocate mbuf for outgoing packet: {:?}", e.cause);
+                        warn!("transmit(): {}", cause);
+                        if let Some(ref diagnostics) = self.diagnostics {
+                            diagnostics.on_transmit_drop();
+                        }
+                        return Err(Fail::new(libc::ENOBUFS, &cause));
+                    },
+                };
                 debug_assert!(buf.len() < mbuf.len());
                 mbuf.trim(mbuf.len() - buf.len()).expect("Should be able to trim");
-                mbuf.copy_from_slice(&buf);
+                // The packet may be a chain of heap-allocated segments, so gather them into the mbuf.
+                let mut offset: usize = 0;
+                for segment in buf.segments() {
+                    mbuf[offset..offset + segment.len()].copy_from_slice(segment);
+                    offset += segment.len();
+                }
 
                 mbuf
             },
         };
 
-        let mut mbuf_ptr: *mut rte_mbuf = expect_some!(outgoing_pkt.into_mbuf(), "mbuf cannot be empty");
-        let num_sent: u16 = unsafe { rte_eth_tx_burst(self.port_id, 0, &mut mbuf_ptr, 1) };
-        debug_assert_eq!(num_sent, 1);
+        // The packet may be a chain of mbufs, whose headers all live in the first segment.
+        let class: usize = classify(outgoing_pkt.first_segment()).index();
+        if self.tx_backlog[class].len() >= TX_BACKLOG_MAX_SIZE {
+            let cause: String = format!("transmit backlog is full (class={:?})", class);
+            warn!("transmit(): {}", cause);
+            if let Some(ref diagnostics) = self.diagnostics {
+                diagnostics.on_transmit_drop();
+            }
+            return Err(Fail::new(libc::ENOBUFS, &cause));
+        }
+        if let Some(ref diagnostics) = self.diagnostics {
+            diagnostics.on_transmit(&outgoing_pkt);
+        }
+        self.tx_backlog[class].push_back(outgoing_pkt);
+        self.flush_tx_backlog();
         Ok(())
     }
 
     fn receive(&mut self) -> Result<ArrayVec<DemiBuffer, RECEIVE_BATCH_SIZE>, Fail> {
         timer!("catnip::runtime::receive");
 
+        // Packets that did not fit into their transmit queue go out as soon as there is room again.
+        if self.tx_backlog.iter().any(|backlog| !backlog.is_empty()) {
+            self.flush_tx_backlog();
+        }
+
         let mut out = ArrayVec::new();
         let mut packets: [*mut rte_mbuf; RECEIVE_BATCH_SIZE] = unsafe { mem::zeroed() };
         let nb_rx = unsafe { rte_eth_rx_burst(self.port_id, 0, packets.as_mut_ptr(), RECEIVE_BATCH_SIZE as u16) };
@@ -357,6 +481,9 @@ impl PhysicalLayer for SharedDPDKRuntime {
             for &packet in &packets[..nb_rx as usize] {
                 // Safety: `packet` is a valid pointer to a properly initialized `rte_mbuf` struct.
                 let buf: DemiBuffer = unsafe { DemiBuffer::from_mbuf(packet) };
+                if let Some(ref diagnostics) = self.diagnostics {
+                    diagnostics.on_receive(&buf);
+                }
                 out.push(buf);
             }
         }
@@ -364,3 +491,15 @@ impl PhysicalLayer for SharedDPDKRuntime {
         Ok(out)
     }
 }
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Returns the priority class of an outgoing frame, which the network stack marked in the DSCP field of its IPv4 header.
+fn classify(frame: &[u8]) -> PriorityClass {
+    if frame.len() < ETHERNET2_HEADER_SIZE + 2 || u16::from_be_bytes([frame[12], frame[13]]) != ETHERTYPE_IPV4 {
+        return PriorityClass::default();
+    }
+    PriorityClass::from_dscp(frame[ETHERNET2_HEADER_SIZE + 1] >> 2)
+}
@@ -2,31 +2,35 @@
 // Licensed under the MIT license.
 
 mod rawsocket;
+mod rss;
 
 //======================================================================================================================
 // Imports
 //======================================================================================================================
 
 use crate::{
-    catpowder::linux::rawsocket::{RawSocket, RawSocketAddr},
-    demi_sgarray_t, demi_sgaseg_t,
+    catpowder::linux::{
+        rawsocket::{RawSocket, RawSocketAddr},
+        rss::{SoftwareRss, SoftwareRssQueue},
+    },
+    demi_sgarray_t,
     demikernel::config::Config,
     expect_ok,
     inetstack::protocols::{layer1::PhysicalLayer, layer2::Ethernet2Header, MAX_HEADER_SIZE},
     runtime::{
         fail::Fail,
         limits,
-        memory::{DemiBuffer, MemoryRuntime},
+        memory::{sgarray_from_buffer, DemiBuffer, MemoryRuntime},
         network::consts::RECEIVE_BATCH_SIZE,
-        Runtime, SharedObject,
+        Runtime,
     },
 };
 use ::arrayvec::ArrayVec;
-use ::libc::c_void;
 use ::std::{
     fs,
     mem::{self, MaybeUninit},
     num::ParseIntError,
+    sync::Arc,
 };
 
 //======================================================================================================================
@@ -36,7 +40,9 @@ use ::std::{
 #[derive(Clone)]
 pub struct LinuxRuntime {
     ifindex: i32,
-    socket: SharedObject<RawSocket>,
+    socket: Arc<RawSocket>,
+    /// Queue of this worker in the software RSS group, if incoming frames are spread across several workers.
+    rss: Option<SoftwareRssQueue>,
 }
 
 //======================================================================================================================
@@ -45,19 +51,36 @@ pub struct LinuxRuntime {
 
 impl LinuxRuntime {
     pub fn new(config: &Config) -> Result<Self, Fail> {
-        let mac_addr: [u8; 6] = [0; 6];
         let ifindex: i32 = match Self::get_ifindex(&config.local_interface_name()?) {
             Ok(ifindex) => ifindex,
             Err(_) => return Err(Fail::new(libc::EINVAL, "could not parse ifindex")),
         };
+
+        // Every raw socket gets a copy of every frame, so workers of a software RSS group share a single one.
+        match config.rss_queue_count() {
+            Ok(queue_count) if queue_count > 1 => {
+                let rss: SoftwareRssQueue = SoftwareRss::join(ifindex, queue_count)?;
+                Ok(Self {
+                    ifindex,
+                    socket: rss.get_socket(),
+                    rss: Some(rss),
+                })
+            },
+            _ => Ok(Self {
+                ifindex,
+                socket: Arc::new(Self::open_socket(ifindex)?),
+                rss: None,
+            }),
+        }
+    }
+
+    /// Opens a raw socket that is bound to the `ifindex` interface.
+    fn open_socket(ifindex: i32) -> Result<RawSocket, Fail> {
+        let mac_addr: [u8; 6] = [0; 6];
         let socket: RawSocket = RawSocket::new()?;
         let sockaddr: RawSocketAddr = RawSocketAddr::new(ifindex, &mac_addr);
         socket.bind(&sockaddr)?;
-
-        Ok(Self {
-            ifindex,
-            socket: SharedObject::<RawSocket>::new(socket),
-        })
+        Ok(socket)
     }
 
     fn get_ifindex(ifname: &str) -> Result<i32, ParseIntError> {
@@ -89,20 +112,8 @@ impl MemoryRuntime for LinuxRuntime {
         // Always allocate with header space for now even if we do not need it.
         let buf: DemiBuffer = DemiBuffer::new_with_headroom(size as u16, MAX_HEADER_SIZE as u16);
 
-        // Create a scatter-gather segment to expose the DemiBuffer to the user.
-        let data: *const u8 = buf.as_ptr();
-        let sga_seg: demi_sgaseg_t = demi_sgaseg_t {
-            sgaseg_buf: data as *mut c_void,
-            sgaseg_len: size as u32,
-        };
-
         // Create and return a new scatter-gather array (which inherits the DemiBuffer's reference).
-        Ok(demi_sgarray_t {
-            sga_buf: buf.into_raw().as_ptr() as *mut c_void,
-            sga_numsegs: 1,
-            sga_segs: [sga_seg],
-            sga_addr: unsafe { mem::zeroed() },
-        })
+        sgarray_from_buffer(buf)
     }
 }
 
@@ -110,6 +121,8 @@ impl Runtime for LinuxRuntime {}
 
 impl PhysicalLayer for LinuxRuntime {
     fn transmit(&mut self, pkt: DemiBuffer) -> Result<(), Fail> {
+        // The raw socket takes a single buffer, so gather the segments of a buffer chain first.
+        let pkt: DemiBuffer = pkt.coalesce(0)?;
         // We clone the packet so as to not remove the ethernet header from the outgoing message.
         let header = Ethernet2Header::parse_and_strip(&mut pkt.clone()).unwrap();
         let dest_addr_arr: [u8; 6] = header.dst_addr().to_array();
@@ -140,6 +153,10 @@ impl PhysicalLayer for LinuxRuntime {
 
         // TODO: change this function to operate directly on DemiBuffer rather than on MaybeUninit<u8>.
 
+        if let Some(ref rss) = self.rss {
+            return rss.receive();
+        }
+
         // This use-case is an example for MaybeUninit in the docs.
         let mut out: [MaybeUninit<u8>; limits::RECVBUF_SIZE_MAX] =
             [unsafe { MaybeUninit::uninit().assume_init() }; limits::RECVBUF_SIZE_MAX];
@@ -0,0 +1,250 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    catpowder::linux::{rawsocket::RawSocket, LinuxRuntime},
+    collections::concurrent_ring::ConcurrentRingBuffer,
+    runtime::{fail::Fail, limits, memory::DemiBuffer, network::consts::RECEIVE_BATCH_SIZE},
+};
+use ::arrayvec::ArrayVec;
+use ::std::{
+    mem::MaybeUninit,
+    slice,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Size of the ring that holds the frames of each worker.
+const RSS_RING_SIZE_BYTES: usize = 1 << 20;
+
+/// Secret key of the Toeplitz hash. This is the default key of hardware RSS implementations, so flows land on the same
+/// queue index as they would with a NIC that is configured with the same number of queues.
+const RSS_KEY: [u8; 40] = [
+    0x6d, 0x5a, 0x56, 0xda, 0x25, 0x5b, 0x0e, 0xc2, 0x41, 0x67, 0x25, 0x3d, 0x43, 0xa3, 0x8f, 0xb0, 0xd0, 0xca, 0x2b,
+    0xcb, 0xae, 0x7b, 0x30, 0xb4, 0x77, 0xcb, 0x2d, 0xa3, 0x80, 0x30, 0xf2, 0x0c, 0x6a, 0x42, 0xb7, 0x3b, 0xbe, 0xac,
+    0x01, 0xfa,
+];
+
+const ETHERNET2_HEADER_SIZE: usize = 14;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const IPV4_HEADER_MIN_SIZE: usize = 20;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// Software RSS group of this process. There is only one, because all workers share the same interface.
+static SOFTWARE_RSS: Mutex<Option<Arc<SoftwareRss>>> = Mutex::new(None);
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Software fallback for receive-side scaling (RSS), for when the NIC cannot spread incoming frames across queues by
+/// itself. All workers share a single raw socket and own one ring each. Whichever worker polls drains the raw socket
+/// and steers every frame to the ring of the worker that owns its flow, so that all frames of a connection are handled
+/// by the same worker.
+pub struct SoftwareRss {
+    ifindex: i32,
+    socket: Arc<RawSocket>,
+    queues: Vec<ConcurrentRingBuffer>,
+    /// Index of the queue that the next worker to join gets.
+    next_queue: AtomicUsize,
+}
+
+/// Handle that a worker holds on its queue of a software RSS group.
+#[derive(Clone)]
+pub struct SoftwareRssQueue {
+    rss: Arc<SoftwareRss>,
+    index: usize,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl SoftwareRss {
+    /// Joins the software RSS group of the `ifindex` interface, setting it up if the caller is the first worker. Each
+    /// worker gets a queue of its own, and at most `queue_count` workers may join.
+    pub fn join(ifindex: i32, queue_count: usize) -> Result<SoftwareRssQueue, Fail> {
+        let mut group: MutexGuard<Option<Arc<SoftwareRss>>> = match SOFTWARE_RSS.lock() {
+            Ok(group) => group,
+            Err(_) => {
+                let cause: String = format!("software RSS group is poisoned");
+                error!("join(): {}", cause);
+                return Err(Fail::new(libc::EIO, &cause));
+            },
+        };
+
+        let rss: Arc<SoftwareRss> = match group.as_ref() {
+            Some(rss) if rss.ifindex != ifindex || rss.queues.len() != queue_count => {
+                let cause: String = format!(
+                    "workers disagree on software RSS settings (ifindex={:?}, queue_count={:?}, expected_ifindex={:?}, \
+                     expected_queue_count={:?})",
+                    ifindex,
+                    queue_count,
+                    rss.ifindex,
+                    rss.queues.len()
+                );
+                error!("join(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+            Some(rss) => rss.clone(),
+            None => {
+                let mut queues: Vec<ConcurrentRingBuffer> = Vec::<ConcurrentRingBuffer>::with_capacity(queue_count);
+                for _ in 0..queue_count {
+                    queues.push(ConcurrentRingBuffer::new(RSS_RING_SIZE_BYTES)?);
+                }
+                let rss: Arc<SoftwareRss> = Arc::new(SoftwareRss {
+                    ifindex,
+                    socket: Arc::new(LinuxRuntime::open_socket(ifindex)?),
+                    queues,
+                    next_queue: AtomicUsize::new(0),
+                });
+                *group = Some(rss.clone());
+                rss
+            },
+        };
+
+        let index: usize = rss.next_queue.fetch_add(1, Ordering::Relaxed);
+        if index >= queue_count {
+            let cause: String = format!("all software RSS queues are taken (queue_count={:?})", queue_count);
+            error!("join(): {}", cause);
+            return Err(Fail::new(libc::EBUSY, &cause));
+        }
+        info!(
+            "join(): joined software RSS group (queue={:?}, queue_count={:?})",
+            index, queue_count
+        );
+
+        Ok(SoftwareRssQueue { rss, index })
+    }
+
+    /// Drains up to a batch of frames from the raw socket and hands each of them over to the worker that owns its flow.
+    fn dispatch(&self) {
+        let buf: [MaybeUninit<u8>; limits::RECVBUF_SIZE_MAX] = [MaybeUninit::<u8>::uninit(); limits::RECVBUF_SIZE_MAX];
+        for _ in 0..RECEIVE_BATCH_SIZE {
+            let nbytes: usize = match self.socket.recvfrom(&buf[..]) {
+                Ok((nbytes, _)) if nbytes > 0 => nbytes,
+                _ => break,
+            };
+            // Safety: the raw socket initialized the first `nbytes` bytes of the buffer.
+            let frame: &[u8] = unsafe { slice::from_raw_parts(buf.as_ptr() as *const u8, nbytes) };
+            match steer(frame) {
+                Some(hash) => self.push(hash as usize % self.queues.len(), frame),
+                // Every worker needs to see these frames, because each of them keeps its own ARP cache.
+                None => (0..self.queues.len()).for_each(|index| self.push(index, frame)),
+            }
+        }
+    }
+
+    fn push(&self, index: usize, frame: &[u8]) {
+        if let Err(e) = self.queues[index].try_push(frame) {
+            warn!("push(): dropping frame (queue={:?}): {:?}", index, e);
+        }
+    }
+}
+
+impl SoftwareRssQueue {
+    /// Returns the raw socket that is shared by all workers of the group.
+    pub fn get_socket(&self) -> Arc<RawSocket> {
+        self.rss.socket.clone()
+    }
+
+    /// Receives a batch of frames for this worker. We steer whatever is pending on the raw socket first, so that the
+    /// group makes progress as long as any of its workers polls.
+    pub fn receive(&self) -> Result<ArrayVec<DemiBuffer, RECEIVE_BATCH_SIZE>, Fail> {
+        self.rss.dispatch();
+
+        let mut batch: ArrayVec<DemiBuffer, RECEIVE_BATCH_SIZE> = ArrayVec::new();
+        let mut buf: [u8; limits::RECVBUF_SIZE_MAX] = [0; limits::RECVBUF_SIZE_MAX];
+        while !batch.is_full() {
+            match self.rss.queues[self.index].try_pop(&mut buf) {
+                Ok(nbytes) => batch.push(DemiBuffer::from_slice(&buf[..nbytes])?),
+                Err(_) => break,
+            }
+        }
+        Ok(batch)
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Computes the hash that steers a frame to a worker. TCP and UDP segments are hashed on their 4-tuple and other IPv4
+/// packets on their addresses. Returns `None` for frames that every worker should see.
+fn steer(frame: &[u8]) -> Option<u32> {
+    if frame.len() < ETHERNET2_HEADER_SIZE {
+        return Some(0);
+    }
+    match u16::from_be_bytes([frame[12], frame[13]]) {
+        ETHERTYPE_ARP => return None,
+        ETHERTYPE_IPV4 => (),
+        _ => return Some(0),
+    }
+
+    let ipv4: &[u8] = &frame[ETHERNET2_HEADER_SIZE..];
+    if ipv4.len() < IPV4_HEADER_MIN_SIZE {
+        return Some(0);
+    }
+    let header_size: usize = ((ipv4[0] & 0x0f) as usize) * 4;
+    let protocol: u8 = ipv4[9];
+    // Only the first fragment carries the ports, so fragments are hashed on their addresses alone.
+    let is_fragment: bool = (u16::from_be_bytes([ipv4[6], ipv4[7]]) & 0x3fff) != 0;
+    let mut input: [u8; 12] = [0; 12];
+    input[..8].copy_from_slice(&ipv4[12..20]);
+    if (protocol == IPPROTO_TCP || protocol == IPPROTO_UDP) && !is_fragment && ipv4.len() >= header_size + 4 {
+        input[8..].copy_from_slice(&ipv4[header_size..header_size + 4]);
+        Some(toeplitz_hash(&input))
+    } else {
+        Some(toeplitz_hash(&input[..8]))
+    }
+}
+
+/// Computes the Toeplitz hash of `input`, as done by hardware RSS.
+fn toeplitz_hash(input: &[u8]) -> u32 {
+    debug_assert!(input.len() + 4 <= RSS_KEY.len());
+    let mut hash: u32 = 0;
+    // Window over the key, which moves by one bit for every bit of input.
+    let mut window: u32 = u32::from_be_bytes([RSS_KEY[0], RSS_KEY[1], RSS_KEY[2], RSS_KEY[3]]);
+    for (i, byte) in input.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                hash ^= window;
+            }
+            window = (window << 1) | ((RSS_KEY[i + 4] >> (7 - bit)) & 1) as u32;
+        }
+    }
+    hash
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::toeplitz_hash;
+    use ::anyhow::Result;
+
+    /// Checks the hash against the verification suite of hardware RSS.
+    #[test]
+    fn test_toeplitz_hash() -> Result<()> {
+        // Source 66.9.149.187:2794, destination 161.142.100.80:1766.
+        let input: [u8; 12] = [66, 9, 149, 187, 161, 142, 100, 80, 0x0a, 0xea, 0x06, 0xe6];
+        crate::ensure_eq!(toeplitz_hash(&input[..8]), 0x323e8fc2);
+        crate::ensure_eq!(toeplitz_hash(&input), 0x51ccc178);
+
+        Ok(())
+    }
+}
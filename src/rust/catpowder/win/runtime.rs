@@ -10,20 +10,19 @@ use crate::{
         api::XdpApi,
         ring::{RxRing, TxRing, XdpBuffer},
     },
-    demi_sgarray_t, demi_sgaseg_t,
+    demi_sgarray_t,
     demikernel::config::Config,
     inetstack::protocols::{layer1::PhysicalLayer, MAX_HEADER_SIZE},
     runtime::{
         fail::Fail,
         libxdp,
-        memory::{DemiBuffer, MemoryRuntime},
+        memory::{sgarray_from_buffer, DemiBuffer, MemoryRuntime},
         network::consts::RECEIVE_BATCH_SIZE,
         Runtime, SharedObject,
     },
 };
 use ::arrayvec::ArrayVec;
-use ::libc::c_void;
-use ::std::{borrow::BorrowMut, mem};
+use ::std::borrow::BorrowMut;
 use windows::Win32::{
     Foundation::ERROR_INSUFFICIENT_BUFFER,
     System::SystemInformation::{
@@ -119,14 +118,21 @@ impl PhysicalLayer for SharedCatpowderRuntime {
 
         let mut idx: u32 = 0;
 
+        // The TX ring has no room left while the NIC is still busy with earlier packets. Report it as ENOBUFS, like
+        // the other backends do when they run out of buffers, so that the caller backs off and retries.
         if self.0.borrow_mut().tx.reserve_tx(Self::RING_LENGTH, &mut idx) != Self::RING_LENGTH {
             let cause = format!("failed to reserve producer space for packet");
             warn!("{}", cause);
-            return Err(Fail::new(libc::EAGAIN, &cause));
+            return Err(Fail::new(libc::ENOBUFS, &cause));
         }
 
+        // Gather the segments of the packet into the XDP buffer.
         let mut buf: XdpBuffer = self.0.borrow_mut().tx.get_buffer(idx, pkt_size);
-        buf.copy_from_slice(&pkt);
+        let mut offset: usize = 0;
+        for segment in pkt.segments() {
+            buf[offset..offset + segment.len()].copy_from_slice(segment);
+            offset += segment.len();
+        }
 
         self.0.borrow_mut().tx.submit_tx(Self::RING_LENGTH);
 
@@ -160,28 +166,24 @@ impl PhysicalLayer for SharedCatpowderRuntime {
     /// Polls for received packets.
     fn receive(&mut self) -> Result<ArrayVec<DemiBuffer, RECEIVE_BATCH_SIZE>, Fail> {
         let mut ret: ArrayVec<DemiBuffer, RECEIVE_BATCH_SIZE> = ArrayVec::new();
-        let mut idx: u32 = 0;
-
-        for rx in self.0.borrow_mut().rx_rings.iter_mut() {
-            if rx.reserve_rx(Self::RING_LENGTH, &mut idx) == Self::RING_LENGTH {
-                let xdp_buffer: XdpBuffer = rx.get_buffer(idx);
-                let dbuf: DemiBuffer = DemiBuffer::from_slice(&*xdp_buffer)?;
-                rx.release_rx(Self::RING_LENGTH);
-
-                ret.push(dbuf);
-
-                rx.reserve_rx_fill(Self::RING_LENGTH, &mut idx);
-                // NB for now there is only ever one element in the fill ring, so we don't have to
-                // change the ring contents.
-                rx.submit_rx_fill(Self::RING_LENGTH);
+        let inner: &mut CatpowderRuntimeInner = self.0.as_mut();
+        Self::receive_from_rings(&mut inner.rx_rings, &mut ret)?;
+        Self::receive_from_rings(&mut inner.vf_rx_rings, &mut ret)?;
+        Ok(ret)
+    }
+}
 
-                if ret.is_full() {
-                    break;
-                }
+impl SharedCatpowderRuntime {
+    /// Takes at most one packet from each of `rings` into `ret`, until `ret` is full.
+    fn receive_from_rings(
+        rings: &mut [RxRing],
+        ret: &mut ArrayVec<DemiBuffer, RECEIVE_BATCH_SIZE>,
+    ) -> Result<(), Fail> {
+        let mut idx: u32 = 0;
+        for rx in rings.iter_mut() {
+            if ret.is_full() {
+                break;
             }
-        }
-
-        for rx in self.0.borrow_mut().vf_rx_rings.iter_mut() {
             if rx.reserve_rx(Self::RING_LENGTH, &mut idx) == Self::RING_LENGTH {
                 let xdp_buffer: XdpBuffer = rx.get_buffer(idx);
                 let dbuf: DemiBuffer = DemiBuffer::from_slice(&*xdp_buffer)?;
@@ -193,14 +195,9 @@ impl PhysicalLayer for SharedCatpowderRuntime {
                 // NB for now there is only ever one element in the fill ring, so we don't have to
                 // change the ring contents.
                 rx.submit_rx_fill(Self::RING_LENGTH);
-
-                if ret.is_full() {
-                    break;
-                }
             }
         }
-
-        Ok(ret)
+        Ok(())
     }
 }
 
@@ -314,20 +311,8 @@ impl MemoryRuntime for SharedCatpowderRuntime {
         // Always allocate with header space for now even if we do not need it.
         let buf: DemiBuffer = DemiBuffer::new_with_headroom(size as u16, MAX_HEADER_SIZE as u16);
 
-        // Create a scatter-gather segment to expose the DemiBuffer to the user.
-        let data: *const u8 = buf.as_ptr();
-        let sga_seg: demi_sgaseg_t = demi_sgaseg_t {
-            sgaseg_buf: data as *mut c_void,
-            sgaseg_len: size as u32,
-        };
-
         // Create and return a new scatter-gather array (which inherits the DemiBuffer's reference).
-        Ok(demi_sgarray_t {
-            sga_buf: buf.into_raw().as_ptr() as *mut c_void,
-            sga_numsegs: 1,
-            sga_segs: [sga_seg],
-            sga_addr: unsafe { mem::zeroed() },
-        })
+        sgarray_from_buffer(buf)
     }
 }
 
@@ -5,14 +5,16 @@
 // Imports
 //======================================================================================================================
 
-use crate::runtime::{conditional_yield_with_timeout, fail::Fail, SharedConditionVariable, SharedObject};
+use crate::runtime::{
+    conditional_yield_until, conditional_yield_with_timeout, fail::Fail, SharedConditionVariable, SharedObject,
+};
 use ::std::{
     collections::{
         vec_deque::{Iter, IterMut},
         VecDeque,
     },
     ops::{Deref, DerefMut},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 //======================================================================================================================
@@ -26,61 +28,214 @@ const DEFAULT_QUEUE_SIZE: usize = 1024;
 // Structures
 //======================================================================================================================
 
-/// This data structure implements an unbounded asynchronous queue that is hooked into the Demikernel scheduler. On
-/// pop, if the queue is empty, the coroutine will yield until there is data to be read.
+/// What a bounded queue does with an item that is pushed while the queue is full.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// The producer waits until there is room. Only [AsyncQueue::push_or_wait] can wait, so a synchronous push onto a
+    /// full queue drops the new item instead.
+    Block,
+    /// The oldest item in the queue is dropped to make room for the new one.
+    DropOldest,
+    /// The new item is dropped.
+    DropNewest,
+}
+
+/// This data structure implements an asynchronous queue that is hooked into the Demikernel scheduler. On pop, if the
+/// queue is empty, the coroutine will yield until there is data to be read. Several coroutines may wait on the same
+/// queue, in which case they are served in the order in which they started to wait. Queues are unbounded unless they
+/// are created with [AsyncQueue::bounded], in which case the overflow policy decides what happens on a push to a full
+/// queue.
 pub struct AsyncQueue<T> {
     queue: VecDeque<T>,
     cond_var: SharedConditionVariable,
+    // Maximum number of items in the queue and what to do when it is exceeded, if the queue is bounded.
+    limit: Option<(usize, OverflowPolicy)>,
+    // Signaled whenever an item leaves a bounded queue, to wake up producers that wait for room.
+    room_cond_var: SharedConditionVariable,
+    // Number of items that were dropped because the queue was full.
+    num_dropped: u64,
+    // Consumers that wait for an item, in the order in which they started to wait. Only the first one may take an item,
+    // and consumers that arrive later do not get to take one ahead of it.
+    consumers: SharedObject<VecDeque<u64>>,
+    // Identifier for the next consumer that has to wait.
+    next_consumer_id: u64,
 }
 
 pub struct SharedAsyncQueue<T>(SharedObject<AsyncQueue<T>>);
 
+/// Takes a waiting consumer out of line when its pop completes, times out or is dropped.
+struct ConsumerGuard {
+    consumers: SharedObject<VecDeque<u64>>,
+    cond_var: SharedConditionVariable,
+    id: u64,
+}
+
 //======================================================================================================================
 // Associate Functions
 //======================================================================================================================
 
 impl<T> AsyncQueue<T> {
-    /// This function allocates a shared async queue with a specified capacity.
-    // TODO: Enforce capacity limit and do not let queue grow past that.
+    /// This function allocates an unbounded async queue with room for `size` items before it has to grow.
     pub fn with_capacity(size: usize) -> Self {
         Self {
             queue: VecDeque::<T>::with_capacity(size),
             cond_var: SharedConditionVariable::default(),
+            limit: None,
+            room_cond_var: SharedConditionVariable::default(),
+            num_dropped: 0,
+            consumers: SharedObject::new(VecDeque::new()),
+            next_consumer_id: 0,
+        }
+    }
+
+    /// This function allocates an async queue that never holds more than `capacity` items. Pushing to it while it is
+    /// full follows `policy`.
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            limit: Some((capacity, policy)),
+            ..Self::with_capacity(capacity)
         }
     }
 
-    /// Push to a async queue. Currently async queues are unbounded, so we can synchronously push to them but we will
-    /// add bounds checking in the future.
+    /// Push to an async queue. If the queue is full, this drops either the new item or the oldest one, depending on the
+    /// overflow policy of the queue.
     pub fn push(&mut self, item: T) {
+        if let Some((capacity, policy)) = self.limit {
+            if self.queue.len() >= capacity {
+                self.num_dropped += 1;
+                match policy {
+                    OverflowPolicy::DropOldest => {
+                        self.queue.pop_front();
+                    },
+                    OverflowPolicy::Block | OverflowPolicy::DropNewest => return,
+                }
+            }
+        }
         self.queue.push_back(item);
-        self.cond_var.signal();
+        self.wake_consumers();
+    }
+
+    /// Push to an async queue. If the queue is full and its overflow policy is to block, this waits until there is room
+    /// for the new item. Otherwise, this behaves like [AsyncQueue::push].
+    pub async fn push_or_wait(&mut self, item: T) {
+        if let Some((capacity, OverflowPolicy::Block)) = self.limit {
+            while self.queue.len() >= capacity {
+                self.room_cond_var.wait().await;
+            }
+        }
+        self.push(item)
     }
 
     pub fn push_front(&mut self, item: T) {
         self.queue.push_front(item);
-        self.cond_var.signal();
+        self.wake_consumers();
+    }
+
+    /// Wakes up the consumer that is first in line for the next item. Consumers are woken up in the order in which they
+    /// started waiting, unless some of them gave up in the meantime, so we wake everybody if more than one is waiting
+    /// and let the first one in line take the item.
+    fn wake_consumers(&mut self) {
+        if self.consumers.len() > 1 {
+            self.cond_var.broadcast();
+        } else {
+            self.cond_var.signal();
+        }
     }
 
-    /// Pop from an async queue. If the queue is empty, this function blocks until it finds something in the queue.
+    /// Pop from an async queue. If the queue is empty, this function blocks until it finds something in the queue or
+    /// until `timeout` passes, in which case it fails with ETIMEDOUT.
     pub async fn pop(&mut self, timeout: Option<Duration>) -> Result<T, Fail> {
-        let wait_condition = async {
-            loop {
+        match timeout {
+            Some(timeout) => conditional_yield_with_timeout(self.wait_for_item(), timeout).await,
+            None => Ok(self.wait_for_item().await),
+        }
+    }
+
+    /// Pop from an async queue. If the queue is empty, this function blocks until it finds something in the queue or
+    /// until `deadline` passes, in which case it fails with ETIMEDOUT.
+    pub async fn pop_until(&mut self, deadline: Option<Instant>) -> Result<T, Fail> {
+        conditional_yield_until(self.wait_for_item(), deadline).await
+    }
+
+    /// Waits until this consumer is first in line and there is an item for it.
+    async fn wait_for_item(&mut self) -> T {
+        // Take an item right away, unless others are already waiting for one.
+        if self.consumers.is_empty() {
+            if let Some(item) = self.queue.pop_front() {
+                self.on_room_made();
+                return item;
+            }
+        }
+
+        let id: u64 = self.next_consumer_id;
+        self.next_consumer_id += 1;
+        self.consumers.push_back(id);
+        let _guard: ConsumerGuard = ConsumerGuard {
+            consumers: self.consumers.clone(),
+            cond_var: self.cond_var.clone(),
+            id,
+        };
+        loop {
+            if self.consumers.front() == Some(&id) {
                 if let Some(item) = self.queue.pop_front() {
+                    self.on_room_made();
                     return item;
-                } else {
-                    self.cond_var.wait().await;
                 }
             }
+            self.cond_var.wait().await;
+        }
+    }
+
+    /// Waits until the items in the queue satisfy `condition`, which is checked right away and then after every push.
+    /// This takes a place in line like a pop does, so that pushes wake it up, but leaves the items in the queue.
+    pub async fn wait_for_condition(&mut self, condition: impl Fn(Iter<T>) -> bool) {
+        if condition(self.queue.iter()) {
+            return;
+        }
+
+        let id: u64 = self.next_consumer_id;
+        self.next_consumer_id += 1;
+        self.consumers.push_back(id);
+        let _guard: ConsumerGuard = ConsumerGuard {
+            consumers: self.consumers.clone(),
+            cond_var: self.cond_var.clone(),
+            id,
         };
-        match timeout {
-            Some(timeout) => conditional_yield_with_timeout(wait_condition, timeout).await,
-            None => Ok(wait_condition.await),
+        loop {
+            self.cond_var.wait().await;
+            if condition(self.queue.iter()) {
+                return;
+            }
         }
     }
 
     /// Try to get the head of the queue.
     pub fn try_pop(&mut self) -> Option<T> {
-        self.queue.pop_front()
+        let item: Option<T> = self.queue.pop_front();
+        if item.is_some() {
+            self.on_room_made();
+        }
+        item
+    }
+
+    /// Remove the first [count] items from the queue at once.
+    pub fn drain_front(&mut self, count: usize) {
+        self.queue.drain(..count);
+        if count > 0 {
+            self.on_room_made();
+        }
+    }
+
+    /// Wakes up a producer that waits for room in a bounded queue.
+    fn on_room_made(&mut self) {
+        if self.limit.is_some() {
+            self.room_cond_var.signal();
+        }
+    }
+
+    /// Get the number of items that were dropped because the queue was full.
+    pub fn get_num_dropped(&self) -> u64 {
+        self.num_dropped
     }
 
     /// Get the length of the queue.
@@ -117,12 +272,19 @@ impl<T> AsyncQueue<T> {
 }
 
 impl<T> SharedAsyncQueue<T> {
-    /// This function allocates a shared async queue with a specified capacity.
-    // TODO: Enforce capacity limit and do not let queue grow past that.
+    /// This function allocates an unbounded shared async queue with room for `size` items before it has to grow.
     #[allow(dead_code)]
     pub fn with_capacity(size: usize) -> Self {
         Self(SharedObject::<AsyncQueue<T>>::new(AsyncQueue::with_capacity(size)))
     }
+
+    /// This function allocates a shared async queue that never holds more than `capacity` items. Pushing to it while it
+    /// is full follows `policy`.
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self(SharedObject::<AsyncQueue<T>>::new(AsyncQueue::bounded(
+            capacity, policy,
+        )))
+    }
 }
 
 //======================================================================================================================
@@ -131,10 +293,7 @@ impl<T> SharedAsyncQueue<T> {
 
 impl<T> Default for AsyncQueue<T> {
     fn default() -> Self {
-        Self {
-            queue: VecDeque::<T>::with_capacity(DEFAULT_QUEUE_SIZE),
-            cond_var: SharedConditionVariable::default(),
-        }
+        Self::with_capacity(DEFAULT_QUEUE_SIZE)
     }
 }
 impl<T> Default for SharedAsyncQueue<T> {
@@ -143,6 +302,17 @@ impl<T> Default for SharedAsyncQueue<T> {
     }
 }
 
+impl Drop for ConsumerGuard {
+    fn drop(&mut self) {
+        let was_first: bool = self.consumers.front() == Some(&self.id);
+        self.consumers.retain(|id| *id != self.id);
+        // The next consumer in line may be able to take an item that was left in the queue.
+        if was_first && !self.consumers.is_empty() {
+            self.cond_var.broadcast();
+        }
+    }
+}
+
 impl<T> Deref for SharedAsyncQueue<T> {
     type Target = AsyncQueue<T>;
 
@@ -162,3 +332,58 @@ impl<T> Clone for SharedAsyncQueue<T> {
         Self(self.0.clone())
     }
 }
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::collections::async_queue::{AsyncQueue, OverflowPolicy};
+    use ::anyhow::Result;
+
+    fn to_vec(queue: &AsyncQueue<u32>) -> Vec<u32> {
+        queue.get_values().copied().collect()
+    }
+
+    #[test]
+    fn drop_oldest_when_full() -> Result<()> {
+        let mut queue: AsyncQueue<u32> = AsyncQueue::bounded(2, OverflowPolicy::DropOldest);
+        for i in 0..4 {
+            queue.push(i);
+        }
+        crate::ensure_eq!(to_vec(&queue), vec![2, 3]);
+        crate::ensure_eq!(queue.get_num_dropped(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn drop_newest_when_full() -> Result<()> {
+        let mut queue: AsyncQueue<u32> = AsyncQueue::bounded(2, OverflowPolicy::DropNewest);
+        for i in 0..4 {
+            queue.push(i);
+        }
+        crate::ensure_eq!(to_vec(&queue), vec![0, 1]);
+        crate::ensure_eq!(queue.get_num_dropped(), 2);
+
+        // Popping makes room again.
+        crate::ensure_eq!(queue.try_pop(), Some(0));
+        queue.push(4);
+        crate::ensure_eq!(to_vec(&queue), vec![1, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unbounded_never_drops() -> Result<()> {
+        let mut queue: AsyncQueue<u32> = AsyncQueue::with_capacity(2);
+        for i in 0..4 {
+            queue.push(i);
+        }
+        crate::ensure_eq!(to_vec(&queue), vec![0, 1, 2, 3]);
+        crate::ensure_eq!(queue.get_num_dropped(), 0);
+
+        Ok(())
+    }
+}
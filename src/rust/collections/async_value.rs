@@ -8,9 +8,13 @@
 use crate::runtime::{
     conditional_yield_until, conditional_yield_with_timeout, fail::Fail, SharedConditionVariable, SharedObject,
 };
+use ::futures::{future::poll_fn, pin_mut};
 use ::std::{
     fmt,
+    future::Future,
     ops::{Deref, DerefMut},
+    pin::Pin,
+    task::Poll,
     time::{Duration, Instant},
 };
 
@@ -31,6 +35,16 @@ pub struct AsyncValue<T: Clone> {
 /// Reference to an AsyncValue that is shared across coroutines.
 pub struct SharedAsyncValue<T: Clone>(SharedObject<AsyncValue<T>>);
 
+//======================================================================================================================
+// Traits
+//======================================================================================================================
+
+/// Something that coroutines can wait on for changes, regardless of the type of its value. See [select].
+pub trait Watched {
+    /// Returns the condition variable that is signaled when this changes.
+    fn get_cond_var(&self) -> SharedConditionVariable;
+}
+
 //======================================================================================================================
 // Associate Functions
 //======================================================================================================================
@@ -74,6 +88,26 @@ impl<T: Clone> AsyncValue<T> {
         conditional_yield_until(self.cond_var.wait(), expiry).await?;
         Ok(self.value.clone())
     }
+
+    /// Waits until the value satisfies `condition`, which is checked right away and then after every change. Returns
+    /// the value that satisfied it or ETIMEDOUT if it did not do so within `timeout`.
+    pub async fn wait_for_condition(
+        &mut self,
+        condition: impl Fn(&T) -> bool,
+        timeout: Option<Duration>,
+    ) -> Result<T, Fail> {
+        let mut cond_var: SharedConditionVariable = self.cond_var.clone();
+        let satisfied = async {
+            while !condition(&self.value) {
+                cond_var.wait().await;
+            }
+        };
+        match timeout {
+            Some(timeout) => conditional_yield_with_timeout(satisfied, timeout).await?,
+            None => satisfied.await,
+        };
+        Ok(self.value.clone())
+    }
 }
 
 impl<T: Clone> SharedAsyncValue<T> {
@@ -82,10 +116,46 @@ impl<T: Clone> SharedAsyncValue<T> {
     }
 }
 
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Waits until any of `watched` changes or until `expiry` passes, whichever comes first. Returns the index of the first
+/// one that changed or ETIMEDOUT. Unlike selecting over `wait_for_change()` futures, this neither allocates nor needs
+/// the watched values to have the same type.
+pub async fn select<const N: usize>(watched: [&dyn Watched; N], expiry: Option<Instant>) -> Result<usize, Fail> {
+    let mut cond_vars: [SharedConditionVariable; N] = watched.map(|watched| watched.get_cond_var());
+    let waits = cond_vars.each_mut().map(|cond_var| cond_var.wait());
+    pin_mut!(waits);
+    let any_change = poll_fn(|context| {
+        // Safety: The futures are polled in place and never moved out of the pinned array.
+        let waits = unsafe { waits.as_mut().get_unchecked_mut() };
+        for (i, wait) in waits.iter_mut().enumerate() {
+            if unsafe { Pin::new_unchecked(wait) }.poll(context).is_ready() {
+                return Poll::Ready(i);
+            }
+        }
+        Poll::Pending
+    });
+    conditional_yield_until(any_change, expiry).await
+}
+
 //======================================================================================================================
 // Associate Functions
 //======================================================================================================================
 
+impl<T: Clone> Watched for SharedAsyncValue<T> {
+    fn get_cond_var(&self) -> SharedConditionVariable {
+        self.cond_var.clone()
+    }
+}
+
+impl Watched for SharedConditionVariable {
+    fn get_cond_var(&self) -> SharedConditionVariable {
+        self.clone()
+    }
+}
+
 impl<T: Clone> Deref for SharedAsyncValue<T> {
     type Target = AsyncValue<T>;
 
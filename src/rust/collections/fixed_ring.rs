@@ -0,0 +1,337 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::std::ops::{Index, IndexMut};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// A double-ended queue with a fixed capacity that is allocated once, when the ring is created. Pushing to a full ring
+/// fails instead of growing it, so the memory footprint of the ring never changes. Inserting into or removing from the
+/// middle of the ring only moves the elements on the shorter side of the affected position.
+pub struct FixedRing<T> {
+    /// Storage for the elements. Slots outside of the occupied range are always empty.
+    slots: Box<[Option<T>]>,
+    /// Index of the slot that holds the first element.
+    head: usize,
+    /// Number of elements in the ring.
+    len: usize,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl<T> FixedRing<T> {
+    /// Creates an empty ring that holds up to `capacity` elements.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity of a ring should be greater than zero");
+        Self {
+            slots: (0..capacity).map(|_| None).collect(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            self.slots[self.slot(index)].as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index < self.len {
+            let slot: usize = self.slot(index);
+            self.slots[slot].as_mut()
+        } else {
+            None
+        }
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    #[allow(unused)]
+    pub fn back(&self) -> Option<&T> {
+        self.len.checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    /// Appends an element to the back of the ring. If the ring is full, the element is handed back to the caller.
+    pub fn push_back(&mut self, item: T) -> Result<(), T> {
+        self.insert(self.len, item)
+    }
+
+    /// Prepends an element to the front of the ring. If the ring is full, the element is handed back to the caller.
+    #[allow(unused)]
+    pub fn push_front(&mut self, item: T) -> Result<(), T> {
+        self.insert(0, item)
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let item: Option<T> = self.slots[self.head].take();
+        self.head = (self.head + 1) % self.capacity();
+        self.len -= 1;
+        item
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let slot: usize = self.slot(self.len - 1);
+        self.len -= 1;
+        self.slots[slot].take()
+    }
+
+    /// Inserts an element at `index`, so that it ends up at that position in the ring. If the ring is full, the
+    /// element is handed back to the caller.
+    pub fn insert(&mut self, index: usize, item: T) -> Result<(), T> {
+        assert!(
+            index <= self.len,
+            "index out of bounds (index={:?}, len={:?})",
+            index,
+            self.len
+        );
+        if self.is_full() {
+            return Err(item);
+        }
+
+        if index < self.len - index {
+            // Open a gap by shifting the elements in front of the index one slot towards the front.
+            self.head = (self.head + self.capacity() - 1) % self.capacity();
+            for i in 0..index {
+                let (from, to): (usize, usize) = (self.slot(i + 1), self.slot(i));
+                self.slots[to] = self.slots[from].take();
+            }
+        } else {
+            // Open a gap by shifting the elements behind the index one slot towards the back.
+            for i in (index..self.len).rev() {
+                let (from, to): (usize, usize) = (self.slot(i), self.slot(i + 1));
+                self.slots[to] = self.slots[from].take();
+            }
+        }
+        let slot: usize = self.slot(index);
+        self.slots[slot] = Some(item);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Replaces the element at `index`, returning the element that was there before.
+    pub fn replace(&mut self, index: usize, item: T) -> T {
+        assert!(
+            index < self.len,
+            "index out of bounds (index={:?}, len={:?})",
+            index,
+            self.len
+        );
+        let slot: usize = self.slot(index);
+        match self.slots[slot].replace(item) {
+            Some(old) => old,
+            None => unreachable!("occupied slots should hold an element (index={:?})", index),
+        }
+    }
+
+    /// Removes the element at `index`.
+    #[allow(unused)]
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+        let slot: usize = self.slot(index);
+        let item: Option<T> = self.slots[slot].take();
+        self.close_gap(index, 1);
+        item
+    }
+
+    /// Drops the `count` elements that start at `index`. The remaining elements are shifted only once, regardless of
+    /// how many elements are removed.
+    pub fn remove_range(&mut self, index: usize, count: usize) {
+        assert!(
+            index + count <= self.len,
+            "range out of bounds (index={:?}, count={:?}, len={:?})",
+            index,
+            count,
+            self.len
+        );
+        for i in index..(index + count) {
+            let slot: usize = self.slot(i);
+            self.slots[slot] = None;
+        }
+        self.close_gap(index, count);
+    }
+
+    #[allow(unused)]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len).filter_map(move |i| self.slots[self.slot(i)].as_ref())
+    }
+
+    /// Closes a gap of `count` empty slots that starts at `index`, by shifting the shorter side of the ring.
+    fn close_gap(&mut self, index: usize, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let tail: usize = self.len - index - count;
+        if index < tail {
+            // Shift the elements in front of the gap towards the back.
+            for i in (0..index).rev() {
+                let (from, to): (usize, usize) = (self.slot(i), self.slot(i + count));
+                self.slots[to] = self.slots[from].take();
+            }
+            self.head = (self.head + count) % self.capacity();
+        } else {
+            // Shift the elements behind the gap towards the front.
+            for i in (index + count)..self.len {
+                let (from, to): (usize, usize) = (self.slot(i), self.slot(i - count));
+                self.slots[to] = self.slots[from].take();
+            }
+        }
+        self.len -= count;
+    }
+
+    /// Converts a position in the ring into an index in the underlying storage.
+    fn slot(&self, index: usize) -> usize {
+        (self.head + index) % self.capacity()
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl<T> Index<usize> for FixedRing<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match self.get(index) {
+            Some(item) => item,
+            None => panic!("index out of bounds (index={:?}, len={:?})", index, self.len),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for FixedRing<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        let len: usize = self.len;
+        match self.get_mut(index) {
+            Some(item) => item,
+            None => panic!("index out of bounds (index={:?}, len={:?})", index, len),
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use crate::collections::fixed_ring::FixedRing;
+    use ::anyhow::Result;
+
+    fn to_vec(ring: &FixedRing<u32>) -> Vec<u32> {
+        ring.iter().copied().collect()
+    }
+
+    #[test]
+    fn push_and_pop_wrap_around() -> Result<()> {
+        let mut ring: FixedRing<u32> = FixedRing::new(4);
+        for i in 0..10 {
+            crate::ensure_eq!(ring.push_back(i).is_ok(), true);
+            crate::ensure_eq!(ring.pop_front(), Some(i));
+        }
+        crate::ensure_eq!(ring.is_empty(), true);
+        crate::ensure_eq!(ring.pop_back(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn push_to_full_ring_fails() -> Result<()> {
+        let mut ring: FixedRing<u32> = FixedRing::new(2);
+        crate::ensure_eq!(ring.push_back(1).is_ok(), true);
+        crate::ensure_eq!(ring.push_front(0).is_ok(), true);
+        crate::ensure_eq!(ring.is_full(), true);
+        crate::ensure_eq!(ring.push_back(2), Err(2));
+        crate::ensure_eq!(ring.insert(1, 3), Err(3));
+        crate::ensure_eq!(to_vec(&ring), vec![0, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn insert_keeps_order() -> Result<()> {
+        let mut ring: FixedRing<u32> = FixedRing::new(8);
+        // Move the head so that the elements wrap around the end of the storage.
+        for i in 0..6 {
+            crate::ensure_eq!(ring.push_back(i).is_ok(), true);
+            ring.pop_front();
+        }
+        for i in [10, 50] {
+            crate::ensure_eq!(ring.push_back(i).is_ok(), true);
+        }
+        crate::ensure_eq!(ring.insert(1, 30).is_ok(), true);
+        crate::ensure_eq!(ring.insert(1, 20).is_ok(), true);
+        crate::ensure_eq!(ring.insert(3, 40).is_ok(), true);
+        crate::ensure_eq!(ring.insert(0, 0).is_ok(), true);
+        crate::ensure_eq!(to_vec(&ring), vec![0, 10, 20, 30, 40, 50]);
+        crate::ensure_eq!(ring.front(), Some(&0));
+        crate::ensure_eq!(ring.back(), Some(&50));
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_closes_gap() -> Result<()> {
+        let mut ring: FixedRing<u32> = FixedRing::new(8);
+        for i in 0..8 {
+            crate::ensure_eq!(ring.push_back(i).is_ok(), true);
+        }
+        crate::ensure_eq!(ring.remove(1), Some(1));
+        crate::ensure_eq!(ring.remove(5), Some(6));
+        crate::ensure_eq!(to_vec(&ring), vec![0, 2, 3, 4, 5, 7]);
+        ring.remove_range(1, 2);
+        crate::ensure_eq!(to_vec(&ring), vec![0, 4, 5, 7]);
+        ring.remove_range(2, 2);
+        crate::ensure_eq!(to_vec(&ring), vec![0, 4]);
+        crate::ensure_eq!(ring.remove(2), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn replace_returns_old_element() -> Result<()> {
+        let mut ring: FixedRing<u32> = FixedRing::new(2);
+        crate::ensure_eq!(ring.push_back(1).is_ok(), true);
+        crate::ensure_eq!(ring.replace(0, 2), 1);
+        crate::ensure_eq!(ring[0], 2);
+
+        Ok(())
+    }
+}
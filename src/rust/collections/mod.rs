@@ -3,7 +3,14 @@
 
 pub mod async_queue;
 pub mod async_value;
+#[allow(dead_code)]
+pub mod concurrent_ring;
+pub mod fixed_ring;
 pub mod hashttlcache;
 pub mod id_map;
 pub mod intrusive;
 pub mod pin_slab;
+#[allow(dead_code)]
+pub mod raw_array;
+#[allow(dead_code)]
+pub mod ring;
@@ -0,0 +1,406 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    demikernel::libos::LibOS,
+    expect_some,
+    runtime::{fail::Fail, QDesc, QToken},
+};
+use ::socket2::{Domain, Type};
+use ::std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    net::{SocketAddr, SocketAddrV4},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Default number of entries in the lookup table. This has to be a prime, and the Maglev paper recommends one that is
+/// much larger than the number of backends, so that each of them gets about the same share of the connections.
+pub const DEFAULT_TABLE_SIZE: usize = 65537;
+
+/// Seeds that tell apart the two permutation hashes of a backend and the hash of a connection.
+const OFFSET_SEED: u64 = 0;
+const SKIP_SEED: u64 = 1;
+const CONNECTION_SEED: u64 = 2;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Whether a backend takes new connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackendState {
+    /// The backend takes new connections.
+    Active,
+    /// The backend keeps the connections that it already has, but takes no new ones. It is removed once the last of
+    /// them is released.
+    Draining,
+}
+
+struct Backend {
+    addr: SocketAddrV4,
+    state: BackendState,
+    connections: usize,
+}
+
+/// Steers connections to backends for L4 load balancers, with consistent hashing as Maglev does it (Eisenbud et al.,
+/// NSDI 2016). Every active backend fills its share of a lookup table in the order of a permutation that depends only on
+/// its own address, so adding or removing one moves few connections between the others. On top of that, the balancer
+/// tracks the 4-tuple of every connection that it steered, so that connections stay on their backend no matter how
+/// the set of backends changes, until they are released.
+///
+/// Backends are known by the index that adding them returns, which stays the same until they are removed.
+pub struct LoadBalancer {
+    backends: Vec<Option<Backend>>,
+    table: Vec<Option<usize>>,
+    connections: HashMap<(SocketAddrV4, SocketAddrV4), usize>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl LoadBalancer {
+    /// Creates a load balancer without backends, whose lookup table has [table_size] entries.
+    pub fn new(table_size: usize) -> Result<Self, Fail> {
+        if !is_prime(table_size) {
+            let cause: String = format!("size of lookup table must be a prime (table_size={:?})", table_size);
+            error!("new(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        Ok(Self {
+            backends: Vec::new(),
+            table: vec![None; table_size],
+            connections: HashMap::new(),
+        })
+    }
+
+    /// Adds a backend that takes new connections right away and returns its index.
+    pub fn add_backend(&mut self, addr: SocketAddrV4) -> Result<usize, Fail> {
+        if self.backends.iter().flatten().any(|backend| backend.addr == addr) {
+            let cause: String = format!("backend already exists (addr={:?})", addr);
+            error!("add_backend(): {}", cause);
+            return Err(Fail::new(libc::EEXIST, &cause));
+        }
+        let backend: Backend = Backend {
+            addr,
+            state: BackendState::Active,
+            connections: 0,
+        };
+        let index: usize = match self.backends.iter().position(|backend| backend.is_none()) {
+            Some(index) => {
+                self.backends[index] = Some(backend);
+                index
+            },
+            None => {
+                self.backends.push(Some(backend));
+                self.backends.len() - 1
+            },
+        };
+        self.populate();
+        Ok(index)
+    }
+
+    /// Stops steering new connections to the backend at [index]. The connections that it already has stay, and the
+    /// backend is removed once the last of them is released.
+    pub fn drain_backend(&mut self, index: usize) -> Result<(), Fail> {
+        let backend: &mut Backend = self.get_backend_mut(index)?;
+        backend.state = BackendState::Draining;
+        if backend.connections == 0 {
+            self.backends[index] = None;
+        }
+        self.populate();
+        Ok(())
+    }
+
+    /// Removes the backend at [index] right away and forgets its connections, e.g. because it failed. Returns how many
+    /// connections it had, whose forwarding the caller should tear down.
+    pub fn remove_backend(&mut self, index: usize) -> Result<usize, Fail> {
+        let connections: usize = self.get_backend_mut(index)?.connections;
+        self.connections.retain(|_, backend| *backend != index);
+        self.backends[index] = None;
+        self.populate();
+        Ok(connections)
+    }
+
+    /// Returns the address and the state of the backend at [index].
+    pub fn get_backend(&self, index: usize) -> Option<(SocketAddrV4, BackendState)> {
+        self.backends
+            .get(index)?
+            .as_ref()
+            .map(|backend| (backend.addr, backend.state))
+    }
+
+    /// Returns how many of the connections that were steered to the backend at [index] are not released yet.
+    pub fn get_connections(&self, index: usize) -> usize {
+        match self.backends.get(index) {
+            Some(Some(backend)) => backend.connections,
+            _ => 0,
+        }
+    }
+
+    /// Returns the index of the backend that takes the connection from [remote] to [local], and tracks the connection
+    /// until it is released. A connection that is tracked already stays on its backend.
+    pub fn steer(&mut self, local: SocketAddrV4, remote: SocketAddrV4) -> Result<usize, Fail> {
+        if let Some(index) = self.connections.get(&(local, remote)) {
+            return Ok(*index);
+        }
+        let index: usize = match self.lookup(local, remote) {
+            Some(index) => index,
+            None => {
+                let cause: String = format!("no backend takes new connections");
+                error!("steer(): {}", cause);
+                return Err(Fail::new(libc::EHOSTUNREACH, &cause));
+            },
+        };
+        let backend: &mut Backend =
+            expect_some!(self.backends[index].as_mut(), "lookup table should hold backends only");
+        backend.connections += 1;
+        self.connections.insert((local, remote), index);
+        Ok(index)
+    }
+
+    /// Stops tracking the connection from [remote] to [local], and returns the index of the backend that it was steered
+    /// to. A draining backend is removed along with its last connection.
+    pub fn release(&mut self, local: SocketAddrV4, remote: SocketAddrV4) -> Option<usize> {
+        let index: usize = self.connections.remove(&(local, remote))?;
+        let backend: &mut Backend = expect_some!(self.backends[index].as_mut(), "tracked backends should exist");
+        backend.connections -= 1;
+        if backend.state == BackendState::Draining && backend.connections == 0 {
+            self.backends[index] = None;
+        }
+        Some(index)
+    }
+
+    /// Steers the connection from [remote] to [local], which [libos] accepted, and starts to connect a new socket to
+    /// the backend that takes it. Returns the index of the backend, the new socket and the queue token of the connect.
+    /// Once the connect completes, [Self::forward] joins both sockets.
+    pub fn connect(
+        &mut self,
+        libos: &mut LibOS,
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+    ) -> Result<(usize, QDesc, QToken), Fail> {
+        let index: usize = self.steer(local, remote)?;
+        let (addr, _): (SocketAddrV4, BackendState) = expect_some!(
+            self.get_backend(index),
+            "connections should be steered to backends that exist"
+        );
+        let result: Result<(QDesc, QToken), Fail> =
+            libos
+                .socket(Domain::IPV4.into(), Type::STREAM.into(), 0)
+                .and_then(|qd| match libos.connect(qd, SocketAddr::V4(addr)) {
+                    Ok(qt) => Ok((qd, qt)),
+                    Err(e) => {
+                        if let Err(e) = libos.close(qd) {
+                            warn!("connect(): failed to close socket (qd={:?}): {:?}", qd, e);
+                        }
+                        Err(e)
+                    },
+                });
+        match result {
+            Ok((qd, qt)) => Ok((index, qd, qt)),
+            Err(e) => {
+                self.release(local, remote);
+                Err(e)
+            },
+        }
+    }
+
+    /// Forwards everything that arrives on [client_qd] to [backend_qd] and the other way around, with one splice for
+    /// each direction. Returns the queue tokens of both splices: the first completes once the client closes its end of
+    /// the stream, the second once the backend does. The caller releases the connection after both completed.
+    pub fn forward(libos: &mut LibOS, client_qd: QDesc, backend_qd: QDesc) -> Result<(QToken, QToken), Fail> {
+        let upstream_qt: QToken = libos.splice(client_qd, backend_qd, None)?;
+        let downstream_qt: QToken = libos.splice(backend_qd, client_qd, None)?;
+        Ok((upstream_qt, downstream_qt))
+    }
+
+    fn get_backend_mut(&mut self, index: usize) -> Result<&mut Backend, Fail> {
+        match self.backends.get_mut(index) {
+            Some(Some(backend)) => Ok(backend),
+            _ => {
+                let cause: String = format!("no such backend (index={:?})", index);
+                error!("get_backend_mut(): {}", cause);
+                Err(Fail::new(libc::ENOENT, &cause))
+            },
+        }
+    }
+
+    // Looks up the backend that takes new connections from [remote] to [local], without tracking them.
+    fn lookup(&self, local: SocketAddrV4, remote: SocketAddrV4) -> Option<usize> {
+        self.table[hash(CONNECTION_SEED, &(local, remote)) % self.table.len()]
+    }
+
+    // Fills the lookup table with the backends that take new connections. Each backend takes turns claiming the next
+    // entry of its permutation that is still free, so all of them end up with the same share of the table, give or take
+    // one entry.
+    fn populate(&mut self) {
+        let table_size: usize = self.table.len();
+        self.table.fill(None);
+
+        // The permutation of each backend starts at an offset and moves on by a skip that is coprime with the size of
+        // the table, so it visits every entry.
+        let mut permutations: Vec<(usize, usize, usize)> = self
+            .backends
+            .iter()
+            .enumerate()
+            .filter_map(|(index, backend)| match backend {
+                Some(backend) if backend.state == BackendState::Active => Some((
+                    index,
+                    hash(OFFSET_SEED, &backend.addr) % table_size,
+                    hash(SKIP_SEED, &backend.addr) % (table_size - 1) + 1,
+                )),
+                _ => None,
+            })
+            .collect();
+        if permutations.is_empty() {
+            return;
+        }
+
+        let mut filled: usize = 0;
+        loop {
+            for (index, next, skip) in permutations.iter_mut() {
+                while self.table[*next].is_some() {
+                    *next = (*next + *skip) % table_size;
+                }
+                self.table[*next] = Some(*index);
+                filled += 1;
+                if filled == table_size {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+fn hash<T: Hash>(seed: u64, value: &T) -> usize {
+    let mut hasher: DefaultHasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    value.hash(&mut hasher);
+    hasher.finish() as usize
+}
+
+fn is_prime(n: usize) -> bool {
+    n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| n % d != 0)
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{BackendState, LoadBalancer, DEFAULT_TABLE_SIZE};
+    use ::anyhow::Result;
+    use ::std::net::{Ipv4Addr, SocketAddrV4};
+
+    const LOCAL: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 1), 80);
+
+    fn client(i: u16) -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, (i >> 8) as u8, i as u8), 49152 + i)
+    }
+
+    fn backend(i: u8) -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(192, 168, 2, i), 8080)
+    }
+
+    #[test]
+    fn test_balancer_spreads_connections() -> Result<()> {
+        let mut balancer: LoadBalancer = LoadBalancer::new(DEFAULT_TABLE_SIZE)?;
+        for i in 0..4 {
+            crate::ensure_eq!(balancer.add_backend(backend(i))?, i as usize);
+        }
+        crate::ensure_eq!(balancer.add_backend(backend(0)).is_err(), true);
+
+        for i in 0..4000 {
+            let index: usize = balancer.steer(LOCAL, client(i))?;
+            crate::ensure_eq!(balancer.steer(LOCAL, client(i))?, index);
+        }
+        // Every backend gets about a quarter of the connections.
+        for i in 0..4 {
+            let connections: usize = balancer.get_connections(i);
+            crate::ensure_eq!(
+                (800..1200).contains(&connections),
+                true,
+                "connections={:?}",
+                connections
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_balancer_moves_few_connections() -> Result<()> {
+        let mut balancer: LoadBalancer = LoadBalancer::new(DEFAULT_TABLE_SIZE)?;
+        for i in 0..4 {
+            balancer.add_backend(backend(i))?;
+        }
+        let before: Vec<Option<usize>> = (0..4000).map(|i| balancer.lookup(LOCAL, client(i))).collect();
+
+        // A new backend takes about a fifth of the connections, and hardly any others move.
+        let index: usize = balancer.add_backend(backend(4))?;
+        let mut moved: usize = 0;
+        let mut taken: usize = 0;
+        for i in 0..4000 {
+            let after: Option<usize> = balancer.lookup(LOCAL, client(i));
+            if after != before[i as usize] {
+                moved += 1;
+            }
+            if after == Some(index) {
+                taken += 1;
+            }
+        }
+        crate::ensure_eq!((600..1000).contains(&taken), true, "taken={:?}", taken);
+        crate::ensure_eq!(moved < taken + taken / 4, true, "moved={:?}, taken={:?}", moved, taken);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_balancer_tracks_connections() -> Result<()> {
+        let mut balancer: LoadBalancer = LoadBalancer::new(251)?;
+        balancer.add_backend(backend(0))?;
+        crate::ensure_eq!(balancer.steer(LOCAL, client(0))?, 0);
+
+        // A tracked connection stays on its backend, even if new connections would go elsewhere.
+        balancer.add_backend(backend(1))?;
+        balancer.drain_backend(0)?;
+        crate::ensure_eq!(balancer.get_backend(0), Some((backend(0), BackendState::Draining)));
+        crate::ensure_eq!(balancer.steer(LOCAL, client(0))?, 0);
+        crate::ensure_eq!(balancer.steer(LOCAL, client(1))?, 1);
+
+        // A draining backend goes away with its last connection.
+        crate::ensure_eq!(balancer.release(LOCAL, client(0)), Some(0));
+        crate::ensure_eq!(balancer.get_backend(0), None);
+        crate::ensure_eq!(balancer.release(LOCAL, client(0)), None);
+
+        // Removing a backend forgets its connections, and without backends there is nowhere to steer them.
+        crate::ensure_eq!(balancer.remove_backend(1)?, 1);
+        crate::ensure_eq!(balancer.steer(LOCAL, client(1)).is_err(), true);
+        crate::ensure_eq!(balancer.add_backend(backend(2))?, 0);
+        crate::ensure_eq!(balancer.steer(LOCAL, client(1))?, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_balancer_needs_prime_table_size() -> Result<()> {
+        crate::ensure_eq!(LoadBalancer::new(256).is_err(), true);
+        crate::ensure_eq!(LoadBalancer::new(1).is_err(), true);
+        crate::ensure_eq!(LoadBalancer::new(257).is_ok(), true);
+        Ok(())
+    }
+}
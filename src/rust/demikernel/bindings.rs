@@ -6,25 +6,37 @@
 //======================================================================================================================
 
 use crate::{
-    demikernel::libos::{name::LibOSName, LibOS},
+    demikernel::{
+        completion_ring::{self, CompletionConsumer, CompletionProducer},
+        libos::{name::LibOSName, LibOS},
+    },
     pal::{
         socketaddrv4_to_sockaddr, AddressFamily, Linger, SockAddrIn, SockAddrIn6, SockAddrStorage, Socklen, AF_INET,
-        AF_INET6, SOL_SOCKET, SO_LINGER,
+        AF_INET6, IPPROTO_IP, IPPROTO_TCP, MSG_PEEK, SHUT_RD, SHUT_RDWR, SHUT_WR, SOL_SOCKET, SO_ERROR, SO_KEEPALIVE,
+        SO_LINGER, SO_RCVLOWAT, TCP_KEEPCNT, TCP_KEEPIDLE, TCP_KEEPINTVL,
     },
     runtime::{
         fail::Fail,
         logging,
-        types::{demi_args_t, demi_callback_t, demi_qresult_t, demi_qtoken_t, demi_sgarray_t, demi_sgaseg_t},
+        types::{
+            demi_args_t, demi_callback_t, demi_linked_op_t, demi_qresult_t, demi_qtoken_t, demi_sgarray_t,
+            demi_sgaseg_t, demi_tcp_info_t, DEMI_SGARRAY_MAXLEN,
+        },
         QToken,
     },
-    SocketOption,
+    PopFlags, QuickAck, SocketOption,
+};
+#[cfg(target_os = "linux")]
+use crate::{
+    inetstack::protocols::layer4::tcp::congestion_control,
+    pal::{IP_TRANSPARENT, TCP_CONGESTION, TCP_CORK, TCP_INFO, TCP_QUICKACK, TCP_SYNCNT},
 };
 use ::libc::{c_int, c_void};
 use ::socket2::SockAddr;
 use ::std::{
     cell::RefCell,
     mem::{self, MaybeUninit},
-    net::{SocketAddr, SocketAddrV4},
+    net::{Shutdown, SocketAddr, SocketAddrV4},
     ptr, slice,
     time::Duration,
 };
@@ -34,6 +46,14 @@ thread_local! {
     static THREAD_LOCAL_LIBOS: RefCell<Option<LibOS>> = RefCell::new(None);
 }
 
+/// Completion ring as seen from C. The thread that runs the libOS only touches the producer and the application thread
+/// only touches the consumer, so the two sides are never borrowed through the same reference.
+#[allow(non_camel_case_types)]
+pub struct demi_completion_ring_t {
+    producer: CompletionProducer,
+    consumer: CompletionConsumer,
+}
+
 #[allow(unused)]
 #[no_mangle]
 pub extern "C" fn demi_init(args: *const demi_args_t) -> c_int {
@@ -253,6 +273,82 @@ pub extern "C" fn demi_close(qd: c_int) -> c_int {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn demi_async_close(qtok_out: *mut demi_qtoken_t, qd: c_int) -> c_int {
+    trace!("demi_async_close()");
+
+    // Check for invalid storage location.
+    if qtok_out.is_null() {
+        warn!("demi_async_close() qtok_out is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Issue close operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.async_close(qd.into()) {
+        Ok(qt) => {
+            unsafe { *qtok_out = qt.into() };
+            0
+        },
+        Err(e) => {
+            trace!("demi_async_close() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn demi_abort(qd: c_int) -> c_int {
+    trace!("demi_abort()");
+
+    // Issue abort operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.abort(qd.into()) {
+        Ok(..) => 0,
+        Err(e) => {
+            trace!("demi_abort() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn demi_shutdown(qd: c_int, how: c_int) -> c_int {
+    trace!("demi_shutdown()");
+
+    let how: Shutdown = match how {
+        SHUT_RD => Shutdown::Read,
+        SHUT_WR => Shutdown::Write,
+        SHUT_RDWR => Shutdown::Both,
+        _ => {
+            warn!("demi_shutdown(): invalid value for how (how={:?})", how);
+            return libc::EINVAL;
+        },
+    };
+
+    // Issue shutdown operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.shutdown(qd.into(), how) {
+        Ok(..) => 0,
+        Err(e) => {
+            trace!("demi_shutdown() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn demi_pushto(
     qtok_out: *mut demi_qtoken_t,
@@ -370,6 +466,114 @@ pub extern "C" fn demi_pop(qtok_out: *mut demi_qtoken_t, qd: c_int) -> c_int {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn demi_pop_flags(qtok_out: *mut demi_qtoken_t, qd: c_int, flags: c_int) -> c_int {
+    trace!("demi_pop_flags()");
+
+    // Check for invalid storage location.
+    if qtok_out.is_null() {
+        warn!("demi_pop_flags() qtok_out is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Check for flags that we do not support.
+    if flags & !MSG_PEEK != 0 {
+        warn!("demi_pop_flags(): only MSG_PEEK is supported (flags={:#x})", flags);
+        return libc::EINVAL;
+    }
+    let flags: PopFlags = PopFlags {
+        peek: flags & MSG_PEEK != 0,
+        ..Default::default()
+    };
+
+    // Issue pop operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.pop_with_flags(qd.into(), None, flags) {
+        Ok(qt) => {
+            unsafe { *qtok_out = qt.into() };
+            0
+        },
+        Err(e) => {
+            trace!("demi_pop_flags() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn demi_splice(qtok_out: *mut demi_qtoken_t, src_qd: c_int, dst_qd: c_int) -> c_int {
+    trace!("demi_splice()");
+
+    // Check for invalid storage location.
+    if qtok_out.is_null() {
+        warn!("demi_splice() qtok_out is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Issue splice operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.splice(src_qd.into(), dst_qd.into(), None) {
+        Ok(qt) => {
+            unsafe { *qtok_out = qt.into() };
+            0
+        },
+        Err(e) => {
+            trace!("demi_splice() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn demi_submit_linked(
+    qts_out: *mut demi_qtoken_t,
+    ops: *const demi_linked_op_t,
+    num_ops: c_int,
+) -> c_int {
+    trace!("demi_submit_linked() {:?} {:?} {:?}", qts_out, ops, num_ops);
+
+    // Check for invalid storage locations.
+    if qts_out.is_null() || ops.is_null() {
+        warn!("demi_submit_linked() qts_out or ops is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Check arguments.
+    if num_ops <= 0 {
+        return libc::EINVAL;
+    }
+
+    // Get operations.
+    let ops: &[demi_linked_op_t] = unsafe { slice::from_raw_parts(ops, num_ops as usize) };
+
+    // Issue linked operations.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.submit_linked(ops) {
+        Ok(qts) => {
+            for (i, qt) in qts.into_iter().enumerate() {
+                unsafe { *qts_out.add(i) = qt.into() };
+            }
+            0
+        },
+        Err(e) => {
+            trace!("demi_submit_linked() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn demi_wait(qr_out: *mut demi_qresult_t, qt: demi_qtoken_t, timeout: *const libc::timespec) -> c_int {
     trace!("demi_wait() {:?} {:?} {:?}", qr_out, qt, timeout);
@@ -525,6 +729,125 @@ pub extern "C" fn demi_wait_next_n(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn demi_completion_ring_create(
+    ring_out: *mut *mut demi_completion_ring_t,
+    capacity: libc::size_t,
+) -> c_int {
+    trace!("demi_completion_ring_create() {:?} {:?}", ring_out, capacity);
+
+    // Check for invalid storage location for the ring.
+    if ring_out.is_null() {
+        warn!("ring_out is a null pointer");
+        return libc::EINVAL;
+    }
+
+    match completion_ring::completion_ring(capacity) {
+        Ok((producer, consumer)) => {
+            let ring: Box<demi_completion_ring_t> = Box::new(demi_completion_ring_t { producer, consumer });
+            unsafe { *ring_out = Box::into_raw(ring) };
+            0
+        },
+        Err(e) => {
+            trace!("demi_completion_ring_create() failed: {:?}", e);
+            e.errno
+        },
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn demi_completion_ring_destroy(ring: *mut demi_completion_ring_t) -> c_int {
+    trace!("demi_completion_ring_destroy() {:?}", ring);
+
+    // Check for invalid ring.
+    if ring.is_null() {
+        warn!("ring is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Safety: We have to trust that our user passes a ring that demi_completion_ring_create() returned and that neither
+    // side uses anymore.
+    drop(unsafe { Box::from_raw(ring) });
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn demi_wait_into_ring(
+    ring: *mut demi_completion_ring_t,
+    qr_written: *mut c_int,
+    timeout: *const libc::timespec,
+) -> c_int {
+    trace!("demi_wait_into_ring() {:?} {:?} {:?}", ring, qr_written, timeout);
+
+    // Check for invalid ring.
+    if ring.is_null() {
+        warn!("ring is a null pointer");
+        return libc::EINVAL;
+    }
+
+    if qr_written.is_null() {
+        warn!("qr_written is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Convert timespec to Duration.
+    let duration: Option<Duration> = if timeout.is_null() {
+        None
+    } else {
+        // Safety: We have to trust that our user is providing a valid timeout pointer for us to dereference.
+        Some(unsafe { Duration::new((*timeout).tv_sec as u64, (*timeout).tv_nsec as u32) })
+    };
+
+    // Safety: Only the thread that runs the libOS uses the producer, while the consumer may be in use on another thread.
+    let producer: &mut CompletionProducer = unsafe { &mut (*ring).producer };
+    let mut handed_off: usize = 0;
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.wait_into_ring(producer, duration) {
+        Ok(count) => {
+            handed_off = count;
+            0
+        },
+        Err(e) => {
+            trace!("demi_wait_into_ring() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    unsafe { *qr_written = handed_off as c_int };
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn demi_completion_ring_pop(qr_out: *mut demi_qresult_t, ring: *mut demi_completion_ring_t) -> c_int {
+    // No trace here, as the application thread may spin on an empty ring.
+
+    // Check for invalid storage location for queue result.
+    if qr_out.is_null() {
+        warn!("qr_out is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Check for invalid ring.
+    if ring.is_null() {
+        warn!("ring is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Safety: Only one application thread uses the consumer, while the producer may be in use on the libOS thread. This
+    // does not touch the libOS, so it works on any thread.
+    let consumer: &mut CompletionConsumer = unsafe { &mut (*ring).consumer };
+    match consumer.try_pop() {
+        Some(qr) => {
+            unsafe { *qr_out = qr };
+            0
+        },
+        None => libc::EAGAIN,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn demi_sgaalloc(size: libc::size_t) -> demi_sgarray_t {
     trace!("demi_sgaalloc()");
@@ -536,7 +859,7 @@ pub extern "C" fn demi_sgaalloc(size: libc::size_t) -> demi_sgarray_t {
             sga_segs: [demi_sgaseg_t {
                 sgaseg_buf: ptr::null_mut() as *mut c_void,
                 sgaseg_len: 0,
-            }; 1],
+            }; DEMI_SGARRAY_MAXLEN],
             sga_addr: unsafe { mem::zeroed() },
         }
     };
@@ -585,6 +908,30 @@ pub extern "C" fn demi_sgafree(sga: *mut demi_sgarray_t) -> c_int {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn demi_sgaappend(sga: *mut demi_sgarray_t, tail: *mut demi_sgarray_t) -> c_int {
+    trace!("demi_sgaappend()");
+
+    // Check if scatter-gather arrays are invalid.
+    if sga.is_null() || tail.is_null() {
+        return libc::EINVAL;
+    }
+
+    // Issue sgaappend operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| match libos.sgaappend(unsafe { &mut *sga }, unsafe { *tail }) {
+        Ok(()) => 0,
+        Err(e) => {
+            trace!("demi_sgaappend() failed: {:?}", e);
+            e.errno
+        },
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
 #[allow(unused)]
 #[no_mangle]
 pub extern "C" fn demi_getsockname(qd: c_int, saddr: *mut sockaddr, size: *mut Socklen) -> c_int {
@@ -604,13 +951,13 @@ pub extern "C" fn demi_setsockopt(
     trace!("demi_setsockopt()");
 
     // Check inputs.
-    if level != SOL_SOCKET {
-        error!("demi_setsockopt(): only options in SOL_SOCKET level are supported");
+    if level != SOL_SOCKET && level != IPPROTO_IP && level != IPPROTO_TCP {
+        error!("demi_setsockopt(): only options in SOL_SOCKET, IPPROTO_IP and IPPROTO_TCP levels are supported");
         return libc::ENOTSUP;
     }
 
-    let opt: SocketOption = match optname {
-        SO_LINGER => {
+    let opt: SocketOption = match (level, optname) {
+        (SOL_SOCKET, SO_LINGER) => {
             // Check for invalid storage locations.
             if optval.is_null() {
                 error!("demi_setsockopt(): linger value is a null pointer");
@@ -628,8 +975,89 @@ pub extern "C" fn demi_setsockopt(
                 _ => SocketOption::Linger(Some(Duration::from_secs(linger.l_linger as u64))),
             }
         },
+        // On Windows, the keepalive option also carries the keepalive timers, so it does not fit into an integer.
+        #[cfg(target_os = "linux")]
+        (SOL_SOCKET, SO_KEEPALIVE) => match read_int_option(optval, optlen) {
+            Ok(value) => SocketOption::KeepAlive(value != 0),
+            Err(errno) => return errno,
+        },
+        (IPPROTO_TCP, TCP_KEEPIDLE) | (IPPROTO_TCP, TCP_KEEPINTVL) | (IPPROTO_TCP, TCP_KEEPCNT) => {
+            let value: c_int = match read_int_option(optval, optlen) {
+                Ok(value) if value > 0 => value,
+                Ok(_) => {
+                    warn!("demi_setsockopt(): keepalive timers and probe count must be positive");
+                    return libc::EINVAL;
+                },
+                Err(errno) => return errno,
+            };
+            match optname {
+                TCP_KEEPIDLE => SocketOption::KeepAliveIdle(Duration::from_secs(value as u64)),
+                TCP_KEEPINTVL => SocketOption::KeepAliveInterval(Duration::from_secs(value as u64)),
+                _ => SocketOption::KeepAliveCount(value as u32),
+            }
+        },
+        // Winsock has no counterpart of this option.
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_QUICKACK) => match read_int_option(optval, optlen) {
+            Ok(0) => SocketOption::QuickAck(QuickAck::Off),
+            Ok(_) => SocketOption::QuickAck(QuickAck::Always),
+            Err(errno) => return errno,
+        },
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_CORK) => match read_int_option(optval, optlen) {
+            Ok(value) => SocketOption::Cork(value != 0),
+            Err(errno) => return errno,
+        },
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_SYNCNT) => match read_int_option(optval, optlen) {
+            Ok(value) if value > 0 => SocketOption::SynRetries(value as u32),
+            Ok(_) => {
+                warn!("demi_setsockopt(): number of SYN retransmissions must be positive");
+                return libc::EINVAL;
+            },
+            Err(errno) => return errno,
+        },
+        #[cfg(target_os = "linux")]
+        (IPPROTO_IP, IP_TRANSPARENT) => match read_int_option(optval, optlen) {
+            Ok(value) => SocketOption::Transparent(value != 0),
+            Err(errno) => return errno,
+        },
+        (SOL_SOCKET, SO_RCVLOWAT) => match read_int_option(optval, optlen) {
+            Ok(value) if value >= 0 => SocketOption::ReceiveLowWatermark(value as usize),
+            Ok(_) => {
+                warn!("demi_setsockopt(): receive low watermark must not be negative");
+                return libc::EINVAL;
+            },
+            Err(errno) => return errno,
+        },
+        // Like on Linux, the name of the algorithm does not need to be null-terminated.
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_CONGESTION) => {
+            if optval.is_null() {
+                error!("demi_setsockopt(): congestion control name is a null pointer");
+                return libc::EINVAL;
+            }
+            let bytes: &[u8] = unsafe { slice::from_raw_parts(optval as *const u8, optlen as usize) };
+            let name: &[u8] = match bytes.iter().position(|byte| *byte == 0) {
+                Some(len) => &bytes[..len],
+                None => bytes,
+            };
+            match ::std::str::from_utf8(name)
+                .ok()
+                .and_then(congestion_control::get_algorithm)
+            {
+                Some((name, _)) => SocketOption::CongestionControl(name),
+                None => {
+                    warn!("demi_setsockopt(): unknown congestion control algorithm");
+                    return libc::ENOENT;
+                },
+            }
+        },
         _ => {
-            error!("demi_setsockopt(): only SO_LINGER is supported right now");
+            error!(
+                "demi_setsockopt(): only SO_LINGER, SO_KEEPALIVE, SO_RCVLOWAT, IP_TRANSPARENT, TCP_CONGESTION, TCP_CORK, \
+                 TCP_QUICKACK, TCP_SYNCNT and TCP keepalive options are supported"
+            );
             return libc::ENOPROTOOPT;
         },
     };
@@ -663,15 +1091,38 @@ pub extern "C" fn demi_getsockopt(
     trace!("demi_getsockopt()");
 
     // Check inputs.
-    if level != SOL_SOCKET {
-        error!("demi_getsockopt(): only options in SOL_SOCKET level are supported");
+    if level != SOL_SOCKET && level != IPPROTO_IP && level != IPPROTO_TCP {
+        error!("demi_getsockopt(): only options in SOL_SOCKET, IPPROTO_IP and IPPROTO_TCP levels are supported");
         return libc::ENOTSUP;
     }
 
-    let opt: SocketOption = match optname {
-        SO_LINGER => SocketOption::Linger(None),
+    let opt: SocketOption = match (level, optname) {
+        // The pending error is not a setting of the socket, so it is not a socket option either.
+        (SOL_SOCKET, SO_ERROR) => return get_last_error(qd, optval, optlen),
+        (SOL_SOCKET, SO_LINGER) => SocketOption::Linger(None),
+        #[cfg(target_os = "linux")]
+        (SOL_SOCKET, SO_KEEPALIVE) => SocketOption::KeepAlive(false),
+        (IPPROTO_TCP, TCP_KEEPIDLE) => SocketOption::KeepAliveIdle(Duration::ZERO),
+        (IPPROTO_TCP, TCP_KEEPINTVL) => SocketOption::KeepAliveInterval(Duration::ZERO),
+        (IPPROTO_TCP, TCP_KEEPCNT) => SocketOption::KeepAliveCount(0),
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_QUICKACK) => SocketOption::QuickAck(QuickAck::Off),
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_CORK) => SocketOption::Cork(false),
+        (SOL_SOCKET, SO_RCVLOWAT) => SocketOption::ReceiveLowWatermark(0),
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_INFO) => SocketOption::TcpInfo(Default::default()),
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_CONGESTION) => SocketOption::CongestionControl(""),
+        #[cfg(target_os = "linux")]
+        (IPPROTO_TCP, TCP_SYNCNT) => SocketOption::SynRetries(0),
+        #[cfg(target_os = "linux")]
+        (IPPROTO_IP, IP_TRANSPARENT) => SocketOption::Transparent(false),
         _ => {
-            error!("demi_getsockopt(): only SO_LINGER is supported right now");
+            error!(
+                "demi_getsockopt(): only SO_ERROR, SO_LINGER, SO_KEEPALIVE, SO_RCVLOWAT, IP_TRANSPARENT, TCP_CONGESTION, \
+                 TCP_CORK, TCP_INFO, TCP_QUICKACK, TCP_SYNCNT and TCP keepalive options are supported"
+            );
             return libc::ENOPROTOOPT;
         },
     };
@@ -698,7 +1149,7 @@ pub extern "C" fn demi_getsockopt(
 
     match ret {
         Ok(option) => {
-            // Unpack the value based on the option. We only support linger right now.
+            // Unpack the value based on the option.
             match option {
                 SocketOption::Linger(linger) => {
                     let result: Linger = match linger {
@@ -722,8 +1173,54 @@ pub extern "C" fn demi_getsockopt(
                         *optlen = result_length as Socklen;
                     }
                 },
+                #[cfg(target_os = "linux")]
+                SocketOption::KeepAlive(keep_alive) => write_int_option(optval, optlen, keep_alive as c_int),
+                SocketOption::KeepAliveIdle(idle) => write_int_option(optval, optlen, idle.as_secs() as c_int),
+                SocketOption::KeepAliveInterval(interval) => {
+                    write_int_option(optval, optlen, interval.as_secs() as c_int)
+                },
+                SocketOption::KeepAliveCount(count) => write_int_option(optval, optlen, count as c_int),
+                // The C interface only tells whether quick ACKs are on, like the option of Linux.
+                SocketOption::QuickAck(quick_ack) => {
+                    write_int_option(optval, optlen, (quick_ack != QuickAck::Off) as c_int)
+                },
+                SocketOption::ReceiveLowWatermark(bytes) => {
+                    write_int_option(optval, optlen, bytes.min(c_int::MAX as usize) as c_int)
+                },
+                SocketOption::Cork(cork) => write_int_option(optval, optlen, cork as c_int),
+                SocketOption::SynRetries(retries) => write_int_option(optval, optlen, retries as c_int),
+                SocketOption::Transparent(transparent) => write_int_option(optval, optlen, transparent as c_int),
+                SocketOption::TcpInfo(info) => {
+                    let result: demi_tcp_info_t = demi_tcp_info_t::from(info);
+                    let result_length: usize = mem::size_of::<demi_tcp_info_t>();
+                    if unsafe { (*optlen as usize) < result_length } {
+                        warn!("demi_getsockopt(): option len is too small for TCP_INFO");
+                        return libc::EINVAL;
+                    }
+                    unsafe {
+                        ptr::copy(
+                            &result as *const demi_tcp_info_t as *const c_void,
+                            optval,
+                            result_length,
+                        );
+                        *optlen = result_length as Socklen;
+                    }
+                },
+                // Like on Linux, the name is cut off if it does not fit, and null-terminated if it does.
+                SocketOption::CongestionControl(name) => {
+                    let result_length: usize = unsafe { (*optlen as usize).min(name.len() + 1) };
+                    unsafe {
+                        let result: &mut [u8] = slice::from_raw_parts_mut(optval as *mut u8, result_length);
+                        let name_length: usize = result_length.min(name.len());
+                        result[..name_length].copy_from_slice(&name.as_bytes()[..name_length]);
+                        if result_length > name.len() {
+                            result[name.len()] = 0;
+                        }
+                        *optlen = result_length as Socklen;
+                    }
+                },
                 _ => {
-                    let cause: String = format!("Only SO_LINGER is supported right now");
+                    let cause: String = format!("unexpected socket option {:?}", option);
                     error!("demi_setsockopt(): {}", cause);
                     return libc::EINVAL;
                 },
@@ -792,6 +1289,66 @@ pub extern "C" fn demi_getpeername(qd: c_int, addr: *mut SockAddr, addrlen: *mut
     }
 }
 
+#[no_mangle]
+pub extern "C" fn demi_has_pending_connections(sockqd: c_int, pending_out: *mut c_int) -> c_int {
+    trace!("demi_has_pending_connections()");
+
+    // Check for invalid storage location.
+    if pending_out.is_null() {
+        warn!("demi_has_pending_connections() pending_out is a null pointer");
+        return libc::EINVAL;
+    }
+
+    let ret: Result<bool, Fail> = match do_syscall(|libos| libos.has_pending_connections(sockqd.into())) {
+        Ok(result) => result,
+        Err(e) => {
+            trace!("demi_has_pending_connections() failed: {:?}", e);
+            return e.errno;
+        },
+    };
+
+    match ret {
+        Ok(pending) => {
+            unsafe { *pending_out = pending as c_int };
+            0
+        },
+        Err(e) => {
+            trace!("demi_has_pending_connections() failed: {:?}", e);
+            e.errno
+        },
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn demi_writable(qtok_out: *mut demi_qtoken_t, sockqd: c_int, min_bytes: usize) -> c_int {
+    trace!("demi_writable()");
+
+    // Check for invalid storage location.
+    if qtok_out.is_null() {
+        warn!("demi_writable() qtok_out is a null pointer");
+        return libc::EINVAL;
+    }
+
+    // Issue writable operation.
+    let ret: Result<i32, Fail> = do_syscall(|libos| {
+        unsafe {
+            *qtok_out = match libos.writable(sockqd.into(), min_bytes) {
+                Ok(qt) => qt.into(),
+                Err(e) => {
+                    trace!("demi_writable() failed: {:?}", e);
+                    return e.errno;
+                },
+            }
+        };
+        0
+    });
+
+    match ret {
+        Ok(ret) => ret,
+        Err(e) => e.errno,
+    }
+}
+
 //======================================================================================================================
 // Standalone Functions
 //======================================================================================================================
@@ -843,6 +1400,46 @@ fn sockaddr_to_socketaddr(saddr: *const sockaddr, size: Socklen) -> Result<Socke
     }
 }
 
+/// Reads the value of an integer socket option. On failure, returns the error code to hand back to the caller.
+fn read_int_option(optval: *const c_void, optlen: Socklen) -> Result<c_int, c_int> {
+    if optval.is_null() {
+        error!("read_int_option(): option value is a null pointer");
+        return Err(libc::EINVAL);
+    }
+    if (optlen as usize) < mem::size_of::<c_int>() {
+        warn!("read_int_option(): option len is incorrect");
+        return Err(libc::EINVAL);
+    }
+    Ok(unsafe { *(optval as *const c_int) })
+}
+
+/// Writes the value of an integer socket option.
+fn write_int_option(optval: *mut c_void, optlen: *mut Socklen, value: c_int) {
+    let result_length: usize = mem::size_of::<c_int>();
+    unsafe {
+        ptr::copy(&value as *const c_int as *const c_void, optval, result_length);
+        *optlen = result_length as Socklen;
+    }
+}
+
+/// Reads and clears the most recent error of the socket `qd` for SO_ERROR. The value is zero if there was none.
+fn get_last_error(qd: c_int, optval: *mut c_void, optlen: *mut Socklen) -> c_int {
+    if optval.is_null() || optlen.is_null() {
+        warn!("get_last_error(): option value or len is a null pointer");
+        return libc::EINVAL;
+    }
+    match do_syscall(|libos| libos.get_last_error(qd.into())) {
+        Ok(Ok(error)) => {
+            write_int_option(optval, optlen, error.map_or(0, |e| e.errno));
+            0
+        },
+        Ok(Err(e)) | Err(e) => {
+            trace!("demi_getsockopt(): {:?}", e);
+            e.errno
+        },
+    }
+}
+
 //======================================================================================================================
 // Unit Tests
 //======================================================================================================================
@@ -858,7 +1455,9 @@ mod test {
     use crate::{
         demikernel::bindings::{demi_getsockopt, demi_init, demi_setsockopt, demi_socket, sockaddr_to_socketaddr},
         ensure_eq, ensure_neq,
-        pal::{AddressFamily, Linger, SockAddrStorage, Socklen, AF_INET, SOL_SOCKET, SO_LINGER},
+        pal::{
+            AddressFamily, Linger, SockAddrStorage, Socklen, AF_INET, IPPROTO_TCP, SOL_SOCKET, SO_LINGER, TCP_KEEPIDLE,
+        },
     };
 
     #[test]
@@ -1023,4 +1622,64 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_set_and_get_keepalive_idle() -> anyhow::Result<()> {
+        // Initialize Demikernel
+
+        use crate::runtime::types::demi_args_t;
+        let args: demi_args_t = demi_args_t::default();
+        let result: c_int = demi_init(&args);
+        ensure_eq!(result, 0);
+
+        let mut qd: c_int = 0;
+        let result: c_int = demi_socket(
+            &mut qd as *mut c_int,
+            Domain::IPV4.into(),
+            Type::STREAM.into(),
+            Protocol::TCP.into(),
+        );
+
+        ensure_eq!(result, 0);
+        ensure_neq!(qd, 0);
+
+        // Zero is not a valid idle time.
+        let idle: c_int = 0;
+        let result: c_int = demi_setsockopt(
+            qd,
+            IPPROTO_TCP,
+            TCP_KEEPIDLE,
+            &idle as *const c_int as *const c_void,
+            mem::size_of::<c_int>() as Socklen,
+        );
+        ensure_eq!(result, libc::EINVAL);
+
+        // Set the idle time.
+        let idle: c_int = 60;
+        let result: c_int = demi_setsockopt(
+            qd,
+            IPPROTO_TCP,
+            TCP_KEEPIDLE,
+            &idle as *const c_int as *const c_void,
+            mem::size_of::<c_int>() as Socklen,
+        );
+        ensure_eq!(result, 0);
+
+        // Check idle time.
+        let mut idle_check: c_int = 0;
+        let mut idle_check_len: usize = 0;
+        let result: c_int = demi_getsockopt(
+            qd,
+            IPPROTO_TCP,
+            TCP_KEEPIDLE,
+            &mut idle_check as *mut c_int as *mut c_void,
+            &mut idle_check_len as *mut usize as *mut Socklen,
+        );
+
+        ensure_eq!(result, 0);
+        ensure_eq!(idle_check_len, mem::size_of::<c_int>());
+        ensure_eq!(idle_check, 60);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,239 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Core-to-core handoff of completions. The thread that runs the libOS polls the network and pushes the results of
+//! completed operations into a single-producer single-consumer ring, from which an application thread on another core
+//! picks them up. This keeps application compute and network polling on separate cores, without sharing the libOS
+//! between threads.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::{fail::Fail, limits, types::demi_qresult_t};
+use ::std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Index that only one side of the ring writes to. It sits on a cache line of its own, so that the two cores do not
+/// keep taking the line away from each other.
+#[repr(align(64))]
+struct PaddedIndex(AtomicUsize);
+
+/// Ring that is shared between the producer and the consumer.
+struct CompletionRing {
+    // Slots for the completions. The producer only writes to the free slots and the consumer only reads from the ready
+    // ones, so they never touch the same slot at the same time.
+    slots: Box<[UnsafeCell<MaybeUninit<demi_qresult_t>>]>,
+    // Pre-computed capacity mask for the slots.
+    mask: usize,
+    // Index of the next completion to pop, which only increases.
+    head: PaddedIndex,
+    // Index of the next completion to push, which only increases.
+    tail: PaddedIndex,
+}
+
+/// Producer side of a completion ring, which belongs to the thread that runs the libOS.
+pub struct CompletionProducer {
+    ring: Arc<CompletionRing>,
+    // Index of the next completion to push.
+    tail: usize,
+    // Head index as of the last time that we looked at it, so that we only touch the consumer's cache line once the
+    // ring looks full.
+    cached_head: usize,
+}
+
+/// Consumer side of a completion ring, which belongs to an application thread.
+pub struct CompletionConsumer {
+    ring: Arc<CompletionRing>,
+    // Index of the next completion to pop.
+    head: usize,
+    // Tail index as of the last time that we looked at it, so that we only touch the producer's cache line once the
+    // ring looks empty.
+    cached_tail: usize,
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Creates a completion ring that holds up to [capacity] completions, which has to be a power of two.
+pub fn completion_ring(capacity: usize) -> Result<(CompletionProducer, CompletionConsumer), Fail> {
+    if !capacity.is_power_of_two() || capacity > limits::COMPLETION_RING_CAPACITY_MAX {
+        let cause: String = format!("invalid capacity (capacity={:?})", capacity);
+        error!("completion_ring(): {}", cause);
+        return Err(Fail::new(libc::EINVAL, &cause));
+    }
+
+    let ring: Arc<CompletionRing> = Arc::new(CompletionRing {
+        slots: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+        mask: capacity - 1,
+        head: PaddedIndex(AtomicUsize::new(0)),
+        tail: PaddedIndex(AtomicUsize::new(0)),
+    });
+    let producer: CompletionProducer = CompletionProducer {
+        ring: ring.clone(),
+        tail: 0,
+        cached_head: 0,
+    };
+    let consumer: CompletionConsumer = CompletionConsumer {
+        ring,
+        head: 0,
+        cached_tail: 0,
+    };
+    Ok((producer, consumer))
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl CompletionRing {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+impl CompletionProducer {
+    /// Returns the number of completions that can be pushed without the ring running full. This number only grows
+    /// until the next push, as the consumer only frees up slots.
+    pub fn get_free_count(&mut self) -> usize {
+        self.cached_head = self.ring.head.0.load(Ordering::Acquire);
+        self.ring.capacity() - self.tail.wrapping_sub(self.cached_head)
+    }
+
+    /// Pushes [qr] into the ring. If the ring is full, [qr] is handed back.
+    pub fn try_push(&mut self, qr: demi_qresult_t) -> Result<(), demi_qresult_t> {
+        if self.tail.wrapping_sub(self.cached_head) == self.ring.capacity() && self.get_free_count() == 0 {
+            return Err(qr);
+        }
+
+        // Safety: The slot is free, so the consumer does not read from it until we publish the new tail below.
+        unsafe { (*self.ring.slots[self.tail & self.ring.mask].get()).write(qr) };
+        self.tail = self.tail.wrapping_add(1);
+        self.ring.tail.0.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl CompletionConsumer {
+    /// Pops the oldest completion from the ring, if there is any.
+    pub fn try_pop(&mut self) -> Option<demi_qresult_t> {
+        if self.head == self.cached_tail {
+            self.cached_tail = self.ring.tail.0.load(Ordering::Acquire);
+            if self.head == self.cached_tail {
+                return None;
+            }
+        }
+
+        // Safety: The slot is ready, so the producer wrote to it before it published the tail that we loaded above, and
+        // it does not write to it again until we publish the new head below.
+        let qr: demi_qresult_t = unsafe { (*self.ring.slots[self.head & self.ring.mask].get()).assume_init_read() };
+        self.head = self.head.wrapping_add(1);
+        self.ring.head.0.store(self.head, Ordering::Release);
+        Some(qr)
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+/// Send trait implementation. Completions only carry pointers to buffers that the application owns once it picked them
+/// up, so they can move to the application thread.
+unsafe impl Send for CompletionRing {}
+
+/// Sync trait implementation. Only one producer and one consumer exist for a ring, and they never access the same slot
+/// at the same time.
+unsafe impl Sync for CompletionRing {}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{completion_ring, CompletionConsumer, CompletionProducer};
+    use crate::runtime::types::{demi_opcode_t, demi_qresult_t};
+    use ::anyhow::Result;
+    use ::std::{mem, thread};
+
+    fn make_result(qt: u64) -> demi_qresult_t {
+        // Safety: All-zero bytes are a valid result.
+        let mut qr: demi_qresult_t = unsafe { mem::zeroed() };
+        qr.qr_opcode = demi_opcode_t::DEMI_OPC_POP;
+        qr.qr_qt = qt;
+        qr
+    }
+
+    #[test]
+    fn test_push_and_pop() -> Result<()> {
+        let (mut producer, mut consumer): (CompletionProducer, CompletionConsumer) = completion_ring(4)?;
+        crate::ensure_eq!(consumer.try_pop().is_none(), true);
+
+        for qt in 0..4 {
+            crate::ensure_eq!(producer.try_push(make_result(qt)).is_ok(), true);
+        }
+        // The full ring hands the completion back.
+        match producer.try_push(make_result(4)) {
+            Err(qr) => crate::ensure_eq!(qr.qr_qt, 4),
+            Ok(()) => anyhow::bail!("push into a full ring should fail"),
+        }
+
+        // Completions come out in order, and popping one frees up its slot.
+        crate::ensure_eq!(consumer.try_pop().map(|qr| qr.qr_qt), Some(0));
+        crate::ensure_eq!(producer.get_free_count(), 1);
+        crate::ensure_eq!(producer.try_push(make_result(4)).is_ok(), true);
+        for qt in 1..5 {
+            crate::ensure_eq!(consumer.try_pop().map(|qr| qr.qr_qt), Some(qt));
+        }
+        crate::ensure_eq!(consumer.try_pop().is_none(), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_capacity() -> Result<()> {
+        crate::ensure_eq!(completion_ring(0).is_err(), true);
+        crate::ensure_eq!(completion_ring(3).is_err(), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_handoff_between_threads() -> Result<()> {
+        const NUM_COMPLETIONS: u64 = 10_000;
+        let (mut producer, mut consumer): (CompletionProducer, CompletionConsumer) = completion_ring(64)?;
+
+        let consumer_thread = thread::spawn(move || -> u64 {
+            let mut next_qt: u64 = 0;
+            while next_qt < NUM_COMPLETIONS {
+                if let Some(qr) = consumer.try_pop() {
+                    assert_eq!(qr.qr_qt, next_qt);
+                    next_qt += 1;
+                }
+            }
+            next_qt
+        });
+
+        for qt in 0..NUM_COMPLETIONS {
+            let mut qr: demi_qresult_t = make_result(qt);
+            while let Err(rejected) = producer.try_push(qr) {
+                qr = rejected;
+            }
+        }
+
+        match consumer_thread.join() {
+            Ok(received) => crate::ensure_eq!(received, NUM_COMPLETIONS),
+            Err(_) => anyhow::bail!("consumer thread panicked"),
+        }
+        Ok(())
+    }
+}
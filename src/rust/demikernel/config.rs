@@ -5,6 +5,8 @@
 // Imports
 //======================================================================================================================
 
+#[cfg(feature = "fault-injection")]
+use crate::runtime::fault::FaultConfig;
 use crate::{pal::KeepAlive, runtime::fail::Fail, MacAddress};
 #[cfg(any(feature = "catnip-libos"))]
 use ::std::ffi::CString;
@@ -23,6 +25,9 @@ mod global_config {
     pub const LOCAL_IPV4_ADDR: &str = "local_ipv4_addr";
     // Local MAC address.
     pub const LOCAL_LINK_ADDR: &str = "local_link_addr";
+    pub const CONTAIN_PANICS: &str = "contain_panics";
+    // Path of the UNIX socket of the control server.
+    pub const CONTROL_SOCKET: &str = "control_socket";
 }
 
 // These apply to all LibOSes.
@@ -40,11 +45,49 @@ mod inetstack_config {
     pub const ARP_CACHE_TTL: &str = "arp_cache_ttl";
     pub const ARP_REQUEST_TIMEOUT: &str = "arp_request_timeout";
     pub const ARP_REQUEST_RETRIES: &str = "arp_request_retries";
+    pub const ARP_PROBE_COUNT: &str = "arp_probe_count";
     pub const MTU: &str = "mtu";
     pub const MSS: &str = "mss";
     pub const ENABLE_JUMBO_FRAMES: &str = "enable_jumbo_frames";
     pub const UDP_CHECKSUM_OFFLOAD: &str = "udp_checksum_offload";
     pub const TCP_CHECKSUM_OFFLOAD: &str = "tcp_checksum_offload";
+    pub const RX_CHECKSUM_SAMPLE_INTERVAL: &str = "rx_checksum_sample_interval";
+    pub const TCP_CONGESTION_CONTROL: &str = "tcp_congestion_control";
+    pub const TCP_INITIAL_CONGESTION_WINDOW: &str = "tcp_initial_congestion_window";
+    pub const TCP_ACK_STRETCH_SEGMENTS: &str = "tcp_ack_stretch_segments";
+    pub const TCP_LEARN_REMOTE_LINK_ADDR: &str = "tcp_learn_remote_link_addr";
+    pub const TCP_TIMESTAMP_RTT_SAMPLES: &str = "tcp_timestamp_rtt_samples";
+    pub const TCP_ECN: &str = "tcp_ecn";
+    pub const TCP_MAX_RECEIVE_BUFFER_SIZE: &str = "tcp_max_receive_buffer_size";
+    pub const TCP_SEND_BUFFER_SIZE: &str = "tcp_send_buffer_size";
+    pub const TCP_MAX_SEND_BUFFER_SIZE: &str = "tcp_max_send_buffer_size";
+    pub const TCP_PMTU_BLACKHOLE_DETECTION: &str = "tcp_pmtu_blackhole_detection";
+    pub const TCP_PACING: &str = "tcp_pacing";
+    pub const TCP_MAX_ACCEPTS_PER_POLL: &str = "tcp_max_accepts_per_poll";
+    pub const TCP_SYN_COOKIES: &str = "tcp_syn_cookies";
+    pub const TCP_MAX_SYN_BACKLOG: &str = "tcp_max_syn_backlog";
+    pub const TCP_BACKLOG_OVERFLOW: &str = "tcp_backlog_overflow";
+    pub const TCP_MAX_TIME_WAIT: &str = "tcp_max_time_wait";
+    pub const TCP_MSL_MS: &str = "tcp_msl_ms";
+    pub const TCP_FIN_TIMEOUT_MS: &str = "tcp_fin_timeout_ms";
+    pub const TCP_TIME_WAIT_RECYCLE: &str = "tcp_time_wait_recycle";
+    pub const TCP_URGENT_DATA: &str = "tcp_urgent_data";
+    // Foreign IPv4 prefixes that we accept TCP connections for, as transparent proxies do.
+    pub const TRANSPARENT_PREFIXES: &str = "transparent_prefixes";
+}
+
+// Fault injection options. These only apply to builds with the fault-injection feature.
+#[cfg(feature = "fault-injection")]
+mod fault_injection_config {
+    pub const SECTION_NAME: &str = "fault_injection";
+    pub const SEED: &str = "seed";
+    // Subsections for the modules that faults can be injected into.
+    pub const MEMORY: &str = "memory";
+    pub const NETWORK: &str = "network";
+    pub const SCHEDULER: &str = "scheduler";
+    pub const FAILURE_PROBABILITY: &str = "failure_probability";
+    pub const DELAY_PROBABILITY: &str = "delay_probability";
+    pub const DELAY_US: &str = "delay_us";
 }
 
 // DPDK options. These only apply to catnip.
@@ -52,6 +95,10 @@ mod inetstack_config {
 mod dpdk_config {
     pub const SECTION_NAME: &str = "dpdk";
     pub const EAL_INIT_ARGS: &str = "eal_init";
+    // Whether to give each priority class a transmit queue of its own.
+    pub const TX_PRIORITY_QUEUES: &str = "tx_priority_queues";
+    // Whether to export counters and packet capture to diagnostics tools that run as a secondary process.
+    pub const DIAGNOSTICS: &str = "diagnostics";
 }
 
 // Raw socket option. This only applies to catpowder.
@@ -61,6 +108,10 @@ mod raw_socket_config {
     #[cfg(target_os = "linux")]
     pub const LOCAL_INTERFACE_NAME: &str = "linux_interface_name";
 
+    // Number of workers to spread incoming frames across, for NICs that do not support receive-side scaling.
+    #[cfg(target_os = "linux")]
+    pub const RSS_QUEUE_COUNT: &str = "rss_queue_count";
+
     // The primary interface index. This should be the virtualized interface for VMs.
     #[cfg(target_os = "windows")]
     pub const LOCAL_INTERFACE_INDEX: &str = "xdp_interface_index";
@@ -158,6 +209,28 @@ impl Config {
         }
     }
 
+    /// Global config: Reads whether a panic in a coroutine should only fail its operation, instead of taking the whole
+    /// process down, from the environment variable and then the underlying configuration file.
+    pub fn contain_panics(&self) -> Result<bool, Fail> {
+        if let Some(contain) = Self::get_typed_env_option(global_config::CONTAIN_PANICS)? {
+            Ok(contain)
+        } else {
+            Self::get_bool_option(self.get_global_config()?, global_config::CONTAIN_PANICS)
+        }
+    }
+
+    /// Global config: Reads the path of the UNIX socket on which the control server listens from the environment
+    /// variable and then the underlying configuration file.
+    pub fn control_socket(&self) -> Result<String, Fail> {
+        if let Some(path) = Self::get_typed_env_option(global_config::CONTROL_SOCKET)? {
+            Ok(path)
+        } else {
+            Self::get_typed_str_option(self.get_global_config()?, global_config::CONTROL_SOCKET, |val: &str| {
+                Some(val.to_string())
+            })
+        }
+    }
+
     /// Tcp socket option: Reads TCP keepalive settings as a `tcp_keepalive` structure from "tcp_keepalive" subsection.
     pub fn tcp_keepalive(&self) -> Result<KeepAlive, Fail> {
         let section: &Yaml = Self::get_subsection(self.get_tcp_socket_options()?, tcp_socket_options::KEEP_ALIVE)?;
@@ -244,6 +317,37 @@ impl Config {
         Ok(None)
     }
 
+    /// Inetstack config: Reads the foreign IPv4 prefixes, such as "10.0.0.0/8", that transparent TCP sockets accept
+    /// connections for from the underlying configuration file. If the list is not present, then no foreign addresses
+    /// are accepted. This cannot be passed in as an environment variable.
+    pub fn transparent_prefixes(&self) -> Result<Vec<(Ipv4Addr, u8)>, Fail> {
+        let prefixes: &Vec<Yaml> = match Self::get_typed_option(
+            self.get_inetstack_config()?,
+            inetstack_config::TRANSPARENT_PREFIXES,
+            |yaml: &Yaml| yaml.as_vec(),
+        ) {
+            Ok(prefixes) => prefixes,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let mut result: Vec<(Ipv4Addr, u8)> = Vec::with_capacity(prefixes.len());
+        for prefix in prefixes {
+            let parsed: Option<(Ipv4Addr, u8)> = prefix.as_str().and_then(|prefix: &str| {
+                let (addr, len) = prefix.split_once('/')?;
+                let len: u8 = len.parse().ok().filter(|len: &u8| *len <= 32)?;
+                Some((addr.parse().ok()?, len))
+            });
+            match parsed {
+                Some(prefix) => result.push(prefix),
+                None => {
+                    let cause: String = format!("Couldn't parse transparent prefix in config: {:?}", prefix);
+                    error!("transparent_prefixes(): {:?}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                },
+            }
+        }
+        Ok(result)
+    }
+
     pub fn arp_cache_ttl(&self) -> Result<Duration, Fail> {
         let ttl: u64 = if let Some(ttl) = Self::get_typed_env_option(inetstack_config::ARP_CACHE_TTL)? {
             ttl
@@ -271,6 +375,16 @@ impl Config {
         Ok(retries)
     }
 
+    /// Inetstack config: Reads the number of ARP probes to send for the local IPv4 address before using it from the
+    /// environment variable and then the underlying configuration file.
+    pub fn arp_probe_count(&self) -> Result<usize, Fail> {
+        if let Some(count) = Self::get_typed_env_option(inetstack_config::ARP_PROBE_COUNT)? {
+            Ok(count)
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::ARP_PROBE_COUNT)
+        }
+    }
+
     #[cfg(all(feature = "catpowder-libos", target_os = "linux"))]
     /// Global config: Reads the "local interface name" parameter from the environment variable and then the underlying
     /// configuration file.
@@ -287,6 +401,17 @@ impl Config {
         }
     }
 
+    #[cfg(all(feature = "catpowder-libos", target_os = "linux"))]
+    /// Raw socket config: Reads the number of workers to spread incoming frames across in software from the environment
+    /// variable and then the underlying configuration file.
+    pub fn rss_queue_count(&self) -> Result<usize, Fail> {
+        if let Some(count) = Self::get_typed_env_option(raw_socket_config::RSS_QUEUE_COUNT)? {
+            Ok(count)
+        } else {
+            Self::get_int_option(self.get_raw_socket_config()?, raw_socket_config::RSS_QUEUE_COUNT)
+        }
+    }
+
     #[cfg(all(feature = "catpowder-libos", target_os = "windows"))]
     /// Global config: Reads the "local interface index" parameter from the environment variable and then the underlying
     /// configuration file.
@@ -344,6 +469,28 @@ impl Config {
         Ok(result)
     }
 
+    #[cfg(feature = "catnip-libos")]
+    /// DPDK Config: Reads whether each priority class should get a transmit queue of its own from the environment
+    /// variable and then the underlying configuration file.
+    pub fn tx_priority_queues(&self) -> Result<bool, Fail> {
+        if let Some(enabled) = Self::get_typed_env_option(dpdk_config::TX_PRIORITY_QUEUES)? {
+            Ok(enabled)
+        } else {
+            Self::get_bool_option(self.get_dpdk_config()?, dpdk_config::TX_PRIORITY_QUEUES)
+        }
+    }
+
+    #[cfg(feature = "catnip-libos")]
+    /// DPDK Config: Reads whether to export counters and packet capture to diagnostics tools that attach as a secondary
+    /// DPDK process from the environment variable and then the underlying configuration file.
+    pub fn dpdk_diagnostics(&self) -> Result<bool, Fail> {
+        if let Some(enabled) = Self::get_typed_env_option(dpdk_config::DIAGNOSTICS)? {
+            Ok(enabled)
+        } else {
+            Self::get_bool_option(self.get_dpdk_config()?, dpdk_config::DIAGNOSTICS)
+        }
+    }
+
     pub fn mtu(&self) -> Result<u16, Fail> {
         if let Some(addr) = Self::get_typed_env_option(inetstack_config::MTU)? {
             Ok(addr)
@@ -360,14 +507,299 @@ impl Config {
         Self::get_bool_option(self.get_inetstack_config()?, inetstack_config::TCP_CHECKSUM_OFFLOAD)
     }
 
+    /// Inetstack config: Reads whether passively opened TCP connections should send to the link address that their SYN
+    /// came from, instead of resolving it with ARP, from the environment variable and then the underlying configuration
+    /// file.
+    pub fn tcp_learn_remote_link_addr(&self) -> Result<bool, Fail> {
+        if let Some(learn) = Self::get_typed_env_option(inetstack_config::TCP_LEARN_REMOTE_LINK_ADDR)? {
+            Ok(learn)
+        } else {
+            Self::get_bool_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_LEARN_REMOTE_LINK_ADDR,
+            )
+        }
+    }
+
+    /// Inetstack config: Reads whether TCP connections that use timestamps should take round-trip time samples from the
+    /// timestamps that their peer echoes, instead of only from segments that were not retransmitted, from the
+    /// environment variable and then the underlying configuration file.
+    pub fn tcp_timestamp_rtt_samples(&self) -> Result<bool, Fail> {
+        if let Some(enabled) = Self::get_typed_env_option(inetstack_config::TCP_TIMESTAMP_RTT_SAMPLES)? {
+            Ok(enabled)
+        } else {
+            Self::get_bool_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_TIMESTAMP_RTT_SAMPLES,
+            )
+        }
+    }
+
+    /// Inetstack config: Reads whether TCP connections negotiate Explicit Congestion Notification (RFC 3168) from the
+    /// environment variable and then the underlying configuration file.
+    pub fn tcp_ecn(&self) -> Result<bool, Fail> {
+        if let Some(enabled) = Self::get_typed_env_option(inetstack_config::TCP_ECN)? {
+            Ok(enabled)
+        } else {
+            Self::get_bool_option(self.get_inetstack_config()?, inetstack_config::TCP_ECN)
+        }
+    }
+
+    /// Inetstack config: Reads the size in bytes up to which TCP connections grow their receive buffer as the application
+    /// reads faster from the environment variable and then the underlying configuration file. Zero turns receive buffer
+    /// autotuning off.
+    pub fn tcp_max_receive_buffer_size(&self) -> Result<usize, Fail> {
+        if let Some(size) = Self::get_typed_env_option(inetstack_config::TCP_MAX_RECEIVE_BUFFER_SIZE)? {
+            Ok(size)
+        } else {
+            Self::get_int_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_MAX_RECEIVE_BUFFER_SIZE,
+            )
+        }
+    }
+
+    /// Inetstack config: Reads the size in bytes of the send buffer of TCP connections, which bounds the data that is
+    /// unsent or unacknowledged, from the environment variable and then the underlying configuration file. Zero leaves the
+    /// send buffer unbounded.
+    pub fn tcp_send_buffer_size(&self) -> Result<usize, Fail> {
+        if let Some(size) = Self::get_typed_env_option(inetstack_config::TCP_SEND_BUFFER_SIZE)? {
+            Ok(size)
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_SEND_BUFFER_SIZE)
+        }
+    }
+
+    /// Inetstack config: Reads the size in bytes up to which TCP connections grow their send buffer with the congestion
+    /// window from the environment variable and then the underlying configuration file.
+    pub fn tcp_max_send_buffer_size(&self) -> Result<usize, Fail> {
+        if let Some(size) = Self::get_typed_env_option(inetstack_config::TCP_MAX_SEND_BUFFER_SIZE)? {
+            Ok(size)
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_MAX_SEND_BUFFER_SIZE)
+        }
+    }
+
+    /// Inetstack config: Reads whether TCP connections fall back to smaller segments after repeated retransmission
+    /// timeouts, in case a router drops large segments without telling us, from the environment variable and then the
+    /// underlying configuration file.
+    pub fn tcp_pmtu_blackhole_detection(&self) -> Result<bool, Fail> {
+        if let Some(enabled) = Self::get_typed_env_option(inetstack_config::TCP_PMTU_BLACKHOLE_DETECTION)? {
+            Ok(enabled)
+        } else {
+            Self::get_bool_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_PMTU_BLACKHOLE_DETECTION,
+            )
+        }
+    }
+
+    /// Inetstack config: Reads whether TCP connections pace their segments at a rate derived from the congestion window
+    /// from the environment variable and then the underlying configuration file.
+    pub fn tcp_pacing(&self) -> Result<bool, Fail> {
+        if let Some(enabled) = Self::get_typed_env_option(inetstack_config::TCP_PACING)? {
+            Ok(enabled)
+        } else {
+            Self::get_bool_option(self.get_inetstack_config()?, inetstack_config::TCP_PACING)
+        }
+    }
+
+    /// Inetstack config: Reads the maximum number of connections that a listening socket completes accepts for in a
+    /// single poll iteration, where 0 means no limit, from the environment variable and then the underlying
+    /// configuration file.
+    pub fn tcp_max_accepts_per_poll(&self) -> Result<usize, Fail> {
+        if let Some(count) = Self::get_typed_env_option(inetstack_config::TCP_MAX_ACCEPTS_PER_POLL)? {
+            Ok(count)
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_MAX_ACCEPTS_PER_POLL)
+        }
+    }
+
+    /// Inetstack config: Reads when listening sockets answer SYN segments with SYN cookies ("off", "under_pressure" or
+    /// "always") from the environment variable and then the underlying configuration file.
+    pub fn tcp_syn_cookies(&self) -> Result<String, Fail> {
+        if let Some(mode) = Self::get_typed_env_option(inetstack_config::TCP_SYN_COOKIES)? {
+            Ok(mode)
+        } else {
+            Self::get_typed_str_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_SYN_COOKIES,
+                |val: &str| Some(val.to_string()),
+            )
+        }
+    }
+
+    /// Inetstack config: Reads the maximum number of handshakes that a listening socket keeps in progress, where 0 means
+    /// the backlog passed to listen, from the environment variable and then the underlying configuration file.
+    pub fn tcp_max_syn_backlog(&self) -> Result<usize, Fail> {
+        if let Some(count) = Self::get_typed_env_option(inetstack_config::TCP_MAX_SYN_BACKLOG)? {
+            Ok(count)
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_MAX_SYN_BACKLOG)
+        }
+    }
+
+    /// Inetstack config: Reads what listening sockets do with SYN segments that do not fit into their queues ("reset" or
+    /// "drop") from the environment variable and then the underlying configuration file.
+    pub fn tcp_backlog_overflow(&self) -> Result<String, Fail> {
+        if let Some(policy) = Self::get_typed_env_option(inetstack_config::TCP_BACKLOG_OVERFLOW)? {
+            Ok(policy)
+        } else {
+            Self::get_typed_str_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_BACKLOG_OVERFLOW,
+                |val: &str| Some(val.to_string()),
+            )
+        }
+    }
+
+    /// Inetstack config: Reads the maximum number of connections that are kept in TIME_WAIT from the environment variable
+    /// and then the underlying configuration file.
+    pub fn tcp_max_time_wait(&self) -> Result<usize, Fail> {
+        if let Some(count) = Self::get_typed_env_option(inetstack_config::TCP_MAX_TIME_WAIT)? {
+            Ok(count)
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_MAX_TIME_WAIT)
+        }
+    }
+
+    /// Inetstack config: Reads the maximum segment lifetime in milliseconds from the environment variable and then the
+    /// underlying configuration file.
+    pub fn tcp_msl(&self) -> Result<Duration, Fail> {
+        let msl: u64 = if let Some(msl) = Self::get_typed_env_option(inetstack_config::TCP_MSL_MS)? {
+            msl
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_MSL_MS)?
+        };
+        Ok(Duration::from_millis(msl))
+    }
+
+    /// Inetstack config: Reads how long a closed connection waits in FIN_WAIT_2 in milliseconds from the environment
+    /// variable and then the underlying configuration file.
+    pub fn tcp_fin_timeout(&self) -> Result<Duration, Fail> {
+        let timeout: u64 = if let Some(timeout) = Self::get_typed_env_option(inetstack_config::TCP_FIN_TIMEOUT_MS)? {
+            timeout
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_FIN_TIMEOUT_MS)?
+        };
+        Ok(Duration::from_millis(timeout))
+    }
+
+    /// Inetstack config: Reads when new outgoing connections may take over the 4-tuple of a connection in TIME_WAIT
+    /// ("never", "timestamps" or "always") from the environment variable and then the underlying configuration file.
+    pub fn tcp_time_wait_recycle(&self) -> Result<String, Fail> {
+        if let Some(policy) = Self::get_typed_env_option(inetstack_config::TCP_TIME_WAIT_RECYCLE)? {
+            Ok(policy)
+        } else {
+            Self::get_typed_str_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_TIME_WAIT_RECYCLE,
+                |val: &str| Some(val.to_string()),
+            )
+        }
+    }
+
+    /// Inetstack config: Reads what established connections do with segments that carry urgent data ("inline", "drop" or
+    /// "reset") from the environment variable and then the underlying configuration file.
+    pub fn tcp_urgent_data(&self) -> Result<String, Fail> {
+        if let Some(policy) = Self::get_typed_env_option(inetstack_config::TCP_URGENT_DATA)? {
+            Ok(policy)
+        } else {
+            Self::get_typed_str_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_URGENT_DATA,
+                |val: &str| Some(val.to_string()),
+            )
+        }
+    }
+
+    /// Inetstack config: Reads the name of the default TCP congestion control algorithm from the environment variable and
+    /// then the underlying configuration file.
+    pub fn tcp_congestion_control(&self) -> Result<String, Fail> {
+        if let Some(name) = Self::get_typed_env_option(inetstack_config::TCP_CONGESTION_CONTROL)? {
+            Ok(name)
+        } else {
+            Self::get_typed_str_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_CONGESTION_CONTROL,
+                |val: &str| Some(val.to_string()),
+            )
+        }
+    }
+
+    /// Inetstack config: Reads the initial TCP congestion window, in segments, from the environment variable and then
+    /// the underlying configuration file.
+    pub fn tcp_initial_congestion_window(&self) -> Result<usize, Fail> {
+        if let Some(segments) = Self::get_typed_env_option(inetstack_config::TCP_INITIAL_CONGESTION_WINDOW)? {
+            Ok(segments)
+        } else {
+            Self::get_int_option(
+                self.get_inetstack_config()?,
+                inetstack_config::TCP_INITIAL_CONGESTION_WINDOW,
+            )
+        }
+    }
+
+    /// Inetstack config: Reads the number of received TCP segments to acknowledge with a single ACK from the environment
+    /// variable and then the underlying configuration file.
+    pub fn tcp_ack_stretch_segments(&self) -> Result<usize, Fail> {
+        if let Some(segments) = Self::get_typed_env_option(inetstack_config::TCP_ACK_STRETCH_SEGMENTS)? {
+            Ok(segments)
+        } else {
+            Self::get_int_option(self.get_inetstack_config()?, inetstack_config::TCP_ACK_STRETCH_SEGMENTS)
+        }
+    }
+
     pub fn udp_checksum_offload(&self) -> Result<bool, Fail> {
         Self::get_bool_option(self.get_inetstack_config()?, inetstack_config::UDP_CHECKSUM_OFFLOAD)
     }
 
+    /// Inetstack config: Reads out of how many received packets with offloaded checksums we validate one in software
+    /// from the environment variable and then the underlying configuration file. Zero turns this validation off.
+    pub fn rx_checksum_sample_interval(&self) -> Result<usize, Fail> {
+        if let Some(interval) = Self::get_typed_env_option(inetstack_config::RX_CHECKSUM_SAMPLE_INTERVAL)? {
+            Ok(interval)
+        } else {
+            Self::get_int_option(
+                self.get_inetstack_config()?,
+                inetstack_config::RX_CHECKSUM_SAMPLE_INTERVAL,
+            )
+        }
+    }
+
     pub fn enable_jumbo_frames(&self) -> Result<bool, Fail> {
         Self::get_bool_option(self.get_inetstack_config()?, inetstack_config::ENABLE_JUMBO_FRAMES)
     }
 
+    /// Returns the faults to inject, if the configuration asks for any. Modules without a subsection of their own are
+    /// left alone.
+    #[cfg(feature = "fault-injection")]
+    pub fn fault_injection(&self) -> Result<Option<FaultConfig>, Fail> {
+        let section: &Yaml = match Self::get_subsection(&self.0, fault_injection_config::SECTION_NAME) {
+            Ok(section) => section,
+            Err(_) => return Ok(None),
+        };
+        let mut config: FaultConfig = FaultConfig::default();
+        if let Ok(seed) = Self::get_int_option(section, fault_injection_config::SEED) {
+            config.seed = seed;
+        }
+        if let Ok(memory) = Self::get_subsection(section, fault_injection_config::MEMORY) {
+            config.memory_failure_probability =
+                Self::get_float_option(memory, fault_injection_config::FAILURE_PROBABILITY)?;
+        }
+        if let Ok(network) = Self::get_subsection(section, fault_injection_config::NETWORK) {
+            config.network_failure_probability =
+                Self::get_float_option(network, fault_injection_config::FAILURE_PROBABILITY)?;
+        }
+        if let Ok(scheduler) = Self::get_subsection(section, fault_injection_config::SCHEDULER) {
+            config.scheduler_delay_probability =
+                Self::get_float_option(scheduler, fault_injection_config::DELAY_PROBABILITY)?;
+            config.scheduler_delay =
+                Duration::from_micros(Self::get_int_option(scheduler, fault_injection_config::DELAY_US)?);
+        }
+        Ok(Some(config))
+    }
+
     //======================================================================================================================
     // Static Functions
     //======================================================================================================================
@@ -456,4 +888,12 @@ impl Config {
     fn get_bool_option(yaml: &Yaml, index: &str) -> Result<bool, Fail> {
         Self::get_typed_option(yaml, index, &Yaml::as_bool)
     }
+
+    /// Same as `Self::require_typed_option` using `Yaml::as_f64` as the receiver, but also accepts integers.
+    #[cfg(feature = "fault-injection")]
+    fn get_float_option(yaml: &Yaml, index: &str) -> Result<f64, Fail> {
+        Self::get_typed_option(yaml, index, |value: &Yaml| {
+            value.as_f64().or_else(|| value.as_i64().map(|value| value as f64))
+        })
+    }
 }
@@ -17,23 +17,29 @@ use crate::catpowder::SharedCatpowderRuntime;
 use crate::inetstack::SharedInetStack;
 #[cfg(feature = "profiler")]
 use crate::perftools::profiler::set_callback;
+#[cfg(feature = "fault-injection")]
+use crate::runtime::fault::{self, FaultModule};
 use crate::{
     demikernel::{
+        completion_ring::CompletionProducer,
         config::Config,
         libos::network::{libos::SharedNetworkLibOS, NetworkLibOSWrapper},
     },
     runtime::{
         fail::Fail,
         limits, logging,
-        network::socket::option::SocketOption,
-        types::{demi_callback_t, demi_qresult_t, demi_sgarray_t},
+        network::socket::{
+            option::{SocketOption, TcpInfo},
+            PopFlags,
+        },
+        types::{demi_callback_t, demi_linked_op_t, demi_opcode_t, demi_qresult_t, demi_sgarray_t},
         QDesc, QToken, SharedDemiRuntime,
     },
     timer,
 };
 use ::std::{
     env,
-    net::{SocketAddr, SocketAddrV4},
+    net::{Shutdown, SocketAddr, SocketAddrV4},
     time::Duration,
 };
 
@@ -77,11 +83,19 @@ impl LibOS {
         };
 
         let config: Config = Config::new(config_path)?;
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = config.fault_injection()? {
+            fault::configure(faults)?;
+        }
         #[allow(unused_mut)]
         let mut runtime: SharedDemiRuntime = SharedDemiRuntime::default();
+        if let Ok(contain_panics) = config.contain_panics() {
+            runtime.set_contain_panics(contain_panics);
+        }
         // Instantiate LibOS.
         #[allow(unreachable_patterns)]
-        let libos: LibOS = match libos_name {
+        #[allow(unused_mut)]
+        let mut libos: LibOS = match libos_name {
             #[cfg(all(feature = "catnap-libos"))]
             LibOSName::Catnap => Self::NetworkLibOS(NetworkLibOSWrapper::Catnap(SharedNetworkLibOS::<
                 SharedCatnapTransport,
@@ -114,6 +128,14 @@ impl LibOS {
             _ => panic!("unsupported libos"),
         };
 
+        // Start the control server, if we were asked to.
+        #[cfg(target_os = "linux")]
+        if let Ok(path) = config.control_socket() {
+            match libos {
+                LibOS::NetworkLibOS(ref mut libos) => libos.start_control_server(&path)?,
+            }
+        }
+
         Ok(libos)
     }
 
@@ -162,6 +184,18 @@ impl LibOS {
         result
     }
 
+    /// Gets the statistics of the TCP connection on the socket referenced by [sockqd] (TCP_INFO).
+    pub fn get_tcp_info(&mut self, sockqd: QDesc) -> Result<TcpInfo, Fail> {
+        match self.get_socket_option(sockqd, SocketOption::TcpInfo(TcpInfo::default()))? {
+            SocketOption::TcpInfo(info) => Ok(info),
+            option => {
+                let cause: String = format!("unexpected socket option (option={:?})", option);
+                error!("get_tcp_info(): {}", cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+
     pub fn getpeername(&mut self, sockqd: QDesc) -> Result<SocketAddrV4, Fail> {
         let result: Result<SocketAddrV4, Fail> = {
             match self {
@@ -174,6 +208,62 @@ impl LibOS {
         result
     }
 
+    /// Closes the I/O queue referenced by [qd] right away, resetting its connection instead of shutting it down
+    /// gracefully.
+    pub fn abort(&mut self, qd: QDesc) -> Result<(), Fail> {
+        let result: Result<(), Fail> = {
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.abort(qd),
+            }
+        };
+
+        self.poll();
+
+        result
+    }
+
+    /// Shuts down the send direction, the receive direction or both directions of the connection on the socket
+    /// referenced by [sockqd], without closing it.
+    pub fn shutdown(&mut self, sockqd: QDesc, how: Shutdown) -> Result<(), Fail> {
+        let result: Result<(), Fail> = {
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.shutdown(sockqd, how),
+            }
+        };
+
+        self.poll();
+
+        result
+    }
+
+    /// Checks whether there are connections waiting to be accepted on the listening socket referenced by [sockqd].
+    pub fn has_pending_connections(&mut self, sockqd: QDesc) -> Result<bool, Fail> {
+        let result: Result<bool, Fail> = {
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.has_pending_connections(sockqd),
+            }
+        };
+
+        self.poll();
+
+        result
+    }
+
+    /// Returns the most recent error that affected the socket referenced by [sockqd] and clears it, like SO_ERROR does.
+    /// Errors that an operation on the socket already reported are not kept, but errors that nothing else reports are,
+    /// such as the reason why a segment was dropped or why the connection was reset.
+    pub fn get_last_error(&mut self, sockqd: QDesc) -> Result<Option<Fail>, Fail> {
+        let result: Result<Option<Fail>, Fail> = {
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.get_last_error(sockqd),
+            }
+        };
+
+        self.poll();
+
+        result
+    }
+
     #[allow(unused_variables)]
     pub fn bind(&mut self, sockqd: QDesc, local: SocketAddr) -> Result<(), Fail> {
         let result: Result<(), Fail> = {
@@ -231,13 +321,17 @@ impl LibOS {
         result
     }
 
-    /// Closes an I/O queue. async_close() + wait() achieves the same effect as this synchronous function.
+    /// Closes an I/O queue. async_close() + wait() achieves the same effect as this synchronous function, including the
+    /// error of a close that fails.
     pub fn close(&mut self, qd: QDesc) -> Result<(), Fail> {
         let result: Result<(), Fail> = {
             timer!("demikernel::close");
             match self {
                 LibOS::NetworkLibOS(libos) => match libos.async_close(qd) {
                     Ok(qt) => match self.wait(qt, None) {
+                        Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_FAILED => {
+                            Err(Fail::new(qr.qr_ret as i32, "close failed"))
+                        },
                         Ok(_) => Ok(()),
                         Err(e) => Err(e),
                     },
@@ -251,6 +345,8 @@ impl LibOS {
         result
     }
 
+    /// Closes an I/O queue asynchronously. The queue token completes once the connection is shut down (or reset) and
+    /// everything that the queue held is released. Closing a queue that is already closing fails with EALREADY.
     pub fn async_close(&mut self, qd: QDesc) -> Result<QToken, Fail> {
         let result: Result<QToken, Fail> = {
             timer!("demikernel::async_close");
@@ -293,8 +389,30 @@ impl LibOS {
         result
     }
 
+    /// Waits until at least `min_bytes` can be pushed to an I/O queue without queueing behind flow or congestion
+    /// control. The operation completes with `DEMI_OPC_WRITABLE`.
+    pub fn writable(&mut self, qd: QDesc, min_bytes: usize) -> Result<QToken, Fail> {
+        let result: Result<QToken, Fail> = {
+            timer!("demikernel::writable");
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.writable(qd, min_bytes),
+            }
+        };
+
+        self.poll();
+
+        result
+    }
+
     /// Pops data from a an I/O queue.
     pub fn pop(&mut self, qd: QDesc, size: Option<usize>) -> Result<QToken, Fail> {
+        self.pop_with_flags(qd, size, PopFlags::default())
+    }
+
+    /// Pops data from an I/O queue as [flags] say. A peek returns the data that a pop would return, but leaves it in
+    /// the queue, so that protocol parsers can look ahead before they consume anything. A pop that waits for all of
+    /// [size] bytes returns them in a single buffer, so that fixed-length headers can be read in one call.
+    pub fn pop_with_flags(&mut self, qd: QDesc, size: Option<usize>, flags: PopFlags) -> Result<QToken, Fail> {
         let result: Result<QToken, Fail> = {
             timer!("demikernel::pop");
 
@@ -309,7 +427,55 @@ impl LibOS {
             }
 
             match self {
-                LibOS::NetworkLibOS(libos) => libos.pop(qd, size),
+                LibOS::NetworkLibOS(libos) => libos.pop(qd, size, flags),
+            }
+        };
+
+        self.poll();
+
+        result
+    }
+
+    /// Forwards data from one I/O queue to another, in chunks of at most `size` bytes.
+    pub fn splice(&mut self, src_qd: QDesc, dst_qd: QDesc, size: Option<usize>) -> Result<QToken, Fail> {
+        let result: Result<QToken, Fail> = {
+            timer!("demikernel::splice");
+
+            // Check if this is a fixed-size splice.
+            if let Some(size) = size {
+                // Check if size is valid.
+                if !((size > 0) && (size <= limits::POP_SIZE_MAX)) {
+                    let cause: String = format!("invalid splice size (size={:?})", size);
+                    error!("splice(): {:?}", &cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                }
+            }
+
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.splice(src_qd, dst_qd, size),
+            }
+        };
+
+        self.poll();
+
+        result
+    }
+
+    /// Submits a chain of linked operations, each of which starts as soon as the one before it completes successfully.
+    /// Returns one queue token per operation.
+    pub fn submit_linked(&mut self, ops: &[demi_linked_op_t]) -> Result<Vec<QToken>, Fail> {
+        let result: Result<Vec<QToken>, Fail> = {
+            timer!("demikernel::submit_linked");
+
+            // Check if the number of operations is valid.
+            if ops.is_empty() || ops.len() > limits::LINKED_OPS_MAX {
+                let cause: String = format!("invalid number of linked operations (num_ops={:?})", ops.len());
+                error!("submit_linked(): {:?}", &cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            }
+
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.submit_linked(ops),
             }
         };
 
@@ -350,7 +516,37 @@ impl LibOS {
         }
     }
 
+    /// Waits for operations to complete and hands their results off to the consumer of `ring`, which usually runs on
+    /// another core. This returns the number of handed-off results once the ring is full or the timeout has expired,
+    /// and only fails with a timeout if no operation completed in the meantime.
+    pub fn wait_into_ring(&mut self, ring: &mut CompletionProducer, timeout: Option<Duration>) -> Result<usize, Fail> {
+        // The consumer only ever frees up slots, so every result that we take from the runtime fits in the ring.
+        let free_count: usize = ring.get_free_count();
+        if free_count == 0 {
+            let cause: String = format!("completion ring is full");
+            warn!("wait_into_ring(): {}", cause);
+            return Err(Fail::new(libc::EAGAIN, &cause));
+        }
+
+        let mut handed_off: usize = 0;
+        let acceptor = |qr: demi_qresult_t| -> bool {
+            if ring.try_push(qr).is_err() {
+                unreachable!("completion ring filled up while we were pushing into it");
+            }
+            handed_off += 1;
+            handed_off < free_count
+        };
+        match self.wait_next_n(acceptor, timeout) {
+            Ok(()) => Ok(handed_off),
+            Err(e) if e.errno == libc::ETIMEDOUT && handed_off > 0 => Ok(handed_off),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn sgaalloc(&mut self, size: usize) -> Result<demi_sgarray_t, Fail> {
+        #[cfg(feature = "fault-injection")]
+        fault::inject_failure(FaultModule::Memory, "sgaalloc")?;
+
         let result: Result<demi_sgarray_t, Fail> = {
             timer!("demikernel::sgaalloc");
             match self {
@@ -372,6 +568,27 @@ impl LibOS {
         result
     }
 
+    /// Appends the segments of the `tail` scatter-gather array to `sga`, so that a single push sends the data of both
+    /// without copying it. On success, `tail` belongs to `sga` and must not be released on its own.
+    pub fn sgaappend(&mut self, sga: &mut demi_sgarray_t, tail: demi_sgarray_t) -> Result<(), Fail> {
+        let result: Result<(), Fail> = {
+            timer!("demikernel::sgaappend");
+            match self {
+                LibOS::NetworkLibOS(libos) => libos.sgaappend(sga, tail),
+            }
+        };
+
+        result
+    }
+
+    /// Dumps the state of all sockets as JSON, for attaching it to bug reports. If `scrub_payload` is set, queued data
+    /// is left out.
+    pub fn dump_state(&mut self, scrub_payload: bool) -> Result<String, Fail> {
+        match self {
+            LibOS::NetworkLibOS(libos) => libos.dump_state(scrub_payload),
+        }
+    }
+
     pub fn poll(&mut self) {
         // No profiling scope here because we may enter a coroutine scope.
         match self {
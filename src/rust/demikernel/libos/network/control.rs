@@ -0,0 +1,252 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! This module implements a small control server that lets operators introspect a running libOS over a UNIX socket.
+//! Clients (e.g. `demictl`) send one command per line and get back a response that ends with an empty line.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    demikernel::libos::network::libos::SharedNetworkLibOS,
+    perftools::stages,
+    runtime::{
+        fail::Fail,
+        logging,
+        network::{
+            socket::option::{SocketOption, TcpInfo},
+            transport::NetworkTransport,
+        },
+        poll_yield, QDesc, QType,
+    },
+};
+use ::futures::FutureExt;
+use ::std::{
+    fs, io,
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Maximum length of a single command.
+const MAX_COMMAND_SIZE: usize = 1024;
+
+/// Maximum number of clients that may be connected at the same time.
+const MAX_CLIENTS: usize = 16;
+
+const HELP: &str = "commands:
+  help                     show this message
+  sockets                  list open sockets
+  tcp-info <qd>            show the statistics of a TCP connection
+  log <spec>               change what gets logged (same syntax as RUST_LOG)
+  stages on|off|reset      turn per-stage cycle counters on or off, or reset them
+  stages                   dump per-stage cycle counters";
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// A client that is connected to the control server, along with whatever it sent that does not make up a full command
+/// yet.
+struct ControlClient {
+    stream: UnixStream,
+    buf: Vec<u8>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl<T: NetworkTransport> SharedNetworkLibOS<T> {
+    /// Starts a control server that listens on the UNIX socket at `path`. The server runs as a background coroutine, so
+    /// it only makes progress while the application polls the libOS.
+    pub fn start_control_server(&mut self, path: &str) -> Result<(), Fail> {
+        // Remove the socket file that a previous run may have left behind.
+        if let Err(e) = fs::remove_file(path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                let cause: String = format!("cannot remove stale control socket (path={:?}): {:?}", path, e);
+                error!("start_control_server(): {}", cause);
+                return Err(Fail::new(libc::EADDRINUSE, &cause));
+            }
+        }
+
+        let listener: UnixListener = match UnixListener::bind(path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let cause: String = format!("cannot bind control socket (path={:?}): {:?}", path, e);
+                error!("start_control_server(): {}", cause);
+                return Err(Fail::new(e.raw_os_error().unwrap_or(libc::EIO), &cause));
+            },
+        };
+        if let Err(e) = listener.set_nonblocking(true) {
+            let cause: String = format!("cannot make control socket non-blocking: {:?}", e);
+            error!("start_control_server(): {}", cause);
+            return Err(Fail::new(e.raw_os_error().unwrap_or(libc::EIO), &cause));
+        }
+
+        let coroutine = Box::pin(self.clone().control_coroutine(listener).fuse());
+        self.get_runtime()
+            .insert_background_coroutine("bgc::network::libos::control", coroutine)?;
+        info!("start_control_server(): listening on {:?}", path);
+        Ok(())
+    }
+
+    async fn control_coroutine(mut self, listener: UnixListener) {
+        let mut clients: Vec<ControlClient> = Vec::with_capacity(MAX_CLIENTS);
+        loop {
+            // Accept new clients.
+            match listener.accept() {
+                Ok((stream, _)) if clients.len() < MAX_CLIENTS => match stream.set_nonblocking(true) {
+                    Ok(()) => clients.push(ControlClient {
+                        stream,
+                        buf: Vec::with_capacity(MAX_COMMAND_SIZE),
+                    }),
+                    Err(e) => warn!("control_coroutine(): cannot make client non-blocking: {:?}", e),
+                },
+                Ok(_) => warn!("control_coroutine(): too many clients, dropping new one"),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => warn!("control_coroutine(): cannot accept client: {:?}", e),
+            }
+
+            // Serve the commands that clients sent, dropping clients that went away.
+            clients.retain_mut(|client| self.serve(client));
+
+            poll_yield().await;
+        }
+    }
+
+    /// Runs all complete commands that `client` sent. Returns false if the client should be dropped.
+    fn serve(&mut self, client: &mut ControlClient) -> bool {
+        let mut chunk: [u8; MAX_COMMAND_SIZE] = [0; MAX_COMMAND_SIZE];
+        match client.stream.read(&mut chunk) {
+            Ok(0) => return false,
+            Ok(nbytes) => client.buf.extend_from_slice(&chunk[..nbytes]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+            Err(e) => {
+                warn!("serve(): cannot read from client: {:?}", e);
+                return false;
+            },
+        }
+
+        while let Some(end) = client.buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = client.buf.drain(..=end).collect();
+            let response: String = match std::str::from_utf8(&line) {
+                Ok(command) => match self.execute(command.trim()) {
+                    Ok(response) => response,
+                    Err(e) => format!("error: {}\n", e.cause),
+                },
+                Err(_) => "error: command is not valid UTF-8\n".to_string(),
+            };
+            // Responses are small, so we just wait for the client to take them.
+            let written: io::Result<()> = client
+                .stream
+                .set_nonblocking(false)
+                .and_then(|_| client.stream.write_all(response.as_bytes()))
+                .and_then(|_| client.stream.write_all(b"\n"))
+                .and_then(|_| client.stream.set_nonblocking(true));
+            if let Err(e) = written {
+                warn!("serve(): cannot write to client: {:?}", e);
+                return false;
+            }
+        }
+
+        if client.buf.len() > MAX_COMMAND_SIZE {
+            warn!("serve(): command too long, dropping client");
+            return false;
+        }
+        true
+    }
+
+    /// Runs a single control command and returns its response.
+    fn execute(&mut self, command: &str) -> Result<String, Fail> {
+        let mut words = command.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (None, _, _) => Ok(String::new()),
+            (Some("help"), None, _) => Ok(format!("{}\n", HELP)),
+            (Some("sockets"), None, _) => Ok(self.list_sockets()),
+            (Some("tcp-info"), Some(qd), None) => match qd.parse::<u32>() {
+                Ok(qd) => self.describe_tcp_info(QDesc::from(qd)),
+                Err(_) => {
+                    let cause: String = format!("invalid queue descriptor (qd={:?})", qd);
+                    Err(Fail::new(libc::EINVAL, &cause))
+                },
+            },
+            (Some("log"), Some(spec), None) => {
+                logging::set_log_spec(spec)?;
+                Ok("ok\n".to_string())
+            },
+            (Some("stages"), None, _) => {
+                let mut out: Vec<u8> = Vec::new();
+                if let Err(e) = stages::write(&mut out) {
+                    let cause: String = format!("cannot dump stage counters: {:?}", e);
+                    return Err(Fail::new(libc::EIO, &cause));
+                }
+                Ok(String::from_utf8_lossy(&out).into_owned())
+            },
+            (Some("stages"), Some(toggle), None) => {
+                match toggle {
+                    "on" => stages::set_enabled(true),
+                    "off" => stages::set_enabled(false),
+                    "reset" => stages::reset(),
+                    _ => {
+                        let cause: String = format!("invalid argument (argument={:?})", toggle);
+                        return Err(Fail::new(libc::EINVAL, &cause));
+                    },
+                }
+                Ok("ok\n".to_string())
+            },
+            _ => {
+                let cause: String = format!("invalid command (command={:?}), try \"help\"", command);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+
+    /// Lists all open sockets, one per line.
+    fn list_sockets(&mut self) -> String {
+        let qds: Vec<QDesc> = self.get_runtime().get_qtable().get_qds().collect();
+        let mut response: String = String::new();
+        for qd in qds {
+            let qtype: QType = match self.get_runtime().get_qtable().get_type(&qd) {
+                Ok(qtype) => qtype,
+                Err(_) => continue,
+            };
+            let (local, remote) = match self.get_addresses(qd) {
+                Ok(addrs) => addrs,
+                Err(_) => continue,
+            };
+            response.push_str(&format!(
+                "qd={:?} type={:?} local={:?} remote={:?}\n",
+                qd, qtype, local, remote
+            ));
+        }
+        response
+    }
+
+    /// Describes the statistics of the TCP connection on `qd`.
+    fn describe_tcp_info(&mut self, qd: QDesc) -> Result<String, Fail> {
+        let info: TcpInfo = match self.get_socket_option(qd, SocketOption::TcpInfo(TcpInfo::default()))? {
+            SocketOption::TcpInfo(info) => info,
+            option => {
+                let cause: String = format!("unexpected socket option (option={:?})", option);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+        };
+        Ok(format!(
+            "state={:?} srtt={:?} rto={:?} cwnd={} ssthresh={} retransmits={} in_flight={} rcv_wnd={} mss={}\n",
+            info.state,
+            info.smoothed_rtt,
+            info.rto,
+            info.congestion_window,
+            info.slow_start_threshold,
+            info.retransmitted_segments,
+            info.bytes_in_flight,
+            info.receive_window,
+            info.mss
+        ))
+    }
+}
@@ -6,29 +6,42 @@
 //======================================================================================================================
 
 use crate::{
+    collections::async_value::SharedAsyncValue,
     demikernel::libos::network::queue::SharedNetworkQueue,
     expect_ok, expect_some,
+    inetstack::{
+        protocols::layer4::{
+            tcp::handoff::{ConnectionHandoff, HandoffSender},
+            Socket,
+        },
+        SharedInetStack,
+    },
     pal::{socketaddrv4_to_sockaddr, SOMAXCONN},
+    perftools::stages::Stage,
     runtime::{
         fail::Fail,
         limits,
         memory::DemiBuffer,
         network::{
-            socket::{option::SocketOption, SocketId},
+            socket::{option::SocketOption, PopFlags, SocketId},
             transport::NetworkTransport,
             unwrap_socketaddr,
         },
         queue::{downcast_queue, IoQueue, OperationResult},
-        types::{demi_accept_result_t, demi_opcode_t, demi_qr_value_t, demi_qresult_t, demi_sgarray_t},
+        types::{
+            demi_accept_result_t, demi_linked_op_t, demi_opcode_t, demi_qr_value_t, demi_qresult_t, demi_sgarray_t,
+            DEMI_LINKED_QD,
+        },
         QDesc, QToken, SharedDemiRuntime, SharedObject,
     },
-    QType,
+    stage_timer, QType,
 };
 use ::futures::FutureExt;
+use ::serde_json::{json, Value};
 use ::socket2::{Domain, Protocol, Type};
 use ::std::{
     mem,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    net::{Shutdown, SocketAddr, SocketAddrV4},
     ops::{Deref, DerefMut},
     time::Duration,
 };
@@ -49,6 +62,22 @@ pub struct NetworkLibOS<T: NetworkTransport> {
 #[derive(Clone)]
 pub struct SharedNetworkLibOS<T: NetworkTransport>(SharedObject<NetworkLibOS<T>>);
 
+/// An operation of a linked chain, see [SharedNetworkLibOS::submit_linked].
+enum LinkedOperation {
+    Accept,
+    Pop,
+    Push(DemiBuffer),
+}
+
+/// Outcome of an operation of a linked chain, which the next operation in the chain waits for.
+#[derive(Clone, Copy)]
+enum LinkState {
+    Pending,
+    /// The operation succeeded on the given queue, or accepted a connection on it.
+    Completed(QDesc),
+    Failed,
+}
+
 //======================================================================================================================
 // Associate Functions
 //======================================================================================================================
@@ -96,6 +125,30 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
         self.get_shared_queue(&qd)?.getpeername()
     }
 
+    /// Shuts down the send direction, the receive direction or both directions of the connection on [qd]. Unlike close,
+    /// this keeps the queue open, so that the application can still read after it is done sending.
+    pub fn shutdown(&mut self, qd: QDesc, how: Shutdown) -> Result<(), Fail> {
+        trace!("shutdown() qd={:?}, how={:?}", qd, how);
+        self.get_shared_queue(&qd)?.shutdown(how)
+    }
+
+    /// Returns the local and remote addresses of the socket behind `qd`, if it has them.
+    pub fn get_addresses(&self, qd: QDesc) -> Result<(Option<SocketAddr>, Option<SocketAddr>), Fail> {
+        let queue: SharedNetworkQueue<T> = self.get_shared_queue(&qd)?;
+        Ok((queue.local(), queue.remote()))
+    }
+
+    pub fn has_pending_connections(&mut self, qd: QDesc) -> Result<bool, Fail> {
+        trace!("has_pending_connections() qd={:?}", qd);
+        self.get_shared_queue(&qd)?.has_pending_connections()
+    }
+
+    /// Returns the most recent error that affected the socket behind `qd` and clears it, like SO_ERROR does.
+    pub fn get_last_error(&mut self, qd: QDesc) -> Result<Option<Fail>, Fail> {
+        trace!("get_last_error() qd={:?}", qd);
+        self.get_shared_queue(&qd)?.get_last_error()
+    }
+
     /// This function contains the LibOS-level functionality needed to bind a SharedNetworkQueue to a local address.
     pub fn bind(&mut self, qd: QDesc, socket_addr: SocketAddr) -> Result<(), Fail> {
         trace!("bind() qd={:?}, local={:?}", qd, socket_addr);
@@ -103,14 +156,6 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
         // We only support IPv4 addresses.
         let socket_addrv4: SocketAddrV4 = unwrap_socketaddr(socket_addr)?;
 
-        // We only support the wildcard address for UDP sockets.
-        // FIXME: https://github.com/demikernel/demikernel/issues/189
-        if *socket_addrv4.ip() == Ipv4Addr::UNSPECIFIED && self.get_shared_queue(&qd)?.get_qtype() != QType::UdpSocket {
-            let cause: String = format!("cannot bind to wildcard address (qd={:?})", qd);
-            error!("bind(): {}", cause);
-            return Err(Fail::new(libc::ENOTSUP, &cause));
-        }
-
         // We only support the wildcard address for UDP sockets.
         // FIXME: https://github.com/demikernel/demikernel/issues/582
         if socket_addr.port() == 0 && self.get_shared_queue(&qd)?.get_qtype() != QType::UdpSocket {
@@ -119,6 +164,8 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             return Err(Fail::new(libc::ENOTSUP, &cause));
         }
 
+        // Only an exact match conflicts. A socket that is bound to a specific address may share its port with one that is
+        // bound to the wildcard address, and takes precedence over it (BSD semantics).
         if self.runtime.is_addr_in_use(socket_addrv4) {
             let cause: String = format!("address is already bound to a socket (qd={:?}", qd);
             error!("bind(): {}", &cause);
@@ -159,7 +206,7 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             let coroutine = Box::pin(self.clone().accept_coroutine(qd).fuse());
             self.runtime
                 .clone()
-                .insert_io_coroutine("ioc::network::libos::accept", coroutine)
+                .insert_io_coroutine("ioc::network::libos::accept", qd, coroutine)
         };
 
         queue.accept(coroutine_constructor)
@@ -210,7 +257,7 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             let coroutine = Box::pin(self.clone().connect_coroutine(qd, remote).fuse());
             self.runtime
                 .clone()
-                .insert_io_coroutine("ioc::network::libos::connect", coroutine)
+                .insert_io_coroutine("ioc::network::libos::connect", qd, coroutine)
         };
 
         queue.connect(coroutine_constructor)
@@ -250,7 +297,7 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             let coroutine = Box::pin(self.clone().close_coroutine(qd).fuse());
             self.runtime
                 .clone()
-                .insert_io_coroutine("ioc::network::libos::close", coroutine)
+                .insert_io_coroutine("ioc::network::libos::close", qd, coroutine)
         };
 
         queue.close(coroutine_constructor)
@@ -267,26 +314,11 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             Ok(queue) => queue,
             Err(e) => return (qd, OperationResult::Failed(e)),
         };
-        // Wait for close operation to complete.
-        match queue.close_coroutine().await {
-            Ok(()) => {
-                // If the queue was bound, remove from the socket id to queue descriptor table.
-                if let Some(local) = queue.local() {
-                    // FIXME: add IPv6 support; https://github.com/microsoft/demikernel/issues/935
-                    self.runtime.remove_socket_id_to_qd(&SocketId::Passive(expect_ok!(
-                        unwrap_socketaddr(local),
-                        "we only support IPv4"
-                    )));
-                }
-                // Remove the queue from the queue table. Expect is safe here because we looked up the queue to
-                // schedule this coroutine and no other close coroutine should be able to run due to state machine
-                // checks.
-                expect_ok!(
-                    self.runtime.free_queue::<SharedNetworkQueue<T>>(&qd),
-                    "queue should exist"
-                );
-                (qd, OperationResult::Close)
-            },
+        // Wait for close operation to complete. The queue is closed either way, so we always free it.
+        let result: Result<(), Fail> = queue.close_coroutine().await;
+        self.release_queue(qd, &queue);
+        match result {
+            Ok(()) => (qd, OperationResult::Close),
             Err(e) => {
                 warn!("async_close() qd={:?}: {:?}", qd, &e);
                 (qd, OperationResult::Failed(e))
@@ -294,6 +326,34 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
         }
     }
 
+    /// Closes [qd] right away. Connections are reset instead of being shut down gracefully, so pending operations on
+    /// [qd] fail and our peer learns that we gave up.
+    pub fn abort(&mut self, qd: QDesc) -> Result<(), Fail> {
+        trace!("abort() qd={:?}", qd);
+        let mut queue: SharedNetworkQueue<T> = self.get_shared_queue(&qd)?;
+        queue.abort()?;
+        self.release_queue(qd, &queue);
+        Ok(())
+    }
+
+    /// Frees [qd] along with its [queue], once the queue is closed.
+    fn release_queue(&mut self, qd: QDesc, queue: &SharedNetworkQueue<T>) {
+        // If the queue was bound, remove from the socket id to queue descriptor table.
+        if let Some(local) = queue.local() {
+            // FIXME: add IPv6 support; https://github.com/microsoft/demikernel/issues/935
+            self.runtime.remove_socket_id_to_qd(&SocketId::Passive(expect_ok!(
+                unwrap_socketaddr(local),
+                "we only support IPv4"
+            )));
+        }
+        // Remove the queue from the queue table. Expect is safe here because we looked up the queue before closing it
+        // and no other close should be able to run due to state machine checks.
+        expect_ok!(
+            self.runtime.free_queue::<SharedNetworkQueue<T>>(&qd),
+            "queue should exist"
+        );
+    }
+
     /// Synchronous code to push [buf] to a SharedNetworkQueue and its underlying POSIX socket. This function schedules the
     /// coroutine that asynchronously runs the push and any synchronous multi-queue functionality before the push
     /// begins.
@@ -310,10 +370,10 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             let coroutine = Box::pin(self.clone().push_coroutine(qd, buf).fuse());
             self.runtime
                 .clone()
-                .insert_io_coroutine("ioc::network::libos::push", coroutine)
+                .insert_io_coroutine("ioc::network::libos::push", qd, coroutine)
         };
 
-        queue.push(coroutine_constructor)
+        queue.push(None, coroutine_constructor)
     }
 
     /// Asynchronous code to push [buf] to a SharedNetworkQueue and its underlying POSIX socket. This function returns a
@@ -353,10 +413,10 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             let coroutine = Box::pin(self.clone().pushto_coroutine(qd, buf, remote).fuse());
             self.runtime
                 .clone()
-                .insert_io_coroutine("ioc::network::libos::pushto", coroutine)
+                .insert_io_coroutine("ioc::network::libos::pushto", qd, coroutine)
         };
 
-        queue.push(coroutine_constructor)
+        queue.push(Some(remote), coroutine_constructor)
     }
 
     /// Asynchronous code to pushto [buf] to [remote] on a SharedNetworkQueue and its underlying POSIX socket. This function
@@ -380,21 +440,59 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
         }
     }
 
+    /// Synchronous code to wait until at least [min_bytes] can be pushed to a SharedNetworkQueue without queueing behind
+    /// its send window or congestion window. Applications use this to stop generating data while a connection is
+    /// blocked, instead of queueing it up.
+    pub fn writable(&mut self, qd: QDesc, min_bytes: usize) -> Result<QToken, Fail> {
+        trace!("writable() qd={:?}, min_bytes={:?}", qd, min_bytes);
+
+        if min_bytes == 0 {
+            let cause: String = format!("minimum number of bytes must be positive");
+            error!("writable(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+
+        let mut queue: SharedNetworkQueue<T> = self.get_shared_queue(&qd)?;
+        let coroutine_constructor = || -> Result<QToken, Fail> {
+            let coroutine = Box::pin(self.clone().writable_coroutine(qd, min_bytes).fuse());
+            self.runtime
+                .clone()
+                .insert_io_coroutine("ioc::network::libos::writable", qd, coroutine)
+        };
+
+        queue.writable(coroutine_constructor)
+    }
+
+    /// Asynchronous code to wait until a SharedNetworkQueue is writable.
+    async fn writable_coroutine(self, qd: QDesc, min_bytes: usize) -> (QDesc, OperationResult) {
+        let mut queue: SharedNetworkQueue<T> = match self.get_shared_queue(&qd) {
+            Ok(queue) => queue,
+            Err(e) => return (qd, OperationResult::Failed(e)),
+        };
+        match queue.writable_coroutine(min_bytes).await {
+            Ok(()) => (qd, OperationResult::Writable),
+            Err(e) => {
+                warn!("writable() qd={:?}: {:?}", qd, &e);
+                (qd, OperationResult::Failed(e))
+            },
+        }
+    }
+
     /// Synchronous code to pop data from a SharedNetworkQueue and its underlying POSIX socket of optional [size]. This
     /// function schedules the asynchronous coroutine and performs any necessary synchronous, multi-queue operations
     /// at the LibOS-level before beginning the pop.
-    pub fn pop(&mut self, qd: QDesc, size: Option<usize>) -> Result<QToken, Fail> {
-        trace!("pop() qd={:?}, size={:?}", qd, size);
+    pub fn pop(&mut self, qd: QDesc, size: Option<usize>, flags: PopFlags) -> Result<QToken, Fail> {
+        trace!("pop() qd={:?}, size={:?}, flags={:?}", qd, size, flags);
 
         // We just assert 'size' here, because it was previously checked at PDPIX layer.
         debug_assert!(size.is_none() || ((size.unwrap() > 0) && (size.unwrap() <= limits::POP_SIZE_MAX)));
 
         let mut queue: SharedNetworkQueue<T> = self.get_shared_queue(&qd)?;
         let coroutine_constructor = || -> Result<QToken, Fail> {
-            let coroutine = Box::pin(self.clone().pop_coroutine(qd, size).fuse());
+            let coroutine = Box::pin(self.clone().pop_coroutine(qd, size, flags).fuse());
             self.runtime
                 .clone()
-                .insert_io_coroutine("ioc::network::libos::pop", coroutine)
+                .insert_io_coroutine("ioc::network::libos::pop", qd, coroutine)
         };
 
         queue.pop(coroutine_constructor)
@@ -403,7 +501,7 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
     /// Asynchronous code to pop data from a SharedNetworkQueue and its underlying POSIX socket of optional [size]. This
     /// function returns a coroutine that asynchronously runs pop and performs any necessary multi-queue operations at
     /// the LibOS-level after the pop succeeds or fails.
-    async fn pop_coroutine(self, qd: QDesc, size: Option<usize>) -> (QDesc, OperationResult) {
+    async fn pop_coroutine(self, qd: QDesc, size: Option<usize>, flags: PopFlags) -> (QDesc, OperationResult) {
         // Grab the queue, make sure it hasn't been closed in the meantime.
         // This will bump the Rc refcount so the coroutine can have it's own reference to the shared queue data
         // structure and the SharedNetworkQueue will not be freed until this coroutine finishes.
@@ -413,13 +511,25 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
         };
 
         // Wait for pop to complete.
-        match queue.pop_coroutine(size).await {
+        let result: Result<(Option<SocketAddr>, DemiBuffer), Fail> = queue.pop_coroutine(size, flags).await;
+        // Let the application know how much more it can pop right away. This is only a hint, so failing to get it
+        // does not fail the pop.
+        let buffered: usize = if result.is_ok() {
+            queue.get_buffered_bytes().unwrap_or(0)
+        } else {
+            0
+        };
+        match result {
             // FIXME: add IPv6 support; https://github.com/microsoft/demikernel/issues/935
             Ok((Some(addr), buf)) => (
                 qd,
-                OperationResult::Pop(Some(expect_ok!(unwrap_socketaddr(addr), "we only support IPv4")), buf),
+                OperationResult::Pop(
+                    Some(expect_ok!(unwrap_socketaddr(addr), "we only support IPv4")),
+                    buf,
+                    buffered,
+                ),
             ),
-            Ok((None, buf)) => (qd, OperationResult::Pop(None, buf)),
+            Ok((None, buf)) => (qd, OperationResult::Pop(None, buf, buffered)),
             Err(e) => {
                 warn!("pop() qd={:?}: {:?}", qd, &e);
                 (qd, OperationResult::Failed(e))
@@ -427,6 +537,209 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
         }
     }
 
+    /// Synchronous code to forward everything that arrives on [src_qd] to [dst_qd], in chunks of at most [size] bytes.
+    /// Buffers are moved from one queue to the other as they are, so the application never sees them and no bytes are
+    /// copied on the way. This function schedules the coroutine that runs the forwarding, which completes as a push on
+    /// [dst_qd] once [src_qd] reaches the end of its stream.
+    pub fn splice(&mut self, src_qd: QDesc, dst_qd: QDesc, size: Option<usize>) -> Result<QToken, Fail> {
+        trace!("splice() src_qd={:?}, dst_qd={:?}, size={:?}", src_qd, dst_qd, size);
+
+        // We just assert 'size' here, because it was previously checked at PDPIX layer.
+        debug_assert!(size.is_none() || ((size.unwrap() > 0) && (size.unwrap() <= limits::POP_SIZE_MAX)));
+
+        if src_qd == dst_qd {
+            let cause: String = format!("cannot splice a queue into itself (qd={:?})", src_qd);
+            error!("splice(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+
+        let mut src_queue: SharedNetworkQueue<T> = self.get_shared_queue(&src_qd)?;
+        let mut dst_queue: SharedNetworkQueue<T> = self.get_shared_queue(&dst_qd)?;
+        let coroutine_constructor = || -> Result<QToken, Fail> {
+            let coroutine = Box::pin(self.clone().splice_coroutine(src_qd, dst_qd, size).fuse());
+            self.runtime
+                .clone()
+                .insert_io_coroutine("ioc::network::libos::splice", dst_qd, coroutine)
+        };
+
+        // Both queues have to be in a state where we may pop from the source and push to the destination.
+        src_queue.pop(|| dst_queue.push(None, coroutine_constructor))
+    }
+
+    /// Asynchronous code to forward data from [src_qd] to [dst_qd]. This function returns a coroutine that pops from
+    /// the source and pushes each buffer that it gets to the destination, until the source reaches the end of its
+    /// stream or either operation fails. The end of the stream is passed on by shutting down the send direction of the
+    /// destination, so that its remote sees it as well.
+    async fn splice_coroutine(self, src_qd: QDesc, dst_qd: QDesc, size: Option<usize>) -> (QDesc, OperationResult) {
+        // Grab both queues, make sure they haven't been closed in the meantime.
+        let mut src_queue: SharedNetworkQueue<T> = match self.get_shared_queue(&src_qd) {
+            Ok(queue) => queue,
+            Err(e) => return (dst_qd, OperationResult::Failed(e)),
+        };
+        let mut dst_queue: SharedNetworkQueue<T> = match self.get_shared_queue(&dst_qd) {
+            Ok(queue) => queue,
+            Err(e) => return (dst_qd, OperationResult::Failed(e)),
+        };
+
+        loop {
+            let mut buf: DemiBuffer = match src_queue.pop_coroutine(size, PopFlags::default()).await {
+                // An empty buffer means that the remote closed its end of the stream.
+                Ok((_, buf)) if buf.len() == 0 => {
+                    if let Err(e) = dst_queue.shutdown(Shutdown::Write) {
+                        warn!("splice() dst_qd={:?}: {:?}", dst_qd, &e);
+                        return (dst_qd, OperationResult::Failed(e));
+                    }
+                    return (dst_qd, OperationResult::Push);
+                },
+                Ok((_, buf)) => buf,
+                Err(e) => {
+                    warn!("splice() src_qd={:?}: {:?}", src_qd, &e);
+                    return (dst_qd, OperationResult::Failed(e));
+                },
+            };
+            // Wait for the push to complete before popping again, so that a slow destination holds the source back.
+            if let Err(e) = dst_queue.push_coroutine(&mut buf, None).await {
+                warn!("splice() dst_qd={:?}: {:?}", dst_qd, &e);
+                return (dst_qd, OperationResult::Failed(e));
+            }
+        }
+    }
+
+    /// Synchronous code to submit a chain of linked operations, each of which starts as soon as the one before it
+    /// completes successfully. An operation on [DEMI_LINKED_QD] runs on the queue of the operation before it, which is
+    /// the new connection if that one was an accept. Every operation completes on its own queue token, and once an
+    /// operation fails, the rest of the chain fails with ECANCELED. This saves applications the round trip through
+    /// wait() between dependent operations, e.g. between accepting a connection and popping its first request.
+    pub fn submit_linked(&mut self, ops: &[demi_linked_op_t]) -> Result<Vec<QToken>, Fail> {
+        trace!("submit_linked() num_ops={:?}", ops.len());
+
+        // We just assert the number of operations here, because it was previously checked at PDPIX layer.
+        debug_assert!(!ops.is_empty() && ops.len() <= limits::LINKED_OPS_MAX);
+
+        // Check the whole chain before starting any of it.
+        let mut chain: Vec<(Option<QDesc>, LinkedOperation)> = Vec::with_capacity(ops.len());
+        for (index, op) in ops.iter().enumerate() {
+            let qd: Option<QDesc> = match op.op_qd {
+                DEMI_LINKED_QD if index == 0 => {
+                    let cause: String = format!("chain must start with an operation on a queue");
+                    warn!("submit_linked(): {}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                },
+                DEMI_LINKED_QD => None,
+                qd => Some(QDesc::from(qd)),
+            };
+            let operation: LinkedOperation = match op.op_opcode {
+                demi_opcode_t::DEMI_OPC_ACCEPT => LinkedOperation::Accept,
+                demi_opcode_t::DEMI_OPC_POP => LinkedOperation::Pop,
+                demi_opcode_t::DEMI_OPC_PUSH => {
+                    let buf: DemiBuffer = self.transport.clone_sgarray(&op.op_sga)?;
+                    if buf.len() == 0 {
+                        let cause: String = format!("zero-length buffer (index={:?})", index);
+                        warn!("submit_linked(): {}", cause);
+                        return Err(Fail::new(libc::EINVAL, &cause));
+                    }
+                    LinkedOperation::Push(buf)
+                },
+                _ => {
+                    let cause: String = format!("operation cannot be linked (index={:?})", index);
+                    warn!("submit_linked(): {}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                },
+            };
+            // Queues that are closed in the meantime make the operation fail once it starts.
+            if let Some(qd) = qd {
+                self.get_shared_queue(&qd)?;
+            }
+            chain.push((qd, operation));
+        }
+        let first_qd: QDesc = QDesc::from(ops[0].op_qd);
+        let mut first_queue: Option<SharedNetworkQueue<T>> = Some(self.get_shared_queue(&first_qd)?);
+
+        // The first operation waits for the whole chain to be scheduled, so that nothing runs if scheduling fails.
+        let mut start: SharedAsyncValue<LinkState> = SharedAsyncValue::new(LinkState::Pending);
+        let mut previous: SharedAsyncValue<LinkState> = start.clone();
+        let mut qts: Vec<QToken> = Vec::with_capacity(chain.len());
+        for (qd, operation) in chain {
+            let next: SharedAsyncValue<LinkState> = SharedAsyncValue::new(LinkState::Pending);
+            let coroutine = Box::pin(
+                self.clone()
+                    .linked_coroutine(qd, first_qd, operation, previous, next.clone())
+                    .fuse(),
+            );
+            let mut runtime: SharedDemiRuntime = self.runtime.clone();
+            let coroutine_constructor = move || -> Result<QToken, Fail> {
+                runtime.insert_io_coroutine("ioc::network::libos::linked", qd.unwrap_or(first_qd), coroutine)
+            };
+            // The first operation starts right away, so its queue has to be ready for it already.
+            let result: Result<QToken, Fail> = match first_queue.take() {
+                Some(mut queue) => match ops[0].op_opcode {
+                    demi_opcode_t::DEMI_OPC_ACCEPT => queue.accept(coroutine_constructor),
+                    demi_opcode_t::DEMI_OPC_POP => queue.pop(coroutine_constructor),
+                    _ => queue.push(None, coroutine_constructor),
+                },
+                None => coroutine_constructor(),
+            };
+            match result {
+                Ok(qt) => qts.push(qt),
+                Err(e) => {
+                    // The operations that were scheduled already have not started yet, as they all wait for the first
+                    // one. Remove them, so that they do not leave results behind that nobody waits for.
+                    for qt in qts {
+                        self.runtime.remove_coroutine(qt);
+                    }
+                    return Err(e);
+                },
+            }
+            previous = next;
+        }
+        start.set(LinkState::Completed(first_qd));
+
+        Ok(qts)
+    }
+
+    /// Asynchronous code to run an operation of a linked chain. This function returns a coroutine that waits for the
+    /// operation before it in the chain and then runs [operation] on [qd], or on the queue of the operation before it if
+    /// there is no [qd].
+    async fn linked_coroutine(
+        self,
+        qd: Option<QDesc>,
+        first_qd: QDesc,
+        operation: LinkedOperation,
+        mut previous: SharedAsyncValue<LinkState>,
+        mut next: SharedAsyncValue<LinkState>,
+    ) -> (QDesc, OperationResult) {
+        let previous_state: LinkState = match previous
+            .wait_for_condition(|state| !matches!(state, LinkState::Pending), None)
+            .await
+        {
+            Ok(state) => state,
+            Err(e) => return (qd.unwrap_or(first_qd), OperationResult::Failed(e)),
+        };
+        let qd: QDesc = match previous_state {
+            LinkState::Completed(previous_qd) => qd.unwrap_or(previous_qd),
+            _ => {
+                next.set(LinkState::Failed);
+                let cause: String = format!("previous operation in the chain failed");
+                return (
+                    qd.unwrap_or(first_qd),
+                    OperationResult::Failed(Fail::new(libc::ECANCELED, &cause)),
+                );
+            },
+        };
+
+        let (qd, result): (QDesc, OperationResult) = match operation {
+            LinkedOperation::Accept => self.accept_coroutine(qd).await,
+            LinkedOperation::Pop => self.pop_coroutine(qd, None, PopFlags::default()).await,
+            LinkedOperation::Push(buf) => self.push_coroutine(qd, buf).await,
+        };
+        next.set(match result {
+            OperationResult::Accept((new_qd, _)) => LinkState::Completed(new_qd),
+            OperationResult::Failed(_) => LinkState::Failed,
+            _ => LinkState::Completed(qd),
+        });
+        (qd, result)
+    }
+
     /// Waits for a pending I/O operation to complete or a timeout to expire.
     /// This is just a single-token convenience wrapper for wait_any().
     pub fn wait(&mut self, qt: QToken, timeout: Duration) -> Result<demi_qresult_t, Fail> {
@@ -461,6 +774,7 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
     }
 
     pub fn create_result(&self, result: OperationResult, qd: QDesc, qt: QToken) -> demi_qresult_t {
+        stage_timer!(Stage::AppCompletion);
         match result {
             OperationResult::Connect => demi_qresult_t {
                 qr_opcode: demi_opcode_t::DEMI_OPC_CONNECT,
@@ -471,10 +785,16 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
             },
             OperationResult::Accept((new_qd, addr)) => {
                 let saddr: libc::sockaddr = socketaddrv4_to_sockaddr(&addr);
+                // The local address is the original destination of the connection, which transparent sockets need.
+                let local: libc::sockaddr = match self.get_addresses(new_qd) {
+                    Ok((Some(SocketAddr::V4(local)), _)) => socketaddrv4_to_sockaddr(&local),
+                    _ => unsafe { mem::zeroed() },
+                };
                 let qr_value: demi_qr_value_t = demi_qr_value_t {
                     ares: demi_accept_result_t {
                         qd: new_qd.into(),
                         addr: saddr,
+                        local,
                     },
                 };
                 demi_qresult_t {
@@ -492,7 +812,7 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
                 qr_ret: 0,
                 qr_value: unsafe { mem::zeroed() },
             },
-            OperationResult::Pop(addr, bytes) => match self.transport.into_sgarray(bytes) {
+            OperationResult::Pop(addr, bytes, buffered) => match self.transport.into_sgarray(bytes) {
                 Ok(mut sga) => {
                     if let Some(addr) = addr {
                         sga.sga_addr = socketaddrv4_to_sockaddr(&addr);
@@ -502,7 +822,7 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
                         qr_opcode: demi_opcode_t::DEMI_OPC_POP,
                         qr_qd: qd.into(),
                         qr_qt: qt.into(),
-                        qr_ret: 0,
+                        qr_ret: buffered as i64,
                         qr_value,
                     }
                 },
@@ -524,6 +844,13 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
                 qr_ret: 0,
                 qr_value: unsafe { mem::zeroed() },
             },
+            OperationResult::Writable => demi_qresult_t {
+                qr_opcode: demi_opcode_t::DEMI_OPC_WRITABLE,
+                qr_qd: qd.into(),
+                qr_qt: qt.into(),
+                qr_ret: 0,
+                qr_value: unsafe { mem::zeroed() },
+            },
             OperationResult::Failed(e) => {
                 warn!("Operation Failed: {:?}", e);
                 demi_qresult_t {
@@ -552,6 +879,11 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
         self.transport.sgafree(sga)
     }
 
+    /// Appends the segments of one scatter-gather array to another.
+    pub fn sgaappend(&self, sga: &mut demi_sgarray_t, tail: demi_sgarray_t) -> Result<(), Fail> {
+        self.transport.sgaappend(sga, tail)
+    }
+
     /// This function gets a shared queue reference out of the I/O queue table. The type if a ref counted pointer to the
     /// queue itself.
     fn get_shared_queue(&self, qd: &QDesc) -> Result<SharedNetworkQueue<T>, Fail> {
@@ -569,6 +901,56 @@ impl<T: NetworkTransport> SharedNetworkLibOS<T> {
     }
 }
 
+/// Associate Functions that only apply to the network stack of Demikernel.
+impl SharedNetworkLibOS<SharedInetStack> {
+    /// Hands connections that the listening socket `qd` establishes off to worker cores through `handoff`. Worker cores
+    /// take them over with [SharedNetworkLibOS::adopt_tcp_connection].
+    pub fn set_tcp_handoff(&mut self, qd: QDesc, handoff: HandoffSender) -> Result<(), Fail> {
+        trace!("set_tcp_handoff() qd={:?}", qd);
+        let mut queue: SharedNetworkQueue<SharedInetStack> = self.get_shared_queue(&qd)?;
+        self.transport.set_tcp_handoff(queue.get_mut_socket(), handoff)
+    }
+
+    /// Takes over a TCP connection that a listening socket on another core handed off, and returns a queue descriptor
+    /// for it.
+    pub fn adopt_tcp_connection(&mut self, handoff: ConnectionHandoff) -> Result<QDesc, Fail> {
+        trace!("adopt_tcp_connection() remote={:?}", handoff.remote);
+        let (socket, remote): (Socket, SocketAddr) = self.transport.adopt_tcp_connection(handoff)?;
+        let queue: SharedNetworkQueue<SharedInetStack> =
+            SharedNetworkQueue::new_connected(QType::TcpSocket, socket, remote, self.transport.clone());
+        Ok(self.runtime.alloc_queue(queue))
+    }
+
+    /// Dumps the state of all sockets, their windows and their queues as JSON, e.g. to attach it to a bug report. If
+    /// `scrub_payload` is set, the dump only has the length of queued data, not the data itself.
+    pub fn dump_state(&mut self, scrub_payload: bool) -> Result<String, Fail> {
+        let qds: Vec<QDesc> = self.runtime.get_qtable().get_qds().collect();
+        let mut sockets: Vec<Value> = Vec::with_capacity(qds.len());
+        for qd in qds {
+            let mut queue: SharedNetworkQueue<SharedInetStack> = match self.get_shared_queue(&qd) {
+                Ok(queue) => queue,
+                Err(_) => continue,
+            };
+            let mut socket: Value = queue.get_mut_socket().dump_state(scrub_payload);
+            socket["qd"] = json!(u32::from(qd));
+            sockets.push(socket);
+        }
+
+        let state: Value = json!({
+            "scrubbed": scrub_payload,
+            "sockets": sockets,
+        });
+        match serde_json::to_string_pretty(&state) {
+            Ok(dump) => Ok(dump),
+            Err(e) => {
+                let cause: String = format!("cannot serialize state: {:?}", e);
+                error!("dump_state(): {}", cause);
+                Err(Fail::new(libc::EIO, &cause))
+            },
+        }
+    }
+}
+
 //======================================================================================================================
 // Trait Implementations
 //======================================================================================================================
@@ -5,6 +5,8 @@
 // Imports
 //======================================================================================================================
 
+#[cfg(target_os = "linux")]
+pub mod control;
 pub mod libos;
 pub mod queue;
 
@@ -17,13 +19,13 @@ use crate::{
     pal::SOMAXCONN,
     runtime::{
         fail::Fail,
-        network::socket::option::SocketOption,
-        types::{demi_qresult_t, demi_sgarray_t},
+        network::socket::{option::SocketOption, PopFlags},
+        types::{demi_linked_op_t, demi_qresult_t, demi_sgarray_t},
         QDesc, QToken,
     },
 };
 use ::std::{
-    net::{SocketAddr, SocketAddrV4},
+    net::{Shutdown, SocketAddr, SocketAddrV4},
     time::Duration,
 };
 
@@ -106,6 +108,54 @@ impl NetworkLibOSWrapper {
         }
     }
 
+    /// Closes a queue right away, resetting its connection.
+    pub fn abort(&mut self, qd: QDesc) -> Result<(), Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.abort(qd),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.abort(qd),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.abort(qd),
+        }
+    }
+
+    /// Shuts down one or both directions of the connection on the socket.
+    pub fn shutdown(&mut self, sockqd: QDesc, how: Shutdown) -> Result<(), Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.shutdown(sockqd, how),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.shutdown(sockqd, how),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.shutdown(sockqd, how),
+        }
+    }
+
+    /// Checks whether there are connections waiting to be accepted on a listening socket.
+    pub fn has_pending_connections(&mut self, sockqd: QDesc) -> Result<bool, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.has_pending_connections(sockqd),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.has_pending_connections(sockqd),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.has_pending_connections(sockqd),
+        }
+    }
+
+    /// Returns the most recent error that affected a socket and clears it.
+    pub fn get_last_error(&mut self, sockqd: QDesc) -> Result<Option<Fail>, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.get_last_error(sockqd),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.get_last_error(sockqd),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.get_last_error(sockqd),
+        }
+    }
+
     /// Binds a socket to a local address.
     pub fn bind(&mut self, sockqd: QDesc, local: SocketAddr) -> Result<(), Fail> {
         match self {
@@ -205,15 +255,83 @@ impl NetworkLibOSWrapper {
         }
     }
 
+    /// Waits until at least `min_bytes` can be pushed to a socket without queueing.
+    pub fn writable(&mut self, sockqd: QDesc, min_bytes: usize) -> Result<QToken, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.writable(sockqd, min_bytes),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.writable(sockqd, min_bytes),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.writable(sockqd, min_bytes),
+        }
+    }
+
+    /// Forwards data from one socket to another, without handing it to the application.
+    pub fn splice(&mut self, src_qd: QDesc, dst_qd: QDesc, size: Option<usize>) -> Result<QToken, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.splice(src_qd, dst_qd, size),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.splice(src_qd, dst_qd, size),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.splice(src_qd, dst_qd, size),
+        }
+    }
+
+    /// Submits a chain of operations, each of which starts as soon as the one before it completes successfully.
+    pub fn submit_linked(&mut self, ops: &[demi_linked_op_t]) -> Result<Vec<QToken>, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.submit_linked(ops),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.submit_linked(ops),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.submit_linked(ops),
+        }
+    }
+
+    /// Dumps the state of all sockets as JSON. Only libOSes that run our own network stack support this.
+    pub fn dump_state(&mut self, scrub_payload: bool) -> Result<String, Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.dump_state(scrub_payload),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(_) => {
+                let cause: String = format!(
+                    "dumping state is not supported by catnap (scrub_payload={:?})",
+                    scrub_payload
+                );
+                error!("dump_state(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.dump_state(scrub_payload),
+        }
+    }
+
+    /// Starts a control server on the UNIX socket at `path`.
+    #[cfg(target_os = "linux")]
+    pub fn start_control_server(&mut self, path: &str) -> Result<(), Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.start_control_server(path),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.start_control_server(path),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.start_control_server(path),
+        }
+    }
+
     /// Pops data from a socket.
-    pub fn pop(&mut self, sockqd: QDesc, size: Option<usize>) -> Result<QToken, Fail> {
+    pub fn pop(&mut self, sockqd: QDesc, size: Option<usize>, flags: PopFlags) -> Result<QToken, Fail> {
         match self {
             #[cfg(feature = "catpowder-libos")]
-            NetworkLibOSWrapper::Catpowder(libos) => libos.pop(sockqd, size),
+            NetworkLibOSWrapper::Catpowder(libos) => libos.pop(sockqd, size, flags),
             #[cfg(all(feature = "catnap-libos"))]
-            NetworkLibOSWrapper::Catnap(libos) => libos.pop(sockqd, size),
+            NetworkLibOSWrapper::Catnap(libos) => libos.pop(sockqd, size, flags),
             #[cfg(feature = "catnip-libos")]
-            NetworkLibOSWrapper::Catnip(libos) => libos.pop(sockqd, size),
+            NetworkLibOSWrapper::Catnip(libos) => libos.pop(sockqd, size, flags),
         }
     }
 
@@ -300,4 +418,16 @@ impl NetworkLibOSWrapper {
             NetworkLibOSWrapper::Catnip(libos) => libos.sgafree(sga),
         }
     }
+
+    /// Appends the segments of one scatter-gather array to another.
+    pub fn sgaappend(&self, sga: &mut demi_sgarray_t, tail: demi_sgarray_t) -> Result<(), Fail> {
+        match self {
+            #[cfg(feature = "catpowder-libos")]
+            NetworkLibOSWrapper::Catpowder(libos) => libos.sgaappend(sga, tail),
+            #[cfg(all(feature = "catnap-libos"))]
+            NetworkLibOSWrapper::Catnap(libos) => libos.sgaappend(sga, tail),
+            #[cfg(feature = "catnip-libos")]
+            NetworkLibOSWrapper::Catnip(libos) => libos.sgaappend(sga, tail),
+        }
+    }
 }
@@ -10,7 +10,7 @@ use crate::runtime::{
     limits,
     memory::DemiBuffer,
     network::{
-        socket::{operation::SocketOp, option::SocketOption, state::SocketStateMachine},
+        socket::{operation::SocketOp, option::SocketOption, state::SocketStateMachine, PopFlags},
         transport::NetworkTransport,
     },
     queue::{IoQueue, QType},
@@ -20,7 +20,7 @@ use ::futures::{pin_mut, select_biased, FutureExt};
 use ::socket2::{Domain, Type};
 use ::std::{
     any::Any,
-    net::{SocketAddr, SocketAddrV4},
+    net::{Shutdown, SocketAddr, SocketAddrV4},
     ops::{Deref, DerefMut},
 };
 
@@ -76,6 +76,24 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
         })))
     }
 
+    /// Creates a queue for a socket that was connected outside of the LibOS, for instance a TCP connection that was
+    /// handed off by another core.
+    pub fn new_connected(qtype: QType, socket: T::SocketDescriptor, remote: SocketAddr, transport: T) -> Self {
+        Self(SharedObject::new(NetworkQueue::<T> {
+            qtype,
+            state_machine: SocketStateMachine::new_established(),
+            socket,
+            local: None,
+            remote: Some(remote),
+            transport,
+        }))
+    }
+
+    /// Returns the underlying socket, for operations that are specific to the network transport.
+    pub fn get_mut_socket(&mut self) -> &mut T::SocketDescriptor {
+        &mut self.socket
+    }
+
     /// Sets a socket option on the socket.
     pub fn set_socket_option(&mut self, option: SocketOption) -> Result<(), Fail> {
         // Ensure that option can be set, depending on the state of the socket.
@@ -98,6 +116,12 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
         self.transport.clone().getpeername(&mut self.socket)
     }
 
+    /// Shuts down one or both directions of the connection on this queue. The queue stays open until it is closed.
+    pub fn shutdown(&mut self, how: Shutdown) -> Result<(), Fail> {
+        self.state_machine.may_shutdown()?;
+        self.transport.clone().shutdown(&mut self.socket, how)
+    }
+
     /// Binds the target queue to `local` address.
     pub fn bind(&mut self, local: SocketAddr) -> Result<(), Fail> {
         self.state_machine.prepare(SocketOp::Bind)?;
@@ -132,6 +156,22 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
         }
     }
 
+    /// Checks whether there are connections waiting to be accepted on this queue, without starting an accept.
+    pub fn has_pending_connections(&mut self) -> Result<bool, Fail> {
+        self.state_machine.may_accept()?;
+        self.transport.clone().has_pending_connections(&mut self.socket)
+    }
+
+    /// Returns the most recent error that affected the socket of this queue and clears it.
+    pub fn get_last_error(&mut self) -> Result<Option<Fail>, Fail> {
+        self.transport.clone().get_last_error(&mut self.socket)
+    }
+
+    /// Returns the number of bytes that can be popped from this queue without waiting.
+    pub fn get_buffered_bytes(&mut self) -> Result<usize, Fail> {
+        self.transport.clone().get_buffered_bytes(&mut self.socket)
+    }
+
     /// Starts a coroutine to begin accepting on this queue. This function contains all of the single-queue,
     /// synchronous functionality necessary to start an accept.
     pub fn accept<F>(&mut self, coroutine_constructor: F) -> Result<QToken, Fail>
@@ -166,13 +206,21 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
             }
         };
 
-        // 3. Successfully accepted a connection, so construct a new queue.
+        // 3. Successfully accepted a connection, so construct a new queue. The local address of the connection is not
+        // necessarily the one of the listening queue, as transparent sockets take connections to foreign addresses.
         trace!("connection accepted ({:?})", new_socket);
+        let mut new_socket: T::SocketDescriptor = new_socket;
+        let local: Option<SocketAddr> = self
+            .transport
+            .clone()
+            .getsockname(&mut new_socket)
+            .ok()
+            .map(SocketAddr::V4);
         Ok(Self(SharedObject::new(NetworkQueue {
             qtype: self.qtype,
             state_machine: SocketStateMachine::new_established(),
             socket: new_socket,
-            local: None,
+            local,
             remote: Some(saddr),
             transport: self.transport.clone(),
         })))
@@ -237,6 +285,20 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
         self.do_generic_sync_control_path_call(coroutine_constructor)
     }
 
+    /// Closes this queue right away, resetting its connection instead of shutting it down gracefully. Pending operations on
+    /// the queue fail.
+    pub fn abort(&mut self) -> Result<(), Fail> {
+        self.state_machine.prepare(SocketOp::Close)?;
+        if let Err(e) = self.transport.clone().abort(&mut self.socket) {
+            self.state_machine.abort();
+            return Err(e);
+        }
+        self.state_machine.commit();
+        self.state_machine.prepare(SocketOp::Closed)?;
+        self.state_machine.commit();
+        Ok(())
+    }
+
     /// Close this queue. This function contains all the single-queue functionality to synchronously close a queue.
     pub fn hard_close(&mut self) -> Result<(), Fail> {
         //self.state_machine.prepare(SocketOp::Close)?;
@@ -252,32 +314,36 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
     }
 
     /// Asynchronously closes this queue. This function contains all of the single-queue, asynchronous code necessary
-    /// to close a queue and any single-queue functionality after the close completes.
+    /// to close a queue and any single-queue functionality after the close completes. The queue ends up closed even if
+    /// the close fails, because it cannot be closed a second time: we then abort it to release what it holds, and
+    /// return the error of the close.
     pub async fn close_coroutine(&mut self) -> Result<(), Fail> {
-        match self.transport.clone().close(&mut self.socket).await {
-            Ok(()) => {
-                self.state_machine.prepare(SocketOp::Closed)?;
-                self.state_machine.commit();
-                Ok(())
-            },
-            Err(e) => Err(e),
+        let result: Result<(), Fail> = self.transport.clone().close(&mut self.socket).await;
+        if let Err(ref e) = result {
+            warn!("close_coroutine(): close failed, aborting instead (error={:?})", e);
+            if let Err(e) = self.transport.clone().abort(&mut self.socket) {
+                warn!("close_coroutine(): abort failed (error={:?})", e);
+            }
         }
+        self.state_machine.prepare(SocketOp::Closed)?;
+        self.state_machine.commit();
+        result
     }
 
     /// Schedule a coroutine to push to this queue. This function contains all of the single-queue,
     /// asynchronous code necessary to run push a buffer and any single-queue functionality after the push completes.
-    pub fn push<F>(&mut self, coroutine_constructor: F) -> Result<QToken, Fail>
+    pub fn push<F>(&mut self, addr: Option<SocketAddr>, coroutine_constructor: F) -> Result<QToken, Fail>
     where
         F: FnOnce() -> Result<QToken, Fail>,
     {
-        self.state_machine.may_push()?;
+        self.state_machine.may_push_to(addr)?;
         coroutine_constructor()
     }
 
     /// Asynchronously push data to the queue. This function contains all of the single-queue, asynchronous code
     /// necessary to push to the queue and any single-queue functionality after the push completes.
     pub async fn push_coroutine(&mut self, buf: &mut DemiBuffer, addr: Option<SocketAddr>) -> Result<(), Fail> {
-        self.state_machine.may_push()?;
+        self.state_machine.may_push_to(addr)?;
 
         let result = {
             let mut state_machine: SocketStateMachine = self.state_machine.clone();
@@ -298,6 +364,33 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
         result
     }
 
+    /// Schedules a coroutine that waits until this queue is writable.
+    pub fn writable<F>(&mut self, coroutine_constructor: F) -> Result<QToken, Fail>
+    where
+        F: FnOnce() -> Result<QToken, Fail>,
+    {
+        self.state_machine.may_push()?;
+        coroutine_constructor()
+    }
+
+    /// Asynchronously waits until at least `min_bytes` can be pushed to the queue without queueing behind flow or
+    /// congestion control.
+    pub async fn writable_coroutine(&mut self, min_bytes: usize) -> Result<(), Fail> {
+        self.state_machine.may_push()?;
+
+        let mut state_machine: SocketStateMachine = self.state_machine.clone();
+        let mut transport: T = self.transport.clone();
+        let state_tracker = state_machine.while_may_push().fuse();
+        let operation = transport.wait_for_writable(&mut self.socket, min_bytes).fuse();
+        pin_mut!(state_tracker);
+        pin_mut!(operation);
+
+        select_biased! {
+            fail = state_tracker => Err(fail),
+            result = operation => result,
+        }
+    }
+
     /// Schedules a coroutine to pop from this queue. This function contains all of the single-queue,
     /// asynchronous code necessary to pop a buffer from this queue and any single-queue functionality after the pop
     /// completes.
@@ -311,14 +404,18 @@ impl<T: NetworkTransport> SharedNetworkQueue<T> {
 
     /// Asynchronously pops data from the queue. This function contains all of the single-queue, asynchronous code
     /// necessary to pop from a queue and any single-queue functionality after the pop completes.
-    pub async fn pop_coroutine(&mut self, size: Option<usize>) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
+    pub async fn pop_coroutine(
+        &mut self,
+        size: Option<usize>,
+        flags: PopFlags,
+    ) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
         self.state_machine.may_pop()?;
         let size: usize = size.unwrap_or(limits::RECVBUF_SIZE_MAX);
 
         let mut state_machine: SocketStateMachine = self.state_machine.clone();
         let mut transport: T = self.transport.clone();
         let state_tracker = state_machine.while_may_pop().fuse();
-        let operation = transport.pop(&mut self.socket, size).fuse();
+        let operation = transport.pop(&mut self.socket, size, flags).fuse();
         pin_mut!(state_tracker);
         pin_mut!(operation);
 
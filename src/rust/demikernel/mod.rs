@@ -1,6 +1,8 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+pub mod balancer;
 pub mod bindings;
+pub mod completion_ring;
 pub mod config;
 pub mod libos;
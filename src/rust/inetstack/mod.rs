@@ -10,28 +10,40 @@ use crate::runtime::network::types::MacAddress;
 use crate::{
     demi_sgarray_t,
     demikernel::config::Config,
-    inetstack::protocols::layer4::{Peer, Socket},
+    inetstack::protocols::layer4::{
+        extension::{ExtensionTransmitter, ProtocolExtension},
+        rmp::{SharedRmpPeer, RMP_PROTOCOL},
+        tcp::congestion_control::CongestionControlConstructor,
+        tcp::handoff::{ConnectionHandoff, CoreHint, HandoffSender},
+        Peer, Socket,
+    },
     runtime::{
         fail::Fail,
         memory::{DemiBuffer, MemoryRuntime},
-        network::{socket::option::SocketOption, transport::NetworkTransport},
+        network::{
+            socket::{option::SocketOption, PopFlags},
+            transport::NetworkTransport,
+        },
         poll_yield, SharedDemiRuntime, SharedObject,
     },
 };
 use ::socket2::{Domain, Type};
 #[cfg(test)]
-use ::std::{collections::HashMap, hash::RandomState, net::Ipv4Addr, time::Duration};
+use ::std::{collections::HashMap, hash::RandomState, time::Duration};
 use protocols::{layer1::PhysicalLayer, layer2::SharedLayer2Endpoint, layer3::SharedLayer3Endpoint};
 
 use ::futures::FutureExt;
 use ::std::{
     fmt::Debug,
-    net::{SocketAddr, SocketAddrV4},
+    net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4},
     ops::{Deref, DerefMut},
 };
 
 use crate::timer;
 
+#[cfg(feature = "homa")]
+use crate::inetstack::protocols::layer4::homa::{SharedHomaPeer, HOMA_PROTOCOL};
+
 //======================================================================================================================
 // Exports
 //======================================================================================================================
@@ -101,10 +113,88 @@ impl SharedInetStack {
             for _ in 0..MAX_RECV_ITERS {
                 self.layer4_endpoint.poll_once();
             }
+            self.layer4_endpoint.end_poll_iteration();
             poll_yield().await;
         }
     }
 
+    /// Changes the local IPv4 address of the stack at runtime, for instance when a standby takes over a virtual IP
+    /// address. The new address is announced with a gratuitous ARP and sockets that are bound to the old address, but not
+    /// connected, are rebound to the new one.
+    pub fn set_local_ipv4(&mut self, local_ipv4_addr: Ipv4Addr) -> Result<(), Fail> {
+        self.layer4_endpoint.set_local_addr(local_ipv4_addr)
+    }
+
+    /// Pushes `buf` to the TCP socket `sd` without waiting for room in its send buffer or for acknowledgement. This fails
+    /// with EAGAIN when the send buffer is full, so that applications can do their own flow control.
+    pub fn try_push(&mut self, sd: &mut Socket, buf: &mut DemiBuffer) -> Result<(), Fail> {
+        self.layer4_endpoint.try_push(sd, buf)
+    }
+
+    /// Registers a protocol extension for IP protocol number `protocol`, so that it receives the datagrams of this protocol.
+    /// The extension sends its own datagrams through the returned transmitter.
+    pub fn register_protocol(
+        &mut self,
+        protocol: u8,
+        extension: Box<dyn ProtocolExtension>,
+    ) -> Result<ExtensionTransmitter, Fail> {
+        self.layer4_endpoint.register_protocol(protocol, extension)
+    }
+
+    /// Removes the protocol extension that is registered for IP protocol number `protocol`.
+    pub fn unregister_protocol(&mut self, protocol: u8) -> Result<Box<dyn ProtocolExtension>, Fail> {
+        self.layer4_endpoint.unregister_protocol(protocol)
+    }
+
+    /// Registers the experimental RMP message transport, which then sends datagrams of up to `mss` bytes and paces them
+    /// with the congestion control algorithm that `congestion_control` constructs.
+    pub fn register_rmp(
+        &mut self,
+        mss: usize,
+        congestion_control: CongestionControlConstructor,
+    ) -> Result<SharedRmpPeer, Fail> {
+        let mut peer: SharedRmpPeer = SharedRmpPeer::new(self.runtime.clone(), mss, congestion_control);
+        let transmitter: ExtensionTransmitter = self.register_protocol(RMP_PROTOCOL, Box::new(peer.clone()))?;
+        peer.attach(transmitter);
+        Ok(peer)
+    }
+
+    /// Registers the experimental Homa transport, which sends packets of at most `mss` bytes and keeps `rtt_bytes` of
+    /// each message in flight, as a protocol extension and returns its peer.
+    #[cfg(feature = "homa")]
+    pub fn register_homa(&mut self, mss: usize, rtt_bytes: u32) -> Result<SharedHomaPeer, Fail> {
+        let mut peer: SharedHomaPeer = SharedHomaPeer::new(self.runtime.clone(), mss, rtt_bytes);
+        let transmitter: ExtensionTransmitter = self.register_protocol(HOMA_PROTOCOL, Box::new(peer.clone()))?;
+        peer.attach(transmitter)?;
+        Ok(peer)
+    }
+
+    /// Hands connections that the listening socket `sd` establishes off to worker cores through `handoff`, instead of
+    /// queuing them for accept. Worker cores take them over with [SharedInetStack::adopt_tcp_connection].
+    pub fn set_tcp_handoff(&mut self, sd: &mut Socket, handoff: HandoffSender) -> Result<(), Fail> {
+        self.layer4_endpoint.set_tcp_handoff(sd, handoff)
+    }
+
+    /// Sets the hand-off channels that accept hints on the listening socket `sd` refer to, one per worker core. The
+    /// channel at index `i` belongs to the worker core with hint `i`.
+    pub fn set_tcp_handoff_targets(&mut self, sd: &mut Socket, targets: Vec<HandoffSender>) -> Result<(), Fail> {
+        self.layer4_endpoint.set_tcp_handoff_targets(sd, targets)
+    }
+
+    /// Waits for the next connection on the listening socket `sd` and hands it off to the worker core with hint
+    /// `core`, so that its control block and buffers are allocated on that core once the worker takes it over with
+    /// [SharedInetStack::adopt_tcp_connection]. Connections that the application opens itself are allocated on the core
+    /// that calls connect, so they should be opened from the core that is meant to serve them.
+    pub async fn accept_on_core(&mut self, sd: &mut Socket, core: CoreHint) -> Result<SocketAddr, Fail> {
+        self.layer4_endpoint.accept_on_core(sd, core).await
+    }
+
+    /// Takes over a TCP connection that a listening socket on another core handed off. The connection is scheduled on
+    /// the runtime of this stack from then on.
+    pub fn adopt_tcp_connection(&mut self, handoff: ConnectionHandoff) -> Result<(Socket, SocketAddr), Fail> {
+        self.layer4_endpoint.adopt_tcp_connection(handoff)
+    }
+
     #[cfg(test)]
     /// Schedule a ping.
     pub async fn ping(&mut self, addr: Ipv4Addr, timeout: Option<Duration>) -> Result<Duration, Fail> {
@@ -186,6 +276,14 @@ impl NetworkTransport for SharedInetStack {
         self.layer4_endpoint.getpeername(sd)
     }
 
+    fn getsockname(&mut self, sd: &mut Self::SocketDescriptor) -> Result<SocketAddrV4, Fail> {
+        self.layer4_endpoint.getsockname(sd)
+    }
+
+    fn shutdown(&mut self, sd: &mut Self::SocketDescriptor, how: Shutdown) -> Result<(), Fail> {
+        self.layer4_endpoint.shutdown(sd, how)
+    }
+
     ///
     /// **Brief**
     ///
@@ -221,6 +319,18 @@ impl NetworkTransport for SharedInetStack {
         self.layer4_endpoint.listen(sd, backlog)
     }
 
+    fn has_pending_connections(&mut self, sd: &mut Self::SocketDescriptor) -> Result<bool, Fail> {
+        self.layer4_endpoint.has_pending_connections(sd)
+    }
+
+    fn get_last_error(&mut self, sd: &mut Self::SocketDescriptor) -> Result<Option<Fail>, Fail> {
+        self.layer4_endpoint.get_last_error(sd)
+    }
+
+    fn get_buffered_bytes(&mut self, sd: &mut Self::SocketDescriptor) -> Result<usize, Fail> {
+        self.layer4_endpoint.get_buffered_bytes(sd)
+    }
+
     ///
     /// **Brief**
     ///
@@ -267,6 +377,11 @@ impl NetworkTransport for SharedInetStack {
         self.layer4_endpoint.close(sd).await
     }
 
+    /// Closes a socket right away, resetting its connection if it has one.
+    fn abort(&mut self, sd: &mut Self::SocketDescriptor) -> Result<(), Fail> {
+        self.layer4_endpoint.abort(sd)
+    }
+
     /// Forcibly close a socket. This should only be used on clean up.
     fn hard_close(&mut self, sd: &mut Self::SocketDescriptor) -> Result<(), Fail> {
         self.layer4_endpoint.hard_close(sd)
@@ -284,14 +399,19 @@ impl NetworkTransport for SharedInetStack {
         self.layer4_endpoint.push(sd, buf, addr).await
     }
 
+    async fn wait_for_writable(&mut self, sd: &mut Self::SocketDescriptor, min_bytes: usize) -> Result<(), Fail> {
+        self.layer4_endpoint.wait_for_writable(sd, min_bytes).await
+    }
+
     /// Create a pop request to write data from IO connection represented by `qd` into a buffer
     /// allocated by the application.
     async fn pop(
         &mut self,
         sd: &mut Self::SocketDescriptor,
         size: usize,
+        flags: PopFlags,
     ) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
-        self.layer4_endpoint.pop(sd, size).await
+        self.layer4_endpoint.pop(sd, size, flags).await
     }
 
     fn get_runtime(&self) -> &SharedDemiRuntime {
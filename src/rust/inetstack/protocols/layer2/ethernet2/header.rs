@@ -67,6 +67,8 @@ impl Ethernet2Header {
     /// Create and prepend the ethernet header onto the packet in [buf].
     pub fn serialize_and_attach(&self, buf: &mut DemiBuffer) {
         buf.prepend(ETHERNET2_HEADER_SIZE).expect("Should have enough headroom");
+        // The header always lives in the first segment, even if the payload is a chain.
+        let buf: &mut [u8] = buf.first_segment_mut();
         buf[0..6].copy_from_slice(&self.dst_addr.octets());
         buf[6..12].copy_from_slice(&self.src_addr.octets());
         buf[12..14].copy_from_slice(&(self.ether_type as u16).to_be_bytes());
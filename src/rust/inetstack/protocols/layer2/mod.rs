@@ -15,16 +15,20 @@ pub use self::ethernet2::{
 // Imports
 //======================================================================================================================
 
+#[cfg(feature = "fault-injection")]
+use crate::runtime::fault::{self, FaultModule};
 use crate::{
     demi_sgarray_t,
     demikernel::config::Config,
     inetstack::protocols::layer1::PhysicalLayer,
+    perftools::stages::{self, Stage},
     runtime::{
         fail::Fail,
         memory::{DemiBuffer, MemoryRuntime},
         network::{consts::RECEIVE_BATCH_SIZE, types::MacAddress},
         SharedObject,
     },
+    stage_timer,
 };
 use ::arrayvec::ArrayVec;
 use ::std::ops::{Deref, DerefMut};
@@ -53,9 +57,15 @@ impl SharedLayer2Endpoint {
         })))
     }
 
-    pub fn receive(&mut self) -> Result<ArrayVec<(EtherType2, DemiBuffer), RECEIVE_BATCH_SIZE>, Fail> {
-        let mut batch: ArrayVec<(EtherType2, DemiBuffer), RECEIVE_BATCH_SIZE> = ArrayVec::new();
-        for mut pkt in self.layer1_endpoint.receive()? {
+    /// Receives a batch of frames. Along with each payload, we hand over its type and the link address of its sender.
+    pub fn receive(&mut self) -> Result<ArrayVec<(EtherType2, MacAddress, DemiBuffer), RECEIVE_BATCH_SIZE>, Fail> {
+        let mut batch: ArrayVec<(EtherType2, MacAddress, DemiBuffer), RECEIVE_BATCH_SIZE> = ArrayVec::new();
+        let packets: ArrayVec<DemiBuffer, RECEIVE_BATCH_SIZE> = {
+            stage_timer!(Stage::DriverPoll);
+            self.layer1_endpoint.receive()?
+        };
+        stages::count_packets(packets.len());
+        for mut pkt in packets {
             let header: Ethernet2Header = match Ethernet2Header::parse_and_strip(&mut pkt) {
                 Ok(result) => result,
                 Err(e) => {
@@ -73,7 +83,7 @@ impl SharedLayer2Endpoint {
                 let cause: &str = "invalid link address";
                 warn!("dropping packet: {}", cause);
             }
-            batch.push((header.ether_type(), pkt))
+            batch.push((header.ether_type(), header.src_addr(), pkt))
         }
         Ok(batch)
     }
@@ -92,6 +102,9 @@ impl SharedLayer2Endpoint {
         eth2_type: EtherType2,
         mut pkt: DemiBuffer,
     ) -> Result<(), Fail> {
+        #[cfg(feature = "fault-injection")]
+        fault::inject_failure(FaultModule::Network, "transmit")?;
+
         let eth2_header: Ethernet2Header = Ethernet2Header::new(remote_link_addr, self.local_link_addr, eth2_type);
         eth2_header.serialize_and_attach(&mut pkt);
         self.layer1_endpoint.transmit(pkt)
@@ -17,7 +17,7 @@ use crate::{
         fail::Fail,
         memory::DemiBuffer,
         network::{config::ArpConfig, types::MacAddress},
-        SharedDemiRuntime, SharedObject,
+        yield_with_timeout, SharedDemiRuntime, SharedObject,
     },
 };
 use ::futures::{
@@ -36,16 +36,34 @@ use ::std::{
 // Constants
 //======================================================================================================================
 
+/// Time between two consecutive ARP probes (PROBE_MIN in RFC 5227).
+const ARP_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time to wait for a conflicting reply after the last ARP probe (ANNOUNCE_WAIT in RFC 5227).
+const ARP_ANNOUNCE_WAIT: Duration = Duration::from_secs(2);
+
 //======================================================================================================================
 // Structures
 //======================================================================================================================
 
+/// State of the local IPv4 address with respect to duplicate address detection (see RFC 5227).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AddressState {
+    /// We are still probing for the address, so we may not use it yet.
+    Tentative,
+    /// No other host claimed the address, so we may use it.
+    Assigned,
+    /// The host with the given link address uses the address too, so we stopped using it.
+    Conflict(MacAddress),
+}
+
 ///
 /// Arp Peer
 ///
 pub struct ArpPeer {
     layer2_endpoint: SharedLayer2Endpoint,
     local_ipv4_addr: Ipv4Addr,
+    address_state: AddressState,
     cache: ArpCache,
     waiters: HashMap<Ipv4Addr, LinkedList<Sender<MacAddress>>>,
     arp_config: ArpConfig,
@@ -76,9 +94,15 @@ impl SharedArpPeer {
             arp_config.is_enabled(),
         );
 
+        let address_state: AddressState = if arp_config.get_probe_count() > 0 {
+            AddressState::Tentative
+        } else {
+            AddressState::Assigned
+        };
         let peer: SharedArpPeer = Self(SharedObject::new(ArpPeer {
             layer2_endpoint,
             local_ipv4_addr: config.local_ipv4_addr()?,
+            address_state,
             cache,
             waiters: HashMap::default(),
             arp_config,
@@ -86,6 +110,9 @@ impl SharedArpPeer {
         }));
         // This is a future returned by the async function.
         runtime.insert_background_coroutine("bgc::inetstack::arp::background", Box::pin(peer.clone().poll().fuse()))?;
+        if address_state == AddressState::Tentative {
+            runtime.insert_background_coroutine("bgc::inetstack::arp::probe", Box::pin(peer.clone().probe().fuse()))?;
+        }
         Ok(peer.clone())
     }
 
@@ -147,6 +174,10 @@ impl SharedArpPeer {
             };
             debug!("Received {:?}", header);
 
+            if self.arp_config.get_probe_count() > 0 {
+                self.detect_conflict(&header);
+            }
+
             // from RFC 826:
             // > Merge_flag := false
             // > If the pair <protocol type, sender protocol address> is
@@ -172,7 +203,10 @@ impl SharedArpPeer {
                 }
             };
             // from RFC 826: ?Am I the target protocol address?
-            if header.get_destination_protocol_addr() != self.local_ipv4_addr {
+            // An ARP probe from another host has no sender protocol address, so there is nothing to learn from it.
+            if header.get_destination_protocol_addr() != self.local_ipv4_addr
+                || header.get_sender_protocol_addr().is_unspecified()
+            {
                 if !merge_flag {
                     // we didn't do something.
                     let cause: String = format!("unrecognized IP address");
@@ -199,6 +233,10 @@ impl SharedArpPeer {
             }
 
             match header.get_operation() {
+                // We may not answer for an address that we are still probing for or that another host uses.
+                ArpOperation::Request if self.address_state != AddressState::Assigned => {
+                    debug!("not answering for address in state {:?}", self.address_state);
+                },
                 ArpOperation::Request => {
                     // from RFC 826:
                     // > Swap hardware and protocol fields, putting the local
@@ -235,11 +273,140 @@ impl SharedArpPeer {
         }
     }
 
+    /// Probes for the local IPv4 address before using it (see RFC 5227). If no other host claims the address, it gets
+    /// assigned and announced. Otherwise, the address stays unusable until it is changed.
+    async fn probe(mut self) {
+        let probe_count: usize = self.arp_config.get_probe_count();
+        for i in 0..probe_count {
+            if self.address_state != AddressState::Tentative {
+                return;
+            }
+            // from RFC 5227:
+            // > The 'sender IP address' field MUST be set to all zeroes; this is to avoid polluting ARP caches in other
+            // > hosts on the same link in the case where the address turns out to be already in use by another host.
+            let header: ArpHeader = ArpHeader::new(
+                ArpOperation::Request,
+                self.layer2_endpoint.get_local_link_addr(),
+                Ipv4Addr::UNSPECIFIED,
+                MacAddress::nil(),
+                self.local_ipv4_addr,
+            );
+            debug!("Probing {:?}", header);
+            if let Err(e) = self
+                .layer2_endpoint
+                .transmit_arp_packet(MacAddress::broadcast(), header.create_and_serialize())
+            {
+                warn!("Could not send ARP probe: {:?}", e);
+            }
+            let timeout: Duration = if i + 1 < probe_count {
+                ARP_PROBE_INTERVAL
+            } else {
+                ARP_ANNOUNCE_WAIT
+            };
+            yield_with_timeout(timeout).await;
+        }
+        if self.address_state == AddressState::Tentative {
+            info!(
+                "probe(): no conflict found (local_ipv4_addr={:?})",
+                self.local_ipv4_addr
+            );
+            self.address_state = AddressState::Assigned;
+            self.announce();
+        }
+    }
+
+    /// Checks whether an incoming ARP packet shows that another host uses our address.
+    fn detect_conflict(&mut self, header: &ArpHeader) {
+        if header.get_sender_hardware_addr() == self.layer2_endpoint.get_local_link_addr() {
+            return;
+        }
+        // from RFC 5227:
+        // > If [...] the host receives any ARP packet (Request *or* Reply) on the interface where the probe is being
+        // > performed, where the packet's 'sender IP address' is the address being probed for, then the host MUST
+        // > treat this address as being in use by some other host [...]. In addition, if during this period the host
+        // > receives any ARP Probe where the packet's 'target IP address' is the address being probed for, and the
+        // > packet's 'sender hardware address' is not the hardware address of any of the host's interfaces, then the
+        // > host SHOULD similarly treat this as an address conflict.
+        let is_claimed: bool = header.get_sender_protocol_addr() == self.local_ipv4_addr;
+        let is_probed: bool = self.address_state == AddressState::Tentative
+            && header.get_sender_protocol_addr().is_unspecified()
+            && header.get_destination_protocol_addr() == self.local_ipv4_addr;
+        if (is_claimed || is_probed) && self.address_state != AddressState::Conflict(header.get_sender_hardware_addr())
+        {
+            error!(
+                "detect_conflict(): local address is in use by another host, refusing to use it (local_ipv4_addr={:?}, \
+                 remote_link_addr={:?})",
+                self.local_ipv4_addr,
+                header.get_sender_hardware_addr()
+            );
+            self.address_state = AddressState::Conflict(header.get_sender_hardware_addr());
+        }
+    }
+
+    /// Fails if the local IPv4 address may not be used, either because we are still probing for it or because another
+    /// host uses it.
+    pub fn check_local_addr(&self) -> Result<(), Fail> {
+        match self.address_state {
+            AddressState::Assigned => Ok(()),
+            AddressState::Tentative => {
+                let cause: String = format!(
+                    "still probing for local address (local_ipv4_addr={:?})",
+                    self.local_ipv4_addr
+                );
+                warn!("check_local_addr(): {}", cause);
+                Err(Fail::new(libc::EADDRNOTAVAIL, &cause))
+            },
+            AddressState::Conflict(remote_link_addr) => {
+                let cause: String = format!(
+                    "local address is in use by another host (local_ipv4_addr={:?}, remote_link_addr={:?})",
+                    self.local_ipv4_addr, remote_link_addr
+                );
+                error!("check_local_addr(): {}", cause);
+                Err(Fail::new(libc::EADDRINUSE, &cause))
+            },
+        }
+    }
+
+    /// Changes the local IPv4 address and announces it with a gratuitous ARP, so that our neighbors update their caches
+    /// right away instead of waiting for their entries to expire.
+    pub fn set_local_addr(&mut self, local_ipv4_addr: Ipv4Addr) {
+        self.local_ipv4_addr = local_ipv4_addr;
+        match self.address_state {
+            // The new address is announced once probing completes.
+            AddressState::Tentative => return,
+            // The conflict was on the previous address.
+            AddressState::Conflict(_) => self.address_state = AddressState::Assigned,
+            AddressState::Assigned => (),
+        }
+        self.announce();
+    }
+
+    /// Sends an ARP announcement for the local IPv4 address. This is an ARP request where both the sender and the
+    /// target protocol addresses are ours (see RFC 5227).
+    fn announce(&mut self) {
+        let header: ArpHeader = ArpHeader::new(
+            ArpOperation::Request,
+            self.layer2_endpoint.get_local_link_addr(),
+            self.local_ipv4_addr,
+            MacAddress::nil(),
+            self.local_ipv4_addr,
+        );
+        debug!("Announcing {:?}", header);
+        if let Err(e) = self
+            .layer2_endpoint
+            .transmit_arp_packet(MacAddress::broadcast(), header.create_and_serialize())
+        {
+            // Our neighbors still learn the new address when they query it.
+            warn!("Could not send ARP announcement: {:?}", e);
+        }
+    }
+
     pub fn try_query(&self, ipv4_addr: Ipv4Addr) -> Option<MacAddress> {
         self.cache.get(ipv4_addr).cloned()
     }
 
     pub async fn query(&mut self, ipv4_addr: Ipv4Addr) -> Result<MacAddress, Fail> {
+        self.check_local_addr()?;
         if let Some(&link_addr) = self.cache.get(ipv4_addr) {
             return Ok(link_addr);
         }
@@ -153,6 +153,143 @@ fn arp_cache_update() -> Result<()> {
     Ok(())
 }
 
+/// Tests that changing the local address announces the new address and answers queries for it.
+#[test]
+fn arp_announce_on_address_change() -> Result<()> {
+    let mut now: Instant = Instant::now();
+    let local_mac: MacAddress = test_helpers::ALICE_MAC;
+    let new_local_ipv4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 42);
+    let remote_mac: MacAddress = test_helpers::BOB_MAC;
+    let remote_ipv4: Ipv4Addr = test_helpers::BOB_IPV4;
+    let mut engine: SharedEngine = new_engine(now, &test_helpers::ALICE_CONFIG_PATH)?;
+
+    engine.get_transport().set_local_ipv4(new_local_ipv4)?;
+
+    // Check that a gratuitous ARP was broadcast for the new address.
+    let mut buffers: VecDeque<DemiBuffer> = engine.pop_all_frames();
+    crate::ensure_eq!(buffers.len(), 1);
+    let mut pkt: DemiBuffer = buffers.pop_front().unwrap();
+    let eth2_header: Ethernet2Header = Ethernet2Header::parse_and_strip(&mut pkt)?;
+    crate::ensure_eq!(eth2_header.dst_addr(), MacAddress::broadcast());
+    crate::ensure_eq!(eth2_header.src_addr(), local_mac);
+    crate::ensure_eq!(eth2_header.ether_type(), EtherType2::Arp);
+    let arp_header: ArpHeader = ArpHeader::parse_and_consume(pkt)?;
+    crate::ensure_eq!(arp_header.get_operation(), ArpOperation::Request);
+    crate::ensure_eq!(arp_header.get_sender_hardware_addr(), local_mac);
+    crate::ensure_eq!(arp_header.get_sender_protocol_addr(), new_local_ipv4);
+    crate::ensure_eq!(arp_header.get_destination_protocol_addr(), new_local_ipv4);
+
+    // Check that queries for the new address are answered.
+    engine.push_frame(build_arp_query(&remote_mac, &remote_ipv4, &new_local_ipv4));
+    now += Duration::from_micros(1);
+    engine.advance_clock(now);
+    engine.poll();
+    let mut buffers: VecDeque<DemiBuffer> = engine.pop_all_frames();
+    crate::ensure_eq!(buffers.len(), 1);
+    let mut pkt: DemiBuffer = buffers.pop_front().unwrap();
+    Ethernet2Header::parse_and_strip(&mut pkt)?;
+    let arp_header: ArpHeader = ArpHeader::parse_and_consume(pkt)?;
+    crate::ensure_eq!(arp_header.get_operation(), ArpOperation::Reply);
+    crate::ensure_eq!(arp_header.get_sender_protocol_addr(), new_local_ipv4);
+
+    Ok(())
+}
+
+/// Tests that the local address is probed for, and then announced, before being used.
+#[test]
+fn arp_probe_before_use() -> Result<()> {
+    let mut now: Instant = Instant::now();
+    let local_mac: MacAddress = test_helpers::ALICE_MAC;
+    let local_ipv4: Ipv4Addr = test_helpers::ALICE_IPV4;
+    let remote_mac: MacAddress = test_helpers::BOB_MAC;
+    let remote_ipv4: Ipv4Addr = test_helpers::BOB_IPV4;
+    let mut engine: SharedEngine = new_engine(now, test_helpers::ALICE_PROBE_CONFIG_PATH)?;
+
+    // Check that a probe is broadcast for each configured attempt.
+    for _ in 0..2 {
+        engine.poll();
+        let mut buffers: VecDeque<DemiBuffer> = engine.pop_all_frames();
+        crate::ensure_eq!(buffers.len(), 1);
+        let mut pkt: DemiBuffer = buffers.pop_front().unwrap();
+        let eth2_header: Ethernet2Header = Ethernet2Header::parse_and_strip(&mut pkt)?;
+        crate::ensure_eq!(eth2_header.dst_addr(), MacAddress::broadcast());
+        crate::ensure_eq!(eth2_header.src_addr(), local_mac);
+        let arp_header: ArpHeader = ArpHeader::parse_and_consume(pkt)?;
+        crate::ensure_eq!(arp_header.get_operation(), ArpOperation::Request);
+        crate::ensure_eq!(arp_header.get_sender_hardware_addr(), local_mac);
+        crate::ensure_eq!(arp_header.get_sender_protocol_addr(), Ipv4Addr::UNSPECIFIED);
+        crate::ensure_eq!(arp_header.get_destination_protocol_addr(), local_ipv4);
+
+        // Queries for the address are not answered while probing.
+        engine.push_frame(build_arp_query(&remote_mac, &remote_ipv4, &local_ipv4));
+        engine.poll();
+        crate::ensure_eq!(engine.pop_all_frames().len(), 0);
+
+        now += Duration::from_secs(1);
+        engine.advance_clock(now);
+    }
+
+    // Check that the address is announced once nobody claimed it.
+    now += Duration::from_secs(1);
+    engine.advance_clock(now);
+    engine.poll();
+    let mut buffers: VecDeque<DemiBuffer> = engine.pop_all_frames();
+    crate::ensure_eq!(buffers.len(), 1);
+    let mut pkt: DemiBuffer = buffers.pop_front().unwrap();
+    Ethernet2Header::parse_and_strip(&mut pkt)?;
+    let arp_header: ArpHeader = ArpHeader::parse_and_consume(pkt)?;
+    crate::ensure_eq!(arp_header.get_operation(), ArpOperation::Request);
+    crate::ensure_eq!(arp_header.get_sender_protocol_addr(), local_ipv4);
+    crate::ensure_eq!(arp_header.get_destination_protocol_addr(), local_ipv4);
+
+    // Check that queries for the address are answered from now on.
+    engine.push_frame(build_arp_query(&remote_mac, &remote_ipv4, &local_ipv4));
+    engine.poll();
+    let mut buffers: VecDeque<DemiBuffer> = engine.pop_all_frames();
+    crate::ensure_eq!(buffers.len(), 1);
+    let mut pkt: DemiBuffer = buffers.pop_front().unwrap();
+    Ethernet2Header::parse_and_strip(&mut pkt)?;
+    let arp_header: ArpHeader = ArpHeader::parse_and_consume(pkt)?;
+    crate::ensure_eq!(arp_header.get_operation(), ArpOperation::Reply);
+    crate::ensure_eq!(arp_header.get_sender_protocol_addr(), local_ipv4);
+
+    Ok(())
+}
+
+/// Tests that the local address is not used when another host answers a probe for it.
+#[test]
+fn arp_probe_conflict() -> Result<()> {
+    let mut now: Instant = Instant::now();
+    let local_ipv4: Ipv4Addr = test_helpers::ALICE_IPV4;
+    let remote_mac: MacAddress = test_helpers::BOB_MAC;
+    let remote_ipv4: Ipv4Addr = test_helpers::BOB_IPV4;
+    let mut engine: SharedEngine = new_engine(now, test_helpers::ALICE_PROBE_CONFIG_PATH)?;
+
+    engine.poll();
+    crate::ensure_eq!(engine.pop_all_frames().len(), 1);
+
+    // Another host claims our address.
+    engine.push_frame(build_arp_query(&remote_mac, &local_ipv4, &remote_ipv4));
+    engine.poll();
+
+    // Check that probing stops and the address is never announced.
+    for _ in 0..3 {
+        now += Duration::from_secs(1);
+        engine.advance_clock(now);
+        engine.poll();
+        crate::ensure_eq!(engine.pop_all_frames().len(), 0);
+    }
+
+    // Ensure that sending fails with EADDRINUSE. The query fails before it sends anything, so polling it once suffices.
+    let mut inetstack: SharedInetStack = engine.get_transport();
+    match inetstack.arp_query(remote_ipv4).now_or_never() {
+        Some(Err(err)) => crate::ensure_eq!(err.errno, libc::EADDRINUSE),
+        result => anyhow::bail!("arp query must fail with EADDRINUSE (result={:?})", result),
+    }
+
+    Ok(())
+}
+
 #[test]
 fn arp_cache_timeout() -> Result<()> {
     use crate::QToken;
@@ -94,4 +94,8 @@ impl Icmpv4Header {
     pub fn get_protocol(&self) -> Icmpv4Type2 {
         self.protocol
     }
+
+    pub fn get_code(&self) -> u8 {
+        self.code
+    }
 }
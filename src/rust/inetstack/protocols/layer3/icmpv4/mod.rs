@@ -2,6 +2,7 @@
 // Licensed under the MIT license.
 
 mod header;
+mod path_mtu;
 mod peer;
 mod protocol;
 
@@ -9,4 +10,5 @@ mod protocol;
 // #[cfg(test)]
 // mod tests;
 
+pub use path_mtu::{FragmentationNeeded, QUOTED_TRANSPORT_HEADER_SIZE};
 pub use peer::SharedIcmpv4Peer;
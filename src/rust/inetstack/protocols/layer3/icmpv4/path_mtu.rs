@@ -0,0 +1,177 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// We send every datagram with the DF flag set, so routers that cannot forward one of them drop it and send an ICMP
+// Fragmentation Needed message back to us (RFC 1191). The message quotes the header of the datagram that was dropped,
+// which tells the transport protocol the connection whose segments have to get smaller.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    inetstack::protocols::layer3::{
+        icmpv4::{header::Icmpv4Header, protocol::Icmpv4Type2},
+        ip::IpProtocol,
+        ipv4::IPV4_HEADER_MIN_SIZE,
+    },
+    runtime::memory::DemiBuffer,
+};
+use ::std::net::Ipv4Addr;
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+// Code of Destination Unreachable messages that report a datagram that needed fragmentation although it had DF set.
+const ICMPV4_CODE_FRAGMENTATION_NEEDED: u8 = 4;
+
+/// Number of bytes of the transport header of the dropped datagram that an ICMP error message quotes at least.
+pub const QUOTED_TRANSPORT_HEADER_SIZE: usize = 8;
+
+// MTUs of common links, for routers that do not report the MTU of the next hop (RFC 1191 Section 7).
+const MTU_PLATEAUS: [u16; 10] = [32000, 17914, 8166, 4352, 2002, 1492, 1006, 508, 296, 68];
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// An ICMP Fragmentation Needed message about a datagram that we sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FragmentationNeeded {
+    /// Destination of the datagram that was dropped.
+    pub remote_ipv4_addr: Ipv4Addr,
+    pub protocol: IpProtocol,
+    /// Beginning of the transport header of the datagram, which identifies the connection that it belongs to.
+    pub transport_header: [u8; QUOTED_TRANSPORT_HEADER_SIZE],
+    /// Largest datagram that the path is able to carry.
+    pub mtu: u16,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl FragmentationNeeded {
+    /// Parses the ICMP message in [buf], which was sent to [local_ipv4_addr]. Returns None if it is not a valid
+    /// Fragmentation Needed message about a datagram that we sent.
+    pub fn parse(local_ipv4_addr: Ipv4Addr, buf: &DemiBuffer) -> Option<Self> {
+        let mut icmpv4_buf: DemiBuffer = buf.clone();
+        let icmpv4_hdr: Icmpv4Header = Icmpv4Header::parse_and_strip(&mut icmpv4_buf).ok()?;
+        let next_hop_mtu: u16 = match icmpv4_hdr.get_protocol() {
+            Icmpv4Type2::DestinationUnreachable { next_hop_mtu }
+                if icmpv4_hdr.get_code() == ICMPV4_CODE_FRAGMENTATION_NEEDED =>
+            {
+                next_hop_mtu
+            },
+            _ => return None,
+        };
+
+        // Parse the quoted header of the dropped datagram.
+        let quoted: &[u8] = &icmpv4_buf[..];
+        if quoted.len() < IPV4_HEADER_MIN_SIZE as usize {
+            return None;
+        }
+        let header_size: usize = (quoted[0] & 0xf) as usize * 4;
+        if header_size < IPV4_HEADER_MIN_SIZE as usize || quoted.len() < header_size + QUOTED_TRANSPORT_HEADER_SIZE {
+            return None;
+        }
+        let total_length: u16 = u16::from_be_bytes([quoted[2], quoted[3]]);
+        let src_ipv4_addr: Ipv4Addr = Ipv4Addr::new(quoted[12], quoted[13], quoted[14], quoted[15]);
+        if src_ipv4_addr != local_ipv4_addr {
+            warn!(
+                "parse(): ignoring ICMP message about a datagram that we did not send (src={:?})",
+                src_ipv4_addr
+            );
+            return None;
+        }
+        let mut transport_header: [u8; QUOTED_TRANSPORT_HEADER_SIZE] = [0; QUOTED_TRANSPORT_HEADER_SIZE];
+        transport_header.copy_from_slice(&quoted[header_size..header_size + QUOTED_TRANSPORT_HEADER_SIZE]);
+
+        // Routers that predate RFC 1191 do not report the MTU of the next hop, so we guess it from the size of the
+        // dropped datagram. A reported MTU that would have carried the datagram is bogus.
+        let mtu: u16 = match next_hop_mtu {
+            0 => *MTU_PLATEAUS.iter().find(|plateau| **plateau < total_length)?,
+            mtu if mtu < total_length => mtu,
+            _ => return None,
+        };
+
+        Some(Self {
+            remote_ipv4_addr: Ipv4Addr::new(quoted[16], quoted[17], quoted[18], quoted[19]),
+            protocol: IpProtocol::from(quoted[9]),
+            transport_header,
+            mtu,
+        })
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::FragmentationNeeded;
+    use crate::{
+        inetstack::protocols::layer3::{
+            icmpv4::{
+                header::{Icmpv4Header, ICMPV4_HEADER_SIZE},
+                protocol::Icmpv4Type2,
+            },
+            ip::IpProtocol,
+        },
+        runtime::memory::DemiBuffer,
+    };
+    use ::anyhow::Result;
+    use ::std::net::Ipv4Addr;
+
+    const LOCAL_IPV4: Ipv4Addr = Ipv4Addr::new(198, 0, 0, 1);
+    const REMOTE_IPV4: Ipv4Addr = Ipv4Addr::new(198, 0, 0, 2);
+
+    // Builds a Fragmentation Needed message about a TCP segment of [total_length] bytes from [src] to REMOTE_IPV4.
+    fn build_message(src: Ipv4Addr, total_length: u16, next_hop_mtu: u16) -> Result<DemiBuffer> {
+        let mut quoted: Vec<u8> = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, 6, 0, 0];
+        quoted[2..4].copy_from_slice(&total_length.to_be_bytes());
+        quoted.extend_from_slice(&src.octets());
+        quoted.extend_from_slice(&REMOTE_IPV4.octets());
+        quoted.extend_from_slice(&[0, 80, 1, 187, 0, 0, 0, 42]);
+        let mut buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&quoted, ICMPV4_HEADER_SIZE)?;
+        Icmpv4Header::new(Icmpv4Type2::DestinationUnreachable { next_hop_mtu }, 4).serialize_and_attach(&mut buf);
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_parse_fragmentation_needed() -> Result<()> {
+        let buf: DemiBuffer = build_message(LOCAL_IPV4, 1500, 1400)?;
+        let message: Option<FragmentationNeeded> = FragmentationNeeded::parse(LOCAL_IPV4, &buf);
+        crate::ensure_eq!(
+            message,
+            Some(FragmentationNeeded {
+                remote_ipv4_addr: REMOTE_IPV4,
+                protocol: IpProtocol::TCP,
+                transport_header: [0, 80, 1, 187, 0, 0, 0, 42],
+                mtu: 1400,
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fragmentation_needed_without_mtu() -> Result<()> {
+        // Old routers leave the MTU out, so we pick the next smaller plateau.
+        let buf: DemiBuffer = build_message(LOCAL_IPV4, 1500, 0)?;
+        crate::ensure_eq!(
+            FragmentationNeeded::parse(LOCAL_IPV4, &buf).map(|message| message.mtu),
+            Some(1492)
+        );
+
+        // An MTU that fits the datagram is bogus, and so is a message about a datagram that we did not send.
+        let buf: DemiBuffer = build_message(LOCAL_IPV4, 1500, 1500)?;
+        crate::ensure_eq!(FragmentationNeeded::parse(LOCAL_IPV4, &buf), None);
+        let buf: DemiBuffer = build_message(REMOTE_IPV4, 1500, 1400)?;
+        crate::ensure_eq!(FragmentationNeeded::parse(LOCAL_IPV4, &buf), None);
+
+        Ok(())
+    }
+}
@@ -10,6 +10,7 @@ use crate::{
             arp::SharedArpPeer,
             icmpv4::{
                 header::{Icmpv4Header, ICMPV4_HEADER_SIZE},
+                path_mtu::FragmentationNeeded,
                 protocol::{Icmpv4Type2, ICMPV4_ECHO_REQUEST_MESSAGE_SIZE},
             },
             ip::IpProtocol,
@@ -158,9 +159,20 @@ impl SharedIcmpv4Peer {
         }
     }
 
-    /// Parses and handles a ICMP message.
-    pub fn receive(&mut self, ipv4_hdr: Ipv4Header, buf: DemiBuffer) {
+    /// Changes the local IPv4 address that we reply from.
+    pub fn set_local_addr(&mut self, local_ipv4_addr: Ipv4Addr) {
+        self.local_ipv4_addr = local_ipv4_addr;
+    }
+
+    /// Parses and handles a ICMP message. Fragmentation Needed messages are handed back right away, as they are up to the
+    /// transport protocol that sent the datagram that was dropped.
+    pub fn receive(&mut self, ipv4_hdr: Ipv4Header, buf: DemiBuffer) -> Option<FragmentationNeeded> {
+        if let Some(message) = FragmentationNeeded::parse(self.local_ipv4_addr, &buf) {
+            debug!("receive(): {:?}", message);
+            return Some(message);
+        }
         self.recv_queue.push((ipv4_hdr, buf));
+        None
     }
 
     /// Computes the identifier for an ICMP message.
@@ -1,42 +1,47 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
-//======================================================================================================================
-// Imports
-//======================================================================================================================
-
-use crate::runtime::fail::Fail;
-
 //======================================================================================================================
 // Structures
 //======================================================================================================================
 
 /// Ipv4 Protocol
-#[repr(u8)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum IpProtocol {
     /// Internet Control Message Protocol
-    ICMPv4 = 0x01,
+    ICMPv4,
     /// Transmission Control Protocol
-    TCP = 0x06,
+    TCP,
     /// User Datagram Protocol
-    UDP = 0x11,
+    UDP,
+    /// Any other protocol, which is up to the protocol extensions that are registered with layer 4.
+    Other(u8),
 }
 
 //======================================================================================================================
 // Trait Implementations
 //======================================================================================================================
 
-/// TryFrom trait implementation.
-impl TryFrom<u8> for IpProtocol {
-    type Error = Fail;
+/// From trait implementation.
+impl From<u8> for IpProtocol {
+    fn from(value: u8) -> Self {
+        match value {
+            0x01 => IpProtocol::ICMPv4,
+            0x06 => IpProtocol::TCP,
+            0x11 => IpProtocol::UDP,
+            _ => IpProtocol::Other(value),
+        }
+    }
+}
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+/// From trait implementation.
+impl From<IpProtocol> for u8 {
+    fn from(value: IpProtocol) -> Self {
         match value {
-            0x01 => Ok(IpProtocol::ICMPv4),
-            0x06 => Ok(IpProtocol::TCP),
-            0x11 => Ok(IpProtocol::UDP),
-            _ => Err(Fail::new(libc::ENOTSUP, "unsupported IPv4 protocol")),
+            IpProtocol::ICMPv4 => 0x01,
+            IpProtocol::TCP => 0x06,
+            IpProtocol::UDP => 0x11,
+            IpProtocol::Other(value) => value,
         }
     }
 }
@@ -47,6 +47,19 @@ const IPV4_CTRL_FLAG_MF: u8 = 0x1;
 // Structures
 //======================================================================================================================
 
+/// Explicit Congestion Notification code points (RFC 3168 Section 5).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Ecn {
+    /// The transport does not support ECN.
+    NotEct = 0,
+    /// ECN-capable transport, ECT(1).
+    Ect1 = 1,
+    /// ECN-capable transport, ECT(0).
+    Ect0 = 2,
+    /// A router on the path experienced congestion.
+    Ce = 3,
+}
+
 /// IPv4 Datagram Header
 #[derive(Debug, Copy, Clone)]
 pub struct Ipv4Header {
@@ -107,6 +120,29 @@ impl Ipv4Header {
         (self.ihl as usize) << 2
     }
 
+    /// Marks the packet with the `dscp` code point, which only has 6 bits.
+    pub fn set_dscp(&mut self, dscp: u8) {
+        debug_assert!(dscp < 64);
+        self.dscp = dscp & 0x3f;
+    }
+
+    /// Marks the packet with the `ecn` code point.
+    pub fn set_ecn(&mut self, ecn: Ecn) {
+        self.ecn = ecn as u8;
+    }
+
+    pub fn get_ecn(&self) -> Ecn {
+        Ecn::from(self.ecn)
+    }
+
+    pub fn set_identification(&mut self, identification: u16) {
+        self.identification = identification;
+    }
+
+    pub fn get_identification(&self) -> u16 {
+        self.identification
+    }
+
     /// Parses and strips the IPv4 header from the packet in [buf].
     pub fn parse_and_strip(buf: &mut DemiBuffer) -> Result<Self, Fail> {
         // The datagram should be as big as the header.
@@ -130,17 +166,15 @@ impl Ipv4Header {
         }
         let hdr_buf: &[u8] = &buf[..hdr_size as usize];
 
-        // Differentiated services code point.
+        // Differentiated services code point. This is only meaningful to the sender and the network, as we do not
+        // prioritize incoming packets, so a marked packet is nothing unusual.
         let dscp: u8 = hdr_buf[1] >> 2;
         if dscp != 0 {
-            warn!("ignoring dscp field (dscp={:?})", dscp);
+            debug!("ignoring dscp field (dscp={:?})", dscp);
         }
 
-        // Explicit congestion notification.
+        // Explicit congestion notification. The transport layer decides what to make of it.
         let ecn: u8 = hdr_buf[1] & 3;
-        if ecn != 0 {
-            warn!("ignoring ecn field (ecn={:?})", ecn);
-        }
 
         let total_length: u16 = u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]);
         if total_length < hdr_size {
@@ -189,7 +223,7 @@ impl Ipv4Header {
             return Err(Fail::new(EBADMSG, "ipv4 datagram too old"));
         }
 
-        let protocol: IpProtocol = IpProtocol::try_from(hdr_buf[9])?;
+        let protocol: IpProtocol = IpProtocol::from(hdr_buf[9]);
 
         let header_checksum: u16 = u16::from_be_bytes([hdr_buf[10], hdr_buf[11]]);
         if header_checksum == 0xffff {
@@ -230,6 +264,8 @@ impl Ipv4Header {
         buf.prepend(IPV4_HEADER_MIN_SIZE as usize)
             .expect("Should be sufficient headroom");
         let pkt_size_bytes: usize = buf.len();
+        // The header always lives in the first segment, even if the payload is a chain.
+        let buf: &mut [u8] = buf.first_segment_mut();
 
         // Version + IHL.
         buf[0] = (self.version << 4) | self.ihl;
@@ -250,7 +286,7 @@ impl Ipv4Header {
         buf[8] = self.ttl;
 
         // Protocol.
-        buf[9] = self.protocol as u8;
+        buf[9] = u8::from(self.protocol);
 
         // Skip the checksum (bytes 10..12) until we finish writing the header.
 
@@ -300,3 +336,18 @@ impl Ipv4Header {
         !state as u16
     }
 }
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl From<u8> for Ecn {
+    fn from(value: u8) -> Self {
+        match value & 3 {
+            0 => Ecn::NotEct,
+            1 => Ecn::Ect1,
+            2 => Ecn::Ect0,
+            _ => Ecn::Ce,
+        }
+    }
+}
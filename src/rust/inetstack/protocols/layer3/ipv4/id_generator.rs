@@ -0,0 +1,127 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Generation of the identification field of outgoing IPv4 datagrams. The field has to be unique among the datagrams of a
+// flow (i.e. source, destination and protocol) that may be in the network at the same time, so that fragments are not
+// reassembled into the wrong datagram (RFC 6864). A single global counter would satisfy that, but it lets anyone who
+// talks to us observe how many datagrams we send to everyone else, which is what idle scans build on (RFC 7739). So
+// instead, flows are hashed with a secret key into a table of counters that start at random values.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::inetstack::protocols::{draw_secret, layer3::ip::IpProtocol};
+use ::rand::{prelude::SmallRng, Rng, SeedableRng};
+use ::std::net::Ipv4Addr;
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+// Number of counters that flows are spread over. Flows that hash to the same counter share it, which is still correct,
+// just not as private.
+const NUM_COUNTERS: usize = 1024;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Generates the identification field of outgoing IPv4 datagrams from per-flow counters.
+pub struct IdGenerator {
+    // Secret that is mixed into the hash of a flow, so that others cannot tell which flows share a counter.
+    key: u32,
+    counters: Box<[u16; NUM_COUNTERS]>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl IdGenerator {
+    pub fn new(rng_seed: [u8; 32]) -> Self {
+        // Where the counters start has to be as secret as the key, or others could still predict the identification
+        // fields of a flow.
+        let mut rng: SmallRng = SmallRng::from_seed(rng_seed);
+        let mut rng: SmallRng = SmallRng::from_seed(draw_secret(&mut rng));
+        let mut counters: Box<[u16; NUM_COUNTERS]> = Box::new([0; NUM_COUNTERS]);
+        rng.fill(&mut counters[..]);
+        Self {
+            key: rng.gen(),
+            counters,
+        }
+    }
+
+    /// Returns the identification field of the next datagram that goes from [src_addr] to [dst_addr] and carries
+    /// [protocol].
+    pub fn next_id(&mut self, src_addr: Ipv4Addr, dst_addr: Ipv4Addr, protocol: IpProtocol) -> u16 {
+        let index: usize = self.hash(src_addr, dst_addr, protocol);
+        let counter: &mut u16 = &mut self.counters[index];
+        *counter = counter.wrapping_add(1);
+        *counter
+    }
+
+    // Returns the index of the counter of a flow.
+    fn hash(&self, src_addr: Ipv4Addr, dst_addr: Ipv4Addr, protocol: IpProtocol) -> usize {
+        let crc: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_CKSUM);
+        let mut digest = crc.digest();
+        digest.update(&src_addr.octets());
+        digest.update(&dst_addr.octets());
+        digest.update(&[u8::from(protocol)]);
+        digest.update(&self.key.to_be_bytes());
+        digest.finalize() as usize % NUM_COUNTERS
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::IdGenerator;
+    use crate::inetstack::protocols::layer3::ip::IpProtocol;
+    use ::anyhow::Result;
+    use ::std::net::Ipv4Addr;
+
+    const LOCAL_IPV4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+
+    #[test]
+    fn test_ids_of_a_flow_are_sequential() -> Result<()> {
+        let mut generator: IdGenerator = IdGenerator::new([7; 32]);
+        let remote: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
+        let first: u16 = generator.next_id(LOCAL_IPV4, remote, IpProtocol::TCP);
+        for i in 1..=u16::MAX as u32 {
+            crate::ensure_eq!(
+                generator.next_id(LOCAL_IPV4, remote, IpProtocol::TCP),
+                first.wrapping_add(i as u16)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flows_do_not_share_a_global_counter() -> Result<()> {
+        let mut generator: IdGenerator = IdGenerator::new([7; 32]);
+        let observer: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+        let before: u16 = generator.next_id(LOCAL_IPV4, observer, IpProtocol::TCP);
+
+        // Sending to other hosts does not show in the identification fields that the observer sees, unless their flow
+        // happens to share its counter.
+        let mut shared: usize = 0;
+        for host in 2..=255 {
+            let remote: Ipv4Addr = Ipv4Addr::new(10, 0, 0, host);
+            if generator.hash(LOCAL_IPV4, remote, IpProtocol::TCP)
+                == generator.hash(LOCAL_IPV4, observer, IpProtocol::TCP)
+            {
+                shared += 1;
+            }
+            generator.next_id(LOCAL_IPV4, remote, IpProtocol::TCP);
+        }
+        let after: u16 = generator.next_id(LOCAL_IPV4, observer, IpProtocol::TCP);
+        crate::ensure_eq!(after, before.wrapping_add(1 + shared as u16));
+
+        Ok(())
+    }
+}
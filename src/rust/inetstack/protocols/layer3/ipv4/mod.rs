@@ -2,6 +2,7 @@
 // Licensed under the MIT license.
 
 mod header;
+mod id_generator;
 
 #[cfg(test)]
 mod tests;
@@ -10,4 +11,7 @@ mod tests;
 // Exports
 //======================================================================================================================
 
-pub use self::header::{Ipv4Header, IPV4_HEADER_MAX_SIZE, IPV4_HEADER_MIN_SIZE};
+pub use self::{
+    header::{Ecn, Ipv4Header, IPV4_HEADER_MAX_SIZE, IPV4_HEADER_MIN_SIZE},
+    id_generator::IdGenerator,
+};
@@ -7,7 +7,10 @@
 
 use crate::{
     inetstack::{
-        protocols::layer3::{ip::IpProtocol, ipv4::Ipv4Header},
+        protocols::layer3::{
+            ip::IpProtocol,
+            ipv4::{Ecn, Ipv4Header, IPV4_HEADER_MIN_SIZE},
+        },
         test_helpers::{ALICE_IPV4, BOB_IPV4},
     },
     runtime::memory::DemiBuffer,
@@ -99,7 +102,7 @@ fn test_ipv4_header_parse_good() -> Result<()> {
             0x2,
             0,
             1,
-            IpProtocol::UDP as u8,
+            u8::from(IpProtocol::UDP),
             &ALICE_IPV4.octets(),
             &BOB_IPV4.octets(),
             None,
@@ -127,6 +130,59 @@ fn test_ipv4_header_parse_good() -> Result<()> {
     Ok(())
 }
 
+/// Parses the ECN field of IPv4 headers and writes it back.
+#[test]
+fn test_ipv4_header_ecn() -> Result<()> {
+    const HEADER_SIZE: usize = 20;
+    let mut buf: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
+
+    for ecn in [Ecn::NotEct, Ecn::Ect1, Ecn::Ect0, Ecn::Ce] {
+        build_ipv4_header(
+            &mut buf,
+            4,
+            5,
+            0,
+            ecn as u8,
+            HEADER_SIZE as u16,
+            0,
+            0x2,
+            0,
+            1,
+            u8::from(IpProtocol::TCP),
+            &ALICE_IPV4.octets(),
+            &BOB_IPV4.octets(),
+            None,
+        );
+        let mut pkt: DemiBuffer = DemiBuffer::from_slice(&buf).expect("'buf' should fit in a DemiBuffer");
+        let ipv4_hdr: Ipv4Header = Ipv4Header::parse_and_strip(&mut pkt)?;
+        crate::ensure_eq!(ipv4_hdr.get_ecn(), ecn);
+    }
+
+    // The ECN field shares its byte with the DSCP field.
+    let mut ipv4_hdr: Ipv4Header = Ipv4Header::new(ALICE_IPV4, BOB_IPV4, IpProtocol::TCP);
+    ipv4_hdr.set_dscp(46);
+    ipv4_hdr.set_ecn(Ecn::Ect0);
+    let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, HEADER_SIZE as u16);
+    ipv4_hdr.serialize_and_attach(&mut pkt);
+    crate::ensure_eq!(pkt[1], (46 << 2) | Ecn::Ect0 as u8);
+
+    Ok(())
+}
+
+/// Tests that the identification field survives serialization and parsing.
+#[test]
+fn test_ipv4_header_identification() -> Result<()> {
+    let mut ipv4_hdr: Ipv4Header = Ipv4Header::new(ALICE_IPV4, BOB_IPV4, IpProtocol::TCP);
+    ipv4_hdr.set_identification(0xbeef);
+    let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, IPV4_HEADER_MIN_SIZE);
+    ipv4_hdr.serialize_and_attach(&mut pkt);
+    crate::ensure_eq!(u16::from_be_bytes([pkt[4], pkt[5]]), 0xbeef);
+    let ipv4_hdr: Ipv4Header = Ipv4Header::parse_and_strip(&mut pkt)?;
+    crate::ensure_eq!(ipv4_hdr.get_identification(), 0xbeef);
+
+    Ok(())
+}
+
 //======================================================================================================================
 // Unit-Tests for Invalid Path
 //======================================================================================================================
@@ -152,7 +208,7 @@ fn test_ipv4_header_parse_invalid_version() -> Result<()> {
             0x2,
             0,
             1,
-            IpProtocol::UDP as u8,
+            u8::from(IpProtocol::UDP),
             &ALICE_IPV4.octets(),
             &BOB_IPV4.octets(),
             None,
@@ -192,7 +248,7 @@ fn test_ipv4_header_parse_invalid_ihl() -> Result<()> {
             0x2,
             0,
             1,
-            IpProtocol::UDP as u8,
+            u8::from(IpProtocol::UDP),
             &ALICE_IPV4.octets(),
             &BOB_IPV4.octets(),
             None,
@@ -233,7 +289,7 @@ fn test_ipv4_header_parse_invalid_total_length() -> Result<()> {
             0x2,
             0,
             1,
-            IpProtocol::UDP as u8,
+            u8::from(IpProtocol::UDP),
             &ALICE_IPV4.octets(),
             &BOB_IPV4.octets(),
             None,
@@ -274,7 +330,7 @@ fn test_ipv4_header_parse_invalid_flags() -> Result<()> {
         flags,
         0,
         1,
-        IpProtocol::UDP as u8,
+        u8::from(IpProtocol::UDP),
         &ALICE_IPV4.octets(),
         &BOB_IPV4.octets(),
         None,
@@ -312,7 +368,7 @@ fn test_ipv4_header_parse_invalid_ttl() -> Result<()> {
         0x2,
         0,
         ttl,
-        IpProtocol::UDP as u8,
+        u8::from(IpProtocol::UDP),
         &ALICE_IPV4.octets(),
         &BOB_IPV4.octets(),
         None,
@@ -329,15 +385,16 @@ fn test_ipv4_header_parse_invalid_ttl() -> Result<()> {
     }
 }
 
-/// Parses a malformed IPv4 header with invalid protocol field.
+/// Parses IPv4 headers whose protocol field carries protocols that the stack does not implement itself. These are left to
+/// protocol extensions.
 #[test]
-fn test_ipv4_header_parse_invalid_protocol() -> Result<()> {
+fn test_ipv4_header_parse_other_protocol() -> Result<()> {
     const HEADER_SIZE: usize = 20;
     const PAYLOAD_SIZE: usize = 0;
     const DATAGRAM_SIZE: usize = HEADER_SIZE + PAYLOAD_SIZE;
     let mut buf: [u8; DATAGRAM_SIZE] = [0; DATAGRAM_SIZE];
 
-    // Iterate over invalid values for protocol.
+    // Iterate over unassigned values for protocol.
     for protocol in 144..252 {
         build_ipv4_header(
             &mut buf,
@@ -362,8 +419,8 @@ fn test_ipv4_header_parse_invalid_protocol() -> Result<()> {
         };
 
         match Ipv4Header::parse_and_strip(&mut buf) {
-            Ok(_) => anyhow::ensure!(false, "parsed ipv4 header with invalid protocol={:?}", protocol),
-            Err(_) => {},
+            Ok(ipv4_hdr) => crate::ensure_eq!(ipv4_hdr.get_protocol(), IpProtocol::Other(protocol)),
+            Err(e) => anyhow::bail!("could not parse ipv4 header with protocol={:?}: {:?}", protocol, e),
         };
     }
 
@@ -391,7 +448,7 @@ fn test_ipv4_header_parse_invalid_header_checksum() -> Result<()> {
         0x2,
         0,
         1,
-        IpProtocol::UDP as u8,
+        u8::from(IpProtocol::UDP),
         &ALICE_IPV4.octets(),
         &BOB_IPV4.octets(),
         Some(hdr_checksum),
@@ -433,7 +490,7 @@ fn test_ipv4_header_parse_unsupported_dscp() -> Result<()> {
             0x2,
             0,
             1,
-            IpProtocol::UDP as u8,
+            u8::from(IpProtocol::UDP),
             &ALICE_IPV4.octets(),
             &BOB_IPV4.octets(),
             None,
@@ -474,7 +531,7 @@ fn test_ipv4_header_parse_unsupported_ecn() -> Result<()> {
             0x2,
             0,
             1,
-            IpProtocol::UDP as u8,
+            u8::from(IpProtocol::UDP),
             &ALICE_IPV4.octets(),
             &BOB_IPV4.octets(),
             None,
@@ -518,7 +575,7 @@ fn test_ipv4_header_parse_unsupported_fragmentation() -> Result<()> {
         flags,
         0,
         1,
-        IpProtocol::UDP as u8,
+        u8::from(IpProtocol::UDP),
         &ALICE_IPV4.octets(),
         &BOB_IPV4.octets(),
         None,
@@ -548,7 +605,7 @@ fn test_ipv4_header_parse_unsupported_fragmentation() -> Result<()> {
         0x2,
         fragment_offset,
         1,
-        IpProtocol::UDP as u8,
+        u8::from(IpProtocol::UDP),
         &ALICE_IPV4.octets(),
         &BOB_IPV4.octets(),
         None,
@@ -568,9 +625,8 @@ fn test_ipv4_header_parse_unsupported_fragmentation() -> Result<()> {
     }
 }
 
-/// Parses a malformed IPv4 header with unsupported protocol field.
-///
-/// TODO: Drop this test once we support them.
+/// Parses IPv4 headers whose protocol field carries assigned protocols that the stack does not implement itself. Like
+/// unassigned ones, these are left to protocol extensions.
 #[test]
 fn test_ipv4_header_parse_unsupported_protocol() -> Result<()> {
     const HEADER_SIZE: usize = 20;
@@ -578,7 +634,7 @@ fn test_ipv4_header_parse_unsupported_protocol() -> Result<()> {
     const DATAGRAM_SIZE: usize = HEADER_SIZE + PAYLOAD_SIZE;
     let mut buf: [u8; DATAGRAM_SIZE] = [0; DATAGRAM_SIZE];
 
-    // Iterate over unsupported values for fragment flags.
+    // Iterate over assigned values for protocol.
     for protocol in 0..143 {
         match protocol {
             // Skip supported protocols.
@@ -607,8 +663,8 @@ fn test_ipv4_header_parse_unsupported_protocol() -> Result<()> {
                 };
 
                 match Ipv4Header::parse_and_strip(&mut buf) {
-                    Ok(_) => anyhow::bail!("parsed ipv4 header with protocol={:?}. Do we support it now?", protocol,),
-                    Err(_) => {},
+                    Ok(ipv4_hdr) => crate::ensure_eq!(ipv4_hdr.get_protocol(), IpProtocol::Other(protocol)),
+                    Err(e) => anyhow::bail!("could not parse ipv4 header with protocol={:?}: {:?}", protocol, e),
                 };
             },
         };
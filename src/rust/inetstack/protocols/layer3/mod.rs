@@ -12,23 +12,30 @@ pub mod ipv4;
 
 use arrayvec::ArrayVec;
 
-pub use self::{arp::SharedArpPeer, icmpv4::SharedIcmpv4Peer, ip::IpProtocol, ipv4::Ipv4Header};
+pub use self::{
+    arp::SharedArpPeer,
+    icmpv4::{FragmentationNeeded, SharedIcmpv4Peer},
+    ip::IpProtocol,
+    ipv4::{Ecn, IdGenerator, Ipv4Header},
+};
 
 use crate::{
     demi_sgarray_t,
     demikernel::config::Config,
     inetstack::protocols::layer2::{EtherType2, SharedLayer2Endpoint},
+    perftools::stages::Stage,
     runtime::{
         fail::Fail,
         memory::{DemiBuffer, MemoryRuntime},
-        network::consts::RECEIVE_BATCH_SIZE,
+        network::{consts::RECEIVE_BATCH_SIZE, socket::option::PriorityClass},
         SharedDemiRuntime, SharedObject,
     },
-    MacAddress,
+    stage_timer, MacAddress,
 };
 #[cfg(test)]
 use ::std::{collections::HashMap, hash::RandomState, time::Duration};
 use ::std::{
+    mem,
     net::Ipv4Addr,
     ops::{Deref, DerefMut},
 };
@@ -42,6 +49,11 @@ pub struct Layer3Endpoint {
     arp: SharedArpPeer,
     icmpv4: SharedIcmpv4Peer,
     local_ipv4_addr: Ipv4Addr,
+    // Foreign prefixes that we accept TCP segments for, along with the length of each prefix.
+    transparent_prefixes: Vec<(Ipv4Addr, u8)>,
+    id_generator: IdGenerator,
+    // ICMP Fragmentation Needed messages that the transport protocols did not pick up yet.
+    fragmentation_needed: Vec<FragmentationNeeded>,
 }
 
 #[derive(Clone)]
@@ -64,19 +76,28 @@ impl SharedLayer3Endpoint {
             arp: arp.clone(),
             icmpv4: SharedIcmpv4Peer::new(&config, runtime, layer2_endpoint.clone(), arp, rng_seed)?,
             local_ipv4_addr: config.local_ipv4_addr()?,
+            transparent_prefixes: config.transparent_prefixes()?,
+            id_generator: IdGenerator::new(rng_seed),
+            fragmentation_needed: Vec::new(),
             layer2_endpoint,
         })))
     }
 
-    pub fn receive(&mut self) -> Result<ArrayVec<(Ipv4Addr, IpProtocol, DemiBuffer), RECEIVE_BATCH_SIZE>, Fail> {
-        let mut batch: ArrayVec<(Ipv4Addr, IpProtocol, DemiBuffer), RECEIVE_BATCH_SIZE> = ArrayVec::new();
-        for (eth2_type, mut packet) in self.layer2_endpoint.receive()? {
+    /// Receives a batch of packets for upper layers. Along with each payload, we hand over the IPv4 addresses of its
+    /// sender and of its destination, the link address of the last hop that forwarded it to us and its ECN code point.
+    pub fn receive(
+        &mut self,
+    ) -> Result<ArrayVec<(Ipv4Addr, Ipv4Addr, MacAddress, IpProtocol, Ecn, DemiBuffer), RECEIVE_BATCH_SIZE>, Fail> {
+        let mut batch: ArrayVec<(Ipv4Addr, Ipv4Addr, MacAddress, IpProtocol, Ecn, DemiBuffer), RECEIVE_BATCH_SIZE> =
+            ArrayVec::new();
+        for (eth2_type, src_link_addr, mut packet) in self.layer2_endpoint.receive()? {
             match eth2_type {
                 EtherType2::Arp => {
                     self.arp.receive(packet);
                     continue;
                 },
                 EtherType2::Ipv4 => {
+                    stage_timer!(Stage::Layer3Parse);
                     let header = match Ipv4Header::parse_and_strip(&mut packet) {
                         Ok(header) => header,
                         Err(e) => {
@@ -87,8 +108,13 @@ impl SharedLayer3Endpoint {
                     };
                     debug!("Ipv4 received {:?}", header);
 
-                    // Check that the destination matches our IP address; otherwise, discard.
-                    if header.get_dest_addr() != self.local_ipv4_addr && !header.get_dest_addr().is_broadcast() {
+                    // Check that the destination matches our IP address; otherwise, discard. TCP segments to the
+                    // foreign prefixes that we intercept are the exception.
+                    let dst_ipv4_addr: Ipv4Addr = header.get_dest_addr();
+                    if dst_ipv4_addr != self.local_ipv4_addr
+                        && !dst_ipv4_addr.is_broadcast()
+                        && !(header.get_protocol() == IpProtocol::TCP && self.is_transparent_addr(dst_ipv4_addr))
+                    {
                         let cause: String = format!("Invalid destination address");
                         warn!("dropping packet: {}", cause);
                         continue;
@@ -107,10 +133,19 @@ impl SharedLayer3Endpoint {
                     let protocol: IpProtocol = header.get_protocol();
                     match protocol {
                         IpProtocol::ICMPv4 => {
-                            self.icmpv4.receive(header, packet);
+                            if let Some(message) = self.icmpv4.receive(header, packet) {
+                                self.fragmentation_needed.push(message);
+                            }
                             continue;
                         },
-                        _ => batch.push((header.get_src_addr(), protocol, packet)),
+                        _ => batch.push((
+                            header.get_src_addr(),
+                            dst_ipv4_addr,
+                            src_link_addr,
+                            protocol,
+                            header.get_ecn(),
+                            packet,
+                        )),
                     }
                 },
                 EtherType2::Ipv6 => warn!("Ipv6 not supported yet"), // Ignore for now.
@@ -119,43 +154,144 @@ impl SharedLayer3Endpoint {
         Ok(batch)
     }
 
-    pub fn transmit_tcp_packet_nonblocking(&mut self, remote_ipv4_addr: Ipv4Addr, pkt: DemiBuffer) -> Result<(), Fail> {
+    /// Hands over the ICMP Fragmentation Needed messages that we received since the last call, so that the transport
+    /// protocols shrink the segments that they send on the affected paths.
+    pub fn take_fragmentation_needed(&mut self) -> Vec<FragmentationNeeded> {
+        mem::take(&mut self.fragmentation_needed)
+    }
+
+    /// Sends a TCP segment from `local_ipv4_addr`, which is either our own address or a foreign one that we intercept
+    /// connections for, to `remote_ipv4_addr` right away. Fails with EAGAIN if its link address is not cached.
+    pub fn transmit_tcp_packet_nonblocking(
+        &mut self,
+        local_ipv4_addr: Ipv4Addr,
+        remote_ipv4_addr: Ipv4Addr,
+        priority: PriorityClass,
+        ecn: Ecn,
+        pkt: DemiBuffer,
+    ) -> Result<(), Fail> {
         let remote_link_addr: MacAddress = match self.arp.try_query(remote_ipv4_addr) {
             Some(addr) => addr,
             _ => return Err(Fail::new(libc::EAGAIN, "destination not in ARP cache")),
         };
 
-        self.transmit_packet(remote_ipv4_addr, remote_link_addr, IpProtocol::TCP, pkt)
+        self.transmit_packet(
+            local_ipv4_addr,
+            remote_ipv4_addr,
+            remote_link_addr,
+            IpProtocol::TCP,
+            priority,
+            ecn,
+            pkt,
+        )
     }
 
+    /// Sends a TCP segment from `local_ipv4_addr` to `remote_ipv4_addr`, resolving its link address first if needed.
     pub async fn transmit_tcp_packet_blocking(
         &mut self,
+        local_ipv4_addr: Ipv4Addr,
         remote_ipv4_addr: Ipv4Addr,
+        priority: PriorityClass,
         pkt: DemiBuffer,
     ) -> Result<(), Fail> {
         let remote_link_addr: MacAddress = self.arp.query(remote_ipv4_addr).await?;
 
-        self.transmit_packet(remote_ipv4_addr, remote_link_addr, IpProtocol::TCP, pkt)
+        self.transmit_packet(
+            local_ipv4_addr,
+            remote_ipv4_addr,
+            remote_link_addr,
+            IpProtocol::TCP,
+            priority,
+            Ecn::NotEct,
+            pkt,
+        )
     }
 
     pub async fn transmit_udp_packet_blocking(
         &mut self,
         remote_ipv4_addr: Ipv4Addr,
+        priority: PriorityClass,
+        pkt: DemiBuffer,
+    ) -> Result<(), Fail> {
+        let remote_link_addr: MacAddress = self.arp.query(remote_ipv4_addr).await?;
+
+        self.transmit_packet(
+            self.local_ipv4_addr,
+            remote_ipv4_addr,
+            remote_link_addr,
+            IpProtocol::UDP,
+            priority,
+            Ecn::NotEct,
+            pkt,
+        )
+    }
+
+    /// Sends a packet of `ip_protocol` to `remote_ipv4_addr` right away. Fails with EAGAIN if its link address is not
+    /// cached.
+    pub fn transmit_packet_nonblocking(
+        &mut self,
+        remote_ipv4_addr: Ipv4Addr,
+        ip_protocol: IpProtocol,
+        priority: PriorityClass,
+        pkt: DemiBuffer,
+    ) -> Result<(), Fail> {
+        let remote_link_addr: MacAddress = match self.arp.try_query(remote_ipv4_addr) {
+            Some(addr) => addr,
+            _ => return Err(Fail::new(libc::EAGAIN, "destination not in ARP cache")),
+        };
+
+        self.transmit_packet(
+            self.local_ipv4_addr,
+            remote_ipv4_addr,
+            remote_link_addr,
+            ip_protocol,
+            priority,
+            Ecn::NotEct,
+            pkt,
+        )
+    }
+
+    /// Sends a packet of `ip_protocol` to `remote_ipv4_addr`, resolving its link address first if needed.
+    pub async fn transmit_packet_blocking(
+        &mut self,
+        remote_ipv4_addr: Ipv4Addr,
+        ip_protocol: IpProtocol,
+        priority: PriorityClass,
         pkt: DemiBuffer,
     ) -> Result<(), Fail> {
         let remote_link_addr: MacAddress = self.arp.query(remote_ipv4_addr).await?;
 
-        self.transmit_packet(remote_ipv4_addr, remote_link_addr, IpProtocol::UDP, pkt)
+        self.transmit_packet(
+            self.local_ipv4_addr,
+            remote_ipv4_addr,
+            remote_link_addr,
+            ip_protocol,
+            priority,
+            Ecn::NotEct,
+            pkt,
+        )
     }
 
+    /// Sends a packet from `local_ipv4_addr` to `remote_link_addr`, marking it with the DSCP code point of its
+    /// `priority` class and with the `ecn` code point.
     pub fn transmit_packet(
         &mut self,
+        local_ipv4_addr: Ipv4Addr,
         remote_ipv4_addr: Ipv4Addr,
         remote_link_addr: MacAddress,
         ip_protocol: IpProtocol,
+        priority: PriorityClass,
+        ecn: Ecn,
         mut pkt: DemiBuffer,
     ) -> Result<(), Fail> {
-        let ipv4_header: Ipv4Header = Ipv4Header::new(self.local_ipv4_addr, remote_ipv4_addr, ip_protocol);
+        self.arp.check_local_addr()?;
+        let mut ipv4_header: Ipv4Header = Ipv4Header::new(local_ipv4_addr, remote_ipv4_addr, ip_protocol);
+        ipv4_header.set_dscp(priority.dscp());
+        ipv4_header.set_ecn(ecn);
+        let identification: u16 = self
+            .id_generator
+            .next_id(local_ipv4_addr, remote_ipv4_addr, ip_protocol);
+        ipv4_header.set_identification(identification);
         ipv4_header.serialize_and_attach(&mut pkt);
         self.layer2_endpoint.transmit_ipv4_packet(remote_link_addr, pkt)
     }
@@ -164,6 +300,21 @@ impl SharedLayer3Endpoint {
         self.local_ipv4_addr
     }
 
+    /// Checks whether `addr` is a foreign address that we intercept TCP connections for, as transparent proxies do.
+    pub fn is_transparent_addr(&self, addr: Ipv4Addr) -> bool {
+        self.transparent_prefixes.iter().any(|(prefix, len)| {
+            let mask: u32 = u32::MAX.checked_shl(32 - *len as u32).unwrap_or(0);
+            u32::from(addr) & mask == u32::from(*prefix) & mask
+        })
+    }
+
+    /// Changes the local IPv4 address and announces the new address to our neighbors.
+    pub fn set_local_addr(&mut self, local_ipv4_addr: Ipv4Addr) {
+        self.local_ipv4_addr = local_ipv4_addr;
+        self.icmpv4.set_local_addr(local_ipv4_addr);
+        self.arp.set_local_addr(local_ipv4_addr);
+    }
+
     #[cfg(test)]
     pub async fn ping(&mut self, addr: Ipv4Addr, timeout: Option<Duration>) -> Result<Duration, Fail> {
         self.icmpv4.ping(addr, timeout).await
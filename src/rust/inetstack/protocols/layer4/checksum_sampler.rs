@@ -0,0 +1,145 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// When the NIC validates the checksums of received packets, a misconfigured offload silently lets corrupted packets
+// through. Validating the checksums of a sample of these packets in software catches such configurations without paying
+// for the validation of every packet.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::inetstack::protocols::layer4::stats_delta::Counters;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Results of the software validation of checksums that the hardware claimed to have validated already.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChecksumStats {
+    /// Number of packets whose checksum we validated in software.
+    pub validated: u64,
+    /// Number of validated packets whose checksum did not match their contents.
+    pub mismatches: u64,
+}
+
+/// Picks the received packets whose checksum we validate in software, although the hardware did it already.
+#[derive(Debug)]
+pub struct ChecksumSampler {
+    // We validate one out of this many packets. Zero turns sampling off.
+    interval: usize,
+    // Number of packets that go by until we validate the next one.
+    countdown: usize,
+    stats: ChecksumStats,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl ChecksumSampler {
+    /// Creates a sampler that validates one out of every [interval] packets, starting with the first one. An interval of
+    /// zero turns sampling off.
+    pub fn new(interval: usize) -> Self {
+        Self {
+            interval,
+            countdown: 0,
+            stats: ChecksumStats::default(),
+        }
+    }
+
+    /// Validates the checksum of a received packet with [verify] if the packet is part of the sample. Returns whether the
+    /// packet should be processed, which is not the case if it was sampled and its checksum does not match.
+    pub fn check<F: FnOnce() -> bool>(&mut self, verify: F) -> bool {
+        if self.interval == 0 {
+            return true;
+        }
+        if self.countdown > 0 {
+            self.countdown -= 1;
+            return true;
+        }
+        self.countdown = self.interval - 1;
+        self.stats.validated += 1;
+        if verify() {
+            return true;
+        }
+        self.stats.mismatches += 1;
+        warn!(
+            "check(): checksum mismatch on a packet that the hardware validated (mismatches={:?}, validated={:?})",
+            self.stats.mismatches, self.stats.validated
+        );
+        false
+    }
+
+    pub fn get_stats(&self) -> ChecksumStats {
+        self.stats
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl Counters for ChecksumStats {
+    fn delta(&self, baseline: &Self) -> Self {
+        Self {
+            validated: self.validated - baseline.validated,
+            mismatches: self.mismatches - baseline.mismatches,
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{ChecksumSampler, ChecksumStats};
+    use ::anyhow::Result;
+
+    #[test]
+    fn test_sampling_interval() -> Result<()> {
+        let mut sampler: ChecksumSampler = ChecksumSampler::new(4);
+        let mut verified: usize = 0;
+        for _ in 0..10 {
+            crate::ensure_eq!(
+                sampler.check(|| {
+                    verified += 1;
+                    true
+                }),
+                true
+            );
+        }
+        // The first packet is validated, then every fourth one.
+        crate::ensure_eq!(verified, 3);
+        crate::ensure_eq!(
+            sampler.get_stats(),
+            ChecksumStats {
+                validated: 3,
+                mismatches: 0
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sampled_mismatch() -> Result<()> {
+        let mut sampler: ChecksumSampler = ChecksumSampler::new(2);
+        // Packets that are not part of the sample get through even with a bad checksum.
+        crate::ensure_eq!(sampler.check(|| false), false);
+        crate::ensure_eq!(sampler.check(|| false), true);
+        crate::ensure_eq!(sampler.check(|| true), true);
+        crate::ensure_eq!(sampler.get_stats().mismatches, 1);
+        crate::ensure_eq!(sampler.get_stats().validated, 2);
+
+        // Sampling can be turned off entirely.
+        let mut sampler: ChecksumSampler = ChecksumSampler::new(0);
+        crate::ensure_eq!(sampler.check(|| false), true);
+        crate::ensure_eq!(sampler.get_stats().validated, 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Protocol extensions are transport protocols that live outside of the stack, such as experimental or datacenter-specific
+// protocols. They register for an IP protocol number that the stack does not implement itself, receive the payload of
+// every datagram that carries this protocol number and send their own datagrams through the layer 3 endpoint.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    inetstack::protocols::layer3::{ip::IpProtocol, Ecn, SharedLayer3Endpoint},
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{socket::option::PriorityClass, types::MacAddress},
+    },
+};
+use ::std::{collections::HashMap, net::Ipv4Addr};
+
+//======================================================================================================================
+// Traits
+//======================================================================================================================
+
+/// A transport protocol that plugs into layer 4 under its own IP protocol number.
+pub trait ProtocolExtension {
+    /// Handles the [payload] of a datagram that [src_ipv4_addr] sent us, along with the link address of the last hop that
+    /// forwarded it to us and its ECN code point.
+    fn receive(&mut self, src_ipv4_addr: Ipv4Addr, src_link_addr: MacAddress, ecn: Ecn, payload: DemiBuffer);
+}
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Sends the datagrams of a protocol extension through the layer 3 endpoint.
+#[derive(Clone)]
+pub struct ExtensionTransmitter {
+    protocol: IpProtocol,
+    layer3_endpoint: SharedLayer3Endpoint,
+}
+
+/// Protocol extensions that are registered with layer 4, by IP protocol number.
+#[derive(Default)]
+pub struct ProtocolExtensions {
+    extensions: HashMap<u8, Box<dyn ProtocolExtension>>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl ExtensionTransmitter {
+    pub fn new(protocol: u8, layer3_endpoint: SharedLayer3Endpoint) -> Self {
+        Self {
+            protocol: IpProtocol::Other(protocol),
+            layer3_endpoint,
+        }
+    }
+
+    pub fn get_protocol(&self) -> IpProtocol {
+        self.protocol
+    }
+
+    pub fn get_local_addr(&self) -> Ipv4Addr {
+        self.layer3_endpoint.get_local_addr()
+    }
+
+    /// Sends [pkt] to [remote_ipv4_addr] in a datagram of our protocol, resolving the link address of the remote first if
+    /// needed.
+    pub async fn transmit(
+        &mut self,
+        remote_ipv4_addr: Ipv4Addr,
+        priority: PriorityClass,
+        pkt: DemiBuffer,
+    ) -> Result<(), Fail> {
+        let protocol: IpProtocol = self.protocol;
+        self.layer3_endpoint
+            .transmit_packet_blocking(remote_ipv4_addr, protocol, priority, pkt)
+            .await
+    }
+
+    /// Sends [pkt] to [remote_ipv4_addr] in a datagram of our protocol right away. Fails with EAGAIN if the link address
+    /// of the remote is not known yet.
+    pub fn try_transmit(
+        &mut self,
+        remote_ipv4_addr: Ipv4Addr,
+        priority: PriorityClass,
+        pkt: DemiBuffer,
+    ) -> Result<(), Fail> {
+        let protocol: IpProtocol = self.protocol;
+        self.layer3_endpoint
+            .transmit_packet_nonblocking(remote_ipv4_addr, protocol, priority, pkt)
+    }
+}
+
+impl ProtocolExtensions {
+    /// Registers [extension] for IP protocol number [protocol], which must be one that the stack does not implement.
+    pub fn register(&mut self, protocol: u8, extension: Box<dyn ProtocolExtension>) -> Result<(), Fail> {
+        if !matches!(IpProtocol::from(protocol), IpProtocol::Other(_)) {
+            let cause: String = format!("protocol is implemented by the stack (protocol={:?})", protocol);
+            error!("register(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        if self.extensions.contains_key(&protocol) {
+            let cause: String = format!("protocol is already registered (protocol={:?})", protocol);
+            error!("register(): {}", cause);
+            return Err(Fail::new(libc::EEXIST, &cause));
+        }
+        self.extensions.insert(protocol, extension);
+        Ok(())
+    }
+
+    /// Removes the extension that is registered for IP protocol number [protocol]. Datagrams of this protocol are dropped
+    /// from then on.
+    pub fn unregister(&mut self, protocol: u8) -> Result<Box<dyn ProtocolExtension>, Fail> {
+        match self.extensions.remove(&protocol) {
+            Some(extension) => Ok(extension),
+            None => {
+                let cause: String = format!("protocol is not registered (protocol={:?})", protocol);
+                error!("unregister(): {}", cause);
+                Err(Fail::new(libc::ENOENT, &cause))
+            },
+        }
+    }
+
+    /// Hands a datagram of IP protocol number [protocol] to the extension that is registered for it, if any.
+    pub fn receive(
+        &mut self,
+        protocol: u8,
+        src_ipv4_addr: Ipv4Addr,
+        src_link_addr: MacAddress,
+        ecn: Ecn,
+        payload: DemiBuffer,
+    ) {
+        match self.extensions.get_mut(&protocol) {
+            Some(extension) => extension.receive(src_ipv4_addr, src_link_addr, ecn, payload),
+            None => trace!(
+                "receive(): dropping datagram of unregistered protocol (protocol={:?}, remote={:?})",
+                protocol,
+                src_ipv4_addr
+            ),
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{ProtocolExtension, ProtocolExtensions};
+    use crate::{
+        inetstack::protocols::layer3::{ip::IpProtocol, Ecn},
+        runtime::{memory::DemiBuffer, network::types::MacAddress},
+    };
+    use ::anyhow::Result;
+    use ::std::{cell::Cell, net::Ipv4Addr, rc::Rc};
+
+    // Protocol number that is reserved for experimentation and testing (RFC 3692).
+    const EXPERIMENTAL_PROTOCOL: u8 = 253;
+    const REMOTE_IPV4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
+
+    struct CountingExtension {
+        received: Rc<Cell<usize>>,
+    }
+
+    impl ProtocolExtension for CountingExtension {
+        fn receive(&mut self, src_ipv4_addr: Ipv4Addr, _: MacAddress, _: Ecn, _: DemiBuffer) {
+            assert_eq!(src_ipv4_addr, REMOTE_IPV4);
+            self.received.set(self.received.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_register_and_dispatch() -> Result<()> {
+        let mut extensions: ProtocolExtensions = ProtocolExtensions::default();
+        let received: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+        let extension: Box<CountingExtension> = Box::new(CountingExtension {
+            received: received.clone(),
+        });
+        crate::ensure_eq!(extensions.register(EXPERIMENTAL_PROTOCOL, extension).is_ok(), true);
+
+        extensions.receive(
+            EXPERIMENTAL_PROTOCOL,
+            REMOTE_IPV4,
+            MacAddress::broadcast(),
+            Ecn::NotEct,
+            DemiBuffer::new(0),
+        );
+        crate::ensure_eq!(received.get(), 1);
+
+        // Datagrams of other protocols do not reach the extension and neither do they once it is gone.
+        extensions.receive(
+            EXPERIMENTAL_PROTOCOL + 1,
+            REMOTE_IPV4,
+            MacAddress::broadcast(),
+            Ecn::NotEct,
+            DemiBuffer::new(0),
+        );
+        crate::ensure_eq!(extensions.unregister(EXPERIMENTAL_PROTOCOL).is_ok(), true);
+        extensions.receive(
+            EXPERIMENTAL_PROTOCOL,
+            REMOTE_IPV4,
+            MacAddress::broadcast(),
+            Ecn::NotEct,
+            DemiBuffer::new(0),
+        );
+        crate::ensure_eq!(received.get(), 1);
+        crate::ensure_eq!(
+            extensions.unregister(EXPERIMENTAL_PROTOCOL).err().map(|e| e.errno),
+            Some(libc::ENOENT)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_rejects_taken_protocols() -> Result<()> {
+        let mut extensions: ProtocolExtensions = ProtocolExtensions::default();
+        let received: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+
+        // Protocols that the stack implements itself cannot be taken over.
+        for protocol in [IpProtocol::ICMPv4, IpProtocol::TCP, IpProtocol::UDP] {
+            let extension: Box<CountingExtension> = Box::new(CountingExtension {
+                received: received.clone(),
+            });
+            crate::ensure_eq!(
+                extensions
+                    .register(u8::from(protocol), extension)
+                    .err()
+                    .map(|e| e.errno),
+                Some(libc::EINVAL)
+            );
+        }
+
+        // Neither can the protocols of other extensions.
+        let extension: Box<CountingExtension> = Box::new(CountingExtension {
+            received: received.clone(),
+        });
+        crate::ensure_eq!(extensions.register(EXPERIMENTAL_PROTOCOL, extension).is_ok(), true);
+        let extension: Box<CountingExtension> = Box::new(CountingExtension { received });
+        crate::ensure_eq!(
+            extensions
+                .register(EXPERIMENTAL_PROTOCOL, extension)
+                .err()
+                .map(|e| e.errno),
+            Some(libc::EEXIST)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Receiver-driven scheduling of incoming messages. Following the shortest remaining processing time first policy, we
+// grant the messages with the fewest remaining bytes and keep up to one round trip's worth of each of them in flight.
+// Granting more than one message at a time (overcommitment) keeps our link busy when some senders do not respond right
+// away, and the shortest message gets the highest priority so that it goes ahead of the others in the network.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::network::socket::option::PriorityClass;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// What the grant scheduler knows about an incoming message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate<K> {
+    pub key: K,
+    pub msg_len: u32,
+    pub received_bytes: u32,
+    pub granted: u32,
+}
+
+/// A grant to send everything before [offset] at [priority].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grant<K> {
+    pub key: K,
+    pub offset: u32,
+    pub priority: PriorityClass,
+}
+
+pub struct GrantScheduler {
+    // Bytes that are in flight for a message that we grant.
+    rtt_bytes: u32,
+    // Number of messages that we grant at once.
+    overcommit: usize,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl GrantScheduler {
+    pub fn new(rtt_bytes: u32, overcommit: usize) -> Self {
+        Self { rtt_bytes, overcommit }
+    }
+
+    /// Bytes that senders send without waiting for a grant, which is also the priority at which they send them.
+    pub fn unscheduled_bytes(&self) -> u32 {
+        self.rtt_bytes
+    }
+
+    /// Returns the grants that we have to send for [candidates], which are the incoming messages that we did not
+    /// receive completely yet. Messages that get no grant wait until shorter ones complete.
+    pub fn schedule<K: Copy>(&self, candidates: &[Candidate<K>]) -> Vec<Grant<K>> {
+        let mut active: Vec<&Candidate<K>> = candidates.iter().filter(|c| c.granted < c.msg_len).collect();
+        active.sort_by_key(|c| c.msg_len - c.received_bytes);
+        active
+            .into_iter()
+            .take(self.overcommit)
+            .enumerate()
+            .filter_map(|(rank, c)| {
+                let offset: u32 = c.msg_len.min(c.received_bytes.saturating_add(self.rtt_bytes));
+                if offset <= c.granted {
+                    return None;
+                }
+                let priority: PriorityClass = if rank == 0 {
+                    PriorityClass::BestEffort
+                } else {
+                    PriorityClass::Background
+                };
+                Some(Grant {
+                    key: c.key,
+                    offset,
+                    priority,
+                })
+            })
+            .collect()
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{Candidate, Grant, GrantScheduler};
+    use crate::runtime::network::socket::option::PriorityClass;
+    use ::anyhow::Result;
+
+    #[test]
+    fn test_grants_shortest_remaining_first() -> Result<()> {
+        let scheduler: GrantScheduler = GrantScheduler::new(100, 2);
+        let candidates: [Candidate<u32>; 3] = [
+            Candidate {
+                key: 1,
+                msg_len: 1000,
+                received_bytes: 100,
+                granted: 100,
+            },
+            Candidate {
+                key: 2,
+                msg_len: 300,
+                received_bytes: 100,
+                granted: 100,
+            },
+            Candidate {
+                key: 3,
+                msg_len: 500,
+                received_bytes: 100,
+                granted: 100,
+            },
+        ];
+        let grants: Vec<Grant<u32>> = scheduler.schedule(&candidates);
+        crate::ensure_eq!(
+            grants,
+            vec![
+                Grant {
+                    key: 2,
+                    offset: 200,
+                    priority: PriorityClass::BestEffort,
+                },
+                Grant {
+                    key: 3,
+                    offset: 200,
+                    priority: PriorityClass::Background,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_grants_skip_fully_granted_messages() -> Result<()> {
+        let scheduler: GrantScheduler = GrantScheduler::new(100, 1);
+        let candidates: [Candidate<u32>; 2] = [
+            Candidate {
+                key: 1,
+                msg_len: 150,
+                received_bytes: 100,
+                granted: 150,
+            },
+            Candidate {
+                key: 2,
+                msg_len: 1000,
+                received_bytes: 950,
+                granted: 980,
+            },
+        ];
+        let grants: Vec<Grant<u32>> = scheduler.schedule(&candidates);
+        crate::ensure_eq!(
+            grants,
+            vec![Grant {
+                key: 2,
+                offset: 1000,
+                priority: PriorityClass::BestEffort,
+            }]
+        );
+
+        Ok(())
+    }
+}
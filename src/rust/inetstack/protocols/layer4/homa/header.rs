@@ -0,0 +1,263 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    inetstack::protocols::layer4::homa::HOMA_PROTOCOL,
+    runtime::{fail::Fail, memory::DemiBuffer, network::socket::option::PriorityClass},
+};
+use ::libc::EBADMSG;
+use ::std::{net::Ipv4Addr, slice::ChunksExact};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Size of a Homa header (in bytes).
+pub const HOMA_HEADER_SIZE: usize = 32;
+
+// Set in packets that the client of an RPC sends.
+const FLAG_FROM_CLIENT: u8 = 0x01;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Kind of a Homa packet.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HomaKind {
+    /// Carries [length] bytes of a message, starting at [offset].
+    Data = 0,
+    /// Allows the sender of a message to send everything before [offset] at [priority].
+    Grant = 1,
+    /// Asks the sender of a message to send [length] bytes starting at [offset] again.
+    Resend = 2,
+    /// Tells the client that the server does not know about the RPC, so the request has to be sent again.
+    Unknown = 3,
+    /// Tells the client that the server is still working on the response.
+    Busy = 4,
+    /// Tells the server that the client received the whole response, so the server can forget about the RPC.
+    Ack = 5,
+}
+
+/// Homa Packet Header
+///
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-------------------------------+-------------------------------+
+/// |          Source Port          |       Destination Port        |
+/// +---------------+---------------+-------------------------------+
+/// |     Kind      |     Flags     |           Checksum            |
+/// +---------------+---------------+-------------------------------+
+/// |                                                               |
+/// +                            RPC ID                             +
+/// |                                                               |
+/// +---------------------------------------------------------------+
+/// |                        Message Length                         |
+/// +---------------------------------------------------------------+
+/// |                            Offset                             |
+/// +---------------------------------------------------------------+
+/// |                            Length                             |
+/// +---------------+-----------------------------------------------+
+/// |   Priority    |                   Reserved                    |
+/// +---------------+-----------------------------------------------+
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HomaHeader {
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub kind: HomaKind,
+    /// Whether the client of the RPC sent this packet. This tells requests apart from responses.
+    pub from_client: bool,
+    /// Identifier of the RPC, which the client picks.
+    pub rpc_id: u64,
+    /// Length of the whole message that a data packet belongs to.
+    pub msg_len: u32,
+    pub offset: u32,
+    pub length: u32,
+    /// Priority at which granted bytes are to be sent.
+    pub priority: PriorityClass,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl HomaHeader {
+    /// Parses and strips the Homa header off of the packet in [buf] that [src_ipv4_addr] sent to [dst_ipv4_addr].
+    pub fn parse_and_strip(
+        src_ipv4_addr: &Ipv4Addr,
+        dst_ipv4_addr: &Ipv4Addr,
+        buf: &mut DemiBuffer,
+    ) -> Result<Self, Fail> {
+        if buf.len() < HOMA_HEADER_SIZE {
+            return Err(Fail::new(EBADMSG, "Homa packet too small"));
+        }
+        let (hdr_buf, payload_buf): (&[u8], &[u8]) = buf[..].split_at(HOMA_HEADER_SIZE);
+        let checksum: u16 = u16::from_be_bytes([hdr_buf[6], hdr_buf[7]]);
+        if checksum != Self::checksum(src_ipv4_addr, dst_ipv4_addr, hdr_buf, payload_buf) {
+            return Err(Fail::new(EBADMSG, "Homa checksum mismatch"));
+        }
+        let kind: HomaKind = match hdr_buf[4] {
+            0 => HomaKind::Data,
+            1 => HomaKind::Grant,
+            2 => HomaKind::Resend,
+            3 => HomaKind::Unknown,
+            4 => HomaKind::Busy,
+            5 => HomaKind::Ack,
+            _ => return Err(Fail::new(EBADMSG, "unknown Homa packet kind")),
+        };
+        let priority: PriorityClass = match hdr_buf[28] {
+            0 => PriorityClass::Background,
+            1 => PriorityClass::BestEffort,
+            2 => PriorityClass::LatencyCritical,
+            _ => return Err(Fail::new(EBADMSG, "unknown Homa priority")),
+        };
+        let mut rpc_id_bytes: [u8; 8] = [0; 8];
+        rpc_id_bytes.copy_from_slice(&hdr_buf[8..16]);
+        let header: HomaHeader = Self {
+            src_port: u16::from_be_bytes([hdr_buf[0], hdr_buf[1]]),
+            dst_port: u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]),
+            kind,
+            from_client: hdr_buf[5] & FLAG_FROM_CLIENT != 0,
+            rpc_id: u64::from_be_bytes(rpc_id_bytes),
+            msg_len: u32::from_be_bytes([hdr_buf[16], hdr_buf[17], hdr_buf[18], hdr_buf[19]]),
+            offset: u32::from_be_bytes([hdr_buf[20], hdr_buf[21], hdr_buf[22], hdr_buf[23]]),
+            length: u32::from_be_bytes([hdr_buf[24], hdr_buf[25], hdr_buf[26], hdr_buf[27]]),
+            priority,
+        };
+        buf.adjust(HOMA_HEADER_SIZE)
+            .expect("Buffer should be at least long enough to hold the Homa header");
+        Ok(header)
+    }
+
+    /// Serializes and prepends the Homa header on to the packet in [buf] that goes from [src_ipv4_addr] to
+    /// [dst_ipv4_addr].
+    pub fn serialize_and_attach(&self, buf: &mut DemiBuffer, src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr) {
+        buf.prepend(HOMA_HEADER_SIZE).expect("Should have enough headroom");
+        let (hdr_buf, payload): (&mut [u8], &mut [u8]) = buf[..].split_at_mut(HOMA_HEADER_SIZE);
+        hdr_buf[0..2].copy_from_slice(&self.src_port.to_be_bytes());
+        hdr_buf[2..4].copy_from_slice(&self.dst_port.to_be_bytes());
+        hdr_buf[4] = self.kind as u8;
+        hdr_buf[5] = if self.from_client { FLAG_FROM_CLIENT } else { 0 };
+        hdr_buf[6..8].copy_from_slice(&[0, 0]);
+        hdr_buf[8..16].copy_from_slice(&self.rpc_id.to_be_bytes());
+        hdr_buf[16..20].copy_from_slice(&self.msg_len.to_be_bytes());
+        hdr_buf[20..24].copy_from_slice(&self.offset.to_be_bytes());
+        hdr_buf[24..28].copy_from_slice(&self.length.to_be_bytes());
+        hdr_buf[28] = match self.priority {
+            PriorityClass::Background => 0,
+            PriorityClass::BestEffort => 1,
+            PriorityClass::LatencyCritical => 2,
+        };
+        hdr_buf[29..32].copy_from_slice(&[0, 0, 0]);
+        let checksum: u16 = Self::checksum(src_ipv4_addr, dst_ipv4_addr, hdr_buf, payload);
+        hdr_buf[6..8].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    /// Computes the checksum of a Homa packet, which works just like the UDP checksum. The checksum field itself is
+    /// skipped.
+    fn checksum(src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr, hdr_buf: &[u8], data: &[u8]) -> u16 {
+        let mut state: u32 = 0xffff;
+
+        // Pseudo header.
+        let src_octets: [u8; 4] = src_ipv4_addr.octets();
+        state += u16::from_be_bytes([src_octets[0], src_octets[1]]) as u32;
+        state += u16::from_be_bytes([src_octets[2], src_octets[3]]) as u32;
+        let dst_octets: [u8; 4] = dst_ipv4_addr.octets();
+        state += u16::from_be_bytes([dst_octets[0], dst_octets[1]]) as u32;
+        state += u16::from_be_bytes([dst_octets[2], dst_octets[3]]) as u32;
+        state += u16::from_be_bytes([0, HOMA_PROTOCOL]) as u32;
+        state += (hdr_buf.len() + data.len()) as u32;
+
+        // Header, without the checksum.
+        for (i, chunk) in hdr_buf.chunks_exact(2).enumerate() {
+            if i != 3 {
+                state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+            }
+        }
+
+        // Payload, padded with a zero if it has an odd number of bytes.
+        let mut chunks_iter: ChunksExact<u8> = data.chunks_exact(2);
+        while let Some(chunk) = chunks_iter.next() {
+            state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+        }
+        if let Some(&b) = chunks_iter.remainder().get(0) {
+            state += u16::from_be_bytes([b, 0]) as u32;
+        }
+
+        while state > 0xFFFF {
+            state -= 0xFFFF;
+        }
+        !state as u16
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{HomaHeader, HomaKind, HOMA_HEADER_SIZE};
+    use crate::runtime::{memory::DemiBuffer, network::socket::option::PriorityClass};
+    use ::anyhow::Result;
+    use ::std::net::Ipv4Addr;
+
+    const ALICE_IPV4: Ipv4Addr = Ipv4Addr::new(198, 0, 0, 1);
+    const BOB_IPV4: Ipv4Addr = Ipv4Addr::new(198, 0, 0, 2);
+
+    #[test]
+    fn test_homa_header_round_trip() -> Result<()> {
+        let header: HomaHeader = HomaHeader {
+            src_port: 0x32,
+            dst_port: 0x45,
+            kind: HomaKind::Data,
+            from_client: true,
+            rpc_id: 0x0102030405060708,
+            msg_len: 3000,
+            offset: 1400,
+            length: 5,
+            priority: PriorityClass::LatencyCritical,
+        };
+        let payload: [u8; 5] = [1, 2, 3, 4, 5];
+        let mut buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&payload, HOMA_HEADER_SIZE)?;
+        header.serialize_and_attach(&mut buf, &ALICE_IPV4, &BOB_IPV4);
+        crate::ensure_eq!(buf.len(), HOMA_HEADER_SIZE + payload.len());
+
+        let parsed: HomaHeader = HomaHeader::parse_and_strip(&ALICE_IPV4, &BOB_IPV4, &mut buf)?;
+        crate::ensure_eq!(parsed, header);
+        crate::ensure_eq!(&buf[..], &payload[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_homa_header_checksum_mismatch() -> Result<()> {
+        let header: HomaHeader = HomaHeader {
+            src_port: 0x32,
+            dst_port: 0x45,
+            kind: HomaKind::Grant,
+            from_client: false,
+            rpc_id: 7,
+            msg_len: 0,
+            offset: 20000,
+            length: 0,
+            priority: PriorityClass::Background,
+        };
+        let mut buf: DemiBuffer = DemiBuffer::new_with_headroom(0, HOMA_HEADER_SIZE as u16);
+        header.serialize_and_attach(&mut buf, &ALICE_IPV4, &BOB_IPV4);
+        // A packet that was meant for someone else does not pass the check.
+        crate::ensure_eq!(
+            HomaHeader::parse_and_strip(&ALICE_IPV4, &ALICE_IPV4, &mut buf).is_err(),
+            true
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Messages of a Homa RPC. Senders may send the first round trip's worth of a message right away and have to wait for
+// grants from the receiver for the rest of it. Receivers reassemble messages from packets that may arrive in any order.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::{fail::Fail, memory::DemiBuffer, network::socket::option::PriorityClass};
+use ::libc::{EBADMSG, EMSGSIZE};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Largest message that we send or receive (in bytes). Messages are kept in a single buffer.
+pub const MAX_MESSAGE_SIZE: usize = u16::MAX as usize - 1;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// A message that we are receiving.
+pub struct IncomingMessage {
+    buf: DemiBuffer,
+    // Byte ranges that we received, sorted by start and without overlaps.
+    ranges: Vec<(u32, u32)>,
+    received_bytes: u32,
+    // Bytes that the sender may send without waiting for us.
+    granted: u32,
+    // Timer ticks since we last received a packet of the message.
+    idle_ticks: usize,
+}
+
+/// A message that we are sending.
+pub struct OutgoingMessage {
+    buf: DemiBuffer,
+    // Bytes that the receiver allows us to send.
+    granted: u32,
+    // Priority at which we send granted bytes.
+    priority: PriorityClass,
+    // First byte that we did not send yet.
+    next_offset: u32,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl IncomingMessage {
+    /// Starts receiving a message of [msg_len] bytes, of which the sender sends the first [unscheduled_bytes] without
+    /// waiting for a grant.
+    pub fn new(msg_len: u32, unscheduled_bytes: u32) -> Result<Self, Fail> {
+        if msg_len as usize > MAX_MESSAGE_SIZE {
+            let cause: String = format!("message too large (msg_len={:?})", msg_len);
+            error!("new(): {}", cause);
+            return Err(Fail::new(EMSGSIZE, &cause));
+        }
+        Ok(Self {
+            buf: DemiBuffer::new(msg_len as u16),
+            ranges: Vec::new(),
+            received_bytes: 0,
+            granted: unscheduled_bytes.min(msg_len),
+            idle_ticks: 0,
+        })
+    }
+
+    /// Copies [payload], which starts at [offset] of the message, into place.
+    pub fn receive(&mut self, offset: u32, payload: &[u8]) -> Result<(), Fail> {
+        let start: usize = offset as usize;
+        let end: usize = start + payload.len();
+        if end > self.buf.len() {
+            let cause: String = format!(
+                "data beyond the end of the message (offset={:?}, len={:?}, msg_len={:?})",
+                offset,
+                payload.len(),
+                self.buf.len()
+            );
+            error!("receive(): {}", cause);
+            return Err(Fail::new(EBADMSG, &cause));
+        }
+        self.idle_ticks = 0;
+        if start == end {
+            return Ok(());
+        }
+        self.buf[start..end].copy_from_slice(payload);
+        self.add_range(start as u32, end as u32);
+        // Senders never send ungranted bytes, so whatever arrived was granted.
+        self.granted = self.granted.max(end as u32);
+        Ok(())
+    }
+
+    pub fn msg_len(&self) -> u32 {
+        self.buf.len() as u32
+    }
+
+    pub fn received_bytes(&self) -> u32 {
+        self.received_bytes
+    }
+
+    pub fn granted(&self) -> u32 {
+        self.granted
+    }
+
+    pub fn set_granted(&mut self, granted: u32) {
+        self.granted = granted.min(self.msg_len()).max(self.granted);
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received_bytes == self.msg_len()
+    }
+
+    /// Returns the first range of granted bytes that we did not receive yet, as offset and length.
+    pub fn get_missing(&self) -> Option<(u32, u32)> {
+        let mut next: u32 = 0;
+        for &(start, end) in self.ranges.iter() {
+            if start > next {
+                break;
+            }
+            next = next.max(end);
+        }
+        if next >= self.granted {
+            return None;
+        }
+        let end: u32 = match self.ranges.iter().find(|(start, _)| *start > next) {
+            Some(&(start, _)) => start.min(self.granted),
+            None => self.granted,
+        };
+        Some((next, end - next))
+    }
+
+    /// Advances the idle timer of the message by one tick and returns the number of ticks since we last received a
+    /// packet of it.
+    pub fn on_tick(&mut self) -> usize {
+        self.idle_ticks += 1;
+        self.idle_ticks
+    }
+
+    /// Hands out the message, once it is complete.
+    pub fn take(self) -> DemiBuffer {
+        debug_assert!(self.is_complete());
+        self.buf
+    }
+
+    fn add_range(&mut self, start: u32, end: u32) {
+        let mut start: u32 = start;
+        let mut end: u32 = end;
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(self.ranges.len() + 1);
+        let mut inserted: bool = false;
+        for &(s, e) in self.ranges.iter() {
+            if e < start {
+                merged.push((s, e));
+            } else if s > end {
+                if !inserted {
+                    merged.push((start, end));
+                    inserted = true;
+                }
+                merged.push((s, e));
+            } else {
+                start = start.min(s);
+                end = end.max(e);
+            }
+        }
+        if !inserted {
+            merged.push((start, end));
+        }
+        self.ranges = merged;
+        self.received_bytes = self.ranges.iter().map(|(s, e)| e - s).sum();
+    }
+}
+
+impl OutgoingMessage {
+    /// Starts sending [buf], of which we may send the first [unscheduled_bytes] without waiting for a grant.
+    pub fn new(buf: DemiBuffer, unscheduled_bytes: u32) -> Result<Self, Fail> {
+        if buf.len() > MAX_MESSAGE_SIZE {
+            let cause: String = format!("message too large (len={:?})", buf.len());
+            error!("new(): {}", cause);
+            return Err(Fail::new(EMSGSIZE, &cause));
+        }
+        // Chunks of the message are handed out as slices, so gather the segments of a buffer chain first.
+        let buf: DemiBuffer = buf.coalesce(0)?;
+        let granted: u32 = unscheduled_bytes.min(buf.len() as u32);
+        Ok(Self {
+            buf,
+            granted,
+            priority: PriorityClass::LatencyCritical,
+            next_offset: 0,
+        })
+    }
+
+    pub fn msg_len(&self) -> u32 {
+        self.buf.len() as u32
+    }
+
+    pub fn priority(&self) -> PriorityClass {
+        self.priority
+    }
+
+    /// Accounts for a grant of everything before [offset], to be sent at [priority]. Grants may arrive out of order, so
+    /// they never take back bytes that we were granted before.
+    pub fn on_grant(&mut self, offset: u32, priority: PriorityClass) {
+        if offset > self.granted {
+            self.granted = offset.min(self.msg_len());
+            self.priority = priority;
+        }
+    }
+
+    /// Returns the next chunk of at most [max_len] granted bytes that we did not send yet, as offset and payload.
+    pub fn next_chunk(&mut self, max_len: usize) -> Option<(u32, &[u8])> {
+        if self.next_offset >= self.granted {
+            return None;
+        }
+        let start: u32 = self.next_offset;
+        let end: u32 = self.granted.min(start + max_len as u32);
+        self.next_offset = end;
+        Some((start, &self.buf[start as usize..end as usize]))
+    }
+
+    /// Returns the payload of [length] bytes at [offset], clamped to what we sent already, so that the receiver can
+    /// ask for it again.
+    pub fn chunk(&self, offset: u32, length: u32) -> &[u8] {
+        let start: usize = (offset.min(self.next_offset)) as usize;
+        let end: usize = (offset.saturating_add(length).min(self.next_offset)) as usize;
+        &self.buf[start..end]
+    }
+
+    /// Starts sending the message from the beginning again, as the receiver lost track of it.
+    pub fn restart(&mut self, unscheduled_bytes: u32) {
+        self.granted = unscheduled_bytes.min(self.msg_len());
+        self.priority = PriorityClass::LatencyCritical;
+        self.next_offset = 0;
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{IncomingMessage, OutgoingMessage};
+    use crate::runtime::{memory::DemiBuffer, network::socket::option::PriorityClass};
+    use ::anyhow::Result;
+
+    #[test]
+    fn test_incoming_message_reassembles_out_of_order() -> Result<()> {
+        let data: Vec<u8> = (0..30).collect();
+        let mut msg: IncomingMessage = IncomingMessage::new(30, 30)?;
+        msg.receive(20, &data[20..30])?;
+        msg.receive(0, &data[0..10])?;
+        crate::ensure_eq!(msg.is_complete(), false);
+        crate::ensure_eq!(msg.get_missing(), Some((10, 10)));
+        // Duplicates do not count twice.
+        msg.receive(0, &data[0..10])?;
+        crate::ensure_eq!(msg.received_bytes(), 20);
+        msg.receive(10, &data[10..20])?;
+        crate::ensure_eq!(msg.is_complete(), true);
+        crate::ensure_eq!(msg.get_missing(), None);
+        crate::ensure_eq!(&msg.take()[..], &data[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_incoming_message_only_asks_for_granted_bytes() -> Result<()> {
+        let data: Vec<u8> = vec![0; 10];
+        let mut msg: IncomingMessage = IncomingMessage::new(100, 10)?;
+        crate::ensure_eq!(msg.get_missing(), Some((0, 10)));
+        msg.receive(0, &data)?;
+        crate::ensure_eq!(msg.get_missing(), None);
+        msg.set_granted(40);
+        crate::ensure_eq!(msg.get_missing(), Some((10, 30)));
+        crate::ensure_eq!(msg.receive(95, &data).is_err(), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_outgoing_message_follows_grants() -> Result<()> {
+        let data: Vec<u8> = (0..100).collect();
+        let mut msg: OutgoingMessage = OutgoingMessage::new(DemiBuffer::from_slice(&data)?, 30)?;
+        crate::ensure_eq!(
+            msg.next_chunk(20).map(|(offset, chunk)| (offset, chunk.len())),
+            Some((0, 20))
+        );
+        crate::ensure_eq!(
+            msg.next_chunk(20).map(|(offset, chunk)| (offset, chunk.len())),
+            Some((20, 10))
+        );
+        crate::ensure_eq!(msg.next_chunk(20).is_none(), true);
+
+        // Stale grants are ignored.
+        msg.on_grant(70, PriorityClass::BestEffort);
+        msg.on_grant(50, PriorityClass::Background);
+        crate::ensure_eq!(msg.priority(), PriorityClass::BestEffort);
+        crate::ensure_eq!(
+            msg.next_chunk(100).map(|(offset, chunk)| (offset, chunk.len())),
+            Some((30, 40))
+        );
+        crate::ensure_eq!(msg.chunk(60, 100), &data[60..70]);
+
+        msg.restart(30);
+        crate::ensure_eq!(
+            msg.next_chunk(100).map(|(offset, chunk)| (offset, chunk.len())),
+            Some((0, 30))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_outgoing_message_gathers_chain() -> Result<()> {
+        let data: Vec<u8> = (0..100).collect();
+        let mut buf: DemiBuffer = DemiBuffer::from_slice(&data[..40])?;
+        buf.append(DemiBuffer::from_slice(&data[40..])?)?;
+        let mut msg: OutgoingMessage = OutgoingMessage::new(buf, 100)?;
+        crate::ensure_eq!(msg.msg_len(), 100);
+        crate::ensure_eq!(msg.next_chunk(60), Some((0, &data[..60])));
+        crate::ensure_eq!(msg.chunk(30, 20), &data[30..50]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Homa is an experimental receiver-driven transport for RPCs in datacenters. Senders send the first round trip's worth
+// of a message right away and the rest of it only as the receiver grants it, so that the receiver decides which message
+// goes first. Receivers grant the messages with the fewest remaining bytes at the highest priorities, which map to the
+// transmit queues of the multi-queue drivers. Homa plugs into layer 4 as a protocol extension and is only built with the
+// `homa` feature.
+
+//======================================================================================================================
+// Exports
+//======================================================================================================================
+
+mod grants;
+pub mod header;
+mod message;
+pub mod peer;
+pub mod socket;
+
+pub use self::{
+    message::MAX_MESSAGE_SIZE,
+    peer::SharedHomaPeer,
+    socket::{HomaRequest, SharedHomaSocket},
+};
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::std::time::Duration;
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// IP protocol number of Homa, which is one that is reserved for experimentation and testing (RFC 3692).
+pub const HOMA_PROTOCOL: u8 = 254;
+
+/// Bytes that are in flight for a message by default, which should cover one round trip.
+pub const DEFAULT_RTT_BYTES: u32 = 10_000;
+
+// Number of incoming messages that we grant at once.
+const OVERCOMMIT: usize = 2;
+
+// Interval of the timer that recovers from lost packets.
+const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
+// Timer ticks without hearing from the remote after which we ask it to send what we miss.
+const RESEND_TICKS: usize = 2;
+
+// Resend requests that go unanswered in a row before we give up on the remote.
+const MAX_RESENDS: usize = 5;
+
+// Timer ticks for which servers keep responses around, in case clients ask for parts of them again.
+const RESPONSE_LINGER_TICKS: usize = RESEND_TICKS * (MAX_RESENDS + 1);
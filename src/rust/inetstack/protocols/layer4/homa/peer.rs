@@ -0,0 +1,757 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::async_queue::SharedAsyncQueue,
+    inetstack::protocols::{
+        layer3::Ecn,
+        layer4::{
+            extension::{ExtensionTransmitter, ProtocolExtension},
+            homa::{
+                grants::{Candidate, Grant, GrantScheduler},
+                header::{HomaHeader, HomaKind, HOMA_HEADER_SIZE},
+                message::{IncomingMessage, OutgoingMessage},
+                socket::{HomaRequest, SharedHomaSocket},
+                MAX_RESENDS, OVERCOMMIT, RESEND_TICKS, RESPONSE_LINGER_TICKS, TICK_INTERVAL,
+            },
+        },
+        MAX_HEADER_SIZE,
+    },
+    runtime::{
+        fail::Fail, memory::DemiBuffer, network::socket::option::PriorityClass, network::types::MacAddress,
+        yield_with_timeout, SharedDemiRuntime, SharedObject,
+    },
+};
+use ::futures::FutureExt;
+use ::libc::{EADDRINUSE, ECANCELED, ENOENT, ENOTCONN, ETIMEDOUT};
+use ::std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddrV4},
+    ops::{Deref, DerefMut},
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+// An RPC that we issued.
+struct ClientRpc {
+    local_port: u16,
+    server: SocketAddrV4,
+    request: OutgoingMessage,
+    response: Option<IncomingMessage>,
+    completion: SharedAsyncQueue<Result<DemiBuffer, Fail>>,
+    // Timer ticks since we last heard from the server.
+    idle_ticks: usize,
+    // Resend requests that went unanswered in a row.
+    resends: usize,
+}
+
+// An RPC that we serve.
+struct ServerRpc {
+    local_port: u16,
+    // The request, until it is complete and handed to the application.
+    request: Option<IncomingMessage>,
+    // The response, once the application provided it.
+    response: Option<OutgoingMessage>,
+    // Timer ticks since we last heard from the client.
+    idle_ticks: usize,
+}
+
+// Incoming messages that compete for grants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IncomingKey {
+    Request(SocketAddrV4, u64),
+    Response(u64),
+}
+
+/// Homa Peer
+pub struct HomaPeer {
+    runtime: SharedDemiRuntime,
+    /// Sends our packets, once we are registered as a protocol extension.
+    transmitter: Option<ExtensionTransmitter>,
+    /// Largest packet that we send, including the Homa header.
+    mss: usize,
+    scheduler: GrantScheduler,
+    next_rpc_id: u64,
+    /// Requests that arrived on bound ports and wait for the application, by local port.
+    sockets: HashMap<u16, SharedAsyncQueue<HomaRequest>>,
+    client_rpcs: HashMap<u64, ClientRpc>,
+    /// RPCs that we serve, by client and RPC ID.
+    server_rpcs: HashMap<(SocketAddrV4, u64), ServerRpc>,
+}
+
+#[derive(Clone)]
+pub struct SharedHomaPeer(SharedObject<HomaPeer>);
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl SharedHomaPeer {
+    /// Creates a peer that sends packets of at most [mss] bytes and keeps [rtt_bytes] of each message in flight.
+    pub fn new(runtime: SharedDemiRuntime, mss: usize, rtt_bytes: u32) -> Self {
+        Self(SharedObject::new(HomaPeer {
+            runtime,
+            transmitter: None,
+            mss,
+            scheduler: GrantScheduler::new(rtt_bytes, OVERCOMMIT),
+            next_rpc_id: 1,
+            sockets: HashMap::new(),
+            client_rpcs: HashMap::new(),
+            server_rpcs: HashMap::new(),
+        }))
+    }
+
+    /// Hands us the transmitter that we got when registering as a protocol extension and starts our timer.
+    pub fn attach(&mut self, transmitter: ExtensionTransmitter) -> Result<(), Fail> {
+        self.transmitter = Some(transmitter);
+        let mut runtime: SharedDemiRuntime = self.runtime.clone();
+        runtime.insert_background_coroutine("bgc::inetstack::homa::timer", Box::pin(self.clone().timer().fuse()))?;
+        Ok(())
+    }
+
+    /// Binds a socket to [port], which both issues RPCs and serves the RPCs that clients send to [port].
+    pub fn bind(&mut self, port: u16) -> Result<SharedHomaSocket, Fail> {
+        if self.sockets.contains_key(&port) {
+            let cause: String = format!("port is already in use (port={:?})", port);
+            error!("bind(): {}", cause);
+            return Err(Fail::new(EADDRINUSE, &cause));
+        }
+        let requests: SharedAsyncQueue<HomaRequest> = SharedAsyncQueue::default();
+        self.sockets.insert(port, requests.clone());
+        Ok(SharedHomaSocket::new(self.clone(), port, requests))
+    }
+
+    /// Unbinds [port] and forgets about the RPCs that we issued and served on it.
+    pub fn close(&mut self, port: u16) {
+        self.sockets.remove(&port);
+        self.server_rpcs.retain(|_, rpc| rpc.local_port != port);
+        let closed: Vec<u64> = self
+            .client_rpcs
+            .iter()
+            .filter(|(_, rpc)| rpc.local_port == port)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in closed {
+            if let Some(mut rpc) = self.client_rpcs.remove(&id) {
+                rpc.completion
+                    .push(Err(Fail::new(ECANCELED, "socket was closed while the RPC was pending")));
+            }
+        }
+    }
+
+    /// Sends [buf] from [local_port] to [server] as the request of a new RPC and waits for the response.
+    pub async fn send_request(
+        &mut self,
+        local_port: u16,
+        server: SocketAddrV4,
+        buf: DemiBuffer,
+    ) -> Result<DemiBuffer, Fail> {
+        let mut transmitter: ExtensionTransmitter = self.get_transmitter()?;
+        let rpc_id: u64 = self.next_rpc_id;
+        self.next_rpc_id += 1;
+        let mut request: OutgoingMessage = OutgoingMessage::new(buf, self.scheduler.unscheduled_bytes())?;
+        // The first packet goes out on its own, so that the link address of the server gets resolved.
+        let (offset, payload): (u32, Vec<u8>) = request
+            .next_chunk(self.max_payload())
+            .map(|(offset, payload)| (offset, payload.to_vec()))
+            .unwrap_or((0, Vec::new()));
+        let header: HomaHeader = HomaHeader {
+            msg_len: request.msg_len(),
+            offset,
+            length: payload.len() as u32,
+            ..Self::control_header(local_port, server.port(), HomaKind::Data, true, rpc_id)
+        };
+        let first: DemiBuffer = self.packet(&header, &payload, server.ip())?;
+        let mut completion: SharedAsyncQueue<Result<DemiBuffer, Fail>> = SharedAsyncQueue::default();
+        self.client_rpcs.insert(
+            rpc_id,
+            ClientRpc {
+                local_port,
+                server,
+                request,
+                response: None,
+                completion: completion.clone(),
+                idle_ticks: 0,
+                resends: 0,
+            },
+        );
+        if let Err(e) = transmitter
+            .transmit(*server.ip(), PriorityClass::LatencyCritical, first)
+            .await
+        {
+            self.client_rpcs.remove(&rpc_id);
+            return Err(e);
+        }
+        self.pump_request(rpc_id);
+        completion.pop(None).await?
+    }
+
+    /// Sends [buf] as the response to [request], which arrived on [local_port].
+    pub async fn send_response(&mut self, local_port: u16, request: &HomaRequest, buf: DemiBuffer) -> Result<(), Fail> {
+        let mut transmitter: ExtensionTransmitter = self.get_transmitter()?;
+        let mut response: OutgoingMessage = OutgoingMessage::new(buf, self.scheduler.unscheduled_bytes())?;
+        let key: (SocketAddrV4, u64) = (request.client, request.id);
+        match self.server_rpcs.get(&key) {
+            Some(rpc) if rpc.local_port == local_port && rpc.request.is_none() && rpc.response.is_none() => (),
+            _ => {
+                let cause: String = format!(
+                    "no pending RPC to respond to (client={:?}, id={:?})",
+                    request.client, request.id
+                );
+                error!("send_response(): {}", cause);
+                return Err(Fail::new(ENOENT, &cause));
+            },
+        }
+        let (offset, payload): (u32, Vec<u8>) = response
+            .next_chunk(self.max_payload())
+            .map(|(offset, payload)| (offset, payload.to_vec()))
+            .unwrap_or((0, Vec::new()));
+        let header: HomaHeader = HomaHeader {
+            msg_len: response.msg_len(),
+            offset,
+            length: payload.len() as u32,
+            ..Self::control_header(local_port, request.client.port(), HomaKind::Data, false, request.id)
+        };
+        let first: DemiBuffer = self.packet(&header, &payload, request.client.ip())?;
+        if let Some(rpc) = self.server_rpcs.get_mut(&key) {
+            rpc.response = Some(response);
+            rpc.idle_ticks = 0;
+        }
+        transmitter
+            .transmit(*request.client.ip(), PriorityClass::LatencyCritical, first)
+            .await?;
+        self.pump_response(key);
+        Ok(())
+    }
+
+    fn get_transmitter(&self) -> Result<ExtensionTransmitter, Fail> {
+        match self.transmitter.as_ref() {
+            Some(transmitter) => Ok(transmitter.clone()),
+            None => {
+                let cause: &str = "not registered as a protocol extension";
+                error!("get_transmitter(): {}", cause);
+                Err(Fail::new(ENOTCONN, cause))
+            },
+        }
+    }
+
+    fn max_payload(&self) -> usize {
+        self.mss - HOMA_HEADER_SIZE
+    }
+
+    // Header of a packet without payload, which goes out at the highest priority.
+    fn control_header(src_port: u16, dst_port: u16, kind: HomaKind, from_client: bool, rpc_id: u64) -> HomaHeader {
+        HomaHeader {
+            src_port,
+            dst_port,
+            kind,
+            from_client,
+            rpc_id,
+            msg_len: 0,
+            offset: 0,
+            length: 0,
+            priority: PriorityClass::LatencyCritical,
+        }
+    }
+
+    // Copies [payload] into a new packet, so that headers never get written into the buffer of a message.
+    fn packet(&self, header: &HomaHeader, payload: &[u8], remote_ipv4_addr: &Ipv4Addr) -> Result<DemiBuffer, Fail> {
+        let mut buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(payload, MAX_HEADER_SIZE)?;
+        let local_ipv4_addr: Ipv4Addr = self.get_transmitter()?.get_local_addr();
+        header.serialize_and_attach(&mut buf, &local_ipv4_addr, remote_ipv4_addr);
+        Ok(buf)
+    }
+
+    // Sends [header] without payload right away, as control packets are not worth waiting for.
+    fn send_control(&mut self, header: HomaHeader, remote_ipv4_addr: &Ipv4Addr) {
+        let result: Result<(), Fail> = self.packet(&header, &[], remote_ipv4_addr).and_then(|buf| {
+            self.get_transmitter()?
+                .try_transmit(*remote_ipv4_addr, PriorityClass::LatencyCritical, buf)
+        });
+        if let Err(e) = result {
+            warn!("send_control(): could not send {:?}: {:?}", header.kind, e);
+        }
+    }
+
+    // Sends whatever we may send of the request of RPC [rpc_id].
+    fn pump_request(&mut self, rpc_id: u64) {
+        let max_payload: usize = self.max_payload();
+        let mut packets: Vec<(HomaHeader, Vec<u8>)> = Vec::new();
+        let server: SocketAddrV4 = match self.client_rpcs.get_mut(&rpc_id) {
+            Some(rpc) => {
+                let msg_len: u32 = rpc.request.msg_len();
+                let priority: PriorityClass = rpc.request.priority();
+                while let Some((offset, payload)) = rpc.request.next_chunk(max_payload) {
+                    let header: HomaHeader = HomaHeader {
+                        msg_len,
+                        offset,
+                        length: payload.len() as u32,
+                        priority,
+                        ..Self::control_header(rpc.local_port, rpc.server.port(), HomaKind::Data, true, rpc_id)
+                    };
+                    packets.push((header, payload.to_vec()));
+                }
+                rpc.server
+            },
+            None => return,
+        };
+        self.send_data(packets, server.ip());
+    }
+
+    // Sends whatever we may send of the response of the RPC that [key] identifies.
+    fn pump_response(&mut self, key: (SocketAddrV4, u64)) {
+        let max_payload: usize = self.max_payload();
+        let mut packets: Vec<(HomaHeader, Vec<u8>)> = Vec::new();
+        if let Some(rpc) = self.server_rpcs.get_mut(&key) {
+            if let Some(response) = rpc.response.as_mut() {
+                let msg_len: u32 = response.msg_len();
+                let priority: PriorityClass = response.priority();
+                while let Some((offset, payload)) = response.next_chunk(max_payload) {
+                    let header: HomaHeader = HomaHeader {
+                        msg_len,
+                        offset,
+                        length: payload.len() as u32,
+                        priority,
+                        ..Self::control_header(rpc.local_port, key.0.port(), HomaKind::Data, false, key.1)
+                    };
+                    packets.push((header, payload.to_vec()));
+                }
+            }
+        }
+        self.send_data(packets, key.0.ip());
+    }
+
+    fn send_data(&mut self, packets: Vec<(HomaHeader, Vec<u8>)>, remote_ipv4_addr: &Ipv4Addr) {
+        for (header, payload) in packets {
+            let priority: PriorityClass = header.priority;
+            let result: Result<(), Fail> = self
+                .packet(&header, &payload, remote_ipv4_addr)
+                .and_then(|buf| self.get_transmitter()?.try_transmit(*remote_ipv4_addr, priority, buf));
+            if let Err(e) = result {
+                // The receiver asks for lost data again.
+                warn!("send_data(): could not send data: {:?}", e);
+                return;
+            }
+        }
+    }
+
+    // Hands out grants to the incoming messages that deserve them.
+    fn send_grants(&mut self) {
+        let mut candidates: Vec<Candidate<IncomingKey>> = Vec::new();
+        for (key, rpc) in self.server_rpcs.iter() {
+            if let Some(msg) = rpc.request.as_ref() {
+                candidates.push(Self::candidate(IncomingKey::Request(key.0, key.1), msg));
+            }
+        }
+        for (id, rpc) in self.client_rpcs.iter() {
+            if let Some(msg) = rpc.response.as_ref() {
+                candidates.push(Self::candidate(IncomingKey::Response(*id), msg));
+            }
+        }
+        let grants: Vec<Grant<IncomingKey>> = self.scheduler.schedule(&candidates);
+        for grant in grants {
+            self.send_grant(grant.key, grant.offset, grant.priority);
+        }
+    }
+
+    fn candidate(key: IncomingKey, msg: &IncomingMessage) -> Candidate<IncomingKey> {
+        Candidate {
+            key,
+            msg_len: msg.msg_len(),
+            received_bytes: msg.received_bytes(),
+            granted: msg.granted(),
+        }
+    }
+
+    fn send_grant(&mut self, key: IncomingKey, offset: u32, priority: PriorityClass) {
+        let (header, remote): (HomaHeader, SocketAddrV4) = match key {
+            IncomingKey::Request(client, rpc_id) => match self.server_rpcs.get_mut(&(client, rpc_id)) {
+                Some(ServerRpc {
+                    local_port,
+                    request: Some(msg),
+                    ..
+                }) => {
+                    msg.set_granted(offset);
+                    let header: HomaHeader = HomaHeader {
+                        offset,
+                        priority,
+                        ..Self::control_header(*local_port, client.port(), HomaKind::Grant, false, rpc_id)
+                    };
+                    (header, client)
+                },
+                _ => return,
+            },
+            IncomingKey::Response(rpc_id) => match self.client_rpcs.get_mut(&rpc_id) {
+                Some(ClientRpc {
+                    local_port,
+                    server,
+                    response: Some(msg),
+                    ..
+                }) => {
+                    msg.set_granted(offset);
+                    let header: HomaHeader = HomaHeader {
+                        offset,
+                        priority,
+                        ..Self::control_header(*local_port, server.port(), HomaKind::Grant, true, rpc_id)
+                    };
+                    (header, *server)
+                },
+                _ => return,
+            },
+        };
+        self.send_control(header, remote.ip());
+    }
+
+    fn receive_request_data(&mut self, client: SocketAddrV4, header: HomaHeader, payload: DemiBuffer) {
+        let key: (SocketAddrV4, u64) = (client, header.rpc_id);
+        if !self.server_rpcs.contains_key(&key) {
+            if !self.sockets.contains_key(&header.dst_port) {
+                trace!(
+                    "receive(): dropping request for unbound port (port={:?})",
+                    header.dst_port
+                );
+                return;
+            }
+            let request: IncomingMessage =
+                match IncomingMessage::new(header.msg_len, self.scheduler.unscheduled_bytes()) {
+                    Ok(request) => request,
+                    Err(e) => {
+                        warn!("receive(): dropping request: {:?}", e);
+                        return;
+                    },
+                };
+            self.server_rpcs.insert(
+                key,
+                ServerRpc {
+                    local_port: header.dst_port,
+                    request: Some(request),
+                    response: None,
+                    idle_ticks: 0,
+                },
+            );
+        }
+        let rpc: &mut ServerRpc = self.server_rpcs.get_mut(&key).expect("RPC should exist");
+        rpc.idle_ticks = 0;
+        let request: &mut IncomingMessage = match rpc.request.as_mut() {
+            Some(request) => request,
+            // We have the whole request already.
+            None => return,
+        };
+        if let Err(e) = request.receive(
+            header.offset,
+            &payload[..header.length.min(payload.len() as u32) as usize],
+        ) {
+            warn!("receive(): dropping request data: {:?}", e);
+            return;
+        }
+        if request.is_complete() {
+            let buf: DemiBuffer = rpc.request.take().expect("request should be there").take();
+            let local_port: u16 = rpc.local_port;
+            if let Some(requests) = self.sockets.get_mut(&local_port) {
+                requests.push(HomaRequest {
+                    client,
+                    id: header.rpc_id,
+                    buf,
+                });
+            }
+        }
+        self.send_grants();
+    }
+
+    fn receive_response_data(&mut self, header: HomaHeader, payload: DemiBuffer) {
+        let unscheduled_bytes: u32 = self.scheduler.unscheduled_bytes();
+        let rpc: &mut ClientRpc = match self.client_rpcs.get_mut(&header.rpc_id) {
+            Some(rpc) => rpc,
+            None => {
+                trace!("receive(): dropping response for unknown RPC (id={:?})", header.rpc_id);
+                return;
+            },
+        };
+        rpc.idle_ticks = 0;
+        rpc.resends = 0;
+        if rpc.response.is_none() {
+            match IncomingMessage::new(header.msg_len, unscheduled_bytes) {
+                Ok(response) => rpc.response = Some(response),
+                Err(e) => {
+                    warn!("receive(): dropping response: {:?}", e);
+                    return;
+                },
+            }
+        }
+        let response: &mut IncomingMessage = rpc.response.as_mut().expect("response should be there");
+        if let Err(e) = response.receive(
+            header.offset,
+            &payload[..header.length.min(payload.len() as u32) as usize],
+        ) {
+            warn!("receive(): dropping response data: {:?}", e);
+            return;
+        }
+        if response.is_complete() {
+            let mut rpc: ClientRpc = self.client_rpcs.remove(&header.rpc_id).expect("RPC should exist");
+            let buf: DemiBuffer = rpc.response.take().expect("response should be there").take();
+            rpc.completion.push(Ok(buf));
+            let ack: HomaHeader =
+                Self::control_header(rpc.local_port, rpc.server.port(), HomaKind::Ack, true, header.rpc_id);
+            self.send_control(ack, rpc.server.ip());
+        }
+        self.send_grants();
+    }
+
+    fn receive_grant(&mut self, remote: SocketAddrV4, header: HomaHeader) {
+        if header.from_client {
+            let key: (SocketAddrV4, u64) = (remote, header.rpc_id);
+            if let Some(response) = self.server_rpcs.get_mut(&key).and_then(|rpc| {
+                rpc.idle_ticks = 0;
+                rpc.response.as_mut()
+            }) {
+                response.on_grant(header.offset, header.priority);
+                self.pump_response(key);
+            }
+        } else if let Some(rpc) = self.client_rpcs.get_mut(&header.rpc_id) {
+            rpc.idle_ticks = 0;
+            rpc.resends = 0;
+            rpc.request.on_grant(header.offset, header.priority);
+            self.pump_request(header.rpc_id);
+        }
+    }
+
+    fn receive_resend(&mut self, remote: SocketAddrV4, header: HomaHeader) {
+        if header.from_client {
+            // The client misses (part of) our response.
+            let key: (SocketAddrV4, u64) = (remote, header.rpc_id);
+            let reply: Option<(HomaHeader, Vec<u8>)> = match self.server_rpcs.get_mut(&key) {
+                None => Some((
+                    Self::control_header(header.dst_port, remote.port(), HomaKind::Unknown, false, header.rpc_id),
+                    Vec::new(),
+                )),
+                Some(rpc) => {
+                    rpc.idle_ticks = 0;
+                    match rpc.response.as_ref() {
+                        // The request is still coming in or the application did not respond yet.
+                        None => Some((
+                            Self::control_header(rpc.local_port, remote.port(), HomaKind::Busy, false, header.rpc_id),
+                            Vec::new(),
+                        )),
+                        Some(response) => {
+                            let payload: &[u8] = response.chunk(header.offset, header.length);
+                            if payload.is_empty() {
+                                None
+                            } else {
+                                let data: HomaHeader = HomaHeader {
+                                    msg_len: response.msg_len(),
+                                    offset: header.offset,
+                                    length: payload.len() as u32,
+                                    priority: response.priority(),
+                                    ..Self::control_header(
+                                        rpc.local_port,
+                                        remote.port(),
+                                        HomaKind::Data,
+                                        false,
+                                        header.rpc_id,
+                                    )
+                                };
+                                Some((data, payload.to_vec()))
+                            }
+                        },
+                    }
+                },
+            };
+            if let Some((reply, payload)) = reply {
+                self.send_data(vec![(reply, payload)], remote.ip());
+            }
+        } else {
+            // The server misses (part of) our request.
+            let reply: Option<(HomaHeader, Vec<u8>)> = match self.client_rpcs.get_mut(&header.rpc_id) {
+                Some(rpc) => {
+                    rpc.idle_ticks = 0;
+                    rpc.resends = 0;
+                    let payload: &[u8] = rpc.request.chunk(header.offset, header.length);
+                    let data: HomaHeader = HomaHeader {
+                        msg_len: rpc.request.msg_len(),
+                        offset: header.offset,
+                        length: payload.len() as u32,
+                        priority: rpc.request.priority(),
+                        ..Self::control_header(rpc.local_port, rpc.server.port(), HomaKind::Data, true, header.rpc_id)
+                    };
+                    Some((data, payload.to_vec()))
+                },
+                None => None,
+            };
+            if let Some((reply, payload)) = reply {
+                self.send_data(vec![(reply, payload)], remote.ip());
+            }
+        }
+    }
+
+    fn receive_unknown(&mut self, header: HomaHeader) {
+        let unscheduled_bytes: u32 = self.scheduler.unscheduled_bytes();
+        if let Some(rpc) = self.client_rpcs.get_mut(&header.rpc_id) {
+            // The server lost track of our RPC, so we start all over again.
+            rpc.idle_ticks = 0;
+            rpc.response = None;
+            rpc.request.restart(unscheduled_bytes);
+            self.pump_request(header.rpc_id);
+        }
+    }
+
+    fn receive_busy(&mut self, header: HomaHeader) {
+        if let Some(rpc) = self.client_rpcs.get_mut(&header.rpc_id) {
+            rpc.idle_ticks = 0;
+            rpc.resends = 0;
+        }
+    }
+
+    fn receive_ack(&mut self, remote: SocketAddrV4, header: HomaHeader) {
+        self.server_rpcs.remove(&(remote, header.rpc_id));
+    }
+
+    // Wakes up periodically to recover from lost packets.
+    async fn timer(mut self) {
+        loop {
+            yield_with_timeout(TICK_INTERVAL).await;
+            self.on_tick();
+        }
+    }
+
+    fn on_tick(&mut self) {
+        let unscheduled_bytes: u32 = self.scheduler.unscheduled_bytes();
+        let mut controls: Vec<(HomaHeader, SocketAddrV4)> = Vec::new();
+        let mut regrants: Vec<(IncomingKey, u32)> = Vec::new();
+
+        // Clients ask for what they miss and give up on servers that stopped responding.
+        let mut aborted: Vec<u64> = Vec::new();
+        for (id, rpc) in self.client_rpcs.iter_mut() {
+            rpc.idle_ticks += 1;
+            if rpc.idle_ticks < RESEND_TICKS {
+                continue;
+            }
+            rpc.idle_ticks = 0;
+            rpc.resends += 1;
+            if rpc.resends > MAX_RESENDS {
+                aborted.push(*id);
+                continue;
+            }
+            let (offset, length): (u32, u32) = match rpc.response.as_ref() {
+                Some(response) => match response.get_missing() {
+                    Some(missing) => missing,
+                    None => {
+                        // Our last grant seems to have been lost.
+                        regrants.push((IncomingKey::Response(*id), response.granted()));
+                        continue;
+                    },
+                },
+                None => (0, unscheduled_bytes),
+            };
+            let header: HomaHeader = HomaHeader {
+                offset,
+                length,
+                ..Self::control_header(rpc.local_port, rpc.server.port(), HomaKind::Resend, true, *id)
+            };
+            controls.push((header, rpc.server));
+        }
+        for id in aborted {
+            if let Some(mut rpc) = self.client_rpcs.remove(&id) {
+                let cause: String = format!("server stopped responding (server={:?}, id={:?})", rpc.server, id);
+                warn!("on_tick(): {}", cause);
+                rpc.completion.push(Err(Fail::new(ETIMEDOUT, &cause)));
+            }
+        }
+
+        // Servers ask for what they miss of requests and forget about responses that clients never acknowledged.
+        let mut expired: Vec<(SocketAddrV4, u64)> = Vec::new();
+        for (key, rpc) in self.server_rpcs.iter_mut() {
+            match (rpc.request.as_mut(), rpc.response.as_ref()) {
+                (Some(request), _) => {
+                    let idle_ticks: usize = request.on_tick();
+                    if idle_ticks > RESEND_TICKS * (MAX_RESENDS + 1) {
+                        expired.push(*key);
+                    } else if idle_ticks % RESEND_TICKS == 0 {
+                        match request.get_missing() {
+                            Some((offset, length)) => controls.push((
+                                HomaHeader {
+                                    offset,
+                                    length,
+                                    ..Self::control_header(rpc.local_port, key.0.port(), HomaKind::Resend, false, key.1)
+                                },
+                                key.0,
+                            )),
+                            None => regrants.push((IncomingKey::Request(key.0, key.1), request.granted())),
+                        }
+                    }
+                },
+                (None, Some(_)) => {
+                    rpc.idle_ticks += 1;
+                    if rpc.idle_ticks > RESPONSE_LINGER_TICKS {
+                        expired.push(*key);
+                    }
+                },
+                // The application is working on the response.
+                (None, None) => (),
+            }
+        }
+        for key in expired {
+            self.server_rpcs.remove(&key);
+        }
+
+        for (header, remote) in controls {
+            self.send_control(header, remote.ip());
+        }
+        for (key, offset) in regrants {
+            self.send_grant(key, offset, PriorityClass::BestEffort);
+        }
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl ProtocolExtension for SharedHomaPeer {
+    fn receive(&mut self, src_ipv4_addr: Ipv4Addr, _: MacAddress, _: Ecn, mut payload: DemiBuffer) {
+        let local_ipv4_addr: Ipv4Addr = match self.transmitter.as_ref() {
+            Some(transmitter) => transmitter.get_local_addr(),
+            None => return,
+        };
+        let header: HomaHeader = match HomaHeader::parse_and_strip(&src_ipv4_addr, &local_ipv4_addr, &mut payload) {
+            Ok(header) => header,
+            Err(e) => {
+                warn!("receive(): dropping invalid packet: {:?}", e);
+                return;
+            },
+        };
+        let remote: SocketAddrV4 = SocketAddrV4::new(src_ipv4_addr, header.src_port);
+        match (header.kind, header.from_client) {
+            (HomaKind::Data, true) => self.receive_request_data(remote, header, payload),
+            (HomaKind::Data, false) => self.receive_response_data(header, payload),
+            (HomaKind::Grant, _) => self.receive_grant(remote, header),
+            (HomaKind::Resend, _) => self.receive_resend(remote, header),
+            (HomaKind::Unknown, false) => self.receive_unknown(header),
+            (HomaKind::Busy, false) => self.receive_busy(header),
+            (HomaKind::Ack, true) => self.receive_ack(remote, header),
+            (kind, from_client) => warn!(
+                "receive(): dropping {:?} packet in the wrong direction (from_client={:?})",
+                kind, from_client
+            ),
+        }
+    }
+}
+
+impl Deref for SharedHomaPeer {
+    type Target = HomaPeer;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.deref()
+    }
+}
+
+impl DerefMut for SharedHomaPeer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.deref_mut()
+    }
+}
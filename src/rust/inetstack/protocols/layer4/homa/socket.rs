@@ -0,0 +1,70 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::async_queue::SharedAsyncQueue,
+    inetstack::protocols::layer4::homa::peer::SharedHomaPeer,
+    runtime::{fail::Fail, memory::DemiBuffer},
+};
+use ::std::net::SocketAddrV4;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// A request that a client sent to one of our sockets.
+pub struct HomaRequest {
+    pub client: SocketAddrV4,
+    /// Identifier of the RPC, which the response refers to.
+    pub id: u64,
+    pub buf: DemiBuffer,
+}
+
+/// A Homa socket, which issues RPCs to servers and serves the RPCs that clients send to its port.
+#[derive(Clone)]
+pub struct SharedHomaSocket {
+    peer: SharedHomaPeer,
+    port: u16,
+    requests: SharedAsyncQueue<HomaRequest>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl SharedHomaSocket {
+    pub fn new(peer: SharedHomaPeer, port: u16, requests: SharedAsyncQueue<HomaRequest>) -> Self {
+        Self { peer, port, requests }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Sends [buf] to [server] as the request of a new RPC and waits for the response.
+    pub async fn send_request(&mut self, server: SocketAddrV4, buf: DemiBuffer) -> Result<DemiBuffer, Fail> {
+        let port: u16 = self.port;
+        self.peer.send_request(port, server, buf).await
+    }
+
+    /// Waits for the next request that a client sends to us.
+    pub async fn recv_request(&mut self) -> Result<HomaRequest, Fail> {
+        self.requests.pop(None).await
+    }
+
+    /// Sends [buf] as the response to [request].
+    pub async fn send_response(&mut self, request: &HomaRequest, buf: DemiBuffer) -> Result<(), Fail> {
+        let port: u16 = self.port;
+        self.peer.send_response(port, request, buf).await
+    }
+
+    /// Unbinds the socket. RPCs that we issued and did not complete yet fail with ECANCELED.
+    pub fn close(&mut self) {
+        let port: u16 = self.port;
+        self.peer.close(port);
+    }
+}
@@ -5,7 +5,14 @@
 // Exports
 //======================================================================================================================
 
+pub mod checksum_sampler;
 pub mod ephemeral;
+pub mod extension;
+#[cfg(feature = "homa")]
+pub mod homa;
+pub mod pacer;
+pub mod rmp;
+pub mod stats_delta;
 pub mod tcp;
 pub mod udp;
 
@@ -13,30 +20,35 @@ pub mod udp;
 // Imports
 //======================================================================================================================
 
-#[cfg(test)]
 use crate::runtime::network::types::MacAddress;
 use crate::{
     demi_sgarray_t,
     demikernel::config::Config,
     expect_some,
     inetstack::protocols::{
-        layer3::{ip::IpProtocol, SharedLayer3Endpoint},
+        layer3::{ip::IpProtocol, Ecn, SharedLayer3Endpoint},
         layer4::{
             ephemeral::EphemeralPorts,
-            tcp::{SharedTcpPeer, SharedTcpSocket},
+            extension::{ExtensionTransmitter, ProtocolExtension, ProtocolExtensions},
+            tcp::{
+                handoff::{ConnectionHandoff, CoreHint, HandoffSender},
+                SharedTcpPeer, SharedTcpSocket,
+            },
             udp::{SharedUdpPeer, SharedUdpSocket},
         },
     },
+    perftools::stages::Stage,
     runtime::{
         fail::Fail,
         memory::{DemiBuffer, MemoryRuntime},
         network::{consts::RECEIVE_BATCH_SIZE, unwrap_socketaddr},
         SharedDemiRuntime,
     },
-    timer, SocketOption,
+    stage_timer, timer, PopFlags, SocketOption,
 };
+use ::serde_json::{json, Value};
 use ::socket2::{Domain, Type};
-use ::std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use ::std::net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4};
 #[cfg(test)]
 use ::std::{collections::HashMap, hash::RandomState, time::Duration};
 
@@ -51,6 +63,7 @@ pub struct Peer {
     udp: SharedUdpPeer,
     layer3_endpoint: SharedLayer3Endpoint,
     ephemeral_ports: EphemeralPorts,
+    extensions: ProtocolExtensions,
 }
 
 /// Socket Representation.
@@ -79,6 +92,7 @@ impl Peer {
             udp,
             layer3_endpoint,
             ephemeral_ports: EphemeralPorts::default(),
+            extensions: ProtocolExtensions::default(),
         })
     }
 
@@ -92,16 +106,39 @@ impl Peer {
             Ok(_) => (),
             Err(_) => warn!("Could not receive from network interface, continuing ..."),
         }
+        for message in self.layer3_endpoint.take_fragmentation_needed() {
+            match message.protocol {
+                IpProtocol::TCP => self.tcp.on_fragmentation_needed(message),
+                // Datagrams are up to the application to size.
+                protocol => debug!("poll_once(): ignoring fragmentation needed (protocol={:?})", protocol),
+            }
+        }
     }
 
-    fn receive_batch(&mut self, batch: ArrayVec<(Ipv4Addr, IpProtocol, DemiBuffer), RECEIVE_BATCH_SIZE>) {
+    /// Marks the end of a poll iteration of the stack.
+    pub fn end_poll_iteration(&mut self) {
+        self.tcp.end_poll_iteration();
+    }
+
+    fn receive_batch(
+        &mut self,
+        batch: ArrayVec<(Ipv4Addr, Ipv4Addr, MacAddress, IpProtocol, Ecn, DemiBuffer), RECEIVE_BATCH_SIZE>,
+    ) {
         timer!("inetstack::poll_bg_work::for::for");
         trace!("found packets: {:?}", batch.len());
-        for (src_ipv4_addr, ip_type, payload) in batch {
+        for (src_ipv4_addr, dst_ipv4_addr, src_link_addr, ip_type, ecn, payload) in batch {
             match ip_type {
-                IpProtocol::TCP => self.tcp.receive(src_ipv4_addr, payload),
+                IpProtocol::TCP => {
+                    stage_timer!(Stage::TcpProcess);
+                    self.tcp
+                        .receive(src_ipv4_addr, dst_ipv4_addr, src_link_addr, ecn, payload)
+                },
                 IpProtocol::UDP => self.udp.receive(src_ipv4_addr, payload),
-                _ => unreachable!("Should have been handled at a lower layer"),
+                IpProtocol::Other(protocol) => {
+                    self.extensions
+                        .receive(protocol, src_ipv4_addr, src_link_addr, ecn, payload)
+                },
+                IpProtocol::ICMPv4 => unreachable!("Should have been handled at a lower layer"),
             }
         }
     }
@@ -118,15 +155,45 @@ impl Peer {
         }
     }
 
+    /// Registers [extension] to handle the datagrams of IP protocol number [protocol], which must be one that the stack
+    /// does not implement itself. Returns the transmitter through which the extension sends its own datagrams.
+    pub fn register_protocol(
+        &mut self,
+        protocol: u8,
+        extension: Box<dyn ProtocolExtension>,
+    ) -> Result<ExtensionTransmitter, Fail> {
+        self.extensions.register(protocol, extension)?;
+        Ok(ExtensionTransmitter::new(protocol, self.layer3_endpoint.clone()))
+    }
+
+    /// Removes the extension that handles IP protocol number [protocol] and hands it back.
+    pub fn unregister_protocol(&mut self, protocol: u8) -> Result<Box<dyn ProtocolExtension>, Fail> {
+        self.extensions.unregister(protocol)
+    }
+
+    /// Changes the local IPv4 address at runtime. Our neighbors learn the new address through a gratuitous ARP, and
+    /// sockets that are bound to the old address, but not connected, move over to the new one.
+    pub fn set_local_addr(&mut self, local_ipv4_addr: Ipv4Addr) -> Result<(), Fail> {
+        if local_ipv4_addr.is_unspecified() || local_ipv4_addr.is_broadcast() || local_ipv4_addr.is_multicast() {
+            let cause: String = format!("invalid local address (local={:?})", local_ipv4_addr);
+            error!("set_local_addr(): {}", &cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+
+        let old_ipv4_addr: Ipv4Addr = self.layer3_endpoint.get_local_addr();
+        self.layer3_endpoint.set_local_addr(local_ipv4_addr);
+        if old_ipv4_addr != local_ipv4_addr {
+            self.tcp.rebind(old_ipv4_addr, local_ipv4_addr);
+            self.udp.rebind(old_ipv4_addr, local_ipv4_addr);
+        }
+        Ok(())
+    }
+
     /// Set an SO_* option on the socket.
     pub fn set_socket_option(&mut self, sd: &mut Socket, option: SocketOption) -> Result<(), Fail> {
         match sd {
             Socket::Tcp(socket) => self.tcp.set_socket_option(socket, option),
-            Socket::Udp(_) => {
-                let cause: String = format!("Socket options are not supported on UDP sockets");
-                error!("get_socket_option(): {}", cause);
-                Err(Fail::new(libc::ENOTSUP, &cause))
-            },
+            Socket::Udp(socket) => self.udp.set_socket_option(socket, option),
         }
     }
 
@@ -135,11 +202,7 @@ impl Peer {
     pub fn get_socket_option(&mut self, sd: &mut Socket, option: SocketOption) -> Result<SocketOption, Fail> {
         match sd {
             Socket::Tcp(socket) => self.tcp.get_socket_option(socket, option),
-            Socket::Udp(_) => {
-                let cause: String = format!("Socket options are not supported on UDP sockets");
-                error!("get_socket_option(): {}", cause);
-                Err(Fail::new(libc::ENOTSUP, &cause))
-            },
+            Socket::Udp(socket) => self.udp.get_socket_option(socket, option),
         }
     }
 
@@ -154,6 +217,26 @@ impl Peer {
         }
     }
 
+    /// Gets the local address of a socket. Like on Linux, sockets that are not bound yet report the wildcard address.
+    pub fn getsockname(&mut self, sd: &mut Socket) -> Result<SocketAddrV4, Fail> {
+        let local: Option<SocketAddrV4> = match sd {
+            Socket::Tcp(socket) => socket.local(),
+            Socket::Udp(socket) => socket.local(),
+        };
+        Ok(local.unwrap_or(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)))
+    }
+
+    pub fn shutdown(&mut self, sd: &mut Socket, how: Shutdown) -> Result<(), Fail> {
+        match sd {
+            Socket::Tcp(socket) => self.tcp.shutdown(socket, how),
+            Socket::Udp(_) => {
+                let cause: String = format!("shutdown is not supported on UDP sockets");
+                error!("shutdown(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
     ///
     /// **Brief**
     ///
@@ -223,6 +306,67 @@ impl Peer {
         }
     }
 
+    /// Returns the most recent error that affected the socket `sd` and clears it. UDP sockets report their errors
+    /// through the operations that run into them.
+    pub fn get_last_error(&mut self, sd: &mut Socket) -> Result<Option<Fail>, Fail> {
+        match sd {
+            Socket::Tcp(socket) => Ok(self.tcp.get_last_error(socket)),
+            Socket::Udp(_) => Ok(None),
+        }
+    }
+
+    /// Returns the number of bytes that can be popped from the socket `sd` without waiting. For UDP sockets, these are
+    /// the bytes of all datagrams that are waiting to be popped.
+    pub fn get_buffered_bytes(&mut self, sd: &mut Socket) -> Result<usize, Fail> {
+        match sd {
+            Socket::Tcp(socket) => Ok(self.tcp.get_buffered_bytes(socket)),
+            Socket::Udp(socket) => Ok(self.udp.get_buffered_bytes(socket)),
+        }
+    }
+
+    /// Hands connections that the listening socket `sd` establishes off to worker cores through `handoff`.
+    pub fn set_tcp_handoff(&mut self, sd: &mut Socket, handoff: HandoffSender) -> Result<(), Fail> {
+        match sd {
+            Socket::Tcp(socket) => self.tcp.set_handoff(socket, handoff),
+            Socket::Udp(_) => {
+                let cause: String = format!("connection hand-off is not supported on UDP sockets");
+                error!("set_tcp_handoff(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
+    /// Sets the hand-off channels of the worker cores that accept hints on the listening socket `sd` refer to.
+    pub fn set_tcp_handoff_targets(&mut self, sd: &mut Socket, targets: Vec<HandoffSender>) -> Result<(), Fail> {
+        match sd {
+            Socket::Tcp(socket) => self.tcp.set_handoff_targets(socket, targets),
+            Socket::Udp(_) => {
+                let cause: String = format!("connection hand-off is not supported on UDP sockets");
+                error!("set_tcp_handoff_targets(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
+    /// Waits for the next connection on the listening socket `sd` and hands it off to the worker core with hint `core`.
+    pub async fn accept_on_core(&mut self, sd: &mut Socket, core: CoreHint) -> Result<SocketAddr, Fail> {
+        match sd {
+            Socket::Tcp(socket) => Ok(self.tcp.accept_on_core(socket, core).await?.into()),
+            Socket::Udp(_) => {
+                let cause: String = format!("operation not supported");
+                error!("accept_on_core(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
+    /// Takes over a TCP connection that was handed off by a listening socket on another core.
+    pub fn adopt_tcp_connection(&mut self, handoff: ConnectionHandoff) -> Result<(Socket, SocketAddr), Fail> {
+        let remote: SocketAddrV4 = handoff.remote;
+        let socket: SharedTcpSocket = self.tcp.adopt(handoff)?;
+        Ok((Socket::Tcp(socket), remote.into()))
+    }
+
     ///
     /// **Brief**
     ///
@@ -254,6 +398,18 @@ impl Peer {
         }
     }
 
+    /// Checks, without blocking, whether there are connections waiting to be accepted on the listening socket `sd`.
+    pub fn has_pending_connections(&mut self, sd: &mut Socket) -> Result<bool, Fail> {
+        match sd {
+            Socket::Tcp(socket) => self.tcp.has_pending_connections(socket),
+            Socket::Udp(_) => {
+                let cause: String = format!("Pending connections are not supported on UDP sockets");
+                error!("has_pending_connections(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
     ///
     /// **Brief**
     ///
@@ -273,15 +429,23 @@ impl Peer {
             Socket::Tcp(socket) => {
                 // FIXME: add IPv6 support; https://github.com/microsoft/demikernel/issues/935
                 let remote: SocketAddrV4 = unwrap_socketaddr(remote)?;
-                // If not bound, allocate an ephemeral port.
+                // If not bound, allocate an ephemeral port. Sockets that are bound to the wildcard address connect from
+                // the local address.
                 let local: SocketAddrV4 = match socket.local() {
+                    Some(local) if local.ip().is_unspecified() => {
+                        SocketAddrV4::new(self.layer3_endpoint.get_local_addr(), local.port())
+                    },
                     Some(local) => local,
                     None => SocketAddrV4::new(self.layer3_endpoint.get_local_addr(), self.ephemeral_ports.alloc()?),
                 };
 
                 self.tcp.connect(socket, local, remote).await
             },
-            _ => Err(Fail::new(libc::EINVAL, "invalid queue type")),
+            Socket::Udp(socket) => {
+                // FIXME: add IPv6 support; https://github.com/microsoft/demikernel/issues/935
+                let remote: SocketAddrV4 = unwrap_socketaddr(remote)?;
+                self.udp.connect(socket, remote)
+            },
         }
     }
 
@@ -321,6 +485,14 @@ impl Peer {
         }
     }
 
+    /// Closes a socket right away. TCP connections are reset instead of being shut down gracefully.
+    pub fn abort(&mut self, sd: &mut Socket) -> Result<(), Fail> {
+        if let Socket::Tcp(socket) = sd {
+            self.tcp.abort(socket);
+        }
+        self.hard_close(sd)
+    }
+
     /// Forcibly close a socket. This should only be used on clean up.
     pub fn hard_close(&mut self, sd: &mut Socket) -> Result<(), Fail> {
         let local_port: Option<u16> = match sd {
@@ -356,12 +528,39 @@ impl Peer {
         }
     }
 
+    /// Pushes a buffer to a TCP socket without waiting for it to be acknowledged. Unlike [push](Self::push), this fails
+    /// with EAGAIN instead of waiting when the send buffer of the socket is full.
+    pub fn try_push(&mut self, sd: &mut Socket, buf: &mut DemiBuffer) -> Result<(), Fail> {
+        match sd {
+            Socket::Tcp(socket) => self.tcp.try_push(socket, buf),
+            Socket::Udp(_) => {
+                let cause: String = format!("non-blocking push is not supported on UDP sockets");
+                error!("try_push(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
+    /// Waits until at least `min_bytes` can be pushed to the socket without queueing behind its send window or its
+    /// congestion window. UDP sockets do not buffer outgoing data, so they are always writable.
+    pub async fn wait_for_writable(&mut self, sd: &mut Socket, min_bytes: usize) -> Result<(), Fail> {
+        match sd {
+            Socket::Tcp(socket) => self.tcp.wait_for_writable(socket, min_bytes).await,
+            Socket::Udp(_) => Ok(()),
+        }
+    }
+
     /// Create a pop request to write data from IO connection represented by `qd` into a buffer
     /// allocated by the application.
-    pub async fn pop(&mut self, sd: &mut Socket, size: usize) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
+    pub async fn pop(
+        &mut self,
+        sd: &mut Socket,
+        size: usize,
+        flags: PopFlags,
+    ) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
         match sd {
-            Socket::Tcp(socket) => self.tcp.pop(socket, size).await,
-            Socket::Udp(socket) => self.udp.pop(socket, size).await,
+            Socket::Tcp(socket) => self.tcp.pop(socket, size, flags).await,
+            Socket::Udp(socket) => self.udp.pop(socket, size, flags).await,
         }
     }
 }
@@ -381,6 +580,17 @@ impl Peer {
     }
 }
 
+impl Socket {
+    /// Describes the socket for a state dump. Unless `scrub_payload` is set, this includes the data that is queued for
+    /// sending or reading.
+    pub fn dump_state(&self, scrub_payload: bool) -> Value {
+        match self {
+            Socket::Tcp(socket) => socket.dump_state(scrub_payload),
+            Socket::Udp(socket) => socket.dump_state(scrub_payload),
+        }
+    }
+}
+
 //======================================================================================================================
 // Trait Implementations
 //======================================================================================================================
@@ -402,3 +612,18 @@ impl MemoryRuntime for Peer {
         self.layer3_endpoint.sgafree(sga)
     }
 }
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Describes a buffer of payload data for a state dump. Unless `scrub_payload` is set, this includes the data itself,
+/// hex-encoded, so that the dump does not leak application data by accident.
+pub fn dump_payload(buf: &DemiBuffer, scrub_payload: bool) -> Value {
+    if scrub_payload {
+        json!({ "len": buf.len() })
+    } else {
+        let data: String = buf.segments().flatten().map(|byte| format!("{:02x}", byte)).collect();
+        json!({ "len": buf.len(), "data": data })
+    }
+}
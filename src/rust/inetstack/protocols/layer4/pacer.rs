@@ -0,0 +1,134 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::network::socket::option::{Pacing, PacingRate};
+use ::std::time::{Duration, Instant};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Token bucket that shapes the output of a socket. The bucket fills up at the configured rate and holds at most one
+/// burst worth of tokens. Sending a packet costs one token when pacing by packets, or one token per byte when pacing by
+/// bytes.
+#[derive(Debug)]
+pub struct Pacer {
+    pacing: Pacing,
+    /// Tokens that are currently available. This goes negative when a packet is larger than the burst size, so that
+    /// the packet is paid for before the next one goes out.
+    tokens: f64,
+    /// Last time at which the bucket was refilled.
+    last_refill: Instant,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl Pacer {
+    /// Creates a pacer that starts with a full burst worth of tokens.
+    pub fn new(pacing: Pacing, now: Instant) -> Self {
+        Self {
+            pacing,
+            tokens: pacing.burst as f64,
+            last_refill: now,
+        }
+    }
+
+    pub fn get_pacing(&self) -> Pacing {
+        self.pacing
+    }
+
+    /// Changes the rate and burst size, keeping the tokens that are currently in the bucket.
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+        self.tokens = self.tokens.min(pacing.burst as f64);
+    }
+
+    /// Tries to take the tokens for a packet of `len` bytes at time `now`. Returns how long the caller has to wait
+    /// before trying again if there are not enough tokens yet.
+    pub fn try_consume(&mut self, now: Instant, len: usize) -> Result<(), Duration> {
+        let (rate, cost): (u64, f64) = match self.pacing.rate {
+            PacingRate::PacketsPerSecond(rate) => (rate, 1.0),
+            PacingRate::BytesPerSecond(rate) => (rate, len as f64),
+        };
+        let burst: f64 = self.pacing.burst as f64;
+
+        // Refill the bucket.
+        if now > self.last_refill {
+            let elapsed: Duration = now - self.last_refill;
+            self.tokens = (self.tokens + elapsed.as_secs_f64() * rate as f64).min(burst);
+            self.last_refill = now;
+        }
+
+        // A packet larger than the burst size can only go out once the bucket is full.
+        let required: f64 = cost.min(burst);
+        if self.tokens >= required {
+            self.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((required - self.tokens) / rate as f64))
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        inetstack::protocols::layer4::pacer::Pacer,
+        runtime::network::socket::option::{Pacing, PacingRate},
+    };
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    #[test]
+    fn test_pacer_packets_per_second() -> Result<()> {
+        let now: Instant = Instant::now();
+        let pacing: Pacing = Pacing {
+            rate: PacingRate::PacketsPerSecond(10),
+            burst: 2,
+        };
+        let mut pacer: Pacer = Pacer::new(pacing, now);
+
+        // The first burst goes out right away.
+        crate::ensure_eq!(pacer.try_consume(now, 1000), Ok(()));
+        crate::ensure_eq!(pacer.try_consume(now, 1000), Ok(()));
+        // Then, packets are spaced out by the rate.
+        crate::ensure_eq!(pacer.try_consume(now, 1000), Err(Duration::from_millis(100)));
+        crate::ensure_eq!(pacer.try_consume(now + Duration::from_millis(100), 1000), Ok(()));
+        // The bucket never holds more than one burst.
+        let later: Instant = now + Duration::from_secs(10);
+        crate::ensure_eq!(pacer.try_consume(later, 1000), Ok(()));
+        crate::ensure_eq!(pacer.try_consume(later, 1000), Ok(()));
+        crate::ensure_eq!(pacer.try_consume(later, 1000).is_err(), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pacer_bytes_per_second() -> Result<()> {
+        let now: Instant = Instant::now();
+        let pacing: Pacing = Pacing {
+            rate: PacingRate::BytesPerSecond(1000),
+            burst: 500,
+        };
+        let mut pacer: Pacer = Pacer::new(pacing, now);
+
+        crate::ensure_eq!(pacer.try_consume(now, 400), Ok(()));
+        crate::ensure_eq!(pacer.try_consume(now, 200), Err(Duration::from_millis(100)));
+        // A packet that is larger than the burst goes out once the bucket is full, and is paid for afterwards.
+        let full: Instant = now + Duration::from_millis(400);
+        crate::ensure_eq!(pacer.try_consume(full, 1500), Ok(()));
+        crate::ensure_eq!(pacer.try_consume(full, 100), Err(Duration::from_millis(1100)));
+
+        Ok(())
+    }
+}
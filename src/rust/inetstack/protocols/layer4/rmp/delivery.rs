@@ -0,0 +1,164 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Receive side of an RMP association. Messages are numbered, so we can tell duplicates apart from new messages and, on
+// ordered associations, hold back messages until the ones before them arrived. Unordered associations hand every message
+// to the application as soon as it arrives, so that a lost message does not hold up the ones after it.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::memory::DemiBuffer;
+use ::std::collections::HashMap;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// What became of a received message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrival {
+    /// The message is new.
+    New,
+    /// We received the message before, so it must not be delivered again. It still has to be acknowledged, as our peer
+    /// seems to have missed our acknowledgement.
+    Duplicate,
+    /// The message is too far ahead of the first message that we are missing, so we drop it without acknowledging it.
+    OutOfWindow,
+}
+
+/// Tracks which messages of an association were received and delivers them to the application.
+pub struct DeliveryQueue {
+    // Whether messages are delivered in the order in which they were sent.
+    ordered: bool,
+    // Number of the first message that we did not receive yet.
+    next_msg_no: u32,
+    // Messages after the first missing one that were received already. The buffer is only kept until the message is
+    // delivered, which happens right away on unordered associations.
+    received_ahead: HashMap<u32, Option<DemiBuffer>>,
+    // Number of messages after the first missing one that we accept.
+    window: u32,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl DeliveryQueue {
+    pub fn new(ordered: bool, window: u32) -> Self {
+        Self {
+            ordered,
+            next_msg_no: 0,
+            received_ahead: HashMap::new(),
+            window,
+        }
+    }
+
+    /// Accounts for the arrival of message [msg_no] and hands every message that can be delivered now to [deliver], in
+    /// order.
+    pub fn receive<F: FnMut(DemiBuffer)>(&mut self, msg_no: u32, buf: DemiBuffer, mut deliver: F) -> Arrival {
+        let ahead: u32 = msg_no.wrapping_sub(self.next_msg_no);
+        if ahead >= self.window {
+            // Messages that we received already are at most one window behind.
+            return if self.next_msg_no.wrapping_sub(msg_no) <= self.window {
+                Arrival::Duplicate
+            } else {
+                Arrival::OutOfWindow
+            };
+        }
+        if self.received_ahead.contains_key(&msg_no) {
+            return Arrival::Duplicate;
+        }
+
+        if ahead == 0 {
+            deliver(buf);
+            self.next_msg_no = self.next_msg_no.wrapping_add(1);
+            // Catch up with the messages that arrived ahead of this one.
+            while let Some(held) = self.received_ahead.remove(&self.next_msg_no) {
+                if let Some(buf) = held {
+                    deliver(buf);
+                }
+                self.next_msg_no = self.next_msg_no.wrapping_add(1);
+            }
+        } else if self.ordered {
+            self.received_ahead.insert(msg_no, Some(buf));
+        } else {
+            deliver(buf);
+            self.received_ahead.insert(msg_no, None);
+        }
+        Arrival::New
+    }
+
+    /// Returns the number of the first message that we did not receive yet.
+    pub fn get_next_msg_no(&self) -> u32 {
+        self.next_msg_no
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{Arrival, DeliveryQueue};
+    use crate::runtime::memory::DemiBuffer;
+    use ::anyhow::Result;
+
+    const WINDOW: u32 = 8;
+
+    // Feeds messages into the queue in the given order and returns the messages that it delivered, in order.
+    fn receive_all(queue: &mut DeliveryQueue, msg_nos: &[u32]) -> Result<(Vec<u8>, Vec<Arrival>)> {
+        let mut delivered: Vec<u8> = Vec::new();
+        let mut arrivals: Vec<Arrival> = Vec::new();
+        for msg_no in msg_nos {
+            let buf: DemiBuffer = DemiBuffer::from_slice(&[*msg_no as u8])?;
+            arrivals.push(queue.receive(*msg_no, buf, |buf| delivered.push(buf[0])));
+        }
+        Ok((delivered, arrivals))
+    }
+
+    #[test]
+    fn test_ordered_delivery() -> Result<()> {
+        let mut queue: DeliveryQueue = DeliveryQueue::new(true, WINDOW);
+        let (delivered, arrivals) = receive_all(&mut queue, &[0, 2, 3, 2, 1])?;
+        crate::ensure_eq!(delivered, vec![0, 1, 2, 3]);
+        crate::ensure_eq!(
+            arrivals,
+            vec![
+                Arrival::New,
+                Arrival::New,
+                Arrival::New,
+                Arrival::Duplicate,
+                Arrival::New
+            ]
+        );
+        crate::ensure_eq!(queue.get_next_msg_no(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unordered_delivery() -> Result<()> {
+        let mut queue: DeliveryQueue = DeliveryQueue::new(false, WINDOW);
+        // A lost message does not hold up the ones after it, but none is delivered twice.
+        let (delivered, arrivals) = receive_all(&mut queue, &[0, 2, 3, 2, 1, 0])?;
+        crate::ensure_eq!(delivered, vec![0, 2, 3, 1]);
+        crate::ensure_eq!(arrivals[3], Arrival::Duplicate);
+        crate::ensure_eq!(arrivals[5], Arrival::Duplicate);
+        crate::ensure_eq!(queue.get_next_msg_no(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_receive_window() -> Result<()> {
+        let mut queue: DeliveryQueue = DeliveryQueue::new(true, WINDOW);
+        let (delivered, arrivals) = receive_all(&mut queue, &[WINDOW, WINDOW - 1, 0])?;
+        crate::ensure_eq!(delivered, vec![0]);
+        crate::ensure_eq!(arrivals, vec![Arrival::OutOfWindow, Arrival::New, Arrival::New]);
+
+        Ok(())
+    }
+}
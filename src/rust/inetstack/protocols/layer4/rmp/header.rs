@@ -0,0 +1,206 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    inetstack::protocols::layer4::rmp::RMP_PROTOCOL,
+    runtime::{fail::Fail, memory::DemiBuffer},
+};
+use ::libc::EBADMSG;
+use ::std::{net::Ipv4Addr, slice::ChunksExact};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Size of an RMP header (in bytes).
+pub const RMP_HEADER_SIZE: usize = 16;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Kind of an RMP datagram.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RmpKind {
+    /// Carries a single message.
+    Data = 0,
+    /// Acknowledges a single message along with all messages before a cumulative message number.
+    Ack = 1,
+}
+
+/// RMP Datagram Header
+///
+/// ```text
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-------------------------------+-------------------------------+
+/// |          Source Port          |       Destination Port        |
+/// +---------------+---------------+-------------------------------+
+/// |     Kind      |   Reserved    |           Checksum            |
+/// +---------------+---------------+-------------------------------+
+/// |                        Message Number                         |
+/// +---------------------------------------------------------------+
+/// |                   Cumulative Message Number                   |
+/// +---------------------------------------------------------------+
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RmpHeader {
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub kind: RmpKind,
+    /// Number of the message that a data datagram carries or that an ACK acknowledges.
+    pub msg_no: u32,
+    /// In ACKs, the number of the first message that our peer has not received. All messages before it were received.
+    pub cumulative_msg_no: u32,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl RmpHeader {
+    /// Parses and strips the RMP header off of the datagram in [buf] that [src_ipv4_addr] sent to [dst_ipv4_addr].
+    pub fn parse_and_strip(
+        src_ipv4_addr: &Ipv4Addr,
+        dst_ipv4_addr: &Ipv4Addr,
+        buf: &mut DemiBuffer,
+    ) -> Result<Self, Fail> {
+        if buf.len() < RMP_HEADER_SIZE {
+            return Err(Fail::new(EBADMSG, "RMP datagram too small"));
+        }
+        let (hdr_buf, payload_buf): (&[u8], &[u8]) = buf[..].split_at(RMP_HEADER_SIZE);
+        let checksum: u16 = u16::from_be_bytes([hdr_buf[6], hdr_buf[7]]);
+        if checksum != Self::checksum(src_ipv4_addr, dst_ipv4_addr, hdr_buf, payload_buf) {
+            return Err(Fail::new(EBADMSG, "RMP checksum mismatch"));
+        }
+        let kind: RmpKind = match hdr_buf[4] {
+            0 => RmpKind::Data,
+            1 => RmpKind::Ack,
+            _ => return Err(Fail::new(EBADMSG, "unknown RMP datagram kind")),
+        };
+        let header: RmpHeader = Self {
+            src_port: u16::from_be_bytes([hdr_buf[0], hdr_buf[1]]),
+            dst_port: u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]),
+            kind,
+            msg_no: u32::from_be_bytes([hdr_buf[8], hdr_buf[9], hdr_buf[10], hdr_buf[11]]),
+            cumulative_msg_no: u32::from_be_bytes([hdr_buf[12], hdr_buf[13], hdr_buf[14], hdr_buf[15]]),
+        };
+        buf.adjust(RMP_HEADER_SIZE)
+            .expect("Buffer should be at least long enough to hold the RMP header");
+        Ok(header)
+    }
+
+    /// Serializes and prepends the RMP header on to the datagram in [buf] that goes from [src_ipv4_addr] to
+    /// [dst_ipv4_addr].
+    pub fn serialize_and_attach(&self, buf: &mut DemiBuffer, src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr) {
+        buf.prepend(RMP_HEADER_SIZE).expect("Should have enough headroom");
+        let (hdr_buf, payload): (&mut [u8], &mut [u8]) = buf[..].split_at_mut(RMP_HEADER_SIZE);
+        hdr_buf[0..2].copy_from_slice(&self.src_port.to_be_bytes());
+        hdr_buf[2..4].copy_from_slice(&self.dst_port.to_be_bytes());
+        hdr_buf[4] = self.kind as u8;
+        hdr_buf[5] = 0;
+        hdr_buf[6..8].copy_from_slice(&[0, 0]);
+        hdr_buf[8..12].copy_from_slice(&self.msg_no.to_be_bytes());
+        hdr_buf[12..16].copy_from_slice(&self.cumulative_msg_no.to_be_bytes());
+        let checksum: u16 = Self::checksum(src_ipv4_addr, dst_ipv4_addr, hdr_buf, payload);
+        hdr_buf[6..8].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    /// Computes the checksum of an RMP datagram, which works just like the UDP checksum. The checksum field itself is
+    /// skipped.
+    fn checksum(src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr, hdr_buf: &[u8], data: &[u8]) -> u16 {
+        let mut state: u32 = 0xffff;
+
+        // Pseudo header.
+        let src_octets: [u8; 4] = src_ipv4_addr.octets();
+        state += u16::from_be_bytes([src_octets[0], src_octets[1]]) as u32;
+        state += u16::from_be_bytes([src_octets[2], src_octets[3]]) as u32;
+        let dst_octets: [u8; 4] = dst_ipv4_addr.octets();
+        state += u16::from_be_bytes([dst_octets[0], dst_octets[1]]) as u32;
+        state += u16::from_be_bytes([dst_octets[2], dst_octets[3]]) as u32;
+        state += u16::from_be_bytes([0, RMP_PROTOCOL]) as u32;
+        state += (hdr_buf.len() + data.len()) as u32;
+
+        // Header, without the checksum.
+        for (i, chunk) in hdr_buf.chunks_exact(2).enumerate() {
+            if i != 3 {
+                state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+            }
+        }
+
+        // Payload, padded with a zero if it has an odd number of bytes.
+        let mut chunks_iter: ChunksExact<u8> = data.chunks_exact(2);
+        while let Some(chunk) = chunks_iter.next() {
+            state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+        }
+        if let Some(&b) = chunks_iter.remainder().get(0) {
+            state += u16::from_be_bytes([b, 0]) as u32;
+        }
+
+        while state > 0xFFFF {
+            state -= 0xFFFF;
+        }
+        !state as u16
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{RmpHeader, RmpKind, RMP_HEADER_SIZE};
+    use crate::runtime::memory::DemiBuffer;
+    use ::anyhow::Result;
+    use ::std::net::Ipv4Addr;
+
+    const ALICE_IPV4: Ipv4Addr = Ipv4Addr::new(198, 0, 0, 1);
+    const BOB_IPV4: Ipv4Addr = Ipv4Addr::new(198, 0, 0, 2);
+
+    #[test]
+    fn test_rmp_header_round_trip() -> Result<()> {
+        let header: RmpHeader = RmpHeader {
+            src_port: 0x32,
+            dst_port: 0x45,
+            kind: RmpKind::Ack,
+            msg_no: 7,
+            cumulative_msg_no: 5,
+        };
+        let payload: [u8; 5] = [1, 2, 3, 4, 5];
+        let mut buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&payload, RMP_HEADER_SIZE)?;
+        header.serialize_and_attach(&mut buf, &ALICE_IPV4, &BOB_IPV4);
+        crate::ensure_eq!(buf.len(), RMP_HEADER_SIZE + payload.len());
+
+        let parsed: RmpHeader = RmpHeader::parse_and_strip(&ALICE_IPV4, &BOB_IPV4, &mut buf)?;
+        crate::ensure_eq!(parsed, header);
+        crate::ensure_eq!(&buf[..], &payload[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rmp_header_checksum_mismatch() -> Result<()> {
+        let header: RmpHeader = RmpHeader {
+            src_port: 0x32,
+            dst_port: 0x45,
+            kind: RmpKind::Data,
+            msg_no: 7,
+            cumulative_msg_no: 0,
+        };
+        let mut buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&[1, 2, 3], RMP_HEADER_SIZE)?;
+        header.serialize_and_attach(&mut buf, &ALICE_IPV4, &BOB_IPV4);
+        buf[RMP_HEADER_SIZE] ^= 0xff;
+        crate::ensure_eq!(
+            RmpHeader::parse_and_strip(&ALICE_IPV4, &BOB_IPV4, &mut buf).is_err(),
+            true
+        );
+
+        Ok(())
+    }
+}
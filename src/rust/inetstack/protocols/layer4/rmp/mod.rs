@@ -0,0 +1,25 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// RMP is an experimental reliable message transport for RPC workloads. Unlike TCP, it preserves message boundaries and
+// retransmits only the messages that were lost, and associations may opt out of ordered delivery so that a lost message
+// does not hold up the ones after it. RMP plugs into layer 4 as a protocol extension and reuses the congestion control
+// and retransmission timeout machinery of TCP.
+
+//======================================================================================================================
+// Exports
+//======================================================================================================================
+
+mod delivery;
+pub mod header;
+pub mod peer;
+pub mod socket;
+
+pub use self::{peer::SharedRmpPeer, socket::SharedRmpSocket};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// IP protocol number of RMP, which is one that is reserved for experimentation and testing (RFC 3692).
+pub const RMP_PROTOCOL: u8 = 253;
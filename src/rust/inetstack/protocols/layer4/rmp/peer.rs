@@ -0,0 +1,221 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::async_queue::SharedAsyncQueue,
+    inetstack::protocols::{
+        layer3::Ecn,
+        layer4::{
+            extension::{ExtensionTransmitter, ProtocolExtension},
+            rmp::{
+                header::{RmpHeader, RmpKind},
+                socket::SharedRmpSocket,
+            },
+            tcp::congestion_control::CongestionControlConstructor,
+        },
+    },
+    runtime::{fail::Fail, memory::DemiBuffer, network::types::MacAddress, SharedDemiRuntime, SharedObject},
+};
+use ::futures::FutureExt;
+use ::std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddrV4},
+    ops::{Deref, DerefMut},
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+// A local port on which we accept associations.
+struct Listener {
+    ordered: bool,
+    accept_queue: SharedAsyncQueue<SharedRmpSocket>,
+}
+
+/// RMP Peer
+pub struct RmpPeer {
+    runtime: SharedDemiRuntime,
+    /// Sends our datagrams, once we are registered as a protocol extension.
+    transmitter: Option<ExtensionTransmitter>,
+    /// Largest datagram that we send, including the RMP header.
+    mss: usize,
+    congestion_control: CongestionControlConstructor,
+    /// Associations, by local port and remote endpoint.
+    sockets: HashMap<(u16, SocketAddrV4), SharedRmpSocket>,
+    listeners: HashMap<u16, Listener>,
+}
+
+#[derive(Clone)]
+pub struct SharedRmpPeer(SharedObject<RmpPeer>);
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl SharedRmpPeer {
+    pub fn new(runtime: SharedDemiRuntime, mss: usize, congestion_control: CongestionControlConstructor) -> Self {
+        Self(SharedObject::new(RmpPeer {
+            runtime,
+            transmitter: None,
+            mss,
+            congestion_control,
+            sockets: HashMap::new(),
+            listeners: HashMap::new(),
+        }))
+    }
+
+    /// Hands us the transmitter that we got when registering as a protocol extension.
+    pub fn attach(&mut self, transmitter: ExtensionTransmitter) {
+        self.transmitter = Some(transmitter);
+    }
+
+    /// Opens an association from [local_port] to [remote]. Messages are delivered in the order in which they were sent
+    /// only if [ordered] is set.
+    pub fn connect(&mut self, local_port: u16, remote: SocketAddrV4, ordered: bool) -> Result<SharedRmpSocket, Fail> {
+        if self.sockets.contains_key(&(local_port, remote)) {
+            let cause: String = format!(
+                "association already exists (local_port={:?}, remote={:?})",
+                local_port, remote
+            );
+            error!("connect(): {}", cause);
+            return Err(Fail::new(libc::EADDRINUSE, &cause));
+        }
+        self.new_socket(local_port, remote, ordered)
+    }
+
+    /// Accepts associations on [port]. Messages are delivered in the order in which they were sent only if [ordered] is
+    /// set.
+    pub fn listen(&mut self, port: u16, ordered: bool) -> Result<(), Fail> {
+        if self.listeners.contains_key(&port) {
+            let cause: String = format!("port is already listening (port={:?})", port);
+            error!("listen(): {}", cause);
+            return Err(Fail::new(libc::EADDRINUSE, &cause));
+        }
+        self.listeners.insert(
+            port,
+            Listener {
+                ordered,
+                accept_queue: SharedAsyncQueue::default(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Waits for the next association that a remote opens to [port].
+    pub async fn accept(&mut self, port: u16) -> Result<SharedRmpSocket, Fail> {
+        let mut accept_queue: SharedAsyncQueue<SharedRmpSocket> = match self.listeners.get(&port) {
+            Some(listener) => listener.accept_queue.clone(),
+            None => {
+                let cause: String = format!("port is not listening (port={:?})", port);
+                error!("accept(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+        };
+        accept_queue.pop(None).await
+    }
+
+    /// Closes [socket] and forgets about it.
+    pub fn close(&mut self, socket: &mut SharedRmpSocket) {
+        socket.close();
+        self.sockets.remove(&(socket.local().port(), socket.remote()));
+    }
+
+    fn new_socket(&mut self, local_port: u16, remote: SocketAddrV4, ordered: bool) -> Result<SharedRmpSocket, Fail> {
+        let transmitter: ExtensionTransmitter = match self.transmitter.as_ref() {
+            Some(transmitter) => transmitter.clone(),
+            None => {
+                let cause: &str = "not registered as a protocol extension";
+                error!("new_socket(): {}", cause);
+                return Err(Fail::new(libc::ENOTCONN, cause));
+            },
+        };
+        let local: SocketAddrV4 = SocketAddrV4::new(transmitter.get_local_addr(), local_port);
+        let socket: SharedRmpSocket = SharedRmpSocket::new(
+            local,
+            remote,
+            self.runtime.clone(),
+            transmitter,
+            self.mss,
+            self.congestion_control,
+            ordered,
+        );
+        self.runtime.insert_background_coroutine(
+            "bgc::inetstack::rmp::retransmitter",
+            Box::pin(socket.clone().background_retransmitter().fuse()),
+        )?;
+        self.sockets.insert((local_port, remote), socket.clone());
+        Ok(socket)
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl ProtocolExtension for SharedRmpPeer {
+    fn receive(&mut self, src_ipv4_addr: Ipv4Addr, _: MacAddress, _: Ecn, mut payload: DemiBuffer) {
+        let local_ipv4_addr: Ipv4Addr = match self.transmitter.as_ref() {
+            Some(transmitter) => transmitter.get_local_addr(),
+            None => return,
+        };
+        let header: RmpHeader = match RmpHeader::parse_and_strip(&src_ipv4_addr, &local_ipv4_addr, &mut payload) {
+            Ok(header) => header,
+            Err(e) => {
+                warn!("receive(): dropping invalid datagram: {:?}", e);
+                return;
+            },
+        };
+        let remote: SocketAddrV4 = SocketAddrV4::new(src_ipv4_addr, header.src_port);
+        let mut socket: SharedRmpSocket = match self.sockets.get(&(header.dst_port, remote)) {
+            Some(socket) => socket.clone(),
+            None if header.kind == RmpKind::Data => {
+                // Remotes open associations to listening ports simply by sending to them.
+                let ordered: bool = match self.listeners.get(&header.dst_port) {
+                    Some(listener) => listener.ordered,
+                    None => {
+                        trace!(
+                            "receive(): dropping datagram for unknown association (remote={:?})",
+                            remote
+                        );
+                        return;
+                    },
+                };
+                let socket: SharedRmpSocket = match self.new_socket(header.dst_port, remote, ordered) {
+                    Ok(socket) => socket,
+                    Err(e) => {
+                        warn!("receive(): could not accept association: {:?}", e);
+                        return;
+                    },
+                };
+                if let Some(listener) = self.listeners.get_mut(&header.dst_port) {
+                    listener.accept_queue.push(socket.clone());
+                }
+                socket
+            },
+            None => {
+                trace!("receive(): dropping ACK for unknown association (remote={:?})", remote);
+                return;
+            },
+        };
+        socket.receive(header, payload);
+    }
+}
+
+impl Deref for SharedRmpPeer {
+    type Target = RmpPeer;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.deref()
+    }
+}
+
+impl DerefMut for SharedRmpPeer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.deref_mut()
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::{
+        async_queue::SharedAsyncQueue,
+        async_value::{select, SharedAsyncValue},
+    },
+    inetstack::protocols::{
+        layer4::{
+            extension::ExtensionTransmitter,
+            rmp::{
+                delivery::{Arrival, DeliveryQueue},
+                header::{RmpHeader, RmpKind, RMP_HEADER_SIZE},
+            },
+            tcp::{
+                congestion_control::{
+                    CongestionControlAlgorithm, CongestionControlConstructor, SlowStartCongestionAvoidance,
+                },
+                RtoCalculator, SeqNumber,
+            },
+        },
+        MAX_HEADER_SIZE,
+    },
+    runtime::{
+        fail::Fail, memory::DemiBuffer, network::socket::option::PriorityClass, SharedConditionVariable,
+        SharedDemiRuntime, SharedObject,
+    },
+};
+use ::std::{
+    collections::VecDeque,
+    net::{Ipv4Addr, SocketAddrV4},
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+// Number of messages after the first missing one that a receiver accepts.
+const RECEIVE_WINDOW_MESSAGES: u32 = 1024;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+// A message that was sent but not acknowledged yet.
+struct OutgoingMessage {
+    msg_no: u32,
+    buf: DemiBuffer,
+    // Position of the message in the byte stream that congestion control sees.
+    offset: SeqNumber,
+    // Time of the first transmission, which is cleared once the message is retransmitted (Karn's algorithm).
+    initial_tx: Option<Instant>,
+    last_tx: Instant,
+}
+
+/// An association between a local port and a remote endpoint over which messages are sent reliably. Every message goes
+/// into a single datagram and is acknowledged on its own, so a lost message is the only one that is retransmitted.
+pub struct RmpSocket {
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+    runtime: SharedDemiRuntime,
+    transmitter: ExtensionTransmitter,
+    mss: usize,
+    closed: bool,
+
+    // Number of the next message that we send and its offset in the byte stream that congestion control sees.
+    next_msg_no: u32,
+    next_offset: SeqNumber,
+    // Messages that were sent but not acknowledged, oldest first.
+    unacked: VecDeque<OutgoingMessage>,
+    rto_calculator: RtoCalculator,
+    retransmit_deadline: SharedAsyncValue<Option<Instant>>,
+    congestion_control: CongestionControlAlgorithm,
+    // Wakes up senders when messages are acknowledged or the association is closed.
+    acked: SharedConditionVariable,
+
+    delivery: DeliveryQueue,
+    recv_queue: SharedAsyncQueue<DemiBuffer>,
+}
+
+#[derive(Clone)]
+pub struct SharedRmpSocket(SharedObject<RmpSocket>);
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl SharedRmpSocket {
+    pub fn new(
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+        runtime: SharedDemiRuntime,
+        transmitter: ExtensionTransmitter,
+        mss: usize,
+        congestion_control: CongestionControlConstructor,
+        ordered: bool,
+    ) -> Self {
+        Self(SharedObject::new(RmpSocket {
+            local,
+            remote,
+            runtime,
+            transmitter,
+            mss,
+            closed: false,
+            next_msg_no: 0,
+            next_offset: SeqNumber::from(0),
+            unacked: VecDeque::new(),
+            rto_calculator: RtoCalculator::new(),
+            retransmit_deadline: SharedAsyncValue::new(None),
+            congestion_control: congestion_control(mss, SeqNumber::from(0), None),
+            acked: SharedConditionVariable::default(),
+            delivery: DeliveryQueue::new(ordered, RECEIVE_WINDOW_MESSAGES),
+            recv_queue: SharedAsyncQueue::default(),
+        }))
+    }
+
+    /// Sends [buf] as a single message and waits until our peer acknowledged it. Messages must fit into a single
+    /// datagram of [mss] bytes along with the RMP header.
+    pub async fn send(&mut self, buf: DemiBuffer) -> Result<(), Fail> {
+        if buf.len() + RMP_HEADER_SIZE > self.mss {
+            let cause: String = format!("message does not fit into a datagram (len={:?})", buf.len());
+            error!("send(): {}", cause);
+            return Err(Fail::new(libc::EMSGSIZE, &cause));
+        }
+        // A message goes out in a single datagram, with a checksum over all of its data, so gather the segments of a
+        // buffer chain first.
+        let buf: DemiBuffer = buf.coalesce(MAX_HEADER_SIZE)?;
+        self.check_open()?;
+
+        // Wait until the message fits into the congestion window, unless nothing is in flight.
+        let cwnd_watched: SharedAsyncValue<u32> = self.congestion_control.get_cwnd();
+        let acked_watched: SharedConditionVariable = self.acked.clone();
+        while !self.unacked.is_empty() && self.get_bytes_in_flight() + buf.len() as u32 > cwnd_watched.get() {
+            select([&cwnd_watched, &acked_watched], None).await?;
+            self.check_open()?;
+        }
+
+        let msg_no: u32 = self.next_msg_no;
+        let offset: SeqNumber = self.next_offset;
+        let now: Instant = self.runtime.get_now();
+        self.next_msg_no = self.next_msg_no.wrapping_add(1);
+        self.next_offset = self.next_offset + SeqNumber::from(buf.len() as u32);
+        let rto: Duration = self.rto_calculator.rto();
+        self.congestion_control.on_send(rto, buf.len() as u32);
+        self.unacked.push_back(OutgoingMessage {
+            msg_no,
+            buf: buf.clone(),
+            offset,
+            initial_tx: Some(now),
+            last_tx: now,
+        });
+        if self.retransmit_deadline.get().is_none() {
+            self.retransmit_deadline.set(Some(now + rto));
+        }
+        self.transmit_data(msg_no, buf).await?;
+
+        // Wait until the message is acknowledged.
+        let mut acked: SharedConditionVariable = self.acked.clone();
+        while self.unacked.iter().any(|message| message.msg_no == msg_no) {
+            self.check_open()?;
+            acked.wait().await;
+        }
+        // Closing the association drops unacknowledged messages, too.
+        self.check_open()
+    }
+
+    /// Waits for the next message from our peer.
+    pub async fn recv(&mut self) -> Result<DemiBuffer, Fail> {
+        self.check_open()?;
+        self.recv_queue.pop(None).await
+    }
+
+    /// Closes the association. Messages that were not acknowledged yet are dropped and their senders fail.
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.unacked.clear();
+        self.retransmit_deadline.set(None);
+        self.acked.broadcast();
+    }
+
+    /// Handles a datagram that our peer sent over this association.
+    pub fn receive(&mut self, header: RmpHeader, buf: DemiBuffer) {
+        if self.closed {
+            return;
+        }
+        match header.kind {
+            RmpKind::Data => self.process_data(header.msg_no, buf),
+            RmpKind::Ack => self.process_ack(header.msg_no, header.cumulative_msg_no),
+        }
+    }
+
+    /// Retransmits messages whose acknowledgement is overdue, until the association is closed.
+    pub async fn background_retransmitter(mut self) {
+        let retransmit_deadline_watched: SharedAsyncValue<Option<Instant>> = self.retransmit_deadline.clone();
+        loop {
+            if self.closed {
+                return;
+            }
+            let deadline: Option<Instant> = retransmit_deadline_watched.get();
+            match select([&retransmit_deadline_watched], deadline).await {
+                Ok(_) => continue,
+                Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT => {
+                    if let Err(e) = self.retransmit_overdue().await {
+                        warn!("background_retransmitter(): could not retransmit: {:?}", e);
+                    }
+                },
+                Err(e) => {
+                    warn!("background_retransmitter(): {:?}", e);
+                    return;
+                },
+            }
+        }
+    }
+
+    pub fn local(&self) -> SocketAddrV4 {
+        self.local
+    }
+
+    pub fn remote(&self) -> SocketAddrV4 {
+        self.remote
+    }
+
+    fn check_open(&self) -> Result<(), Fail> {
+        if self.closed {
+            let cause: &str = "association is closed";
+            warn!("check_open(): {}", cause);
+            return Err(Fail::new(libc::ECONNRESET, cause));
+        }
+        Ok(())
+    }
+
+    fn get_bytes_in_flight(&self) -> u32 {
+        self.unacked.iter().map(|message| message.buf.len() as u32).sum()
+    }
+
+    // Returns the offset of the oldest unacknowledged message in the byte stream that congestion control sees.
+    fn get_unacked_offset(&self) -> SeqNumber {
+        match self.unacked.front() {
+            Some(message) => message.offset,
+            None => self.next_offset,
+        }
+    }
+
+    fn process_data(&mut self, msg_no: u32, buf: DemiBuffer) {
+        let mut recv_queue: SharedAsyncQueue<DemiBuffer> = self.recv_queue.clone();
+        match self.delivery.receive(msg_no, buf, |buf| recv_queue.push(buf)) {
+            Arrival::New | Arrival::Duplicate => {
+                let cumulative_msg_no: u32 = self.delivery.get_next_msg_no();
+                self.transmit_ack(msg_no, cumulative_msg_no);
+            },
+            Arrival::OutOfWindow => trace!("process_data(): dropping message out of window (msg_no={:?})", msg_no),
+        }
+    }
+
+    fn process_ack(&mut self, msg_no: u32, cumulative_msg_no: u32) {
+        let now: Instant = self.runtime.get_now();
+        let unacked_offset: SeqNumber = self.get_unacked_offset();
+        let mut rtt_sample: Option<Duration> = None;
+        let mut acked_any: bool = false;
+        // Messages are numbered modulo 2^32, so everything less than 2^31 messages before the cumulative message number
+        // counts as before it.
+        self.unacked.retain(|message| {
+            let acked: bool = message.msg_no == msg_no || (cumulative_msg_no.wrapping_sub(message.msg_no) as i32) > 0;
+            if acked {
+                acked_any = true;
+                if message.msg_no == msg_no {
+                    rtt_sample = message.initial_tx.map(|initial_tx| now - initial_tx);
+                }
+            }
+            !acked
+        });
+        if !acked_any {
+            return;
+        }
+
+        if let Some(rtt) = rtt_sample {
+            self.rto_calculator.add_sample(rtt);
+            self.congestion_control.on_rtt_sample(rtt);
+        }
+        // Congestion control only sees the oldest unacknowledged message move forward, just like SND.UNA in TCP.
+        let new_unacked_offset: SeqNumber = self.get_unacked_offset();
+        if new_unacked_offset != unacked_offset {
+            let rto: Duration = self.rto_calculator.rto();
+            let next_offset: SeqNumber = self.next_offset;
+            self.congestion_control
+                .on_ack_received(rto, unacked_offset, next_offset, new_unacked_offset);
+        }
+        let deadline: Option<Instant> = self.get_retransmit_deadline();
+        self.retransmit_deadline.set(deadline);
+        self.acked.broadcast();
+    }
+
+    // Returns when the oldest transmission of an unacknowledged message times out.
+    fn get_retransmit_deadline(&self) -> Option<Instant> {
+        let rto: Duration = self.rto_calculator.rto();
+        self.unacked.iter().map(|message| message.last_tx + rto).min()
+    }
+
+    async fn retransmit_overdue(&mut self) -> Result<(), Fail> {
+        let now: Instant = self.runtime.get_now();
+        let unacked_offset: SeqNumber = self.get_unacked_offset();
+        let rto: Duration = self.rto_calculator.rto();
+        self.congestion_control.on_rto(unacked_offset);
+        self.rto_calculator.back_off();
+
+        let mut overdue: Vec<(u32, DemiBuffer)> = Vec::new();
+        for message in self.unacked.iter_mut() {
+            if message.last_tx + rto <= now {
+                message.initial_tx = None;
+                message.last_tx = now;
+                overdue.push((message.msg_no, message.buf.clone()));
+            }
+        }
+        let deadline: Option<Instant> = self.get_retransmit_deadline();
+        self.retransmit_deadline.set(deadline);
+        for (msg_no, buf) in overdue {
+            self.transmit_data(msg_no, buf).await?;
+        }
+        Ok(())
+    }
+
+    async fn transmit_data(&mut self, msg_no: u32, mut buf: DemiBuffer) -> Result<(), Fail> {
+        let header: RmpHeader = RmpHeader {
+            src_port: self.local.port(),
+            dst_port: self.remote.port(),
+            kind: RmpKind::Data,
+            msg_no,
+            cumulative_msg_no: 0,
+        };
+        let local_ipv4_addr: Ipv4Addr = self.transmitter.get_local_addr();
+        header.serialize_and_attach(&mut buf, &local_ipv4_addr, self.remote.ip());
+        let remote_ipv4_addr: Ipv4Addr = *self.remote.ip();
+        self.transmitter
+            .transmit(remote_ipv4_addr, PriorityClass::default(), buf)
+            .await
+    }
+
+    // Acknowledges a message right away. If that fails, our peer retransmits the message and we try again.
+    fn transmit_ack(&mut self, msg_no: u32, cumulative_msg_no: u32) {
+        let header: RmpHeader = RmpHeader {
+            src_port: self.local.port(),
+            dst_port: self.remote.port(),
+            kind: RmpKind::Ack,
+            msg_no,
+            cumulative_msg_no,
+        };
+        let mut buf: DemiBuffer = DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16);
+        let local_ipv4_addr: Ipv4Addr = self.transmitter.get_local_addr();
+        header.serialize_and_attach(&mut buf, &local_ipv4_addr, self.remote.ip());
+        let remote_ipv4_addr: Ipv4Addr = *self.remote.ip();
+        if let Err(e) = self
+            .transmitter
+            .try_transmit(remote_ipv4_addr, PriorityClass::default(), buf)
+        {
+            warn!("transmit_ack(): could not send ACK: {:?}", e);
+        }
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl Deref for SharedRmpSocket {
+    type Target = RmpSocket;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.deref()
+    }
+}
+
+impl DerefMut for SharedRmpSocket {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.deref_mut()
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Monitoring agents sample counters periodically to compute rates. Instead of having every agent keep the previous
+// values around, we keep a baseline next to the counters and hand out the change since that baseline, along with the
+// time that it covers. Since a snapshot is taken in a single call, no update can slip in between reading the counters
+// and resetting the baseline.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::std::time::{Duration, Instant};
+
+//======================================================================================================================
+// Traits
+//======================================================================================================================
+
+/// Counters that can be subtracted from each other.
+pub trait Counters: Clone {
+    /// Returns the change in these counters since [baseline]. Values that are not counters (e.g. gauges) are taken from
+    /// [self] as they are.
+    fn delta(&self, baseline: &Self) -> Self;
+}
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Change in a set of counters over an interval of time.
+#[derive(Clone, Debug)]
+pub struct StatsDelta<T> {
+    /// Length of the interval that the change covers.
+    pub interval: Duration,
+    /// Change in the counters over the interval.
+    pub counters: T,
+}
+
+/// Values of a set of counters at the time of the last reset.
+#[derive(Debug)]
+pub struct StatsBaseline<T: Counters> {
+    counters: T,
+    taken_at: Instant,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl<T: Counters> StatsBaseline<T> {
+    /// Takes [counters] as the baseline at [now].
+    pub fn new(counters: T, now: Instant) -> Self {
+        Self {
+            counters,
+            taken_at: now,
+        }
+    }
+
+    /// Returns the change from the baseline to [current] at [now]. If [reset] is set, [current] becomes the new baseline,
+    /// so that the next snapshot only covers what happens from now on.
+    pub fn snapshot(&mut self, current: T, now: Instant, reset: bool) -> StatsDelta<T> {
+        let delta: StatsDelta<T> = StatsDelta {
+            interval: now.saturating_duration_since(self.taken_at),
+            counters: current.delta(&self.counters),
+        };
+        if reset {
+            self.counters = current;
+            self.taken_at = now;
+        }
+        delta
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{Counters, StatsBaseline, StatsDelta};
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TestCounters {
+        packets: u64,
+        queue_length: u64,
+    }
+
+    impl Counters for TestCounters {
+        fn delta(&self, baseline: &Self) -> Self {
+            Self {
+                packets: self.packets - baseline.packets,
+                queue_length: self.queue_length,
+            }
+        }
+    }
+
+    #[test]
+    fn test_snapshot_and_reset() -> Result<()> {
+        let start: Instant = Instant::now();
+        let mut baseline: StatsBaseline<TestCounters> = StatsBaseline::new(
+            TestCounters {
+                packets: 10,
+                queue_length: 1,
+            },
+            start,
+        );
+
+        // Without a reset, snapshots keep covering everything since the baseline.
+        let now: Instant = start + Duration::from_secs(1);
+        let current: TestCounters = TestCounters {
+            packets: 15,
+            queue_length: 3,
+        };
+        let delta: StatsDelta<TestCounters> = baseline.snapshot(current.clone(), now, false);
+        crate::ensure_eq!(delta.interval, Duration::from_secs(1));
+        crate::ensure_eq!(delta.counters.packets, 5);
+        crate::ensure_eq!(delta.counters.queue_length, 3);
+        let delta: StatsDelta<TestCounters> = baseline.snapshot(current, now, true);
+        crate::ensure_eq!(delta.counters.packets, 5);
+
+        // After a reset, snapshots only cover what happened since.
+        let now: Instant = start + Duration::from_secs(3);
+        let current: TestCounters = TestCounters {
+            packets: 22,
+            queue_length: 0,
+        };
+        let delta: StatsDelta<TestCounters> = baseline.snapshot(current, now, true);
+        crate::ensure_eq!(delta.interval, Duration::from_secs(2));
+        crate::ensure_eq!(delta.counters.packets, 7);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Limits the number of accepts that a listening socket completes in a single poll iteration of the stack. A burst of
+/// new connections is then accepted over several iterations, which leaves room for established connections to make
+/// progress in between.
+pub struct AcceptPacer {
+    /// Maximum number of accepts per poll iteration. Zero means no limit.
+    max_accepts_per_poll: usize,
+    /// Poll iteration that [accepts] refers to.
+    iteration: u64,
+    /// Number of accepts that were completed in [iteration].
+    accepts: usize,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl AcceptPacer {
+    pub fn new(max_accepts_per_poll: usize) -> Self {
+        Self {
+            max_accepts_per_poll,
+            iteration: 0,
+            accepts: 0,
+        }
+    }
+
+    /// Takes an accept out of the budget of poll iteration `iteration`. Returns false if the budget of that iteration is
+    /// used up already, in which case the accept has to wait for a later iteration.
+    pub fn try_take(&mut self, iteration: u64) -> bool {
+        if self.max_accepts_per_poll == 0 {
+            return true;
+        }
+        if iteration != self.iteration {
+            self.iteration = iteration;
+            self.accepts = 0;
+        }
+        if self.accepts >= self.max_accepts_per_poll {
+            return false;
+        }
+        self.accepts += 1;
+        true
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::AcceptPacer;
+    use ::anyhow::Result;
+
+    #[test]
+    fn test_accept_pacer() -> Result<()> {
+        let mut pacer: AcceptPacer = AcceptPacer::new(2);
+        crate::ensure_eq!(pacer.try_take(1), true);
+        crate::ensure_eq!(pacer.try_take(1), true);
+        crate::ensure_eq!(pacer.try_take(1), false);
+        // The budget is refilled in the next poll iteration.
+        crate::ensure_eq!(pacer.try_take(2), true);
+
+        // Without a limit, every accept goes through.
+        let mut pacer: AcceptPacer = AcceptPacer::new(0);
+        for _ in 0..100 {
+            crate::ensure_eq!(pacer.try_take(1), true);
+        }
+
+        Ok(())
+    }
+}
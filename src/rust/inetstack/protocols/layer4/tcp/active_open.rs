@@ -6,25 +6,30 @@
 //======================================================================================================================
 
 use crate::{
-    collections::{async_queue::SharedAsyncQueue, async_value::SharedAsyncValue},
+    collections::{
+        async_queue::{OverflowPolicy, SharedAsyncQueue},
+        async_value::SharedAsyncValue,
+    },
     expect_some,
     inetstack::protocols::{
-        layer3::SharedLayer3Endpoint,
+        layer3::{Ecn, SharedLayer3Endpoint},
         layer4::tcp::{
-            constants::{FALLBACK_MSS, MAX_WINDOW_SCALE},
-            established::{
-                congestion_control::{self, CongestionControl},
-                EstablishedSocket,
-            },
+            constants::{FALLBACK_MSS, MAX_HANDSHAKE_SEGMENTS, MAX_WINDOW_SCALE},
+            established::EstablishedSocket,
             header::{TcpHeader, TcpOptions2},
+            timestamps::Timestamps,
             SeqNumber,
         },
         MAX_HEADER_SIZE,
     },
     runtime::{
+        conditional_yield_until,
         fail::Fail,
         memory::DemiBuffer,
-        network::{config::TcpConfig, socket::option::TcpSocketOptions},
+        network::{
+            config::TcpConfig,
+            socket::option::{PriorityClass, SynBackoff, TcpSocketOptions},
+        },
         SharedDemiRuntime, SharedObject,
     },
 };
@@ -32,6 +37,7 @@ use ::futures::{select_biased, FutureExt};
 use ::std::{
     net::{Ipv4Addr, SocketAddrV4},
     ops::{Deref, DerefMut},
+    time::{Duration, Instant},
 };
 
 //======================================================================================================================
@@ -57,6 +63,8 @@ pub struct ActiveOpenSocket {
     tcp_config: TcpConfig,
     socket_options: TcpSocketOptions,
     state: SharedAsyncValue<State>,
+    // Time at which the timestamp clock of the connection starts (RFC 7323).
+    timestamps_base: Instant,
 }
 
 #[derive(Clone)]
@@ -84,10 +92,11 @@ impl SharedActiveOpenSocket {
             remote,
             runtime: runtime.clone(),
             layer3_endpoint,
-            recv_queue: SharedAsyncQueue::default(),
+            recv_queue: SharedAsyncQueue::bounded(MAX_HANDSHAKE_SEGMENTS, OverflowPolicy::DropNewest),
             tcp_config,
             socket_options: default_socket_options,
             state: SharedAsyncValue::new(State::Connecting),
+            timestamps_base: runtime.get_now(),
         })))
     }
 
@@ -122,14 +131,28 @@ impl SharedActiveOpenSocket {
 
         let remote_seq_num = header.seq_num + SeqNumber::from(1);
 
+        // Timestamps are only used if our peer answered ours (RFC 7323 Section 3.2).
+        let timestamps: Option<Timestamps> = header.iter_options().find_map(|option| match option {
+            TcpOptions2::Timestamp { sender_timestamp, .. } => {
+                info!("Received timestamp: {}", sender_timestamp);
+                Some(Timestamps::new(self.timestamps_base, *sender_timestamp, remote_seq_num))
+            },
+            _ => None,
+        });
+
         let mut tcp_hdr = TcpHeader::new(self.local.port(), self.remote.port());
         tcp_hdr.ack = true;
         tcp_hdr.ack_num = remote_seq_num;
         tcp_hdr.window_size = self.tcp_config.get_receive_window_size();
         tcp_hdr.seq_num = self.local_isn + SeqNumber::from(1);
+        if let Some(timestamps) = timestamps {
+            tcp_hdr.push_option(timestamps.get_option(self.runtime.get_now()));
+        }
         debug!("Sending ACK: {:?}", tcp_hdr);
 
+        let src_ipv4_addr: Ipv4Addr = self.local.ip().clone();
         let dst_ipv4_addr: Ipv4Addr = self.remote.ip().clone();
+        let priority: PriorityClass = self.socket_options.get_priority();
         let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16);
         tcp_hdr.serialize_and_attach(
             &mut pkt,
@@ -137,11 +160,17 @@ impl SharedActiveOpenSocket {
             self.remote.ip(),
             self.tcp_config.get_rx_checksum_offload(),
         );
-        self.layer3_endpoint
-            .transmit_tcp_packet_nonblocking(dst_ipv4_addr, pkt)?;
+        self.layer3_endpoint.transmit_tcp_packet_nonblocking(
+            src_ipv4_addr,
+            dst_ipv4_addr,
+            priority,
+            Ecn::NotEct,
+            pkt,
+        )?;
 
         let mut remote_window_scale = None;
         let mut mss = FALLBACK_MSS;
+        let mut sack_permitted: bool = false;
         for option in header.iter_options() {
             match option {
                 TcpOptions2::WindowScale(w) => {
@@ -152,9 +181,22 @@ impl SharedActiveOpenSocket {
                     info!("Received advertised MSS: {}", m);
                     mss = *m as usize;
                 },
+                TcpOptions2::SelectiveAcknowlegementPermitted => {
+                    info!("Received SACK permitted");
+                    sack_permitted = true;
+                },
                 _ => continue,
             }
         }
+        // Never send segments larger than what we advertise ourselves, as those may not fit into our MTU.
+        let mss: usize = self.tcp_config.get_send_mss(mss);
+        info!("Send MSS: {}", mss);
+
+        // Our peer agrees to use ECN with an ECN-setup SYN+ACK, which carries ECE but not CWR (RFC 3168 Section 6.1.1).
+        let ecn_capable: bool = self.tcp_config.get_ecn() && header.ece && !header.cwr;
+        if ecn_capable {
+            info!("Received ECN-setup SYN+ACK");
+        }
 
         let (local_window_scale, remote_window_scale): (u8, u8) = match remote_window_scale {
             Some(remote_window_scale) => {
@@ -194,6 +236,7 @@ impl SharedActiveOpenSocket {
         Ok(EstablishedSocket::new(
             self.local,
             self.remote,
+            None,
             self.runtime.clone(),
             self.layer3_endpoint.clone(),
             self.recv_queue.clone(),
@@ -207,18 +250,44 @@ impl SharedActiveOpenSocket {
             tx_window_size,
             remote_window_scale,
             mss,
-            congestion_control::None::new,
+            sack_permitted,
+            timestamps,
+            ecn_capable,
+            self.tcp_config
+                .get_congestion_control_by_name(self.socket_options.get_congestion_control()),
             None,
         )?)
     }
 
-    pub async fn connect(mut self) -> Result<EstablishedSocket, Fail> {
-        // Start connection handshake.
-        let handshake_retries: usize = self.tcp_config.get_handshake_retries();
-        let handshake_timeout = self.tcp_config.get_handshake_timeout();
+    pub async fn connect(self) -> Result<EstablishedSocket, Fail> {
+        // The socket may give up on the whole handshake after a while, no matter how many SYNs are left to send.
+        let connect_timeout: Option<Duration> = self.socket_options.get_connect_timeout();
+        let deadline: Option<Instant> = connect_timeout.map(|timeout| self.runtime.get_now() + timeout);
+        match conditional_yield_until(self.clone().handshake(), deadline).await {
+            Ok(result) => result,
+            Err(_) => {
+                let cause: String = format!("connect timed out (timeout={:?})", connect_timeout);
+                error!("connect(): {}", cause);
+                Err(Fail::new(libc::ETIMEDOUT, &cause))
+            },
+        }
+    }
+
+    async fn handshake(mut self) -> Result<EstablishedSocket, Fail> {
+        // Start connection handshake. We retransmit the SYN as often as the socket asks for, and wait longer for an
+        // answer with every retransmission if it asks for a backoff.
+        let syn_retries: u32 = self
+            .socket_options
+            .get_syn_retries()
+            .unwrap_or(self.tcp_config.get_syn_retries());
+        let syn_backoff: SynBackoff = self
+            .socket_options
+            .get_syn_backoff()
+            .unwrap_or(self.tcp_config.get_syn_backoff());
+        let mut syn_timeout: Duration = syn_backoff.initial_timeout;
 
         // Try to connect.
-        for _ in 0..handshake_retries {
+        for _ in 0..=syn_retries {
             // Set up SYN packet.
             let mut tcp_hdr = TcpHeader::new(self.local.port(), self.remote.port());
             tcp_hdr.syn = true;
@@ -232,8 +301,28 @@ impl SharedActiveOpenSocket {
             tcp_hdr.push_option(TcpOptions2::WindowScale(self.tcp_config.get_window_scale()));
             info!("Advertising window scale: {}", self.tcp_config.get_window_scale());
 
+            // We report out-of-order data (RFC 2018) and duplicate data (RFC 2883) in SACK blocks.
+            tcp_hdr.push_option(TcpOptions2::SelectiveAcknowlegementPermitted);
+            info!("Advertising SACK permitted");
+
+            // Our peer echoes this back if it agrees to use timestamps (RFC 7323).
+            tcp_hdr.push_option(TcpOptions2::Timestamp {
+                sender_timestamp: Timestamps::clock(self.timestamps_base, self.runtime.get_now()),
+                echo_timestamp: 0,
+            });
+            info!("Advertising timestamps");
+
+            // We ask our peer to use ECN with an ECN-setup SYN (RFC 3168 Section 6.1.1).
+            if self.tcp_config.get_ecn() {
+                tcp_hdr.ece = true;
+                tcp_hdr.cwr = true;
+                info!("Advertising ECN");
+            }
+
             debug!("Sending SYN {:?}", tcp_hdr);
+            let src_ipv4_addr: Ipv4Addr = self.local.ip().clone();
             let dst_ipv4_addr: Ipv4Addr = self.remote.ip().clone();
+            let priority: PriorityClass = self.socket_options.get_priority();
             let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16);
             tcp_hdr.serialize_and_attach(
                 &mut pkt,
@@ -244,7 +333,7 @@ impl SharedActiveOpenSocket {
             // Send SYN.
             if let Err(e) = self
                 .layer3_endpoint
-                .transmit_tcp_packet_blocking(dst_ipv4_addr, pkt)
+                .transmit_tcp_packet_blocking(src_ipv4_addr, dst_ipv4_addr, priority, pkt)
                 .await
             {
                 warn!("Could not send SYN: {:?}", e);
@@ -262,13 +351,16 @@ impl SharedActiveOpenSocket {
                     return Err(Fail::new(libc::ECONNABORTED, &cause));
                 }
             },
-            r = recv_queue.pop(Some(handshake_timeout)).fuse() => match r {
+            r = recv_queue.pop(Some(syn_timeout)).fuse() => match r {
                 Ok((_, header, _)) => match self.process_ack(header) {
                         Ok(socket) => return Ok(socket),
                         Err(Fail { errno, cause: _ }) if errno == libc::EAGAIN => continue,
                         Err(e) => return Err(e),
                     },
-                    Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT => continue,
+                    Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT => {
+                        syn_timeout = syn_backoff.next_timeout(syn_timeout);
+                        continue
+                    },
                     Err(_) => {
                         unreachable!(
                             "either the ack deadline changed or the deadline passed, no other errors are possible!"
@@ -278,9 +370,9 @@ impl SharedActiveOpenSocket {
             }
         }
 
-        let cause: String = format!("connection handshake timed out");
-        error!("connect(): {}", cause);
-        Err(Fail::new(libc::ECONNREFUSED, &cause))
+        let cause: String = format!("connection handshake timed out (syn_retries={:?})", syn_retries);
+        error!("handshake(): {}", cause);
+        Err(Fail::new(libc::ETIMEDOUT, &cause))
     }
 
     pub fn close(&mut self) {
@@ -2,3 +2,10 @@
 // Licensed under the MIT license.
 
 pub use crate::runtime::network::consts::{DEFAULT_MSS, FALLBACK_MSS, MAX_MSS, MAX_WINDOW_SCALE, MIN_MSS, MSL};
+
+/// Maximum number of incoming segments that we queue for a connection that is still in its handshake. Further
+/// segments are dropped, so that a flood of segments for half-open connections cannot exhaust our memory.
+pub const MAX_HANDSHAKE_SEGMENTS: usize = 64;
+
+/// Maximum number of times that a connecting socket may retransmit its SYN, which is the limit of Linux as well.
+pub const MAX_SYN_RETRIES: u32 = 127;
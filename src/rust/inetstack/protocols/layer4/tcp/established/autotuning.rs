@@ -0,0 +1,167 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Receive buffer autotuning (dynamic right-sizing). The amount of data that the application reads within a round trip is
+// what our peer manages to deliver through the current window. If our peer is to speed up, the window has to be larger
+// than that, so we grow the receive buffer to twice the amount whenever it comes close to limiting our peer. Without
+// our own data to time, we estimate the round-trip time from how long it takes our peer to send a full window.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+use ::std::{
+    cmp,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Grows the receive buffer of a connection to keep up with the rate at which the application reads.
+#[derive(Debug)]
+pub struct ReceiveBufferTuner {
+    // Upper bound of the receive buffer size.
+    max_size_frames: u32,
+    // Smoothed estimate of the round-trip time, once we have a sample.
+    rtt: Option<Duration>,
+    // RCV.NXT that completes the window that we are currently timing and the time at which that window started.
+    rtt_seq_no: SeqNumber,
+    rtt_start: Instant,
+    // Sequence number of the first byte that the application read in the current round trip and the time at which that
+    // round trip started.
+    read_seq_no: SeqNumber,
+    read_start: Instant,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl ReceiveBufferTuner {
+    /// Creates a tuner for a connection whose next byte to receive is [receive_next], whose next byte to read is
+    /// [reader_next] and whose receive window is [window_size_frames] bytes.
+    pub fn new(
+        max_size_frames: u32,
+        receive_next: SeqNumber,
+        reader_next: SeqNumber,
+        window_size_frames: u32,
+        now: Instant,
+    ) -> Self {
+        Self {
+            max_size_frames,
+            rtt: None,
+            rtt_seq_no: receive_next + SeqNumber::from(window_size_frames),
+            rtt_start: now,
+            read_seq_no: reader_next,
+            read_start: now,
+        }
+    }
+
+    /// Takes a round-trip time sample once our peer sent a full window, now that RCV.NXT is [receive_next] and the
+    /// receive window is [window_size_frames] bytes.
+    pub fn on_receive(&mut self, now: Instant, receive_next: SeqNumber, window_size_frames: u32) {
+        if receive_next < self.rtt_seq_no {
+            return;
+        }
+        // A peer that does not use the whole window makes this an overestimate, so lower samples win right away.
+        let sample: Duration = now.saturating_duration_since(self.rtt_start);
+        self.rtt = Some(match self.rtt {
+            Some(rtt) if rtt < sample => (rtt * 7 + sample) / 8,
+            _ => sample,
+        });
+        self.rtt_seq_no = receive_next + SeqNumber::from(cmp::max(window_size_frames, 1));
+        self.rtt_start = now;
+    }
+
+    /// Accounts for the application having read everything up to [reader_next]. Returns the size that the receive
+    /// buffer should grow to from [buffer_size_frames], if any.
+    pub fn on_read(&mut self, now: Instant, reader_next: SeqNumber, buffer_size_frames: u32) -> Option<u32> {
+        let rtt: Duration = self.rtt?;
+        if now.saturating_duration_since(self.read_start) < rtt {
+            return None;
+        }
+        let read_frames: u32 = (reader_next - self.read_seq_no).into();
+        self.read_seq_no = reader_next;
+        self.read_start = now;
+
+        let size_frames: u32 = cmp::min(read_frames.saturating_mul(2), self.max_size_frames);
+        if size_frames > buffer_size_frames {
+            Some(size_frames)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::ReceiveBufferTuner;
+    use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    const WINDOW: u32 = 64 * 1024;
+    const MAX_BUFFER: u32 = 1024 * 1024;
+
+    #[test]
+    fn test_rtt_from_full_windows() -> Result<()> {
+        let start: Instant = Instant::now();
+        let mut tuner: ReceiveBufferTuner =
+            ReceiveBufferTuner::new(MAX_BUFFER, SeqNumber::from(0), SeqNumber::from(0), WINDOW, start);
+
+        // No sample until our peer sent a full window.
+        tuner.on_receive(start + Duration::from_millis(5), SeqNumber::from(WINDOW - 1), WINDOW);
+        crate::ensure_eq!(tuner.get_rtt(), None);
+        tuner.on_receive(start + Duration::from_millis(10), SeqNumber::from(WINDOW), WINDOW);
+        crate::ensure_eq!(tuner.get_rtt(), Some(Duration::from_millis(10)));
+
+        // Higher samples are smoothed, lower ones are taken right away.
+        tuner.on_receive(start + Duration::from_millis(28), SeqNumber::from(2 * WINDOW), WINDOW);
+        crate::ensure_eq!(tuner.get_rtt(), Some(Duration::from_millis(11)));
+        tuner.on_receive(start + Duration::from_millis(33), SeqNumber::from(3 * WINDOW), WINDOW);
+        crate::ensure_eq!(tuner.get_rtt(), Some(Duration::from_millis(5)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_grows_with_reads() -> Result<()> {
+        let start: Instant = Instant::now();
+        let rtt: Duration = Duration::from_millis(10);
+        let mut tuner: ReceiveBufferTuner =
+            ReceiveBufferTuner::new(MAX_BUFFER, SeqNumber::from(0), SeqNumber::from(0), WINDOW, start);
+
+        // Without a round-trip time estimate, we do not know the rate.
+        crate::ensure_eq!(tuner.on_read(start + rtt, SeqNumber::from(WINDOW), WINDOW), None);
+        tuner.on_receive(start + rtt, SeqNumber::from(WINDOW), WINDOW);
+
+        // Reading a full window per round trip doubles the buffer, but only once per round trip.
+        let mut now: Instant = start + 2 * rtt;
+        crate::ensure_eq!(tuner.on_read(now, SeqNumber::from(WINDOW), WINDOW), Some(2 * WINDOW));
+        crate::ensure_eq!(tuner.on_read(now, SeqNumber::from(3 * WINDOW), 2 * WINDOW), None);
+
+        // Reading up to half of the buffer per round trip does not grow it.
+        now += rtt;
+        crate::ensure_eq!(tuner.on_read(now, SeqNumber::from(3 * WINDOW), 4 * WINDOW), None);
+
+        // The buffer does not grow past its upper bound.
+        now += rtt;
+        crate::ensure_eq!(
+            tuner.on_read(now, SeqNumber::from(3 * WINDOW + MAX_BUFFER), 4 * WINDOW),
+            Some(MAX_BUFFER)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::async_value::SharedAsyncValue,
+    inetstack::protocols::layer4::tcp::{
+        established::congestion_control::{
+            Bbr, CongestionControl, Cubic, FastRetransmitRecovery, LimitedTransmit, Metrics, None, OptionValue,
+            SlowStartCongestionAvoidance,
+        },
+        established::delivery_rate::RateSample,
+        SeqNumber,
+    },
+};
+use ::std::time::{Duration, Instant};
+
+//======================================================================================================================
+// Macros
+//======================================================================================================================
+
+/// Forwards a call to the congestion control algorithm that is wrapped by a [CongestionControlAlgorithm].
+macro_rules! dispatch {
+    ($self:expr, $cc:ident => $body:expr) => {
+        match $self {
+            CongestionControlAlgorithm::None($cc) => $body,
+            CongestionControlAlgorithm::Cubic($cc) => $body,
+            CongestionControlAlgorithm::Bbr($cc) => $body,
+            CongestionControlAlgorithm::Dynamic($cc) => $body,
+        }
+    };
+}
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// A congestion control algorithm. The built-in algorithms are dispatched statically, so processing an ACK does not
+/// require any virtual calls. Other algorithms can still be plugged in as a trait object.
+#[derive(Debug)]
+pub enum CongestionControlAlgorithm {
+    None(None),
+    Cubic(Cubic),
+    Bbr(Bbr),
+    Dynamic(Box<dyn CongestionControl>),
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl SlowStartCongestionAvoidance for CongestionControlAlgorithm {
+    #[inline]
+    fn get_cwnd(&self) -> SharedAsyncValue<u32> {
+        dispatch!(self, cc => cc.get_cwnd())
+    }
+
+    #[inline]
+    fn on_cwnd_check_before_send(&mut self, rto: Duration, bytes_in_flight: u32) {
+        dispatch!(self, cc => cc.on_cwnd_check_before_send(rto, bytes_in_flight))
+    }
+
+    #[inline]
+    fn on_idle_restart(&mut self, now: Instant, rto: Duration, idle_time: Duration) {
+        dispatch!(self, cc => cc.on_idle_restart(now, rto, idle_time))
+    }
+
+    #[inline]
+    fn on_app_limited(&mut self, bytes_in_flight: u32) {
+        dispatch!(self, cc => cc.on_app_limited(bytes_in_flight))
+    }
+
+    #[inline]
+    fn on_ack_received(&mut self, rto: Duration, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
+        dispatch!(self, cc => cc.on_ack_received(rto, send_unacked, send_next, ack_seq_no))
+    }
+
+    #[inline]
+    fn on_rto(&mut self, send_unacked: SeqNumber) {
+        dispatch!(self, cc => cc.on_rto(send_unacked))
+    }
+
+    #[inline]
+    fn on_spurious_rto(&mut self) {
+        dispatch!(self, cc => cc.on_spurious_rto())
+    }
+
+    #[inline]
+    fn on_send(&mut self, rto: Duration, num_sent_bytes: u32) {
+        dispatch!(self, cc => cc.on_send(rto, num_sent_bytes))
+    }
+
+    #[inline]
+    fn on_rtt_sample(&mut self, rtt: Duration) {
+        dispatch!(self, cc => cc.on_rtt_sample(rtt))
+    }
+
+    #[inline]
+    fn on_delivery_rate_sample(&mut self, sample: &RateSample) {
+        dispatch!(self, cc => cc.on_delivery_rate_sample(sample))
+    }
+
+    #[inline]
+    fn on_ecn_echo(&mut self) {
+        dispatch!(self, cc => cc.on_ecn_echo())
+    }
+
+    #[inline]
+    fn get_ssthresh(&self) -> u32 {
+        dispatch!(self, cc => cc.get_ssthresh())
+    }
+
+    #[inline]
+    fn get_pacing_rate(&self) -> Option<u64> {
+        dispatch!(self, cc => cc.get_pacing_rate())
+    }
+}
+
+impl FastRetransmitRecovery for CongestionControlAlgorithm {
+    #[inline]
+    fn get_duplicate_ack_count(&self) -> u32 {
+        dispatch!(self, cc => cc.get_duplicate_ack_count())
+    }
+
+    #[inline]
+    fn get_retransmit_now_flag(&self) -> SharedAsyncValue<bool> {
+        dispatch!(self, cc => cc.get_retransmit_now_flag())
+    }
+
+    #[inline]
+    fn on_fast_retransmit(&mut self) {
+        dispatch!(self, cc => cc.on_fast_retransmit())
+    }
+
+    #[inline]
+    fn on_spurious_fast_retransmit(&mut self) {
+        dispatch!(self, cc => cc.on_spurious_fast_retransmit())
+    }
+
+    #[inline]
+    fn on_out_of_order_received(&mut self, receive_next: SeqNumber, seg_start: SeqNumber, seg_end: SeqNumber) {
+        dispatch!(self, cc => cc.on_out_of_order_received(receive_next, seg_start, seg_end))
+    }
+}
+
+impl LimitedTransmit for CongestionControlAlgorithm {
+    #[inline]
+    fn get_limited_transmit_cwnd_increase(&self) -> SharedAsyncValue<u32> {
+        dispatch!(self, cc => cc.get_limited_transmit_cwnd_increase())
+    }
+}
+
+impl Metrics for CongestionControlAlgorithm {
+    fn get_metrics(&self) -> Vec<(&'static str, OptionValue)> {
+        dispatch!(self, cc => cc.get_metrics())
+    }
+}
+
+//======================================================================================================================
+// Benchmarks
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::CongestionControlAlgorithm;
+    use crate::inetstack::protocols::layer4::tcp::{
+        established::congestion_control::{CongestionControl, Cubic, SlowStartCongestionAvoidance},
+        SeqNumber,
+    };
+    use ::std::time::Duration;
+    use ::test::{black_box, Bencher};
+
+    const MSS: usize = 1460;
+
+    /// Feeds one ACK per segment of a window of [num_segments] segments into [cc].
+    fn ack_window<C: SlowStartCongestionAvoidance + ?Sized>(
+        cc: &mut C,
+        send_unacked: &mut SeqNumber,
+        num_segments: u32,
+    ) {
+        let rto: Duration = Duration::from_secs(1);
+        let send_next: SeqNumber = *send_unacked + SeqNumber::from(num_segments * MSS as u32);
+        for _ in 0..num_segments {
+            let ack_seq_no: SeqNumber = *send_unacked + SeqNumber::from(MSS as u32);
+            cc.on_ack_received(rto, *send_unacked, send_next, black_box(ack_seq_no));
+            *send_unacked = ack_seq_no;
+        }
+    }
+
+    /// ACK processing through a trait object, as the control block used to do it.
+    #[bench]
+    fn bench_ack_processing_dynamic(b: &mut Bencher) {
+        let mut cc: Box<dyn CongestionControl> = match <Cubic as CongestionControl>::new(MSS, SeqNumber::from(0), None)
+        {
+            CongestionControlAlgorithm::Cubic(cubic) => Box::new(cubic),
+            _ => unreachable!("the constructor of cubic should return cubic"),
+        };
+        let mut send_unacked: SeqNumber = SeqNumber::from(0);
+
+        b.iter(|| ack_window(cc.as_mut(), &mut send_unacked, 64));
+    }
+
+    /// ACK processing through static dispatch.
+    #[bench]
+    fn bench_ack_processing_static(b: &mut Bencher) {
+        let mut cc: CongestionControlAlgorithm = <Cubic as CongestionControl>::new(MSS, SeqNumber::from(0), None);
+        let mut send_unacked: SeqNumber = SeqNumber::from(0);
+
+        b.iter(|| ack_window(&mut cc, &mut send_unacked, 64));
+    }
+}
@@ -0,0 +1,452 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// This is an implementation of BBR (version 1) congestion control, as described in draft-cardwell-iccrg-bbr-congestion-
+// control-00.  Instead of reacting to loss, BBR builds a model of the path out of two estimates: the bottleneck
+// bandwidth (the windowed maximum of the delivery rate) and the round-trip propagation time (the windowed minimum of the
+// RTT).  The sending rate (pacing rate) and cwnd are then derived from the bandwidth-delay product of that model.  The
+// model is updated from the delivery rate samples that the sender takes on every ACK (see delivery_rate.rs).
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    collections::async_value::SharedAsyncValue,
+    inetstack::protocols::layer4::tcp::{
+        established::{
+            congestion_control::{
+                CongestionControl, CongestionControlAlgorithm, FastRetransmitRecovery, LimitedTransmit, Metrics,
+                OptionValue, Options, SlowStartCongestionAvoidance,
+            },
+            delivery_rate::RateSample,
+        },
+        SeqNumber,
+    },
+};
+use ::std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Operating modes of the BBR state machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// Exponentially grow the sending rate until the bottleneck bandwidth is found.
+    Startup,
+    /// Drain the queue that was built during startup.
+    Drain,
+    /// Cycle the pacing gain to probe for more bandwidth.
+    ProbeBw,
+    /// Briefly reduce the amount of data in flight to measure the round-trip propagation time.
+    ProbeRtt,
+}
+
+#[derive(Debug)]
+pub struct Bbr {
+    mss: u32,
+    mode: Mode,
+    cwnd: SharedAsyncValue<u32>, // Congestion window: Max number of bytes that may be in flight.
+    prior_cwnd: u32,             // The last cwnd before entering ProbeRTT or a loss recovery episode.
+    in_rto_recovery: bool,       // Are we waiting for the retransmission after an RTO to be acknowledged.
+    pacing_gain: f64,
+    cwnd_gain: f64,
+    bytes_in_flight: u32,
+
+    // Bottleneck bandwidth estimation.
+    next_round_delivered: u64,           // Delivered bytes at which the next round trip starts.
+    round_count: u64,                    // Number of round trips so far.
+    btl_bw_filter: VecDeque<(f64, u64)>, // Delivery rate samples (bytes per second) tagged with their round.
+    full_bw: f64,                        // Baseline bandwidth for detecting that the pipe is full.
+    full_bw_count: u32,                  // Number of rounds without significant bandwidth growth.
+    filled_pipe: bool,                   // Have we found the bottleneck bandwidth?
+
+    // Round-trip propagation time estimation.
+    min_rtt: Option<Duration>,
+    min_rtt_stamp: Instant,
+    probe_rtt_done_stamp: Option<Instant>,
+
+    // ProbeBW gain cycling.
+    cycle_index: usize,
+    cycle_stamp: Instant,
+
+    // Fast Recovery / Fast Retransmit State
+    duplicate_ack_count: u32,
+    fast_retransmit_now: SharedAsyncValue<bool>,
+    limited_transmit_cwnd_increase: SharedAsyncValue<u32>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl CongestionControl for Bbr {
+    fn new(mss: usize, _seq_no: SeqNumber, options: Option<Options>) -> CongestionControlAlgorithm {
+        let mss: u32 = mss.try_into().unwrap();
+        let options: Options = options.unwrap_or_default();
+        let initial_cwnd: u32 = match options.get_int("initial_cwnd") {
+            Some(initial_cwnd) => initial_cwnd as u32,
+            None => Self::INITIAL_CWND_SEGMENTS * mss,
+        };
+        let now: Instant = Instant::now();
+        CongestionControlAlgorithm::Bbr(Self {
+            mss,
+            mode: Mode::Startup,
+            cwnd: SharedAsyncValue::new(initial_cwnd),
+            prior_cwnd: 0,
+            in_rto_recovery: false,
+            pacing_gain: Self::HIGH_GAIN,
+            cwnd_gain: Self::HIGH_GAIN,
+            bytes_in_flight: 0,
+            next_round_delivered: 0,
+            round_count: 0,
+            btl_bw_filter: VecDeque::new(),
+            full_bw: 0.0,
+            full_bw_count: 0,
+            filled_pipe: false,
+            min_rtt: None,
+            min_rtt_stamp: now,
+            probe_rtt_done_stamp: None,
+            cycle_index: 0,
+            cycle_stamp: now,
+            duplicate_ack_count: 0,
+            fast_retransmit_now: SharedAsyncValue::new(false),
+            limited_transmit_cwnd_increase: SharedAsyncValue::new(0),
+        })
+    }
+}
+
+impl Bbr {
+    // Gain used in Startup to double the sending rate every round (2/ln(2)).
+    const HIGH_GAIN: f64 = 2.885;
+    // Gain used in Drain to empty the queue built in Startup.
+    const DRAIN_GAIN: f64 = 1.0 / Self::HIGH_GAIN;
+    // Gain used for cwnd in ProbeBW.
+    const CWND_GAIN: f64 = 2.0;
+    // Pacing gains cycled through in ProbeBW.
+    const PACING_GAIN_CYCLE: [f64; 8] = [1.25, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+    // Length of the bottleneck bandwidth filter in round trips.
+    const BTL_BW_FILTER_ROUNDS: u64 = 10;
+    // Length of the round-trip propagation time filter.
+    const MIN_RTT_FILTER_LEN: Duration = Duration::from_secs(10);
+    // Time spent in ProbeRTT.
+    const PROBE_RTT_DURATION: Duration = Duration::from_millis(200);
+    // The pipe is considered full after this many rounds without this much growth.
+    const FULL_BW_THRESH: f64 = 1.25;
+    const FULL_BW_COUNT: u32 = 3;
+    // Minimum cwnd, in segments, to keep the ACK clock going.
+    const MIN_PIPE_CWND_SEGMENTS: u32 = 4;
+    const INITIAL_CWND_SEGMENTS: u32 = 10;
+    const DUP_ACK_THRESHOLD: u32 = 3;
+
+    /// Returns the current estimate of the bottleneck bandwidth in bytes per second.
+    fn btl_bw(&self) -> Option<f64> {
+        self.btl_bw_filter
+            .iter()
+            .map(|(bw, _)| *bw)
+            .fold(None, |acc: Option<f64>, bw| Some(acc.map_or(bw, |acc| acc.max(bw))))
+    }
+
+    /// Returns the estimated bandwidth-delay product in bytes scaled by [gain].
+    fn bdp(&self, gain: f64) -> Option<u32> {
+        match (self.btl_bw(), self.min_rtt) {
+            (Some(btl_bw), Some(min_rtt)) => Some((btl_bw * min_rtt.as_secs_f64() * gain) as u32),
+            _ => None,
+        }
+    }
+
+    fn min_pipe_cwnd(&self) -> u32 {
+        Self::MIN_PIPE_CWND_SEGMENTS * self.mss
+    }
+
+    // Counts round trips. A round trip ends when the data that was sent at its start has been delivered.
+    fn update_round(&mut self, sample: &RateSample) -> bool {
+        if sample.prior_delivered >= self.next_round_delivered {
+            self.next_round_delivered = sample.prior_delivered + sample.delivered;
+            self.round_count += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Adds a delivery rate sample to the bottleneck bandwidth filter.
+    fn update_btl_bw(&mut self, sample: &RateSample) {
+        let delivery_rate: f64 = match sample.delivery_rate() {
+            Some(delivery_rate) => delivery_rate,
+            None => return,
+        };
+        // Samples over less than a round trip are too noisy.
+        if self.min_rtt.is_some_and(|min_rtt| sample.interval < min_rtt) {
+            return;
+        }
+        // Application-limited samples only tell us that the bandwidth is at least as high as measured.
+        if sample.is_app_limited && delivery_rate < self.btl_bw().unwrap_or(0.0) {
+            return;
+        }
+
+        // Expire old samples and add the new one.
+        while let Some((_, round)) = self.btl_bw_filter.front() {
+            if self.round_count - round >= Self::BTL_BW_FILTER_ROUNDS {
+                self.btl_bw_filter.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.btl_bw_filter.push_back((delivery_rate, self.round_count));
+    }
+
+    fn check_full_pipe(&mut self) {
+        if self.filled_pipe {
+            return;
+        }
+        let btl_bw: f64 = self.btl_bw().unwrap_or(0.0);
+        if btl_bw >= self.full_bw * Self::FULL_BW_THRESH {
+            // Still growing.
+            self.full_bw = btl_bw;
+            self.full_bw_count = 0;
+        } else {
+            self.full_bw_count += 1;
+            self.filled_pipe = self.full_bw_count >= Self::FULL_BW_COUNT;
+        }
+    }
+
+    fn enter_probe_bw(&mut self, now: Instant) {
+        self.mode = Mode::ProbeBw;
+        self.cwnd_gain = Self::CWND_GAIN;
+        // Skip the draining phase of the cycle, there is nothing to drain yet.
+        self.cycle_index = 2;
+        self.cycle_stamp = now;
+        self.pacing_gain = Self::PACING_GAIN_CYCLE[self.cycle_index];
+    }
+
+    fn enter_startup(&mut self) {
+        self.mode = Mode::Startup;
+        self.pacing_gain = Self::HIGH_GAIN;
+        self.cwnd_gain = Self::HIGH_GAIN;
+    }
+
+    fn update_gain_cycle(&mut self, now: Instant) {
+        let min_rtt: Duration = match self.min_rtt {
+            Some(min_rtt) => min_rtt,
+            None => return,
+        };
+        let bdp: u32 = self.bdp(1.0).unwrap_or(0);
+        let is_full_length: bool = now.duration_since(self.cycle_stamp) > min_rtt;
+        let should_advance: bool = if self.pacing_gain > 1.0 {
+            is_full_length && self.bytes_in_flight >= self.bdp(self.pacing_gain).unwrap_or(0)
+        } else if self.pacing_gain < 1.0 {
+            is_full_length || self.bytes_in_flight <= bdp
+        } else {
+            is_full_length
+        };
+        if should_advance {
+            self.cycle_index = (self.cycle_index + 1) % Self::PACING_GAIN_CYCLE.len();
+            self.cycle_stamp = now;
+            self.pacing_gain = Self::PACING_GAIN_CYCLE[self.cycle_index];
+        }
+    }
+
+    fn update_mode(&mut self, now: Instant, new_round: bool) {
+        match self.mode {
+            Mode::Startup if self.filled_pipe => {
+                self.mode = Mode::Drain;
+                self.pacing_gain = Self::DRAIN_GAIN;
+                self.cwnd_gain = Self::HIGH_GAIN;
+            },
+            Mode::Drain if self.bytes_in_flight <= self.bdp(1.0).unwrap_or(0) => self.enter_probe_bw(now),
+            Mode::ProbeBw => self.update_gain_cycle(now),
+            Mode::ProbeRtt => self.update_probe_rtt(now, new_round),
+            _ => (),
+        }
+
+        // Enter ProbeRTT if the min RTT estimate has not been refreshed for a whole filter length.
+        let min_rtt_expired: bool = now.duration_since(self.min_rtt_stamp) > Self::MIN_RTT_FILTER_LEN;
+        if min_rtt_expired && self.mode != Mode::ProbeRtt {
+            self.mode = Mode::ProbeRtt;
+            self.pacing_gain = 1.0;
+            self.cwnd_gain = 1.0;
+            self.prior_cwnd = max(self.prior_cwnd, self.cwnd.get());
+            self.probe_rtt_done_stamp = None;
+        }
+    }
+
+    fn update_probe_rtt(&mut self, now: Instant, new_round: bool) {
+        match self.probe_rtt_done_stamp {
+            None if self.bytes_in_flight <= self.min_pipe_cwnd() => {
+                self.probe_rtt_done_stamp = Some(now + Self::PROBE_RTT_DURATION);
+            },
+            Some(done_stamp) if new_round && now >= done_stamp => {
+                self.min_rtt_stamp = now;
+                let cwnd: u32 = max(self.cwnd.get(), self.prior_cwnd);
+                self.cwnd.set(cwnd);
+                self.prior_cwnd = 0;
+                self.probe_rtt_done_stamp = None;
+                if self.filled_pipe {
+                    self.enter_probe_bw(now);
+                } else {
+                    self.enter_startup();
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn update_cwnd(&mut self, bytes_acknowledged: u32) {
+        let cwnd: u32 = self.cwnd.get();
+        let new_cwnd: u32 = if self.mode == Mode::ProbeRtt {
+            min(cwnd, self.min_pipe_cwnd())
+        } else {
+            match self.bdp(self.cwnd_gain) {
+                Some(target) if self.filled_pipe => min(cwnd + bytes_acknowledged, target),
+                Some(target) if cwnd < target => cwnd + bytes_acknowledged,
+                Some(_) => cwnd,
+                // No model yet, grow as in slow start.
+                None => cwnd + bytes_acknowledged,
+            }
+        };
+        let new_cwnd: u32 = max(new_cwnd, self.min_pipe_cwnd());
+        if new_cwnd != cwnd {
+            self.cwnd.set(new_cwnd);
+        }
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl SlowStartCongestionAvoidance for Bbr {
+    fn get_cwnd(&self) -> SharedAsyncValue<u32> {
+        self.cwnd.clone()
+    }
+
+    fn get_pacing_rate(&self) -> Option<u64> {
+        self.btl_bw().map(|btl_bw| (btl_bw * self.pacing_gain) as u64)
+    }
+
+    fn on_ack_received(
+        &mut self,
+        _rto: Duration,
+        send_unacked: SeqNumber,
+        send_next: SeqNumber,
+        ack_seq_no: SeqNumber,
+    ) {
+        let bytes_acknowledged: u32 = (ack_seq_no - send_unacked).into();
+        // The model and cwnd are updated from the delivery rate sample that the sender takes from the same ACK.
+        self.bytes_in_flight = (send_next - ack_seq_no).into();
+        if bytes_acknowledged == 0 {
+            // ACK is a duplicate. BBR does not back off on loss, but we still trigger a fast retransmit.
+            self.duplicate_ack_count += 1;
+            if self.duplicate_ack_count == Self::DUP_ACK_THRESHOLD {
+                self.fast_retransmit_now.set(true);
+            } else if self.duplicate_ack_count < Self::DUP_ACK_THRESHOLD {
+                self.limited_transmit_cwnd_increase.modify(|ltci| ltci + self.mss);
+            }
+            return;
+        }
+        self.duplicate_ack_count = 0;
+
+        // The retransmission went through, so restore the cwnd that we had before the RTO.
+        if self.in_rto_recovery {
+            self.in_rto_recovery = false;
+            let cwnd: u32 = max(self.cwnd.get(), self.prior_cwnd);
+            self.cwnd.set(cwnd);
+            if self.mode != Mode::ProbeRtt {
+                self.prior_cwnd = 0;
+            }
+        }
+    }
+
+    fn on_delivery_rate_sample(&mut self, sample: &RateSample) {
+        let now: Instant = Instant::now();
+        let new_round: bool = self.update_round(sample);
+        self.update_btl_bw(sample);
+        if new_round && !sample.is_app_limited {
+            self.check_full_pipe();
+        }
+        self.update_mode(now, new_round);
+        self.update_cwnd(sample.newly_acked);
+    }
+
+    fn on_rtt_sample(&mut self, rtt: Duration) {
+        let now: Instant = Instant::now();
+        let min_rtt_expired: bool = now.duration_since(self.min_rtt_stamp) > Self::MIN_RTT_FILTER_LEN;
+        if self.min_rtt.map_or(true, |min_rtt| rtt <= min_rtt) || min_rtt_expired {
+            self.min_rtt = Some(rtt);
+            self.min_rtt_stamp = now;
+        }
+    }
+
+    fn on_rto(&mut self, _send_unacked: SeqNumber) {
+        // Keep only one segment in flight until the retransmission is acknowledged, then restore cwnd.
+        if !self.in_rto_recovery {
+            self.prior_cwnd = max(self.prior_cwnd, self.cwnd.get());
+            self.in_rto_recovery = true;
+        }
+        self.cwnd.set(self.mss);
+    }
+
+    fn on_idle_restart(&mut self, now: Instant, _rto: Duration, _idle_time: Duration) {
+        // When restarting from idle, BBR keeps its cwnd but paces at exactly the estimated bandwidth, so that the
+        // restart neither bursts nor builds a queue.
+        if self.mode == Mode::ProbeBw {
+            self.pacing_gain = 1.0;
+            self.cycle_stamp = now;
+        }
+    }
+
+    fn on_send(&mut self, _rto: Duration, num_bytes_sent: u32) {
+        let new_value: u32 = self.limited_transmit_cwnd_increase.get().saturating_sub(num_bytes_sent);
+        self.limited_transmit_cwnd_increase.set_without_notify(new_value);
+    }
+}
+
+impl FastRetransmitRecovery for Bbr {
+    fn get_duplicate_ack_count(&self) -> u32 {
+        self.duplicate_ack_count
+    }
+
+    fn get_retransmit_now_flag(&self) -> SharedAsyncValue<bool> {
+        self.fast_retransmit_now.clone()
+    }
+
+    fn on_fast_retransmit(&mut self) {
+        self.fast_retransmit_now.set_without_notify(false);
+    }
+}
+
+impl LimitedTransmit for Bbr {
+    fn get_limited_transmit_cwnd_increase(&self) -> SharedAsyncValue<u32> {
+        self.limited_transmit_cwnd_increase.clone()
+    }
+}
+
+impl Metrics for Bbr {
+    fn get_metrics(&self) -> Vec<(&'static str, OptionValue)> {
+        let mut metrics: Vec<(&'static str, OptionValue)> = vec![
+            ("cwnd", OptionValue::Int(self.cwnd.get() as i64)),
+            ("bbr_mode", OptionValue::String(format!("{:?}", self.mode))),
+            ("pacing_gain", OptionValue::Float(self.pacing_gain)),
+            ("cwnd_gain", OptionValue::Float(self.cwnd_gain)),
+            ("filled_pipe", OptionValue::Bool(self.filled_pipe)),
+            ("round_count", OptionValue::Int(self.round_count as i64)),
+        ];
+        if let Some(pacing_rate) = self.get_pacing_rate() {
+            metrics.push(("pacing_rate", OptionValue::Int(pacing_rate as i64)));
+        }
+        if let Some(btl_bw) = self.btl_bw() {
+            metrics.push(("btl_bw", OptionValue::Float(btl_bw)));
+        }
+        if let Some(min_rtt) = self.min_rtt {
+            metrics.push(("min_rtt_us", OptionValue::Int(min_rtt.as_micros() as i64)));
+        }
+        metrics
+    }
+}
@@ -17,7 +17,8 @@ use crate::{
     collections::async_value::SharedAsyncValue,
     inetstack::protocols::layer4::tcp::{
         established::congestion_control::{
-            CongestionControl, FastRetransmitRecovery, LimitedTransmit, Options, SlowStartCongestionAvoidance,
+            CongestionControl, CongestionControlAlgorithm, CwndValidator, FastRetransmitRecovery, LimitedTransmit,
+            Metrics, OptionValue, Options, SlowStartCongestionAvoidance,
         },
         SeqNumber,
     },
@@ -41,12 +42,15 @@ pub struct Cubic {
     pub cwnd: SharedAsyncValue<u32>, // Congestion window: Max number of bytes that may be in flight ot prevent congestion.
     pub fast_convergence: bool, // Should we employ the fast convergence algorithm (Only recommended if there are multiple CUBIC streams on the same network, in which case we'll cede capacity to new ones faster).
     pub initial_cwnd: u32,      // The initial value of cwnd, which gets used if the connection ever resets.
-    pub last_send_time: Cell<Instant>, // The moment at which we last sent data.
+    pub restart_after_idle: bool, // Should we shrink cwnd towards the restart window after an idle period (RFC 5681).
     pub last_congestion_was_rto: Cell<bool>, // A flag for whether the last congestion event was detected by RTO.
     pub retransmitted_packets_in_flight: Cell<u32>, // A flag for if there is currently a retransmitted packet in flight.
     pub rtt_at_last_send: Cell<Duration>,           // The RTT at the moment we last sent data.
+    pub srtt: Cell<Option<Duration>>, // Smoothed RTT (RFC 6298), which sets the periods of cwnd validation.
     pub ssthresh: Cell<u32>, // The size of cwnd at which we will change from using slow start to congestion avoidance.
     pub w_max: Cell<u32>,    // The size of cwnd before the previous congestion event.
+    pub cwnd_validator: Option<CwndValidator>, // Congestion window validation state (RFC 7661), if enabled.
+    pub undo_state: Cell<Option<(u32, u32, u32)>>, // cwnd, ssthresh and w_max before the last RTO or fast retransmit, to undo it if it was spurious.
 
     // Fast Recovery / Fast Retransmit State
     pub duplicate_ack_count: Cell<u32>, // The number of consecutive duplicate ACKs we've received.
@@ -63,31 +67,42 @@ pub struct Cubic {
 //======================================================================================================================
 
 impl CongestionControl for Cubic {
-    fn new(mss: usize, seq_no: SeqNumber, options: Option<Options>) -> Box<dyn CongestionControl> {
+    fn new(mss: usize, seq_no: SeqNumber, options: Option<Options>) -> CongestionControlAlgorithm {
         let mss: u32 = mss.try_into().unwrap();
-        // The initial value of cwnd is set according to RFC5681, section 3.1, page 7.
-        let initial_cwnd: u32 = match mss {
-            0..=1095 => 4 * mss,
-            1096..=2190 => 3 * mss,
-            _ => 2 * mss,
-        };
-
         let options: Options = options.unwrap_or_default();
+        // If not given, the initial value of cwnd is set according to RFC5681, section 3.1, page 7.
+        let initial_cwnd: u32 = match options.get_int("initial_cwnd") {
+            Some(initial_cwnd) => initial_cwnd as u32,
+            None => match mss {
+                0..=1095 => 4 * mss,
+                1096..=2190 => 3 * mss,
+                _ => 2 * mss,
+            },
+        };
         let fast_convergence: bool = options.get_bool("fast_convergence").unwrap_or(true);
+        let cwnd_validation: bool = options.get_bool("cwnd_validation").unwrap_or(true);
+        let restart_after_idle: bool = options.get_bool("restart_after_idle").unwrap_or(true);
 
-        Box::new(Self {
+        CongestionControlAlgorithm::Cubic(Self {
             mss,
             // Slow Start / Congestion Avoidance State
             ca_start: Cell::new(Instant::now()), // Record the start time of the congestion avoidance period.
             cwnd: SharedAsyncValue::new(initial_cwnd),
             fast_convergence,
             initial_cwnd,
-            last_send_time: Cell::new(Instant::now()),
+            restart_after_idle,
             retransmitted_packets_in_flight: Cell::new(0),
             rtt_at_last_send: Cell::new(Duration::new(1, 0)), // The default RTT is 1 sec.
+            srtt: Cell::new(None),
             ssthresh: Cell::new(u32::MAX), // According to RFC5681 ssthresh should be initialised 'arbitrarily high'.
             w_max: Cell::new(0), // Because ssthresh is u32::MAX, this will be set appropriately during the 1st congestion event.
             last_congestion_was_rto: Cell::new(false),
+            undo_state: Cell::new(None),
+            cwnd_validator: if cwnd_validation {
+                Some(CwndValidator::new(Instant::now()))
+            } else {
+                None
+            },
 
             in_fast_recovery: Cell::new(false),
             fast_retransmit_now: SharedAsyncValue::new(false),
@@ -147,7 +162,18 @@ impl Cubic {
             // Check against recover specified in RFC6582.
             self.in_fast_recovery.set(true);
             self.recover.set(send_next);
-            let reduced_cwnd: u32 = (cwnd as f32 * Self::BETA_CUBIC) as u32;
+            // Remember the state before the reduction, so that we can restore it if the fast retransmit turns out to be
+            // spurious.
+            self.undo_state.set(Some((cwnd, self.ssthresh.get(), self.w_max.get())));
+            let mut reduced_cwnd: u32 = (cwnd as f32 * Self::BETA_CUBIC) as u32;
+            // If cwnd was not validated, reduce it based on what was actually in use (RFC 7661 Section 4.6.1).
+            let flight_size: u32 = (send_next - ack_seq_no).into();
+            let rtt: Duration = self.srtt.get().unwrap_or(self.rtt_at_last_send.get());
+            if let Some(validator) = self.cwnd_validator.as_mut() {
+                if let Some(nvp_cwnd) = validator.on_congestion(Instant::now(), flight_size, self.mss, rtt) {
+                    reduced_cwnd = min(reduced_cwnd, nvp_cwnd);
+                }
+            }
 
             if self.fast_convergence {
                 self.fast_convergence();
@@ -220,6 +246,14 @@ impl Cubic {
         let cwnd: u32 = self.cwnd.get();
         let ssthresh: u32 = self.ssthresh.get();
 
+        // Do not grow a cwnd that the application is not using (RFC 7661 Section 4.3).
+        let srtt: Duration = self.srtt.get().unwrap_or(rto);
+        if let Some(validator) = self.cwnd_validator.as_mut() {
+            if !validator.update_phase(Instant::now(), cwnd, srtt) {
+                return;
+            }
+        }
+
         if cwnd < ssthresh {
             // Slow start.
             self.cwnd.modify(|c| c + min(bytes_acknowledged, mss));
@@ -247,6 +281,13 @@ impl Cubic {
 
     fn on_rto_ss_ca(&mut self) {
         let cwnd: u32 = self.cwnd.get();
+        let rpif: u32 = self.retransmitted_packets_in_flight.get();
+
+        // Remember the state before the first timeout in a row, so that we can restore it if the timeout turns out to be
+        // spurious.
+        if rpif == 0 {
+            self.undo_state.set(Some((cwnd, self.ssthresh.get(), self.w_max.get())));
+        }
 
         if self.fast_convergence {
             self.fast_convergence();
@@ -255,7 +296,6 @@ impl Cubic {
         }
         self.cwnd.set(self.mss);
 
-        let rpif: u32 = self.retransmitted_packets_in_flight.get();
         if rpif == 0 {
             // If we lost a retransmitted packet, we don't shrink ssthresh.
             // So we have to check if a retransmitted packet was in flight before we shrink it.
@@ -287,23 +327,74 @@ impl SlowStartCongestionAvoidance for Cubic {
         self.cwnd.clone()
     }
 
-    fn on_cwnd_check_before_send(&mut self) {
-        let long_time_since_send: bool =
-            Instant::now().duration_since(self.last_send_time.get()) > self.rtt_at_last_send.get();
-        if long_time_since_send {
-            let restart_window: u32 = min(self.initial_cwnd, self.cwnd.get());
+    fn get_ssthresh(&self) -> u32 {
+        self.ssthresh.get()
+    }
+
+    fn on_idle_restart(&mut self, now: Instant, rto: Duration, idle_time: Duration) {
+        if !self.restart_after_idle {
+            return;
+        }
+        // Restart the ACK clock from the restart window (RFC 5681 Section 4.1).
+        let cwnd: u32 = self.cwnd.get();
+        let restart_window: u32 = min(self.initial_cwnd, cwnd);
+        if self.cwnd_validator.is_some() {
+            // The window was not validated while we were idle, so halve it for every RTO that we were idle instead of
+            // dropping it all at once, and keep it in ssthresh so that slow start quickly regains it (RFC 2861 Section
+            // 3, RFC 7661 Section 4.4.2).
+            let idle_rtos: u32 = (idle_time.as_nanos() / max(rto.as_nanos(), 1))
+                .try_into()
+                .unwrap_or(u32::MAX);
+            let decayed_cwnd: u32 = cwnd.checked_shr(idle_rtos).unwrap_or(0);
+            self.ssthresh
+                .set(max(self.ssthresh.get(), (cwnd / 4).saturating_mul(3)));
+            self.cwnd.set(max(decayed_cwnd, restart_window));
+        } else {
             self.cwnd.set(restart_window);
-            self.limited_transmit_cwnd_increase.set_without_notify(0);
+        }
+        self.limited_transmit_cwnd_increase.set_without_notify(0);
+        // Start a new congestion avoidance epoch so that the cubic function does not jump back to w_max.
+        self.ca_start.set(now);
+    }
+
+    fn on_app_limited(&mut self, bytes_in_flight: u32) {
+        // Running out of data with most of the window unused starts the non-validated phase right away, instead of
+        // waiting for the pipeACK measurement to catch up (RFC 7661 Section 4.3).
+        let cwnd: u32 = self.cwnd.get();
+        if let Some(validator) = self.cwnd_validator.as_mut() {
+            validator.on_app_limited(Instant::now(), cwnd, bytes_in_flight);
+        }
+    }
+
+    fn on_cwnd_check_before_send(&mut self, _rto: Duration, _bytes_in_flight: u32) {
+        // Decay cwnd if it has not been validated for a whole non-validated period (RFC 7661 Section 4.4.2).
+        let cwnd: u32 = self.cwnd.get();
+        let ssthresh: u32 = self.ssthresh.get();
+        let initial_cwnd: u32 = self.initial_cwnd;
+        if let Some(validator) = self.cwnd_validator.as_mut() {
+            if let Some((new_cwnd, new_ssthresh)) =
+                validator.on_nvp_expired(Instant::now(), cwnd, ssthresh, initial_cwnd)
+            {
+                self.ssthresh.set(new_ssthresh);
+                self.cwnd.set(new_cwnd);
+            }
         }
     }
 
     fn on_send(&mut self, rto: Duration, num_bytes_sent: u32) {
-        self.last_send_time.set(Instant::now());
         self.rtt_at_last_send.set(rto);
         let new_value: u32 = self.limited_transmit_cwnd_increase.get().saturating_sub(num_bytes_sent);
         self.limited_transmit_cwnd_increase.set_without_notify(new_value);
     }
 
+    fn on_rtt_sample(&mut self, rtt: Duration) {
+        // Smooth the samples like the RTO calculator does (RFC 6298 Section 2).
+        self.srtt.set(Some(match self.srtt.get() {
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        }));
+    }
+
     fn on_ack_received(&mut self, rto: Duration, send_unacked: SeqNumber, send_next: SeqNumber, ack_seq_no: SeqNumber) {
         let bytes_acknowledged: u32 = (ack_seq_no - send_unacked).into();
         if bytes_acknowledged == 0 {
@@ -316,6 +407,13 @@ impl SlowStartCongestionAvoidance for Cubic {
         } else {
             self.duplicate_ack_count.set(0);
 
+            // Measure how much of cwnd is actually in use (pipeACK). Until there is an RTT sample, the RTO stands in for
+            // the RTT.
+            let srtt: Duration = self.srtt.get().unwrap_or(rto);
+            if let Some(validator) = self.cwnd_validator.as_mut() {
+                validator.on_ack(Instant::now(), bytes_acknowledged, srtt);
+            }
+
             if self.in_fast_recovery.get() {
                 // Fast Recovery response to new data.
                 self.on_ack_received_fast_recovery(send_unacked, send_next, ack_seq_no);
@@ -332,6 +430,36 @@ impl SlowStartCongestionAvoidance for Cubic {
         self.on_rto_ss_ca();
         self.on_rto_fast_recovery(send_unacked);
     }
+
+    fn on_spurious_rto(&mut self) {
+        // Undo the window reduction of the spurious timeout (RFC 5682 Section 3).
+        if let Some((cwnd, ssthresh, w_max)) = self.undo_state.take() {
+            self.cwnd.set(cwnd);
+            self.ssthresh.set(ssthresh);
+            self.w_max.set(w_max);
+        }
+        self.retransmitted_packets_in_flight.set(0);
+        self.last_congestion_was_rto.set(false);
+    }
+
+    fn on_ecn_echo(&mut self) {
+        // Fast recovery already reduced the window for this round of congestion.
+        if self.in_fast_recovery.get() {
+            return;
+        }
+        let cwnd: u32 = self.cwnd.get();
+        if self.fast_convergence {
+            self.fast_convergence();
+        } else {
+            self.w_max.set(cwnd);
+        }
+        let reduced_cwnd: u32 = max((cwnd as f32 * Self::BETA_CUBIC) as u32, 2 * self.mss);
+        self.ssthresh.set(reduced_cwnd);
+        self.cwnd.set(reduced_cwnd);
+        // Go straight back into congestion avoidance, as there is nothing to recover.
+        self.ca_start.set(Instant::now());
+        self.last_congestion_was_rto.set(false);
+    }
 }
 
 impl FastRetransmitRecovery for Cubic {
@@ -349,6 +477,17 @@ impl FastRetransmitRecovery for Cubic {
         // I should really use some other mechanism here just because it would be nicer...
         self.fast_retransmit_now.set_without_notify(false);
     }
+
+    fn on_spurious_fast_retransmit(&mut self) {
+        // Undo the window reduction of the spurious fast retransmit and leave fast recovery (RFC 3708 Section 3.2).
+        if let Some((cwnd, ssthresh, w_max)) = self.undo_state.take() {
+            self.cwnd.set(cwnd);
+            self.ssthresh.set(ssthresh);
+            self.w_max.set(w_max);
+        }
+        self.in_fast_recovery.set(false);
+        self.duplicate_ack_count.set(0);
+    }
 }
 
 impl LimitedTransmit for Cubic {
@@ -356,3 +495,18 @@ impl LimitedTransmit for Cubic {
         self.limited_transmit_cwnd_increase.clone()
     }
 }
+
+impl Metrics for Cubic {
+    fn get_metrics(&self) -> Vec<(&'static str, OptionValue)> {
+        vec![
+            ("cwnd", OptionValue::Int(self.cwnd.get() as i64)),
+            ("ssthresh", OptionValue::Int(self.ssthresh.get() as i64)),
+            ("w_max", OptionValue::Int(self.w_max.get() as i64)),
+            ("in_fast_recovery", OptionValue::Bool(self.in_fast_recovery.get())),
+            (
+                "duplicate_ack_count",
+                OptionValue::Int(self.duplicate_ack_count.get() as i64),
+            ),
+        ]
+    }
+}
@@ -1,24 +1,37 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+mod algorithm;
+mod bbr;
 mod cubic;
 mod none;
 mod options;
+mod validation;
 
-use crate::{collections::async_value::SharedAsyncValue, inetstack::protocols::layer4::tcp::SeqNumber};
-use ::std::{fmt::Debug, time::Duration};
+use crate::{
+    collections::async_value::SharedAsyncValue,
+    inetstack::protocols::layer4::tcp::{established::delivery_rate::RateSample, SeqNumber},
+};
+use ::std::{
+    cmp::{max, min},
+    fmt::Debug,
+    time::{Duration, Instant},
+};
 
 pub use self::{
+    algorithm::CongestionControlAlgorithm,
+    bbr::Bbr,
     cubic::Cubic,
     none::None,
     options::{OptionValue, Options},
+    validation::CwndValidator,
 };
 
 pub trait SlowStartCongestionAvoidance {
     fn get_cwnd(&self) -> SharedAsyncValue<u32>;
 
     // Called immediately before the cwnd check is performed before data is sent.
-    fn on_cwnd_check_before_send(&mut self) {}
+    fn on_cwnd_check_before_send(&mut self, _rto: Duration, _bytes_in_flight: u32) {}
 
     fn on_ack_received(
         &mut self,
@@ -32,8 +45,39 @@ pub trait SlowStartCongestionAvoidance {
     // Called immediately before retransmit after RTO.
     fn on_rto(&mut self, _send_unacked: SeqNumber) {}
 
+    // Called when the last RTO was detected to be spurious, so that the window reduction can be undone.
+    fn on_spurious_rto(&mut self) {}
+
+    // Called before data is sent after the connection has been idle, i.e. nothing was in flight and nothing was sent
+    // for longer than one RTO. The window is stale by then, so it should not be sent in one burst.
+    fn on_idle_restart(&mut self, _now: Instant, _rto: Duration, _idle_time: Duration) {}
+
+    // Called when the sender runs out of data to send, so the rest of the window goes unused for now.
+    fn on_app_limited(&mut self, _bytes_in_flight: u32) {}
+
     // Called immediately before a segment is sent for the 1st time.
     fn on_send(&mut self, _rto: Duration, _num_sent_bytes: u32) {}
+
+    // Called when an acknowledgement yields a new round-trip time sample.
+    fn on_rtt_sample(&mut self, _rtt: Duration) {}
+
+    // Called when an acknowledgement of new data yields a delivery rate sample. This is called after on_ack_received()
+    // and on_rtt_sample() for the same acknowledgement.
+    fn on_delivery_rate_sample(&mut self, _sample: &RateSample) {}
+
+    // Called when our peer echoes a congestion experienced mark (RFC 3168), at most once per window of data. This is a
+    // sign of congestion just like a lost segment, except that nothing needs to be retransmitted.
+    fn on_ecn_echo(&mut self) {}
+
+    // Slow start threshold in bytes. Algorithms that do not use one report it as arbitrarily high.
+    fn get_ssthresh(&self) -> u32 {
+        u32::MAX
+    }
+
+    // Rate in bytes per second at which the sender should pace segments, if the algorithm uses pacing.
+    fn get_pacing_rate(&self) -> Option<u64> {
+        Option::None
+    }
 }
 
 pub trait FastRetransmitRecovery
@@ -47,6 +91,15 @@ where
     fn get_retransmit_now_flag(&self) -> SharedAsyncValue<bool>;
 
     fn on_fast_retransmit(&mut self) {}
+
+    // Called when our peer reported all retransmissions of the last fast retransmit as duplicates (RFC 3708), so that
+    // the window reduction can be undone.
+    fn on_spurious_fast_retransmit(&mut self) {}
+
+    // Called when the receiver stores a segment that arrived ahead of the next expected sequence number. This is a
+    // sign of reordering or loss on the path, which early retransmit (RFC 5827) and RACK-style reordering tolerance
+    // can take into account.
+    fn on_out_of_order_received(&mut self, _receive_next: SeqNumber, _seg_start: SeqNumber, _seg_end: SeqNumber) {}
 }
 
 pub trait LimitedTransmit
@@ -56,10 +109,225 @@ where
     fn get_limited_transmit_cwnd_increase(&self) -> SharedAsyncValue<u32>;
 }
 
-pub trait CongestionControl: SlowStartCongestionAvoidance + FastRetransmitRecovery + LimitedTransmit + Debug {
-    fn new(mss: usize, seq_no: SeqNumber, options: Option<options::Options>) -> Box<dyn CongestionControl>
+pub trait Metrics
+where
+    Self: SlowStartCongestionAvoidance,
+{
+    // Key/value pairs that describe the internal state of the algorithm, so that algorithms can be compared.
+    fn get_metrics(&self) -> Vec<(&'static str, OptionValue)> {
+        vec![("cwnd", OptionValue::Int(self.get_cwnd().get() as i64))]
+    }
+}
+
+pub trait CongestionControl:
+    SlowStartCongestionAvoidance + FastRetransmitRecovery + LimitedTransmit + Metrics + Debug
+{
+    fn new(mss: usize, seq_no: SeqNumber, options: Option<options::Options>) -> CongestionControlAlgorithm
     where
         Self: Sized;
 }
 
-pub type CongestionControlConstructor = fn(usize, SeqNumber, Option<options::Options>) -> Box<dyn CongestionControl>;
+pub type CongestionControlConstructor = fn(usize, SeqNumber, Option<options::Options>) -> CongestionControlAlgorithm;
+
+/// Computes the initial congestion window in bytes for a connection that starts with [segments] segments of [mss]
+/// bytes. The initial window is never larger than the window advertised by our peer, but it always allows at least one
+/// segment to be sent.
+pub fn get_initial_cwnd(segments: usize, mss: usize, peer_window_size: u32) -> u32 {
+    let initial_cwnd: u32 = segments.saturating_mul(mss).try_into().unwrap_or(u32::MAX);
+    max(min(initial_cwnd, peer_window_size), mss as u32)
+}
+
+/// Congestion control algorithms that connections can use, by name.
+pub const ALGORITHMS: &[(&str, CongestionControlConstructor)] = &[
+    ("none", <None as CongestionControl>::new),
+    ("cubic", <Cubic as CongestionControl>::new),
+    ("bbr", <Bbr as CongestionControl>::new),
+];
+
+/// Looks up a congestion control algorithm by its name, returning its canonical name and its constructor.
+pub fn get_algorithm(name: &str) -> Option<(&'static str, CongestionControlConstructor)> {
+    ALGORITHMS.iter().copied().find(|(algorithm, _)| *algorithm == name)
+}
+
+/// Looks up the constructor of a congestion control algorithm by its name.
+pub fn get_constructor(name: &str) -> Option<CongestionControlConstructor> {
+    get_algorithm(name).map(|(_, constructor)| constructor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        get_algorithm, get_constructor, get_initial_cwnd, CongestionControl, CongestionControlAlgorithm, Cubic,
+        FastRetransmitRecovery, Metrics, OptionValue, Options, SlowStartCongestionAvoidance,
+    };
+    use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::{
+        cmp::min,
+        time::{Duration, Instant},
+    };
+
+    const MSS: usize = 1460;
+
+    /// Counts the round trips that Cubic needs to deliver a flow of [flow_size] bytes over a path without loss, when
+    /// starting with an initial window of [initial_cwnd_segments] segments.
+    fn short_flow_round_trips(flow_size: usize, initial_cwnd_segments: usize) -> usize {
+        let mut options: Options = Options::default();
+        let initial_cwnd: u32 = get_initial_cwnd(initial_cwnd_segments, MSS, u32::MAX);
+        options.insert_int("initial_cwnd".to_string(), initial_cwnd as i64);
+        let mut cc: CongestionControlAlgorithm =
+            <Cubic as CongestionControl>::new(MSS, SeqNumber::from(0), Some(options));
+
+        let rto: Duration = Duration::from_secs(1);
+        let mut send_unacked: SeqNumber = SeqNumber::from(0);
+        let mut remaining: usize = flow_size;
+        let mut round_trips: usize = 0;
+        while remaining > 0 {
+            // Send a full window, then get one ACK per segment.
+            let window: usize = min(cc.get_cwnd().get() as usize, remaining);
+            let send_next: SeqNumber = send_unacked + SeqNumber::from(window as u32);
+            let mut acked: usize = 0;
+            while acked < window {
+                let segment: usize = min(MSS, window - acked);
+                let ack_seq_no: SeqNumber = send_unacked + SeqNumber::from(segment as u32);
+                cc.on_ack_received(rto, send_unacked, send_next, ack_seq_no);
+                send_unacked = ack_seq_no;
+                acked += segment;
+            }
+            remaining -= window;
+            round_trips += 1;
+        }
+        round_trips
+    }
+
+    #[test]
+    fn test_initial_cwnd_is_clamped_to_peer_window() -> Result<()> {
+        crate::ensure_eq!(get_initial_cwnd(10, MSS, u32::MAX), 10 * MSS as u32);
+        crate::ensure_eq!(get_initial_cwnd(10, MSS, 4096), 4096);
+        // We always allow at least one segment.
+        crate::ensure_eq!(get_initial_cwnd(10, MSS, 0), MSS as u32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_flow_completion_with_initial_cwnd() -> Result<()> {
+        // A 10-segment flow fits in the first flight with IW10.
+        crate::ensure_eq!(short_flow_round_trips(10 * MSS, 10), 1);
+        // The same flow needs several round trips with the RFC 5681 initial window.
+        crate::ensure_eq!(short_flow_round_trips(10 * MSS, 3), 3);
+        crate::ensure_eq!(short_flow_round_trips(10 * MSS, 1), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_constructor() -> Result<()> {
+        crate::ensure_eq!(get_constructor("none").is_some(), true);
+        crate::ensure_eq!(get_constructor("cubic").is_some(), true);
+        crate::ensure_eq!(get_constructor("bbr").is_some(), true);
+        crate::ensure_eq!(get_constructor("reno").is_none(), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_algorithm() -> Result<()> {
+        crate::ensure_eq!(get_algorithm("cubic").map(|(name, _)| name), Some("cubic"));
+        crate::ensure_eq!(get_algorithm("CUBIC").is_none(), true);
+        crate::ensure_eq!(get_algorithm("").is_none(), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_metrics() -> Result<()> {
+        for (name, constructor) in super::ALGORITHMS {
+            let cc: CongestionControlAlgorithm = constructor(MSS, SeqNumber::from(0), Option::None);
+            let metrics: Vec<(&'static str, OptionValue)> = cc.get_metrics();
+            match metrics.iter().find(|(key, _)| *key == "cwnd") {
+                Some((_, OptionValue::Int(cwnd))) => crate::ensure_eq!(*cwnd, cc.get_cwnd().get() as i64),
+                _ => anyhow::bail!("{} should report cwnd", name),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_spurious_rto_restores_cwnd() -> Result<()> {
+        let mut cc: CongestionControlAlgorithm =
+            <Cubic as CongestionControl>::new(MSS, SeqNumber::from(0), Option::None);
+        let initial_cwnd: u32 = cc.get_cwnd().get();
+
+        cc.on_rto(SeqNumber::from(0));
+        crate::ensure_eq!(cc.get_cwnd().get(), MSS as u32);
+
+        cc.on_spurious_rto();
+        crate::ensure_eq!(cc.get_cwnd().get(), initial_cwnd);
+        Ok(())
+    }
+
+    #[test]
+    fn test_spurious_fast_retransmit_restores_cwnd() -> Result<()> {
+        let mut cc: CongestionControlAlgorithm =
+            <Cubic as CongestionControl>::new(MSS, SeqNumber::from(0), Option::None);
+        let initial_cwnd: u32 = cc.get_cwnd().get();
+
+        // Three duplicate ACKs trigger a fast retransmit, which shrinks the window.
+        let rto: Duration = Duration::from_secs(1);
+        let send_next: SeqNumber = SeqNumber::from(10 * MSS as u32);
+        for _ in 0..3 {
+            cc.on_ack_received(rto, SeqNumber::from(0), send_next, SeqNumber::from(0));
+        }
+        crate::ensure_eq!(cc.get_retransmit_now_flag().get(), true);
+        crate::ensure_eq!(cc.get_cwnd().get() < initial_cwnd, true);
+
+        cc.on_spurious_fast_retransmit();
+        crate::ensure_eq!(cc.get_cwnd().get(), initial_cwnd);
+        Ok(())
+    }
+
+    #[test]
+    fn test_idle_restart_decays_cwnd() -> Result<()> {
+        let mut options: Options = Options::default();
+        options.insert_int("initial_cwnd".to_string(), 2 * MSS as i64);
+        let mut cc: CongestionControlAlgorithm =
+            <Cubic as CongestionControl>::new(MSS, SeqNumber::from(0), Some(options));
+
+        // Grow cwnd to 16 segments in slow start, with an RTT well below the RTO.
+        let rtt: Duration = Duration::from_millis(100);
+        let rto: Duration = Duration::from_secs(1);
+        let mut send_unacked: SeqNumber = SeqNumber::from(0);
+        while cc.get_cwnd().get() < 16 * MSS as u32 {
+            let ack_seq_no: SeqNumber = send_unacked + SeqNumber::from(MSS as u32);
+            cc.on_ack_received(rtt, send_unacked, ack_seq_no, ack_seq_no);
+            send_unacked = ack_seq_no;
+        }
+        cc.on_send(rtt, MSS as u32);
+
+        // The stale window is halved for every RTO that the connection was idle, not for every RTT.
+        let now: Instant = Instant::now();
+        cc.on_idle_restart(now, rto, 2 * rto + Duration::from_millis(1));
+        crate::ensure_eq!(cc.get_cwnd().get(), 4 * MSS as u32);
+
+        // It never drops below the restart window.
+        cc.on_idle_restart(now, rto, 10 * rto);
+        crate::ensure_eq!(cc.get_cwnd().get(), 2 * MSS as u32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ecn_echo_reduces_cwnd() -> Result<()> {
+        let mut cc: CongestionControlAlgorithm =
+            <Cubic as CongestionControl>::new(MSS, SeqNumber::from(0), Option::None);
+        let initial_cwnd: u32 = cc.get_cwnd().get();
+
+        // The window shrinks as if a segment was lost, but there is nothing to retransmit.
+        cc.on_ecn_echo();
+        crate::ensure_eq!(cc.get_cwnd().get(), (initial_cwnd as f32 * 0.7) as u32);
+        crate::ensure_eq!(cc.get_retransmit_now_flag().get(), false);
+
+        // It never shrinks below two segments.
+        for _ in 0..10 {
+            cc.on_ecn_echo();
+        }
+        crate::ensure_eq!(cc.get_cwnd().get(), 2 * MSS as u32);
+        Ok(())
+    }
+}
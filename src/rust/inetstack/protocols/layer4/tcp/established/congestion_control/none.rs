@@ -9,7 +9,8 @@ use crate::{
     collections::async_value::SharedAsyncValue,
     inetstack::protocols::layer4::tcp::{
         established::congestion_control::{
-            CongestionControl, FastRetransmitRecovery, LimitedTransmit, Options, SlowStartCongestionAvoidance,
+            CongestionControl, CongestionControlAlgorithm, FastRetransmitRecovery, LimitedTransmit, Metrics, Options,
+            SlowStartCongestionAvoidance,
         },
         SeqNumber,
     },
@@ -33,8 +34,8 @@ pub struct None {
 //======================================================================================================================
 
 impl CongestionControl for None {
-    fn new(_mss: usize, _seq_no: SeqNumber, _options: Option<Options>) -> Box<dyn CongestionControl> {
-        Box::new(Self {
+    fn new(_mss: usize, _seq_no: SeqNumber, _options: Option<Options>) -> CongestionControlAlgorithm {
+        CongestionControlAlgorithm::None(Self {
             cwnd: SharedAsyncValue::new(u32::MAX),
             fast_retransmit_flag: SharedAsyncValue::new(false),
             limited_retransmit_cwnd_increase: SharedAsyncValue::new(0),
@@ -57,3 +58,4 @@ impl LimitedTransmit for None {
         self.limited_retransmit_cwnd_increase.clone()
     }
 }
+impl Metrics for None {}
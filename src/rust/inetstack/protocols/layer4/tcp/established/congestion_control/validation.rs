@@ -0,0 +1,228 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Congestion window validation for application-limited senders.
+// See RFC 7661 (NewCWV) for details.
+//
+// A sender that is application-limited does not exercise its whole cwnd, so the path never validates that window.
+// NewCWV tracks the volume of data that the peer actually acknowledges per RTT (pipeACK).  While pipeACK is below half
+// of cwnd the sender is in the non-validated phase (NVP): cwnd is not allowed to grow, and if the NVP lasts longer
+// than the non-validated period the cwnd is decayed so that a stale window cannot trigger a burst when the application
+// resumes sending. The window is decayed once for every non-validated period that the NVP lasts.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::std::{
+    cmp::max,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+// Non-validated period (NVP) as recommended by RFC 7661 Section 5.
+const NON_VALIDATED_PERIOD: Duration = Duration::from_secs(300);
+
+// Lower bound for the pipeACK measurement period (RFC 7661 Section 4.2).
+const MIN_PIPE_ACK_MEASUREMENT_PERIOD: Duration = Duration::from_secs(1);
+
+// Number of RTTs that a pipeACK sample is retained for (RFC 7661 Section 4.2).
+const PIPE_ACK_MEASUREMENT_RTTS: u32 = 3;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+#[derive(Debug)]
+pub struct CwndValidator {
+    // Bytes acknowledged since the current pipeACK sample started.
+    sample_bytes: u32,
+    // Start of the current pipeACK sample.
+    sample_start: Instant,
+    // The largest completed pipeACK sample and the time at which it was taken.
+    pipe_ack: Option<(u32, Instant)>,
+    // The time at which the non-validated phase started, if we are in it.
+    nvp_start: Option<Instant>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl CwndValidator {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            sample_bytes: 0,
+            sample_start: now,
+            pipe_ack: None,
+            nvp_start: None,
+        }
+    }
+
+    /// Accounts for [bytes_acknowledged] newly acknowledged bytes and updates pipeACK.
+    pub fn on_ack(&mut self, now: Instant, bytes_acknowledged: u32, rtt: Duration) {
+        self.sample_bytes = self.sample_bytes.saturating_add(bytes_acknowledged);
+
+        // Close the current sample once it covers at least one RTT.
+        if now.duration_since(self.sample_start) >= rtt {
+            let sample: u32 = self.sample_bytes;
+            self.sample_bytes = 0;
+            self.sample_start = now;
+            self.pipe_ack = match self.pipe_ack {
+                Some((pipe_ack, _)) if pipe_ack > sample && !Self::is_stale(now, self.pipe_ack, rtt) => self.pipe_ack,
+                _ => Some((sample, now)),
+            };
+        }
+    }
+
+    /// Returns the current pipeACK value in bytes, if a valid measurement is available.
+    pub fn get_pipe_ack(&self, now: Instant, rtt: Duration) -> Option<u32> {
+        if Self::is_stale(now, self.pipe_ack, rtt) {
+            None
+        } else {
+            self.pipe_ack.map(|(pipe_ack, _)| pipe_ack)
+        }
+    }
+
+    /// Updates the validation phase for [cwnd] and returns whether cwnd is currently validated. cwnd should only be
+    /// grown while it is validated.
+    pub fn update_phase(&mut self, now: Instant, cwnd: u32, rtt: Duration) -> bool {
+        // Without a pipeACK measurement we cannot tell if the sender is application-limited (RFC 7661 Section 4.3).
+        let validated: bool = match self.get_pipe_ack(now, rtt) {
+            Some(pipe_ack) => pipe_ack >= cwnd / 2,
+            None => true,
+        };
+        if validated {
+            self.nvp_start = None;
+        } else if self.nvp_start.is_none() {
+            self.nvp_start = Some(now);
+        }
+        validated
+    }
+
+    /// Starts the non-validated phase if the sender ran out of data to send while less than half of [cwnd] is in
+    /// flight, as the sender is application-limited then (RFC 7661 Section 4.3).
+    pub fn on_app_limited(&mut self, now: Instant, cwnd: u32, bytes_in_flight: u32) {
+        if bytes_in_flight < cwnd / 2 && self.nvp_start.is_none() {
+            self.nvp_start = Some(now);
+        }
+    }
+
+    /// Checks whether the non-validated phase has outlasted the non-validated period. If so, returns the new values for
+    /// cwnd and ssthresh as given by RFC 7661 Section 4.4.2, applied once for every period that has passed, and starts a
+    /// new period.
+    pub fn on_nvp_expired(&mut self, now: Instant, cwnd: u32, ssthresh: u32, initial_cwnd: u32) -> Option<(u32, u32)> {
+        let mut nvp_start: Instant = self.nvp_start?;
+        let mut new_cwnd: u32 = cwnd;
+        let mut new_ssthresh: u32 = ssthresh;
+        while now.saturating_duration_since(nvp_start) > NON_VALIDATED_PERIOD && new_cwnd > initial_cwnd {
+            nvp_start += NON_VALIDATED_PERIOD;
+            new_ssthresh = max(new_ssthresh, (new_cwnd / 4).saturating_mul(3));
+            new_cwnd = max(new_cwnd / 2, initial_cwnd);
+        }
+        if new_cwnd == cwnd {
+            return None;
+        }
+        self.nvp_start = Some(now);
+        Some((new_cwnd, new_ssthresh))
+    }
+
+    /// Computes the window to use after a congestion event in the non-validated phase (RFC 7661 Section 4.6.1).
+    /// Returns None if the sender is not in the non-validated phase.
+    pub fn on_congestion(&mut self, now: Instant, flight_size: u32, mss: u32, rtt: Duration) -> Option<u32> {
+        // Leave the non-validated phase, the window is recomputed below.
+        self.nvp_start.take()?;
+        let pipe_ack: u32 = self.get_pipe_ack(now, rtt).unwrap_or(0);
+        Some(max(max(pipe_ack, flight_size) / 2, mss))
+    }
+
+    fn is_stale(now: Instant, pipe_ack: Option<(u32, Instant)>, rtt: Duration) -> bool {
+        let period: Duration = max(rtt * PIPE_ACK_MEASUREMENT_RTTS, MIN_PIPE_ACK_MEASUREMENT_PERIOD);
+        match pipe_ack {
+            Some((_, taken)) => now.duration_since(taken) > period,
+            None => true,
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{CwndValidator, NON_VALIDATED_PERIOD};
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    const RTT: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn test_cwnd_validation_app_limited() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut validator: CwndValidator = CwndValidator::new(now);
+
+        // Without a measurement the cwnd is treated as validated.
+        crate::ensure_eq!(validator.update_phase(now, 64_000, RTT), true);
+
+        // Application only manages to fill a small fraction of cwnd.
+        validator.on_ack(now + RTT, 4_000, RTT);
+        crate::ensure_eq!(validator.get_pipe_ack(now + RTT, RTT), Some(4_000));
+        crate::ensure_eq!(validator.update_phase(now + RTT, 64_000, RTT), false);
+
+        // Not decayed before the non-validated period ends.
+        crate::ensure_eq!(validator.on_nvp_expired(now + RTT, 64_000, u32::MAX, 4_000), None);
+
+        // Decayed once the non-validated period ends.
+        let later: Instant = now + RTT + NON_VALIDATED_PERIOD + RTT;
+        crate::ensure_eq!(
+            validator.on_nvp_expired(later, 64_000, 10_000, 4_000),
+            Some((32_000, 48_000))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cwnd_validation_validated() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut validator: CwndValidator = CwndValidator::new(now);
+
+        validator.on_ack(now + RTT, 40_000, RTT);
+        crate::ensure_eq!(validator.update_phase(now + RTT, 64_000, RTT), true);
+        // Running out of data with most of cwnd in flight does not make the sender application-limited.
+        validator.on_app_limited(now + RTT, 64_000, 40_000);
+        crate::ensure_eq!(validator.on_congestion(now + RTT, 64_000, 1_000, RTT), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cwnd_validation_decay_per_period() -> Result<()> {
+        let now: Instant = Instant::now();
+        let mut validator: CwndValidator = CwndValidator::new(now);
+
+        // Running out of data with most of cwnd unused starts the non-validated phase.
+        validator.on_app_limited(now, 64_000, 4_000);
+
+        // cwnd is halved once for every period that has passed.
+        let later: Instant = now + 3 * NON_VALIDATED_PERIOD + RTT;
+        crate::ensure_eq!(
+            validator.on_nvp_expired(later, 64_000, 10_000, 4_000),
+            Some((8_000, 48_000))
+        );
+
+        // It never drops below the initial window.
+        let much_later: Instant = later + 10 * NON_VALIDATED_PERIOD;
+        crate::ensure_eq!(
+            validator.on_nvp_expired(much_later, 8_000, 48_000, 4_000),
+            Some((4_000, 48_000))
+        );
+        crate::ensure_eq!(validator.on_nvp_expired(much_later, 4_000, 48_000, 4_000), None);
+
+        Ok(())
+    }
+}
@@ -9,33 +9,60 @@ use crate::{
     async_timer,
     collections::{async_queue::SharedAsyncQueue, async_value::SharedAsyncValue},
     inetstack::protocols::{
-        layer3::SharedLayer3Endpoint,
-        layer4::tcp::{
-            constants::MSL,
-            established::{
-                congestion_control::{self, CongestionControlConstructor},
-                receiver::Receiver,
-                sender::Sender,
+        layer3::{ip::IpProtocol, ipv4::IPV4_HEADER_MIN_SIZE, Ecn, SharedLayer3Endpoint},
+        layer4::{
+            stats_delta::{StatsBaseline, StatsDelta},
+            tcp::{
+                established::{
+                    congestion_control::{
+                        self, CongestionControlAlgorithm, CongestionControlConstructor, FastRetransmitRecovery,
+                        LimitedTransmit, Metrics, SlowStartCongestionAvoidance,
+                    },
+                    ecn::EcnState,
+                    receiver::Receiver,
+                    sender::{RetransmissionCause, Sender},
+                    stats::Stats,
+                },
+                header::{SelectiveAcknowlegement, TcpHeader, TcpOptions2, MIN_TCP_HEADER_SIZE},
+                listener_stats::FirstByteTimer,
+                time_wait::TimeWaitEntry,
+                timestamps::{get_timestamps_option, Timestamps, TIMESTAMPS_OPTION_SIZE},
+                SeqNumber,
             },
-            header::TcpHeader,
-            SeqNumber,
         },
         MAX_HEADER_SIZE,
     },
     runtime::{
+        conditional_yield_until, conditional_yield_with_timeout,
         fail::Fail,
         memory::DemiBuffer,
-        network::{config::TcpConfig, socket::option::TcpSocketOptions},
-        yield_with_timeout, SharedDemiRuntime, SharedObject,
+        network::{
+            config::TcpConfig,
+            consts::MIN_MSS,
+            socket::option::{DelayedAck, PriorityClass, QuickAck, TcpInfo, TcpSocketOptions, TcpState},
+            types::MacAddress,
+        },
+        SharedConditionVariable, SharedDemiRuntime, SharedObject,
     },
 };
 use ::futures::{never::Never, pin_mut, FutureExt};
+use ::serde_json::{json, Value};
 use ::std::{
+    cmp, mem,
     net::{Ipv4Addr, SocketAddrV4},
     ops::{Deref, DerefMut},
     time::{Duration, Instant},
 };
 
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+const CACHE_LINE_SIZE_BYTES: usize = 64;
+
+// Upper bound for the size of a control block, which we keep for every connection. Think twice before raising it.
+const MAX_CONTROL_BLOCK_SIZE_BYTES: usize = 22 * CACHE_LINE_SIZE_BYTES;
+
 //======================================================================================================================
 // Structures
 //======================================================================================================================
@@ -60,17 +87,38 @@ pub enum State {
 //======================================================================================================================
 
 /// Transmission control block for representing our TCP connection.
+///
+/// We keep one of these for every connection, so its layout matters. Fields that we touch for every segment that we
+/// send or receive come first, with the small ones packed into the first cache line. Fields that we only touch when
+/// closing the connection or updating statistics come last. The declaration order is kept by `repr(C)`.
+#[repr(C)]
 pub struct ControlBlock {
+    // TCP Connection State.
+    state: State,
+
+    // Whether SACK was negotiated during the handshake (RFC 2018). We use it to report out-of-order and duplicate data,
+    // and to avoid retransmitting data that our peer reported.
+    sack_permitted: bool,
+
+    // The only piece of the TCP configuration that we need after the connection has been set up.
+    tx_checksum_offload: bool,
+
+    // Link address that we learned from the SYN of our peer, if any. We send to it directly instead of going through
+    // the ARP cache.
+    remote_link_addr: Option<MacAddress>,
+
     local: SocketAddrV4,
     remote: SocketAddrV4,
 
+    // State of the timestamps option (RFC 7323), if both ends agreed to use it. It is boxed to keep the control block
+    // within its size budget.
+    timestamps: Option<Box<Timestamps>>,
+
+    // ECN state (RFC 3168), if both ends agreed to use it.
+    ecn: Option<EcnState>,
+
     layer3_endpoint: SharedLayer3Endpoint,
     runtime: SharedDemiRuntime,
-    tcp_config: TcpConfig,
-    socket_options: TcpSocketOptions,
-
-    // TCP Connection State.
-    state: State,
 
     // Send Sequence Variables from RFC 793.
 
@@ -99,10 +147,32 @@ pub struct ControlBlock {
     receiver: Receiver,
 
     // Congestion control trait implementation we're currently using.
-    // TODO: Consider switching this to a static implementation to avoid V-table call overhead.
-    congestion_control_algorithm: Box<dyn congestion_control::CongestionControl>,
+    congestion_control_algorithm: CongestionControlAlgorithm,
+
+    // Cold fields from here on.
+    socket_options: TcpSocketOptions,
+
+    // Wakes up the keepalive coroutine when the keepalive options change.
+    keepalive_options_changed: SharedConditionVariable,
+
+    // Measures the time until our peer sends its first byte, if a listening socket established this connection. It is
+    // boxed because it is only needed for a short while.
+    first_byte_timer: Option<Box<FirstByteTimer>>,
+
+    // Most recent error that affected the connection and that the application has not read yet (SO_ERROR). It is boxed
+    // because most connections never run into one.
+    last_error: Option<Box<Fail>>,
+
+    // Per-connection statistics.
+    stats: Stats,
+
+    // Statistics at the time they were last reset, which snapshots are relative to. It is boxed because it is only
+    // needed when the application asks for snapshots.
+    stats_baseline: Box<StatsBaseline<Stats>>,
 }
 
+const _: () = assert!(mem::size_of::<ControlBlock>() <= MAX_CONTROL_BLOCK_SIZE_BYTES);
+
 #[derive(Clone)]
 pub struct SharedControlBlock(SharedObject<ControlBlock>);
 //======================================================================================================================
@@ -111,6 +181,7 @@ impl SharedControlBlock {
     pub fn new(
         local: SocketAddrV4,
         remote: SocketAddrV4,
+        remote_link_addr: Option<MacAddress>,
         layer3_endpoint: SharedLayer3Endpoint,
         runtime: SharedDemiRuntime,
         tcp_config: TcpConfig,
@@ -125,41 +196,104 @@ impl SharedControlBlock {
         send_window_size_frames: u32,
         send_window_scale_shift_bits: u8,
         sender_mss: usize,
+        // Whether both ends agreed to use SACK (RFC 2018).
+        sack_permitted: bool,
+        // State of the timestamps option (RFC 7323), if both ends agreed to use it.
+        timestamps: Option<Timestamps>,
+        // Whether both ends agreed to use ECN (RFC 3168).
+        ecn_capable: bool,
         congestion_control_algorithm_constructor: CongestionControlConstructor,
         congestion_control_options: Option<congestion_control::Options>,
         mut recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>,
     ) -> Self {
-        let sender: Sender = Sender::new(
+        // The timestamps option goes into every segment, so it takes room away from the data (RFC 6691).
+        let sender_mss: usize = match timestamps {
+            Some(_) => sender_mss - TIMESTAMPS_OPTION_SIZE,
+            None => sender_mss,
+        };
+        let mut sender: Sender = Sender::new(
             sender_initial_seq_no,
             send_window_size_frames,
             send_window_scale_shift_bits,
             sender_mss,
         );
-        let receiver: Receiver = Receiver::new(
+        if timestamps.is_some() && tcp_config.get_timestamp_rtt_samples() {
+            sender.enable_timestamp_rtt_samples();
+        }
+        if tcp_config.get_pmtu_blackhole_detection() {
+            sender.enable_pmtu_blackhole_detection();
+        }
+        if tcp_config.get_pacing() {
+            sender.enable_pacing();
+        }
+        sender.set_send_buffer_size(
+            tcp_config.get_send_buffer_size() as u32,
+            tcp_config.get_max_send_buffer_size() as u32,
+        );
+        let mut receiver: Receiver = Receiver::new(
             receive_initial_seq_no,
             receive_initial_seq_no,
             receive_ack_delay_timeout_secs,
+            tcp_config.get_ack_stretch_segments() as u32,
             receive_window_size_frames,
             receive_window_scale_shift_bits,
+            tcp_config.get_advertised_mss(),
+            default_socket_options.get_quickack(),
+            default_socket_options.get_delayed_ack(),
+            runtime.get_now(),
         );
-        let congestion_control_algorithm =
-            congestion_control_algorithm_constructor(sender_mss, sender_initial_seq_no, congestion_control_options);
+        receiver.set_urgent_data(tcp_config.get_urgent_data());
+        if tcp_config.get_max_receive_buffer_size() > 0 {
+            receiver.enable_autotuning(tcp_config.get_max_receive_buffer_size() as u32, runtime.get_now());
+        }
+        // Unless the caller asked for a specific value, the initial window comes from the configuration but is clamped
+        // to the window that our peer advertised.
+        let mut congestion_control_options: congestion_control::Options =
+            congestion_control_options.unwrap_or_default();
+        if congestion_control_options.get_int("initial_cwnd").is_none() {
+            let initial_cwnd: u32 = congestion_control::get_initial_cwnd(
+                tcp_config.get_initial_congestion_window(),
+                sender_mss,
+                send_window_size_frames,
+            );
+            congestion_control_options.insert_int("initial_cwnd".to_string(), initial_cwnd as i64);
+        }
+        let congestion_control_algorithm = congestion_control_algorithm_constructor(
+            sender_mss,
+            sender_initial_seq_no,
+            Some(congestion_control_options),
+        );
+        let now: Instant = runtime.get_now();
         let mut self_: Self = Self(SharedObject::<ControlBlock>::new(ControlBlock {
+            state: State::Established,
+            sack_permitted,
+            tx_checksum_offload: tcp_config.get_tx_checksum_offload(),
+            remote_link_addr,
             local,
             remote,
+            timestamps: timestamps.map(Box::new),
+            ecn: if ecn_capable {
+                Some(EcnState::new(sender_initial_seq_no))
+            } else {
+                None
+            },
             layer3_endpoint,
             runtime,
-            tcp_config,
-            socket_options: default_socket_options,
             sender,
-            state: State::Established,
             receiver,
             congestion_control_algorithm,
+            socket_options: default_socket_options,
+            keepalive_options_changed: SharedConditionVariable::default(),
+            first_byte_timer: None,
+            last_error: None,
+            stats: Stats::default(),
+            stats_baseline: Box::new(StatsBaseline::new(Stats::default(), now)),
         }));
         trace!("receive_queue size {:?}", recv_queue.len());
-        // Process all pending received packets while setting up the connection.
+        // Process all pending received packets while setting up the connection. Their ECN code points were not kept, but
+        // our peer only marks data as ECN-capable once the connection is established on its side.
         while let Some((_ipv4_addr, header, data)) = recv_queue.try_pop() {
-            self_.receive(header, data);
+            self_.receive(Ecn::NotEct, header, data);
         }
 
         self_
@@ -177,7 +311,152 @@ impl SharedControlBlock {
         self.runtime.get_now()
     }
 
-    pub fn receive(&mut self, tcp_hdr: TcpHeader, buf: DemiBuffer) {
+    pub fn get_stats(&self) -> Stats {
+        Stats {
+            congestion_control: self.congestion_control_algorithm.get_metrics(),
+            ..self.stats.clone()
+        }
+    }
+
+    /// Records [error] as the most recent error that affected the connection, replacing any that was not read yet.
+    pub fn set_last_error(&mut self, error: Fail) {
+        self.last_error = Some(Box::new(error));
+    }
+
+    /// Returns the most recent error that affected the connection and clears it, like SO_ERROR does.
+    pub fn take_last_error(&mut self) -> Option<Fail> {
+        self.last_error.take().map(|error| *error)
+    }
+
+    /// Returns the number of received bytes that the application has not popped yet (FIONREAD).
+    pub fn get_buffered_bytes(&self) -> usize {
+        self.receiver.get_buffered_bytes()
+    }
+
+    /// Returns a snapshot of the state of the connection (TCP_INFO).
+    pub fn get_tcp_info(&self) -> TcpInfo {
+        let state: TcpState = match self.state {
+            State::Established => TcpState::Established,
+            State::FinWait1 => TcpState::FinWait1,
+            State::FinWait2 => TcpState::FinWait2,
+            State::Closing => TcpState::Closing,
+            State::TimeWait => TcpState::TimeWait,
+            State::CloseWait => TcpState::CloseWait,
+            State::LastAck => TcpState::LastAck,
+            State::Closed => TcpState::Closed,
+        };
+        TcpInfo {
+            state,
+            smoothed_rtt: self.sender.get_srtt(),
+            rto: self.sender.get_rto(),
+            congestion_window: self.congestion_control_get_cwnd().get(),
+            slow_start_threshold: self.congestion_control_algorithm.get_ssthresh(),
+            retransmitted_segments: self.stats.retransmitted_segments,
+            bytes_in_flight: self.sender.get_bytes_in_flight(),
+            receive_window: self.receiver.get_receive_window_size(),
+            mss: self.sender.get_mss(),
+        }
+    }
+
+    /// Describes the connection for a state dump. Unless `scrub_payload` is set, this includes the data that is queued
+    /// for sending or reading.
+    pub fn dump_state(&self, scrub_payload: bool) -> Value {
+        json!({
+            "state": format!("{:?}", self.state),
+            "local": self.local.to_string(),
+            "remote": self.remote.to_string(),
+            "remote_link_addr": self.remote_link_addr.map(|addr| addr.to_canonical()),
+            "sack_permitted": self.sack_permitted,
+            "timestamps_recent": self.timestamps.as_ref().map(|timestamps| timestamps.get_recent()),
+            "ecn": self.ecn.is_some(),
+            "priority": format!("{:?}", self.socket_options.get_priority()),
+            "congestion_window": self.congestion_control_get_cwnd().get(),
+            "sender": self.sender.dump_state(scrub_payload),
+            "receiver": self.receiver.dump_state(scrub_payload),
+            "stats": self.get_stats().dump_state(),
+        })
+    }
+
+    /// Returns the change in the per-connection statistics since they were last reset, and resets them if [reset] is
+    /// set. The statistics that [get_stats] returns are not affected.
+    pub fn snapshot_stats(&mut self, reset: bool) -> StatsDelta<Stats> {
+        let current: Stats = self.get_stats();
+        let now: Instant = self.get_now();
+        self.stats_baseline.snapshot(current, now, reset)
+    }
+
+    pub fn get_mut_stats(&mut self) -> &mut Stats {
+        &mut self.stats
+    }
+
+    /// Changes the priority class of the segments that are sent from now on.
+    pub fn set_priority(&mut self, priority: PriorityClass) {
+        self.socket_options.set_priority(priority);
+    }
+
+    /// Changes when received segments are acknowledged right away, instead of delaying the ACK.
+    pub fn set_quickack(&mut self, quick_ack: QuickAck) {
+        self.socket_options.set_quickack(quick_ack);
+        self.receiver.set_quickack(quick_ack);
+    }
+
+    /// Changes how long and for how many segments ACKs for received data are delayed.
+    pub fn set_delayed_ack(&mut self, delayed_ack: DelayedAck) {
+        self.socket_options.set_delayed_ack(delayed_ack);
+        self.receiver.set_delayed_ack(delayed_ack);
+    }
+
+    /// Changes the number of bytes that have to be buffered before a pop completes.
+    pub fn set_receive_low_watermark(&mut self, bytes: usize) {
+        self.socket_options.set_receive_low_watermark(bytes);
+    }
+
+    /// Checks whether Nagle's algorithm coalesces small segments, which is the case unless TCP_NODELAY is set.
+    pub fn is_nagle_enabled(&self) -> bool {
+        !self.socket_options.get_nodelay()
+    }
+
+    pub fn is_corked(&self) -> bool {
+        self.socket_options.get_cork()
+    }
+
+    /// Holds back partial segments if [cork] is set. Otherwise, whatever was held back goes out right away.
+    pub fn set_cork(&mut self, cork: bool) {
+        self.socket_options.set_cork(cork);
+        self.sender.on_cork_changed();
+    }
+
+    /// Checks whether the congestion window survives idle periods while segments are paced.
+    pub fn keeps_cwnd_after_idle_when_paced(&self) -> bool {
+        self.socket_options.get_keep_cwnd_after_idle_when_paced()
+    }
+
+    pub fn set_keep_cwnd_after_idle_when_paced(&mut self, keep_cwnd: bool) {
+        self.socket_options.set_keep_cwnd_after_idle_when_paced(keep_cwnd);
+    }
+
+    /// Changes how long a close waits for our peer to acknowledge the remaining data before resetting the connection.
+    pub fn set_linger(&mut self, linger: Option<Duration>) {
+        self.socket_options.set_linger(linger);
+    }
+
+    /// Turns Nagle's algorithm off for segments that are sent from now on if [no_delay] is set, and back on otherwise.
+    pub fn set_nodelay(&mut self, no_delay: bool) {
+        self.socket_options.set_nodelay(no_delay);
+        self.sender.on_nodelay_changed();
+    }
+
+    /// Changes the keepalive settings of the connection to the ones in [options].
+    pub fn set_keepalive_options(&mut self, options: &TcpSocketOptions) {
+        self.socket_options.set_keepalive(options.get_keepalive());
+        self.socket_options.set_keepalive_idle(options.get_keepalive_idle());
+        self.socket_options
+            .set_keepalive_interval(options.get_keepalive_interval());
+        self.socket_options.set_keepalive_count(options.get_keepalive_count());
+        self.keepalive_options_changed.signal();
+    }
+
+    pub fn receive(&mut self, ecn: Ecn, tcp_hdr: TcpHeader, buf: DemiBuffer) {
         debug!(
             "{:?} Connection Receiving {} bytes + {:?}",
             self.state,
@@ -185,9 +464,36 @@ impl SharedControlBlock {
             tcp_hdr,
         );
 
+        // Find out whether the ACK for this segment should echo a congestion experienced mark.
+        if self.ecn.is_some() && ecn == Ecn::Ce {
+            self.stats.congestion_experienced_received += 1;
+        }
+        if let Some(ecn_state) = self.ecn.as_mut() {
+            ecn_state.on_receive(ecn, tcp_hdr.cwr);
+        }
+
         let cb: Self = self.clone();
         let now: Instant = self.runtime.get_now();
         self.receiver.receive(tcp_hdr, buf, cb, now);
+
+        if self.first_byte_timer.is_some() {
+            let receive_next: SeqNumber = self.receiver.receive_next_seq_no();
+            let fin_received: bool = matches!(
+                self.state,
+                State::CloseWait | State::Closing | State::TimeWait | State::LastAck
+            );
+            let done: bool = match self.first_byte_timer.as_mut() {
+                Some(timer) => timer.on_receive(now, receive_next, fin_received),
+                None => false,
+            };
+            if done {
+                self.first_byte_timer = None;
+            }
+        }
+    }
+
+    pub fn start_first_byte_timer(&mut self, timer: FirstByteTimer) {
+        self.first_byte_timer = Some(Box::new(timer));
     }
 
     pub fn congestion_control_watch_retransmit_now_flag(&self) -> SharedAsyncValue<bool> {
@@ -198,6 +504,21 @@ impl SharedControlBlock {
         self.congestion_control_algorithm.on_fast_retransmit()
     }
 
+    pub fn on_out_of_order_dropped(&mut self, num_segments: usize) {
+        self.stats.out_of_order_segments_dropped += num_segments as u64;
+    }
+
+    pub fn congestion_control_on_out_of_order_received(
+        &mut self,
+        receive_next: SeqNumber,
+        seg_start: SeqNumber,
+        seg_end: SeqNumber,
+    ) {
+        self.stats.out_of_order_segments_received += 1;
+        self.congestion_control_algorithm
+            .on_out_of_order_received(receive_next, seg_start, seg_end)
+    }
+
     pub fn congestion_control_on_rto(&mut self, send_unacknowledged: SeqNumber) {
         self.congestion_control_algorithm.on_rto(send_unacknowledged)
     }
@@ -206,8 +527,17 @@ impl SharedControlBlock {
         self.congestion_control_algorithm.on_send(rto, num_sent_bytes)
     }
 
-    pub fn congestion_control_on_cwnd_check_before_send(&mut self) {
-        self.congestion_control_algorithm.on_cwnd_check_before_send()
+    pub fn congestion_control_on_idle_restart(&mut self, now: Instant, rto: Duration, idle_time: Duration) {
+        self.congestion_control_algorithm.on_idle_restart(now, rto, idle_time)
+    }
+
+    pub fn congestion_control_on_app_limited(&mut self, bytes_in_flight: u32) {
+        self.congestion_control_algorithm.on_app_limited(bytes_in_flight)
+    }
+
+    pub fn congestion_control_on_cwnd_check_before_send(&mut self, rto: Duration, bytes_in_flight: u32) {
+        self.congestion_control_algorithm
+            .on_cwnd_check_before_send(rto, bytes_in_flight)
     }
 
     pub fn congestion_control_get_cwnd(&self) -> SharedAsyncValue<u32> {
@@ -218,6 +548,14 @@ impl SharedControlBlock {
         self.congestion_control_algorithm.get_limited_transmit_cwnd_increase()
     }
 
+    pub fn congestion_control_get_ssthresh(&self) -> u32 {
+        self.congestion_control_algorithm.get_ssthresh()
+    }
+
+    pub fn congestion_control_get_pacing_rate(&self) -> Option<u64> {
+        self.congestion_control_algorithm.get_pacing_rate()
+    }
+
     pub fn process_ack(&mut self, header: &TcpHeader, now: Instant) -> Result<(), Fail> {
         let send_unacknowledged: SeqNumber = self.sender.get_unacked_seq_no();
         let send_next: SeqNumber = self.sender.get_next_seq_no();
@@ -230,11 +568,57 @@ impl SharedControlBlock {
         self.congestion_control_algorithm
             .on_ack_received(rto, send_unacknowledged, send_next, header.ack_num);
 
+        // Our peer received a packet that a router marked because of congestion (RFC 3168 Section 6.1.2).
+        let reduce_cwnd: bool = match self.ecn.as_mut() {
+            Some(ecn_state) if header.ece => ecn_state.on_echo_received(header.ack_num, send_next),
+            _ => false,
+        };
+        if reduce_cwnd {
+            self.stats.ecn_window_reductions += 1;
+            self.congestion_control_algorithm.on_ecn_echo();
+        }
+
+        // Check whether this ACK shows that the last retransmission timeout was spurious, either because F-RTO (RFC 5682)
+        // detected it or because our peer reported all retransmissions as duplicates (RFC 3708).
+        let mut spurious_rto: bool = self.sender.process_frto(header.ack_num);
+        if let Some((begin, end)) = self.get_duplicate_sack(header) {
+            debug!("process_ack(): received DSACK for {:?}..{:?}", begin, end);
+            self.stats.duplicate_sacks_received += 1;
+            match self.sender.process_dsack(begin, end) {
+                Some(RetransmissionCause::Timeout) => spurious_rto = true,
+                Some(RetransmissionCause::FastRetransmit) => {
+                    debug!("process_ack(): spurious fast retransmit detected");
+                    self.stats.spurious_fast_retransmits += 1;
+                    self.congestion_control_algorithm.on_spurious_fast_retransmit();
+                },
+                None => (),
+            }
+        }
+        if spurious_rto {
+            debug!("process_ack(): spurious retransmission timeout detected");
+            self.stats.spurious_retransmission_timeouts += 1;
+            self.congestion_control_algorithm.on_spurious_rto();
+        }
+
         // Check whether this is an ack for data that we have sent.
         if header.ack_num <= send_next {
+            // With timestamps, the RTT is measured from the timestamp that our peer echoes (RFC 7323 Section 4).
+            let timestamp_rtt: Option<Duration> = match (self.timestamps.as_deref(), get_timestamps_option(header)) {
+                (Some(timestamps), Some((_, ts_ecr))) => timestamps.get_rtt_sample(now, ts_ecr),
+                _ => None,
+            };
             // Does not matter when we get this since the clock will not move between the beginning of packet
             // processing and now without a call to advance_clock.
-            self.sender.process_ack(header, now);
+            self.sender.process_ack(header, now, timestamp_rtt);
+            if let Some(rtt) = self.sender.take_rtt_sample() {
+                self.congestion_control_algorithm.on_rtt_sample(rtt);
+            }
+            if let Some(sample) = self.sender.take_rate_sample() {
+                self.congestion_control_algorithm.on_delivery_rate_sample(&sample);
+            }
+            self.process_sack(header, now);
+            let mut cb: Self = self.clone();
+            self.sender.detect_lost_segments(now, &mut cb);
         } else {
             // This segment acknowledges data we have yet to send!?  Send an ACK and drop the segment.
             // TODO: See RFC 5961, this could be a Blind Data Injection Attack.
@@ -246,10 +630,94 @@ impl SharedControlBlock {
         Ok(())
     }
 
+    /// Returns the duplicate SACK block in [header], if any. See RFC 2883 Section 4.
+    fn get_duplicate_sack(&self, header: &TcpHeader) -> Option<(SeqNumber, SeqNumber)> {
+        if !self.sack_permitted {
+            return None;
+        }
+        header.iter_options().find_map(|option| match option {
+            TcpOptions2::SelectiveAcknowlegement { num_sacks, sacks } if *num_sacks > 0 => {
+                if Self::is_duplicate_sack(header, *num_sacks, sacks) {
+                    Some((sacks[0].begin, sacks[0].end))
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        })
+    }
+
+    /// Checks whether the first SACK block in [header] is a DSACK. It is if it was already acknowledged or if it is part
+    /// of the second block.
+    fn is_duplicate_sack(header: &TcpHeader, num_sacks: usize, sacks: &[SelectiveAcknowlegement; 4]) -> bool {
+        let first: SelectiveAcknowlegement = sacks[0];
+        first.end <= header.ack_num || (num_sacks > 1 && sacks[1].begin <= first.begin && first.end <= sacks[1].end)
+    }
+
+    /// Hands the SACK blocks in [header] that report out-of-order data (RFC 2018) to the sender, so that it does not
+    /// retransmit the data that they cover.
+    fn process_sack(&mut self, header: &TcpHeader, now: Instant) {
+        if !self.sack_permitted {
+            return;
+        }
+        for option in header.iter_options() {
+            if let TcpOptions2::SelectiveAcknowlegement { num_sacks, sacks } = option {
+                let first: usize = if *num_sacks > 0 && Self::is_duplicate_sack(header, *num_sacks, sacks) {
+                    1
+                } else {
+                    0
+                };
+                let blocks: &[SelectiveAcknowlegement] = &sacks[first..*num_sacks];
+                self.stats.sack_blocks_received += blocks.len() as u64;
+                self.sender.process_sack(blocks, now);
+            }
+        }
+    }
+
+    /// Runs the PAWS test (RFC 7323 Section 5.3) on [header]. Returns false if the segment is an old duplicate and must
+    /// be dropped. Segments without a timestamp are accepted, as are resets.
+    pub fn check_paws(&mut self, header: &TcpHeader) -> bool {
+        match (self.timestamps.as_deref(), get_timestamps_option(header)) {
+            (Some(timestamps), Some((ts_val, _))) if !header.rst && !timestamps.is_acceptable(ts_val) => {
+                self.stats.paws_rejections += 1;
+                false
+            },
+            _ => true,
+        }
+    }
+
+    /// Updates the timestamp that we echo to our peer with the one in [header], which was found acceptable.
+    pub fn update_timestamps_recent(&mut self, header: &TcpHeader) {
+        if let (Some(timestamps), Some((ts_val, _))) = (self.timestamps.as_mut(), get_timestamps_option(header)) {
+            timestamps.update_recent(ts_val, header.seq_num);
+        }
+    }
+
     pub fn get_unacked_seq_no(&self) -> SeqNumber {
         self.sender.get_unacked_seq_no()
     }
 
+    /// Shrinks our segments to fit into datagrams of [mtu] bytes, as a router reported that it dropped the one that
+    /// carried the segment at [seq_no]. Reports about segments that are not in flight are stale or forged, so we ignore
+    /// them (RFC 5927 Section 4.1).
+    pub fn on_fragmentation_needed(&mut self, seq_no: SeqNumber, mtu: u16) {
+        if seq_no < self.sender.get_unacked_seq_no() || seq_no >= self.sender.get_next_seq_no() {
+            debug!("on_fragmentation_needed(): ignoring report about a segment that is not in flight");
+            return;
+        }
+        let options_size: usize = if self.timestamps.is_some() {
+            TIMESTAMPS_OPTION_SIZE
+        } else {
+            0
+        };
+        let headers_size: usize = IPV4_HEADER_MIN_SIZE as usize + MIN_TCP_HEADER_SIZE + options_size;
+        let mss: usize = cmp::max((mtu as usize).saturating_sub(headers_size), MIN_MSS - options_size);
+        let cause: String = format!("path MTU is smaller than our segments (mtu={:?})", mtu);
+        self.set_last_error(Fail::new(libc::EMSGSIZE, &cause));
+        let mut cb: Self = self.clone();
+        self.sender.on_fragmentation_needed(mss, &mut cb);
+    }
+
     /// Fetch a TCP header filling out various values based on our current state.
     /// TODO: Fix the "filling out various values based on our current state" part to actually do that correctly.
     pub fn tcp_header(&self) -> TcpHeader {
@@ -259,6 +727,7 @@ impl SharedControlBlock {
         // Note that once we reach a synchronized state we always include a valid acknowledgement number.
         header.ack = true;
         header.ack_num = self.receiver.receive_next_seq_no();
+        header.ece = self.ecn.map_or(false, |ecn| ecn.is_echo_pending());
 
         // Return this header.
         header
@@ -275,8 +744,82 @@ impl SharedControlBlock {
         self.emit(header, None);
     }
 
-    /// Transmit this message to our connected peer.
+    /// Sends an ACK to our peer right away, unless the background sender has data ready to go out within this poll,
+    /// which carries the ACK instead. In that case, the delayed ACK timer expires right after the background sender had
+    /// its turn, so that the ACK still goes out on its own if the data is held back after all.
+    pub fn send_ack_or_piggyback(&mut self, now: Instant) {
+        let cb: Self = self.clone();
+        if self.sender.has_data_ready_to_send(&cb) {
+            trace!("deferring ack to piggyback on data");
+            self.receiver.set_receive_ack_deadline(Some(now));
+        } else {
+            self.send_ack();
+        }
+    }
+
+    /// Transmit this message to our connected peer. A segment that cannot be transmitted is treated like a lost one.
     pub fn emit(&mut self, header: TcpHeader, body: Option<DemiBuffer>) {
+        let _ = self.transmit(header, body, Ecn::NotEct);
+    }
+
+    /// Transmits a segment with data that was not sent before. Only these segments are marked as ECN-capable, as pure
+    /// ACKs, window probes and retransmissions must not be (RFC 3168 Section 6.1.4 to 6.1.6).
+    /// Unlike [emit], this reports whether the lower layers took the segment, so that the sender can hold on to data
+    /// that could not be sent for lack of buffers.
+    pub fn emit_new_data(&mut self, mut header: TcpHeader, body: DemiBuffer) -> Result<(), Fail> {
+        let ecn: Ecn = match self.ecn.as_mut() {
+            Some(ecn_state) => {
+                header.cwr = ecn_state.take_cwr();
+                Ecn::Ect0
+            },
+            None => Ecn::NotEct,
+        };
+        let cwr: bool = header.cwr;
+        let result: Result<(), Fail> = self.transmit(header, Some(body), ecn);
+        if let (Err(_), Some(ecn_state)) = (result.as_ref(), self.ecn.as_mut()) {
+            // The CWR flag did not make it out, so the next new data segment has to carry it instead.
+            ecn_state.restore_cwr(cwr);
+        }
+        result
+    }
+
+    fn transmit(&mut self, mut header: TcpHeader, body: Option<DemiBuffer>, ecn: Ecn) -> Result<(), Fail> {
+        // Once both ends agreed to use timestamps, every segment carries one (RFC 7323 Section 3.2).
+        let now: Instant = self.runtime.get_now();
+        if let Some(timestamps) = self.timestamps.as_mut() {
+            header.push_option(timestamps.get_option(now));
+            timestamps.on_ack_sent(header.ack_num);
+        }
+
+        // Report duplicate data (RFC 2883) and out-of-order data (RFC 2018) that we have received. We only do this on pure
+        // ACKs, so that the SACK option does not have to fit into the same segment as a full MSS of data.
+        if self.sack_permitted && body.is_none() {
+            let mut sacks: [SelectiveAcknowlegement; 4] = [SelectiveAcknowlegement {
+                begin: header.ack_num,
+                end: header.ack_num,
+            }; 4];
+            let mut num_sacks: usize = 0;
+            let duplicate_block: Option<(SeqNumber, SeqNumber)> = self.receiver.take_duplicate_block();
+            if let Some((begin, end)) = duplicate_block {
+                sacks[0] = SelectiveAcknowlegement { begin, end };
+                num_sacks += 1;
+                self.stats.duplicate_sacks_sent += 1;
+            }
+            // Only three blocks fit next to the timestamps option.
+            let max_sacks: usize = if self.timestamps.is_some() { 3 } else { 4 };
+            // A duplicate block is followed by the out-of-order block that it is part of, if any.
+            let num_blocks: usize = self.receiver.get_sack_blocks(
+                duplicate_block.map(|(begin, _)| begin),
+                &mut sacks[num_sacks..max_sacks],
+            );
+            self.stats.sack_blocks_sent += num_blocks as u64;
+            num_sacks += num_blocks;
+            if num_sacks > 0 {
+                header.push_option(TcpOptions2::SelectiveAcknowlegement { num_sacks, sacks });
+            }
+        }
+
+        let has_data: bool = body.is_some();
         // Only perform this debug print in debug builds.  debug_assertions is compiler set in non-optimized builds.
         let mut pkt = match body {
             Some(body) => {
@@ -292,36 +835,150 @@ impl SharedControlBlock {
         // This routine should only ever be called to send TCP segments that contain a valid ACK value.
         debug_assert!(header.ack);
 
+        let local_ipv4_addr: Ipv4Addr = self.local.ip().clone();
         let remote_ipv4_addr: Ipv4Addr = self.remote.ip().clone();
-        header.serialize_and_attach(
-            &mut pkt,
-            self.local.ip(),
-            self.remote.ip(),
-            self.tcp_config.get_tx_checksum_offload(),
-        );
+        header.serialize_and_attach(&mut pkt, self.local.ip(), self.remote.ip(), self.tx_checksum_offload);
 
         // Call lower L3 layer to send the segment.
-        if let Err(e) = self
-            .layer3_endpoint
-            .transmit_tcp_packet_nonblocking(remote_ipv4_addr, pkt)
-        {
+        let priority: PriorityClass = self.socket_options.get_priority();
+        let result: Result<(), Fail> = match self.remote_link_addr {
+            Some(remote_link_addr) => self.layer3_endpoint.transmit_packet(
+                local_ipv4_addr,
+                remote_ipv4_addr,
+                remote_link_addr,
+                IpProtocol::TCP,
+                priority,
+                ecn,
+                pkt,
+            ),
+            None => self.layer3_endpoint.transmit_tcp_packet_nonblocking(
+                local_ipv4_addr,
+                remote_ipv4_addr,
+                priority,
+                ecn,
+                pkt,
+            ),
+        };
+        if let Err(e) = result {
             warn!("could not emit packet: {:?}", e);
-            return;
+            return Err(e);
         }
 
         // Post-send operations follow.
         // Review: We perform these after the send, in order to keep send latency as low as possible.
 
+        if has_data && self.receiver.is_ack_pending() {
+            self.stats.piggybacked_acks += 1;
+        }
         // Since we sent an ACK, cancel any outstanding delayed ACK request.
-        self.receiver.set_receive_ack_deadline(None);
+        self.receiver.on_ack_sent();
+        Ok(())
+    }
+    /// Sends a keepalive probe (RFC 1122 Section 4.2.3.6). It carries a sequence number that our peer has already
+    /// acknowledged, so our peer answers it with an ACK.
+    fn send_keepalive_probe(&mut self) {
+        let mut header: TcpHeader = self.tcp_header();
+        header.seq_num = self.sender.get_next_seq_no() - SeqNumber::from(1);
+        self.stats.keepalive_probes_sent += 1;
+        self.emit(header, None);
+    }
+
+    /// Gives up on the connection. We reset it, so that our peer does not hold on to it either, and fail the reads and
+    /// writes of the application with [errno].
+    pub fn abort(&mut self, errno: libc::c_int) {
+        if self.state == State::Closed {
+            return;
+        }
+        let mut header: TcpHeader = self.tcp_header();
+        header.seq_num = self.sender.get_next_seq_no();
+        header.rst = true;
+        self.emit(header, None);
+        self.tear_down(errno);
+    }
+
+    /// Our peer reset the connection, so we fail the reads and writes of the application with ECONNRESET.
+    pub fn on_reset(&mut self) {
+        self.tear_down(libc::ECONNRESET);
+    }
+
+    fn tear_down(&mut self, errno: libc::c_int) {
+        self.state = State::Closed;
+        self.sender.discard();
+        self.receiver.abort(errno);
+        if let Some(mut timer) = self.first_byte_timer.take() {
+            timer.on_abort();
+        }
     }
+
     pub async fn push(&mut self, buf: DemiBuffer) -> Result<(), Fail> {
+        if let Some(errno) = self.receiver.get_abort_errno() {
+            let cause: String = format!("connection aborted");
+            warn!("push(): {}", cause);
+            return Err(Fail::new(errno, &cause));
+        }
         let cb: Self = self.clone();
         self.sender.push(buf, cb).await
     }
 
+    /// Queues `buf` for sending without waiting for room in the send buffer or for its acknowledgement. Fails with EAGAIN
+    /// if the send buffer is full.
+    pub fn try_push(&mut self, buf: DemiBuffer) -> Result<(), Fail> {
+        if let Some(errno) = self.receiver.get_abort_errno() {
+            let cause: String = format!("connection aborted");
+            warn!("try_push(): {}", cause);
+            return Err(Fail::new(errno, &cause));
+        }
+        let cb: Self = self.clone();
+        self.sender.try_push(buf, cb)
+    }
+
+    pub async fn wait_for_writable(&mut self, min_bytes: usize) -> Result<(), Fail> {
+        let cb: Self = self.clone();
+        self.sender.wait_for_writable(min_bytes, cb).await
+    }
+
     pub async fn pop(&mut self, size: Option<usize>) -> Result<DemiBuffer, Fail> {
-        self.receiver.pop(size).await
+        let low_watermark: usize = self.socket_options.get_receive_low_watermark();
+        let buf: DemiBuffer = self.receiver.pop(size, low_watermark).await?;
+        let now: Instant = self.get_now();
+        self.receiver.autotune(now);
+        Ok(buf)
+    }
+
+    /// Pops exactly `size` bytes, coalescing as many buffers as it takes into a single one (MSG_WAITALL). Returns fewer
+    /// bytes only if the connection is closed or fails before all of them arrive, in which case the next pop reports
+    /// the end of the connection or the failure.
+    pub async fn pop_all(&mut self, size: usize) -> Result<DemiBuffer, Fail> {
+        if size > u16::MAX as usize {
+            let cause: String = format!("cannot wait for more bytes than a buffer can hold (size={:?})", size);
+            error!("pop_all(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        let first: DemiBuffer = self.pop(Some(size)).await?;
+        if first.len() == size || first.is_empty() {
+            return Ok(first);
+        }
+
+        let mut buf: DemiBuffer = DemiBuffer::new(size as u16);
+        buf[..first.len()].copy_from_slice(&first);
+        let mut filled: usize = first.len();
+        while filled < size {
+            let next: DemiBuffer = match self.pop(Some(size - filled)).await {
+                Ok(next) if !next.is_empty() => next,
+                // Hand out what arrived so far. Both the end-of-file marker and the failure stay in the receive queue.
+                _ => break,
+            };
+            buf[filled..filled + next.len()].copy_from_slice(&next);
+            filled += next.len();
+        }
+        buf.trim(size - filled)?;
+        Ok(buf)
+    }
+
+    /// Returns data like [Self::pop] does, but leaves it in the receive queue.
+    pub async fn peek(&mut self, size: Option<usize>) -> Result<DemiBuffer, Fail> {
+        let low_watermark: usize = self.socket_options.get_receive_low_watermark();
+        self.receiver.peek(size, low_watermark).await
     }
 
     pub fn process_fin(&mut self) {
@@ -337,12 +994,59 @@ impl SharedControlBlock {
     pub fn get_state(&self) -> State {
         self.state
     }
-    // This coroutine runs the close protocol.
-    pub async fn close(&mut self) -> Result<(), Fail> {
-        // Assert we are in a valid state and move to new state.
+
+    /// Shuts down the send direction of the connection (SHUT_WR). Our FIN goes out behind the data that was pushed
+    /// before, while we keep receiving until our peer closes its side as well.
+    pub fn shutdown_write(&mut self) -> Result<(), Fail> {
+        if self.sender.is_fin_requested() {
+            return Ok(());
+        }
+        let state: State = match self.state {
+            State::Established => State::FinWait1,
+            State::CloseWait => State::LastAck,
+            state => {
+                let cause: String = format!("connection is not open (state={:?})", state);
+                error!("shutdown_write(): {}", cause);
+                return Err(Fail::new(libc::ENOTCONN, &cause));
+            },
+        };
+        self.sender.push_fin()?;
+        self.state = state;
+        Ok(())
+    }
+
+    /// Shuts down the receive direction of the connection (SHUT_RD). Reads return end-of-file from now on.
+    pub fn shutdown_read(&mut self) {
+        self.receiver.shutdown_read();
+    }
+
+    // This coroutine runs the close protocol. Once our FIN is acknowledged, we wait for the FIN of our peer for at most
+    // [fin_timeout]. Sockets that linger wait for the acknowledgement of our FIN for at most their linger timeout, and
+    // reset the connection if it runs out. A linger timeout of zero resets the connection right away (SO_LINGER).
+    pub async fn close(&mut self, fin_timeout: Duration) -> Result<(), Fail> {
+        // A connection that was reset has nothing left to shut down.
+        if self.state == State::Closed && self.receiver.get_abort_errno().is_some() {
+            return Ok(());
+        }
+        if self.socket_options.get_linger() == Some(Duration::ZERO) {
+            debug!("close(): resetting connection, as the socket does not linger");
+            self.abort(libc::ECONNABORTED);
+            return Ok(());
+        }
+        // Send our FIN, unless a shutdown of the send direction did so already.
+        if !self.sender.is_fin_requested() {
+            match self.state {
+                State::Established | State::CloseWait => self.shutdown_write()?,
+                _ => {
+                    let cause: String = format!("socket is already closing");
+                    error!("close(): {}", cause);
+                    return Err(Fail::new(libc::EBADF, &cause));
+                },
+            }
+        }
         match self.state {
-            State::Established => self.local_close().await,
-            State::CloseWait => self.remote_already_closed().await,
+            State::FinWait1 | State::Closing => self.local_close(fin_timeout).await,
+            State::LastAck => self.remote_already_closed().await,
             _ => {
                 let cause: String = format!("socket is already closing");
                 error!("close(): {}", cause);
@@ -351,40 +1055,91 @@ impl SharedControlBlock {
         }
     }
 
-    async fn local_close(&mut self) -> Result<(), Fail> {
-        // 1. Start close protocol by setting state and sending FIN.
-        self.state = State::FinWait1;
-        self.sender.push_fin_and_wait_for_ack().await?;
+    async fn local_close(&mut self, fin_timeout: Duration) -> Result<(), Fail> {
+        // 1. Wait until our peer acknowledges our FIN.
+        if !self.wait_for_fin_ack().await? {
+            return Ok(());
+        }
 
         // 2. Got ACK to our FIN. Check if we also received a FIN from remote in the meantime.
         let state: State = self.state;
         match state {
             State::FinWait1 => {
                 self.state = State::FinWait2;
-                // Haven't received a FIN yet from remote, so wait.
-                self.receiver.wait_for_fin().await?;
+                // Haven't received a FIN yet from remote, so wait. A peer that never sends one would hold on to this
+                // connection forever, so we reset it and let go of it after a while, like Linux does.
+                match conditional_yield_with_timeout(self.receiver.wait_for_fin(), fin_timeout).await {
+                    Ok(result) => result?,
+                    Err(_) => {
+                        let cause: String = format!("no FIN from peer (timeout={:?})", fin_timeout);
+                        warn!("local_close(): {}", cause);
+                        self.set_last_error(Fail::new(libc::ETIMEDOUT, &cause));
+                        self.abort(libc::ETIMEDOUT);
+                        return Ok(());
+                    },
+                }
             },
             State::Closing => self.state = State::TimeWait,
             state => unreachable!("Cannot be in any other state at this point: {:?}", state),
         };
-        // 3. TIMED_WAIT
+        // 3. TIME_WAIT. The connection is handed over to the TIME_WAIT table of the peer from here on, so that it does
+        // not hold on to this coroutine.
         debug_assert_eq!(self.state, State::TimeWait);
-        trace!("socket options: {:?}", self.socket_options.get_linger());
-        let timeout: Duration = self.socket_options.get_linger().unwrap_or(MSL * 2);
-        yield_with_timeout(timeout).await;
-        self.state = State::Closed;
         Ok(())
     }
 
+    /// Returns what the connection needs to remember while it is in TIME_WAIT, if it got there. It stays there for the
+    /// linger timeout of the socket, or for twice the maximum segment lifetime [msl] if there is none.
+    pub fn get_time_wait_entry(&self, msl: Duration) -> Option<TimeWaitEntry> {
+        if self.state != State::TimeWait {
+            return None;
+        }
+        trace!("socket options: {:?}", self.socket_options.get_linger());
+        let duration: Duration = self.socket_options.get_linger().unwrap_or(msl * 2);
+        Some(TimeWaitEntry::new(
+            self.sender.get_next_seq_no(),
+            self.receiver.receive_next_seq_no(),
+            self.receiver.hdr_window_size(),
+            self.timestamps.as_deref().copied(),
+            duration,
+            self.get_now(),
+        ))
+    }
+
     async fn remote_already_closed(&mut self) -> Result<(), Fail> {
-        // 0. Move state forward
-        self.state = State::LastAck;
-        // 1. Send FIN and wait for ack before closing.
-        self.sender.push_fin_and_wait_for_ack().await?;
-        self.state = State::Closed;
+        // Wait for the ACK of our FIN before closing.
+        if self.wait_for_fin_ack().await? {
+            self.state = State::Closed;
+        }
         Ok(())
     }
 
+    // Waits until our peer acknowledges our FIN, and with it all data that we sent before. If the socket lingers and the
+    // linger timeout runs out first, we reset the connection instead and return false. We also return false if our peer
+    // resets the connection while we wait.
+    async fn wait_for_fin_ack(&mut self) -> Result<bool, Fail> {
+        let linger: Duration = match self.socket_options.get_linger() {
+            Some(linger) => linger,
+            None => {
+                self.sender.wait_for_fin_ack().await?;
+                return Ok(self.state != State::Closed);
+            },
+        };
+        match conditional_yield_with_timeout(self.sender.wait_for_fin_ack(), linger).await {
+            Ok(result) => {
+                result?;
+                Ok(self.state != State::Closed)
+            },
+            Err(_) => {
+                let cause: String = format!("data left unacknowledged after linger timeout (timeout={:?})", linger);
+                warn!("wait_for_fin_ack(): {}", cause);
+                self.set_last_error(Fail::new(libc::ETIMEDOUT, &cause));
+                self.abort(libc::ETIMEDOUT);
+                Ok(false)
+            },
+        }
+    }
+
     pub async fn background(&self) {
         let acknowledger = async_timer!(
             "tcp::established::background::acknowledger",
@@ -403,7 +1158,16 @@ impl SharedControlBlock {
         let sender = async_timer!("tcp::established::background::sender", self.clone().background_sender()).fuse();
         pin_mut!(sender);
 
-        let r = futures::join!(acknowledger, retransmitter, sender);
+        let keepalive = async_timer!(
+            "tcp::established::background::keepalive",
+            self.clone().background_keepalive()
+        )
+        .fuse();
+        pin_mut!(keepalive);
+
+        // The sender goes ahead of the acknowledger, so that data that is ready to go out carries an ACK that was
+        // deferred to piggyback on it before the delayed ACK timer sends the ACK on its own.
+        let r = futures::join!(sender, acknowledger, retransmitter, keepalive);
         error!("Connection terminated: {:?}", r);
     }
 
@@ -421,6 +1185,64 @@ impl SharedControlBlock {
         let cb: Self = self.clone();
         self.receiver.acknowledger(cb).await
     }
+
+    /// Probes our peer once the connection has been idle for the keepalive time and gives up on the connection when
+    /// the configured number of probes in a row goes unanswered.
+    pub async fn background_keepalive(mut self) -> Result<Never, Fail> {
+        let mut options_changed: SharedConditionVariable = self.keepalive_options_changed.clone();
+        // Time since which we have neither heard from our peer nor had data in flight.
+        let mut idle_since: Instant = self.get_now();
+        // Number of probes that our peer has not answered yet and the time at which we sent the last one.
+        let mut unanswered_probes: u32 = 0;
+        let mut last_probe_time: Instant = idle_since;
+        loop {
+            // Anything that our peer sends shows that it is still there.
+            let last_receive_time: Instant = self.receiver.get_last_receive_time();
+            if last_receive_time > idle_since {
+                idle_since = last_receive_time;
+                unanswered_probes = 0;
+            }
+
+            let deadline: Option<Instant> = if !self.socket_options.is_keepalive_enabled() {
+                None
+            } else if unanswered_probes == 0 {
+                Some(idle_since + self.socket_options.get_keepalive_idle())
+            } else {
+                Some(last_probe_time + self.socket_options.get_keepalive_interval())
+            };
+            match conditional_yield_until(options_changed.wait(), deadline).await {
+                // The options changed, so recompute the deadline.
+                Ok(()) => continue,
+                Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT => (),
+                Err(e) => return Err(e),
+            }
+
+            match self.state {
+                State::Established | State::CloseWait => (),
+                _ => return Err(Fail::new(libc::ECONNRESET, "connection closed")),
+            }
+            if self.receiver.get_last_receive_time() > idle_since {
+                continue;
+            }
+            let now: Instant = self.get_now();
+            // The retransmission timer takes care of connections with data in flight.
+            if self.sender.get_unacked_seq_no() != self.sender.get_next_seq_no() {
+                idle_since = now;
+                unanswered_probes = 0;
+                continue;
+            }
+            if unanswered_probes >= self.socket_options.get_keepalive_count() {
+                let cause: String = format!("{} keepalive probes went unanswered", unanswered_probes);
+                warn!("background_keepalive(): {}", cause);
+                self.set_last_error(Fail::new(libc::ETIMEDOUT, &cause));
+                self.abort(libc::ETIMEDOUT);
+                return Err(Fail::new(libc::ETIMEDOUT, &cause));
+            }
+            self.send_keepalive_probe();
+            unanswered_probes += 1;
+            last_probe_time = now;
+        }
+    }
 }
 
 //======================================================================================================================
@@ -440,3 +1262,41 @@ impl DerefMut for SharedControlBlock {
         self.0.deref_mut()
     }
 }
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod test {
+    use super::{ControlBlock, CACHE_LINE_SIZE_BYTES, MAX_CONTROL_BLOCK_SIZE_BYTES};
+    use crate::runtime::SharedDemiRuntime;
+    use ::anyhow::Result;
+    use ::std::mem::{self, offset_of};
+
+    /// Tests if `ControlBlock` does not grow past its size budget.
+    #[test]
+    fn test_size_control_block() -> Result<()> {
+        crate::ensure_eq!(mem::size_of::<ControlBlock>() <= MAX_CONTROL_BLOCK_SIZE_BYTES, true);
+        Ok(())
+    }
+
+    /// Tests if the fields that we touch for every segment come before the ones that we seldom touch.
+    #[test]
+    fn test_layout_control_block() -> Result<()> {
+        // The small per-segment fields share the first cache line.
+        crate::ensure_eq!(
+            offset_of!(ControlBlock, runtime) + mem::size_of::<SharedDemiRuntime>() <= CACHE_LINE_SIZE_BYTES,
+            true
+        );
+        crate::ensure_eq!(
+            offset_of!(ControlBlock, socket_options) > offset_of!(ControlBlock, congestion_control_algorithm),
+            true
+        );
+        crate::ensure_eq!(
+            offset_of!(ControlBlock, stats) > offset_of!(ControlBlock, congestion_control_algorithm),
+            true
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Delivery rate estimation, as described in draft-cheng-iccrg-delivery-rate-estimation-02. Every transmitted segment
+// records how much data had been delivered when it was sent. When that segment is acknowledged, the data delivered in
+// between, divided by the time that passed, gives a sample of the delivery rate. Rate-based congestion control (e.g.
+// BBR) builds its model of the path out of these samples.
+// TODO: Also take samples from segments that are SACKed, not only from those that are cumulatively acknowledged.
+
+use ::std::{
+    cmp::max,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Delivery state of the connection at the moment that a segment was (re)transmitted.
+#[derive(Clone, Copy, Debug)]
+pub struct DeliveryState {
+    // Bytes delivered when the segment was sent.
+    delivered: u64,
+    // Time at which the last delivery before the segment was sent happened.
+    delivered_time: Instant,
+    // Send time of the segment that started the send interval of this sample.
+    first_sent_time: Instant,
+    // Send time of the segment.
+    sent_time: Instant,
+    // Whether the application was not sending fast enough to fill the pipe when the segment was sent.
+    is_app_limited: bool,
+}
+
+/// Delivery rate sample taken from an ACK.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateSample {
+    /// Bytes delivered when the acknowledged segment was sent.
+    pub prior_delivered: u64,
+    /// Bytes delivered over the sample interval.
+    pub delivered: u64,
+    /// Length of the sample interval.
+    pub interval: Duration,
+    /// Bytes newly acknowledged by the ACK that produced this sample.
+    pub newly_acked: u32,
+    /// Whether the sample was taken while the application was not sending fast enough to fill the pipe. Such samples
+    /// underestimate the bandwidth of the path.
+    pub is_app_limited: bool,
+}
+
+/// Tracks the delivery state of a connection.
+#[derive(Debug)]
+pub struct DeliveryRateEstimator {
+    // Total bytes delivered so far.
+    delivered: u64,
+    // Time of the last delivery.
+    delivered_time: Instant,
+    // Send time of the segment that starts the current send interval.
+    first_sent_time: Instant,
+    // If non-zero, the amount of delivered bytes at which the current application-limited period ends.
+    app_limited_until: u64,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl RateSample {
+    /// Returns the delivery rate of this sample in bytes per second.
+    pub fn delivery_rate(&self) -> Option<f64> {
+        if self.interval.is_zero() {
+            None
+        } else {
+            Some(self.delivered as f64 / self.interval.as_secs_f64())
+        }
+    }
+}
+
+impl DeliveryRateEstimator {
+    /// Creates an estimator. The timestamps are reset when the first segment is sent, so [now] only matters until then.
+    pub fn new(now: Instant) -> Self {
+        Self {
+            delivered: 0,
+            delivered_time: now,
+            first_sent_time: now,
+            app_limited_until: 0,
+        }
+    }
+
+    /// Records the delivery state for a segment that is (re)transmitted at [now] while [bytes_in_flight] bytes are
+    /// outstanding.
+    pub fn on_send(&mut self, now: Instant, bytes_in_flight: u32) -> DeliveryState {
+        // Start a new send interval when restarting from idle, so that the idle time is not counted.
+        if bytes_in_flight == 0 {
+            self.first_sent_time = now;
+            self.delivered_time = now;
+        }
+        DeliveryState {
+            delivered: self.delivered,
+            delivered_time: self.delivered_time,
+            first_sent_time: self.first_sent_time,
+            sent_time: now,
+            is_app_limited: self.app_limited_until != 0,
+        }
+    }
+
+    /// Marks the connection as application-limited until the data that is currently in flight has been delivered.
+    pub fn on_app_limited(&mut self, bytes_in_flight: u32) {
+        self.app_limited_until = max(self.delivered + bytes_in_flight as u64, 1);
+    }
+
+    /// Takes a sample from an ACK that newly acknowledges [newly_acked] bytes at [now]. [newest] is the delivery state of
+    /// the most recently sent segment that the ACK covers.
+    pub fn on_ack(&mut self, now: Instant, newly_acked: u32, newest: &DeliveryState) -> RateSample {
+        self.delivered += newly_acked as u64;
+        self.delivered_time = now;
+        if self.app_limited_until != 0 && self.delivered > self.app_limited_until {
+            self.app_limited_until = 0;
+        }
+        // The next sample starts where this one ends.
+        self.first_sent_time = newest.sent_time;
+
+        // Use the longer of the send and ACK intervals, so that ACK compression does not inflate the rate.
+        let send_elapsed: Duration = newest.sent_time.saturating_duration_since(newest.first_sent_time);
+        let ack_elapsed: Duration = now.saturating_duration_since(newest.delivered_time);
+        RateSample {
+            prior_delivered: newest.delivered,
+            delivered: self.delivered - newest.delivered,
+            interval: max(send_elapsed, ack_elapsed),
+            newly_acked,
+            is_app_limited: newest.is_app_limited,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn get_delivered(&self) -> u64 {
+        self.delivered
+    }
+}
+
+impl DeliveryState {
+    pub fn get_sent_time(&self) -> Instant {
+        self.sent_time
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{DeliveryRateEstimator, DeliveryState, RateSample};
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    const MSS: u32 = 1000;
+
+    #[test]
+    fn test_delivery_rate_of_steady_flow() -> Result<()> {
+        let start: Instant = Instant::now();
+        let rtt: Duration = Duration::from_millis(100);
+        let mut estimator: DeliveryRateEstimator = DeliveryRateEstimator::new(start);
+
+        // Send ten segments back-to-back, then get them acknowledged one RTT later.
+        let sent: Vec<DeliveryState> = (0..10).map(|i| estimator.on_send(start, i * MSS)).collect();
+        let mut sample: Option<RateSample> = None;
+        for state in sent.iter() {
+            sample = Some(estimator.on_ack(start + rtt, MSS, state));
+        }
+        let sample: RateSample = sample.unwrap();
+        crate::ensure_eq!(sample.prior_delivered, 0);
+        crate::ensure_eq!(sample.delivered, 10 * MSS as u64);
+        crate::ensure_eq!(sample.interval, rtt);
+        crate::ensure_eq!(sample.delivery_rate(), Some(100_000.0));
+        crate::ensure_eq!(estimator.get_delivered(), 10 * MSS as u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_app_limited_samples() -> Result<()> {
+        let start: Instant = Instant::now();
+        let mut estimator: DeliveryRateEstimator = DeliveryRateEstimator::new(start);
+
+        // The application runs out of data after the first segment.
+        let first: DeliveryState = estimator.on_send(start, 0);
+        estimator.on_app_limited(MSS);
+        let second: DeliveryState = estimator.on_send(start, MSS);
+
+        let now: Instant = start + Duration::from_millis(10);
+        crate::ensure_eq!(estimator.on_ack(now, MSS, &first).is_app_limited, false);
+        crate::ensure_eq!(estimator.on_ack(now, MSS, &second).is_app_limited, true);
+
+        // The application-limited period ends once the data that was in flight has been delivered.
+        let third: DeliveryState = estimator.on_send(now, 0);
+        crate::ensure_eq!(estimator.on_ack(now, MSS, &third).is_app_limited, false);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restart_from_idle() -> Result<()> {
+        let start: Instant = Instant::now();
+        let mut estimator: DeliveryRateEstimator = DeliveryRateEstimator::new(start);
+        let first: DeliveryState = estimator.on_send(start, 0);
+        estimator.on_ack(start + Duration::from_millis(10), MSS, &first);
+
+        // The idle time between the ACK and the next send is not part of the next sample.
+        let restart: Instant = start + Duration::from_secs(10);
+        let second: DeliveryState = estimator.on_send(restart, 0);
+        let sample: RateSample = estimator.on_ack(restart + Duration::from_millis(10), MSS, &second);
+        crate::ensure_eq!(sample.interval, Duration::from_millis(10));
+        crate::ensure_eq!(sample.delivered, MSS as u64);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// Explicit Congestion Notification for TCP, as described in RFC 3168. Routers on the path may mark packets of an
+// ECN-capable connection with congestion experienced (CE) instead of dropping them. The receiver echoes the mark back
+// with the ECE flag on its ACKs until the sender confirms with the CWR flag that it reduced its congestion window.
+
+use crate::inetstack::protocols::{layer3::Ecn, layer4::tcp::SeqNumber};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// ECN state of a connection whose ends agreed to use ECN during the handshake.
+#[derive(Clone, Copy, Debug)]
+pub struct EcnState {
+    // Whether our ACKs carry the ECE flag, because we received a CE mark and our peer did not send CWR since.
+    echo_pending: bool,
+    // Whether our next new data segment carries the CWR flag, because we reduced the congestion window.
+    cwr_pending: bool,
+    // SND.NXT when we last reduced the congestion window. Echoes for data up to here were caused by the same round of
+    // congestion, so we only react to the first one (RFC 3168 Section 6.1.2).
+    recover: SeqNumber,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl EcnState {
+    pub fn new(send_initial_seq_no: SeqNumber) -> Self {
+        Self {
+            echo_pending: false,
+            cwr_pending: false,
+            recover: send_initial_seq_no,
+        }
+    }
+
+    /// Processes the [ecn] code point and the [cwr] flag of a received segment.
+    pub fn on_receive(&mut self, ecn: Ecn, cwr: bool) {
+        if cwr {
+            self.echo_pending = false;
+        }
+        // A segment may carry both CWR and a new CE mark, in which case we keep echoing.
+        if ecn == Ecn::Ce {
+            self.echo_pending = true;
+        }
+    }
+
+    pub fn is_echo_pending(&self) -> bool {
+        self.echo_pending
+    }
+
+    /// Processes an ACK for [ack_num] that carries the ECE flag, while SND.NXT is [send_next]. Returns whether the
+    /// congestion window should be reduced.
+    pub fn on_echo_received(&mut self, ack_num: SeqNumber, send_next: SeqNumber) -> bool {
+        if ack_num <= self.recover {
+            return false;
+        }
+        self.recover = send_next;
+        self.cwr_pending = true;
+        true
+    }
+
+    /// Returns whether the next new data segment should carry the CWR flag.
+    pub fn take_cwr(&mut self) -> bool {
+        let cwr: bool = self.cwr_pending;
+        self.cwr_pending = false;
+        cwr
+    }
+
+    /// Puts back the CWR flag that [take_cwr] returned, if the segment that should have carried it was not sent.
+    pub fn restore_cwr(&mut self, cwr: bool) {
+        self.cwr_pending |= cwr;
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::EcnState;
+    use crate::inetstack::protocols::{layer3::Ecn, layer4::tcp::SeqNumber};
+    use ::anyhow::Result;
+
+    #[test]
+    fn test_echo_until_cwr() -> Result<()> {
+        let mut ecn: EcnState = EcnState::new(SeqNumber::from(0));
+        ecn.on_receive(Ecn::Ect0, false);
+        crate::ensure_eq!(ecn.is_echo_pending(), false);
+
+        // We keep echoing a CE mark, even on segments that were not marked themselves, until our peer sends CWR.
+        ecn.on_receive(Ecn::Ce, false);
+        crate::ensure_eq!(ecn.is_echo_pending(), true);
+        ecn.on_receive(Ecn::Ect0, false);
+        crate::ensure_eq!(ecn.is_echo_pending(), true);
+        ecn.on_receive(Ecn::Ect0, true);
+        crate::ensure_eq!(ecn.is_echo_pending(), false);
+
+        // A new CE mark on the CWR segment itself is echoed again.
+        ecn.on_receive(Ecn::Ce, true);
+        crate::ensure_eq!(ecn.is_echo_pending(), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_react_once_per_window() -> Result<()> {
+        let mut ecn: EcnState = EcnState::new(SeqNumber::from(0));
+
+        // The first echo reduces the window and makes the next data segment carry CWR.
+        crate::ensure_eq!(
+            ecn.on_echo_received(SeqNumber::from(1000), SeqNumber::from(10000)),
+            true
+        );
+        crate::ensure_eq!(ecn.take_cwr(), true);
+        crate::ensure_eq!(ecn.take_cwr(), false);
+
+        // Echoes for data that was already in flight at that point do not reduce it again.
+        crate::ensure_eq!(
+            ecn.on_echo_received(SeqNumber::from(5000), SeqNumber::from(12000)),
+            false
+        );
+        crate::ensure_eq!(
+            ecn.on_echo_received(SeqNumber::from(10000), SeqNumber::from(12000)),
+            false
+        );
+        crate::ensure_eq!(ecn.take_cwr(), false);
+
+        // Echoes for data that was sent after the reduction do.
+        crate::ensure_eq!(
+            ecn.on_echo_received(SeqNumber::from(10001), SeqNumber::from(12000)),
+            true
+        );
+
+        Ok(())
+    }
+}
@@ -1,32 +1,50 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+mod autotuning;
 pub mod congestion_control;
 pub mod ctrlblk;
+pub mod delivery_rate;
+mod ecn;
+mod rack;
 mod receiver;
-mod rto;
+pub mod rto;
 mod sender;
+pub mod stats;
 
 use crate::{
     collections::async_queue::SharedAsyncQueue,
     inetstack::protocols::{
         layer3::SharedLayer3Endpoint,
-        layer4::tcp::{
-            congestion_control::CongestionControlConstructor, established::ctrlblk::SharedControlBlock,
-            header::TcpHeader, SeqNumber,
+        layer4::{
+            stats_delta::StatsDelta,
+            tcp::{
+                congestion_control::CongestionControlConstructor,
+                established::{ctrlblk::SharedControlBlock, stats::Stats},
+                header::TcpHeader,
+                listener_stats::FirstByteTimer,
+                time_wait::TimeWaitEntry,
+                timestamps::Timestamps,
+                SeqNumber,
+            },
         },
     },
     runtime::{
         fail::Fail,
         memory::DemiBuffer,
-        network::{config::TcpConfig, socket::option::TcpSocketOptions},
+        network::{
+            config::TcpConfig,
+            socket::option::{TcpInfo, TcpSocketOptions},
+            types::MacAddress,
+        },
         SharedDemiRuntime,
     },
     QToken,
 };
 use ::futures::FutureExt;
+use ::serde_json::Value;
 use ::std::{
-    net::{Ipv4Addr, SocketAddrV4},
+    net::{Ipv4Addr, Shutdown, SocketAddrV4},
     time::Duration,
 };
 
@@ -46,6 +64,8 @@ impl EstablishedSocket {
     pub fn new(
         local: SocketAddrV4,
         remote: SocketAddrV4,
+        // Link address to send to, instead of resolving the remote address with ARP.
+        remote_link_addr: Option<MacAddress>,
         mut runtime: SharedDemiRuntime,
         layer3_endpoint: SharedLayer3Endpoint,
         recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>,
@@ -59,6 +79,10 @@ impl EstablishedSocket {
         sender_window_size: u32,
         sender_window_scale: u8,
         sender_mss: usize,
+        sack_permitted: bool,
+        timestamps: Option<Timestamps>,
+        // Whether both ends agreed to use ECN (RFC 3168).
+        ecn_capable: bool,
         cc_constructor: CongestionControlConstructor,
         congestion_control_options: Option<congestion_control::Options>,
     ) -> Result<Self, Fail> {
@@ -66,6 +90,7 @@ impl EstablishedSocket {
         let cb = SharedControlBlock::new(
             local,
             remote,
+            remote_link_addr,
             layer3_endpoint,
             runtime.clone(),
             tcp_config,
@@ -78,6 +103,9 @@ impl EstablishedSocket {
             sender_window_size,
             sender_window_scale,
             sender_mss,
+            sack_permitted,
+            timestamps,
+            ecn_capable,
             cc_constructor,
             congestion_control_options,
             recv_queue.clone(),
@@ -99,19 +127,89 @@ impl EstablishedSocket {
         self.cb.push(buf).await
     }
 
+    pub fn try_push(&mut self, buf: DemiBuffer) -> Result<(), Fail> {
+        self.cb.try_push(buf)
+    }
+
+    pub async fn wait_for_writable(&mut self, min_bytes: usize) -> Result<(), Fail> {
+        self.cb.wait_for_writable(min_bytes).await
+    }
+
     pub async fn pop(&mut self, size: Option<usize>) -> Result<DemiBuffer, Fail> {
         self.cb.pop(size).await
     }
 
-    pub async fn close(&mut self) -> Result<(), Fail> {
-        self.cb.close().await
+    pub async fn pop_all(&mut self, size: usize) -> Result<DemiBuffer, Fail> {
+        self.cb.pop_all(size).await
+    }
+
+    pub async fn peek(&mut self, size: Option<usize>) -> Result<DemiBuffer, Fail> {
+        self.cb.peek(size).await
+    }
+
+    pub async fn close(&mut self, fin_timeout: Duration) -> Result<(), Fail> {
+        self.cb.close(fin_timeout).await
+    }
+
+    /// Resets the connection. Reads and writes that are pending or come later fail with ECONNABORTED.
+    pub fn abort(&mut self) {
+        self.cb.abort(libc::ECONNABORTED);
+    }
+
+    pub fn shutdown(&mut self, how: Shutdown) -> Result<(), Fail> {
+        if how != Shutdown::Write {
+            self.cb.shutdown_read();
+        }
+        if how != Shutdown::Read {
+            self.cb.shutdown_write()?;
+        }
+        Ok(())
     }
 
     pub fn endpoints(&self) -> (SocketAddrV4, SocketAddrV4) {
         (self.cb.get_local(), self.cb.get_remote())
     }
 
+    /// Returns what the connection needs to remember while it is in TIME_WAIT, if it got there.
+    pub fn get_time_wait_entry(&self, msl: Duration) -> Option<TimeWaitEntry> {
+        self.cb.get_time_wait_entry(msl)
+    }
+
     pub fn get_cb(&self) -> SharedControlBlock {
         self.cb.clone()
     }
+
+    pub fn get_stats(&self) -> Stats {
+        self.cb.get_stats()
+    }
+
+    pub fn snapshot_stats(&mut self, reset: bool) -> StatsDelta<Stats> {
+        self.cb.snapshot_stats(reset)
+    }
+
+    pub fn get_tcp_info(&self) -> TcpInfo {
+        self.cb.get_tcp_info()
+    }
+
+    pub fn take_last_error(&mut self) -> Option<Fail> {
+        self.cb.take_last_error()
+    }
+
+    pub fn get_buffered_bytes(&self) -> usize {
+        self.cb.get_buffered_bytes()
+    }
+
+    pub fn on_fragmentation_needed(&mut self, seq_no: SeqNumber, mtu: u16) {
+        self.cb.on_fragmentation_needed(seq_no, mtu)
+    }
+
+    /// Starts measuring how long our peer takes to send its first byte, on behalf of the listening socket that
+    /// established this connection.
+    pub fn start_first_byte_timer(&mut self, timer: FirstByteTimer) {
+        self.cb.start_first_byte_timer(timer)
+    }
+
+    pub fn dump_state(&self, scrub_payload: bool) -> Value {
+        self.cb.dump_state(scrub_payload)
+    }
 }
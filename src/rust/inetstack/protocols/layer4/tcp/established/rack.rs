@@ -0,0 +1,220 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+// RACK-TLP loss detection, as described in RFC 8985. RACK declares a segment lost once a segment that was sent
+// sufficiently later has been delivered, based on send times instead of on counting duplicate ACKs. The Tail Loss
+// Probe (TLP) retransmits the last segment when the ACKs stop coming, so that losses at the end of a burst show up
+// without waiting for a retransmission timeout.
+
+use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+use ::std::{
+    cmp::min,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+// Number of SACKed segments after which we stop tolerating reordering, if we have not seen any yet. This matches the
+// duplicate ACK threshold of fast retransmit (RFC 8985 Section 6.2).
+const DUP_THRESH: usize = 3;
+
+// Worst-case delayed ACK timeout, which is added to the probe timeout when only one segment is in flight, as our peer
+// may hold back the ACK for it (RFC 8985 Section 7.2).
+const WORST_CASE_ACK_DELAY: Duration = Duration::from_millis(200);
+
+// Probe timeout to use until we have measured the round-trip time (RFC 8985 Section 7.2).
+const INITIAL_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// RACK state of a connection (RFC 8985 Section 6.1).
+#[derive(Debug)]
+pub struct Rack {
+    // Send time and end of the most recently sent segment that was delivered, and the round-trip time that it measured.
+    xmit_ts: Option<Instant>,
+    end_seq: SeqNumber,
+    rtt: Duration,
+    // Minimum round-trip time measured so far.
+    min_rtt: Option<Duration>,
+    // Highest end of a delivered segment. Delivering an original transmission that ends below it shows that the network
+    // reordered segments.
+    fack: Option<SeqNumber>,
+    reordering_seen: bool,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl Rack {
+    pub fn new() -> Self {
+        Self {
+            xmit_ts: None,
+            end_seq: SeqNumber::from(0),
+            rtt: Duration::ZERO,
+            min_rtt: None,
+            fack: None,
+            reordering_seen: false,
+        }
+    }
+
+    /// Updates the state with a segment that ends at [end_seq], was last sent at [sent_time] and is newly acknowledged
+    /// or SACKed at [now] (RFC 8985 Section 6.2).
+    pub fn on_delivered(&mut self, sent_time: Instant, end_seq: SeqNumber, retransmitted: bool, now: Instant) {
+        let rtt: Duration = now.saturating_duration_since(sent_time);
+        // An ACK that arrives sooner than any round trip after a retransmission was most likely sent for the original
+        // transmission, so it tells us nothing about the send time of the delivered segment.
+        if retransmitted && self.min_rtt.is_some_and(|min_rtt| rtt < min_rtt) {
+            return;
+        }
+        self.min_rtt = Some(self.min_rtt.map_or(rtt, |min_rtt| min(min_rtt, rtt)));
+
+        // An older segment does not move the state back in time.
+        if !self.is_sent_before(sent_time, end_seq) {
+            self.xmit_ts = Some(sent_time);
+            self.end_seq = end_seq;
+            self.rtt = rtt;
+        }
+
+        match self.fack {
+            Some(fack) if end_seq <= fack => {
+                if !retransmitted {
+                    self.reordering_seen = true;
+                }
+            },
+            _ => self.fack = Some(end_seq),
+        }
+    }
+
+    /// Returns how much longer than a round trip we wait before we declare a segment lost, given the smoothed round-trip
+    /// time [srtt] and the number of segments that our peer SACKed (RFC 8985 Section 6.2).
+    pub fn get_reordering_window(&self, srtt: Option<Duration>, sacked_segments: usize) -> Duration {
+        if !self.reordering_seen && sacked_segments >= DUP_THRESH {
+            return Duration::ZERO;
+        }
+        match (self.min_rtt, srtt) {
+            (Some(min_rtt), Some(srtt)) => min(min_rtt / 4, srtt),
+            (Some(min_rtt), None) => min_rtt / 4,
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns when the segment that ends at [end_seq] and was last sent at [sent_time] is lost, if it was not delivered
+    /// by then. Segments that were sent after the most recently delivered one are not known to be lost at all (RFC 8985
+    /// Section 6.2).
+    pub fn get_loss_deadline(&self, sent_time: Instant, end_seq: SeqNumber, reo_wnd: Duration) -> Option<Instant> {
+        if self.is_sent_before(sent_time, end_seq) {
+            Some(sent_time + self.rtt + reo_wnd)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the probe timeout for [segments_in_flight] segments, given the smoothed round-trip time [srtt] (RFC 8985
+    /// Section 7.2). The caller must not let it expire after the retransmission timeout.
+    pub fn get_probe_timeout(srtt: Option<Duration>, segments_in_flight: usize) -> Duration {
+        match srtt {
+            Some(srtt) if segments_in_flight == 1 => 2 * srtt + WORST_CASE_ACK_DELAY,
+            Some(srtt) => 2 * srtt,
+            None => INITIAL_PROBE_TIMEOUT,
+        }
+    }
+
+    // Checks whether a segment was sent before the most recently delivered one. Segments that were sent at the same time
+    // are ordered by their sequence numbers.
+    fn is_sent_before(&self, sent_time: Instant, end_seq: SeqNumber) -> bool {
+        match self.xmit_ts {
+            Some(xmit_ts) => sent_time < xmit_ts || (sent_time == xmit_ts && end_seq < self.end_seq),
+            None => false,
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{Rack, WORST_CASE_ACK_DELAY};
+    use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    #[test]
+    fn test_loss_deadline() -> Result<()> {
+        let mut rack: Rack = Rack::new();
+        let start: Instant = Instant::now();
+        let rtt: Duration = Duration::from_millis(40);
+
+        // Nothing is lost before anything was delivered.
+        crate::ensure_eq!(
+            rack.get_loss_deadline(start, SeqNumber::from(100), Duration::ZERO),
+            None
+        );
+
+        // The second segment is delivered, so the first one is lost one round trip plus the reordering window after it
+        // was sent. The third one was sent later, so it is not known to be lost.
+        rack.on_delivered(start, SeqNumber::from(200), false, start + rtt);
+        let reo_wnd: Duration = rack.get_reordering_window(Some(rtt), 1);
+        crate::ensure_eq!(reo_wnd, rtt / 4);
+        crate::ensure_eq!(
+            rack.get_loss_deadline(start, SeqNumber::from(100), reo_wnd),
+            Some(start + rtt + reo_wnd)
+        );
+        crate::ensure_eq!(
+            rack.get_loss_deadline(start + Duration::from_millis(1), SeqNumber::from(300), reo_wnd),
+            None
+        );
+
+        // With enough SACKed segments and no reordering, we stop waiting for reordered segments.
+        crate::ensure_eq!(rack.get_reordering_window(Some(rtt), 3), Duration::ZERO);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reordering_seen() -> Result<()> {
+        let mut rack: Rack = Rack::new();
+        let start: Instant = Instant::now();
+        let rtt: Duration = Duration::from_millis(40);
+
+        rack.on_delivered(start, SeqNumber::from(200), false, start + rtt);
+        // The first segment arrives late, so the network reorders segments and we keep tolerating that.
+        rack.on_delivered(start, SeqNumber::from(100), false, start + rtt);
+        crate::ensure_eq!(rack.get_reordering_window(Some(rtt), 3), rtt / 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_ack_for_original_transmission() -> Result<()> {
+        let mut rack: Rack = Rack::new();
+        let start: Instant = Instant::now();
+        let rtt: Duration = Duration::from_millis(40);
+        rack.on_delivered(start, SeqNumber::from(100), false, start + rtt);
+
+        // An ACK that follows a retransmission too soon was sent for the original transmission.
+        let retransmitted: Instant = start + 2 * rtt;
+        rack.on_delivered(retransmitted, SeqNumber::from(200), true, retransmitted + rtt / 2);
+        crate::ensure_eq!(
+            rack.get_loss_deadline(start + rtt, SeqNumber::from(300), Duration::ZERO),
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_probe_timeout() -> Result<()> {
+        let srtt: Duration = Duration::from_millis(40);
+        crate::ensure_eq!(Rack::get_probe_timeout(Some(srtt), 2), 2 * srtt);
+        // Our peer may delay the ACK for a single segment.
+        crate::ensure_eq!(Rack::get_probe_timeout(Some(srtt), 1), 2 * srtt + WORST_CASE_ACK_DELAY);
+        Ok(())
+    }
+}
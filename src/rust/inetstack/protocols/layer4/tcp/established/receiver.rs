@@ -6,29 +6,45 @@
 //======================================================================================================================
 
 use ::std::{
-    collections::VecDeque,
+    cmp,
+    collections::vec_deque::Iter,
     time::{Duration, Instant},
 };
 
 use crate::{
-    collections::{async_queue::AsyncQueue, async_value::SharedAsyncValue},
-    expect_ok,
-    inetstack::protocols::layer4::tcp::{
-        established::ctrlblk::State, established::SharedControlBlock, header::TcpHeader, SeqNumber,
+    collections::{async_queue::AsyncQueue, async_value::SharedAsyncValue, fixed_ring::FixedRing},
+    expect_ok, expect_some,
+    inetstack::protocols::layer4::{
+        dump_payload,
+        tcp::{
+            established::autotuning::ReceiveBufferTuner,
+            established::ctrlblk::State,
+            established::SharedControlBlock,
+            header::{SelectiveAcknowlegement, TcpHeader},
+            SeqNumber,
+        },
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{
+            config::UrgentData,
+            consts::DEFAULT_TCP_ACK_STRETCH_SEGMENTS,
+            socket::option::{DelayedAck, QuickAck},
+        },
     },
-    runtime::{fail::Fail, memory::DemiBuffer},
 };
 
 use ::futures::never::Never;
+use ::serde_json::{json, Value};
 
 //======================================================================================================================
 // Constants
 //======================================================================================================================
 
-// TODO: Review this value (and its purpose).  It (16 segments) seems awfully small (would make fast retransmit less
-// useful), and this mechanism isn't the best way to protect ourselves against deliberate out-of-order segment attacks.
-// Ideally, we'd limit out-of-order data to that which (along with the unread data) will fit in the receive window.
-const MAX_OUT_OF_ORDER_SIZE_FRAMES: usize = 16;
+// Upper bound on the number of entries that we pre-allocate in the pop queue. The queue still grows past this if the
+// application falls behind on reading small segments.
+const MAX_POP_QUEUE_SIZE_FRAMES: usize = 1024;
 
 //======================================================================================================================
 // Data Structures
@@ -66,10 +82,32 @@ pub struct Receiver {
 
     ack_deadline_time_secs: SharedAsyncValue<Option<Instant>>,
 
+    // Number of received segments (or full-sized segments worth of data) that we acknowledge with a single ACK, along
+    // with the number of segments and bytes of data that we have received since we last sent an ACK.
+    ack_stretch_segments: u32,
+    unacknowledged_segments: u32,
+    unacknowledged_bytes: u32,
+
+    // Delayed ACK timeout and number of segments per ACK that the stack is configured with, which apply unless the
+    // socket picks a delayed ACK policy of its own.
+    default_ack_delay_timeout: Duration,
+    default_ack_stretch_segments: u32,
+
+    // Size of a full-sized segment that our peer may send us.
+    mss: u32,
+
+    // When we acknowledge segments right away instead of delaying the ACK (TCP_QUICKACK) and how many more data segments
+    // we acknowledge right away before going back to delayed ACKs.
+    quick_ack: QuickAck,
+    quick_ack_segments: u32,
+
     // This is our receive buffer size, which is also the maximum size of our receive window.
     // Note: The maximum possible advertised window is 1 GiB with window scaling and 64 KiB without.
     buffer_size_frames: u32,
 
+    // Grows the receive buffer as the rate at which the application reads goes up, if autotuning is on.
+    autotuner: Option<Box<ReceiveBufferTuner>>,
+
     // TODO: Review how this is used.  We could have separate window scale factors, so there should be one for the
     // receiver and one for the sender.
     // This is the receive-side window scale factor.
@@ -80,7 +118,33 @@ pub struct Receiver {
     // receive window) but can't yet present to the user because we're missing some other data that comes between this
     // and what we've already presented to the user.
     //
-    out_of_order_frames: VecDeque<(SeqNumber, DemiBuffer)>,
+    // This is a fixed-capacity ring that holds as many full-sized segments as fit in the receive buffer, so storing and
+    // recovering out-of-order segments never allocates. It is only reallocated when the receive buffer grows.
+    out_of_order_frames: FixedRing<(SeqNumber, DemiBuffer)>,
+
+    // Number of bytes in the out-of-order store. This is limited by the receive window, rather than by the number of
+    // segments, so that we hold on to everything our peer is allowed to send us on high bandwidth-delay paths.
+    out_of_order_bytes: u32,
+
+    // Sequence space of the most recently received duplicate data that we have not reported to our peer yet. This is
+    // reported as a duplicate SACK block (RFC 2883).
+    duplicate_block: Option<(SeqNumber, SeqNumber)>,
+
+    // Sequence number of the most recently received out-of-order segment. The SACK block that contains it is reported
+    // first (RFC 2018 Section 4).
+    last_out_of_order_seq_no: Option<SeqNumber>,
+
+    // Time at which we last accepted a segment from our peer. Keepalive probes go out once this gets too old.
+    last_receive_time: Instant,
+
+    // Error that reads fail with after the connection was aborted.
+    abort_errno: Option<libc::c_int>,
+
+    // Whether the application shut down the receive direction, in which case we drop the data that we receive.
+    read_shutdown: bool,
+
+    // What we do with segments that carry urgent data.
+    urgent_data: UrgentData,
 }
 
 //======================================================================================================================
@@ -92,29 +156,59 @@ impl Receiver {
         reader_next_seq_no: SeqNumber,
         receive_next_seq_no: SeqNumber,
         ack_delay_timeout_secs: Duration,
+        ack_stretch_segments: u32,
         window_size_frames: u32,
         window_scale_shift_bits: u8,
+        mss: usize,
+        quick_ack: QuickAck,
+        delayed_ack: DelayedAck,
+        now: Instant,
     ) -> Self {
-        Self {
+        // Number of full-sized segments that fit in the receive window.
+        let window_size_segments: usize = cmp::max(window_size_frames as usize / cmp::max(mss, 1), 1);
+        let mut receiver: Self = Self {
             reader_next_seq_no,
             receive_next_seq_no,
             fin_seq_no: SharedAsyncValue::new(None),
-            pop_queue: AsyncQueue::with_capacity(1024),
+            pop_queue: AsyncQueue::with_capacity(cmp::min(window_size_segments, MAX_POP_QUEUE_SIZE_FRAMES)),
             ack_delay_timeout_secs,
             ack_deadline_time_secs: SharedAsyncValue::new(None),
+            ack_stretch_segments,
+            unacknowledged_segments: 0,
+            unacknowledged_bytes: 0,
+            default_ack_delay_timeout: ack_delay_timeout_secs,
+            default_ack_stretch_segments: ack_stretch_segments,
+            mss: cmp::max(mss, 1) as u32,
+            quick_ack,
+            quick_ack_segments: Self::quick_ack_budget(quick_ack),
             buffer_size_frames: window_size_frames,
+            autotuner: None,
             window_scale_shift_bits,
-            out_of_order_frames: VecDeque::with_capacity(64),
-        }
+            out_of_order_frames: FixedRing::new(window_size_segments),
+            out_of_order_bytes: 0,
+            duplicate_block: None,
+            last_out_of_order_seq_no: None,
+            last_receive_time: now,
+            abort_errno: None,
+            read_shutdown: false,
+            urgent_data: UrgentData::default(),
+        };
+        receiver.set_delayed_ack(delayed_ack);
+        receiver
     }
 
-    pub async fn pop(&mut self, size: Option<usize>) -> Result<DemiBuffer, Fail> {
+    /// Pops up to `size` bytes of data, waiting until at least `low_watermark` bytes are buffered or the connection is
+    /// closed.
+    pub async fn pop(&mut self, size: Option<usize>, low_watermark: usize) -> Result<DemiBuffer, Fail> {
         debug!("waiting on pop {:?}", size);
+        self.wait_for_low_watermark(size, low_watermark).await;
         let buf: DemiBuffer = if let Some(size) = size {
             let mut buf: DemiBuffer = self.pop_queue.pop(None).await?;
-            // Split the buffer if it's too big.
+            // Split the buffer if it's too big. The rest stays at the front of the queue for the next pop.
             if buf.len() > size {
-                buf.split_front(size)?
+                let front: DemiBuffer = buf.split_front(size)?;
+                self.pop_queue.push_front(buf);
+                front
             } else {
                 buf
             }
@@ -127,18 +221,89 @@ impl Receiver {
                 self.reader_next_seq_no = self.reader_next_seq_no + SeqNumber::from(buf.len() as u32);
             },
             _ => {
+                // An aborted connection leaves the end-of-file marker in place as well, but reads fail instead.
+                if let Some(errno) = self.abort_errno {
+                    self.pop_queue.push_front(DemiBuffer::new(0));
+                    let cause: String = format!("connection aborted");
+                    warn!("pop(): {}", cause);
+                    return Err(Fail::new(errno, &cause));
+                }
                 debug!("found FIN");
-                self.reader_next_seq_no = self.reader_next_seq_no + 1.into();
+                // Only the first pop that finds the FIN moves past it. Put the end-of-file marker back, so that this
+                // and every later pop complete right away instead of waiting for data that will never arrive.
+                if self.fin_seq_no.get() == Some(self.reader_next_seq_no) {
+                    self.reader_next_seq_no = self.reader_next_seq_no + 1.into();
+                }
+                self.pop_queue.push_front(DemiBuffer::new(0));
             },
         }
 
         Ok(buf)
     }
 
-    pub fn receive(&mut self, tcp_hdr: TcpHeader, buf: DemiBuffer, cb: SharedControlBlock, now: Instant) {
-        match self.process_packet(tcp_hdr, buf, cb, now) {
+    /// Returns up to `size` bytes of the data that the next pop would return, without consuming it (MSG_PEEK). Like a
+    /// pop, this waits until at least `low_watermark` bytes are buffered or the connection is closed. The returned
+    /// buffer shares its memory with the data that stays in the queue.
+    pub async fn peek(&mut self, size: Option<usize>, low_watermark: usize) -> Result<DemiBuffer, Fail> {
+        debug!("waiting on peek {:?}", size);
+        self.wait_for_low_watermark(size, low_watermark).await;
+        self.pop_queue
+            .wait_for_condition(|mut bufs| bufs.next().is_some())
+            .await;
+        let mut buf: DemiBuffer = expect_some!(self.pop_queue.get_front(), "should have waited for a buffer").clone();
+        if buf.is_empty() {
+            if let Some(errno) = self.abort_errno {
+                let cause: String = format!("connection aborted");
+                warn!("peek(): {}", cause);
+                return Err(Fail::new(errno, &cause));
+            }
+            // The end-of-file marker stays in place, so the next pop still moves past the FIN.
+            return Ok(buf);
+        }
+        if let Some(size) = size {
+            if buf.len() > size {
+                buf.trim(buf.len() - size)?;
+            }
+        }
+        Ok(buf)
+    }
+
+    // Waits until at least [low_watermark] bytes are buffered or the connection is closed. Our peer cannot send more
+    // than fits into the receive buffer, so we never wait for more than that, nor for more than the caller asked for.
+    async fn wait_for_low_watermark(&mut self, size: Option<usize>, low_watermark: usize) {
+        let low_watermark: usize = low_watermark
+            .min(size.unwrap_or(usize::MAX))
+            .min(self.buffer_size_frames as usize);
+        if low_watermark > 1 {
+            self.pop_queue
+                .wait_for_condition(|bufs| Self::has_low_watermark(bufs, low_watermark))
+                .await;
+        }
+    }
+
+    // Checks whether the buffers [bufs] hold at least [low_watermark] bytes or end with the end-of-file marker.
+    fn has_low_watermark(bufs: Iter<DemiBuffer>, low_watermark: usize) -> bool {
+        let mut buffered: usize = 0;
+        for buf in bufs {
+            if buf.is_empty() {
+                return true;
+            }
+            buffered += buf.len();
+            if buffered >= low_watermark {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn receive(&mut self, tcp_hdr: TcpHeader, buf: DemiBuffer, mut cb: SharedControlBlock, now: Instant) {
+        match self.process_packet(tcp_hdr, buf, cb.clone(), now) {
             Ok(()) => (),
-            Err(e) => debug!("Dropped packet: {:?}", e),
+            Err(e) => {
+                debug!("Dropped packet: {:?}", e);
+                // Let the application find out why, as it has no other way of telling that the segment was dropped.
+                cb.set_last_error(e);
+            },
         }
     }
 
@@ -178,6 +343,7 @@ impl Receiver {
         let mut seg_start: SeqNumber = header.seq_num;
         let mut seg_end: SeqNumber = seg_start;
         let mut seg_len: u32 = data.len() as u32;
+        let idle: bool = now.saturating_duration_since(self.last_receive_time) > self.ack_delay_timeout_secs;
 
         // Check if the segment is in the receive window and trim off everything else.
         self.check_segment_in_window(
@@ -188,17 +354,22 @@ impl Receiver {
             &mut seg_len,
             &mut cb,
         )?;
-        self.check_rst(&header)?;
-        self.check_syn(&header)?;
+        self.last_receive_time = now;
+        self.check_rst(&header, &mut cb)?;
+        self.check_syn(&header, &mut cb)?;
         self.process_ack(&header, &mut cb, now)?;
 
-        // TODO: Check the URG bit.  If we decide to support this, how should we do it?
-        if header.urg {
-            warn!("Got packet with URG bit set!");
-        }
+        self.check_urg(&header, &mut cb)?;
 
+        let quick_ack: bool = self.check_quick_ack(data.len() > 0, idle);
+        self.unacknowledged_bytes = self.unacknowledged_bytes.saturating_add(data.len() as u32);
         if data.len() > 0 {
             self.process_data(data, seg_start, seg_end, seg_len, &mut cb)?;
+            let receive_next: SeqNumber = self.receive_next_seq_no;
+            let window_size_frames: u32 = self.get_receive_window_size();
+            if let Some(autotuner) = self.autotuner.as_mut() {
+                autotuner.on_receive(now, receive_next, window_size_frames);
+            }
         }
 
         // Process FIN flag.
@@ -231,16 +402,30 @@ impl Receiver {
         }
         // We should ACK this segment, preferably via piggybacking on a response.
         // TODO: Consider replacing the delayed ACK timer with a simple flag.
-        if self.ack_deadline_time_secs.get().is_none() {
+        self.unacknowledged_segments += 1;
+        // Stretching ACKs hides loss and reordering from our peer, so fall back to the standard behavior while we have
+        // a hole in the sequence space.
+        let segments_per_ack: u32 = if self.out_of_order_frames.is_empty() {
+            self.ack_stretch_segments
+        } else {
+            DEFAULT_TCP_ACK_STRETCH_SEGMENTS as u32
+        };
+        // Besides counting segments, we ACK at least every second full-sized segment worth of data (RFC 5681 Section
+        // 4.2), which also holds when our peer sends segments that are larger than the MSS, like coalesced ones.
+        if quick_ack
+            || self.unacknowledged_segments >= segments_per_ack
+            || self.unacknowledged_bytes >= segments_per_ack.saturating_mul(self.mss)
+        {
+            // We owe our peer an ACK for enough segments (or right away), so cancel the timer and ACK now. If data is
+            // about to go out anyway, the ACK piggybacks on it instead.
+            self.ack_deadline_time_secs.set(None);
+            trace!("process_packet(): sending ack on deadline expiration");
+            cb.send_ack_or_piggyback(now);
+        } else if self.ack_deadline_time_secs.get().is_none() {
             // Start the delayed ACK timer to ensure an ACK gets sent soon even if no piggyback opportunity occurs.
             let timeout: Duration = self.ack_delay_timeout_secs;
             // Getting the current time is extremely cheap as it is just a variable lookup.
             self.ack_deadline_time_secs.set(Some(now + timeout));
-        } else {
-            // We already owe our peer an ACK (the timer was already running), so cancel the timer and ACK now.
-            self.ack_deadline_time_secs.set(None);
-            trace!("process_packet(): sending ack on deadline expiration");
-            cb.send_ack();
         }
 
         Ok(())
@@ -279,6 +464,18 @@ impl Receiver {
         // Review: We don't need all of these intermediate variables in the fast path.  It might be more efficient to
         // rework this to calculate some of them only when needed, even if we need to (re)do it in multiple places.
 
+        // Drop old duplicates whose sequence numbers look valid because the sequence space wrapped around (RFC 7323
+        // Section 5.3).
+        if !cb.check_paws(header) {
+            if !header.rst {
+                trace!("check_segment_in_window(): send ack on PAWS failure");
+                cb.send_ack();
+            }
+            let cause: String = format!("timestamp older than the most recent one");
+            error!("check_segment_in_window(): {}", cause);
+            return Err(Fail::new(libc::EBADMSG, &cause));
+        }
+
         if header.syn {
             *seg_len += 1;
         }
@@ -303,6 +500,9 @@ impl Receiver {
                 if *seg_end < receive_next {
                     // This is an entirely duplicate (i.e. old) segment.  ACK (if not RST) and drop.
                     //
+                    if *seg_len > 0 {
+                        self.duplicate_block = Some((*seg_start, *seg_end + SeqNumber::from(1)));
+                    }
                     if !header.rst {
                         trace!("check_segment_in_window(): send ack on duplicate segment");
                         cb.send_ack();
@@ -315,6 +515,7 @@ impl Receiver {
                     // If there is a SYN at the start of this segment, remove it too.
                     //
                     let mut duplicate: u32 = u32::from(receive_next - *seg_start);
+                    self.duplicate_block = Some((*seg_start, receive_next));
                     *seg_start = *seg_start + SeqNumber::from(duplicate);
                     *seg_len -= duplicate;
                     if header.syn {
@@ -371,18 +572,19 @@ impl Receiver {
         // we receive (as long as they're in the window) as we receive them, even if they're out-of-order.  It's only
         // when we get to processing the data (and FIN) that we store aside any out-of-order segments for later.
         debug_assert!(receive_next <= *seg_start && *seg_end < after_receive_window);
+        cb.update_timestamps_recent(header);
         Ok(())
     }
 
     // Check the RST bit.
-    fn check_rst(&mut self, header: &TcpHeader) -> Result<(), Fail> {
+    fn check_rst(&mut self, header: &TcpHeader, cb: &mut SharedControlBlock) -> Result<(), Fail> {
         if header.rst {
             // TODO: RFC 5961 "Blind Reset Attack Using the RST Bit" prevention would have us ACK and drop if the new
             // segment doesn't start precisely on RCV.NXT.
 
             // Our peer has given up.  Shut the connection down hard.
             info!("Received RST");
-            // TODO: Schedule a close coroutine.
+            cb.on_reset();
             let cause: String = format!("remote reset connection");
             info!("check_rst(): {}", cause);
             return Err(Fail::new(libc::ECONNRESET, &cause));
@@ -391,26 +593,52 @@ impl Receiver {
     }
 
     // Check the SYN bit.
-    fn check_syn(&mut self, header: &TcpHeader) -> Result<(), Fail> {
+    fn check_syn(&mut self, header: &TcpHeader, cb: &mut SharedControlBlock) -> Result<(), Fail> {
         // Note: RFC 793 says to check security/compartment and precedence next, but those are largely deprecated.
 
         // Check the SYN bit.
         if header.syn {
             // TODO: RFC 5961 "Blind Reset Attack Using the SYN Bit" prevention would have us always ACK and drop here.
 
-            // Receiving a SYN here is an error.
+            // Receiving a SYN here is an error, so we reset the connection (RFC 793 Section 3.9). Outstanding reads and
+            // writes fail with ECONNRESET.
             let cause: String = format!("Received in-window SYN on established connection.");
             error!("{}", cause);
-            // TODO: Send Reset.
-            // TODO: Return all outstanding Receive and Send requests with "reset" responses.
             // TODO: Flush all segment queues.
-
-            // TODO: Start the close coroutine
+            cb.abort(libc::ECONNRESET);
             return Err(Fail::new(libc::EBADMSG, &cause));
         }
         Ok(())
     }
 
+    // Check the URG bit. We do not support urgent data, so it either goes in line with the rest of the data, which is what
+    // the urgent pointer of most stacks ends up meaning anyway (RFC 6093), or the segment is refused.
+    fn check_urg(&mut self, header: &TcpHeader, cb: &mut SharedControlBlock) -> Result<(), Fail> {
+        if !header.urg {
+            return Ok(());
+        }
+        match self.urgent_data {
+            UrgentData::Inline => {
+                trace!(
+                    "check_urg(): delivering urgent data inline (pointer={:?})",
+                    header.urgent_pointer
+                );
+                Ok(())
+            },
+            UrgentData::Drop => {
+                let cause: String = format!("Received segment with urgent data on established connection.");
+                warn!("{}", cause);
+                Err(Fail::new(libc::EBADMSG, &cause))
+            },
+            UrgentData::Reset => {
+                let cause: String = format!("Received segment with urgent data on established connection.");
+                error!("{}", cause);
+                cb.abort(libc::ECONNRESET);
+                Err(Fail::new(libc::EBADMSG, &cause))
+            },
+        }
+    }
+
     // Check the ACK bit.
     fn process_ack(&mut self, header: &TcpHeader, cb: &mut SharedControlBlock, now: Instant) -> Result<(), Fail> {
         if !header.ack {
@@ -447,7 +675,12 @@ impl Receiver {
             match cb.get_state() {
                 State::Established | State::FinWait1 | State::FinWait2 => {
                     debug_assert_eq!(seg_len, data.len() as u32);
-                    self.store_out_of_order_segment(seg_start, seg_end, data);
+                    self.last_out_of_order_seq_no = Some(seg_start);
+                    let num_dropped: usize = self.store_out_of_order_segment(seg_start, seg_end, data);
+                    if num_dropped > 0 {
+                        cb.on_out_of_order_dropped(num_dropped);
+                    }
+                    cb.congestion_control_on_out_of_order_received(self.receive_next_seq_no, seg_start, seg_end);
                     // Sending an ACK here is only a "MAY" according to the RFCs, but helpful for fast retransmit.
                     trace!("process_data(): send ack on out-of-order segment");
                     cb.send_ack();
@@ -468,103 +701,144 @@ impl Receiver {
     // If the new segment had a FIN it has been removed prior to this routine being called.
     // Note: Since this is not the "fast path", this is written for clarity over efficiency.
     //
-    fn store_out_of_order_segment(&mut self, mut new_start: SeqNumber, mut new_end: SeqNumber, mut buf: DemiBuffer) {
+    // Returns the number of segments that were dropped (including the new one) to keep the out-of-order store within its
+    // limits.
+    //
+    fn store_out_of_order_segment(
+        &mut self,
+        mut new_start: SeqNumber,
+        new_end: SeqNumber,
+        mut buf: DemiBuffer,
+    ) -> usize {
+        // Position in the out-of-order store where the new segment goes.
         let mut action_index: usize = self.out_of_order_frames.len();
-        let mut another_pass_neeeded: bool = true;
-
-        while another_pass_neeeded {
-            another_pass_neeeded = false;
-
-            // Find the new segment's place in the out-of-order store.
-            // The out-of-order store is sorted by starting sequence number, and contains no duplicate data.
-            action_index = self.out_of_order_frames.len();
-            for index in 0..self.out_of_order_frames.len() {
-                let stored_segment: &(SeqNumber, DemiBuffer) = &self.out_of_order_frames[index];
-
-                // Properties of the segment stored at this index.
-                let stored_start: SeqNumber = stored_segment.0;
-                let stored_len: u32 = stored_segment.1.len() as u32;
-                debug_assert_ne!(stored_len, 0);
-                let stored_end: SeqNumber = stored_start + SeqNumber::from(stored_len - 1);
-
-                //
-                // The new data segment has six possibilites when compared to an existing out-of-order segment:
-                //
-                //                                |<- out-of-order segment ->|
-                //
-                // |<- new before->|    |<- new front overlap ->|    |<- new end overlap ->|    |<- new after ->|
-                //                                   |<- new duplicate ->|
-                //                            |<- new completely encompassing ->|
-                //
-                if new_start < stored_start {
-                    // The new segment starts before the start of this out-of-order segment.
-                    if new_end < stored_start {
-                        // The new segment comes completely before this out-of-order segment.
-                        // Since the out-of-order store is sorted, we don't need to check for overlap with any more.
-                        action_index = index;
-                        break;
-                    }
-                    // The end of the new segment overlaps with the start of this out-of-order segment.
-                    if stored_end < new_end {
-                        // The new segment ends after the end of this out-of-order segment.  In other words, the new
-                        // segment completely encompasses the out-of-order segment.
-
-                        // Set flags to remove the currently stored segment and re-run the insertion loop, as the
-                        // new segment may completely encompass even more segments.
-                        another_pass_neeeded = true;
-                        action_index = index;
-                        break;
-                    }
-                    // We have some data overlap between the new segment and the front of the out-of-order segment.
-                    // Trim the end of the new segment and stop checking for out-of-order overlap.
-                    let excess: u32 = u32::from(new_end - stored_start) + 1;
-                    new_end = new_end - SeqNumber::from(excess);
-                    expect_ok!(
-                        buf.trim(excess as usize),
-                        "'buf' should contain at least 'excess' bytes"
-                    );
+        // Range of stored segments that the new segment completely encompasses. Since the out-of-order store is
+        // sorted and contains no duplicate data, these are always adjacent to each other.
+        let mut first_encompassed_index: Option<usize> = None;
+        let mut num_encompassed: usize = 0;
+
+        // Find the new segment's place in the out-of-order store.
+        // The out-of-order store is sorted by starting sequence number, and contains no duplicate data.
+        for index in 0..self.out_of_order_frames.len() {
+            let stored_segment: &(SeqNumber, DemiBuffer) = &self.out_of_order_frames[index];
+
+            // Properties of the segment stored at this index.
+            let stored_start: SeqNumber = stored_segment.0;
+            let stored_len: u32 = stored_segment.1.len() as u32;
+            debug_assert_ne!(stored_len, 0);
+            let stored_end: SeqNumber = stored_start + SeqNumber::from(stored_len - 1);
+
+            //
+            // The new data segment has six possibilites when compared to an existing out-of-order segment:
+            //
+            //                                |<- out-of-order segment ->|
+            //
+            // |<- new before->|    |<- new front overlap ->|    |<- new end overlap ->|    |<- new after ->|
+            //                                   |<- new duplicate ->|
+            //                            |<- new completely encompassing ->|
+            //
+            if new_start < stored_start {
+                // The new segment starts before the start of this out-of-order segment.
+                if new_end < stored_start {
+                    // The new segment comes completely before this out-of-order segment.
+                    // Since the out-of-order store is sorted, we don't need to check for overlap with any more.
+                    action_index = index;
                     break;
-                } else {
-                    // The new segment starts at or after the start of this out-of-order segment.
-                    // This is the stored_start <= new_start case.
-                    if new_end <= stored_end {
-                        // And the new segment ends at or before this out-of-order segment.
-                        // The new segment's data is a complete duplicate of this out-of-order segment's data.
-                        // Just drop the new segment.
-                        return;
-                    }
-                    if stored_end < new_start {
-                        // The new segment comes entirely after this out-of-order segment.
-                        // Continue to check the next out-of-order segment for potential overlap.
-                        continue;
-                    }
-                    // We have some data overlap between the new segment and the end of the out-of-order segment.
-                    // Adjust the beginning of the new segment and continue on to check the next out-of-order segment.
-                    let duplicate: u32 = u32::from(stored_end - new_start);
-                    new_start = new_start + SeqNumber::from(duplicate);
-                    expect_ok!(
-                        buf.adjust(duplicate as usize),
-                        "'buf' should contain at least 'duplicate' bytes"
-                    );
+                }
+                // The end of the new segment overlaps with the start of this out-of-order segment.
+                if stored_end < new_end {
+                    // The new segment ends after the end of this out-of-order segment.  In other words, the new
+                    // segment completely encompasses the out-of-order segment.
+
+                    // Mark the currently stored segment for removal and keep going, as the new segment may completely
+                    // encompass even more segments.
+                    first_encompassed_index.get_or_insert(index);
+                    num_encompassed += 1;
+                    continue;
+                }
+                // We have some data overlap between the new segment and the front of the out-of-order segment.
+                // Trim the end of the new segment and stop checking for out-of-order overlap.
+                let excess: u32 = u32::from(new_end - stored_start) + 1;
+                expect_ok!(
+                    buf.trim(excess as usize),
+                    "'buf' should contain at least 'excess' bytes"
+                );
+                action_index = index;
+                break;
+            } else {
+                // The new segment starts at or after the start of this out-of-order segment.
+                // This is the stored_start <= new_start case.
+                if new_end <= stored_end {
+                    // And the new segment ends at or before this out-of-order segment.
+                    // The new segment's data is a complete duplicate of this out-of-order segment's data.
+                    // Just drop the new segment.
+                    self.duplicate_block = Some((new_start, new_end + SeqNumber::from(1)));
+                    return 0;
+                }
+                if stored_end < new_start {
+                    // The new segment comes entirely after this out-of-order segment.
+                    // Continue to check the next out-of-order segment for potential overlap.
                     continue;
                 }
+                // We have some data overlap between the new segment and the end of the out-of-order segment.
+                // Adjust the beginning of the new segment and continue on to check the next out-of-order segment.
+                let duplicate: u32 = u32::from(stored_end - new_start);
+                new_start = new_start + SeqNumber::from(duplicate);
+                expect_ok!(
+                    buf.adjust(duplicate as usize),
+                    "'buf' should contain at least 'duplicate' bytes"
+                );
+                continue;
             }
+        }
+
+        if let Some(first_encompassed_index) = first_encompassed_index {
+            // The new segment takes the place of the first segment that it encompasses, and the other encompassed
+            // segments are removed in one go. The encompassed segments hold less data than the new one, which still
+            // fits in the receive window.
+            let encompassed_bytes: u32 = (first_encompassed_index..first_encompassed_index + num_encompassed)
+                .filter_map(|index| self.out_of_order_frames.get(index))
+                .map(|(_, stored_buf)| stored_buf.len() as u32)
+                .sum();
+            self.out_of_order_bytes = self.out_of_order_bytes - encompassed_bytes + buf.len() as u32;
+            self.out_of_order_frames
+                .replace(first_encompassed_index, (new_start, buf));
+            self.out_of_order_frames
+                .remove_range(first_encompassed_index + 1, num_encompassed - 1);
+            return 0;
+        }
 
-            if another_pass_neeeded {
-                // The new segment completely encompassed an existing segment, which we will now remove.
-                self.out_of_order_frames.remove(action_index);
+        // Make room for the new segment by dropping the latest entries, which are the furthest away from being
+        // delivered, as long as the out-of-order store is full or would exceed the receive window. The new segment itself
+        // is dropped instead if it would be the latest entry.
+        let new_len: u32 = buf.len() as u32;
+        let max_bytes: u32 = self.get_receive_window_size();
+        let mut num_dropped: usize = 0;
+        while self.out_of_order_frames.is_full() || self.out_of_order_bytes + new_len > max_bytes {
+            if action_index == self.out_of_order_frames.len() {
+                debug!(
+                    "store_out_of_order_segment(): dropping segment (seq_no={:?}, len={:?})",
+                    new_start, new_len
+                );
+                return num_dropped + 1;
+            }
+            if let Some((dropped_start, dropped_buf)) = self.out_of_order_frames.pop_back() {
+                debug!(
+                    "store_out_of_order_segment(): evicting segment (seq_no={:?}, len={:?})",
+                    dropped_start,
+                    dropped_buf.len()
+                );
+                self.out_of_order_bytes -= dropped_buf.len() as u32;
+                num_dropped += 1;
             }
         }
 
         // Insert the new segment into the correct position.
-        self.out_of_order_frames.insert(action_index, (new_start, buf));
-
-        // If the out-of-order store now contains too many entries, delete the later entries.
-        // TODO: The out-of-order store is already limited (in size) by our receive window, while the below check
-        // imposes a limit on the number of entries.  Do we need this?  Presumably for attack mitigation?
-        while self.out_of_order_frames.len() > MAX_OUT_OF_ORDER_SIZE_FRAMES {
-            self.out_of_order_frames.pop_back();
+        if self.out_of_order_frames.insert(action_index, (new_start, buf)).is_err() {
+            unreachable!("out-of-order store should have room for the new segment");
         }
+        self.out_of_order_bytes += new_len;
+        num_dropped
     }
 
     // This routine takes an incoming in-order TCP segment and adds the data to the user's receive queue.  If the new
@@ -583,7 +857,7 @@ impl Receiver {
         self.receive_next_seq_no = self.receive_next_seq_no + SeqNumber::from(buf.len() as u32);
         // This inserts the segment and wakes a waiting pop coroutine.
         debug!("pushing buffer");
-        self.pop_queue.push(buf);
+        self.deliver(buf);
 
         // Okay, we've successfully received some new data.  Check if any of the formerly out-of-order data waiting in
         // the out-of-order queue is now in-order.  If so, we can move it to the receive queue.
@@ -594,9 +868,10 @@ impl Receiver {
                     // This data is now considered to be "received" by TCP, and included in our RCV.NXT calculation.
                     debug!("Recovering out-of-order packet at {}", self.receive_next_seq_no);
                     if let Some(temp) = self.out_of_order_frames.pop_front() {
+                        self.out_of_order_bytes -= temp.1.len() as u32;
                         self.receive_next_seq_no = self.receive_next_seq_no + SeqNumber::from(temp.1.len() as u32);
                         // This inserts the segment and wakes a waiting pop coroutine.
-                        self.pop_queue.push(temp.1);
+                        self.deliver(temp.1);
                     }
                 } else {
                     // Since our out-of-order list is sorted, we can stop when the next segment is not in sequence.
@@ -606,24 +881,257 @@ impl Receiver {
         }
     }
 
+    // Hands in-order data to the application. Once it shut down the receive direction, the data is dropped instead, as if
+    // it had been read right away, so that the receive window stays open.
+    fn deliver(&mut self, buf: DemiBuffer) {
+        if self.read_shutdown {
+            self.reader_next_seq_no = self.reader_next_seq_no + SeqNumber::from(buf.len() as u32);
+        } else {
+            self.pop_queue.push(buf);
+        }
+    }
+
     pub fn set_receive_ack_deadline(&mut self, ack_deadline_timeout_secs: Option<Instant>) {
         self.ack_deadline_time_secs.set(ack_deadline_timeout_secs)
     }
 
+    // Checks whether we owe our peer an ACK that the delayed ACK timer would send.
+    pub fn is_ack_pending(&self) -> bool {
+        self.ack_deadline_time_secs.get().is_some()
+    }
+
+    // Called whenever we send an ACK, which acknowledges every segment that we have received so far.
+    pub fn on_ack_sent(&mut self) {
+        self.unacknowledged_segments = 0;
+        self.unacknowledged_bytes = 0;
+        self.set_receive_ack_deadline(None);
+    }
+
     fn process_fin(&mut self, cb: &mut SharedControlBlock) {
         cb.process_fin();
         self.push_fin();
     }
 
+    // Takes the duplicate data that has not been reported to our peer yet.
+    pub fn take_duplicate_block(&mut self) -> Option<(SeqNumber, SeqNumber)> {
+        self.duplicate_block.take()
+    }
+
+    // Fills [sacks] with SACK blocks that describe the out-of-order data that we hold (RFC 2018) and returns how many
+    // blocks were filled in. The block that contains [first_seq_no] goes first, or the one that contains the most
+    // recently received out-of-order segment if there is none. The remaining blocks follow in sequence order.
+    pub fn get_sack_blocks(&self, first_seq_no: Option<SeqNumber>, sacks: &mut [SelectiveAcknowlegement]) -> usize {
+        let first_seq_no: Option<SeqNumber> = first_seq_no.or(self.last_out_of_order_seq_no);
+        let is_first = move |(begin, end): &(SeqNumber, SeqNumber)| {
+            first_seq_no.is_some_and(|seq_no| *begin <= seq_no && seq_no < *end)
+        };
+        let blocks = self
+            .out_of_order_blocks()
+            .filter(is_first)
+            .chain(self.out_of_order_blocks().filter(|block| !is_first(block)));
+        let mut num_sacks: usize = 0;
+        for ((begin, end), sack) in blocks.zip(sacks.iter_mut()) {
+            *sack = SelectiveAcknowlegement { begin, end };
+            num_sacks += 1;
+        }
+        num_sacks
+    }
+
+    // Iterates over the contiguous ranges of sequence space in the out-of-order store, as [begin, end) pairs.
+    fn out_of_order_blocks(&self) -> impl Iterator<Item = (SeqNumber, SeqNumber)> + '_ {
+        let mut index: usize = 0;
+        ::std::iter::from_fn(move || {
+            let (begin, buf): &(SeqNumber, DemiBuffer) = self.out_of_order_frames.get(index)?;
+            let mut end: SeqNumber = *begin + SeqNumber::from(buf.len() as u32);
+            index += 1;
+            // Adjacent segments are reported as a single block.
+            while let Some((next_begin, next_buf)) = self.out_of_order_frames.get(index) {
+                if *next_begin != end {
+                    break;
+                }
+                end = end + SeqNumber::from(next_buf.len() as u32);
+                index += 1;
+            }
+            Some((*begin, end))
+        })
+    }
+
+    /// Changes when received segments are acknowledged right away. Setting a number of segments applies it to the next
+    /// data segments as well, as if the connection had been idle.
+    pub fn set_quickack(&mut self, quick_ack: QuickAck) {
+        self.quick_ack = quick_ack;
+        self.quick_ack_segments = Self::quick_ack_budget(quick_ack);
+    }
+
+    /// Changes how long and for how many segments ACKs for received data are delayed. An ACK that is already scheduled
+    /// keeps its deadline.
+    pub fn set_delayed_ack(&mut self, delayed_ack: DelayedAck) {
+        (self.ack_delay_timeout_secs, self.ack_stretch_segments) = match delayed_ack {
+            DelayedAck::Default => (self.default_ack_delay_timeout, self.default_ack_stretch_segments),
+            // Acknowledging every single segment leaves nothing for the delayed ACK timer to do.
+            DelayedAck::Disabled => (self.default_ack_delay_timeout, 1),
+            DelayedAck::Enabled { timeout, segments } => (timeout, segments),
+        };
+    }
+
+    // Returns the number of data segments that are acknowledged right away after the connection has been idle.
+    fn quick_ack_budget(quick_ack: QuickAck) -> u32 {
+        match quick_ack {
+            QuickAck::AfterIdle(segments) => segments,
+            QuickAck::Off | QuickAck::Always => 0,
+        }
+    }
+
+    // Checks whether a segment that was received after the connection was [idle] and that carries data if [has_data]
+    // should be acknowledged right away.
+    fn check_quick_ack(&mut self, has_data: bool, idle: bool) -> bool {
+        match self.quick_ack {
+            QuickAck::Off => false,
+            QuickAck::Always => true,
+            QuickAck::AfterIdle(segments) => {
+                if !has_data {
+                    return false;
+                }
+                if idle {
+                    self.quick_ack_segments = segments;
+                }
+                if self.quick_ack_segments == 0 {
+                    return false;
+                }
+                self.quick_ack_segments -= 1;
+                true
+            },
+        }
+    }
+
+    pub fn get_last_receive_time(&self) -> Instant {
+        self.last_receive_time
+    }
+
+    /// Makes pending and future reads fail with [errno].
+    pub fn abort(&mut self, errno: libc::c_int) {
+        self.abort_errno = Some(errno);
+        self.pop_queue.push(DemiBuffer::new(0));
+    }
+
+    /// Drops unread data along with data that arrives later and makes pending and future reads return end-of-file.
+    pub fn shutdown_read(&mut self) {
+        if self.read_shutdown {
+            return;
+        }
+        self.read_shutdown = true;
+        while let Some(buf) = self.pop_queue.try_pop() {
+            self.reader_next_seq_no = self.reader_next_seq_no + SeqNumber::from(buf.len() as u32);
+        }
+        self.pop_queue.push(DemiBuffer::new(0));
+    }
+
+    pub fn get_abort_errno(&self) -> Option<libc::c_int> {
+        self.abort_errno
+    }
+
     pub fn receive_next_seq_no(&self) -> SeqNumber {
         self.receive_next_seq_no
     }
 
+    /// Changes what we do with segments that carry urgent data.
+    pub fn set_urgent_data(&mut self, urgent_data: UrgentData) {
+        self.urgent_data = urgent_data;
+    }
+
+    /// Lets the receive buffer grow up to [max_size_frames] as the application reads faster. The buffer never grows past
+    /// what we can advertise with our window scale factor.
+    pub fn enable_autotuning(&mut self, max_size_frames: u32, now: Instant) {
+        let max_size_frames: u32 = cmp::min(max_size_frames, (u16::MAX as u32) << self.window_scale_shift_bits);
+        if max_size_frames <= self.buffer_size_frames {
+            return;
+        }
+        self.autotuner = Some(Box::new(ReceiveBufferTuner::new(
+            max_size_frames,
+            self.receive_next_seq_no,
+            self.reader_next_seq_no,
+            self.get_receive_window_size(),
+            now,
+        )));
+    }
+
+    /// Grows the receive buffer if the application read enough in the last round trip to make it limit our peer.
+    pub fn autotune(&mut self, now: Instant) {
+        let reader_next: SeqNumber = self.reader_next_seq_no;
+        let buffer_size_frames: u32 = self.buffer_size_frames;
+        if let Some(autotuner) = self.autotuner.as_mut() {
+            if let Some(size_frames) = autotuner.on_read(now, reader_next, buffer_size_frames) {
+                debug!(
+                    "autotune(): growing receive buffer (old={:?}, new={:?})",
+                    buffer_size_frames, size_frames
+                );
+                self.buffer_size_frames = size_frames;
+                // Keep room for as many full-sized segments as before, relative to the size of the receive buffer.
+                let capacity: usize = self.out_of_order_frames.capacity() * size_frames as usize
+                    / cmp::max(buffer_size_frames as usize, 1);
+                self.grow_out_of_order_store(capacity);
+            }
+        }
+    }
+
+    // Makes room in the out-of-order store for [capacity] segments.
+    fn grow_out_of_order_store(&mut self, capacity: usize) {
+        if capacity <= self.out_of_order_frames.capacity() {
+            return;
+        }
+        let mut out_of_order_frames: FixedRing<(SeqNumber, DemiBuffer)> = FixedRing::new(capacity);
+        while let Some(entry) = self.out_of_order_frames.pop_front() {
+            if out_of_order_frames.push_back(entry).is_err() {
+                unreachable!("grown out-of-order store should have room for all stored segments");
+            }
+        }
+        self.out_of_order_frames = out_of_order_frames;
+    }
+
+    /// Returns the number of bytes that are buffered in the pop queue, which the application can pop without waiting.
+    pub fn get_buffered_bytes(&self) -> usize {
+        self.pop_queue.get_values().map(|buf| buf.len()).sum()
+    }
+
     pub fn get_receive_window_size(&self) -> u32 {
         let bytes_unread: u32 = (self.receive_next_seq_no - self.reader_next_seq_no).into();
         self.buffer_size_frames - bytes_unread
     }
 
+    // Describe the receive-side state for a state dump.
+    pub fn dump_state(&self, scrub_payload: bool) -> Value {
+        let pop_queue: Vec<Value> = self
+            .pop_queue
+            .get_values()
+            .map(|buf| dump_payload(buf, scrub_payload))
+            .collect();
+        let out_of_order: Vec<Value> = (0..self.out_of_order_frames.len())
+            .filter_map(|i| self.out_of_order_frames.get(i))
+            .map(|(seq_no, buf)| {
+                json!({
+                    "seq_no": u32::from(*seq_no),
+                    "payload": dump_payload(buf, scrub_payload),
+                })
+            })
+            .collect();
+        json!({
+            "reader_next": u32::from(self.reader_next_seq_no),
+            "receive_next": u32::from(self.receive_next_seq_no),
+            "fin_seq_no": self.fin_seq_no.get().map(u32::from),
+            "out_of_order_bytes": self.out_of_order_bytes,
+            "receive_window": self.get_receive_window_size(),
+            "receive_window_scale": self.window_scale_shift_bits,
+            "buffer_size": self.buffer_size_frames,
+            "autotuning_rtt_us": self.autotuner.as_ref().and_then(|a| a.get_rtt()).map(|rtt| rtt.as_micros() as u64),
+            "unacknowledged_segments": self.unacknowledged_segments,
+            "unacknowledged_bytes": self.unacknowledged_bytes,
+            "ack_delay_timeout_us": self.ack_delay_timeout_secs.as_micros() as u64,
+            "ack_stretch_segments": self.ack_stretch_segments,
+            "pop_queue": pop_queue,
+            "out_of_order": out_of_order,
+        })
+    }
+
     pub fn hdr_window_size(&self) -> u16 {
         let window_size: u32 = self.get_receive_window_size();
         let hdr_window_size: u16 = expect_ok!(
@@ -647,7 +1155,6 @@ impl Receiver {
             // - TCP should implement a delayed ACK
             // - The delay must be less than 500ms
             // - For a stream of full-sized segments, there should be an ack for every other segment.
-            // TODO: Implement SACKs
             match ack_deadline.wait_for_change_until(deadline).await {
                 Ok(value) => {
                     deadline = value;
@@ -6,18 +6,42 @@
 //======================================================================================================================
 
 use crate::{
-    collections::{async_queue::SharedAsyncQueue, async_value::SharedAsyncValue},
-    inetstack::protocols::layer4::tcp::{
-        established::{rto::RtoCalculator, SharedControlBlock},
-        header::TcpHeader,
-        SeqNumber,
+    collections::{
+        async_queue::SharedAsyncQueue,
+        async_value::{select, SharedAsyncValue},
+    },
+    inetstack::protocols::{
+        layer4::{
+            dump_payload,
+            pacer::Pacer,
+            tcp::{
+                established::{
+                    ctrlblk::State,
+                    delivery_rate::{DeliveryRateEstimator, DeliveryState, RateSample},
+                    rack::Rack,
+                    rto::RtoCalculator,
+                    SharedControlBlock,
+                },
+                header::{SelectiveAcknowlegement, TcpHeader},
+                SeqNumber,
+            },
+        },
+        MAX_HEADER_SIZE,
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{
+            consts::MIN_MSS,
+            socket::option::{Pacing, PacingRate},
+        },
+        yield_with_timeout, SharedConditionVariable,
     },
-    runtime::{conditional_yield_until, fail::Fail, memory::DemiBuffer},
 };
-use ::futures::{pin_mut, select_biased, FutureExt};
-use ::libc::{EBUSY, EINVAL};
+use ::libc::{EAGAIN, EBUSY, EINVAL, ENOBUFS, EPIPE};
+use ::serde_json::{json, Value};
 use ::std::{
-    fmt,
+    fmt, mem,
     time::{Duration, Instant},
 };
 use futures::never::Never;
@@ -27,7 +51,8 @@ use std::cmp;
 // Data Structures
 //======================================================================================================================
 
-// Structure of entries on our unacknowledged queue.
+// Structure of entries on our unacknowledged queue. The data is a view into the buffer that was pushed by the
+// application (see DemiBuffer::split_front()), so keeping it around for retransmission does not copy it.
 // TODO: We currently allocate these on the fly when we add a buffer to the queue.  Would be more efficient to have a
 // buffer structure that held everything we need directly, thus avoiding this extra wrapper.
 //
@@ -35,17 +60,77 @@ pub struct UnackedSegment {
     pub bytes: Option<DemiBuffer>,
     // Set to `None` on retransmission to implement Karn's algorithm.
     pub initial_tx: Option<Instant>,
+    // Whether our peer reported this segment as received in a SACK block (RFC 2018), so it must not be retransmitted.
+    pub sacked: bool,
+    // Delivery state of the connection when the segment was last (re)transmitted, for delivery rate estimation.
+    pub delivery: DeliveryState,
 }
 
-// Hard limit for unsent queue.
-// TODO: Remove this.  We should limit the unsent queue by either having a (configurable) send buffer size (in bytes,
-// not segments) and rejecting send requests that exceed that, or by limiting the user's send buffer allocations.
+// Hard limit for unsent queue. The send buffer size bounds the unsent queue in bytes, but it is unbounded by default and
+// does not keep the application from queueing many tiny buffers.
 const UNSENT_QUEUE_CUTOFF: usize = 1024;
 
 // Minimum size for unacknowledged queue. This number doesn't really matter very much, it just sets the initial size
 // of the unacked queue, below which memory allocation is not required.
 const MIN_UNACKED_QUEUE_SIZE_FRAMES: usize = 64;
 
+// Number of full-sized segments that may be sent back-to-back when pacing.
+const PACING_BURST_SEGMENTS: usize = 2;
+
+// Gains, in percent, that are applied to cwnd/SRTT when we derive the pacing rate from the congestion window. Slow start
+// paces faster, so that cwnd can still double every round trip. These match the defaults of Linux.
+const PACING_SLOW_START_GAIN: u64 = 200;
+const PACING_CONGESTION_AVOIDANCE_GAIN: u64 = 120;
+
+// How long a partial segment may be held back while the socket is corked, like the cork timer of Linux.
+const CORK_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Upper bound of the persist timer, which backs off exponentially between window probes while our peer advertises a
+// zero window. This matches the upper bound of the RTO.
+const MAX_PERSIST_TIMEOUT: Duration = Duration::from_secs(60);
+
+// How long the background sender waits before it tries again to send a segment for which the lower layers had no
+// buffers.
+const NO_BUFFERS_RETRY_DELAY: Duration = Duration::from_millis(1);
+
+// State of Forward RTO-Recovery (F-RTO) after a retransmission timeout. See RFC 5682 Section 2.1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FrtoState {
+    // Not recovering from a retransmission timeout.
+    Inactive,
+    // The timed out segment was retransmitted and we are waiting for the first ACK that follows.
+    AwaitingFirstAck { recover: SeqNumber },
+    // New data was sent after the first ACK and we are waiting for the second ACK that follows.
+    AwaitingSecondAck,
+}
+
+/// Events that made us retransmit and reduce the congestion window, which are undone if our peer reports all of the
+/// retransmissions as duplicates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetransmissionCause {
+    Timeout,
+    FastRetransmit,
+}
+
+// Retransmissions after a timeout or fast retransmit that our peer has not yet reported as duplicates. See RFC 3708.
+#[derive(Clone, Copy, Debug)]
+struct DsackUndo {
+    cause: RetransmissionCause,
+    // Sequence space that was outstanding when the retransmissions started.
+    start: SeqNumber,
+    recover: SeqNumber,
+    // Number of retransmissions without a matching duplicate SACK.
+    retransmissions: u32,
+}
+
+// Number of retransmission timeouts in a row after which we suspect that a router drops our segments because they are
+// too large, without telling us (RFC 2923 Section 2.1). Every further run of this many timeouts halves the MSS again.
+const PMTU_BLACKHOLE_RTOS: u8 = 2;
+
+// Number of retransmission timeouts in a row after which we tell the application that the connection may be broken,
+// which is when Linux starts to doubt the route as well (tcp_retries1).
+const WARNING_RTOS: u8 = 3;
+
 // Minimum size for unsent queue. This number doesn't really matter very much, it just sets the initial size
 // of the unacked queue, below which memory allocation is not required.
 const MIN_UNSENT_QUEUE_SIZE_FRAMES: usize = 64;
@@ -80,6 +165,42 @@ pub struct Sender {
     // Retransmission Timeout (RTO) calculator.
     rto_calculator: RtoCalculator,
 
+    // Latest round-trip time sample that has not been handed to congestion control yet.
+    rtt_sample: Option<Duration>,
+
+    // Whether round-trip time samples come from the timestamps option (RFC 7323) instead of from transmission times.
+    timestamp_rtt_samples: bool,
+
+    // Whether we fall back to smaller segments after repeated retransmission timeouts, and the number of timeouts in a
+    // row since our peer last acknowledged new data.
+    pmtu_blackhole_detection: bool,
+    consecutive_rtos: u8,
+
+    // Delivery rate estimation and the latest sample that has not been handed to congestion control yet.
+    delivery_rate: DeliveryRateEstimator,
+    rate_sample: Option<RateSample>,
+
+    // Spaces out segments when the congestion control algorithm asks for pacing, or at a rate derived from cwnd/SRTT if
+    // pacing is enabled for all connections.
+    pacer: Option<Pacer>,
+    pace_by_cwnd: bool,
+
+    // F-RTO state and the number of new bytes that F-RTO allows us to send regardless of cwnd.
+    frto_state: FrtoState,
+    frto_send_allowance_bytes: u32,
+
+    // Retransmissions that can be undone if our peer reports all of them as duplicates.
+    dsack_undo: Option<DsackUndo>,
+
+    // RACK-TLP loss detection (RFC 8985). The loss probe deadline belongs to the RACK reordering timer while segments
+    // wait to be declared lost, and to the tail loss probe timer otherwise.
+    rack: Rack,
+    loss_probe_deadline: SharedAsyncValue<Option<Instant>>,
+    reordering_timer_armed: bool,
+
+    // End of the sequence space that was in flight when we sent the tail loss probe that is still outstanding.
+    tlp_end_seq: Option<SeqNumber>,
+
     // In RFC 793 terms, this is SND.NXT.
     send_next_seq_no: SharedAsyncValue<SeqNumber>,
 
@@ -87,13 +208,43 @@ pub struct Sender {
     // send_next_seq_no.
     unsent_next_seq_no: SeqNumber,
 
+    // Sequence number of next data to be pushed, including data whose push waits for room in the send buffer and is not
+    // in the unsent queue yet. Pushes take their place in the stream in the order in which they were called.
+    reserved_next_seq_no: SeqNumber,
+
+    // Send buffer size, which bounds the data that is unacknowledged or unsent, and the size up to which it grows with
+    // the congestion window. Zero means that the send buffer is unbounded.
+    send_buffer_size: u32,
+    max_send_buffer_size: u32,
+
+    // Wakes up pushes that wait for their turn to go into the unsent queue.
+    send_buffer_wakeup: SharedConditionVariable,
+
+    // Whether the application is done sending, so that a FIN goes out once the data that was pushed before it is queued.
+    fin_requested: bool,
+
     // Sequence number of the FIN, after we should never allocate more sequence numbers.
     fin_seq_no: Option<SeqNumber>,
 
+    // Whether the connection was torn down and its unsent and unacknowledged data thrown away, so that our FIN will
+    // never be acknowledged.
+    discarded: bool,
+
     // This is the send buffer (user data we do not yet have window to send). If the option is None, then it indicates
     // a FIN. This keeps us from having to allocate an empty Demibuffer to indicate FIN.
     unsent_queue: SharedAsyncQueue<Option<DemiBuffer>>,
 
+    // Wakes up the background sender while Nagle's algorithm or the cork holds back a small segment, either because more
+    // data was pushed, because Nagle's algorithm was turned off or the socket was uncorked, or because a FIN was queued.
+    nagle_wakeup: SharedConditionVariable,
+
+    // When the partial segment that the cork holds back has to go out anyway.
+    cork_deadline: Option<Instant>,
+
+    // When we last sent new data. This is cleared once congestion control has been told that the connection went idle,
+    // so that it only restarts from idle once per idle period.
+    last_send_time: Option<Instant>,
+
     // Available window to send into, as advertised by our peer.  In RFC 793 terms, this is SND.WND.
     send_window: SharedAsyncValue<u32>,
     send_window_last_update_seq: SeqNumber, // SND.WL1
@@ -102,8 +253,8 @@ pub struct Sender {
     // RFC 1323: Number of bits to shift advertised window, defaults to zero.
     send_window_scale_shift_bits: u8,
 
-    // Maximum Segment Size currently in use for this connection.
-    // TODO: Revisit this once we support path MTU discovery.
+    // Maximum Segment Size currently in use for this connection. It shrinks when the path turns out to carry smaller
+    // segments only.
     mss: usize,
 }
 
@@ -118,10 +269,34 @@ impl Sender {
             unacked_queue: SharedAsyncQueue::with_capacity(MIN_UNACKED_QUEUE_SIZE_FRAMES),
             retransmit_deadline_time_secs: SharedAsyncValue::new(None),
             rto_calculator: RtoCalculator::new(),
+            rtt_sample: None,
+            timestamp_rtt_samples: false,
+            pmtu_blackhole_detection: false,
+            consecutive_rtos: 0,
+            delivery_rate: DeliveryRateEstimator::new(Instant::now()),
+            rate_sample: None,
+            pacer: None,
+            pace_by_cwnd: false,
+            frto_state: FrtoState::Inactive,
+            frto_send_allowance_bytes: 0,
+            dsack_undo: None,
+            rack: Rack::new(),
+            loss_probe_deadline: SharedAsyncValue::new(None),
+            reordering_timer_armed: false,
+            tlp_end_seq: None,
             send_next_seq_no: SharedAsyncValue::new(seq_no),
             unsent_next_seq_no: seq_no,
+            reserved_next_seq_no: seq_no,
+            send_buffer_size: 0,
+            max_send_buffer_size: 0,
+            send_buffer_wakeup: SharedConditionVariable::default(),
+            fin_requested: false,
             fin_seq_no: None,
+            discarded: false,
             unsent_queue: SharedAsyncQueue::with_capacity(MIN_UNSENT_QUEUE_SIZE_FRAMES),
+            nagle_wakeup: SharedConditionVariable::default(),
+            cork_deadline: None,
+            last_send_time: None,
             send_window: SharedAsyncValue::new(send_window),
             send_window_last_update_seq: seq_no,
             send_window_last_update_ack: seq_no,
@@ -130,10 +305,20 @@ impl Sender {
         }
     }
 
-    // This function sends a packet and waits for it to be acked.
-    pub async fn push(&mut self, mut buf: DemiBuffer, mut cb: SharedControlBlock) -> Result<(), Fail> {
-        // If the user is done sending (i.e. has called close on this connection), then they shouldn't be sending.
-        debug_assert!(self.fin_seq_no.is_none());
+    // Bounds the send buffer to `size` bytes, growing it with the congestion window up to `max_size` bytes. A size of zero
+    // leaves the send buffer unbounded.
+    pub fn set_send_buffer_size(&mut self, size: u32, max_size: u32) {
+        self.send_buffer_size = size;
+        self.max_send_buffer_size = max_size;
+    }
+
+    // This function sends a packet and waits for it to be acked. While the send buffer is full, it first waits for room,
+    // behind pushes that were waiting already. It fails with ENOBUFS if there were no buffers to send the packet, see
+    // [enqueue].
+    pub async fn push(&mut self, buf: DemiBuffer, mut cb: SharedControlBlock) -> Result<(), Fail> {
+        // If the user is done sending (i.e. has called close or shutdown on this connection), then they shouldn't be
+        // sending.
+        self.check_not_finished("push")?;
         // Our API supports send buffers up to usize (variable, depends upon architecture) in size.  While we could
         // allow for larger send buffers, it is simpler and more practical to limit a single send to 1 GiB, which is
         // also the maximum value a TCP can advertise as its receive window (with maximum window scaling).
@@ -151,49 +336,202 @@ impl Sender {
             return Err(Fail::new(EBUSY, "too many packets to send"));
         }
 
-        // Place the buffer in the unsent queue.
-        self.unsent_next_seq_no = self.unsent_next_seq_no + (buf.len() as u32).into();
-        if self.send_window.get() > 0 {
-            self.send_segment(&mut buf, &mut cb);
-        }
-        if buf.len() > 0 {
-            self.unsent_queue.push(Some(buf));
+        // Take our place in the stream, then wait until the data before it is queued and there is room for ours.
+        let start_seq_no: SeqNumber = self.reserved_next_seq_no;
+        let ack_seq_no: SeqNumber = start_seq_no + (buf.len() as u32).into();
+        self.reserved_next_seq_no = ack_seq_no;
+        let cwnd_watched: SharedAsyncValue<u32> = cb.congestion_control_get_cwnd();
+        while !self.can_enqueue(start_seq_no, buf.len(), &cb) {
+            select([&self.send_unacked, &cwnd_watched, &self.send_buffer_wakeup], None).await?;
         }
+        self.enqueue(buf, &mut cb)?;
 
         // Wait until the sequnce number of the pushed buffer is acknowledged.
         let mut send_unacked_watched: SharedAsyncValue<SeqNumber> = self.send_unacked.clone();
-        let ack_seq_no: SeqNumber = self.unsent_next_seq_no;
         debug_assert!(send_unacked_watched.get() < ack_seq_no);
-        while send_unacked_watched.get() < ack_seq_no {
-            send_unacked_watched.wait_for_change(None).await?;
+        send_unacked_watched
+            .wait_for_condition(|send_unacked| *send_unacked >= ack_seq_no, None)
+            .await?;
+        Ok(())
+    }
+
+    // Places the buffer in the unsent queue without waiting for its acknowledgement. Unlike push(), this fails with EAGAIN
+    // instead of waiting when the buffer does not fit into the send buffer or other pushes wait for room already, so that
+    // applications can do their own flow control.
+    pub fn try_push(&mut self, buf: DemiBuffer, mut cb: SharedControlBlock) -> Result<(), Fail> {
+        self.check_not_finished("try_push")?;
+        let len: u32 = buf
+            .len()
+            .try_into()
+            .map_err(|_| Fail::new(EINVAL, "buffer too large"))?;
+        if self.unsent_queue.len() > UNSENT_QUEUE_CUTOFF {
+            return Err(Fail::new(EBUSY, "too many packets to send"));
+        }
+
+        let start_seq_no: SeqNumber = self.reserved_next_seq_no;
+        if !self.can_enqueue(start_seq_no, buf.len(), &cb) {
+            let cause: String = format!("send buffer is full (size={:?})", self.send_buffer_size);
+            debug!("try_push(): {}", cause);
+            return Err(Fail::new(EAGAIN, &cause));
+        }
+        self.reserved_next_seq_no = start_seq_no + len.into();
+        self.enqueue(buf, &mut cb)
+    }
+
+    // Returns whether data that starts at `start_seq_no` can go into the unsent queue. All data before it has to be
+    // queued already and, unless nothing is buffered, it has to fit into the send buffer.
+    fn can_enqueue(&mut self, start_seq_no: SeqNumber, len: usize, cb: &SharedControlBlock) -> bool {
+        if self.unsent_next_seq_no != start_seq_no {
+            return false;
+        }
+        self.autotune_send_buffer(cb.congestion_control_get_cwnd().get());
+        let buffered: u32 = (start_seq_no - self.send_unacked.get()).into();
+        self.send_buffer_size == 0 || buffered == 0 || buffered as usize + len <= self.send_buffer_size as usize
+    }
+
+    // Grows the send buffer to twice the congestion window, so that the unsent queue holds enough data to fill the
+    // congestion window again while the data in flight is being acknowledged.
+    fn autotune_send_buffer(&mut self, cwnd: u32) {
+        if self.send_buffer_size > 0 && self.max_send_buffer_size > self.send_buffer_size {
+            let size: u32 = cmp::max(self.send_buffer_size, cwnd.saturating_mul(2));
+            self.send_buffer_size = cmp::min(size, self.max_send_buffer_size);
+        }
+    }
+
+    // Places the buffer in the unsent queue. We only send it right away if it does not have to wait behind data that was
+    // pushed earlier. If the lower layers have no buffers to send it right away, this fails with ENOBUFS and takes the
+    // buffer out of the stream again, so that the application can push it again later. This is not possible once later
+    // pushes took their place behind it, in which case the buffer stays queued for the background sender.
+    fn enqueue(&mut self, mut buf: DemiBuffer, cb: &mut SharedControlBlock) -> Result<(), Fail> {
+        let start_seq_no: SeqNumber = self.unsent_next_seq_no;
+        let nothing_unsent: bool = start_seq_no == self.send_next_seq_no.get();
+        self.unsent_next_seq_no = start_seq_no + (buf.len() as u32).into();
+        if nothing_unsent
+            && self.send_window.get() > 0
+            && !self.is_held_by_nagle(buf.len(), cb)
+            && !self.is_held_by_cork(buf.len(), cb)
+            && self.try_pace(cmp::min(buf.len(), self.mss), cb).is_ok()
+        {
+            if let Err(e) = self.send_segment(&mut buf, cb) {
+                if self.reserved_next_seq_no == self.unsent_next_seq_no {
+                    self.unsent_next_seq_no = start_seq_no;
+                    self.reserved_next_seq_no = start_seq_no;
+                    self.enqueue_fin_if_requested();
+                    return Err(e);
+                }
+            }
         }
+        if buf.len() > 0 {
+            self.unsent_queue.push(Some(buf));
+            self.nagle_wakeup.signal();
+        }
+        self.enqueue_fin_if_requested();
+        // Let the next push in line check for room.
+        self.send_buffer_wakeup.broadcast();
         Ok(())
     }
 
-    // Places a FIN marker in the outgoing data stream. No data can be pushed after this.
-    pub async fn push_fin_and_wait_for_ack(&mut self) -> Result<(), Fail> {
-        debug_assert!(self.fin_seq_no.is_none());
+    // Fails pushes that come after the application shut down the send direction.
+    fn check_not_finished(&self, caller: &str) -> Result<(), Fail> {
+        if self.fin_requested {
+            let cause: &str = "send direction was shut down";
+            warn!("{}(): {}", caller, cause);
+            return Err(Fail::new(EPIPE, cause));
+        }
+        Ok(())
+    }
+
+    // Waits until at least `min_bytes` can be pushed without queueing behind the send window or the congestion window.
+    // This lets applications hold off on generating data while the connection is blocked.
+    pub async fn wait_for_writable(&mut self, min_bytes: usize, cb: SharedControlBlock) -> Result<(), Fail> {
+        let cwnd_watched: SharedAsyncValue<u32> = cb.congestion_control_get_cwnd();
+        while self.get_writable_bytes(&cb) < min_bytes {
+            select([&self.send_unacked, &self.send_window, &cwnd_watched], None).await?;
+        }
+        Ok(())
+    }
+
+    // Returns how many bytes fit into the smaller of the send window and the congestion window, on top of the data that
+    // is in flight or queued for sending.
+    pub fn get_writable_bytes(&self, cb: &SharedControlBlock) -> usize {
+        let outstanding: u32 = (self.reserved_next_seq_no - self.send_unacked.get()).into();
+        let limit: u32 = cmp::min(self.send_window.get(), cb.congestion_control_get_cwnd().get());
+        limit.saturating_sub(outstanding) as usize
+    }
+
+    // Places a FIN marker in the outgoing data stream, behind pushes that still wait for room in the send buffer. No
+    // data can be pushed after this.
+    pub fn push_fin(&mut self) -> Result<(), Fail> {
+        debug_assert!(!self.fin_requested);
         // TODO: We need to fix this the correct way: limit our send buffer size to the amount we're willing to buffer.
         if self.unsent_queue.len() > UNSENT_QUEUE_CUTOFF {
             return Err(Fail::new(EBUSY, "too many packets to send"));
         }
+        self.fin_requested = true;
+        self.enqueue_fin_if_requested();
+        Ok(())
+    }
 
+    // Throws away the data that is unsent or unacknowledged, along with the FIN, and stops the timers that would send it
+    // again. This is for connections that were reset, which never send anything again.
+    pub fn discard(&mut self) {
+        let unsent: usize = self.unsent_queue.len();
+        self.unsent_queue.drain_front(unsent);
+        let unacked: usize = self.unacked_queue.len();
+        self.unacked_queue.drain_front(unacked);
+        self.retransmit_deadline_time_secs.set(None);
+        self.loss_probe_deadline.set(None);
+        self.cork_deadline = None;
+        // Let a close that waits for the acknowledgement of our FIN know that it will not come.
+        self.discarded = true;
+        self.send_buffer_wakeup.broadcast();
+    }
+
+    // Returns whether the application is done sending.
+    pub fn is_fin_requested(&self) -> bool {
+        self.fin_requested
+    }
+
+    // Waits until our peer acknowledges our FIN.
+    pub async fn wait_for_fin_ack(&mut self) -> Result<(), Fail> {
+        debug_assert!(self.fin_requested);
+        loop {
+            // Stop waiting if the connection is torn down in the meantime.
+            if self.discarded {
+                return Ok(());
+            }
+            if let Some(fin_seq_no) = self.fin_seq_no {
+                if self.send_unacked.get() >= fin_seq_no + 1.into() {
+                    return Ok(());
+                }
+            }
+            select([&self.send_unacked, &self.send_buffer_wakeup], None).await?;
+        }
+    }
+
+    // Queues the FIN once the application asked for it and all data that was pushed before it is queued.
+    fn enqueue_fin_if_requested(&mut self) {
+        if !self.fin_requested || self.fin_seq_no.is_some() || self.unsent_next_seq_no != self.reserved_next_seq_no {
+            return;
+        }
         self.fin_seq_no = Some(self.unsent_next_seq_no);
         self.unsent_next_seq_no = self.unsent_next_seq_no + 1.into();
+        self.reserved_next_seq_no = self.unsent_next_seq_no;
         self.unsent_queue.push(None);
-
-        let mut send_unacked_watched: SharedAsyncValue<SeqNumber> = self.send_unacked.clone();
-        let fin_ack_num: SeqNumber = self.unsent_next_seq_no;
-        while self.send_unacked.get() < fin_ack_num {
-            send_unacked_watched.wait_for_change(None).await?;
-        }
-        Ok(())
+        self.send_buffer_wakeup.broadcast();
+        // Nothing is held back once the connection is closing.
+        self.nagle_wakeup.signal();
     }
 
     pub async fn background_sender(&mut self, mut cb: SharedControlBlock) -> Result<Never, Fail> {
         loop {
             // Get next bit of unsent data.
-            if let Some(buf) = self.unsent_queue.pop(None).await? {
+            let next: Option<DemiBuffer> = self.unsent_queue.pop(None).await?;
+            // Nothing goes out anymore once the connection was reset.
+            if cb.get_state() == State::Closed {
+                continue;
+            }
+            if let Some(buf) = next {
                 self.send_buffer(buf, &mut cb).await?;
             } else {
                 let now: Instant = cb.get_now();
@@ -210,6 +548,7 @@ impl Sender {
         debug_assert!(self.fin_seq_no.is_some_and(|s| { s == header.seq_num }));
         header.fin = true;
         cb.emit(header, None);
+        let delivery: DeliveryState = self.delivery_rate.on_send(now, self.get_bytes_in_flight());
         // Update SND.NXT.
         self.send_next_seq_no.modify(|s| s + 1.into());
 
@@ -217,6 +556,8 @@ impl Sender {
         let unacked_segment = UnackedSegment {
             bytes: None,
             initial_tx: Some(now),
+            sacked: false,
+            delivery,
         };
         self.unacked_queue.push(unacked_segment);
         // Set the retransmit timer.
@@ -229,44 +570,265 @@ impl Sender {
     }
 
     async fn send_buffer(&mut self, mut buffer: DemiBuffer, cb: &mut SharedControlBlock) -> Result<(), Fail> {
-        let mut send_unacked_watched: SharedAsyncValue<SeqNumber> = self.send_unacked.clone();
-        let mut cwnd_watched: SharedAsyncValue<u32> = cb.congestion_control_get_cwnd();
-
-        // The limited transmit algorithm may increase the effective size of cwnd by up to 2 * mss.
-        let mut ltci_watched: SharedAsyncValue<u32> = cb.congestion_control_get_limited_transmit_cwnd_increase();
-        let mut win_sz_watched: SharedAsyncValue<u32> = self.send_window.clone();
-
         // Try in a loop until we send this segment.
         loop {
+            // The connection may have been reset while we waited for room to send.
+            if cb.get_state() == State::Closed {
+                return Ok(());
+            }
             // If we don't have any window size at all, we need to transition to PERSIST mode and
             // repeatedly send window probes until window opens up.
-            if win_sz_watched.get() == 0 {
+            if self.send_window.get() == 0 {
+                // While data is in flight, the ACKs for it (or the retransmission timer) tell us about window updates.
+                // The persist timer only takes over once all of it has been acknowledged.
+                if self.get_bytes_in_flight() > 0 {
+                    self.wait_for_ack_or_open_window().await;
+                    continue;
+                }
                 // Send a window probe (this is a one-byte packet designed to elicit a window update from our peer).
-                self.send_window_probe(buffer.split_front(1)?, cb).await?;
+                self.send_window_probe(Self::split_segment(&mut buffer, 1)?, cb).await?;
+                if buffer.len() == 0 {
+                    return Ok(());
+                }
             } else {
-                // TODO: Nagle's algorithm - We need to coalese small buffers together to send MSS sized packets.
                 // TODO: Silly window syndrome - See RFC 1122's discussion of the SWS avoidance algorithm.
 
-                // We have some window, try to send some or all of the segment.
-                let _: usize = self.send_segment(&mut buffer, cb);
+                // Nagle's algorithm (RFC 1122 Section 4.2.3.4): Coalesce small buffers into full-sized segments and
+                // hold back a segment that is still too small while we have data in flight.
+                if cb.is_nagle_enabled() && buffer.len() < self.mss {
+                    buffer = self.coalesce_unsent(buffer)?;
+                    if self.is_held_by_nagle(buffer.len(), cb) {
+                        self.wait_for_nagle(cb).await;
+                        continue;
+                    }
+                }
+
+                // TCP_CORK: Coalesce small buffers as well, but hold back a segment that is still too small until the
+                // socket is uncorked or the cork timer expires, no matter whether data is in flight.
+                if cb.is_corked() && buffer.len() < self.mss {
+                    buffer = self.coalesce_unsent(buffer)?;
+                    if self.is_held_by_cork(buffer.len(), cb) && self.wait_for_uncork(cb).await {
+                        continue;
+                    }
+                }
+                self.cork_deadline = None;
+
+                // Space out segments if congestion control asks for pacing.
+                self.wait_for_pacing(cmp::min(buffer.len(), self.mss), cb).await;
+
+                // We have some window, try to send some or all of the segment. If the lower layers have no buffers
+                // for it, nothing was sent and we try again a bit later.
+                let sent_bytes: usize = match self.send_segment(&mut buffer, cb) {
+                    Ok(sent_bytes) => sent_bytes,
+                    Err(_) => {
+                        yield_with_timeout(NO_BUFFERS_RETRY_DELAY).await;
+                        continue;
+                    },
+                };
                 // If the buffer is now empty, then we sent all of it.
                 if buffer.len() == 0 {
+                    // Delivery rate samples taken while we run out of data underestimate the bandwidth.
+                    // The same goes for the congestion window, which the path does not validate while we do not use it.
+                    if self.unsent_queue.is_empty() {
+                        self.delivery_rate.on_app_limited(self.get_bytes_in_flight());
+                        cb.congestion_control_on_app_limited(self.get_bytes_in_flight());
+                    }
                     return Ok(());
                 }
-                // Otherwise, wait until something limiting the window changes and then try again to finish sending
-                // the segment.
-                futures::select_biased! {
-                    _ = send_unacked_watched.wait_for_change(None).fuse() => (),
-                    _ = self.send_next_seq_no.wait_for_change(None).fuse() => (),
-                    _ = win_sz_watched.wait_for_change(None).fuse() => (),
-                    _ = cwnd_watched.wait_for_change(None).fuse() => (),
-                    _ = ltci_watched.wait_for_change(None).fuse() => (),
-                };
+                // If the window is still open, keep going. Otherwise, wait until the window opens and then try again
+                // to finish sending the segment.
+                if sent_bytes == 0 {
+                    self.wait_for_open_window(cb).await;
+                }
             }
         }
     }
 
+    // Checks whether Nagle's algorithm holds back a segment of `len` bytes. Small segments have to wait until all data in
+    // flight has been acknowledged, unless we are about to close the connection.
+    fn is_held_by_nagle(&self, len: usize, cb: &SharedControlBlock) -> bool {
+        cb.is_nagle_enabled()
+            && len < self.mss
+            && self.fin_seq_no.is_none()
+            && self.send_next_seq_no.get() != self.send_unacked.get()
+    }
+
+    // Checks whether data is queued that the background sender sends as soon as it runs, so that an ACK that we owe our
+    // peer can go out along with it. Anything else that may still hold the data back, like pacing, is left to the delayed
+    // ACK timer.
+    pub fn has_data_ready_to_send(&self, cb: &SharedControlBlock) -> bool {
+        let queued_bytes: u32 = (self.unsent_next_seq_no - self.send_next_seq_no.get()).into();
+        if queued_bytes == 0
+            || self.is_held_by_nagle(queued_bytes as usize, cb)
+            || self.is_held_by_cork(queued_bytes as usize, cb)
+        {
+            return false;
+        }
+        let sent_data: u32 = (self.send_next_seq_no.get() - self.send_unacked.get()).into();
+        let effective_cwnd: u32 =
+            cb.congestion_control_get_cwnd().get() + cb.congestion_control_get_limited_transmit_cwnd_increase().get();
+        let win_sz: u32 = self.send_window.get();
+        Self::has_open_window(win_sz, sent_data, effective_cwnd)
+            && Self::calculate_open_window_bytes(win_sz, sent_data, self.mss, effective_cwnd) > 0
+    }
+
+    // Checks whether the cork holds back a segment of `len` bytes. Only full-sized segments go out while the socket is
+    // corked, unless we are about to close the connection.
+    fn is_held_by_cork(&self, len: usize, cb: &SharedControlBlock) -> bool {
+        cb.is_corked() && len < self.mss && self.fin_seq_no.is_none()
+    }
+
+    // Waits until more data is pushed, the socket is uncorked or the connection is closing. Returns false if the cork
+    // timer expired instead, in which case the held back segment has to go out as it is.
+    async fn wait_for_uncork(&mut self, cb: &mut SharedControlBlock) -> bool {
+        let now: Instant = cb.get_now();
+        let deadline: Instant = *self.cork_deadline.get_or_insert(now + CORK_TIMEOUT);
+        select([&self.nagle_wakeup], Some(deadline)).await.is_ok()
+    }
+
+    // Appends the buffers at the front of the unsent queue to `buffer`, as long as the result fits into a segment.
+    fn coalesce_unsent(&mut self, buffer: DemiBuffer) -> Result<DemiBuffer, Fail> {
+        let mut len: usize = buffer.len();
+        let mut num_buffers: usize = 0;
+        for buf in self.unsent_queue.get_values() {
+            match buf {
+                Some(buf) if len + buf.len() <= self.mss => {
+                    len += buf.len();
+                    num_buffers += 1;
+                },
+                _ => break,
+            }
+        }
+        if num_buffers == 0 {
+            return Ok(buffer);
+        }
+
+        let mut bytes: Vec<u8> = Vec::with_capacity(len);
+        buffer.segments().for_each(|segment| bytes.extend_from_slice(segment));
+        for _ in 0..num_buffers {
+            if let Some(Some(buf)) = self.unsent_queue.try_pop() {
+                buf.segments().for_each(|segment| bytes.extend_from_slice(segment));
+            }
+        }
+        DemiBuffer::from_slice_with_headroom(&bytes, MAX_HEADER_SIZE)
+    }
+
+    // Waits until Nagle's algorithm may let a held back segment go. That is, until our peer acknowledges data, more data
+    // is pushed or Nagle's algorithm is turned off.
+    async fn wait_for_nagle(&mut self, cb: &mut SharedControlBlock) {
+        while self.is_held_by_nagle(0, cb) {
+            // Without an expiry, this only returns once something changed.
+            match select([&self.send_unacked, &self.nagle_wakeup], None).await {
+                Ok(0) => continue,
+                _ => return,
+            }
+        }
+    }
+
+    // Returns the rate in bytes per second at which we space out segments, if any. The congestion control algorithm
+    // takes precedence, otherwise we spread cwnd over the smoothed round-trip time if enabled.
+    fn get_pacing_rate(&self, cb: &SharedControlBlock) -> Option<u64> {
+        match cb.congestion_control_get_pacing_rate() {
+            Some(rate) => Some(rate),
+            None if self.pace_by_cwnd => {
+                let srtt: Duration = self.rto_calculator.srtt()?;
+                let cwnd: u32 = cb.congestion_control_get_cwnd().get();
+                Some(Self::get_cwnd_pacing_rate(
+                    cwnd,
+                    cb.congestion_control_get_ssthresh(),
+                    srtt,
+                ))
+            },
+            None => None,
+        }
+    }
+
+    // Computes the pacing rate in bytes per second that spreads a window of [cwnd] bytes over [srtt], with a gain that
+    // depends on whether we are in slow start.
+    fn get_cwnd_pacing_rate(cwnd: u32, ssthresh: u32, srtt: Duration) -> u64 {
+        let gain: u64 = if cwnd < ssthresh {
+            PACING_SLOW_START_GAIN
+        } else {
+            PACING_CONGESTION_AVOIDANCE_GAIN
+        };
+        let srtt_us: u64 = cmp::max(srtt.as_micros(), 1).try_into().unwrap_or(u64::MAX);
+        (cwnd as u64 * gain * 10_000) / srtt_us
+    }
+
+    // Checks whether the pacing rate allows us to send a segment of `len` bytes now. Otherwise, returns how long we have
+    // to wait.
+    fn try_pace(&mut self, len: usize, cb: &SharedControlBlock) -> Result<(), Duration> {
+        let rate: u64 = match self.get_pacing_rate(cb) {
+            Some(rate) if rate > 0 => rate,
+            _ => {
+                self.pacer = None;
+                return Ok(());
+            },
+        };
+        let pacing: Pacing = Pacing {
+            rate: PacingRate::BytesPerSecond(rate),
+            burst: (PACING_BURST_SEGMENTS * self.mss) as u64,
+        };
+        let now: Instant = cb.get_now();
+        let pacer: &mut Pacer = self.pacer.get_or_insert_with(|| Pacer::new(pacing, now));
+        pacer.set_pacing(pacing);
+        pacer.try_consume(now, len)
+    }
+
+    // Waits until the pacing rate allows us to send a segment of `len` bytes.
+    async fn wait_for_pacing(&mut self, len: usize, cb: &mut SharedControlBlock) {
+        while let Err(delay) = self.try_pace(len, cb) {
+            yield_with_timeout(delay).await;
+        }
+    }
+
+    // Wakes up the background sender if Nagle's algorithm holds back a segment, so that it checks whether it still
+    // should.
+    pub fn on_nodelay_changed(&mut self) {
+        self.nagle_wakeup.signal();
+    }
+
+    // Wakes up the background sender if the cork holds back a segment, so that it goes out once the socket is uncorked.
+    pub fn on_cork_changed(&mut self) {
+        self.nagle_wakeup.signal();
+    }
+
+    // Waits until we are allowed to send more data or until our peer closes its window. We only wake up for events that
+    // can open the window (i.e. ACKs, window updates and cwnd increases) and go back to sleep if the window is still
+    // closed, so a connection that is blocked on its window does not consume any scheduler iterations.
+    async fn wait_for_open_window(&mut self, cb: &mut SharedControlBlock) {
+        let cwnd_watched: SharedAsyncValue<u32> = cb.congestion_control_get_cwnd();
+        // The limited transmit algorithm may increase the effective size of cwnd by up to 2 * mss.
+        let ltci_watched: SharedAsyncValue<u32> = cb.congestion_control_get_limited_transmit_cwnd_increase();
+
+        loop {
+            // Without an expiry, this only returns once something changed.
+            let _ = select(
+                [&self.send_unacked, &self.send_window, &cwnd_watched, &ltci_watched],
+                None,
+            )
+            .await;
+            // A zero window is handled by the caller with window probes.
+            if self.send_window.get() == 0 || self.get_open_window_size_bytes(cb) > 0 {
+                return;
+            }
+        }
+    }
+
+    // Waits until our peer acknowledges data or opens its window.
+    async fn wait_for_ack_or_open_window(&mut self) {
+        while self.send_window.get() == 0 && self.get_bytes_in_flight() > 0 {
+            // Without an expiry, this only returns once something changed.
+            let _ = select([&self.send_unacked, &self.send_window], None).await;
+        }
+    }
+
+    // Sends the [probe] byte beyond the zero window of our peer and keeps retransmitting it on the persist timer until
+    // our peer opens its window (RFC 9293 Section 3.8.6.1). We keep probing as long as it takes, since a peer that
+    // answers the probes with a zero window is alive, just slow to read.
     async fn send_window_probe(&mut self, probe: DemiBuffer, cb: &mut SharedControlBlock) -> Result<(), Fail> {
+        let delivery: DeliveryState = self.delivery_rate.on_send(cb.get_now(), self.get_bytes_in_flight());
+        let probe_seq_no: SeqNumber = self.send_next_seq_no.get();
         // Update SND.NXT.
         self.send_next_seq_no.modify(|s| s + SeqNumber::from(1));
 
@@ -274,37 +836,61 @@ impl Sender {
         let unacked_segment = UnackedSegment {
             bytes: Some(probe.clone()),
             initial_tx: Some(cb.get_now()),
+            sacked: false,
+            delivery,
         };
         self.unacked_queue.push(unacked_segment);
 
-        // Note that we loop here *forever*, exponentially backing off.
-        // TODO: Use the correct PERSIST mode timer here.
-        let mut timeout: Duration = Duration::from_secs(1);
+        // The persist timer starts out at the RTO and backs off exponentially from there.
+        let mut timeout: Duration = self.rto_calculator.rto();
         let mut win_sz_watched: SharedAsyncValue<u32> = self.send_window.clone();
         loop {
             // Create packet.
             let mut header: TcpHeader = cb.tcp_header();
-            header.seq_num = self.send_next_seq_no.get();
+            header.seq_num = probe_seq_no;
             cb.emit(header, Some(probe.clone()));
+            cb.get_mut_stats().window_probes_sent += 1;
 
-            match win_sz_watched.wait_for_change(Some(timeout)).await {
+            match win_sz_watched
+                .wait_for_condition(|window| *window > 0, Some(timeout))
+                .await
+            {
                 Ok(_) => return Ok(()),
-                Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT => timeout *= 2,
-                Err(_) => unreachable!(
-                    "either the ack deadline changed or the deadline passed, no other errors are possible!"
-                ),
+                Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT => {
+                    timeout = Self::back_off_persist_timeout(timeout)
+                },
+                Err(_) => unreachable!("either the window opened or the timer expired, no other errors are possible!"),
             }
         }
     }
 
+    // Returns the persist timeout that follows [timeout] when a window probe goes unanswered.
+    fn back_off_persist_timeout(timeout: Duration) -> Duration {
+        cmp::min(timeout * 2, MAX_PERSIST_TIMEOUT)
+    }
+
+    // Splits the first `offset` bytes off `buffer`. The headers of a segment go into the room in front of its data, which
+    // for the rest of `buffer` is where the data that was split off lives. So the rest gets a zero-length buffer of its
+    // own in front of it to take the headers, as otherwise sending it would overwrite data that we may have to
+    // retransmit. MBufs can only be chained with MBufs, so those keep sharing the room in front of them.
+    fn split_segment(buffer: &mut DemiBuffer, offset: usize) -> Result<DemiBuffer, Fail> {
+        let front: DemiBuffer = buffer.split_front(offset)?;
+        if !buffer.is_empty() && buffer.is_heap_allocated() {
+            let rest: DemiBuffer = mem::replace(buffer, DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16));
+            buffer.append(rest)?;
+        }
+        Ok(front)
+    }
+
     // Takes a segment and attempts to send it. The buffer must be non-zero length and the function returns the number
-    // of bytes sent.
-    fn send_segment(&mut self, segment: &mut DemiBuffer, cb: &mut SharedControlBlock) -> usize {
+    // of bytes sent. If the lower layers have no buffers for the segment, this fails with ENOBUFS and leaves both the
+    // buffer and the state of the sender as they were.
+    fn send_segment(&mut self, segment: &mut DemiBuffer, cb: &mut SharedControlBlock) -> Result<usize, Fail> {
         let buf_len: usize = segment.len();
         debug_assert_ne!(buf_len, 0);
         // Check window size.
         let max_frame_size_bytes: usize = match self.get_open_window_size_bytes(cb) {
-            0 => return 0,
+            0 => return Ok(0),
             size => size,
         };
 
@@ -319,39 +905,58 @@ impl Sender {
                 (buf_len, true)
             }
         };
-        let segment_data: DemiBuffer = segment
-            .split_front(frame_size_bytes)
-            .expect("Should be able to split within the length of the buffer");
+        // Only take the data out of the buffer once it was sent.
+        let segment_data: DemiBuffer = match frame_size_bytes < buf_len {
+            true => segment
+                .clone()
+                .split_front(frame_size_bytes)
+                .expect("Should be able to split within the length of the buffer"),
+            false => segment.clone(),
+        };
 
         let segment_data_len: u32 = segment_data.len() as u32;
 
-        let rto: Duration = self.rto_calculator.rto();
-        cb.congestion_control_on_send(rto, (self.send_next_seq_no.get() - self.send_unacked.get()).into());
-
         // Prepare the segment and send it.
         let mut header: TcpHeader = cb.tcp_header();
         header.seq_num = self.send_next_seq_no.get();
         if do_push {
             header.psh = true;
         }
-        cb.emit(header, Some(segment_data.clone()));
+        if let Err(e) = cb.emit_new_data(header, segment_data.clone()) {
+            // Any other failure is handled like the loss of the segment.
+            if e.errno == ENOBUFS {
+                cb.get_mut_stats().no_buffer_failures += 1;
+                return Err(e);
+            }
+        }
+        Self::split_segment(segment, frame_size_bytes)
+            .expect("Should be able to split within the length of the buffer");
+
+        let rto: Duration = self.rto_calculator.rto();
+        cb.congestion_control_on_send(rto, (self.send_next_seq_no.get() - self.send_unacked.get()).into());
+        let delivery: DeliveryState = self.delivery_rate.on_send(cb.get_now(), self.get_bytes_in_flight());
+        self.last_send_time = Some(cb.get_now());
 
         // Update SND.NXT.
         self.send_next_seq_no.modify(|s| s + SeqNumber::from(segment_data_len));
+        self.frto_send_allowance_bytes = self.frto_send_allowance_bytes.saturating_sub(segment_data_len);
 
         // Put this segment on the unacknowledged list.
         let unacked_segment = UnackedSegment {
             bytes: Some(segment_data),
             initial_tx: Some(cb.get_now()),
+            sacked: false,
+            delivery,
         };
         self.unacked_queue.push(unacked_segment);
 
-        // Set the retransmit timer.
+        // Set the retransmit timer and the tail loss probe timer, which goes off first.
         if self.retransmit_deadline_time_secs.get().is_none() {
             let rto: Duration = self.rto_calculator.rto();
             self.retransmit_deadline_time_secs.set(Some(cb.get_now() + rto));
         }
-        segment_data_len as usize
+        self.arm_tail_loss_probe(cb.get_now());
+        Ok(segment_data_len as usize)
     }
 
     fn get_open_window_size_bytes(&mut self, cb: &mut SharedControlBlock) -> usize {
@@ -360,12 +965,30 @@ impl Sender {
         let send_next: SeqNumber = self.send_next_seq_no.get();
         let sent_data: u32 = (send_next - send_unacknowledged).into();
 
-        // Before we get cwnd for the check, we prompt it to shrink it if the connection has been idle.
-        cb.congestion_control_on_cwnd_check_before_send();
+        // Before we get cwnd for the check, we prompt it to shrink it if the connection has been idle. The ACK clock is
+        // still running while data is in flight, so we are only idle if nothing was sent for more than one RTO after
+        // everything was acknowledged (RFC 5681 Section 4.1). Sockets that opted in keep their cwnd if we pace them, since
+        // the pacer already spreads the window out instead of sending it in one burst.
+        let rto: Duration = self.rto_calculator.rto();
+        if sent_data == 0 {
+            if let Some(last_send_time) = self.last_send_time {
+                let now: Instant = cb.get_now();
+                let idle_time: Duration = now.saturating_duration_since(last_send_time);
+                if idle_time > rto {
+                    self.last_send_time = None;
+                    if !(cb.keeps_cwnd_after_idle_when_paced() && self.get_pacing_rate(cb).is_some()) {
+                        cb.congestion_control_on_idle_restart(now, rto, idle_time);
+                    }
+                }
+            }
+        }
+        cb.congestion_control_on_cwnd_check_before_send(rto, sent_data);
         let cwnd: SharedAsyncValue<u32> = cb.congestion_control_get_cwnd();
 
         // The limited transmit algorithm can increase the effective size of cwnd by up to 2MSS.
         let effective_cwnd: u32 = cwnd.get() + cb.congestion_control_get_limited_transmit_cwnd_increase().get();
+        // F-RTO may allow us to send new data beyond cwnd to probe whether a timeout was spurious.
+        let effective_cwnd: u32 = cmp::max(effective_cwnd, sent_data + self.frto_send_allowance_bytes);
 
         let win_sz: u32 = self.send_window.get();
 
@@ -389,36 +1012,59 @@ impl Sender {
 
     pub async fn background_retransmitter(&mut self, mut cb: SharedControlBlock) -> Result<Never, Fail> {
         // Watch the retransmission deadline.
-        let mut rtx_deadline_watched: SharedAsyncValue<Option<Instant>> = self.retransmit_deadline_time_secs.clone();
+        let rtx_deadline_watched: SharedAsyncValue<Option<Instant>> = self.retransmit_deadline_time_secs.clone();
         // Watch the fast retransmit flag.
-        let mut rtx_fast_retransmit_watched: SharedAsyncValue<bool> = cb.congestion_control_watch_retransmit_now_flag();
+        let rtx_fast_retransmit_watched: SharedAsyncValue<bool> = cb.congestion_control_watch_retransmit_now_flag();
+        // Watch the RACK reordering and tail loss probe deadline.
+        let loss_probe_deadline_watched: SharedAsyncValue<Option<Instant>> = self.loss_probe_deadline.clone();
         loop {
             let rtx_deadline: Option<Instant> = rtx_deadline_watched.get();
             let rtx_fast_retransmit: bool = rtx_fast_retransmit_watched.get();
+            let loss_probe_deadline: Option<Instant> = loss_probe_deadline_watched.get();
             if rtx_fast_retransmit {
                 // Notify congestion control about fast retransmit.
                 cb.congestion_control_on_fast_retransmit();
 
-                // Retransmit earliest unacknowledged segment.
+                // Retransmit earliest unacknowledged segment, along with the other holes that SACK blocks revealed.
+                let mut num_retransmitted: u32 = match self.unacked_queue.is_empty() {
+                    true => 0,
+                    false => 1,
+                };
                 self.retransmit(&mut cb);
+                num_retransmitted += self.retransmit_sack_holes(&mut cb);
+                self.count_undoable_retransmissions(RetransmissionCause::FastRetransmit, num_retransmitted);
                 continue;
             }
 
-            // If either changed, wake up.
-            let something_changed = async {
-                select_biased!(
-                    _ = rtx_deadline_watched.wait_for_change(None).fuse() => (),
-                    _ = rtx_fast_retransmit_watched.wait_for_change(None).fuse() => (),
-                )
+            // The loss probe timer never goes off after the retransmission timer.
+            let (deadline, loss_probe_first): (Option<Instant>, bool) = match (rtx_deadline, loss_probe_deadline) {
+                (Some(rtx_deadline), Some(loss_probe_deadline)) if loss_probe_deadline < rtx_deadline => {
+                    (Some(loss_probe_deadline), true)
+                },
+                (None, Some(loss_probe_deadline)) => (Some(loss_probe_deadline), true),
+                (rtx_deadline, _) => (rtx_deadline, false),
             };
-            pin_mut!(something_changed);
-            match conditional_yield_until(something_changed, rtx_deadline).await {
-                Ok(()) => match self.fin_seq_no {
+
+            // If any changed, wake up.
+            match select(
+                [
+                    &rtx_deadline_watched,
+                    &rtx_fast_retransmit_watched,
+                    &loss_probe_deadline_watched,
+                ],
+                deadline,
+            )
+            .await
+            {
+                Ok(_) => match self.fin_seq_no {
                     Some(fin_seq_no) if self.send_unacked.get() > fin_seq_no => {
                         return Err(Fail::new(libc::ECONNRESET, "connection closed"));
                     },
                     _ => continue,
                 },
+                Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT && loss_probe_first => {
+                    self.on_loss_probe_timeout(&mut cb);
+                },
                 Err(Fail { errno, cause: _ }) if errno == libc::ETIMEDOUT => {
                     // Retransmit timeout.
                     trace!("retransmit wake");
@@ -426,6 +1072,41 @@ impl Sender {
                     // TODO: Is this the best place for this?
                     // TODO: Why call into ControlBlock to get SND.UNA when congestion_control_on_rto() has access to it?
                     cb.congestion_control_on_rto(self.send_unacked.get());
+                    cb.get_mut_stats().retransmission_timeouts += 1;
+
+                    // RFC 5682 Section 2.1: Start F-RTO, unless we are still recovering from a previous timeout.
+                    self.frto_state = match self.frto_state {
+                        FrtoState::Inactive => FrtoState::AwaitingFirstAck {
+                            recover: self.send_next_seq_no.get(),
+                        },
+                        _ => FrtoState::Inactive,
+                    };
+                    self.frto_send_allowance_bytes = 0;
+
+                    // The timeout takes over from RACK-TLP, which starts over with the next ACK.
+                    self.tlp_end_seq = None;
+                    self.reordering_timer_armed = false;
+                    self.loss_probe_deadline.set(None);
+
+                    // Count the retransmission, so that we can detect if our peer reports all of them as duplicates.
+                    self.count_undoable_retransmissions(RetransmissionCause::Timeout, 1);
+
+                    // RFC 2018 Section 8: Our peer may have discarded data that it reported in SACK blocks, so forget
+                    // about them and retransmit everything from the earliest unacknowledged segment on.
+                    for segment in self.unacked_queue.get_mut_values() {
+                        segment.sacked = false;
+                    }
+
+                    // A router that silently drops segments that are too large for it shows up as timeouts in a row.
+                    self.consecutive_rtos = self.consecutive_rtos.saturating_add(1);
+                    if self.pmtu_blackhole_detection && self.consecutive_rtos % PMTU_BLACKHOLE_RTOS == 0 {
+                        self.probe_smaller_mss(&mut cb);
+                    }
+                    if self.consecutive_rtos >= WARNING_RTOS {
+                        let cause: String = format!("{} retransmission timeouts in a row", self.consecutive_rtos);
+                        warn!("background_retransmitter(): {}", cause);
+                        cb.set_last_error(Fail::new(libc::ETIMEDOUT, &cause));
+                    }
 
                     // RFC 6298 Section 5.4: Retransmit earliest unacknowledged segment.
                     self.retransmit(&mut cb);
@@ -448,12 +1129,14 @@ impl Sender {
 
     /// Retransmits the earliest segment that has not (yet) been acknowledged by our peer.
     pub fn retransmit(&mut self, cb: &mut SharedControlBlock) {
+        let bytes_in_flight: u32 = self.get_bytes_in_flight();
         match self.unacked_queue.get_front_mut() {
             Some(segment) => {
                 // We're retransmitting this, so we can no longer use an ACK for it as an RTT measurement (as we can't
                 // tell if the ACK is for the original or the retransmission).  Remove the transmission timestamp from
                 // the entry.
                 segment.initial_tx.take();
+                segment.delivery = self.delivery_rate.on_send(cb.get_now(), bytes_in_flight);
 
                 // Clone the segment data for retransmission.
                 let data: Option<DemiBuffer> = segment.bytes.as_ref().map(|b| b.clone());
@@ -470,13 +1153,379 @@ impl Sender {
                     header.fin = true;
                 }
                 cb.emit(header, data);
+                cb.get_mut_stats().retransmitted_segments += 1;
             },
             None => (),
         }
     }
 
-    // Process an ack.
-    pub fn process_ack(&mut self, header: &TcpHeader, now: Instant) {
+    // Retransmits the segments that were not SACKed by our peer although later data was, as these are most likely lost.
+    // The earliest unacknowledged segment is retransmitted by [Sender::retransmit], and every other segment is only
+    // retransmitted once, so repeated fast retransmits do not flood the network with copies. The retransmissions are
+    // limited to one congestion window. Returns the number of segments that were retransmitted.
+    fn retransmit_sack_holes(&mut self, cb: &mut SharedControlBlock) -> u32 {
+        // The highest sequence number that our peer reported in a SACK block. Segments after it are not known to be lost.
+        let mut highest_sacked: Option<SeqNumber> = None;
+        let mut seq_no: SeqNumber = self.send_unacked.get();
+        for segment in self.unacked_queue.get_values() {
+            seq_no = seq_no + SeqNumber::from(segment.bytes.as_ref().map_or(1, |data| data.len() as u32));
+            if segment.sacked {
+                highest_sacked = Some(seq_no);
+            }
+        }
+        let highest_sacked: SeqNumber = match highest_sacked {
+            Some(highest_sacked) => highest_sacked,
+            None => return 0,
+        };
+
+        let mut budget_bytes: u32 = cb.congestion_control_get_cwnd().get();
+        let bytes_in_flight: u32 = self.get_bytes_in_flight();
+        let mut seq_no: SeqNumber = self.send_unacked.get();
+        let mut num_retransmitted: u32 = 0;
+        for (index, segment) in self.unacked_queue.get_mut_values().enumerate() {
+            let segment_len: u32 = segment.bytes.as_ref().map_or(1, |data| data.len() as u32);
+            let segment_seq_no: SeqNumber = seq_no;
+            seq_no = seq_no + SeqNumber::from(segment_len);
+            if seq_no > highest_sacked {
+                break;
+            }
+            // Skip the earliest segment and those that were already retransmitted or SACKed.
+            if index == 0 || segment.sacked || segment.initial_tx.is_none() {
+                continue;
+            }
+            if budget_bytes < segment_len {
+                break;
+            }
+            segment.initial_tx.take();
+            segment.delivery = self.delivery_rate.on_send(cb.get_now(), bytes_in_flight);
+            budget_bytes -= segment_len;
+            num_retransmitted += 1;
+
+            Self::emit_retransmission(cb, segment_seq_no, segment);
+        }
+        cb.get_mut_stats().sack_retransmissions += num_retransmitted as u64;
+        cb.get_mut_stats().retransmitted_segments += num_retransmitted as u64;
+        num_retransmitted
+    }
+
+    // Sends the segment that starts at [seq_no] again.
+    fn emit_retransmission(cb: &mut SharedControlBlock, seq_no: SeqNumber, segment: &UnackedSegment) {
+        let mut header: TcpHeader = cb.tcp_header();
+        header.seq_num = seq_no;
+        match segment.bytes.as_ref() {
+            Some(data) => {
+                header.psh = true;
+                cb.emit(header, Some(data.clone()));
+            },
+            None => {
+                header.fin = true;
+                cb.emit(header, None);
+            },
+        }
+    }
+
+    /// Runs RACK loss detection (RFC 8985 Section 6.2) after an ACK and its SACK blocks were processed at [now]. The
+    /// segments that were sent a round trip plus the reordering window before the most recently delivered one are
+    /// retransmitted, as far as the congestion window allows. Segments that may only have been reordered arm the
+    /// reordering timer, and the tail loss probe timer is armed if there are none.
+    pub fn detect_lost_segments(&mut self, now: Instant, cb: &mut SharedControlBlock) {
+        let sacked_segments: usize = self.unacked_queue.get_values().filter(|segment| segment.sacked).count();
+        let reo_wnd: Duration = self
+            .rack
+            .get_reordering_window(self.rto_calculator.srtt(), sacked_segments);
+
+        let mut budget_bytes: u32 = cb.congestion_control_get_cwnd().get();
+        let bytes_in_flight: u32 = self.get_bytes_in_flight();
+        let mut reordering_deadline: Option<Instant> = None;
+        let mut seq_no: SeqNumber = self.send_unacked.get();
+        let mut num_retransmitted: u64 = 0;
+        for segment in self.unacked_queue.get_mut_values() {
+            let segment_len: u32 = segment.bytes.as_ref().map_or(1, |data| data.len() as u32);
+            let segment_seq_no: SeqNumber = seq_no;
+            seq_no = seq_no + SeqNumber::from(segment_len);
+            if segment.sacked {
+                continue;
+            }
+            match self
+                .rack
+                .get_loss_deadline(segment.delivery.get_sent_time(), seq_no, reo_wnd)
+            {
+                Some(deadline) if deadline <= now => {
+                    if budget_bytes < segment_len {
+                        break;
+                    }
+                    budget_bytes -= segment_len;
+                    num_retransmitted += 1;
+                    segment.initial_tx.take();
+                    segment.delivery = self.delivery_rate.on_send(now, bytes_in_flight);
+                    Self::emit_retransmission(cb, segment_seq_no, segment);
+                },
+                Some(deadline) => {
+                    reordering_deadline = Some(reordering_deadline.map_or(deadline, |d| cmp::min(d, deadline)));
+                },
+                None => (),
+            }
+        }
+        // TODO: Let congestion control react to losses that RACK detects before enough duplicate ACKs arrive.
+        cb.get_mut_stats().rack_retransmissions += num_retransmitted;
+        cb.get_mut_stats().retransmitted_segments += num_retransmitted;
+        // Restart the retransmission timer if the earliest segment was retransmitted, so that it does not go off for it
+        // right away.
+        if num_retransmitted > 0 {
+            let retransmit_deadline: Option<Instant> = self.update_retransmit_deadline(now);
+            self.retransmit_deadline_time_secs.set(retransmit_deadline);
+        }
+
+        match reordering_deadline {
+            Some(deadline) => {
+                self.reordering_timer_armed = true;
+                self.loss_probe_deadline.set(Some(deadline));
+            },
+            None => {
+                self.reordering_timer_armed = false;
+                self.arm_tail_loss_probe(now);
+            },
+        }
+    }
+
+    // Arms the tail loss probe timer (RFC 8985 Section 7.2), unless the reordering timer is armed, a probe is already
+    // outstanding or the retransmission timer would go off first.
+    fn arm_tail_loss_probe(&mut self, now: Instant) {
+        if self.reordering_timer_armed {
+            return;
+        }
+        let deadline: Option<Instant> = match (self.tlp_end_seq, self.retransmit_deadline_time_secs.get()) {
+            (None, Some(rtx_deadline)) if !self.unacked_queue.is_empty() => {
+                let pto: Duration = Rack::get_probe_timeout(self.rto_calculator.srtt(), self.unacked_queue.len());
+                Some(now + pto).filter(|deadline| *deadline < rtx_deadline)
+            },
+            _ => None,
+        };
+        self.loss_probe_deadline.set(deadline);
+    }
+
+    // Handles the expiration of the loss probe deadline, which either ends the wait for reordered segments or sends a
+    // tail loss probe.
+    fn on_loss_probe_timeout(&mut self, cb: &mut SharedControlBlock) {
+        let now: Instant = cb.get_now();
+        self.loss_probe_deadline.set(None);
+        if self.reordering_timer_armed {
+            self.reordering_timer_armed = false;
+            self.detect_lost_segments(now, cb);
+        } else {
+            self.send_tail_loss_probe(cb);
+        }
+    }
+
+    // Retransmits the last unacknowledged segment, so that the ACK for it reveals whether any of the segments before it
+    // were lost (RFC 8985 Section 7.3). The RFC prefers new data for the probe, but the background sender already sends
+    // all the data that the windows allow. Afterwards, the retransmission timer takes over.
+    fn send_tail_loss_probe(&mut self, cb: &mut SharedControlBlock) {
+        let now: Instant = cb.get_now();
+        let bytes_in_flight: u32 = self.get_bytes_in_flight();
+        let send_next: SeqNumber = self.send_next_seq_no.get();
+        let segment: &mut UnackedSegment = match self.unacked_queue.get_mut_values().last() {
+            Some(segment) => segment,
+            None => return,
+        };
+        let segment_len: u32 = segment.bytes.as_ref().map_or(1, |data| data.len() as u32);
+        segment.initial_tx.take();
+        segment.delivery = self.delivery_rate.on_send(now, bytes_in_flight);
+        Self::emit_retransmission(cb, send_next - SeqNumber::from(segment_len), segment);
+        cb.get_mut_stats().tail_loss_probes += 1;
+        cb.get_mut_stats().retransmitted_segments += 1;
+
+        self.tlp_end_seq = Some(send_next);
+        self.retransmit_deadline_time_secs
+            .set(Some(now + self.rto_calculator.rto()));
+    }
+
+    // Marks the unacknowledged segments that are completely covered by [blocks] as SACKed. This must be called after the
+    // cumulative acknowledgement of the same ACK was processed.
+    pub fn process_sack(&mut self, blocks: &[SelectiveAcknowlegement], now: Instant) {
+        let mut seq_no: SeqNumber = self.send_unacked.get();
+        for segment in self.unacked_queue.get_mut_values() {
+            let segment_start: SeqNumber = seq_no;
+            seq_no = seq_no + SeqNumber::from(segment.bytes.as_ref().map_or(1, |data| data.len() as u32));
+            if !segment.sacked
+                && blocks
+                    .iter()
+                    .any(|block| block.begin <= segment_start && seq_no <= block.end)
+            {
+                segment.sacked = true;
+                self.rack.on_delivered(
+                    segment.delivery.get_sent_time(),
+                    seq_no,
+                    segment.initial_tx.is_none(),
+                    now,
+                );
+            }
+        }
+    }
+
+    // Runs the F-RTO algorithm (RFC 5682 Section 2.1) on an incoming ACK. This must be called before the ACK is
+    // processed. Returns true if the ACK shows that the last retransmission timeout was spurious.
+    pub fn process_frto(&mut self, ack_num: SeqNumber) -> bool {
+        let acks_new_data: bool = self.send_unacked.get() < ack_num;
+        match self.frto_state {
+            FrtoState::Inactive => false,
+            FrtoState::AwaitingFirstAck { recover } => {
+                // Step 2b: The ACK advances the window without covering everything that was outstanding at the time of
+                // the timeout, so we send up to two new segments to find out if the timeout was spurious. Otherwise,
+                // fall back to the conventional recovery (step 2a).
+                let has_unsent_data: bool = self.unsent_next_seq_no > self.send_next_seq_no.get();
+                if acks_new_data && ack_num < recover && has_unsent_data {
+                    self.frto_state = FrtoState::AwaitingSecondAck;
+                    self.frto_send_allowance_bytes = 2 * self.mss as u32;
+                } else {
+                    self.frto_state = FrtoState::Inactive;
+                }
+                false
+            },
+            FrtoState::AwaitingSecondAck => {
+                // Step 3: If this ACK also advances the window, then it acknowledges data that was not retransmitted, so
+                // the timeout was spurious (step 3b). A duplicate ACK means that the timeout was genuine (step 3a).
+                self.frto_state = FrtoState::Inactive;
+                self.frto_send_allowance_bytes = 0;
+                if acks_new_data {
+                    // The timeout is undone now, so duplicate SACKs must not undo it again.
+                    self.dsack_undo = None;
+                }
+                acks_new_data
+            },
+        }
+    }
+
+    // Counts [count] retransmissions because of [cause], so that we can detect if our peer reports all of them as
+    // duplicates. Retransmissions for the same cause belong to the same episode until everything that was outstanding
+    // when it started is acknowledged.
+    fn count_undoable_retransmissions(&mut self, cause: RetransmissionCause, count: u32) {
+        if count == 0 {
+            return;
+        }
+        let send_unacked: SeqNumber = self.send_unacked.get();
+        self.dsack_undo = match self.dsack_undo {
+            Some(undo) if undo.cause == cause && send_unacked < undo.recover => Some(DsackUndo {
+                retransmissions: undo.retransmissions + count,
+                ..undo
+            }),
+            _ => Some(DsackUndo {
+                cause,
+                start: send_unacked,
+                recover: self.send_next_seq_no.get(),
+                retransmissions: count,
+            }),
+        };
+    }
+
+    // Processes a duplicate SACK block for [begin, end) that our peer sent us. Returns the cause of the last
+    // retransmissions if this shows that all of them were unnecessary, so the window reduction was spurious (RFC 3708).
+    pub fn process_dsack(&mut self, begin: SeqNumber, end: SeqNumber) -> Option<RetransmissionCause> {
+        match self.dsack_undo.as_mut() {
+            // Ignore duplicates that we did not cause by retransmitting, e.g. those duplicated by the network.
+            Some(undo) if undo.start <= begin && end <= undo.recover => {
+                undo.retransmissions -= 1;
+                if undo.retransmissions == 0 {
+                    let cause: RetransmissionCause = undo.cause;
+                    self.dsack_undo = None;
+                    return Some(cause);
+                }
+                None
+            },
+            _ => None,
+        }
+    }
+
+    // Takes round-trip time samples from the timestamps option from now on. These also cover retransmitted segments, so
+    // Karn's algorithm is no longer needed.
+    pub fn enable_timestamp_rtt_samples(&mut self) {
+        self.timestamp_rtt_samples = true;
+    }
+
+    pub fn enable_pmtu_blackhole_detection(&mut self) {
+        self.pmtu_blackhole_detection = true;
+    }
+
+    pub fn enable_pacing(&mut self) {
+        self.pace_by_cwnd = true;
+    }
+
+    /// Shrinks our segments to [mss] bytes, because a router on the path reported that it dropped a larger one (RFC
+    /// 1191). The unacknowledged segments that do not fit anymore were dropped as well, so we split them up and send
+    /// them again right away. This is not a sign of congestion, so the congestion window stays as it is.
+    pub fn on_fragmentation_needed(&mut self, mss: usize, cb: &mut SharedControlBlock) {
+        if mss >= self.mss {
+            return;
+        }
+        debug!(
+            "on_fragmentation_needed(): reducing mss (old={:?}, new={:?})",
+            self.mss, mss
+        );
+        self.mss = mss;
+        self.resegment_unacked(cb, true);
+    }
+
+    // Halves the MSS (down to the minimum that every path has to carry) if the earliest unacknowledged segment does not fit
+    // anymore, so that the retransmission after a run of timeouts probes whether smaller segments get through.
+    fn probe_smaller_mss(&mut self, cb: &mut SharedControlBlock) {
+        let mss: usize = cmp::max(self.mss / 2, MIN_MSS);
+        let front_len: usize = match self.unacked_queue.get_front() {
+            Some(UnackedSegment { bytes: Some(data), .. }) => data.len(),
+            _ => return,
+        };
+        if mss >= self.mss || front_len <= mss {
+            return;
+        }
+        warn!(
+            "probe_smaller_mss(): suspecting a path MTU black hole, reducing mss (old={:?}, new={:?})",
+            self.mss, mss
+        );
+        self.mss = mss;
+        self.resegment_unacked(cb, false);
+    }
+
+    // Splits the unacknowledged segments that are larger than the MSS, and sends the pieces right away if [retransmit] is
+    // set. The pieces count as retransmissions, so they do not give RTT samples.
+    fn resegment_unacked(&mut self, cb: &mut SharedControlBlock, retransmit: bool) {
+        let now: Instant = cb.get_now();
+        let bytes_in_flight: u32 = self.get_bytes_in_flight();
+        let mut seq_no: SeqNumber = self.send_unacked.get();
+        let mut segments: Vec<UnackedSegment> = Vec::with_capacity(self.unacked_queue.len());
+        while let Some(segment) = self.unacked_queue.try_pop() {
+            let mut data: DemiBuffer = match segment.bytes {
+                Some(ref data) if data.len() > self.mss && !segment.sacked => data.clone(),
+                _ => {
+                    seq_no = seq_no + SeqNumber::from(segment.bytes.as_ref().map_or(1, |data| data.len() as u32));
+                    segments.push(segment);
+                    continue;
+                },
+            };
+            while !data.is_empty() {
+                let piece_len: usize = cmp::min(self.mss, data.len());
+                let piece: DemiBuffer = Self::split_segment(&mut data, piece_len)
+                    .expect("Should be able to split within the length of the buffer");
+                if retransmit {
+                    let mut header: TcpHeader = cb.tcp_header();
+                    header.seq_num = seq_no;
+                    header.psh = data.is_empty();
+                    cb.emit(header, Some(piece.clone()));
+                }
+                seq_no = seq_no + SeqNumber::from(piece.len() as u32);
+                segments.push(UnackedSegment {
+                    bytes: Some(piece),
+                    initial_tx: None,
+                    sacked: false,
+                    delivery: self.delivery_rate.on_send(now, bytes_in_flight),
+                });
+            }
+        }
+        for segment in segments {
+            self.unacked_queue.push(segment);
+        }
+    }
+
+    // Process an ack. [timestamp_rtt] is the round-trip time sample from the timestamps option of the ACK, if any.
+    pub fn process_ack(&mut self, header: &TcpHeader, now: Instant, timestamp_rtt: Option<Duration>) {
         // Start by checking that the ACK acknowledges something new.
         // TODO: Look into removing Watched types.
         let send_unacknowledged: SeqNumber = self.send_unacked.get();
@@ -487,21 +1536,25 @@ impl Sender {
 
             // Convert the difference in sequence numbers into a u32.
             let bytes_acknowledged: u32 = (header.ack_num - self.send_unacked.get()).into();
-            // Convert that into a usize for counting bytes to remove from the unacked queue.
-            let mut bytes_remaining: usize = bytes_acknowledged as usize;
-            // Remove bytes from the unacked queue.
-            while bytes_remaining != 0 {
-                bytes_remaining = match self.unacked_queue.try_pop() {
-                    Some(segment) if segment.bytes.is_none() => self.process_acked_fin(bytes_remaining, header.ack_num),
-                    Some(segment) => self.process_acked_segment(bytes_remaining, segment, now),
-                    None => {
-                        unreachable!("There should be enough data in the unacked_queue for the number of bytes acked")
-                    }, // Shouldn't have bytes_remaining with no segments remaining in unacked_queue.
-                };
+            // Remove the acknowledged bytes from the unacked queue.
+            self.remove_acknowledged_bytes(bytes_acknowledged as usize, now);
+            // Only ACKs that acknowledge new data give valid samples (RFC 7323 Section 4.2).
+            if let Some(rtt) = timestamp_rtt.filter(|_| self.timestamp_rtt_samples) {
+                self.add_rtt_sample(rtt);
             }
 
             // Update SND.UNA to SEG.ACK.
             self.send_unacked.set(header.ack_num);
+            self.consecutive_rtos = 0;
+
+            // The tail loss probe episode ends once everything that was in flight at the time is acknowledged.
+            // TODO: Reduce the congestion window if the probe repaired a loss (RFC 8985 Section 7.4).
+            if self
+                .tlp_end_seq
+                .is_some_and(|tlp_end_seq| header.ack_num >= tlp_end_seq)
+            {
+                self.tlp_end_seq = None;
+            }
 
             // Check and update send window if necessary.
             self.update_send_window(header);
@@ -521,60 +1574,97 @@ impl Sender {
         }
     }
 
-    fn process_acked_fin(&mut self, bytes_remaining: usize, ack_num: SeqNumber) -> usize {
-        // This buffer is the end-of-send marker.  So we should only have one byte of acknowledged
-        // sequence space remaining (corresponding to our FIN).
-        debug_assert_eq!(bytes_remaining, 1);
-
-        // Double check that the ack is for the FIN sequence number.
-        debug_assert_eq!(
-            ack_num,
-            self.fin_seq_no
-                .map(|s| { s + 1.into() })
-                .expect("should have a FIN set")
+    // Removes [bytes_acknowledged] bytes from the front of the unacked queue. Cumulative ACKs may acknowledge many
+    // segments at once, so we find all of the fully acknowledged segments in a single pass and release them in bulk.
+    fn remove_acknowledged_bytes(&mut self, bytes_acknowledged: usize, now: Instant) {
+        let mut num_acked_segments: usize = 0;
+        let mut fully_acked_bytes: usize = 0;
+        let mut oldest_tx: Option<Instant> = None;
+        // Delivery state of the most recently sent segment that this ACK covers.
+        let mut newest_delivery: Option<DeliveryState> = None;
+        for segment in self.unacked_queue.get_values() {
+            // The FIN takes up one byte of sequence space.
+            let segment_len: usize = segment.bytes.as_ref().map_or(1, |data| data.len());
+            if fully_acked_bytes + segment_len > bytes_acknowledged {
+                break;
+            }
+            debug_assert!(segment.bytes.is_some() || fully_acked_bytes + 1 == bytes_acknowledged);
+            fully_acked_bytes += segment_len;
+            num_acked_segments += 1;
+            if !segment.sacked {
+                let end_seq: SeqNumber = self.send_unacked.get() + SeqNumber::from(fully_acked_bytes as u32);
+                self.rack.on_delivered(
+                    segment.delivery.get_sent_time(),
+                    end_seq,
+                    segment.initial_tx.is_none(),
+                    now,
+                );
+            }
+            oldest_tx = oldest_tx.or(segment.initial_tx);
+            newest_delivery = Self::newer_delivery(newest_delivery, segment.delivery);
+        }
+        debug_assert!(
+            num_acked_segments < self.unacked_queue.len() || fully_acked_bytes == bytes_acknowledged,
+            "There should be enough data in the unacked_queue for the number of bytes acked"
         );
-        0
-    }
+        self.unacked_queue.drain_front(num_acked_segments);
+
+        // The ACK may also cover the beginning of the next segment. Trim the acknowledged bytes off its front, leaving
+        // the rest of it on the unacked queue. This only adjusts the view into the pushed buffer, so it neither copies
+        // nor allocates.
+        let bytes_remaining: usize = bytes_acknowledged - fully_acked_bytes;
+        if bytes_remaining > 0 {
+            let segment: &mut UnackedSegment = self
+                .unacked_queue
+                .get_front_mut()
+                .expect("there should be a partially acknowledged segment");
+            // The rest of the segment was sent at the same time, so it must not be used for another sample.
+            oldest_tx = oldest_tx.or(segment.initial_tx.take());
+            newest_delivery = Self::newer_delivery(newest_delivery, segment.delivery);
+            segment
+                .bytes
+                .as_mut()
+                .expect("there should be data because this is not a FIN.")
+                .adjust(bytes_remaining)
+                .expect("Should be able to adjust because we just checked the length");
+        }
 
-    fn process_acked_segment(&mut self, bytes_remaining: usize, mut segment: UnackedSegment, now: Instant) -> usize {
-        // Add sample for RTO if we have an initial transmit time.
+        // Add sample for RTO if we have an initial transmit time. We take a single sample per ACK from the oldest
+        // segment that it acknowledges.
         // Note that in the case of repacketization, an ack for the first byte is enough for the time sample because it still represents the RTO for that single byte.
-        // TODO: TCP timestamp support.
-        if let Some(initial_tx) = segment.initial_tx {
-            self.rto_calculator.add_sample(now - initial_tx);
-        }
-
-        let mut data: DemiBuffer = segment
-            .bytes
-            .take()
-            .expect("there should be data because this is not a FIN.");
-        if data.len() > bytes_remaining {
-            // Put this segment on the unacknowledged list.
-            let unacked_segment = UnackedSegment {
-                bytes: Some(
-                    data.split_back(bytes_remaining)
-                        .expect("Should be able to split back because we just checked the length"),
-                ),
-                initial_tx: None,
-            };
-            // Leave this segment on the unacknowledged queue.
-            self.unacked_queue.push_front(unacked_segment);
-            0
-        } else {
-            bytes_remaining - data.len()
+        // With timestamps, the caller takes the sample from the ACK instead.
+        if let Some(initial_tx) = oldest_tx.filter(|_| !self.timestamp_rtt_samples) {
+            self.add_rtt_sample(now - initial_tx);
+        }
+
+        if let Some(newest_delivery) = newest_delivery {
+            let sample: RateSample = self
+                .delivery_rate
+                .on_ack(now, bytes_acknowledged as u32, &newest_delivery);
+            self.rate_sample = Some(sample);
         }
     }
 
+    // Returns whichever of the delivery states belongs to the segment that was sent last.
+    fn newer_delivery(newest: Option<DeliveryState>, delivery: DeliveryState) -> Option<DeliveryState> {
+        match newest {
+            Some(newest) if newest.get_sent_time() > delivery.get_sent_time() => Some(newest),
+            _ => Some(delivery),
+        }
+    }
+
+    fn add_rtt_sample(&mut self, rtt: Duration) {
+        self.rto_calculator.add_sample(rtt);
+        self.rtt_sample = Some(rtt);
+    }
+
     fn update_retransmit_deadline(&self, now: Instant) -> Option<Instant> {
         match self.unacked_queue.get_front() {
             Some(UnackedSegment {
-                bytes: _,
                 initial_tx: Some(initial_tx),
+                ..
             }) => Some(*initial_tx + self.rto_calculator.rto()),
-            Some(UnackedSegment {
-                bytes: _,
-                initial_tx: None,
-            }) => Some(now + self.rto_calculator.rto()),
+            Some(UnackedSegment { initial_tx: None, .. }) => Some(now + self.rto_calculator.rto()),
             None => None,
         }
     }
@@ -609,10 +1699,78 @@ impl Sender {
         self.send_next_seq_no.get()
     }
 
+    // Get the latest RTT sample, if one was taken since the last call.
+    pub fn take_rtt_sample(&mut self) -> Option<Duration> {
+        self.rtt_sample.take()
+    }
+
+    // Get the latest delivery rate sample, if one was taken since the last call.
+    pub fn take_rate_sample(&mut self) -> Option<RateSample> {
+        self.rate_sample.take()
+    }
+
+    // Get the amount of data in flight (SND.NXT - SND.UNA).
+    pub fn get_bytes_in_flight(&self) -> u32 {
+        (self.send_next_seq_no.get() - self.send_unacked.get()).into()
+    }
+
     // Get the current estimate of RTO.
     pub fn get_rto(&self) -> Duration {
         self.rto_calculator.rto()
     }
+
+    // Get the smoothed RTT, if one was measured yet.
+    pub fn get_srtt(&self) -> Option<Duration> {
+        self.rto_calculator.srtt()
+    }
+
+    // Get the maximum segment size that we send.
+    pub fn get_mss(&self) -> usize {
+        self.mss
+    }
+
+    // Describe the send-side state for a state dump.
+    pub fn dump_state(&self, scrub_payload: bool) -> Value {
+        let unacked_queue: Vec<Value> = self
+            .unacked_queue
+            .get_values()
+            .map(|segment| {
+                let payload: Value = match segment.bytes.as_ref() {
+                    Some(bytes) => dump_payload(bytes, scrub_payload),
+                    None => Value::Null,
+                };
+                json!({
+                    "payload": payload,
+                    "sacked": segment.sacked,
+                })
+            })
+            .collect();
+        let unsent_queue: Vec<Value> = self
+            .unsent_queue
+            .get_values()
+            .map(|buf| match buf.as_ref() {
+                Some(buf) => dump_payload(buf, scrub_payload),
+                None => Value::Null,
+            })
+            .collect();
+        json!({
+            "send_unacked": u32::from(self.send_unacked.get()),
+            "send_next": u32::from(self.send_next_seq_no.get()),
+            "unsent_next": u32::from(self.unsent_next_seq_no),
+            "reserved_next": u32::from(self.reserved_next_seq_no),
+            "send_buffer_size": self.send_buffer_size,
+            "fin_seq_no": self.fin_seq_no.map(u32::from),
+            "send_window": self.send_window.get(),
+            "send_window_scale": self.send_window_scale_shift_bits,
+            "send_window_last_update_seq": u32::from(self.send_window_last_update_seq),
+            "send_window_last_update_ack": u32::from(self.send_window_last_update_ack),
+            "mss": self.mss,
+            "rto_ms": self.rto_calculator.rto().as_millis() as u64,
+            "retransmit_timer_armed": self.retransmit_deadline_time_secs.get().is_some(),
+            "unacked_queue": unacked_queue,
+            "unsent_queue": unsent_queue,
+        })
+    }
 }
 
 //======================================================================================================================
@@ -630,3 +1788,323 @@ impl fmt::Debug for Sender {
             .finish()
     }
 }
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{RetransmissionCause, Sender, UnackedSegment};
+    use crate::{
+        inetstack::protocols::layer4::tcp::{
+            established::delivery_rate::RateSample,
+            header::{SelectiveAcknowlegement, TcpHeader},
+            SeqNumber,
+        },
+        runtime::memory::DemiBuffer,
+    };
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+    use ::test::{black_box, Bencher};
+
+    const MSS: usize = 1460;
+
+    fn push_segments(sender: &mut Sender, data: &DemiBuffer, num_segments: usize, now: Instant) {
+        for _ in 0..num_segments {
+            sender.unacked_queue.push(UnackedSegment {
+                bytes: Some(data.clone()),
+                initial_tx: Some(now),
+                sacked: false,
+                delivery: sender.delivery_rate.on_send(now, 0),
+            });
+        }
+    }
+
+    #[test]
+    fn test_remove_acknowledged_bytes() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let data: DemiBuffer = DemiBuffer::new(MSS as u16);
+        let now: Instant = Instant::now();
+        push_segments(&mut sender, &data, 3, now);
+
+        // Acknowledge the first segment and half of the second one.
+        sender.remove_acknowledged_bytes(MSS + MSS / 2, now);
+        crate::ensure_eq!(sender.unacked_queue.len(), 2);
+        crate::ensure_eq!(
+            sender.unacked_queue.get_front().unwrap().bytes.as_ref().unwrap().len(),
+            MSS / 2
+        );
+        crate::ensure_eq!(sender.unacked_queue.get_front().unwrap().initial_tx, None);
+        crate::ensure_eq!(sender.take_rtt_sample().is_some(), true);
+
+        // Acknowledge the rest.
+        sender.remove_acknowledged_bytes(MSS / 2 + MSS, now);
+        crate::ensure_eq!(sender.unacked_queue.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_ack_trims_in_place() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let data: DemiBuffer = DemiBuffer::from_slice(&[1; MSS])?;
+        let now: Instant = Instant::now();
+        push_segments(&mut sender, &data, 1, now);
+
+        // The rest of a partially acknowledged segment is still a view into the pushed buffer.
+        sender.remove_acknowledged_bytes(MSS / 2, now);
+        let rest: &DemiBuffer = sender.unacked_queue.get_front().unwrap().bytes.as_ref().unwrap();
+        crate::ensure_eq!(rest.len(), MSS - MSS / 2);
+        crate::ensure_eq!(rest.as_ptr(), data[MSS / 2..].as_ptr());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delivery_rate_sample() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let data: DemiBuffer = DemiBuffer::new(MSS as u16);
+        let now: Instant = Instant::now();
+        push_segments(&mut sender, &data, 3, now);
+
+        // A cumulative ACK for two segments gives a single sample that covers both of them.
+        let rtt: Duration = Duration::from_millis(10);
+        sender.remove_acknowledged_bytes(2 * MSS, now + rtt);
+        let sample: RateSample = sender.take_rate_sample().unwrap();
+        crate::ensure_eq!(sample.delivered, 2 * MSS as u64);
+        crate::ensure_eq!(sample.newly_acked, 2 * MSS as u32);
+        crate::ensure_eq!(sample.interval, rtt);
+        crate::ensure_eq!(sender.take_rate_sample(), None);
+
+        Ok(())
+    }
+
+    /// Sends [num_rounds] segments one after another over a path with a round-trip time of [rtt], where every segment
+    /// has to be retransmitted once, and returns the resulting RTO. The ACKs echo the timestamp of the retransmission.
+    fn lossy_link_rto(timestamp_rtt_samples: bool, num_rounds: u32, rtt: Duration) -> Duration {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        if timestamp_rtt_samples {
+            sender.enable_timestamp_rtt_samples();
+        }
+        let data: DemiBuffer = DemiBuffer::new(MSS as u16);
+        let mut now: Instant = Instant::now();
+        for round in 1..=num_rounds {
+            push_segments(&mut sender, &data, 1, now);
+            sender.send_next_seq_no.set(SeqNumber::from(round * MSS as u32));
+            // The segment is lost and retransmitted, so Karn's algorithm cannot use it.
+            sender.unacked_queue.get_front_mut().unwrap().initial_tx.take();
+            now += rtt;
+
+            let mut header: TcpHeader = TcpHeader::new(0, 0);
+            header.ack = true;
+            header.ack_num = SeqNumber::from(round * MSS as u32);
+            sender.process_ack(&header, now, Some(rtt));
+        }
+        sender.get_rto()
+    }
+
+    #[test]
+    fn test_timestamp_rtt_samples_on_lossy_link() -> Result<()> {
+        let rtt: Duration = Duration::from_millis(200);
+        // Without timestamps, the RTO never leaves its initial value because every sample is discarded.
+        crate::ensure_eq!(lossy_link_rto(false, 10, rtt), Duration::from_secs(1));
+        // With timestamps, the RTO converges to the RTT of the path.
+        let rto: Duration = lossy_link_rto(true, 10, rtt);
+        crate::ensure_eq!(rto > rtt, true);
+        crate::ensure_eq!(rto < Duration::from_millis(300), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamp_rtt_samples_match_karn_without_loss() -> Result<()> {
+        let rtt: Duration = Duration::from_millis(50);
+        let mut karn: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let mut timestamps: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        timestamps.enable_timestamp_rtt_samples();
+        let data: DemiBuffer = DemiBuffer::new(MSS as u16);
+        let mut now: Instant = Instant::now();
+        for round in 1..=5 {
+            for sender in [&mut karn, &mut timestamps] {
+                push_segments(sender, &data, 1, now);
+                sender.send_next_seq_no.set(SeqNumber::from(round * MSS as u32));
+            }
+            now += rtt;
+            let mut header: TcpHeader = TcpHeader::new(0, 0);
+            header.ack = true;
+            header.ack_num = SeqNumber::from(round * MSS as u32);
+            karn.process_ack(&header, now, Some(rtt));
+            timestamps.process_ack(&header, now, Some(rtt));
+            // Both estimators take exactly one sample per ACK.
+            crate::ensure_eq!(karn.take_rtt_sample(), Some(rtt));
+            crate::ensure_eq!(timestamps.take_rtt_sample(), Some(rtt));
+        }
+        crate::ensure_eq!(karn.get_rto(), timestamps.get_rto());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_sack() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let data: DemiBuffer = DemiBuffer::new(MSS as u16);
+        let now: Instant = Instant::now();
+        push_segments(&mut sender, &data, 4, now);
+
+        // Report the third segment and only half of the fourth one as received.
+        let block: SelectiveAcknowlegement = SelectiveAcknowlegement {
+            begin: SeqNumber::from(2 * MSS as u32),
+            end: SeqNumber::from((3 * MSS + MSS / 2) as u32),
+        };
+        sender.process_sack(&[block], now);
+        let sacked: Vec<bool> = sender
+            .unacked_queue
+            .get_values()
+            .map(|segment| segment.sacked)
+            .collect();
+        crate::ensure_eq!(sacked, vec![false, false, true, false]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rack_loss_deadline_from_sack() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let data: DemiBuffer = DemiBuffer::new(MSS as u16);
+        let start: Instant = Instant::now();
+        let interval: Duration = Duration::from_millis(1);
+        for i in 0..3 {
+            push_segments(&mut sender, &data, 1, start + i * interval);
+        }
+
+        // The third segment arrives one round trip after it was sent, so the first one is lost one round trip plus the
+        // reordering window after it was sent. The ACK for the second one may still be on its way.
+        let rtt: Duration = Duration::from_millis(40);
+        let block: SelectiveAcknowlegement = SelectiveAcknowlegement {
+            begin: SeqNumber::from(2 * MSS as u32),
+            end: SeqNumber::from(3 * MSS as u32),
+        };
+        sender.process_sack(&[block], start + 2 * interval + rtt);
+        let reo_wnd: Duration = sender.rack.get_reordering_window(None, 1);
+        crate::ensure_eq!(reo_wnd, rtt / 4);
+        crate::ensure_eq!(
+            sender
+                .rack
+                .get_loss_deadline(start, SeqNumber::from(MSS as u32), reo_wnd),
+            Some(start + rtt + reo_wnd)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coalesce_unsent() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        for byte in 1..=3 {
+            sender.unsent_queue.push(Some(DemiBuffer::from_slice(&[byte; 100])?));
+        }
+        sender.unsent_queue.push(Some(DemiBuffer::new(MSS as u16)));
+
+        // The small buffers are appended to the first one, but the full-sized one does not fit anymore.
+        let coalesced: DemiBuffer = sender.coalesce_unsent(DemiBuffer::from_slice(&[0; 100])?)?;
+        crate::ensure_eq!(coalesced.len(), 400);
+        crate::ensure_eq!(coalesced[100], 1);
+        crate::ensure_eq!(coalesced[399], 3);
+        crate::ensure_eq!(sender.unsent_queue.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_buffer_autotuning() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+
+        // An unbounded send buffer stays unbounded.
+        sender.autotune_send_buffer(10 * MSS as u32);
+        crate::ensure_eq!(sender.send_buffer_size, 0);
+
+        // The send buffer grows to twice the congestion window, but never shrinks or grows past its upper bound.
+        sender.set_send_buffer_size(16 * 1024, 64 * 1024);
+        sender.autotune_send_buffer(4 * 1024);
+        crate::ensure_eq!(sender.send_buffer_size, 16 * 1024);
+        sender.autotune_send_buffer(12 * 1024);
+        crate::ensure_eq!(sender.send_buffer_size, 24 * 1024);
+        sender.autotune_send_buffer(48 * 1024);
+        crate::ensure_eq!(sender.send_buffer_size, 64 * 1024);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_persist_timeout_backoff() -> Result<()> {
+        let mut timeout: Duration = Duration::from_millis(200);
+        timeout = Sender::back_off_persist_timeout(timeout);
+        crate::ensure_eq!(timeout, Duration::from_millis(400));
+
+        // The timeout stops growing at the upper bound, but we keep probing.
+        for _ in 0..16 {
+            timeout = Sender::back_off_persist_timeout(timeout);
+        }
+        crate::ensure_eq!(timeout, Duration::from_secs(60));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cwnd_pacing_rate() -> Result<()> {
+        let srtt: Duration = Duration::from_millis(10);
+        // 100 KB per 10 ms make 10 MB/s, which slow start doubles.
+        crate::ensure_eq!(Sender::get_cwnd_pacing_rate(100_000, u32::MAX, srtt), 20_000_000);
+        crate::ensure_eq!(Sender::get_cwnd_pacing_rate(100_000, 50_000, srtt), 12_000_000);
+        // A round-trip time below the timer resolution does not divide by zero.
+        crate::ensure_eq!(
+            Sender::get_cwnd_pacing_rate(1_000, u32::MAX, Duration::ZERO),
+            2_000 * 1_000_000
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dsack_undo() -> Result<()> {
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let mss: u32 = MSS as u32;
+        sender.send_next_seq_no.set(SeqNumber::from(4 * mss));
+
+        // A fast retransmit resends two segments, so our peer has to report both of them as duplicates.
+        sender.count_undoable_retransmissions(RetransmissionCause::FastRetransmit, 2);
+        crate::ensure_eq!(sender.process_dsack(SeqNumber::from(0), SeqNumber::from(mss)), None);
+        // Duplicates beyond what was outstanding were not caused by us.
+        crate::ensure_eq!(
+            sender.process_dsack(SeqNumber::from(4 * mss), SeqNumber::from(5 * mss)),
+            None
+        );
+        crate::ensure_eq!(
+            sender.process_dsack(SeqNumber::from(2 * mss), SeqNumber::from(3 * mss)),
+            Some(RetransmissionCause::FastRetransmit)
+        );
+
+        // A timeout starts a new episode, which only the timeout's own retransmissions count towards.
+        sender.count_undoable_retransmissions(RetransmissionCause::FastRetransmit, 1);
+        sender.count_undoable_retransmissions(RetransmissionCause::Timeout, 1);
+        crate::ensure_eq!(
+            sender.process_dsack(SeqNumber::from(0), SeqNumber::from(mss)),
+            Some(RetransmissionCause::Timeout)
+        );
+
+        Ok(())
+    }
+
+    /// Processes a cumulative ACK that acknowledges 10k segments at once. This includes refilling the unacked queue.
+    #[bench]
+    fn bench_bulk_ack_trimming(b: &mut Bencher) {
+        const NUM_SEGMENTS: usize = 10_000;
+        let mut sender: Sender = Sender::new(SeqNumber::from(0), u32::MAX, 0, MSS);
+        let data: DemiBuffer = DemiBuffer::new(MSS as u16);
+
+        b.iter(|| {
+            let now: Instant = Instant::now();
+            push_segments(&mut sender, &data, NUM_SEGMENTS, now);
+            sender.remove_acknowledged_bytes(black_box(NUM_SEGMENTS * MSS), now);
+        });
+    }
+}
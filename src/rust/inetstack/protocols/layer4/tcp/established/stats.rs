@@ -0,0 +1,142 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::inetstack::protocols::layer4::{stats_delta::Counters, tcp::congestion_control::OptionValue};
+use ::serde_json::{json, Map, Value};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Per-connection TCP statistics.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Number of segments that we retransmitted, for whatever reason.
+    pub retransmitted_segments: u64,
+    /// Number of retransmission timeouts.
+    pub retransmission_timeouts: u64,
+    /// Number of retransmission timeouts detected as spurious, either by F-RTO (RFC 5682) or DSACK (RFC 3708).
+    pub spurious_retransmission_timeouts: u64,
+    /// Number of fast retransmits whose retransmissions our peer all reported as duplicates (RFC 3708).
+    pub spurious_fast_retransmits: u64,
+    /// Number of duplicate SACK blocks that we sent to report duplicate data (RFC 2883).
+    pub duplicate_sacks_sent: u64,
+    /// Number of duplicate SACK blocks that our peer sent to report duplicate data (RFC 2883).
+    pub duplicate_sacks_received: u64,
+    /// Number of SACK blocks that we sent to report out-of-order data (RFC 2018).
+    pub sack_blocks_sent: u64,
+    /// Number of SACK blocks that our peer sent to report out-of-order data (RFC 2018).
+    pub sack_blocks_received: u64,
+    /// Number of segments that we retransmitted because SACK blocks showed that they were missing.
+    pub sack_retransmissions: u64,
+    /// Number of segments that we retransmitted because RACK declared them lost (RFC 8985).
+    pub rack_retransmissions: u64,
+    /// Number of tail loss probes that we sent because the ACKs for the last segments in flight did not arrive.
+    pub tail_loss_probes: u64,
+    /// Number of segments that were dropped because their timestamp was older than the one that we echo (RFC 7323).
+    pub paws_rejections: u64,
+    /// Number of segments that arrived ahead of the next expected sequence number and were queued out of order.
+    pub out_of_order_segments_received: u64,
+    /// Number of out-of-order segments that we dropped, or evicted later, because they did not fit in the receive window.
+    pub out_of_order_segments_dropped: u64,
+    /// Number of keepalive probes that we sent to check whether an idle peer is still there.
+    pub keepalive_probes_sent: u64,
+    /// Number of window probes that we sent while our peer advertised a zero window, including repeated ones.
+    pub window_probes_sent: u64,
+    /// Number of segments that arrived in packets that a router marked with congestion experienced (RFC 3168).
+    pub congestion_experienced_received: u64,
+    /// Number of times that we reduced the congestion window because our peer echoed a congestion experienced mark.
+    pub ecn_window_reductions: u64,
+    /// Number of times that a new data segment could not be sent because the lower layers ran out of buffers.
+    pub no_buffer_failures: u64,
+    /// Number of ACKs that we owed our peer and sent along with data rather than in a segment of their own.
+    pub piggybacked_acks: u64,
+    /// Internal state of the congestion control algorithm, as reported by the algorithm itself.
+    pub congestion_control: Vec<(&'static str, OptionValue)>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl Stats {
+    /// Describes these statistics for a state dump.
+    pub fn dump_state(&self) -> Value {
+        let congestion_control: Map<String, Value> = self
+            .congestion_control
+            .iter()
+            .map(|(name, value)| {
+                let value: Value = match value {
+                    OptionValue::Bool(b) => json!(b),
+                    OptionValue::Float(f) => json!(f),
+                    OptionValue::Int(i) => json!(i),
+                    OptionValue::String(s) => json!(s),
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        json!({
+            "retransmitted_segments": self.retransmitted_segments,
+            "retransmission_timeouts": self.retransmission_timeouts,
+            "spurious_retransmission_timeouts": self.spurious_retransmission_timeouts,
+            "spurious_fast_retransmits": self.spurious_fast_retransmits,
+            "duplicate_sacks_sent": self.duplicate_sacks_sent,
+            "duplicate_sacks_received": self.duplicate_sacks_received,
+            "sack_blocks_sent": self.sack_blocks_sent,
+            "sack_blocks_received": self.sack_blocks_received,
+            "sack_retransmissions": self.sack_retransmissions,
+            "rack_retransmissions": self.rack_retransmissions,
+            "tail_loss_probes": self.tail_loss_probes,
+            "paws_rejections": self.paws_rejections,
+            "out_of_order_segments_received": self.out_of_order_segments_received,
+            "out_of_order_segments_dropped": self.out_of_order_segments_dropped,
+            "keepalive_probes_sent": self.keepalive_probes_sent,
+            "window_probes_sent": self.window_probes_sent,
+            "congestion_experienced_received": self.congestion_experienced_received,
+            "ecn_window_reductions": self.ecn_window_reductions,
+            "no_buffer_failures": self.no_buffer_failures,
+            "piggybacked_acks": self.piggybacked_acks,
+            "congestion_control": congestion_control,
+        })
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl Counters for Stats {
+    fn delta(&self, baseline: &Self) -> Self {
+        Self {
+            retransmitted_segments: self.retransmitted_segments - baseline.retransmitted_segments,
+            retransmission_timeouts: self.retransmission_timeouts - baseline.retransmission_timeouts,
+            spurious_retransmission_timeouts: self.spurious_retransmission_timeouts
+                - baseline.spurious_retransmission_timeouts,
+            spurious_fast_retransmits: self.spurious_fast_retransmits - baseline.spurious_fast_retransmits,
+            duplicate_sacks_sent: self.duplicate_sacks_sent - baseline.duplicate_sacks_sent,
+            duplicate_sacks_received: self.duplicate_sacks_received - baseline.duplicate_sacks_received,
+            sack_blocks_sent: self.sack_blocks_sent - baseline.sack_blocks_sent,
+            sack_blocks_received: self.sack_blocks_received - baseline.sack_blocks_received,
+            sack_retransmissions: self.sack_retransmissions - baseline.sack_retransmissions,
+            rack_retransmissions: self.rack_retransmissions - baseline.rack_retransmissions,
+            tail_loss_probes: self.tail_loss_probes - baseline.tail_loss_probes,
+            paws_rejections: self.paws_rejections - baseline.paws_rejections,
+            out_of_order_segments_received: self.out_of_order_segments_received
+                - baseline.out_of_order_segments_received,
+            out_of_order_segments_dropped: self.out_of_order_segments_dropped - baseline.out_of_order_segments_dropped,
+            keepalive_probes_sent: self.keepalive_probes_sent - baseline.keepalive_probes_sent,
+            window_probes_sent: self.window_probes_sent - baseline.window_probes_sent,
+            congestion_experienced_received: self.congestion_experienced_received
+                - baseline.congestion_experienced_received,
+            ecn_window_reductions: self.ecn_window_reductions - baseline.ecn_window_reductions,
+            no_buffer_failures: self.no_buffer_failures - baseline.no_buffer_failures,
+            piggybacked_acks: self.piggybacked_acks - baseline.piggybacked_acks,
+            // The state of the congestion control algorithm is not a counter.
+            congestion_control: self.congestion_control.clone(),
+        }
+    }
+}
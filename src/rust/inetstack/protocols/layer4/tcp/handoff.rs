@@ -0,0 +1,75 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    inetstack::protocols::{
+        layer3::Ecn,
+        layer4::tcp::{header::TcpHeader, timestamps::Timestamps, SeqNumber},
+    },
+    runtime::{memory::DemiBuffer, network::types::MacAddress},
+};
+use ::crossbeam_channel::{Receiver, Sender};
+use ::std::net::SocketAddrV4;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// A segment that arrived on the core that owns the listening socket, for a connection that another core took over,
+/// along with the ECN code point of the packet that carried it.
+pub type ForwardedSegment = (Ecn, TcpHeader, DemiBuffer);
+
+/// Index of a worker core among the hand-off targets of a listening socket. Accepts may carry one to pick the core
+/// that takes over the next connection.
+pub type CoreHint = usize;
+
+/// Sending end of a hand-off channel. The core that owns a listening socket uses it to give newly established
+/// connections away.
+pub type HandoffSender = Sender<ConnectionHandoff>;
+
+/// Receiving end of a hand-off channel. Worker cores use it to take over newly established connections. Several
+/// workers may share the same channel, in which case each connection goes to whichever worker asks first.
+pub type HandoffReceiver = Receiver<ConnectionHandoff>;
+
+/// Everything that a worker core needs to take over a connection that was just established on another core. Unlike the
+/// control block, this holds no references into the runtime of the core that set the connection up, so it may cross
+/// threads.
+#[derive(Debug)]
+pub struct ConnectionHandoff {
+    pub local: SocketAddrV4,
+    pub remote: SocketAddrV4,
+    pub remote_link_addr: Option<MacAddress>,
+    /// Next sequence number that we expect to receive.
+    pub receive_seq_no: SeqNumber,
+    pub receive_window_size: u32,
+    pub receive_window_scale: u8,
+    /// Next sequence number that we will send.
+    pub send_seq_no: SeqNumber,
+    pub send_window_size: u32,
+    pub send_window_scale: u8,
+    pub mss: usize,
+    pub sack_permitted: bool,
+    /// State of the timestamps option, if it was negotiated.
+    pub timestamps: Option<Timestamps>,
+    /// Whether ECN (RFC 3168) was negotiated.
+    pub ecn_capable: bool,
+    /// Segments for the connection that arrive on the core that owns the listening socket. That core forwards them
+    /// until the worker reports the connection as closed.
+    pub segments: Receiver<ForwardedSegment>,
+    /// Where the worker reports the remote address of the connection once it closed it, so that the core that owns
+    /// the listening socket stops forwarding segments for it.
+    pub closed: Sender<SocketAddrV4>,
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Creates a hand-off channel that holds up to `capacity` connections that no worker took over yet.
+pub fn channel(capacity: usize) -> (HandoffSender, HandoffReceiver) {
+    ::crossbeam_channel::bounded(capacity)
+}
@@ -0,0 +1,98 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{inetstack::protocols::layer4::stats_delta::Counters, runtime::SharedObject};
+use ::std::ops::{Deref, DerefMut};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Reasons for which a passive open may fail, and how often each of them happened.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HandshakeFailures {
+    /// Number of SYN segments that were rejected because a queue of the listening socket was full.
+    pub backlog_full: u64,
+    /// Number of handshakes that ran out of retries while waiting for the final ACK.
+    pub timeout: u64,
+    /// Number of handshakes that were aborted because the final ACK did not acknowledge our SYN+ACK.
+    pub invalid_ack: u64,
+    /// Number of handshakes that were aborted because we could not send the SYN+ACK.
+    pub transmit: u64,
+    /// Number of handshakes that failed for any other reason.
+    pub other: u64,
+}
+
+/// Handshake statistics that are aggregated across all listening sockets of a TCP peer.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HandshakeStats {
+    /// Number of SYN segments that were received on listening sockets.
+    pub syns_received: u64,
+    /// Number of SYN+ACK segments that were sent, including retransmissions. Every one of these is backed by an entry in
+    /// the table of inflight connections of a listening socket, unlike the ones that carry a SYN cookie.
+    pub syn_acks_sent: u64,
+    /// Number of SYN+ACK segments that carried a SYN cookie instead.
+    pub syn_cookies_sent: u64,
+    /// Number of connections that were established from a valid SYN cookie.
+    pub syn_cookies_accepted: u64,
+    /// Number of ACK segments that did not carry a valid SYN cookie, although they did not belong to any connection.
+    pub syn_cookies_rejected: u64,
+    /// Number of handshakes that completed and produced a connection ready to be accepted.
+    pub handshakes_completed: u64,
+    /// Number of segments that were dropped because too many of them were queued for a connection in its handshake.
+    pub segments_dropped: u64,
+    /// Number of handshakes that failed, by reason.
+    pub failures: HandshakeFailures,
+}
+
+#[derive(Clone)]
+pub struct SharedHandshakeStats(SharedObject<HandshakeStats>);
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl Counters for HandshakeStats {
+    fn delta(&self, baseline: &Self) -> Self {
+        Self {
+            syns_received: self.syns_received - baseline.syns_received,
+            syn_acks_sent: self.syn_acks_sent - baseline.syn_acks_sent,
+            syn_cookies_sent: self.syn_cookies_sent - baseline.syn_cookies_sent,
+            syn_cookies_accepted: self.syn_cookies_accepted - baseline.syn_cookies_accepted,
+            syn_cookies_rejected: self.syn_cookies_rejected - baseline.syn_cookies_rejected,
+            handshakes_completed: self.handshakes_completed - baseline.handshakes_completed,
+            segments_dropped: self.segments_dropped - baseline.segments_dropped,
+            failures: HandshakeFailures {
+                backlog_full: self.failures.backlog_full - baseline.failures.backlog_full,
+                timeout: self.failures.timeout - baseline.failures.timeout,
+                invalid_ack: self.failures.invalid_ack - baseline.failures.invalid_ack,
+                transmit: self.failures.transmit - baseline.failures.transmit,
+                other: self.failures.other - baseline.failures.other,
+            },
+        }
+    }
+}
+
+impl Default for SharedHandshakeStats {
+    fn default() -> Self {
+        Self(SharedObject::<HandshakeStats>::new(HandshakeStats::default()))
+    }
+}
+
+impl Deref for SharedHandshakeStats {
+    type Target = HandshakeStats;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.deref()
+    }
+}
+
+impl DerefMut for SharedHandshakeStats {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.deref_mut()
+    }
+}
@@ -3,11 +3,15 @@
 
 use crate::{
     inetstack::protocols::{layer3::ip::IpProtocol, layer4::tcp::SeqNumber},
-    runtime::{fail::Fail, memory::DemiBuffer},
+    runtime::{
+        fail::Fail,
+        memory::{DemiBuffer, Segments},
+    },
 };
 use ::libc::EBADMSG;
 use ::std::{
     io::{Cursor, Read},
+    iter,
     net::Ipv4Addr,
     slice::ChunksExact,
 };
@@ -158,6 +162,20 @@ impl TcpHeader {
         }
     }
 
+    /// Checks whether the checksum of the TCP segment in [buf] matches its contents. Truncated segments never match.
+    pub fn verify_checksum(local_ipv4_addr: &Ipv4Addr, remote_ipv4_addr: &Ipv4Addr, buf: &[u8]) -> bool {
+        if buf.len() < MIN_TCP_HEADER_SIZE {
+            return false;
+        }
+        let data_offset: usize = (buf[12] >> 4) as usize * 4;
+        if data_offset < MIN_TCP_HEADER_SIZE || buf.len() < data_offset {
+            return false;
+        }
+        let (hdr_buf, data_buf): (&[u8], &[u8]) = buf.split_at(data_offset);
+        let checksum: u16 = u16::from_be_bytes([hdr_buf[16], hdr_buf[17]]);
+        checksum == tcp_checksum(local_ipv4_addr, remote_ipv4_addr, hdr_buf, iter::once(data_buf))
+    }
+
     /// Strip and parse the TCP header from the packet in [buf].
     pub fn parse_and_strip(
         local_ipv4_addr: &Ipv4Addr,
@@ -178,7 +196,7 @@ impl TcpHeader {
         if data_offset > MAX_TCP_HEADER_SIZE {
             return Err(Fail::new(EBADMSG, "TCP data offset too large"));
         }
-        let (hdr_buf, data_buf): (&[u8], &[u8]) = buf[..].split_at(data_offset);
+        let hdr_buf: &[u8] = &buf[..data_offset];
 
         let src_port: u16 = u16::from_be_bytes([hdr_buf[0], hdr_buf[1]]);
         let dst_port: u16 = u16::from_be_bytes([hdr_buf[2], hdr_buf[3]]);
@@ -200,11 +218,8 @@ impl TcpHeader {
 
         let window_size: u16 = u16::from_be_bytes([hdr_buf[14], hdr_buf[15]]);
 
-        if !rx_checksum_offload {
-            let checksum: u16 = u16::from_be_bytes([hdr_buf[16], hdr_buf[17]]);
-            if checksum != tcp_checksum(local_ipv4_addr, remote_ipv4_addr, hdr_buf, data_buf) {
-                return Err(Fail::new(EBADMSG, "TCP checksum mismatch"));
-            }
+        if !rx_checksum_offload && !Self::verify_checksum(local_ipv4_addr, remote_ipv4_addr, &buf[..]) {
+            return Err(Fail::new(EBADMSG, "TCP checksum mismatch"));
         }
 
         let urgent_pointer: u16 = u16::from_be_bytes([hdr_buf[18], hdr_buf[19]]);
@@ -336,7 +351,8 @@ impl TcpHeader {
     ) {
         let header_bytes: usize = self.compute_size();
         pkt.prepend(header_bytes).expect("Should have sufficient headroom");
-        let (hdr_buf, payload): (&mut [u8], &mut [u8]) = pkt[..].split_at_mut(header_bytes);
+        // The header always lives in the first segment, even if the payload is a chain.
+        let hdr_buf: &mut [u8] = &mut pkt.first_segment_mut()[..header_bytes];
 
         let fixed_buf: &mut [u8; MIN_TCP_HEADER_SIZE] = (&mut hdr_buf[..MIN_TCP_HEADER_SIZE]).try_into().unwrap();
         fixed_buf[0..2].copy_from_slice(&self.src_port.to_be_bytes());
@@ -394,13 +410,21 @@ impl TcpHeader {
             *byte = 0;
         }
 
-        // Alright, we've fully filled out the header, time to compute the checksum.
+        // Alright, we've fully filled out the header, time to compute the checksum. The payload may span several
+        // segments, the first of which also holds the header.
+        hdr_buf[16] = 0;
+        hdr_buf[17] = 0;
         if !tx_checksum_offload {
-            let checksum: u16 = tcp_checksum(src_ipv4_addr, dst_ipv4_addr, &hdr_buf[..], &payload);
-            hdr_buf[16..18].copy_from_slice(&checksum.to_be_bytes());
-        } else {
-            hdr_buf[16] = 0;
-            hdr_buf[17] = 0;
+            let mut segments: Segments = pkt.segments();
+            // This unwrap won't panic, as a buffer always has at least one segment.
+            let (hdr_buf, payload): (&[u8], &[u8]) = segments.next().unwrap().split_at(header_bytes);
+            let checksum: u16 = tcp_checksum(
+                src_ipv4_addr,
+                dst_ipv4_addr,
+                hdr_buf,
+                iter::once(payload).chain(segments),
+            );
+            pkt.first_segment_mut()[16..18].copy_from_slice(&checksum.to_be_bytes());
         }
     }
 
@@ -428,9 +452,34 @@ impl TcpHeader {
         self.option_list[self.num_options] = option;
         self.num_options += 1;
     }
+
+    /// Builds the RST that answers this segment, which carried `data_len` bytes of data and belongs to no connection
+    /// that we know of (RFC 793 Section 3.4). Returns None if this segment is a RST itself, as RSTs are never answered.
+    pub fn reset_reply(&self, data_len: usize) -> Option<TcpHeader> {
+        if self.rst {
+            return None;
+        }
+        let mut reply: TcpHeader = TcpHeader::new(self.dst_port, self.src_port);
+        reply.rst = true;
+        if self.ack {
+            // <SEQ=SEG.ACK><CTL=RST>
+            reply.seq_num = self.ack_num;
+        } else {
+            // <SEQ=0><ACK=SEG.SEQ+SEG.LEN><CTL=RST,ACK>
+            let seg_len: u32 = data_len as u32 + self.syn as u32 + self.fin as u32;
+            reply.ack = true;
+            reply.ack_num = self.seq_num + SeqNumber::from(seg_len);
+        }
+        Some(reply)
+    }
 }
 
-fn tcp_checksum(src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr, header: &[u8], data: &[u8]) -> u16 {
+fn tcp_checksum<'a>(
+    src_ipv4_addr: &Ipv4Addr,
+    dst_ipv4_addr: &Ipv4Addr,
+    header: &[u8],
+    data: impl Iterator<Item = &'a [u8]> + Clone,
+) -> u16 {
     let mut state: u32 = 0xffff;
 
     // First, fold in a "pseudo-IP" header of...
@@ -445,10 +494,10 @@ fn tcp_checksum(src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr, header: &[u8
     state += u16::from_be_bytes([dst_octets[2], dst_octets[3]]) as u32;
 
     // 3) 1 byte of zeros and TCP protocol number (1 byte)
-    state += u16::from_be_bytes([0, IpProtocol::TCP as u8]) as u32;
+    state += u16::from_be_bytes([0, u8::from(IpProtocol::TCP)]) as u32;
 
     // 4) TCP segment length (2 bytes)
-    state += (header.len() + data.len()) as u32;
+    state += (header.len() + data.clone().map(|segment| segment.len()).sum::<usize>()) as u32;
 
     let fixed_header: &[u8; MIN_TCP_HEADER_SIZE] = header[..MIN_TCP_HEADER_SIZE].try_into().unwrap();
 
@@ -488,13 +537,30 @@ fn tcp_checksum(src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr, header: &[u8
         }
     }
 
-    // Finally, checksum the data itself.
-    let mut chunks_iter: ChunksExact<u8> = data.chunks_exact(2);
-    while let Some(chunk) = chunks_iter.next() {
-        state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    // Finally, checksum the data itself. A segment of the data may have an odd number of bytes, in which case its last
+    // byte pairs up with the first byte of the next segment.
+    let mut odd_byte: Option<u8> = None;
+    for mut segment in data {
+        if let Some(b) = odd_byte.take() {
+            match segment.split_first() {
+                Some((&first, rest)) => {
+                    state += u16::from_be_bytes([b, first]) as u32;
+                    segment = rest;
+                },
+                None => {
+                    odd_byte = Some(b);
+                    continue;
+                },
+            }
+        }
+        let mut chunks_iter: ChunksExact<u8> = segment.chunks_exact(2);
+        while let Some(chunk) = chunks_iter.next() {
+            state += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+        }
+        odd_byte = chunks_iter.remainder().get(0).copied();
     }
     // Since the data may have an odd number of bytes, pad the last byte with zero if necessary.
-    if let Some(&b) = chunks_iter.remainder().get(0) {
+    if let Some(b) = odd_byte {
         state += u16::from_be_bytes([b, 0]) as u32;
     }
 
@@ -1,43 +1,161 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
-use crate::inetstack::protocols::layer4::tcp::SeqNumber;
-#[allow(unused_imports)]
-use std::{hash::Hasher, net::SocketAddrV4, num::Wrapping};
+//======================================================================================================================
+// Imports
+//======================================================================================================================
 
-#[allow(dead_code)]
+use crate::{inetstack::protocols::layer4::tcp::SeqNumber, runtime::SharedObject};
+use ::std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    net::SocketAddrV4,
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// How long each tick of the clock that is added to initial sequence numbers lasts (RFC 6528 Section 3).
+const CLOCK_TICK: Duration = Duration::from_micros(4);
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Generates initial sequence numbers as RFC 6528 describes: a keyed hash of the addresses of the connection, plus a
+/// clock that ticks every [CLOCK_TICK]. Off-path attackers cannot guess the sequence numbers of a connection without
+/// the secret, while the sequence numbers of consecutive connections between the same addresses keep increasing, so
+/// that segments of an old connection in TIME_WAIT are not taken for segments of a new one.
 pub struct IsnGenerator {
-    nonce: u32,
-    counter: Wrapping<u16>,
+    secret: u128,
+    epoch: Instant,
 }
 
+/// An [IsnGenerator] that active and passive opens share, so that connections between the same addresses draw their
+/// sequence numbers from the same space no matter which end opened them.
+#[derive(Clone)]
+pub struct SharedIsnGenerator(SharedObject<IsnGenerator>);
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
 impl IsnGenerator {
-    pub fn new(nonce: u32) -> Self {
-        Self {
-            nonce,
-            counter: Wrapping(0),
-        }
+    pub fn new(secret: u128, epoch: Instant) -> Self {
+        Self { secret, epoch }
+    }
+
+    /// Replaces the secret that is mixed into the generated sequence numbers. Sequence numbers that were already handed
+    /// out are not affected.
+    pub fn rotate(&mut self, secret: u128) {
+        self.secret = secret;
     }
 
+    /// Generates the initial sequence number of a new connection. Tests rely on connections starting at zero.
     #[cfg(test)]
-    pub fn generate(&mut self, _local: &SocketAddrV4, _remote: &SocketAddrV4) -> SeqNumber {
+    pub fn generate(&self, _local: &SocketAddrV4, _remote: &SocketAddrV4, _now: Instant) -> SeqNumber {
         SeqNumber::from(0)
     }
 
+    /// Generates the initial sequence number of a new connection.
     #[cfg(not(test))]
-    pub fn generate(&mut self, local: &SocketAddrV4, remote: &SocketAddrV4) -> SeqNumber {
-        let crc: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_CKSUM);
-        let mut digest = crc.digest();
-        digest.update(&remote.ip().octets());
-        let remote_port: u16 = remote.port().into();
-        digest.update(&remote_port.to_be_bytes());
-        digest.update(&local.ip().octets());
-        let local_port: u16 = local.port().into();
-        digest.update(&local_port.to_be_bytes());
-        digest.update(&self.nonce.to_be_bytes());
-        let digest = digest.finalize();
-        let isn = SeqNumber::from(digest + self.counter.0 as u32);
-        self.counter += Wrapping(1);
-        isn
+    pub fn generate(&self, local: &SocketAddrV4, remote: &SocketAddrV4, now: Instant) -> SeqNumber {
+        self.compute(local, remote, now)
+    }
+
+    /// Computes ISN = M + F(localip, localport, remoteip, remoteport, secretkey), where M is the clock.
+    fn compute(&self, local: &SocketAddrV4, remote: &SocketAddrV4, now: Instant) -> SeqNumber {
+        let ticks: u128 = now.saturating_duration_since(self.epoch).as_nanos() / CLOCK_TICK.as_nanos();
+        SeqNumber::from((ticks as u32).wrapping_add(self.hash(local, remote)))
+    }
+
+    /// Computes the keyed hash of the addresses of a connection.
+    fn hash(&self, local: &SocketAddrV4, remote: &SocketAddrV4) -> u32 {
+        let mut hasher: DefaultHasher = DefaultHasher::new();
+        self.secret.hash(&mut hasher);
+        local.hash(&mut hasher);
+        remote.hash(&mut hasher);
+        hasher.finish() as u32
+    }
+}
+
+impl SharedIsnGenerator {
+    pub fn new(secret: u128, epoch: Instant) -> Self {
+        Self(SharedObject::<IsnGenerator>::new(IsnGenerator::new(secret, epoch)))
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl Deref for SharedIsnGenerator {
+    type Target = IsnGenerator;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.deref()
+    }
+}
+
+impl DerefMut for SharedIsnGenerator {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.deref_mut()
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{IsnGenerator, CLOCK_TICK};
+    use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::Instant,
+    };
+
+    #[test]
+    fn test_isn_depends_on_addresses_and_secret() -> Result<()> {
+        let now: Instant = Instant::now();
+        let local: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 1), 80);
+        let remote: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), 49152);
+        let other: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), 49153);
+        let mut generator: IsnGenerator = IsnGenerator::new(42, now);
+
+        let isn: SeqNumber = generator.compute(&local, &remote, now);
+        crate::ensure_eq!(generator.compute(&local, &remote, now), isn);
+        crate::ensure_neq!(generator.compute(&local, &other, now), isn);
+        crate::ensure_neq!(generator.compute(&remote, &local, now), isn);
+
+        // Nobody who does not know the secret can tell the sequence numbers that we pick.
+        crate::ensure_neq!(IsnGenerator::new(43, now).compute(&local, &remote, now), isn);
+        generator.rotate(43);
+        crate::ensure_neq!(generator.compute(&local, &remote, now), isn);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_isn_follows_clock() -> Result<()> {
+        let now: Instant = Instant::now();
+        let local: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 1), 80);
+        let remote: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 2), 49152);
+        let generator: IsnGenerator = IsnGenerator::new(42, now);
+
+        // The sequence numbers of consecutive connections between the same addresses keep increasing.
+        let isn: SeqNumber = generator.compute(&local, &remote, now);
+        crate::ensure_eq!(generator.compute(&local, &remote, now + CLOCK_TICK / 2), isn);
+        crate::ensure_eq!(
+            generator.compute(&local, &remote, now + CLOCK_TICK * 1000),
+            isn + SeqNumber::from(1000)
+        );
+
+        Ok(())
     }
 }
@@ -0,0 +1,279 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{inetstack::protocols::layer4::tcp::SeqNumber, runtime::SharedObject};
+use ::serde_json::{json, Value};
+use ::std::{
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+// Number of buckets of a latency histogram. Bucket `i` counts latencies below 2^i microseconds (and at least 2^(i-1)),
+// except for the last one, which counts everything from about 17 seconds on.
+const NUM_LATENCY_BUCKETS: usize = 26;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Histogram of latencies with buckets that double in size, which keeps it small while covering everything from
+/// microseconds to the length of a handshake timeout.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatencyHistogram {
+    buckets: [u64; NUM_LATENCY_BUCKETS],
+    count: u64,
+    sum_us: u64,
+}
+
+/// Handshakes that a listening socket gave up on, by the stage that they were in.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AbandonedHandshakes {
+    /// Number of SYN segments that we never answered, either because one of the queues of the listening socket was full
+    /// or because the SYN+ACK could not be sent.
+    pub before_syn_ack: u64,
+    /// Number of handshakes that did not complete after we sent the SYN+ACK.
+    pub syn_received: u64,
+    /// Number of established connections that were closed or aborted before our peer sent any data.
+    pub before_first_byte: u64,
+}
+
+/// Connection establishment statistics of a single listening socket.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ListenerStats {
+    /// Time from receiving the SYN until the handshake completes.
+    pub syn_to_established: LatencyHistogram,
+    /// Time from completing the handshake until our peer sends its first byte of data.
+    pub established_to_first_byte: LatencyHistogram,
+    /// Handshakes that were abandoned, by stage.
+    pub abandoned: AbandonedHandshakes,
+    /// Number of times that an accept was held back until the next poll iteration, because the listening socket had
+    /// accepted as many connections as it may in one iteration.
+    pub deferred_accepts: u64,
+    /// Number of SYN segments that were turned away because the queue of handshakes in progress was full.
+    pub syn_queue_overflows: u64,
+    /// Number of connections that were turned away because the queue of connections that wait to be accepted was full.
+    pub accept_queue_overflows: u64,
+    /// Number of handshakes in progress when the statistics were taken.
+    pub syn_queue_len: usize,
+    /// Number of connections that waited to be accepted when the statistics were taken.
+    pub accept_queue_len: usize,
+}
+
+#[derive(Clone)]
+pub struct SharedListenerStats(SharedObject<ListenerStats>);
+
+/// Measures how long the peer of a connection that was established by a listening socket takes to send data.
+pub struct FirstByteTimer {
+    listener_stats: SharedListenerStats,
+    established_at: Instant,
+    // RCV.NXT when the connection was established.
+    initial_seq_no: SeqNumber,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl LatencyHistogram {
+    /// Adds a latency sample.
+    pub fn record(&mut self, latency: Duration) {
+        let us: u64 = latency.as_micros().try_into().unwrap_or(u64::MAX);
+        // Index of the smallest power of two that is above the sample.
+        let bucket: usize = (u64::BITS - us.leading_zeros()) as usize;
+        self.buckets[bucket.min(NUM_LATENCY_BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.sum_us = self.sum_us.saturating_add(us);
+    }
+
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn get_mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_micros(self.sum_us / self.count))
+        }
+    }
+
+    /// Returns an upper bound for the latency below which `percentile` percent of the samples fall.
+    pub fn get_percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank: u64 = ((percentile / 100.0 * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen: u64 = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Duration::from_micros(1 << i));
+            }
+        }
+        unreachable!("the buckets should add up to the number of samples")
+    }
+
+    /// Describes the histogram for a state dump. Only buckets with samples are listed, keyed by their upper bound.
+    pub fn dump_state(&self) -> Value {
+        let buckets: Vec<Value> = self
+            .buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(i, count)| json!({ "below_us": 1u64 << i, "count": count }))
+            .collect();
+        let as_us = |latency: Option<Duration>| latency.map(|latency| latency.as_micros() as u64);
+        json!({
+            "count": self.count,
+            "mean_us": as_us(self.get_mean()),
+            "p50_us": as_us(self.get_percentile(50.0)),
+            "p99_us": as_us(self.get_percentile(99.0)),
+            "buckets": buckets,
+        })
+    }
+}
+
+impl ListenerStats {
+    /// Describes these statistics for a state dump.
+    pub fn dump_state(&self) -> Value {
+        json!({
+            "syn_to_established": self.syn_to_established.dump_state(),
+            "established_to_first_byte": self.established_to_first_byte.dump_state(),
+            "abandoned": {
+                "before_syn_ack": self.abandoned.before_syn_ack,
+                "syn_received": self.abandoned.syn_received,
+                "before_first_byte": self.abandoned.before_first_byte,
+            },
+            "deferred_accepts": self.deferred_accepts,
+            "syn_queue": {
+                "len": self.syn_queue_len,
+                "overflows": self.syn_queue_overflows,
+            },
+            "accept_queue": {
+                "len": self.accept_queue_len,
+                "overflows": self.accept_queue_overflows,
+            },
+        })
+    }
+}
+
+impl FirstByteTimer {
+    pub fn new(listener_stats: SharedListenerStats, established_at: Instant, initial_seq_no: SeqNumber) -> Self {
+        Self {
+            listener_stats,
+            established_at,
+            initial_seq_no,
+        }
+    }
+
+    /// Checks whether our peer sent data or closed the connection, now that RCV.NXT is `receive_next`. Returns whether
+    /// the timer is done.
+    pub fn on_receive(&mut self, now: Instant, receive_next: SeqNumber, fin_received: bool) -> bool {
+        if receive_next == self.initial_seq_no {
+            return false;
+        }
+        // The FIN takes up one sequence number.
+        if fin_received && receive_next == self.initial_seq_no + SeqNumber::from(1) {
+            self.listener_stats.abandoned.before_first_byte += 1;
+        } else {
+            let latency: Duration = now.saturating_duration_since(self.established_at);
+            self.listener_stats.established_to_first_byte.record(latency);
+        }
+        true
+    }
+
+    /// Accounts for a connection that was aborted before our peer sent any data.
+    pub fn on_abort(&mut self) {
+        self.listener_stats.abandoned.before_first_byte += 1;
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl Default for SharedListenerStats {
+    fn default() -> Self {
+        Self(SharedObject::<ListenerStats>::new(ListenerStats::default()))
+    }
+}
+
+impl Deref for SharedListenerStats {
+    type Target = ListenerStats;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.deref()
+    }
+}
+
+impl DerefMut for SharedListenerStats {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.deref_mut()
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{FirstByteTimer, LatencyHistogram, SharedListenerStats};
+    use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    #[test]
+    fn test_latency_histogram() -> Result<()> {
+        let mut histogram: LatencyHistogram = LatencyHistogram::default();
+        crate::ensure_eq!(histogram.get_percentile(50.0), None);
+
+        for _ in 0..98 {
+            histogram.record(Duration::from_micros(100));
+        }
+        histogram.record(Duration::from_millis(10));
+        histogram.record(Duration::from_secs(3600));
+        crate::ensure_eq!(histogram.get_count(), 100);
+        // Percentiles are reported as the upper bound of their bucket.
+        crate::ensure_eq!(histogram.get_percentile(50.0), Some(Duration::from_micros(128)));
+        crate::ensure_eq!(histogram.get_percentile(99.0), Some(Duration::from_micros(16384)));
+        // Samples beyond the last bucket end up in it.
+        crate::ensure_eq!(histogram.get_percentile(100.0), Some(Duration::from_micros(1 << 25)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_byte_timer() -> Result<()> {
+        let stats: SharedListenerStats = SharedListenerStats::default();
+        let established_at: Instant = Instant::now();
+        let initial_seq_no: SeqNumber = SeqNumber::from(1000);
+
+        // A segment that carries no data does not stop the timer.
+        let mut timer: FirstByteTimer = FirstByteTimer::new(stats.clone(), established_at, initial_seq_no);
+        let now: Instant = established_at + Duration::from_millis(5);
+        crate::ensure_eq!(timer.on_receive(now, initial_seq_no, false), false);
+        crate::ensure_eq!(timer.on_receive(now, initial_seq_no + SeqNumber::from(1), false), true);
+        crate::ensure_eq!(stats.established_to_first_byte.get_count(), 1);
+
+        // A FIN without any data before it means that the connection was abandoned.
+        let mut timer: FirstByteTimer = FirstByteTimer::new(stats.clone(), established_at, initial_seq_no);
+        crate::ensure_eq!(timer.on_receive(now, initial_seq_no + SeqNumber::from(1), true), true);
+        crate::ensure_eq!(stats.abandoned.before_first_byte, 1);
+
+        // Data that comes along with the FIN still counts.
+        let mut timer: FirstByteTimer = FirstByteTimer::new(stats.clone(), established_at, initial_seq_no);
+        crate::ensure_eq!(timer.on_receive(now, initial_seq_no + SeqNumber::from(11), true), true);
+        crate::ensure_eq!(stats.established_to_first_byte.get_count(), 2);
+
+        Ok(())
+    }
+}
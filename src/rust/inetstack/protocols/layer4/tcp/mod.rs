@@ -1,21 +1,29 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
+mod accept_pacer;
 mod active_open;
 pub mod constants;
 mod established;
+pub mod handoff;
+pub mod handshake_stats;
 pub mod header;
 mod isn_generator;
+pub mod listener_stats;
 mod passive_open;
 pub mod peer;
+mod remote_map;
 mod sequence_number;
 pub mod socket;
+mod syn_cookies;
+mod time_wait;
+pub mod timestamps;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::{
-    established::congestion_control,
+    established::{congestion_control, rto::RtoCalculator},
     header::{MAX_TCP_HEADER_SIZE, MIN_TCP_HEADER_SIZE},
     peer::SharedTcpPeer,
     sequence_number::SeqNumber,
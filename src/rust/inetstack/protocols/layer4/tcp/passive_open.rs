@@ -7,20 +7,24 @@
 
 use crate::{
     collections::{
-        async_queue::{AsyncQueue, SharedAsyncQueue},
+        async_queue::{AsyncQueue, OverflowPolicy, SharedAsyncQueue},
         async_value::SharedAsyncValue,
     },
     expect_some,
     inetstack::protocols::{
-        layer3::SharedLayer3Endpoint,
+        layer3::{ip::IpProtocol, Ecn, SharedLayer3Endpoint},
         layer4::tcp::{
-            constants::FALLBACK_MSS,
-            established::{
-                congestion_control::{self, CongestionControl},
-                EstablishedSocket,
-            },
+            accept_pacer::AcceptPacer,
+            constants::{FALLBACK_MSS, MAX_HANDSHAKE_SEGMENTS},
+            established::EstablishedSocket,
+            handoff::{ConnectionHandoff, CoreHint, ForwardedSegment, HandoffSender},
+            handshake_stats::SharedHandshakeStats,
             header::{TcpHeader, TcpOptions2},
-            isn_generator::IsnGenerator,
+            isn_generator::SharedIsnGenerator,
+            listener_stats::{FirstByteTimer, ListenerStats, SharedListenerStats},
+            remote_map::{RemoteHasher, RemoteKey, RemoteMap},
+            syn_cookies::SynCookieGenerator,
+            timestamps::{get_timestamps_option, Timestamps},
             SeqNumber,
         },
         MAX_HEADER_SIZE,
@@ -29,19 +33,33 @@ use crate::{
         conditional_yield_with_timeout,
         fail::Fail,
         memory::DemiBuffer,
-        network::{config::TcpConfig, consts::MAX_WINDOW_SCALE, socket::option::TcpSocketOptions},
+        network::{
+            config::{BacklogOverflow, SynCookies, TcpConfig},
+            consts::MAX_WINDOW_SCALE,
+            socket::option::{PriorityClass, TcpSocketOptions},
+            types::MacAddress,
+        },
         SharedDemiRuntime, SharedObject,
     },
 };
+use ::crossbeam_channel::{Receiver, Sender, TrySendError};
 use ::futures::FutureExt;
-use ::libc::{EBADMSG, ETIMEDOUT};
+use ::libc::{EAGAIN, EBADMSG, ECONNABORTED, EINVAL, ETIMEDOUT};
+use ::serde_json::{json, Value};
 use ::std::{
-    collections::HashMap,
+    collections::VecDeque,
     net::{Ipv4Addr, SocketAddrV4},
     ops::{Deref, DerefMut},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Number of receive queues of finished handshakes that we keep around for new handshakes to reuse.
+const MAX_SPARE_HANDSHAKE_QUEUES: usize = 256;
+
 //======================================================================================================================
 // Structures
 //======================================================================================================================
@@ -58,16 +76,49 @@ enum State {
 pub struct PassiveSocket {
     // TCP Connection State.
     state: SharedAsyncValue<State>,
-    connections: HashMap<SocketAddrV4, SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>>,
+    // Hashes the remote addresses that the tables below are indexed by.
+    remote_hasher: RemoteHasher,
+    // Handshakes in progress, which are bounded by [max_syn_backlog]. They are only told apart by the remote address,
+    // so a socket that takes connections to several addresses or ports completes at most one handshake at a time with
+    // each remote, whatever its destination.
+    connections: RemoteMap<SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>>,
+    // Receive queues of finished handshakes, which new handshakes reuse instead of allocating their own.
+    spare_queues: Vec<SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>>,
+    // Connections that completed their handshake and wait to be accepted, which are bounded by [max_backlog].
     ready: AsyncQueue<(SocketAddrV4, Result<EstablishedSocket, Fail>)>,
+    // Connections in [ready] that were established, indexed by remote address, so that their segments are delivered
+    // without scanning the accept queue.
+    ready_index: RemoteMap<EstablishedSocket>,
     max_backlog: usize,
-    isn_generator: IsnGenerator,
+    max_syn_backlog: usize,
+    isn_generator: SharedIsnGenerator,
+    // Generates the SYN cookies that we answer SYN segments with instead of keeping state for their handshakes.
+    syn_cookies: SynCookieGenerator,
     local: SocketAddrV4,
     runtime: SharedDemiRuntime,
     layer3_endpoint: SharedLayer3Endpoint,
     tcp_config: TcpConfig,
-    // We do not use these right now, but will in the future.
     socket_options: TcpSocketOptions,
+    // Handshake statistics, which are shared with all other listening sockets of the TCP peer.
+    handshake_stats: SharedHandshakeStats,
+    // Connection establishment statistics of this listening socket alone.
+    listener_stats: SharedListenerStats,
+    // Channel to hand newly established connections off to worker cores, instead of queuing them for accept.
+    handoff: Option<HandoffSender>,
+    // Connections that were handed off, along with the channels that their segments are forwarded through.
+    forwarded: RemoteMap<Sender<ForwardedSegment>>,
+    // Worker cores report the handed-off connections that they closed through this channel.
+    closed_tx: Sender<SocketAddrV4>,
+    closed_rx: Receiver<SocketAddrV4>,
+    // Hand-off channels of the worker cores that accept hints refer to, indexed by hint.
+    handoff_targets: Vec<HandoffSender>,
+    // Accepts that asked for the next connections to go to a particular worker core, in the order in which they were
+    // issued, along with where to report the remote address of the connection.
+    hinted_accepts: VecDeque<(CoreHint, SharedAsyncValue<Option<Result<SocketAddrV4, Fail>>>)>,
+    // Current poll iteration of the stack, which is shared with all other listening sockets of the TCP peer.
+    poll_iteration: SharedAsyncValue<u64>,
+    // Limits the number of accepts that complete in each poll iteration.
+    accept_pacer: AcceptPacer,
 }
 
 #[derive(Clone)]
@@ -85,19 +136,42 @@ impl SharedPassiveSocket {
         layer3_endpoint: SharedLayer3Endpoint,
         tcp_config: TcpConfig,
         default_socket_options: TcpSocketOptions,
-        nonce: u32,
+        isn_generator: SharedIsnGenerator,
+        syn_cookie_secret: u64,
+        hash_seed: u64,
+        handshake_stats: SharedHandshakeStats,
+        poll_iteration: SharedAsyncValue<u64>,
     ) -> Result<Self, Fail> {
+        let accept_pacer: AcceptPacer = AcceptPacer::new(tcp_config.get_max_accepts_per_poll());
+        let syn_cookies: SynCookieGenerator = SynCookieGenerator::new(syn_cookie_secret, runtime.get_now());
+        let max_syn_backlog: usize = tcp_config.get_max_syn_backlog(max_backlog);
+        let (closed_tx, closed_rx): (Sender<SocketAddrV4>, Receiver<SocketAddrV4>) = ::crossbeam_channel::unbounded();
         Ok(Self(SharedObject::<PassiveSocket>::new(PassiveSocket {
             state: SharedAsyncValue::new(State::Listening),
-            connections: HashMap::<SocketAddrV4, SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>>::new(),
+            remote_hasher: RemoteHasher::new(hash_seed),
+            connections: RemoteMap::default(),
+            spare_queues: Vec::new(),
             ready: AsyncQueue::<(SocketAddrV4, Result<EstablishedSocket, Fail>)>::default(),
+            ready_index: RemoteMap::default(),
             max_backlog,
-            isn_generator: IsnGenerator::new(nonce),
+            max_syn_backlog,
+            isn_generator,
+            syn_cookies,
             local,
             runtime,
             layer3_endpoint,
             tcp_config,
             socket_options: default_socket_options,
+            handshake_stats,
+            listener_stats: SharedListenerStats::default(),
+            handoff: None,
+            forwarded: RemoteMap::default(),
+            closed_tx,
+            closed_rx,
+            handoff_targets: Vec::new(),
+            hinted_accepts: VecDeque::new(),
+            poll_iteration,
+            accept_pacer,
         })))
     }
 
@@ -106,10 +180,106 @@ impl SharedPassiveSocket {
         self.local
     }
 
+    /// Returns the local address of a connection whose segments are addressed to `dst`. Sockets that are bound to the
+    /// wildcard address accept connections to the local address of the stack and, if they are transparent, to the
+    /// foreign addresses that the stack intercepts. Sockets that listen on a range of ports keep the port that each
+    /// connection was made to.
+    fn get_connection_local(&self, dst: SocketAddrV4) -> SocketAddrV4 {
+        if !self.local.ip().is_unspecified() {
+            SocketAddrV4::new(*self.local.ip(), dst.port())
+        } else if self.layer3_endpoint.is_transparent_addr(*dst.ip()) {
+            dst
+        } else {
+            SocketAddrV4::new(self.layer3_endpoint.get_local_addr(), dst.port())
+        }
+    }
+
+    /// Moves the socket over to the `local` address. Handshakes that are in progress carry on from the new address.
+    pub fn rebind(&mut self, local: SocketAddrV4) {
+        self.local = local;
+    }
+
+    /// Describes the listening socket for a state dump.
+    pub fn dump_state(&self) -> Value {
+        json!({
+            "local": self.local.to_string(),
+            "backlog": self.max_backlog,
+            "syn_backlog": self.max_syn_backlog,
+            "inflight": self.connections.len(),
+            "ready": self.ready.len(),
+            "handed_off": self.forwarded.len(),
+            "stats": self.get_listener_stats().dump_state(),
+        })
+    }
+
+    /// Returns the connection establishment statistics of this listening socket.
+    pub fn get_listener_stats(&self) -> ListenerStats {
+        let mut listener_stats: ListenerStats = *self.listener_stats;
+        listener_stats.syn_queue_len = self.connections.len();
+        listener_stats.accept_queue_len = self.ready.len();
+        listener_stats
+    }
+
+    /// Hands connections that complete their handshake from now on off through `handoff`, rather than queuing them for
+    /// accept on this core. Connections are queued for accept as usual whenever the channel is full.
+    pub fn set_handoff(&mut self, handoff: HandoffSender) {
+        self.handoff = Some(handoff);
+    }
+
+    /// Sets the hand-off channels of the worker cores that accept hints refer to. The channel at index `i` belongs to
+    /// the worker core with hint `i`.
+    pub fn set_handoff_targets(&mut self, targets: Vec<HandoffSender>) {
+        self.handoff_targets = targets;
+    }
+
+    /// Hands the next connection that completes its handshake off to the worker core with hint `core`, so that the
+    /// control block and buffers of the connection are allocated on that core. Returns the address of the remote peer
+    /// once the connection was handed off.
+    pub async fn do_accept_on_core(&mut self, core: CoreHint) -> Result<SocketAddrV4, Fail> {
+        if core >= self.handoff_targets.len() {
+            let cause: String = format!(
+                "no hand-off channel for core (core={:?}, num_cores={:?})",
+                core,
+                self.handoff_targets.len()
+            );
+            error!("do_accept_on_core(): {}", cause);
+            return Err(Fail::new(EINVAL, &cause));
+        }
+
+        let mut result: SharedAsyncValue<Option<Result<SocketAddrV4, Fail>>> = SharedAsyncValue::new(None);
+        self.hinted_accepts.push_back((core, result.clone()));
+        loop {
+            match result.get() {
+                Some(result) => return result,
+                None => {
+                    result.wait_for_change(None).await?;
+                },
+            }
+        }
+    }
+
     /// Accept a new connection by fetching one from the queue of requests, blocking if there are no new requests.
     pub async fn do_accept(&mut self) -> Result<EstablishedSocket, Fail> {
-        let (_, new_socket) = self.ready.pop(None).await?;
-        new_socket
+        loop {
+            let (remote, new_socket) = self.ready.pop(None).await?;
+            let poll_iteration: u64 = self.poll_iteration.get();
+            if self.accept_pacer.try_take(poll_iteration) {
+                let key: RemoteKey = self.remote_hasher.key(remote);
+                self.ready_index.remove(&key);
+                return new_socket;
+            }
+            // Put the connection back, so that its segments are still delivered to it, and try again in the next poll
+            // iteration.
+            debug!("do_accept(): accept budget used up, deferring (remote={:?})", remote);
+            self.listener_stats.deferred_accepts += 1;
+            self.ready.push_front((remote, new_socket));
+            self.poll_iteration.wait_for_change(None).await?;
+        }
+    }
+
+    /// Returns whether there are completed handshakes that have not been accepted yet.
+    pub fn has_pending_connections(&self) -> bool {
+        !self.ready.is_empty()
     }
 
     // Closes the target socket.
@@ -118,24 +288,62 @@ impl SharedPassiveSocket {
         Ok(())
     }
 
-    pub fn receive(&mut self, ipv4_addr: Ipv4Addr, tcp_hdr: TcpHeader, buf: DemiBuffer) {
+    pub fn receive(
+        &mut self,
+        ipv4_addr: Ipv4Addr,
+        local_ipv4_addr: Ipv4Addr,
+        link_addr: MacAddress,
+        ecn: Ecn,
+        tcp_hdr: TcpHeader,
+        buf: DemiBuffer,
+    ) {
         let remote: SocketAddrV4 = SocketAddrV4::new(ipv4_addr, tcp_hdr.src_port);
+        let dst: SocketAddrV4 = SocketAddrV4::new(local_ipv4_addr, tcp_hdr.dst_port);
+        let key: RemoteKey = self.remote_hasher.key(remote);
 
         // See if this packet is for an ongoing connection set up.
-        if let Some(recv_queue) = self.connections.get_mut(&remote) {
+        if let Some(recv_queue) = self.connections.get_mut(&key) {
             // Packet is either for an inflight request or established connection.
+            let num_dropped: u64 = recv_queue.get_num_dropped();
             recv_queue.push((ipv4_addr, tcp_hdr, buf));
+            if recv_queue.get_num_dropped() > num_dropped {
+                debug!("receive(): too many segments queued, dropping (remote={:?})", remote);
+                self.handshake_stats.segments_dropped += 1;
+            }
             return;
         }
 
-        // See if this packet is for an already established but not accepted socket.
-        if let Some((_, socket)) = self.ready.get_values().find(|(addr, _)| *addr == remote) {
-            if let Ok(socket) = socket {
-                socket.get_cb().receive(tcp_hdr, buf);
+        // See if this packet is for a connection that was handed off to another core.
+        self.remove_closed_forwards();
+        if let Some(segments) = self.forwarded.get(&key) {
+            if segments.send((ecn, tcp_hdr, buf)).is_err() {
+                // The core that took over the connection has closed it.
+                debug!(
+                    "receive(): dropping segment for closed connection (remote={:?})",
+                    remote
+                );
+                self.forwarded.remove(&key);
             }
             return;
         }
 
+        // See if this packet is for an already established but not accepted socket.
+        if let Some(socket) = self.ready_index.get(&key) {
+            socket.get_cb().receive(ecn, tcp_hdr, buf);
+            return;
+        }
+
+        // An ACK may complete a handshake that we answered with a SYN cookie, without keeping any state for it.
+        if self.tcp_config.get_syn_cookies() != SynCookies::Off && tcp_hdr.ack && !tcp_hdr.syn && !tcp_hdr.rst {
+            match self.check_syn_cookie(dst, &remote, &tcp_hdr) {
+                Some(mss) => {
+                    self.establish_from_syn_cookie(dst, remote, ipv4_addr, link_addr, mss, tcp_hdr, buf);
+                    return;
+                },
+                None => self.handshake_stats.syn_cookies_rejected += 1,
+            }
+        }
+
         // Otherwise if not a SYN, then this packet is not for a new connection and we throw it away.
         if !tcp_hdr.syn || tcp_hdr.ack || tcp_hdr.rst {
             let cause: String = format!(
@@ -143,7 +351,7 @@ impl SharedPassiveSocket {
                 tcp_hdr.syn, tcp_hdr.ack, tcp_hdr.rst
             );
             warn!("poll(): {}", cause);
-            self.send_rst(&remote, tcp_hdr);
+            self.send_rst(dst, &remote, tcp_hdr);
             return;
         }
 
@@ -158,37 +366,79 @@ impl SharedPassiveSocket {
         }
 
         // Start a new connection.
-        self.handle_new_syn(remote, tcp_hdr);
+        self.handle_new_syn(dst, key, link_addr, tcp_hdr);
     }
 
-    fn handle_new_syn(&mut self, remote: SocketAddrV4, tcp_hdr: TcpHeader) {
+    fn handle_new_syn(&mut self, dst: SocketAddrV4, key: RemoteKey, link_addr: MacAddress, tcp_hdr: TcpHeader) {
         debug!("Received SYN: {:?}", tcp_hdr);
-        let inflight_len: usize = self.connections.len();
-        // Check backlog. Since we might receive data even on connections that have completed their handshake, all
-        // ready sockets are also in the inflight table.
-        if inflight_len >= self.max_backlog {
-            let cause: String = format!(
-                "backlog full (inflight={}, ready={}, backlog={})",
-                inflight_len,
+        let remote: SocketAddrV4 = key.get_remote();
+        self.handshake_stats.syns_received += 1;
+        let syn_received_at: Instant = self.runtime.get_now();
+        // There is no point in starting a handshake, not even a stateless one, if the connection could not be queued
+        // for accept once it completes.
+        if self.ready.len() >= self.max_backlog {
+            warn!(
+                "handle_new_syn(): accept queue full (ready={}, backlog={})",
                 self.ready.len(),
                 self.max_backlog
             );
-            warn!("handle_new_syn(): {}", cause);
-            self.send_rst(&remote, tcp_hdr);
+            self.listener_stats.accept_queue_overflows += 1;
+            self.reject_syn(dst, &remote, tcp_hdr);
+            return;
+        }
+        let syn_queue_full: bool = self.connections.len() >= self.max_syn_backlog;
+        match self.tcp_config.get_syn_cookies() {
+            SynCookies::Always => return self.send_syn_cookie(dst, remote, link_addr, tcp_hdr),
+            SynCookies::UnderPressure if syn_queue_full => {
+                debug!(
+                    "handle_new_syn(): SYN queue full, answering with SYN cookie (remote={:?})",
+                    remote
+                );
+                return self.send_syn_cookie(dst, remote, link_addr, tcp_hdr);
+            },
+            _ => (),
+        }
+        if syn_queue_full {
+            warn!(
+                "handle_new_syn(): SYN queue full (inflight={}, syn_backlog={})",
+                self.connections.len(),
+                self.max_syn_backlog
+            );
+            self.listener_stats.syn_queue_overflows += 1;
+            self.reject_syn(dst, &remote, tcp_hdr);
             return;
         }
 
         // Send SYN+ACK.
-        let local: SocketAddrV4 = self.local.clone();
-        let local_isn = self.isn_generator.generate(&local, &remote);
+        let local: SocketAddrV4 = self.get_connection_local(dst);
+        let local_isn: SeqNumber = self.isn_generator.generate(&local, &remote, self.runtime.get_now());
         let remote_isn = tcp_hdr.seq_num;
+        // On bridged setups, the host that forwarded the SYN to us may not be the one that ARP resolves the remote
+        // address to, so we optionally send everything for this connection back the way that the SYN came.
+        let remote_link_addr: Option<MacAddress> = if self.tcp_config.get_learn_remote_link_addr() {
+            debug!(
+                "handle_new_syn(): learned link address (remote={:?}, link_addr={:?})",
+                remote, link_addr
+            );
+            Some(link_addr)
+        } else {
+            None
+        };
 
         // Allocate a new coroutine to send the SYN+ACK and retry if necessary.
-        let recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)> =
-            SharedAsyncQueue::<(Ipv4Addr, TcpHeader, DemiBuffer)>::default();
+        let recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)> = self.take_handshake_queue();
         let future = self
             .clone()
-            .send_syn_ack_and_wait_for_ack(remote, remote_isn, local_isn, tcp_hdr, recv_queue.clone())
+            .send_syn_ack_and_wait_for_ack(
+                dst,
+                remote,
+                remote_link_addr,
+                remote_isn,
+                local_isn,
+                tcp_hdr,
+                recv_queue.clone(),
+                syn_received_at,
+            )
             .fuse();
         match self
             .runtime
@@ -202,11 +452,197 @@ impl SharedPassiveSocket {
             },
         };
         // TODO: Clean up the connections table once we have merged all of the routing tables into one.
-        self.connections.insert(remote, recv_queue);
+        self.connections.insert(key, recv_queue);
+    }
+
+    /// Returns an empty receive queue for a new handshake, which is recycled from a finished handshake if possible.
+    fn take_handshake_queue(&mut self) -> SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)> {
+        match self.spare_queues.pop() {
+            Some(recv_queue) => recv_queue,
+            None => SharedAsyncQueue::<(Ipv4Addr, TcpHeader, DemiBuffer)>::bounded(
+                MAX_HANDSHAKE_SEGMENTS,
+                OverflowPolicy::DropNewest,
+            ),
+        }
+    }
+
+    /// Removes the handshake with the remote address of `key` and keeps its receive queue for a new handshake. The
+    /// connection that came out of the handshake has taken all segments out of the queue by then, if there is any.
+    fn remove_handshake(&mut self, key: &RemoteKey) {
+        if let Some(mut recv_queue) = self.connections.remove(key) {
+            if self.spare_queues.len() < MAX_SPARE_HANDSHAKE_QUEUES {
+                while recv_queue.try_pop().is_some() {}
+                self.spare_queues.push(recv_queue);
+            }
+        }
+    }
+
+    /// Stops forwarding segments for the handed-off connections that their worker cores closed.
+    fn remove_closed_forwards(&mut self) {
+        while let Ok(remote) = self.closed_rx.try_recv() {
+            let key: RemoteKey = self.remote_hasher.key(remote);
+            self.forwarded.remove(&key);
+        }
+    }
+
+    /// Turns away the SYN segment `tcp_hdr` from `remote`, which does not fit into our queues, as the configured overflow
+    /// policy says.
+    fn reject_syn(&mut self, dst: SocketAddrV4, remote: &SocketAddrV4, tcp_hdr: TcpHeader) {
+        self.handshake_stats.failures.backlog_full += 1;
+        self.listener_stats.abandoned.before_syn_ack += 1;
+        match self.tcp_config.get_backlog_overflow() {
+            BacklogOverflow::Reset => self.send_rst(dst, remote, tcp_hdr),
+            // Our peer retransmits the SYN, which may find room then.
+            BacklogOverflow::Drop => debug!("reject_syn(): dropping SYN (remote={:?})", remote),
+        }
+    }
+
+    /// Answers the SYN segment `tcp_hdr` from `remote` with a SYN+ACK that carries a SYN cookie, without keeping any
+    /// state for the handshake. We send it straight back to the link address that the SYN came from, since we cannot
+    /// wait for ARP here.
+    fn send_syn_cookie(&mut self, dst: SocketAddrV4, remote: SocketAddrV4, link_addr: MacAddress, tcp_hdr: TcpHeader) {
+        let mss: usize = tcp_hdr
+            .iter_options()
+            .find_map(|option| match option {
+                TcpOptions2::MaximumSegmentSize(m) => Some(*m as usize),
+                _ => None,
+            })
+            .unwrap_or(FALLBACK_MSS);
+        let remote_isn: SeqNumber = tcp_hdr.seq_num;
+        let now: Instant = self.runtime.get_now();
+        let local: SocketAddrV4 = self.get_connection_local(dst);
+        let local_isn: SeqNumber = self.syn_cookies.generate(&local, &remote, remote_isn, mss, now);
+
+        // Only the MSS fits into the cookie, so we do not agree to any other option.
+        let mut tcp_hdr: TcpHeader = TcpHeader::new(local.port(), remote.port());
+        tcp_hdr.syn = true;
+        tcp_hdr.seq_num = local_isn;
+        tcp_hdr.ack = true;
+        tcp_hdr.ack_num = remote_isn + SeqNumber::from(1);
+        tcp_hdr.window_size = self.tcp_config.get_receive_window_size();
+        let advertised_mss: u16 = self.tcp_config.get_advertised_mss() as u16;
+        tcp_hdr.push_option(TcpOptions2::MaximumSegmentSize(advertised_mss));
+
+        debug!("Sending SYN+ACK with SYN cookie: {:?}", tcp_hdr);
+        let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16);
+        tcp_hdr.serialize_and_attach(
+            &mut pkt,
+            local.ip(),
+            remote.ip(),
+            self.tcp_config.get_rx_checksum_offload(),
+        );
+        let priority: PriorityClass = self.socket_options.get_priority();
+        match self.layer3_endpoint.transmit_packet(
+            local.ip().clone(),
+            remote.ip().clone(),
+            link_addr,
+            IpProtocol::TCP,
+            priority,
+            Ecn::NotEct,
+            pkt,
+        ) {
+            Ok(()) => self.handshake_stats.syn_cookies_sent += 1,
+            Err(e) => {
+                warn!("send_syn_cookie(): could not send SYN+ACK: {:?}", e);
+                self.handshake_stats.failures.transmit += 1;
+            },
+        }
+    }
+
+    /// Checks whether `tcp_hdr` acknowledges a SYN+ACK that carried a valid SYN cookie. If so, returns the MSS of our
+    /// peer that the cookie encodes.
+    fn check_syn_cookie(&self, dst: SocketAddrV4, remote: &SocketAddrV4, tcp_hdr: &TcpHeader) -> Option<usize> {
+        let remote_isn: SeqNumber = tcp_hdr.seq_num - SeqNumber::from(1);
+        let cookie: SeqNumber = tcp_hdr.ack_num - SeqNumber::from(1);
+        self.syn_cookies.validate(
+            &self.get_connection_local(dst),
+            remote,
+            remote_isn,
+            cookie,
+            self.runtime.get_now(),
+        )
     }
 
-    /// Sends a RST segment to `remote`.
-    fn send_rst(&mut self, remote: &SocketAddrV4, tcp_hdr: TcpHeader) {
+    /// Establishes the connection with `remote` from the ACK `tcp_hdr`, which carried a valid SYN cookie.
+    fn establish_from_syn_cookie(
+        &mut self,
+        dst: SocketAddrV4,
+        remote: SocketAddrV4,
+        ipv4_addr: Ipv4Addr,
+        link_addr: MacAddress,
+        mss: usize,
+        tcp_hdr: TcpHeader,
+        buf: DemiBuffer,
+    ) {
+        debug!("Received ACK with valid SYN cookie: {:?}", tcp_hdr);
+        self.handshake_stats.syn_cookies_accepted += 1;
+        // Since we kept no state for the handshake, we may only find out now that there is no room for the connection.
+        if self.ready.len() >= self.max_backlog {
+            warn!(
+                "establish_from_syn_cookie(): accept queue full (ready={}, backlog={})",
+                self.ready.len(),
+                self.max_backlog
+            );
+            self.listener_stats.accept_queue_overflows += 1;
+            self.listener_stats.abandoned.syn_received += 1;
+            if self.tcp_config.get_backlog_overflow() == BacklogOverflow::Reset {
+                self.send_rst(dst, &remote, tcp_hdr);
+            }
+            return;
+        }
+        let local_isn: SeqNumber = tcp_hdr.ack_num - SeqNumber::from(1);
+        let remote_isn: SeqNumber = tcp_hdr.seq_num - SeqNumber::from(1);
+        let header_window_size: u16 = tcp_hdr.window_size;
+        let remote_link_addr: Option<MacAddress> = match self.tcp_config.get_learn_remote_link_addr() {
+            true => Some(link_addr),
+            false => None,
+        };
+
+        // If there is data with the ACK, deliver it.
+        let mut recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)> = self.take_handshake_queue();
+        if !buf.is_empty() {
+            recv_queue.push((ipv4_addr, tcp_hdr, buf));
+        }
+
+        let mss: usize = self.tcp_config.get_send_mss(mss);
+        let result: Result<EstablishedSocket, Fail> = match self.establish(
+            dst,
+            recv_queue,
+            remote,
+            remote_link_addr,
+            local_isn,
+            remote_isn,
+            header_window_size,
+            None,
+            mss,
+            false,
+            None,
+            false,
+        ) {
+            Ok(Some(mut socket)) => {
+                let timer: FirstByteTimer = FirstByteTimer::new(
+                    self.listener_stats.clone(),
+                    self.runtime.get_now(),
+                    remote_isn + 1.into(),
+                );
+                socket.start_first_byte_timer(timer);
+                Ok(socket)
+            },
+            // The connection was handed off to another core.
+            Ok(None) => {
+                self.handshake_stats.handshakes_completed += 1;
+                return;
+            },
+            Err(e) => {
+                self.handshake_stats.failures.other += 1;
+                Err(e)
+            },
+        };
+        self.complete_handshake(remote, result);
+    }
+
+    /// Answers `tcp_hdr`, which `remote` addressed to `dst`, with a RST segment.
+    fn send_rst(&mut self, dst: SocketAddrV4, remote: &SocketAddrV4, tcp_hdr: TcpHeader) {
         debug!("send_rst(): sending RST to {:?}", remote);
 
         // If this is an inactive socket, then generate a RST segment.
@@ -227,7 +663,8 @@ impl SharedPassiveSocket {
 
         // Create a RST segment.
         let dst_ipv4_addr: Ipv4Addr = remote.ip().clone();
-        let mut tcp_hdr: TcpHeader = TcpHeader::new(self.local.port(), remote.port());
+        let local: SocketAddrV4 = self.get_connection_local(dst);
+        let mut tcp_hdr: TcpHeader = TcpHeader::new(local.port(), remote.port());
         tcp_hdr.rst = true;
         tcp_hdr.seq_num = seq_num;
         if let Some(ack_num) = ack_num {
@@ -239,28 +676,37 @@ impl SharedPassiveSocket {
         let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16);
         tcp_hdr.serialize_and_attach(
             &mut pkt,
-            self.local.ip(),
+            local.ip(),
             remote.ip(),
             self.tcp_config.get_rx_checksum_offload(),
         );
 
         // Pass on to send through the L2 layer.
-        if let Err(e) = self.layer3_endpoint.transmit_tcp_packet_nonblocking(dst_ipv4_addr, pkt) {
+        let priority: PriorityClass = self.socket_options.get_priority();
+        if let Err(e) =
+            self.layer3_endpoint
+                .transmit_tcp_packet_nonblocking(*local.ip(), dst_ipv4_addr, priority, Ecn::NotEct, pkt)
+        {
             warn!("Could not send RST: {:?}", e);
         }
     }
 
     async fn send_syn_ack_and_wait_for_ack(
         mut self,
+        dst: SocketAddrV4,
         remote: SocketAddrV4,
+        remote_link_addr: Option<MacAddress>,
         remote_isn: SeqNumber,
         local_isn: SeqNumber,
         tcp_hdr: TcpHeader,
         recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>,
+        syn_received_at: Instant,
     ) {
         // Set up new inflight accept connection.
         let mut remote_window_scale = None;
         let mut mss = FALLBACK_MSS;
+        let mut sack_permitted: bool = false;
+        let mut remote_timestamp: Option<u32> = None;
         for option in tcp_hdr.iter_options() {
             match option {
                 TcpOptions2::WindowScale(w) => {
@@ -271,37 +717,110 @@ impl SharedPassiveSocket {
                     info!("Received advertised MSS: {}", m);
                     mss = *m as usize;
                 },
+                TcpOptions2::SelectiveAcknowlegementPermitted => {
+                    info!("Received SACK permitted");
+                    sack_permitted = true;
+                },
+                TcpOptions2::Timestamp { sender_timestamp, .. } => {
+                    info!("Received timestamp: {}", sender_timestamp);
+                    remote_timestamp = Some(*sender_timestamp);
+                },
                 _ => continue,
             }
         }
+        // Never send segments larger than what we advertise ourselves, as those may not fit into our MTU.
+        let mss: usize = self.tcp_config.get_send_mss(mss);
+        info!("Send MSS: {}", mss);
+
+        // Timestamps are only used if our peer sent one in the SYN (RFC 7323 Section 3.2).
+        let timestamps: Option<Timestamps> = remote_timestamp
+            .map(|remote_timestamp| Timestamps::new(self.runtime.get_now(), remote_timestamp, remote_isn + 1.into()));
+
+        // An ECN-setup SYN carries both the ECE and CWR flags (RFC 3168 Section 6.1.1).
+        let ecn_capable: bool = self.tcp_config.get_ecn() && tcp_hdr.ece && tcp_hdr.cwr;
+        if ecn_capable {
+            info!("Received ECN-setup SYN");
+        }
 
         let mut handshake_retries: usize = self.tcp_config.get_handshake_retries();
         let handshake_timeout: Duration = self.tcp_config.get_handshake_timeout();
+        let mut syn_ack_sent: bool = false;
 
         loop {
             // Send the SYN + ACK.
-            if let Err(e) = self.send_syn_ack(local_isn, remote_isn, remote).await {
+            if let Err(e) = self
+                .send_syn_ack(
+                    dst,
+                    local_isn,
+                    remote_isn,
+                    remote,
+                    remote_link_addr,
+                    sack_permitted,
+                    timestamps,
+                    ecn_capable,
+                )
+                .await
+            {
+                self.handshake_stats.failures.transmit += 1;
+                if syn_ack_sent {
+                    self.listener_stats.abandoned.syn_received += 1;
+                } else {
+                    self.listener_stats.abandoned.before_syn_ack += 1;
+                }
                 self.complete_handshake(remote, Err(e));
                 return;
             }
+            self.handshake_stats.syn_acks_sent += 1;
+            syn_ack_sent = true;
 
             // Start ack timer.
 
             // Wait for ACK in response.
             let ack = self.clone().wait_for_ack(
+                dst,
                 recv_queue.clone(),
                 remote,
+                remote_link_addr,
                 local_isn,
                 remote_isn,
                 tcp_hdr.window_size,
                 remote_window_scale,
                 mss,
+                sack_permitted,
+                timestamps,
+                ecn_capable,
             );
 
             // Either we get an ack or a timeout.
             match conditional_yield_with_timeout(ack, handshake_timeout).await {
                 // Got an ack
                 Ok(result) => {
+                    let now: Instant = self.runtime.get_now();
+                    let mut result: Result<EstablishedSocket, Fail> = match result.transpose() {
+                        Some(result) => result,
+                        // The connection was handed off to another core, which measures the time to the first byte.
+                        None => {
+                            self.handshake_stats.handshakes_completed += 1;
+                            self.listener_stats.syn_to_established.record(now - syn_received_at);
+                            return;
+                        },
+                    };
+                    match result {
+                        Err(Fail { errno, cause: _ }) if errno == EBADMSG => {
+                            self.handshake_stats.failures.invalid_ack += 1;
+                            self.listener_stats.abandoned.syn_received += 1;
+                        },
+                        Err(_) => {
+                            self.handshake_stats.failures.other += 1;
+                            self.listener_stats.abandoned.syn_received += 1;
+                        },
+                        Ok(ref mut socket) => {
+                            self.listener_stats.syn_to_established.record(now - syn_received_at);
+                            let timer: FirstByteTimer =
+                                FirstByteTimer::new(self.listener_stats.clone(), now, remote_isn + SeqNumber::from(1));
+                            socket.start_first_byte_timer(timer);
+                        },
+                    }
                     self.complete_handshake(remote, result);
                     return;
                 },
@@ -310,12 +829,15 @@ impl SharedPassiveSocket {
                         handshake_retries = handshake_retries - 1;
                         continue;
                     } else {
-                        self.ready
-                            .push((remote, Err(Fail::new(ETIMEDOUT, "handshake timeout"))));
+                        self.handshake_stats.failures.timeout += 1;
+                        self.listener_stats.abandoned.syn_received += 1;
+                        self.complete_handshake(remote, Err(Fail::new(ETIMEDOUT, "handshake timeout")));
                         return;
                     }
                 },
                 Err(e) => {
+                    self.handshake_stats.failures.other += 1;
+                    self.listener_stats.abandoned.syn_received += 1;
                     self.complete_handshake(remote, Err(e));
                     return;
                 },
@@ -325,11 +847,17 @@ impl SharedPassiveSocket {
 
     async fn send_syn_ack(
         &mut self,
+        dst: SocketAddrV4,
         local_isn: SeqNumber,
         remote_isn: SeqNumber,
         remote: SocketAddrV4,
+        remote_link_addr: Option<MacAddress>,
+        sack_permitted: bool,
+        timestamps: Option<Timestamps>,
+        ecn_capable: bool,
     ) -> Result<(), Fail> {
-        let mut tcp_hdr = TcpHeader::new(self.local.port(), remote.port());
+        let local: SocketAddrV4 = self.get_connection_local(dst);
+        let mut tcp_hdr = TcpHeader::new(local.port(), remote.port());
         tcp_hdr.syn = true;
         tcp_hdr.seq_num = local_isn;
         tcp_hdr.ack = true;
@@ -343,30 +871,67 @@ impl SharedPassiveSocket {
         tcp_hdr.push_option(TcpOptions2::WindowScale(self.tcp_config.get_window_scale()));
         info!("Advertising window scale: {}", self.tcp_config.get_window_scale());
 
+        // SACK may only be permitted in the SYN+ACK if our peer permitted it in the SYN.
+        if sack_permitted {
+            tcp_hdr.push_option(TcpOptions2::SelectiveAcknowlegementPermitted);
+            info!("Advertising SACK permitted");
+        }
+
+        // Likewise, we only send a timestamp if our peer sent one in the SYN.
+        if let Some(timestamps) = timestamps {
+            tcp_hdr.push_option(timestamps.get_option(self.runtime.get_now()));
+        }
+
+        // An ECN-setup SYN+ACK only carries the ECE flag (RFC 3168 Section 6.1.1).
+        if ecn_capable {
+            tcp_hdr.ece = true;
+            info!("Advertising ECN");
+        }
+
         debug!("Sending SYN+ACK: {:?}", tcp_hdr);
         let dst_ipv4_addr: Ipv4Addr = remote.ip().clone();
         let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16);
         tcp_hdr.serialize_and_attach(
             &mut pkt,
-            self.local.ip(),
+            local.ip(),
             remote.ip(),
             self.tcp_config.get_rx_checksum_offload(),
         );
-        self.layer3_endpoint
-            .transmit_tcp_packet_blocking(dst_ipv4_addr, pkt)
-            .await
+        let priority: PriorityClass = self.socket_options.get_priority();
+        match remote_link_addr {
+            Some(remote_link_addr) => self.layer3_endpoint.transmit_packet(
+                *local.ip(),
+                dst_ipv4_addr,
+                remote_link_addr,
+                IpProtocol::TCP,
+                priority,
+                Ecn::NotEct,
+                pkt,
+            ),
+            None => {
+                self.layer3_endpoint
+                    .transmit_tcp_packet_blocking(*local.ip(), dst_ipv4_addr, priority, pkt)
+                    .await
+            },
+        }
     }
 
+    /// Waits for the ACK that completes the handshake. Returns `None` if the connection was handed off to another core.
     async fn wait_for_ack(
-        self,
+        mut self,
+        dst: SocketAddrV4,
         mut recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>,
         remote: SocketAddrV4,
+        remote_link_addr: Option<MacAddress>,
         local_isn: SeqNumber,
         remote_isn: SeqNumber,
         header_window_size: u16,
         remote_window_scale: Option<u8>,
         mss: usize,
-    ) -> Result<EstablishedSocket, Fail> {
+        sack_permitted: bool,
+        mut timestamps: Option<Timestamps>,
+        ecn_capable: bool,
+    ) -> Result<Option<EstablishedSocket>, Fail> {
         let (ipv4_hdr, tcp_hdr, buf) = recv_queue.pop(None).await?;
         debug!("Received ACK: {:?}", tcp_hdr);
 
@@ -375,6 +940,49 @@ impl SharedPassiveSocket {
             return Err(Fail::new(EBADMSG, "invalid SYN+ACK seq num"));
         }
 
+        // Echo the timestamp of the ACK from now on.
+        if let (Some(timestamps), Some((ts_val, _))) = (timestamps.as_mut(), get_timestamps_option(&tcp_hdr)) {
+            timestamps.update_recent(ts_val, tcp_hdr.seq_num);
+        }
+
+        // If there is data with the SYN+ACK, deliver it.
+        if !buf.is_empty() {
+            recv_queue.push((ipv4_hdr, tcp_hdr, buf));
+        }
+
+        self.establish(
+            dst,
+            recv_queue,
+            remote,
+            remote_link_addr,
+            local_isn,
+            remote_isn,
+            header_window_size,
+            remote_window_scale,
+            mss,
+            sack_permitted,
+            timestamps,
+            ecn_capable,
+        )
+    }
+
+    /// Sets up the connection once its handshake completed, either by handing it off to another core or by creating the
+    /// established socket here. Returns `None` if the connection was handed off.
+    fn establish(
+        &mut self,
+        dst: SocketAddrV4,
+        mut recv_queue: SharedAsyncQueue<(Ipv4Addr, TcpHeader, DemiBuffer)>,
+        remote: SocketAddrV4,
+        remote_link_addr: Option<MacAddress>,
+        local_isn: SeqNumber,
+        remote_isn: SeqNumber,
+        header_window_size: u16,
+        remote_window_scale: Option<u8>,
+        mss: usize,
+        sack_permitted: bool,
+        timestamps: Option<Timestamps>,
+        ecn_capable: bool,
+    ) -> Result<Option<EstablishedSocket>, Fail> {
         // Calculate the window.
         let (local_window_scale, remote_window_scale): (u8, u8) = match remote_window_scale {
             Some(remote_window_scale) => {
@@ -413,14 +1021,78 @@ impl SharedPassiveSocket {
             local_window_scale, remote_window_scale
         );
 
-        // If there is data with the SYN+ACK, deliver it.
-        if !buf.is_empty() {
-            recv_queue.push((ipv4_hdr, tcp_hdr, buf));
+        // Accepts that asked for a particular core take precedence over the shared hand-off channel.
+        let target: Option<(
+            HandoffSender,
+            Option<SharedAsyncValue<Option<Result<SocketAddrV4, Fail>>>>,
+        )> = match self.hinted_accepts.pop_front() {
+            Some((core, result)) => Some((self.handoff_targets[core].clone(), Some(result))),
+            None => self.handoff.clone().map(|handoff| (handoff, None)),
+        };
+        if let Some((handoff, mut hinted_result)) = target {
+            let (segments_tx, segments_rx) = ::crossbeam_channel::unbounded::<ForwardedSegment>();
+            let connection: ConnectionHandoff = ConnectionHandoff {
+                local: self.get_connection_local(dst),
+                remote,
+                remote_link_addr,
+                receive_seq_no: remote_isn + SeqNumber::from(1),
+                receive_window_size: local_window_size,
+                receive_window_scale: local_window_scale,
+                send_seq_no: local_isn + SeqNumber::from(1),
+                send_window_size: remote_window_size,
+                send_window_scale: remote_window_scale,
+                mss,
+                sack_permitted,
+                timestamps,
+                ecn_capable,
+                segments: segments_rx,
+                closed: self.closed_tx.clone(),
+            };
+            match handoff.try_send(connection) {
+                Ok(()) => {
+                    debug!("wait_for_ack(): handed connection off (remote={:?})", remote);
+                    // Anything that arrived along with the ACK goes to the new owner of the connection.
+                    while let Some((_, tcp_hdr, buf)) = recv_queue.try_pop() {
+                        // Nothing is lost if the worker already closed the connection again.
+                        let _ = segments_tx.send((Ecn::NotEct, tcp_hdr, buf));
+                    }
+                    let key: RemoteKey = self.remote_hasher.key(remote);
+                    self.remove_handshake(&key);
+                    self.remove_closed_forwards();
+                    self.forwarded.insert(key, segments_tx);
+                    if let Some(result) = hinted_result.as_mut() {
+                        result.set(Some(Ok(remote)));
+                    }
+                    return Ok(None);
+                },
+                Err(TrySendError::Full(_)) => {
+                    warn!(
+                        "wait_for_ack(): hand-off channel is full, accepting locally (remote={:?})",
+                        remote
+                    );
+                    if let Some(result) = hinted_result.as_mut() {
+                        result.set(Some(Err(Fail::new(EAGAIN, "hand-off channel of core is full"))));
+                    }
+                },
+                Err(TrySendError::Disconnected(_)) => {
+                    warn!(
+                        "wait_for_ack(): no workers left to hand off to, accepting locally (remote={:?})",
+                        remote
+                    );
+                    match hinted_result.as_mut() {
+                        Some(result) => {
+                            result.set(Some(Err(Fail::new(ECONNABORTED, "core no longer takes connections"))))
+                        },
+                        None => self.handoff = None,
+                    }
+                },
+            }
         }
 
         let new_socket: EstablishedSocket = EstablishedSocket::new(
-            self.local,
+            self.get_connection_local(dst),
             remote,
+            remote_link_addr,
             self.runtime.clone(),
             self.layer3_endpoint.clone(),
             recv_queue.clone(),
@@ -434,15 +1106,38 @@ impl SharedPassiveSocket {
             remote_window_size,
             remote_window_scale,
             mss,
-            congestion_control::None::new,
+            sack_permitted,
+            timestamps,
+            ecn_capable,
+            self.tcp_config
+                .get_congestion_control_by_name(self.socket_options.get_congestion_control()),
             None,
         )?;
 
-        Ok(new_socket)
+        Ok(Some(new_socket))
     }
 
-    fn complete_handshake(&mut self, remote: SocketAddrV4, result: Result<EstablishedSocket, Fail>) {
-        self.connections.remove(&remote);
+    fn complete_handshake(&mut self, remote: SocketAddrV4, mut result: Result<EstablishedSocket, Fail>) {
+        let key: RemoteKey = self.remote_hasher.key(remote);
+        self.remove_handshake(&key);
+        // The accept queue may have filled up while the handshake was in progress. We then reset the connection, since
+        // our peer already considers it established and would otherwise send data into the void.
+        if self.ready.len() >= self.max_backlog {
+            if let Ok(socket) = result.as_mut() {
+                warn!(
+                    "complete_handshake(): accept queue full, resetting connection (remote={:?}, backlog={})",
+                    remote, self.max_backlog
+                );
+                self.listener_stats.accept_queue_overflows += 1;
+                socket.abort();
+            }
+            return;
+        }
+        // Failures are accounted for by the caller, which knows why the handshake failed.
+        if let Ok(socket) = result.as_ref() {
+            self.handshake_stats.handshakes_completed += 1;
+            self.ready_index.insert(key, socket.clone());
+        }
         self.ready.push((remote, result));
     }
 }
@@ -6,10 +6,27 @@
 //======================================================================================================================
 
 use crate::{
+    collections::{async_queue::SharedAsyncQueue, async_value::SharedAsyncValue},
     demikernel::config::Config,
     inetstack::protocols::{
-        layer3::SharedLayer3Endpoint,
-        layer4::tcp::{header::TcpHeader, isn_generator::IsnGenerator, socket::SharedTcpSocket, SeqNumber},
+        draw_secret,
+        layer3::{Ecn, FragmentationNeeded, SharedLayer3Endpoint},
+        layer4::{
+            checksum_sampler::{ChecksumSampler, ChecksumStats},
+            stats_delta::{StatsBaseline, StatsDelta},
+            tcp::{
+                established::{stats::Stats, EstablishedSocket},
+                handoff::{ConnectionHandoff, CoreHint, ForwardedSegment, HandoffSender},
+                handshake_stats::{HandshakeStats, SharedHandshakeStats},
+                header::TcpHeader,
+                isn_generator::SharedIsnGenerator,
+                listener_stats::ListenerStats,
+                socket::SharedTcpSocket,
+                time_wait::{TimeWaitReply, TimeWaitTable},
+                SeqNumber,
+            },
+        },
+        MAX_HEADER_SIZE,
     },
     runtime::{
         fail::Fail,
@@ -17,19 +34,23 @@ use crate::{
         network::{
             config::TcpConfig,
             socket::{
-                option::{SocketOption, TcpSocketOptions},
-                SocketId,
+                option::{PortRange, PriorityClass, SocketOption, TcpSocketOptions},
+                PopFlags, SocketId,
             },
+            types::MacAddress,
         },
-        SharedDemiRuntime, SharedObject,
+        poll_yield, SharedDemiRuntime, SharedObject,
     },
 };
+use ::crossbeam_channel::{Receiver, Sender, TryRecvError};
+use ::futures::FutureExt;
 use ::rand::{prelude::SmallRng, Rng, SeedableRng};
 
 use ::std::{
     collections::HashMap,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4},
     ops::{Deref, DerefMut},
+    time::Instant,
 };
 
 //======================================================================================================================
@@ -38,13 +59,24 @@ use ::std::{
 
 pub struct TcpPeer {
     runtime: SharedDemiRuntime,
-    isn_generator: IsnGenerator,
+    isn_generator: SharedIsnGenerator,
     layer3_endpoint: SharedLayer3Endpoint,
-    local_ipv4_addr: Ipv4Addr,
     tcp_config: TcpConfig,
     default_socket_options: TcpSocketOptions,
     rng: SmallRng,
     addresses: HashMap<SocketId, SharedTcpSocket>,
+    // Listening sockets that take connections to a range of ports, by the address that they are bound to. They are
+    // also in [addresses], under that address.
+    port_ranges: Vec<(SocketAddrV4, PortRange)>,
+    // Connections that were closed and are in TIME_WAIT. They are not in [addresses], as they have no socket anymore.
+    time_wait: TimeWaitTable,
+    handshake_stats: SharedHandshakeStats,
+    checksum_sampler: ChecksumSampler,
+    // Global statistics at the time they were last reset, which snapshots are relative to.
+    handshake_stats_baseline: StatsBaseline<HandshakeStats>,
+    checksum_stats_baseline: StatsBaseline<ChecksumStats>,
+    // Number of poll iterations of the stack so far, which listening sockets pace their accepts by.
+    poll_iteration: SharedAsyncValue<u64>,
 }
 
 #[derive(Clone)]
@@ -62,16 +94,26 @@ impl SharedTcpPeer {
         rng_seed: [u8; 32],
     ) -> Result<Self, Fail> {
         let mut rng: SmallRng = SmallRng::from_seed(rng_seed);
-        let nonce: u32 = rng.gen();
+        let isn_secret: u128 = draw_secret(&mut rng);
+        let tcp_config: TcpConfig = TcpConfig::new(config)?;
+        let time_wait: TimeWaitTable =
+            TimeWaitTable::new(tcp_config.get_max_time_wait(), tcp_config.get_time_wait_recycle());
+        let now: Instant = runtime.get_now();
         Ok(Self(SharedObject::<TcpPeer>::new(TcpPeer {
-            isn_generator: IsnGenerator::new(nonce),
+            isn_generator: SharedIsnGenerator::new(isn_secret, now),
             runtime,
             layer3_endpoint,
-            local_ipv4_addr: config.local_ipv4_addr()?,
-            tcp_config: TcpConfig::new(config)?,
+            checksum_sampler: ChecksumSampler::new(tcp_config.get_rx_checksum_sample_interval()),
+            tcp_config,
             default_socket_options: TcpSocketOptions::new(config)?,
             rng,
             addresses: HashMap::<SocketId, SharedTcpSocket>::new(),
+            port_ranges: Vec::new(),
+            time_wait,
+            handshake_stats: SharedHandshakeStats::default(),
+            handshake_stats_baseline: StatsBaseline::new(HandshakeStats::default(), now),
+            checksum_stats_baseline: StatsBaseline::new(ChecksumStats::default(), now),
+            poll_iteration: SharedAsyncValue::new(0),
         })))
     }
 
@@ -104,10 +146,14 @@ impl SharedTcpPeer {
         socket.getpeername()
     }
 
+    /// Shuts down one or both directions of the connection on a TCP socket.
+    pub fn shutdown(&mut self, socket: &mut SharedTcpSocket, how: Shutdown) -> Result<(), Fail> {
+        socket.shutdown(how)
+    }
+
     /// Binds a socket to a local address supplied by [local].
     pub fn bind(&mut self, socket: &mut SharedTcpSocket, local: SocketAddrV4) -> Result<(), Fail> {
         // All other checks should have been done already.
-        debug_assert!(!Ipv4Addr::is_unspecified(local.ip()));
         debug_assert!(local.port() != 0);
         debug_assert!(self.addresses.get(&SocketId::Passive(local)).is_none());
 
@@ -121,8 +167,274 @@ impl SharedTcpPeer {
     pub fn listen(&mut self, socket: &mut SharedTcpSocket, backlog: usize) -> Result<(), Fail> {
         // Most checks should have been performed already
         debug_assert!(socket.local().is_some());
-        let nonce: u32 = self.rng.gen();
-        socket.listen(backlog, nonce)
+        let local: SocketAddrV4 = socket.local().unwrap();
+        let ports: Option<PortRange> = socket.get_listen_ports();
+        if let Some(ports) = ports {
+            self.check_port_range(local, ports)?;
+        }
+        let syn_cookie_secret: u64 = draw_secret(&mut self.rng);
+        let hash_seed: u64 = self.rng.gen();
+        socket.listen(
+            backlog,
+            self.isn_generator.clone(),
+            syn_cookie_secret,
+            hash_seed,
+            self.handshake_stats.clone(),
+            self.poll_iteration.clone(),
+        )?;
+        if let Some(ports) = ports {
+            self.port_ranges.push((local, ports));
+        }
+        Ok(())
+    }
+
+    /// Checks whether a socket that is bound to `local` may listen on the range of `ports`. Ranges of sockets that are
+    /// bound to the same address may not overlap, but sockets that listen on single ports within the range take
+    /// precedence, so they do not get in the way.
+    fn check_port_range(&self, local: SocketAddrV4, ports: PortRange) -> Result<(), Fail> {
+        if !ports.contains(local.port()) {
+            let cause: String = format!(
+                "port range must contain the bound port (local={:?}, ports={:?})",
+                local, ports
+            );
+            error!("listen(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        if let Some((other, _)) = self
+            .port_ranges
+            .iter()
+            .find(|(other, other_ports)| other.ip() == local.ip() && other_ports.overlaps(&ports))
+        {
+            let cause: String = format!(
+                "port range overlaps with that of another socket (local={:?}, other={:?}, ports={:?})",
+                local, other, ports
+            );
+            error!("listen(): {}", cause);
+            return Err(Fail::new(libc::EADDRINUSE, &cause));
+        }
+        Ok(())
+    }
+
+    /// Returns the listening socket whose range of ports covers `local`, preferring those that are bound to its address
+    /// over those that are bound to the wildcard address.
+    fn find_port_range(&self, local: SocketAddrV4) -> Option<SocketId> {
+        let find = |ipv4_addr: Ipv4Addr| {
+            self.port_ranges
+                .iter()
+                .find(|(bound, ports)| *bound.ip() == ipv4_addr && ports.contains(local.port()))
+                .map(|(bound, _)| SocketId::Passive(*bound))
+        };
+        find(*local.ip()).or_else(|| find(Ipv4Addr::UNSPECIFIED))
+    }
+
+    /// Forgets the range of ports of the socket that `socket_id` refers to, if it listens on one.
+    fn remove_port_range(&mut self, socket_id: &SocketId) {
+        if let SocketId::Passive(local) = socket_id {
+            self.port_ranges.retain(|(bound, _)| bound != local);
+        }
+    }
+
+    /// Marks the end of a poll iteration of the stack, which refills the accept budget of all listening sockets.
+    pub fn end_poll_iteration(&mut self) {
+        self.poll_iteration.modify(|iteration| iteration.wrapping_add(1));
+    }
+
+    /// Checks whether there are connections waiting to be accepted on a listening TCP socket.
+    pub fn has_pending_connections(&mut self, socket: &mut SharedTcpSocket) -> Result<bool, Fail> {
+        socket.has_pending_connections()
+    }
+
+    /// Returns the most recent error that affected `socket` and clears it (SO_ERROR).
+    pub fn get_last_error(&mut self, socket: &mut SharedTcpSocket) -> Option<Fail> {
+        socket.get_last_error()
+    }
+
+    /// Returns the number of received bytes on `socket` that the application has not popped yet.
+    pub fn get_buffered_bytes(&mut self, socket: &mut SharedTcpSocket) -> usize {
+        socket.get_buffered_bytes()
+    }
+
+    /// Returns the handshake statistics of all listening sockets of this peer.
+    pub fn get_handshake_stats(&self) -> HandshakeStats {
+        *self.handshake_stats
+    }
+
+    /// Returns the connection establishment statistics of the listening socket `socket`.
+    /// Returns the results of validating, in software, the checksums of a sample of the received segments whose checksum
+    /// the hardware validated.
+    pub fn get_checksum_stats(&self) -> ChecksumStats {
+        self.checksum_sampler.get_stats()
+    }
+
+    pub fn get_listener_stats(&self, socket: &SharedTcpSocket) -> Result<ListenerStats, Fail> {
+        socket.get_listener_stats()
+    }
+
+    /// Returns the change in the handshake statistics since they were last reset, and resets them if [reset] is set.
+    /// This does not affect what [get_handshake_stats] returns.
+    pub fn snapshot_handshake_stats(&mut self, reset: bool) -> StatsDelta<HandshakeStats> {
+        let current: HandshakeStats = *self.handshake_stats;
+        let now: Instant = self.runtime.get_now();
+        self.handshake_stats_baseline.snapshot(current, now, reset)
+    }
+
+    /// Returns the change in the checksum validation statistics since they were last reset, and resets them if [reset]
+    /// is set. This does not affect what [get_checksum_stats] returns.
+    pub fn snapshot_checksum_stats(&mut self, reset: bool) -> StatsDelta<ChecksumStats> {
+        let current: ChecksumStats = self.checksum_sampler.get_stats();
+        let now: Instant = self.runtime.get_now();
+        self.checksum_stats_baseline.snapshot(current, now, reset)
+    }
+
+    /// Returns the change in the statistics of the connection on `socket` since they were last reset, and resets them
+    /// if [reset] is set.
+    pub fn snapshot_connection_stats(
+        &mut self,
+        socket: &mut SharedTcpSocket,
+        reset: bool,
+    ) -> Result<StatsDelta<Stats>, Fail> {
+        socket.snapshot_stats(reset)
+    }
+
+    /// Replaces the secret that is used to generate initial sequence numbers, which active opens and every listening
+    /// socket share. Established connections and handshakes that are already in progress are not affected.
+    pub fn rotate_isn_secret(&mut self) {
+        let secret: u128 = draw_secret(&mut self.rng);
+        self.isn_generator.rotate(secret);
+    }
+
+    /// Moves the sockets that are bound to the `old` local IPv4 address, including listening sockets, over to the `new`
+    /// one. Connections keep the address that they were established with.
+    pub fn rebind(&mut self, old: Ipv4Addr, new: Ipv4Addr) {
+        let bound: Vec<SocketAddrV4> = self
+            .addresses
+            .keys()
+            .filter_map(|socket_id| match socket_id {
+                SocketId::Passive(local) if *local.ip() == old => Some(*local),
+                _ => None,
+            })
+            .collect();
+        for old_local in bound {
+            let new_local: SocketAddrV4 = SocketAddrV4::new(new, old_local.port());
+            if let Some(mut socket) = self.addresses.remove(&SocketId::Passive(old_local)) {
+                debug!("rebind(): moving socket from {:?} to {:?}", old_local, new_local);
+                socket.rebind(new_local);
+                self.addresses.insert(SocketId::Passive(new_local), socket);
+                for (bound, _) in self.port_ranges.iter_mut().filter(|(bound, _)| *bound == old_local) {
+                    *bound = new_local;
+                }
+                if let Some(qd) = self.runtime.remove_socket_id_to_qd(&SocketId::Passive(old_local)) {
+                    self.runtime.insert_socket_id_to_qd(SocketId::Passive(new_local), qd);
+                }
+            }
+        }
+    }
+
+    /// Hands connections that `socket` establishes from now on off to worker cores through `handoff`. Workers take them
+    /// over with [SharedTcpPeer::adopt].
+    pub fn set_handoff(&mut self, socket: &mut SharedTcpSocket, handoff: HandoffSender) -> Result<(), Fail> {
+        socket.set_handoff(handoff)
+    }
+
+    /// Sets the hand-off channels that accept hints on `socket` refer to. The channel at index `i` belongs to the
+    /// worker core with hint `i`.
+    pub fn set_handoff_targets(
+        &mut self,
+        socket: &mut SharedTcpSocket,
+        targets: Vec<HandoffSender>,
+    ) -> Result<(), Fail> {
+        socket.set_handoff_targets(targets)
+    }
+
+    /// Takes over a connection that was established by a listening socket on another core. The control block is set up
+    /// anew on this core, so its background coroutine is scheduled here. Segments of the connection that still arrive
+    /// at the core that owns the listening socket are forwarded to this one, until the connection is closed.
+    pub fn adopt(&mut self, handoff: ConnectionHandoff) -> Result<SharedTcpSocket, Fail> {
+        let socket_id: SocketId = SocketId::Active(handoff.local, handoff.remote);
+        if self.addresses.contains_key(&socket_id) {
+            let cause: String = format!(
+                "connection already exists (local={:?}, remote={:?})",
+                handoff.local, handoff.remote
+            );
+            error!("adopt(): {}", cause);
+            return Err(Fail::new(libc::EEXIST, &cause));
+        }
+
+        let established: EstablishedSocket = EstablishedSocket::new(
+            handoff.local,
+            handoff.remote,
+            handoff.remote_link_addr,
+            self.runtime.clone(),
+            self.layer3_endpoint.clone(),
+            SharedAsyncQueue::default(),
+            self.tcp_config.clone(),
+            self.default_socket_options.clone(),
+            handoff.receive_seq_no,
+            self.tcp_config.get_ack_delay_timeout(),
+            handoff.receive_window_size,
+            handoff.receive_window_scale,
+            handoff.send_seq_no,
+            handoff.send_window_size,
+            handoff.send_window_scale,
+            handoff.mss,
+            handoff.sack_permitted,
+            handoff.timestamps,
+            handoff.ecn_capable,
+            self.tcp_config.get_congestion_control(),
+            None,
+        )?;
+        let socket: SharedTcpSocket = SharedTcpSocket::new_established(
+            established,
+            self.runtime.clone(),
+            self.layer3_endpoint.clone(),
+            self.tcp_config.clone(),
+            self.default_socket_options.clone(),
+        );
+
+        let future = self
+            .clone()
+            .forward(
+                SocketId::Active(handoff.local, handoff.remote),
+                handoff.segments,
+                handoff.closed,
+            )
+            .fuse();
+        self.runtime
+            .insert_background_coroutine("bgc::inetstack::tcp::handoff::forward", Box::pin(future))?;
+        self.addresses.insert(socket_id, socket.clone());
+        Ok(socket)
+    }
+
+    /// Delivers the segments that the core that owns the listening socket forwards for an adopted connection. Returns
+    /// once the connection is closed on this core, and then tells the other core through `closed` to stop.
+    async fn forward(
+        mut self,
+        socket_id: SocketId,
+        segments: Receiver<ForwardedSegment>,
+        closed: Sender<SocketAddrV4>,
+    ) {
+        let (local, remote): (SocketAddrV4, SocketAddrV4) = match socket_id {
+            SocketId::Active(local, remote) => (local, remote),
+            SocketId::Passive(_) => unreachable!("adopted connections are always active"),
+        };
+        loop {
+            if !self.addresses.contains_key(&socket_id) {
+                debug!("forward(): connection closed (remote={:?})", remote);
+                // Nobody is left to tell if the listening socket is gone as well.
+                let _ = closed.send(remote);
+                return;
+            }
+            match segments.try_recv() {
+                Ok((ecn, tcp_hdr, buf)) => {
+                    if let Some(socket) = self.addresses.get_mut(&socket_id) {
+                        // Connected sockets do not look at the link address, so any will do.
+                        socket.receive(*remote.ip(), *local.ip(), MacAddress::nil(), ecn, tcp_hdr, buf);
+                    }
+                },
+                Err(TryRecvError::Empty) => poll_yield().await,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
     }
 
     /// Runs until a new connection is accepted.
@@ -140,6 +452,12 @@ impl SharedTcpPeer {
         }
     }
 
+    /// Runs until a new connection was handed off to the worker core with hint `core`. The connection is not tracked
+    /// on this core, as the worker takes it over with [SharedTcpPeer::adopt].
+    pub async fn accept_on_core(&mut self, socket: &mut SharedTcpSocket, core: CoreHint) -> Result<SocketAddrV4, Fail> {
+        socket.accept_on_core(core).await
+    }
+
     /// Runs until the connect to remote is made or times out.
     pub async fn connect(
         &mut self,
@@ -147,8 +465,30 @@ impl SharedTcpPeer {
         local: SocketAddrV4,
         remote: SocketAddrV4,
     ) -> Result<(), Fail> {
-        // If socket is already bound to a local address, use it but remove the old binding.
-        self.addresses.remove(&SocketId::Passive(local));
+        // A connection that used the same addresses before may still be in TIME_WAIT.
+        let now: Instant = self.runtime.get_now();
+        if !self.time_wait.try_reuse(local, remote, now) {
+            let cause: String = format!(
+                "connection is still in TIME_WAIT (local={:?}, remote={:?})",
+                local, remote
+            );
+            error!("connect(): {}", cause);
+            return Err(Fail::new(libc::EADDRINUSE, &cause));
+        }
+        // If socket is already bound to a local address, use it but remove the old binding. Sockets that are bound to
+        // the wildcard address connect from the local address, so their binding moves there.
+        let bound: SocketAddrV4 = socket.local().unwrap_or(local);
+        if bound != local {
+            if self.runtime.get_qd_from_socket_id(&SocketId::Passive(local)).is_some() {
+                let cause: String = format!("address is already bound to a socket (local={:?})", local);
+                error!("connect(): {}", cause);
+                return Err(Fail::new(libc::EADDRINUSE, &cause));
+            }
+            if let Some(qd) = self.runtime.remove_socket_id_to_qd(&SocketId::Passive(bound)) {
+                self.runtime.insert_socket_id_to_qd(SocketId::Passive(local), qd);
+            }
+        }
+        self.addresses.remove(&SocketId::Passive(bound));
         // Insert the connection to receive incoming packets for this address pair.
         // Should we remove the passive entry for the local address if the socket was previously bound?
         if self
@@ -163,7 +503,7 @@ impl SharedTcpPeer {
                 local, remote
             );
         }
-        let local_isn: SeqNumber = self.isn_generator.generate(&local, &remote);
+        let local_isn: SeqNumber = self.isn_generator.generate(&local, &remote, self.runtime.get_now());
         // Wait for connect to complete.
         if let Err(e) = socket.connect(local, remote, local_isn).await {
             self.addresses.remove(&SocketId::Active(local, remote.clone()));
@@ -181,16 +521,29 @@ impl SharedTcpPeer {
         buf.trim(buf.len())
     }
 
+    /// Queues the buffer for sending without waiting for room in the send buffer or for acknowledgement. Fails with
+    /// EAGAIN if the send buffer is full.
+    pub fn try_push(&self, socket: &mut SharedTcpSocket, buf: &mut DemiBuffer) -> Result<(), Fail> {
+        socket.try_push(buf.clone())?;
+        buf.trim(buf.len())
+    }
+
+    /// Waits until at least `min_bytes` can be pushed to the socket without queueing.
+    pub async fn wait_for_writable(&self, socket: &mut SharedTcpSocket, min_bytes: usize) -> Result<(), Fail> {
+        socket.wait_for_writable(min_bytes).await
+    }
+
     /// Sets up a coroutine for popping data from the socket.
     pub async fn pop(
         &self,
         socket: &mut SharedTcpSocket,
         size: usize,
+        flags: PopFlags,
     ) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
         // Grab the queue, make sure it hasn't been closed in the meantime.
         // This will bump the Rc refcount so the coroutine can have it's own reference to the shared queue data
         // structure and the SharedTcpQueue will not be freed until this coroutine finishes.
-        let incoming: DemiBuffer = socket.pop(Some(size)).await?;
+        let incoming: DemiBuffer = socket.pop(Some(size), flags).await?;
         Ok((None, incoming))
     }
 
@@ -200,53 +553,174 @@ impl SharedTcpPeer {
         // Handle result: If unsuccessful, free the new queue descriptor.
         if let Some(socket_id) = socket.close().await? {
             self.addresses.remove(&socket_id);
+            self.remove_port_range(&socket_id);
+            // Connections that we closed first stay in TIME_WAIT, but without the socket.
+            if let (SocketId::Active(local, remote), Some(entry)) = (socket_id, socket.get_time_wait_entry()) {
+                let now: Instant = self.runtime.get_now();
+                self.time_wait.insert(local, remote, entry, now);
+            }
         }
         Ok(())
     }
 
+    /// Returns the number of connections in TIME_WAIT.
+    pub fn get_time_wait_count(&self) -> usize {
+        self.time_wait.get_count()
+    }
+
+    /// Returns the number of closed connections that were not kept in TIME_WAIT because there were too many already.
+    pub fn get_time_wait_overflows(&self) -> u64 {
+        self.time_wait.get_overflows()
+    }
+
+    /// Resets the connection on a TCP socket, if it has one.
+    pub fn abort(&mut self, socket: &mut SharedTcpSocket) {
+        socket.abort()
+    }
+
     pub fn hard_close(&mut self, socket: &mut SharedTcpSocket) -> Result<(), Fail> {
         if let Some(socket_id) = socket.hard_close()? {
             self.addresses.remove(&socket_id);
+            self.remove_port_range(&socket_id);
         }
         Ok(())
     }
 
-    /// Processes an incoming TCP segment.
-    pub fn receive(&mut self, src_ipv4_addr: Ipv4Addr, mut buf: DemiBuffer) {
-        // We can assume that the destination is our local IPv4 address; otherwise, the IP layer would have discarded
-        // the packet already.
-        let tcp_hdr: TcpHeader = match TcpHeader::parse_and_strip(
-            &src_ipv4_addr,
-            &self.local_ipv4_addr,
-            &mut buf,
-            self.tcp_config.get_rx_checksum_offload(),
-        ) {
-            Ok(header) => header,
-            Err(e) => {
-                let cause: String = format!("invalid tcp header: {:?}", e);
-                error!("receive(): {}", &cause);
-                return;
-            },
-        };
-        debug!("TCP received {:?}", tcp_hdr);
-        let local: SocketAddrV4 = SocketAddrV4::new(self.local_ipv4_addr, tcp_hdr.dst_port);
-        let remote: SocketAddrV4 = SocketAddrV4::new(src_ipv4_addr, tcp_hdr.src_port);
-
-        // Retrieve the queue descriptor based on the incoming segment.
-        let socket: &mut SharedTcpSocket = match self.addresses.get_mut(&SocketId::Active(local, remote)) {
-            Some(socket) => socket,
-            None => match self.addresses.get_mut(&SocketId::Passive(local)) {
-                Some(socket) => socket,
-                None => {
-                    let cause: String = format!("no queue descriptor for remote address (remote={})", remote.ip());
+    /// Processes an incoming TCP segment to `dst_ipv4_addr` that was forwarded to us by the host with link address
+    /// `src_link_addr` and carried the `ecn` code point.
+    pub fn receive(
+        &mut self,
+        src_ipv4_addr: Ipv4Addr,
+        dst_ipv4_addr: Ipv4Addr,
+        src_link_addr: MacAddress,
+        ecn: Ecn,
+        mut buf: DemiBuffer,
+    ) {
+        // The destination is either our local IPv4 address, the broadcast address or a foreign address that we
+        // intercept; otherwise, the IP layer would have discarded the packet already. TCP has no use for broadcasts.
+        if dst_ipv4_addr.is_broadcast() {
+            debug!("receive(): dropping broadcast segment (remote={:?})", src_ipv4_addr);
+            return;
+        }
+        let local_ipv4_addr: Ipv4Addr = dst_ipv4_addr;
+        let foreign: bool = local_ipv4_addr != self.layer3_endpoint.get_local_addr();
+        let rx_checksum_offload: bool = self.tcp_config.get_rx_checksum_offload();
+        if rx_checksum_offload
+            && !self
+                .checksum_sampler
+                .check(|| TcpHeader::verify_checksum(&src_ipv4_addr, &local_ipv4_addr, &buf[..]))
+        {
+            return;
+        }
+        let tcp_hdr: TcpHeader =
+            match TcpHeader::parse_and_strip(&src_ipv4_addr, &local_ipv4_addr, &mut buf, rx_checksum_offload) {
+                Ok(header) => header,
+                Err(e) => {
+                    let cause: String = format!("invalid tcp header: {:?}", e);
                     error!("receive(): {}", &cause);
                     return;
                 },
+            };
+        debug!("TCP received {:?}", tcp_hdr);
+        let local: SocketAddrV4 = SocketAddrV4::new(local_ipv4_addr, tcp_hdr.dst_port);
+        let remote: SocketAddrV4 = SocketAddrV4::new(src_ipv4_addr, tcp_hdr.src_port);
+
+        // Connections in TIME_WAIT answer for themselves, unless a new connection takes over their addresses.
+        let now: Instant = self.runtime.get_now();
+        match self.time_wait.receive(local, remote, &tcp_hdr, buf.len(), now) {
+            Some(TimeWaitReply::Drop) => return,
+            Some(TimeWaitReply::Ack(reply)) => {
+                self.transmit_reply(local, remote, reply);
+                return;
+            },
+            Some(TimeWaitReply::Reopen) | None => (),
+        }
+
+        // Retrieve the queue descriptor based on the incoming segment. Connections come first, then listening sockets
+        // that are bound to the local address and those that are bound to the wildcard address, and last those that
+        // listen on a range of ports. Only transparent sockets take new connections to foreign addresses.
+        let wildcard: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, local.port());
+        let socket_id: SocketId = if self.addresses.contains_key(&SocketId::Active(local, remote)) {
+            SocketId::Active(local, remote)
+        } else if self.addresses.contains_key(&SocketId::Passive(local)) {
+            SocketId::Passive(local)
+        } else if self.addresses.contains_key(&SocketId::Passive(wildcard)) {
+            SocketId::Passive(wildcard)
+        } else {
+            self.find_port_range(local).unwrap_or(SocketId::Passive(wildcard))
+        };
+        let on_wildcard: bool = matches!(socket_id, SocketId::Passive(bound) if bound.ip().is_unspecified());
+        let socket: &mut SharedTcpSocket = match self.addresses.get_mut(&socket_id) {
+            Some(socket) if !foreign || !on_wildcard || socket.is_transparent() => socket,
+            // The foreign address is not ours to answer for, so we do not reset the connection either.
+            _ if foreign => {
+                debug!(
+                    "receive(): no transparent socket for foreign address (local={:?}, remote={:?})",
+                    local, remote
+                );
+                return;
+            },
+            _ => {
+                let cause: String = format!("no queue descriptor for remote address (remote={})", remote.ip());
+                error!("receive(): {}", &cause);
+                // Nobody listens on this port, so tell the remote to give up.
+                self.send_rst(local, remote, &tcp_hdr, buf.len());
+                return;
             },
         };
 
         // Dispatch to further processing depending on the socket state.
-        socket.receive(src_ipv4_addr, tcp_hdr, buf)
+        socket.receive(src_ipv4_addr, local_ipv4_addr, src_link_addr, ecn, tcp_hdr, buf)
+    }
+
+    /// Hands the ICMP Fragmentation Needed message [message] to the connection whose segment it quotes.
+    pub fn on_fragmentation_needed(&mut self, message: FragmentationNeeded) {
+        let quoted: &[u8] = &message.transport_header;
+        let local: SocketAddrV4 = SocketAddrV4::new(
+            self.layer3_endpoint.get_local_addr(),
+            u16::from_be_bytes([quoted[0], quoted[1]]),
+        );
+        let remote: SocketAddrV4 =
+            SocketAddrV4::new(message.remote_ipv4_addr, u16::from_be_bytes([quoted[2], quoted[3]]));
+        let seq_no: SeqNumber = SeqNumber::from(u32::from_be_bytes([quoted[4], quoted[5], quoted[6], quoted[7]]));
+        match self.addresses.get_mut(&SocketId::Active(local, remote)) {
+            Some(socket) => socket.on_fragmentation_needed(seq_no, message.mtu),
+            None => debug!(
+                "on_fragmentation_needed(): no connection for the quoted segment (local={:?}, remote={:?})",
+                local, remote
+            ),
+        }
+    }
+
+    /// Answers `tcp_hdr`, a segment from `remote` to `local` that carried `data_len` bytes and belongs to no socket, with
+    /// a RST.
+    fn send_rst(&mut self, local: SocketAddrV4, remote: SocketAddrV4, tcp_hdr: &TcpHeader, data_len: usize) {
+        let reply: TcpHeader = match tcp_hdr.reset_reply(data_len) {
+            Some(reply) => reply,
+            None => return,
+        };
+        debug!("send_rst(): sending RST to {:?}", remote);
+        self.transmit_reply(local, remote, reply);
+    }
+
+    /// Sends `reply`, a segment from `local` that answers one from `remote` outside of any socket.
+    fn transmit_reply(&mut self, local: SocketAddrV4, remote: SocketAddrV4, reply: TcpHeader) {
+        let mut pkt: DemiBuffer = DemiBuffer::new_with_headroom(0, MAX_HEADER_SIZE as u16);
+        reply.serialize_and_attach(
+            &mut pkt,
+            local.ip(),
+            remote.ip(),
+            self.tcp_config.get_tx_checksum_offload(),
+        );
+        if let Err(e) = self.layer3_endpoint.transmit_tcp_packet_nonblocking(
+            *local.ip(),
+            *remote.ip(),
+            PriorityClass::default(),
+            Ecn::NotEct,
+            pkt,
+        ) {
+            warn!("transmit_reply(): could not send reply: {:?}", e);
+        }
     }
 }
 
@@ -0,0 +1,140 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Tables that listening sockets keep per remote address. Every segment that reaches a listening socket is looked up in
+//! several of them, so its remote address is hashed once and the hash is carried along in the key. The hash function is
+//! keyed with a random seed, so that remote peers cannot pick addresses that collide in the tables.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hash, Hasher},
+    net::SocketAddrV4,
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Table that is indexed by remote address.
+pub type RemoteMap<V> = HashMap<RemoteKey, V, BuildHasherDefault<PreHashedHasher>>;
+
+/// Keyed hash function for remote addresses.
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteHasher {
+    seed: u64,
+}
+
+/// Remote address along with its hash.
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteKey {
+    hash: u64,
+    remote: SocketAddrV4,
+}
+
+/// Hasher that passes the hash of a [RemoteKey] through.
+#[derive(Default)]
+pub struct PreHashedHasher(u64);
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl RemoteHasher {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Hashes [remote] into a key for the tables. This is the finalizer of SplitMix64, which mixes every bit of the
+    /// address and the seed into every bit of the hash.
+    pub fn key(&self, remote: SocketAddrV4) -> RemoteKey {
+        let mut hash: u64 = ((u32::from(*remote.ip()) as u64) << 16 | remote.port() as u64) ^ self.seed;
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^= hash >> 31;
+        RemoteKey { hash, remote }
+    }
+}
+
+impl RemoteKey {
+    pub fn get_remote(&self) -> SocketAddrV4 {
+        self.remote
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl PartialEq for RemoteKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.remote == other.remote
+    }
+}
+
+impl Eq for RemoteKey {}
+
+/// Hash trait implementation. Keys are only compared with keys from the same [RemoteHasher], so equal remote addresses
+/// always come with equal hashes.
+impl Hash for RemoteKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl Hasher for PreHashedHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        // Only [RemoteKey] is hashed with this hasher, which writes a single integer.
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ (*byte as u64);
+        }
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{RemoteHasher, RemoteKey, RemoteMap};
+    use ::anyhow::Result;
+    use ::std::net::{Ipv4Addr, SocketAddrV4};
+
+    #[test]
+    fn test_remote_map() -> Result<()> {
+        let hasher: RemoteHasher = RemoteHasher::new(0x1234_5678_9abc_def0);
+        let mut map: RemoteMap<u16> = RemoteMap::default();
+        for port in 0..1024 {
+            map.insert(hasher.key(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), port)), port);
+        }
+
+        let key: RemoteKey = hasher.key(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 80));
+        crate::ensure_eq!(map.get(&key).copied(), Some(80));
+        crate::ensure_eq!(map.remove(&key), Some(80));
+        crate::ensure_eq!(map.get(&key).is_none(), true);
+        crate::ensure_eq!(map.len(), 1023);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remote_hasher_seed() -> Result<()> {
+        let remote: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 80);
+        let key: RemoteKey = RemoteHasher::new(1).key(remote);
+        // Other seeds hash the same address differently.
+        crate::ensure_neq!(key.hash, RemoteHasher::new(2).key(remote).hash);
+        crate::ensure_eq!(key.get_remote(), remote);
+        Ok(())
+    }
+}
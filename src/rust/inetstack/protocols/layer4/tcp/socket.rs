@@ -6,12 +6,25 @@
 //======================================================================================================================
 
 use crate::{
+    collections::async_value::SharedAsyncValue,
     expect_some,
     inetstack::protocols::{
-        layer3::SharedLayer3Endpoint,
-        layer4::tcp::{
-            active_open::SharedActiveOpenSocket, established::EstablishedSocket, header::TcpHeader,
-            passive_open::SharedPassiveSocket, SeqNumber,
+        layer3::{Ecn, SharedLayer3Endpoint},
+        layer4::{
+            stats_delta::StatsDelta,
+            tcp::{
+                active_open::SharedActiveOpenSocket,
+                constants::MAX_SYN_RETRIES,
+                established::{congestion_control, stats::Stats, EstablishedSocket},
+                handoff::{CoreHint, HandoffSender},
+                handshake_stats::SharedHandshakeStats,
+                header::TcpHeader,
+                isn_generator::SharedIsnGenerator,
+                listener_stats::ListenerStats,
+                passive_open::SharedPassiveSocket,
+                time_wait::TimeWaitEntry,
+                SeqNumber,
+            },
         },
     },
     runtime::{
@@ -20,17 +33,20 @@ use crate::{
         network::{
             config::TcpConfig,
             socket::{
-                option::{SocketOption, TcpSocketOptions},
-                SocketId,
+                option::{DelayedAck, PortRange, QuickAck, SocketOption, TcpInfo, TcpSocketOptions},
+                PopFlags, SocketId,
             },
+            types::MacAddress,
         },
         SharedDemiRuntime, SharedObject,
     },
 };
+use ::serde_json::{json, Value};
 use ::std::{
     fmt::Debug,
-    net::{Ipv4Addr, SocketAddrV4},
+    net::{Ipv4Addr, Shutdown, SocketAddrV4},
     ops::{Deref, DerefMut},
+    time::Duration,
 };
 
 //======================================================================================================================
@@ -101,9 +117,201 @@ impl SharedTcpSocket {
     /// Set an SO_* option on the socket.
     pub fn set_socket_option(&mut self, option: SocketOption) -> Result<(), Fail> {
         match option {
-            SocketOption::Linger(linger) => self.socket_options.set_linger(linger),
-            SocketOption::KeepAlive(keep_alive) => self.socket_options.set_keepalive(keep_alive),
-            SocketOption::NoDelay(no_delay) => self.socket_options.set_nodelay(no_delay),
+            SocketOption::Linger(linger) => {
+                self.socket_options.set_linger(linger);
+                // Closing an established connection honors the linger timeout that is set when it closes.
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_linger(linger)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::KeepAlive(_)
+            | SocketOption::KeepAliveIdle(_)
+            | SocketOption::KeepAliveInterval(_)
+            | SocketOption::KeepAliveCount(_) => {
+                match option {
+                    SocketOption::KeepAlive(keep_alive) => self.socket_options.set_keepalive(keep_alive),
+                    SocketOption::KeepAliveIdle(idle) if !idle.is_zero() => {
+                        self.socket_options.set_keepalive_idle(idle)
+                    },
+                    SocketOption::KeepAliveInterval(interval) if !interval.is_zero() => {
+                        self.socket_options.set_keepalive_interval(interval)
+                    },
+                    SocketOption::KeepAliveCount(count) if count > 0 => self.socket_options.set_keepalive_count(count),
+                    _ => {
+                        let cause: String = format!(
+                            "keepalive timers and probe count must be positive (option={:?})",
+                            option
+                        );
+                        error!("set_socket_option(): {}", cause);
+                        return Err(Fail::new(libc::EINVAL, &cause));
+                    },
+                }
+                // Like the priority, keepalive settings apply to connections that are already established.
+                let socket_options: TcpSocketOptions = self.socket_options;
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_keepalive_options(&socket_options)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::NoDelay(no_delay) => {
+                self.socket_options.set_nodelay(no_delay);
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_nodelay(no_delay)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::Cork(cork) => {
+                self.socket_options.set_cork(cork);
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_cork(cork)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::Pacing(_) => {
+                let cause: String = format!("pacing is not supported on TCP sockets");
+                error!("set_socket_option(): {}", cause);
+                return Err(Fail::new(libc::ENOTSUP, &cause));
+            },
+            SocketOption::Priority(priority) => {
+                self.socket_options.set_priority(priority);
+                // Unlike other options, the priority may change on connections that are already established.
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_priority(priority)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::QuickAck(QuickAck::AfterIdle(0)) => {
+                let cause: String = format!("number of segments to acknowledge right away must be positive");
+                error!("set_socket_option(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+            SocketOption::QuickAck(quick_ack) => {
+                self.socket_options.set_quickack(quick_ack);
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_quickack(quick_ack)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::DelayedAck(DelayedAck::Enabled { timeout, segments })
+                if timeout.is_zero() || segments == 0 =>
+            {
+                let cause: String = format!(
+                    "delayed ACK timeout and number of segments must be positive (timeout={:?}, segments={:?})",
+                    timeout, segments
+                );
+                error!("set_socket_option(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+            SocketOption::DelayedAck(delayed_ack) => {
+                self.socket_options.set_delayed_ack(delayed_ack);
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_delayed_ack(delayed_ack)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::ReceiveLowWatermark(bytes) => {
+                self.socket_options.set_receive_low_watermark(bytes);
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_receive_low_watermark(bytes)
+                    },
+                    _ => (),
+                }
+            },
+            SocketOption::TcpInfo(_) => {
+                let cause: String = format!("connection statistics can only be read");
+                error!("set_socket_option(): {}", cause);
+                return Err(Fail::new(libc::ENOPROTOOPT, &cause));
+            },
+            SocketOption::CongestionControl(name) => {
+                // Like Linux, we fail with ENOENT for algorithms that we do not know.
+                let name: &'static str = match congestion_control::get_algorithm(name) {
+                    Some((name, _)) => name,
+                    None => {
+                        let cause: String = format!("unknown congestion control algorithm (name={:?})", name);
+                        error!("set_socket_option(): {}", cause);
+                        return Err(Fail::new(libc::ENOENT, &cause));
+                    },
+                };
+                // The control block picks its algorithm when it is created, so the choice has to come first.
+                match self.state {
+                    SocketState::Unbound | SocketState::Bound(_) => self.socket_options.set_congestion_control(name),
+                    _ => {
+                        let cause: String =
+                            format!("congestion control can only be chosen before connect() or listen()");
+                        error!("set_socket_option(): {}", cause);
+                        return Err(Fail::new(libc::EINVAL, &cause));
+                    },
+                }
+            },
+            // These apply to connects that start afterwards.
+            SocketOption::SynRetries(retries) => {
+                if retries == 0 || retries > MAX_SYN_RETRIES {
+                    let cause: String = format!(
+                        "number of SYN retransmissions must be between 1 and {} (retries={:?})",
+                        MAX_SYN_RETRIES, retries
+                    );
+                    error!("set_socket_option(): {}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                }
+                self.socket_options.set_syn_retries(retries)
+            },
+            SocketOption::SynBackoff(backoff) => {
+                if backoff.initial_timeout.is_zero()
+                    || backoff.multiplier == 0
+                    || backoff.max_timeout < backoff.initial_timeout
+                {
+                    let cause: String = format!("invalid SYN backoff (backoff={:?})", backoff);
+                    error!("set_socket_option(): {}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                }
+                self.socket_options.set_syn_backoff(backoff)
+            },
+            SocketOption::ConnectTimeout(Some(timeout)) if timeout.is_zero() => {
+                let cause: String = format!("connect timeout must be positive");
+                error!("set_socket_option(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+            SocketOption::ConnectTimeout(timeout) => self.socket_options.set_connect_timeout(timeout),
+            SocketOption::Transparent(transparent) => self.socket_options.set_transparent(transparent),
+            SocketOption::ListenPorts(Some(ports)) if ports.first == 0 || ports.first > ports.last => {
+                let cause: String = format!("invalid port range (ports={:?})", ports);
+                error!("set_socket_option(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+            // The peer registers the range when the socket starts listening.
+            SocketOption::ListenPorts(ports) => match self.state {
+                SocketState::Unbound | SocketState::Bound(_) => self.socket_options.set_listen_ports(ports),
+                _ => {
+                    let cause: String = format!("port ranges can only be chosen before listen()");
+                    error!("set_socket_option(): {}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                },
+            },
+            SocketOption::KeepCwndAfterIdleWhenPaced(keep_cwnd) => {
+                self.socket_options.set_keep_cwnd_after_idle_when_paced(keep_cwnd);
+                match self.state {
+                    SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                        socket.cb.set_keep_cwnd_after_idle_when_paced(keep_cwnd)
+                    },
+                    _ => (),
+                }
+            },
         }
         Ok(())
     }
@@ -114,10 +322,63 @@ impl SharedTcpSocket {
         match option {
             SocketOption::Linger(_) => Ok(SocketOption::Linger(self.socket_options.get_linger())),
             SocketOption::KeepAlive(_) => Ok(SocketOption::KeepAlive(self.socket_options.get_keepalive())),
+            SocketOption::KeepAliveIdle(_) => Ok(SocketOption::KeepAliveIdle(self.socket_options.get_keepalive_idle())),
+            SocketOption::KeepAliveInterval(_) => Ok(SocketOption::KeepAliveInterval(
+                self.socket_options.get_keepalive_interval(),
+            )),
+            SocketOption::KeepAliveCount(_) => {
+                Ok(SocketOption::KeepAliveCount(self.socket_options.get_keepalive_count()))
+            },
             SocketOption::NoDelay(_) => Ok(SocketOption::NoDelay(self.socket_options.get_nodelay())),
+            SocketOption::Cork(_) => Ok(SocketOption::Cork(self.socket_options.get_cork())),
+            SocketOption::Pacing(_) => {
+                let cause: String = format!("pacing is not supported on TCP sockets");
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+            SocketOption::Priority(_) => Ok(SocketOption::Priority(self.socket_options.get_priority())),
+            SocketOption::QuickAck(_) => Ok(SocketOption::QuickAck(self.socket_options.get_quickack())),
+            SocketOption::DelayedAck(_) => Ok(SocketOption::DelayedAck(self.socket_options.get_delayed_ack())),
+            SocketOption::ReceiveLowWatermark(_) => Ok(SocketOption::ReceiveLowWatermark(
+                self.socket_options.get_receive_low_watermark(),
+            )),
+            SocketOption::TcpInfo(_) => Ok(SocketOption::TcpInfo(self.get_tcp_info()?)),
+            SocketOption::CongestionControl(_) => Ok(SocketOption::CongestionControl(
+                self.socket_options
+                    .get_congestion_control()
+                    .unwrap_or(self.tcp_config.get_congestion_control_name()),
+            )),
+            SocketOption::SynRetries(_) => Ok(SocketOption::SynRetries(
+                self.socket_options
+                    .get_syn_retries()
+                    .unwrap_or(self.tcp_config.get_syn_retries()),
+            )),
+            SocketOption::SynBackoff(_) => Ok(SocketOption::SynBackoff(
+                self.socket_options
+                    .get_syn_backoff()
+                    .unwrap_or(self.tcp_config.get_syn_backoff()),
+            )),
+            SocketOption::ConnectTimeout(_) => {
+                Ok(SocketOption::ConnectTimeout(self.socket_options.get_connect_timeout()))
+            },
+            SocketOption::Transparent(_) => Ok(SocketOption::Transparent(self.socket_options.get_transparent())),
+            SocketOption::ListenPorts(_) => Ok(SocketOption::ListenPorts(self.socket_options.get_listen_ports())),
+            SocketOption::KeepCwndAfterIdleWhenPaced(_) => Ok(SocketOption::KeepCwndAfterIdleWhenPaced(
+                self.socket_options.get_keep_cwnd_after_idle_when_paced(),
+            )),
         }
     }
 
+    /// Checks whether the socket takes connections to the foreign addresses that the stack intercepts.
+    pub fn is_transparent(&self) -> bool {
+        self.socket_options.get_transparent()
+    }
+
+    /// Returns the range of ports that the socket takes connections to once it listens, if it was given one.
+    pub fn get_listen_ports(&self) -> Option<PortRange> {
+        self.socket_options.get_listen_ports()
+    }
+
     /// Gets the peer address of the socket.
     pub fn getpeername(&mut self) -> Result<SocketAddrV4, Fail> {
         match self.state {
@@ -139,8 +400,26 @@ impl SharedTcpSocket {
         Ok(())
     }
 
+    /// Moves a bound or listening socket over to the `local` address. Sockets in any other state are already
+    /// connected, so they keep their address.
+    pub fn rebind(&mut self, local: SocketAddrV4) {
+        match self.state {
+            SocketState::Bound(_) => self.state = SocketState::Bound(local),
+            SocketState::Listening(ref mut listening_socket) => listening_socket.rebind(local),
+            _ => (),
+        }
+    }
+
     /// Sets the target queue to listen for incoming connections.
-    pub fn listen(&mut self, backlog: usize, nonce: u32) -> Result<(), Fail> {
+    pub fn listen(
+        &mut self,
+        backlog: usize,
+        isn_generator: SharedIsnGenerator,
+        syn_cookie_secret: u64,
+        hash_seed: u64,
+        handshake_stats: SharedHandshakeStats,
+        poll_iteration: SharedAsyncValue<u64>,
+    ) -> Result<(), Fail> {
         let passive_socket: SharedPassiveSocket = SharedPassiveSocket::new(
             expect_some!(
                 self.local(),
@@ -151,12 +430,132 @@ impl SharedTcpSocket {
             self.layer3_endpoint.clone(),
             self.tcp_config.clone(),
             self.socket_options.clone(),
-            nonce,
+            isn_generator,
+            syn_cookie_secret,
+            hash_seed,
+            handshake_stats,
+            poll_iteration,
         )?;
         self.state = SocketState::Listening(passive_socket);
         Ok(())
     }
 
+    /// Hands connections that the listening socket establishes off to worker cores through `handoff`.
+    pub fn set_handoff(&mut self, handoff: HandoffSender) -> Result<(), Fail> {
+        match self.state {
+            SocketState::Listening(ref mut listening_socket) => {
+                listening_socket.set_handoff(handoff);
+                Ok(())
+            },
+            _ => {
+                let cause: String = format!("socket is not listening");
+                error!("set_handoff(): {}", &cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+
+    /// Sets the hand-off channels of the worker cores that accept hints of the listening socket refer to.
+    pub fn set_handoff_targets(&mut self, targets: Vec<HandoffSender>) -> Result<(), Fail> {
+        match self.state {
+            SocketState::Listening(ref mut listening_socket) => {
+                listening_socket.set_handoff_targets(targets);
+                Ok(())
+            },
+            _ => {
+                let cause: String = format!("socket is not listening");
+                error!("set_handoff_targets(): {}", &cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+
+    /// Checks whether there are connections waiting to be accepted on the listening socket.
+    pub fn has_pending_connections(&self) -> Result<bool, Fail> {
+        match self.state {
+            SocketState::Listening(ref listening_socket) => Ok(listening_socket.has_pending_connections()),
+            _ => {
+                let cause: String = format!("socket is not listening");
+                error!("has_pending_connections(): {}", &cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+
+    pub fn get_listener_stats(&self) -> Result<ListenerStats, Fail> {
+        match self.state {
+            SocketState::Listening(ref listening_socket) => Ok(listening_socket.get_listener_stats()),
+            _ => {
+                let cause: String = format!("socket is not listening");
+                error!("get_listener_stats(): {}", &cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+
+    /// Returns the change in the statistics of the connection since they were last reset, and resets them if [reset] is
+    /// set.
+    pub fn snapshot_stats(&mut self, reset: bool) -> Result<StatsDelta<Stats>, Fail> {
+        match self.state {
+            SocketState::Established(ref mut socket) => Ok(socket.snapshot_stats(reset)),
+            _ => {
+                let cause: String = format!("socket is not connected");
+                error!("snapshot_stats(): {}", &cause);
+                Err(Fail::new(libc::ENOTCONN, &cause))
+            },
+        }
+    }
+
+    /// Returns what the connection needs to remember while it is in TIME_WAIT, if a close got it there.
+    pub fn get_time_wait_entry(&self) -> Option<TimeWaitEntry> {
+        match self.state {
+            SocketState::Established(ref socket) => socket.get_time_wait_entry(self.tcp_config.get_msl()),
+            _ => None,
+        }
+    }
+
+    /// Returns a snapshot of the state of the connection (TCP_INFO). Connections that are closing are included, so that
+    /// a stuck close can be debugged as well.
+    pub fn get_tcp_info(&self) -> Result<TcpInfo, Fail> {
+        match self.state {
+            SocketState::Established(ref socket) | SocketState::Closing(ref socket) => Ok(socket.get_tcp_info()),
+            _ => {
+                let cause: String = format!("socket is not connected");
+                error!("get_tcp_info(): {}", &cause);
+                Err(Fail::new(libc::ENOTCONN, &cause))
+            },
+        }
+    }
+
+    /// Returns the most recent error that affected the connection and clears it. Sockets that are not connected have
+    /// not run into any errors that the application would not have seen already.
+    pub fn get_last_error(&mut self) -> Option<Fail> {
+        match self.state {
+            SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => socket.take_last_error(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of received bytes that the application has not popped yet. Sockets that are not connected
+    /// have not received any data.
+    pub fn get_buffered_bytes(&self) -> usize {
+        match self.state {
+            SocketState::Established(ref socket) | SocketState::Closing(ref socket) => socket.get_buffered_bytes(),
+            _ => 0,
+        }
+    }
+
+    /// Shrinks the segments of the connection, as a router reported that it dropped the one at [seq_no] because it was
+    /// larger than [mtu]. Connections that are not established yet only send segments that are small anyway.
+    pub fn on_fragmentation_needed(&mut self, seq_no: SeqNumber, mtu: u16) {
+        match self.state {
+            SocketState::Established(ref mut socket) | SocketState::Closing(ref mut socket) => {
+                socket.on_fragmentation_needed(seq_no, mtu)
+            },
+            _ => debug!("on_fragmentation_needed(): ignoring report for a socket that is not connected"),
+        }
+    }
+
     pub async fn accept(&mut self) -> Result<SharedTcpSocket, Fail> {
         // Wait for a new connection on the listening socket.
         let mut listening_socket: SharedPassiveSocket = match self.state {
@@ -175,6 +574,19 @@ impl SharedTcpSocket {
         Ok(new_queue)
     }
 
+    /// Waits for the next connection and hands it off to the worker core with hint `core`.
+    pub async fn accept_on_core(&mut self, core: CoreHint) -> Result<SocketAddrV4, Fail> {
+        let mut listening_socket: SharedPassiveSocket = match self.state {
+            SocketState::Listening(ref listening_socket) => listening_socket.clone(),
+            _ => {
+                let cause: String = format!("socket is not listening");
+                error!("accept_on_core(): {}", &cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            },
+        };
+        listening_socket.do_accept_on_core(core).await
+    }
+
     pub async fn connect(
         &mut self,
         local: SocketAddrV4,
@@ -208,18 +620,53 @@ impl SharedTcpSocket {
         }
     }
 
-    pub async fn pop(&mut self, size: Option<usize>) -> Result<DemiBuffer, Fail> {
+    pub fn try_push(&mut self, buf: DemiBuffer) -> Result<(), Fail> {
+        match self.state {
+            SocketState::Established(ref mut socket) => socket.try_push(buf),
+            _ => {
+                let cause: String = format!("socket is not in established state");
+                error!("try_push(): {}", &cause);
+                Err(Fail::new(libc::ENOTCONN, &cause))
+            },
+        }
+    }
+
+    pub async fn wait_for_writable(&mut self, min_bytes: usize) -> Result<(), Fail> {
+        match self.state {
+            SocketState::Established(ref mut socket) => socket.wait_for_writable(min_bytes).await,
+            _ => unreachable!("State machine check should ensure that this socket is connected"),
+        }
+    }
+
+    pub async fn pop(&mut self, size: Option<usize>, flags: PopFlags) -> Result<DemiBuffer, Fail> {
         match self.state {
-            SocketState::Established(ref mut socket) => socket.pop(size).await,
+            SocketState::Established(ref mut socket) if flags.peek => socket.peek(size).await,
+            SocketState::Established(ref mut socket) => match size {
+                Some(size) if flags.wait_all => socket.pop_all(size).await,
+                _ => socket.pop(size).await,
+            },
             _ => unreachable!("State machine check should ensure that this socket is connected"),
         }
     }
 
+    /// Shuts down one or both directions of an established connection.
+    pub fn shutdown(&mut self, how: Shutdown) -> Result<(), Fail> {
+        match self.state {
+            SocketState::Established(ref mut socket) => socket.shutdown(how),
+            _ => {
+                let cause: String = format!("socket is not connected");
+                error!("shutdown(): {}", cause);
+                Err(Fail::new(libc::ENOTCONN, &cause))
+            },
+        }
+    }
+
     pub async fn close(&mut self) -> Result<Option<SocketId>, Fail> {
+        let fin_timeout: Duration = self.tcp_config.get_fin_timeout();
         match self.state {
             // Closing an active socket.
             SocketState::Established(ref mut socket) => {
-                socket.close().await?;
+                socket.close(fin_timeout).await?;
                 Ok(Some(SocketId::Active(socket.endpoints().0, socket.endpoints().1)))
             },
             // Closing a listening socket.
@@ -243,6 +690,13 @@ impl SharedTcpSocket {
         }
     }
 
+    /// Resets an established connection, so that our peer learns right away that we gave up on it.
+    pub fn abort(&mut self) {
+        if let SocketState::Established(ref mut socket) = self.state {
+            socket.abort();
+        }
+    }
+
     pub fn hard_close(&mut self) -> Result<Option<SocketId>, Fail> {
         match self.state {
             // Closing an active socket.
@@ -279,7 +733,17 @@ impl SharedTcpSocket {
         }
     }
 
-    pub fn receive(&mut self, ip_hdr: Ipv4Addr, tcp_hdr: TcpHeader, buf: DemiBuffer) {
+    /// Processes a segment from `ip_hdr` to `local_ipv4_addr`. Only listening sockets need to know the latter, since it
+    /// is a foreign address for connections that transparent sockets take.
+    pub fn receive(
+        &mut self,
+        ip_hdr: Ipv4Addr,
+        local_ipv4_addr: Ipv4Addr,
+        link_addr: MacAddress,
+        ecn: Ecn,
+        tcp_hdr: TcpHeader,
+        buf: DemiBuffer,
+    ) {
         match self.state {
             SocketState::Unbound => {
                 warn!("Cannot receive packets on a non-listening or connected socket. Dropping packet.")
@@ -287,10 +751,12 @@ impl SharedTcpSocket {
             SocketState::Bound(_) => {
                 warn!("Cannot receive packets on a non-listening or connected socket. Dropping packet.")
             },
-            SocketState::Listening(ref mut socket) => socket.receive(ip_hdr, tcp_hdr, buf),
+            SocketState::Listening(ref mut socket) => {
+                socket.receive(ip_hdr, local_ipv4_addr, link_addr, ecn, tcp_hdr, buf)
+            },
             SocketState::Connecting(ref mut socket) => socket.receive(ip_hdr, tcp_hdr, buf),
-            SocketState::Established(ref socket) => socket.get_cb().receive(tcp_hdr, buf),
-            SocketState::Closing(ref socket) => socket.get_cb().receive(tcp_hdr, buf),
+            SocketState::Established(ref socket) => socket.get_cb().receive(ecn, tcp_hdr, buf),
+            SocketState::Closing(ref socket) => socket.get_cb().receive(ecn, tcp_hdr, buf),
         }
     }
 
@@ -317,6 +783,43 @@ impl SharedTcpSocket {
             SocketState::Closing(ref socket) => Some(socket.endpoints().1),
         }
     }
+
+    /// Describes the socket for a state dump. Unless `scrub_payload` is set, this includes the data that is queued for
+    /// sending or reading.
+    pub fn dump_state(&self, scrub_payload: bool) -> Value {
+        let local: Option<String> = self.local().map(|addr| addr.to_string());
+        let remote: Option<String> = self.remote().map(|addr| addr.to_string());
+        match self.state {
+            SocketState::Unbound => json!({ "protocol": "tcp", "state": "unbound" }),
+            SocketState::Bound(_) => json!({ "protocol": "tcp", "state": "bound", "local": local }),
+            SocketState::Listening(ref socket) => json!({
+                "protocol": "tcp",
+                "state": "listening",
+                "local": local,
+                "listener": socket.dump_state(),
+            }),
+            SocketState::Connecting(_) => json!({
+                "protocol": "tcp",
+                "state": "connecting",
+                "local": local,
+                "remote": remote,
+            }),
+            SocketState::Established(ref socket) => json!({
+                "protocol": "tcp",
+                "state": "established",
+                "local": local,
+                "remote": remote,
+                "connection": socket.dump_state(scrub_payload),
+            }),
+            SocketState::Closing(ref socket) => json!({
+                "protocol": "tcp",
+                "state": "closing",
+                "local": local,
+                "remote": remote,
+                "connection": socket.dump_state(scrub_payload),
+            }),
+        }
+    }
 }
 
 //======================================================================================================================
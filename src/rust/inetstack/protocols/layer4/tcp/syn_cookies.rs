@@ -0,0 +1,201 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+use ::std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    net::SocketAddrV4,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// MSS values that a SYN cookie can encode. The MSS of our peer is rounded down to the closest one of them.
+const MSS_TABLE: [u16; 8] = [536, 1024, 1220, 1360, 1400, 1440, 1460, 8960];
+
+/// How long each value of the counter that is encoded in a SYN cookie lasts.
+const COUNTER_PERIOD: Duration = Duration::from_secs(64);
+
+/// Number of counter periods for which a SYN cookie is accepted, starting with the one in which it was issued.
+const MAX_COOKIE_AGE: u64 = 2;
+
+/// Bits of a SYN cookie that carry the counter, the index into [MSS_TABLE] and the keyed hash of the connection.
+const COUNTER_SHIFT: u32 = 27;
+const MSS_INDEX_SHIFT: u32 = 24;
+const HASH_MASK: u32 = (1 << MSS_INDEX_SHIFT) - 1;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Generates and validates SYN cookies, which are initial sequence numbers that encode what we need to know about a
+/// connection to establish it from the final ACK of its handshake alone. This way, a listening socket keeps no state for
+/// handshakes that are in progress.
+///
+/// A cookie is laid out as follows, from the most significant bit on:
+/// - 5 bits of a counter that advances every [COUNTER_PERIOD], so that cookies expire.
+/// - 3 bits of the index of the MSS of our peer in [MSS_TABLE].
+/// - 24 bits of a keyed hash of the counter, the addresses of the connection and the initial sequence number of our
+///   peer, so that cookies cannot be forged.
+///
+/// Other options of the SYN segment (window scaling, SACK, timestamps and ECN) do not fit into a cookie, so connections
+/// that are established from one do without them.
+pub struct SynCookieGenerator {
+    secret: u64,
+    epoch: Instant,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl SynCookieGenerator {
+    pub fn new(secret: u64, epoch: Instant) -> Self {
+        Self { secret, epoch }
+    }
+
+    /// Generates the cookie to use as our initial sequence number in the SYN+ACK that answers a SYN with sequence number
+    /// `remote_isn` and an MSS of `mss`.
+    pub fn generate(
+        &self,
+        local: &SocketAddrV4,
+        remote: &SocketAddrV4,
+        remote_isn: SeqNumber,
+        mss: usize,
+        now: Instant,
+    ) -> SeqNumber {
+        let counter: u64 = self.counter(now);
+        let mss_index: usize = MSS_TABLE
+            .iter()
+            .rposition(|table_mss| *table_mss as usize <= mss)
+            .unwrap_or(0);
+        let cookie: u32 = ((counter as u32) << COUNTER_SHIFT)
+            | ((mss_index as u32) << MSS_INDEX_SHIFT)
+            | self.hash(local, remote, remote_isn, counter);
+        SeqNumber::from(cookie)
+    }
+
+    /// Checks whether `cookie` is a cookie that we generated for a SYN with sequence number `remote_isn` and that did
+    /// not expire yet. If so, returns the MSS that it encodes.
+    pub fn validate(
+        &self,
+        local: &SocketAddrV4,
+        remote: &SocketAddrV4,
+        remote_isn: SeqNumber,
+        cookie: SeqNumber,
+        now: Instant,
+    ) -> Option<usize> {
+        let cookie: u32 = cookie.into();
+        let now_counter: u64 = self.counter(now);
+        for age in 0..MAX_COOKIE_AGE {
+            let counter: u64 = match now_counter.checked_sub(age) {
+                Some(counter) => counter,
+                None => break,
+            };
+            if (counter as u32) & 0x1f != cookie >> COUNTER_SHIFT {
+                continue;
+            }
+            if self.hash(local, remote, remote_isn, counter) != cookie & HASH_MASK {
+                return None;
+            }
+            let mss_index: usize = ((cookie >> MSS_INDEX_SHIFT) & 0x7) as usize;
+            return Some(MSS_TABLE[mss_index] as usize);
+        }
+        None
+    }
+
+    /// Returns the number of counter periods that passed since the generator was created.
+    fn counter(&self, now: Instant) -> u64 {
+        (now.saturating_duration_since(self.epoch).as_secs()) / COUNTER_PERIOD.as_secs()
+    }
+
+    /// Computes the keyed hash of a connection for the counter period `counter`.
+    fn hash(&self, local: &SocketAddrV4, remote: &SocketAddrV4, remote_isn: SeqNumber, counter: u64) -> u32 {
+        let mut hasher: DefaultHasher = DefaultHasher::new();
+        self.secret.hash(&mut hasher);
+        local.hash(&mut hasher);
+        remote.hash(&mut hasher);
+        u32::from(remote_isn).hash(&mut hasher);
+        counter.hash(&mut hasher);
+        (hasher.finish() as u32) & HASH_MASK
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{SynCookieGenerator, COUNTER_PERIOD, MAX_COOKIE_AGE};
+    use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_syn_cookie_round_trip() -> Result<()> {
+        let now: Instant = Instant::now();
+        let generator: SynCookieGenerator = SynCookieGenerator::new(0x1234_5678_9abc_def0, now);
+        let local: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 80);
+        let remote: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 50000);
+        let remote_isn: SeqNumber = SeqNumber::from(4242);
+
+        // The MSS of our peer is rounded down to one that the cookie can encode.
+        let cookie: SeqNumber = generator.generate(&local, &remote, remote_isn, 1450, now);
+        crate::ensure_eq!(generator.validate(&local, &remote, remote_isn, cookie, now), Some(1440));
+        let cookie: SeqNumber = generator.generate(&local, &remote, remote_isn, 100, now);
+        crate::ensure_eq!(generator.validate(&local, &remote, remote_isn, cookie, now), Some(536));
+
+        // The cookie only matches the connection that it was generated for.
+        let cookie: SeqNumber = generator.generate(&local, &remote, remote_isn, 1460, now);
+        let other: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 3), 50000);
+        crate::ensure_eq!(generator.validate(&local, &other, remote_isn, cookie, now), None);
+        crate::ensure_eq!(
+            generator.validate(&local, &remote, remote_isn + SeqNumber::from(1), cookie, now),
+            None
+        );
+        crate::ensure_eq!(
+            generator.validate(&local, &remote, remote_isn, cookie + SeqNumber::from(1), now),
+            None
+        );
+
+        // Cookies from a generator with a different secret are rejected.
+        let other_generator: SynCookieGenerator = SynCookieGenerator::new(1, now);
+        crate::ensure_eq!(other_generator.validate(&local, &remote, remote_isn, cookie, now), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_syn_cookie_expiry() -> Result<()> {
+        let epoch: Instant = Instant::now();
+        let generator: SynCookieGenerator = SynCookieGenerator::new(7, epoch);
+        let local: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 80);
+        let remote: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 50000);
+        let remote_isn: SeqNumber = SeqNumber::from(1);
+
+        let issued_at: Instant = epoch + COUNTER_PERIOD - Duration::from_secs(1);
+        let cookie: SeqNumber = generator.generate(&local, &remote, remote_isn, 1460, issued_at);
+
+        // A cookie stays valid into the next counter period, but not beyond.
+        let still_valid: Instant = epoch + COUNTER_PERIOD * (MAX_COOKIE_AGE as u32 - 1);
+        crate::ensure_eq!(
+            generator.validate(&local, &remote, remote_isn, cookie, still_valid),
+            Some(1460)
+        );
+        let expired: Instant = epoch + COUNTER_PERIOD * MAX_COOKIE_AGE as u32;
+        crate::ensure_eq!(generator.validate(&local, &remote, remote_isn, cookie, expired), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::tests::{connect, connect_and_accept, dump_socket_state, get_stat, listen, MAX_ROUNDS},
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{self, engine::SharedEngine},
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::consts::TCP_ACK_DELAY_TIMEOUT,
+        queue::{QDesc, QToken},
+    },
+    DelayedAck, QuickAck, SocketOption, TcpInfo,
+};
+use ::anyhow::Result;
+use ::serde_json::Value;
+use ::std::{
+    collections::VecDeque,
+    net::SocketAddrV4,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Makes Bob send [num_segments] full-sized segments on [bob_fd] and returns the frames that carry them.
+fn send_segments(bob: &mut SharedEngine, bob_fd: QDesc, num_segments: usize) -> Result<VecDeque<DemiBuffer>> {
+    let mss: usize = match bob.get_socket_option(bob_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => info.mss,
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    };
+    let _: QToken = bob.tcp_push(
+        bob_fd,
+        DemiBuffer::from_slice_with_headroom(&vec![1; num_segments * mss], MAX_HEADER_SIZE)?,
+    )?;
+    // The background sender may only get to the rest of the buffer in the next poll.
+    let mut frames: VecDeque<DemiBuffer> = VecDeque::with_capacity(num_segments);
+    for _ in 0..MAX_ROUNDS {
+        bob.poll();
+        frames.extend(bob.pop_all_frames());
+        if frames.len() >= num_segments {
+            break;
+        }
+    }
+    crate::ensure_eq!(frames.len(), num_segments);
+    Ok(frames)
+}
+
+/// Hands [frame] over to [engine] and returns the number of frames that it sends in response.
+fn deliver(engine: &mut SharedEngine, frame: DemiBuffer) -> usize {
+    engine.push_frame(frame);
+    engine.poll();
+    engine.pop_all_frames().len()
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_ack_stretching() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = SharedEngine::new(
+        test_helpers::CARRIE_ACK_STRETCH_CONFIG_PATH,
+        test_helpers::SharedTestPhysicalLayer::new_test(now),
+        now,
+    )?;
+    let (bob_fd, _): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Carrie acknowledges four segments with a single ACK.
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 4)?;
+    for _ in 0..3 {
+        crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+    }
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+
+    // Once a segment goes missing, she tells Bob about every segment that arrives out of order.
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 3)?;
+    let _: Option<DemiBuffer> = frames.pop_front();
+    for frame in frames {
+        crate::ensure_eq!(deliver(&mut carrie, frame), 1);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_out_of_order_arrivals_are_counted() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // The first segment goes missing, so Carrie stores the other two out of order and lets the sender side know.
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 3)?;
+    let missing: DemiBuffer = frames.pop_front().unwrap();
+    for frame in frames {
+        deliver(&mut carrie, frame);
+    }
+    crate::ensure_eq!(get_stat(&mut carrie, carrie_fd, "out_of_order_segments_received")?, 2);
+
+    // Segments that fill the hole arrive in order.
+    deliver(&mut carrie, missing);
+    crate::ensure_eq!(get_stat(&mut carrie, carrie_fd, "out_of_order_segments_received")?, 2);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_quickack() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // By default, Carrie acknowledges every second segment.
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 2)?;
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+
+    // With quick ACKs, she acknowledges every segment right away.
+    carrie.set_socket_option(carrie_fd, SocketOption::QuickAck(QuickAck::Always))?;
+    for frame in send_segments(&mut bob, bob_fd, 2)? {
+        crate::ensure_eq!(deliver(&mut carrie, frame), 1);
+    }
+
+    // A budget of quick ACKs goes to the first segments after the option is set and after every idle period.
+    carrie.set_socket_option(carrie_fd, SocketOption::QuickAck(QuickAck::AfterIdle(2)))?;
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 3)?;
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+    // The connection goes idle for longer than the ACK delay, but not long enough for Bob to retransmit.
+    let carrie_now: Instant = carrie.get_runtime().get_now();
+    carrie.advance_clock(carrie_now + TCP_ACK_DELAY_TIMEOUT + Duration::from_millis(1));
+    carrie.poll();
+    crate::ensure_eq!(carrie.pop_all_frames().len(), 1);
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 3)?;
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_out_of_order_store_is_limited_by_window() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+    let mss: usize = match carrie.get_socket_option(carrie_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => info.mss,
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    };
+
+    // Carrie holds on to as many full-sized segments out of order as fit in her receive window.
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 21)?;
+    let missing: DemiBuffer = frames.pop_front().unwrap();
+    for frame in frames {
+        deliver(&mut carrie, frame);
+    }
+    let receiver: Value = dump_socket_state(&mut carrie, carrie_fd, true)?["connection"]["receiver"].clone();
+    crate::ensure_eq!(
+        receiver["out_of_order"].as_array().map(|segments| segments.len()),
+        Some(20)
+    );
+    crate::ensure_eq!(receiver["out_of_order_bytes"], 20 * mss);
+    crate::ensure_eq!(get_stat(&mut carrie, carrie_fd, "out_of_order_segments_dropped")?, 0);
+    deliver(&mut carrie, missing);
+    let receiver: Value = dump_socket_state(&mut carrie, carrie_fd, true)?["connection"]["receiver"].clone();
+    crate::ensure_eq!(receiver["out_of_order_bytes"], 0);
+
+    // Tiny segments run out of room in the store before they fill the window, and the furthest ones are dropped.
+    let num_segments: usize = u16::MAX as usize / mss + 4;
+    let mut frames: VecDeque<DemiBuffer> = VecDeque::with_capacity(num_segments);
+    for _ in 0..num_segments {
+        let _: QToken = bob.tcp_push(bob_fd, DemiBuffer::from_slice_with_headroom(&[1], MAX_HEADER_SIZE)?)?;
+        bob.poll();
+        frames.extend(bob.pop_all_frames());
+    }
+    crate::ensure_eq!(frames.len(), num_segments);
+    let _: Option<DemiBuffer> = frames.pop_front();
+    for frame in frames {
+        deliver(&mut carrie, frame);
+    }
+    let receiver: Value = dump_socket_state(&mut carrie, carrie_fd, true)?["connection"]["receiver"].clone();
+    let num_stored: usize = receiver["out_of_order"].as_array().map_or(0, |segments| segments.len());
+    let num_dropped: usize = get_stat(&mut carrie, carrie_fd, "out_of_order_segments_dropped")? as usize;
+    crate::ensure_eq!(num_dropped > 0, true);
+    crate::ensure_eq!(num_stored + num_dropped, num_segments - 1);
+    crate::ensure_eq!(receiver["out_of_order_bytes"], num_stored);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_delayed_ack_policy() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Without delayed ACKs, Carrie acknowledges every segment.
+    carrie.set_socket_option(carrie_fd, SocketOption::DelayedAck(DelayedAck::Disabled))?;
+    for frame in send_segments(&mut bob, bob_fd, 2)? {
+        crate::ensure_eq!(deliver(&mut carrie, frame), 1);
+    }
+
+    // With her own policy, she waits for as many segments as she was told to, or for her own timeout.
+    let timeout: Duration = Duration::from_millis(100);
+    let policy: DelayedAck = DelayedAck::Enabled { timeout, segments: 3 };
+    carrie.set_socket_option(carrie_fd, SocketOption::DelayedAck(policy))?;
+    match carrie.get_socket_option(carrie_fd, SocketOption::DelayedAck(DelayedAck::Default))? {
+        SocketOption::DelayedAck(delayed_ack) => crate::ensure_eq!(delayed_ack, policy),
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    }
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 4)?;
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+    let carrie_now: Instant = carrie.get_runtime().get_now();
+    carrie.advance_clock(carrie_now + timeout);
+    carrie.poll();
+    crate::ensure_eq!(carrie.pop_all_frames().len(), 1);
+
+    // Back on the stack's policy, she acknowledges every second full-sized segment.
+    carrie.set_socket_option(carrie_fd, SocketOption::DelayedAck(DelayedAck::Default))?;
+    let mut frames: VecDeque<DemiBuffer> = send_segments(&mut bob, bob_fd, 2)?;
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 0);
+    crate::ensure_eq!(deliver(&mut carrie, frames.pop_front().unwrap()), 1);
+
+    // Policies that never acknowledge anything are rejected.
+    let policy: DelayedAck = DelayedAck::Enabled { timeout, segments: 0 };
+    match carrie.set_socket_option(carrie_fd, SocketOption::DelayedAck(policy)) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+        Ok(()) => anyhow::bail!("a policy without segments should be rejected"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_ack_piggybacks_on_data() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let listen_fd: QDesc = listen(&mut carrie, remote)?;
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    bob.set_socket_option(bob_fd, SocketOption::CongestionControl("cubic"))?;
+    let carrie_fd: QDesc = connect_and_accept(&mut bob, &mut carrie, bob_fd, listen_fd, remote)?;
+    bob.set_socket_option(bob_fd, SocketOption::QuickAck(QuickAck::Always))?;
+
+    // Bob pushes more than his congestion window allows, so some of his data waits for Carrie's ACKs.
+    let mss: usize = match bob.get_socket_option(bob_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => info.mss,
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    };
+    let _: QToken = bob.tcp_push(
+        bob_fd,
+        DemiBuffer::from_slice_with_headroom(&vec![1; 16 * mss], MAX_HEADER_SIZE)?,
+    )?;
+    for _ in 0..MAX_ROUNDS {
+        bob.poll();
+        for frame in bob.pop_all_frames() {
+            carrie.push_frame(frame);
+        }
+    }
+    // Carrie's own ACKs go missing, but the data that she sends back acknowledges everything that she received.
+    let _: VecDeque<DemiBuffer> = carrie.pop_all_frames();
+    let _: QToken = carrie.tcp_push(
+        carrie_fd,
+        DemiBuffer::from_slice_with_headroom(&[1; 8], MAX_HEADER_SIZE)?,
+    )?;
+    carrie.poll();
+    let frames: VecDeque<DemiBuffer> = carrie.pop_all_frames();
+    crate::ensure_eq!(frames.len(), 1);
+
+    // Bob owes Carrie an ACK right away, but the data that her ACK lets him send carries it instead.
+    for frame in frames {
+        bob.push_frame(frame);
+    }
+    bob.poll();
+    let frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+    crate::ensure_eq!(frames.is_empty(), false);
+    for frame in &frames {
+        crate::ensure_eq!(frame.len() > MAX_HEADER_SIZE, true);
+    }
+    crate::ensure_eq!(get_stat(&mut bob, bob_fd, "piggybacked_acks")?, 1);
+
+    Ok(())
+}
@@ -0,0 +1,318 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::tests::{connect_to, exchange_frames, listen, MAX_ROUNDS},
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{
+            self,
+            engine::{SharedEngine, TIMEOUT_SECONDS},
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::types::MacAddress,
+        queue::{OperationResult, QDesc, QToken},
+    },
+    PortRange, SocketOption,
+};
+use ::anyhow::Result;
+use ::std::{
+    collections::VecDeque,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    time::Instant,
+};
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Makes Bob connect to [remote], with a SYN that seems to come from [link_addr] rather than from Bob. Returns the
+/// token of the connect and the SYN-ACK with which Carrie answers.
+fn connect_from_link_addr(
+    bob: &mut SharedEngine,
+    carrie: &mut SharedEngine,
+    bob_fd: QDesc,
+    remote: SocketAddrV4,
+    link_addr: MacAddress,
+) -> Result<(QToken, DemiBuffer)> {
+    let connect_qt: QToken = bob.tcp_connect(bob_fd, remote)?;
+    bob.poll();
+    let mut frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+    crate::ensure_eq!(frames.len(), 1);
+    let mut syn: DemiBuffer = frames.pop_front().unwrap();
+    syn[6..12].copy_from_slice(link_addr.as_bytes());
+    carrie.push_frame(syn);
+    carrie.poll();
+    let mut frames: VecDeque<DemiBuffer> = carrie.pop_all_frames();
+    crate::ensure_eq!(frames.len(), 1);
+    Ok((connect_qt, frames.pop_front().unwrap()))
+}
+
+/// Returns the link address that [frame] is sent to.
+fn get_dst_link_addr(frame: &DemiBuffer) -> MacAddress {
+    MacAddress::from_bytes(&frame[0..6])
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_listen_specific_over_wildcard_address() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+
+    // Carrie listens on the wildcard address and on her address, on the same port.
+    let port: u16 = 80;
+    let carrie_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, port);
+    let wildcard_fd: QDesc = carrie.tcp_socket()?;
+    carrie.tcp_bind(wildcard_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port))?;
+    carrie.tcp_listen(wildcard_fd, 8)?;
+    let specific_fd: QDesc = carrie.tcp_socket()?;
+    carrie.tcp_bind(specific_fd, carrie_addr)?;
+    carrie.tcp_listen(specific_fd, 8)?;
+    let wildcard_qt: QToken = carrie.tcp_accept(wildcard_fd)?;
+    let specific_qt: QToken = carrie.tcp_accept(specific_fd)?;
+
+    // The listening socket that is bound to Carrie's address gets the connection.
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    connect_to(&mut bob, &mut carrie, bob_fd, carrie_addr)?;
+    match carrie.wait(specific_qt, TIMEOUT_SECONDS)? {
+        (qd, OperationResult::Accept(_)) if qd == specific_fd => (),
+        _ => anyhow::bail!("accept should have succeeded on the specific address"),
+    }
+
+    // Once it is closed, the listening socket that is bound to the wildcard address gets them, and the connection uses
+    // Carrie's address.
+    let close_qt: QToken = carrie.tcp_async_close(specific_fd)?;
+    match carrie.wait(close_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Close) => (),
+        _ => anyhow::bail!("close should have succeeded"),
+    }
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    connect_to(&mut bob, &mut carrie, bob_fd, carrie_addr)?;
+    match carrie.wait(wildcard_qt, TIMEOUT_SECONDS)? {
+        (qd, OperationResult::Accept((_, remote))) if qd == wildcard_fd => {
+            crate::ensure_eq!(*remote.ip(), test_helpers::BOB_IPV4)
+        },
+        _ => anyhow::bail!("accept should have succeeded on the wildcard address"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_listen_transparent_takes_foreign_connections() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = SharedEngine::new(
+        test_helpers::BOB_GATEWAY_CONFIG_PATH,
+        test_helpers::SharedTestPhysicalLayer::new_test(now),
+        now,
+    )?;
+    let mut carrie: SharedEngine = SharedEngine::new(
+        test_helpers::CARRIE_TRANSPARENT_CONFIG_PATH,
+        test_helpers::SharedTestPhysicalLayer::new_test(now),
+        now,
+    )?;
+
+    // Carrie listens transparently on the wildcard address.
+    let port: u16 = 80;
+    let listen_fd: QDesc = carrie.tcp_socket()?;
+    carrie.set_socket_option(listen_fd, SocketOption::Transparent(true))?;
+    carrie.tcp_bind(listen_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port))?;
+    carrie.tcp_listen(listen_fd, 8)?;
+    let accept_qt: QToken = carrie.tcp_accept(listen_fd)?;
+
+    // Bob connects to a foreign address that Carrie intercepts, and the connection keeps that address on both ends.
+    let foreign_addr: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 1, 2, 3), port);
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    connect_to(&mut bob, &mut carrie, bob_fd, foreign_addr)?;
+    crate::ensure_eq!(bob.get_addresses(bob_fd)?.1, Some(SocketAddr::V4(foreign_addr)));
+    match carrie.wait(accept_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Accept((carrie_fd, remote))) => {
+            crate::ensure_eq!(*remote.ip(), test_helpers::BOB_IPV4);
+            crate::ensure_eq!(carrie.get_addresses(carrie_fd)?.0, Some(SocketAddr::V4(foreign_addr)));
+        },
+        _ => anyhow::bail!("accept should have succeeded on the transparent socket"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_listen_foreign_connections_need_transparent_socket() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = SharedEngine::new(
+        test_helpers::BOB_GATEWAY_CONFIG_PATH,
+        test_helpers::SharedTestPhysicalLayer::new_test(now),
+        now,
+    )?;
+    let mut carrie: SharedEngine = SharedEngine::new(
+        test_helpers::CARRIE_TRANSPARENT_CONFIG_PATH,
+        test_helpers::SharedTestPhysicalLayer::new_test(now),
+        now,
+    )?;
+
+    // Carrie listens on the wildcard address, but not transparently.
+    let port: u16 = 80;
+    let listen_fd: QDesc = carrie.tcp_socket()?;
+    carrie.tcp_bind(listen_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port))?;
+    carrie.tcp_listen(listen_fd, 8)?;
+
+    // Carrie ignores the SYN to the foreign address, rather than resetting a connection that is not hers.
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    bob.tcp_connect(bob_fd, SocketAddrV4::new(Ipv4Addr::new(10, 1, 2, 3), port))?;
+    bob.poll();
+    let frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+    crate::ensure_eq!(frames.len(), 1);
+    for frame in frames {
+        carrie.push_frame(frame);
+    }
+    carrie.poll();
+    crate::ensure_eq!(carrie.pop_all_frames().len(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_listen_port_range() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+
+    // Carrie listens on a range of ports with one socket, and on a port within the range with another.
+    let ports: PortRange = PortRange {
+        first: 5060,
+        last: 5070,
+    };
+    let range_fd: QDesc = carrie.tcp_socket()?;
+    carrie.set_socket_option(range_fd, SocketOption::ListenPorts(Some(ports)))?;
+    carrie.tcp_bind(range_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, ports.first))?;
+    carrie.tcp_listen(range_fd, 8)?;
+    let single_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 5065);
+    let single_fd: QDesc = carrie.tcp_socket()?;
+    carrie.tcp_bind(single_fd, single_addr)?;
+    carrie.tcp_listen(single_fd, 8)?;
+
+    // The range covers any port that nobody listens on by itself, and the connection keeps the port it was made to.
+    let range_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 5062);
+    let range_qt: QToken = carrie.tcp_accept(range_fd)?;
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    connect_to(&mut bob, &mut carrie, bob_fd, range_addr)?;
+    match carrie.wait(range_qt, TIMEOUT_SECONDS)? {
+        (qd, OperationResult::Accept((carrie_fd, _))) if qd == range_fd => {
+            crate::ensure_eq!(carrie.get_addresses(carrie_fd)?.0, Some(SocketAddr::V4(range_addr)))
+        },
+        _ => anyhow::bail!("accept should have succeeded on the port range"),
+    }
+    let single_qt: QToken = carrie.tcp_accept(single_fd)?;
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    connect_to(&mut bob, &mut carrie, bob_fd, single_addr)?;
+    match carrie.wait(single_qt, TIMEOUT_SECONDS)? {
+        (qd, OperationResult::Accept(_)) if qd == single_fd => (),
+        _ => anyhow::bail!("accept should have succeeded on the single port"),
+    }
+
+    // Ranges must contain the bound port and may not overlap.
+    let outside_fd: QDesc = carrie.tcp_socket()?;
+    carrie.set_socket_option(
+        outside_fd,
+        SocketOption::ListenPorts(Some(PortRange {
+            first: 6000,
+            last: 6010,
+        })),
+    )?;
+    carrie.tcp_bind(outside_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 5999))?;
+    match carrie.tcp_listen(outside_fd, 8) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+        Ok(()) => anyhow::bail!("a range without the bound port should be rejected"),
+    }
+    let overlap_fd: QDesc = carrie.tcp_socket()?;
+    carrie.set_socket_option(
+        overlap_fd,
+        SocketOption::ListenPorts(Some(PortRange {
+            first: 5070,
+            last: 5080,
+        })),
+    )?;
+    carrie.tcp_bind(overlap_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 5075))?;
+    match carrie.tcp_listen(overlap_fd, 8) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EADDRINUSE),
+        Ok(()) => anyhow::bail!("overlapping ranges should be rejected"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_listen_learns_remote_link_addr() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = SharedEngine::new(
+        test_helpers::CARRIE_LEARN_LINK_ADDR_CONFIG_PATH,
+        test_helpers::SharedTestPhysicalLayer::new_test(now),
+        now,
+    )?;
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let listen_fd: QDesc = listen(&mut carrie, remote)?;
+    let accept_qt: QToken = carrie.tcp_accept(listen_fd)?;
+
+    // Bob's SYN comes through another hop on the link, and Carrie answers through that hop instead of resolving Bob.
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    let (connect_qt, mut syn_ack): (QToken, DemiBuffer) =
+        connect_from_link_addr(&mut bob, &mut carrie, bob_fd, remote, test_helpers::ALICE_MAC)?;
+    crate::ensure_eq!(get_dst_link_addr(&syn_ack), test_helpers::ALICE_MAC);
+
+    // The hop hands the SYN-ACK over to Bob, who completes the handshake.
+    syn_ack[0..6].copy_from_slice(test_helpers::BOB_MAC.as_bytes());
+    bob.push_frame(syn_ack);
+    exchange_frames(&mut bob, &mut carrie);
+    match bob.wait(connect_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Connect) => (),
+        (_, result) => anyhow::bail!("connect should have succeeded (result={:?})", result),
+    }
+    let carrie_fd: QDesc = match carrie.wait(accept_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Accept((carrie_fd, _))) => carrie_fd,
+        (_, result) => anyhow::bail!("accept should have succeeded (result={:?})", result),
+    };
+
+    // The data of the connection goes through the same hop.
+    let _: QToken = carrie.tcp_push(
+        carrie_fd,
+        DemiBuffer::from_slice_with_headroom(&[1; 8], MAX_HEADER_SIZE)?,
+    )?;
+    let mut frames: VecDeque<DemiBuffer> = VecDeque::new();
+    for _ in 0..MAX_ROUNDS {
+        carrie.poll();
+        frames.extend(carrie.pop_all_frames());
+        if !frames.is_empty() {
+            break;
+        }
+    }
+    crate::ensure_eq!(frames.len(), 1);
+    crate::ensure_eq!(get_dst_link_addr(&frames[0]), test_helpers::ALICE_MAC);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_listen_resolves_remote_link_addr_by_default() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let _: QDesc = listen(&mut carrie, remote)?;
+
+    // Carrie ignores where Bob's SYN came from and answers him at the link address that ARP resolves.
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    let (_, syn_ack): (QToken, DemiBuffer) =
+        connect_from_link_addr(&mut bob, &mut carrie, bob_fd, remote, test_helpers::ALICE_MAC)?;
+    crate::ensure_eq!(get_dst_link_addr(&syn_ack), test_helpers::BOB_MAC);
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Connection churn through the test engine. Each iteration sets up a connection, accepts it and tears it down again,
+//! so the time per iteration is the cost of a connection on both ends. Churning through 100k connections per second
+//! leaves 10 µs per iteration.
+
+use crate::{
+    expect_ok,
+    inetstack::{
+        protocols::layer4::tcp::tests::{connect_and_accept, exchange_frames, listen},
+        test_helpers::{
+            self,
+            engine::{SharedEngine, TIMEOUT_SECONDS},
+        },
+    },
+    runtime::queue::{OperationResult, QDesc, QToken},
+    SocketOption,
+};
+use ::std::{
+    net::SocketAddrV4,
+    time::{Duration, Instant},
+};
+use ::test::{black_box, Bencher};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Number of connections that stay open while others churn, to show that setting up a connection does not get more
+/// expensive with the number of connections.
+const NUM_IDLE_CONNECTIONS: usize = 4096;
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Connects a new socket of Bob to `remote` and accepts it on `listen_fd`. Returns the sockets of Bob and Carrie.
+fn open_connection(
+    bob: &mut SharedEngine,
+    carrie: &mut SharedEngine,
+    listen_fd: QDesc,
+    remote: SocketAddrV4,
+) -> (QDesc, QDesc) {
+    let bob_fd: QDesc = expect_ok!(bob.tcp_socket(), "socket() should succeed");
+    // Bob closes first, so the connection would otherwise stay in TIME_WAIT on that end for twice the MSL.
+    expect_ok!(
+        bob.set_socket_option(bob_fd, SocketOption::Linger(Some(Duration::ZERO))),
+        "setsockopt() should succeed"
+    );
+    let carrie_fd: QDesc = expect_ok!(
+        connect_and_accept(bob, carrie, bob_fd, listen_fd, remote),
+        "connect() should succeed"
+    );
+    (bob_fd, carrie_fd)
+}
+
+/// Closes the connection between `bob_fd` and `carrie_fd`, with Bob closing first.
+fn close(bob: &mut SharedEngine, carrie: &mut SharedEngine, bob_fd: QDesc, carrie_fd: QDesc) {
+    let bob_close_qt: QToken = expect_ok!(bob.tcp_async_close(bob_fd), "close() should succeed");
+    let carrie_close_qt: QToken = expect_ok!(carrie.tcp_async_close(carrie_fd), "close() should succeed");
+    exchange_frames(bob, carrie);
+    for (engine, qt) in [(&*bob, bob_close_qt), (&*carrie, carrie_close_qt)] {
+        match expect_ok!(engine.wait(qt, TIMEOUT_SECONDS), "close should complete") {
+            (_, OperationResult::Close) => (),
+            (_, result) => panic!("close should have succeeded (result={:?})", result),
+        }
+    }
+}
+
+/// Sets up and tears down one connection per iteration, while `num_idle` other connections stay open.
+fn churn(b: &mut Bencher, num_idle: usize) {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let listen_fd: QDesc = expect_ok!(listen(&mut carrie, remote), "listen() should succeed");
+
+    let idle: Vec<(QDesc, QDesc)> = (0..num_idle)
+        .map(|_| open_connection(&mut bob, &mut carrie, listen_fd, remote))
+        .collect();
+
+    b.iter(|| {
+        let (bob_fd, carrie_fd): (QDesc, QDesc) = open_connection(&mut bob, &mut carrie, listen_fd, remote);
+        close(&mut bob, &mut carrie, black_box(bob_fd), black_box(carrie_fd));
+    });
+
+    black_box(idle);
+}
+
+//======================================================================================================================
+// Benchmarks
+//======================================================================================================================
+
+#[bench]
+fn bench_connect_close(b: &mut Bencher) {
+    churn(b, 0);
+}
+
+#[bench]
+fn bench_connect_close_with_idle_connections(b: &mut Bencher) {
+    churn(b, NUM_IDLE_CONNECTIONS);
+}
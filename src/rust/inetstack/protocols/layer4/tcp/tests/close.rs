@@ -0,0 +1,156 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::tests::{connect, exchange_frames},
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{
+            self,
+            engine::{SharedEngine, TIMEOUT_SECONDS},
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::consts::TCP_FIN_TIMEOUT,
+        queue::{OperationResult, QDesc, QToken},
+    },
+    SocketOption,
+};
+use ::anyhow::Result;
+use ::std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Hands [frames] over to Carrie and checks that they reset her connection on [carrie_fd]. A FIN that comes before the
+/// RST does not matter, as reads fail once the connection is reset.
+fn expect_reset(carrie: &mut SharedEngine, carrie_fd: QDesc, frames: VecDeque<DemiBuffer>) -> Result<()> {
+    for frame in frames {
+        carrie.push_frame(frame);
+    }
+    let pop_qt: QToken = carrie.tcp_pop(carrie_fd)?;
+    match carrie.wait(pop_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Failed(e)) => crate::ensure_eq!(e.errno, libc::ECONNRESET),
+        (_, result) => anyhow::bail!("pop should have failed (result={:?})", result),
+    }
+    Ok(())
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_fin_wait_2_timeout() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Bob closes the connection and Carrie acknowledges the FIN, but never closes the other end.
+    let close_qt: QToken = bob.tcp_async_close(bob_fd)?;
+    exchange_frames(&mut bob, &mut carrie);
+    crate::ensure_eq!(bob.wait(close_qt, Duration::ZERO).is_err(), true);
+
+    // Bob gives up on the connection once the FIN_WAIT_2 timeout expires and resets it.
+    bob.advance_clock(Instant::now() + TCP_FIN_TIMEOUT);
+    match bob.wait(close_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Close) => (),
+        (_, result) => anyhow::bail!("close should have succeeded (result={:?})", result),
+    }
+    let frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+    expect_reset(&mut carrie, carrie_fd, frames)
+}
+
+#[test]
+fn tcp_close_twice() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // A socket that is closing cannot be closed again.
+    let bob_close_qt: QToken = bob.tcp_async_close(bob_fd)?;
+    match bob.tcp_async_close(bob_fd) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EALREADY),
+        Ok(_) => anyhow::bail!("closing a socket twice should fail"),
+    }
+
+    // The close completes only once both ends shut down the connection.
+    exchange_frames(&mut bob, &mut carrie);
+    crate::ensure_eq!(bob.wait(bob_close_qt, Duration::ZERO).is_err(), true);
+    let carrie_close_qt: QToken = carrie.tcp_async_close(carrie_fd)?;
+    exchange_frames(&mut bob, &mut carrie);
+    for (engine, qt) in [(&bob, bob_close_qt), (&carrie, carrie_close_qt)] {
+        match engine.wait(qt, TIMEOUT_SECONDS)? {
+            (_, OperationResult::Close) => (),
+            (_, result) => anyhow::bail!("close should have succeeded (result={:?})", result),
+        }
+    }
+
+    // The socket is gone once it is closed.
+    match bob.tcp_async_close(bob_fd) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EBADF),
+        Ok(_) => anyhow::bail!("closing a closed socket should fail"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_close_zero_linger() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+    bob.set_socket_option(bob_fd, SocketOption::Linger(Some(Duration::ZERO)))?;
+
+    // Bob closes the connection while Carrie has not received his data yet.
+    let _: QToken = bob.tcp_push(bob_fd, DemiBuffer::from_slice_with_headroom(&[1; 8], MAX_HEADER_SIZE)?)?;
+    bob.poll();
+    crate::ensure_eq!(bob.pop_all_frames().len(), 1);
+    let close_qt: QToken = bob.tcp_async_close(bob_fd)?;
+    match bob.wait(close_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Close) => (),
+        (_, result) => anyhow::bail!("close should have succeeded (result={:?})", result),
+    }
+
+    // Instead of a FIN, Bob sends a RST right away and nothing else.
+    let frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+    crate::ensure_eq!(frames.len(), 1);
+    expect_reset(&mut carrie, carrie_fd, frames)
+}
+
+#[test]
+fn tcp_close_linger_timeout() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+    let linger: Duration = Duration::from_secs(5);
+    bob.set_socket_option(bob_fd, SocketOption::Linger(Some(linger)))?;
+
+    // Bob closes the connection, but his FIN never makes it to Carrie.
+    let close_qt: QToken = bob.tcp_async_close(bob_fd)?;
+    // The close queues the FIN and wakes up the background sender, which only sends it in the next poll.
+    bob.poll();
+    bob.poll();
+    crate::ensure_eq!(bob.pop_all_frames().len(), 1);
+    crate::ensure_eq!(bob.wait(close_qt, Duration::ZERO).is_err(), true);
+
+    // Bob resets the connection once the linger timeout runs out.
+    bob.advance_clock(Instant::now() + linger);
+    match bob.wait(close_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Close) => (),
+        (_, result) => anyhow::bail!("close should have succeeded (result={:?})", result),
+    }
+    let frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+    expect_reset(&mut carrie, carrie_fd, frames)
+}
@@ -0,0 +1,70 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::tests::{connect_and_accept, dump_socket_state, exchange_frames, listen},
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{self, engine::SharedEngine},
+    },
+    runtime::{
+        memory::DemiBuffer,
+        queue::{QDesc, QToken},
+    },
+};
+use ::anyhow::Result;
+use ::serde_json::{json, Value};
+use ::std::{net::SocketAddrV4, time::Instant};
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_dump_state() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let listen_fd: QDesc = listen(&mut carrie, remote)?;
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    let carrie_fd: QDesc = connect_and_accept(&mut bob, &mut carrie, bob_fd, listen_fd, remote)?;
+
+    // Bob sends data that Carrie does not read yet.
+    let _: QToken = bob.tcp_push(
+        bob_fd,
+        DemiBuffer::from_slice_with_headroom(&[0xab; 4], MAX_HEADER_SIZE)?,
+    )?;
+    exchange_frames(&mut bob, &mut carrie);
+
+    // The dump has every socket, with the state of connections and the data that is queued on them.
+    let listener: Value = dump_socket_state(&mut carrie, listen_fd, false)?;
+    crate::ensure_eq!(listener["state"], "listening");
+    crate::ensure_eq!(listener["local"], remote.to_string());
+    let socket: Value = dump_socket_state(&mut carrie, carrie_fd, false)?;
+    crate::ensure_eq!(socket["state"], "established");
+    crate::ensure_eq!(socket["local"], remote.to_string());
+    crate::ensure_eq!(
+        socket["connection"]["receiver"]["pop_queue"],
+        json!([{ "len": 4, "data": "abababab" }])
+    );
+    // Carrie delays her ACK, so the data is still unacknowledged on Bob's end.
+    let socket: Value = dump_socket_state(&mut bob, bob_fd, false)?;
+    crate::ensure_eq!(
+        socket["connection"]["sender"]["unacked_queue"],
+        json!([{ "payload": { "len": 4, "data": "abababab" }, "sacked": false }])
+    );
+
+    // Scrubbing leaves the length of queued data, but not the data itself.
+    let socket: Value = dump_socket_state(&mut carrie, carrie_fd, true)?;
+    crate::ensure_eq!(socket["connection"]["receiver"]["pop_queue"], json!([{ "len": 4 }]));
+    let socket: Value = dump_socket_state(&mut bob, bob_fd, true)?;
+    crate::ensure_eq!(
+        socket["connection"]["sender"]["unacked_queue"],
+        json!([{ "payload": { "len": 4 }, "sacked": false }])
+    );
+
+    Ok(())
+}
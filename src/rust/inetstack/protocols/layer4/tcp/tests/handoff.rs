@@ -0,0 +1,201 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::{
+                handoff::{self, ConnectionHandoff, HandoffReceiver, HandoffSender},
+                tests::MAX_ROUNDS,
+            },
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{
+            self,
+            engine::{SharedEngine, TIMEOUT_SECONDS},
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        queue::{OperationResult, QDesc, QToken},
+    },
+};
+use ::anyhow::Result;
+use ::serde_json::Value;
+use ::std::{collections::VecDeque, net::SocketAddrV4, time::Instant};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Bob connects to a listening socket on one core of Carrie, which hands the connection off to her worker core. Bob
+/// only sends to the listening core, which forwards whatever belongs to the worker, but hears back from both.
+struct Cores {
+    bob: SharedEngine,
+    listener: SharedEngine,
+    worker: SharedEngine,
+    listen_fd: QDesc,
+    handoff: HandoffReceiver,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl Cores {
+    fn new() -> Result<Self> {
+        let now: Instant = Instant::now();
+        let mut listener: SharedEngine = test_helpers::new_carrie(now);
+        let listen_fd: QDesc = listener.tcp_socket()?;
+        listener.tcp_bind(listen_fd, Self::remote())?;
+        listener.tcp_listen(listen_fd, 8)?;
+        let (handoff_tx, handoff_rx): (HandoffSender, HandoffReceiver) = handoff::channel(8);
+        listener.tcp_set_handoff(listen_fd, handoff_tx)?;
+
+        Ok(Self {
+            bob: test_helpers::new_bob(now),
+            listener,
+            worker: test_helpers::new_carrie(now),
+            listen_fd,
+            handoff: handoff_rx,
+        })
+    }
+
+    fn remote() -> SocketAddrV4 {
+        SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80)
+    }
+
+    /// Moves frames between Bob and both cores of Carrie until none of them has anything left to send. Like
+    /// [super::exchange_frames], this stops after two quiet rounds.
+    fn exchange_frames(&mut self) {
+        let mut quiet_rounds: usize = 0;
+        for _ in 0..MAX_ROUNDS {
+            self.bob.poll();
+            self.listener.poll();
+            self.worker.poll();
+            let bob_frames: VecDeque<DemiBuffer> = self.bob.pop_all_frames();
+            let mut carrie_frames: VecDeque<DemiBuffer> = self.listener.pop_all_frames();
+            carrie_frames.append(&mut self.worker.pop_all_frames());
+            if bob_frames.is_empty() && carrie_frames.is_empty() {
+                quiet_rounds += 1;
+                if quiet_rounds == 2 {
+                    return;
+                }
+                continue;
+            }
+            quiet_rounds = 0;
+            for frame in bob_frames {
+                self.listener.push_frame(frame);
+            }
+            for frame in carrie_frames {
+                self.bob.push_frame(frame);
+            }
+        }
+    }
+
+    /// Connects Bob to the listening core and has the worker core take the connection over. Returns the sockets of
+    /// Bob and of the worker.
+    fn connect(&mut self) -> Result<(QDesc, QDesc)> {
+        let bob_fd: QDesc = self.bob.tcp_socket()?;
+        let connect_qt: QToken = self.bob.tcp_connect(bob_fd, Self::remote())?;
+        self.exchange_frames();
+        match self.bob.wait(connect_qt, TIMEOUT_SECONDS)? {
+            (_, OperationResult::Connect) => (),
+            (_, result) => anyhow::bail!("connect should have succeeded (result={:?})", result),
+        }
+        let connection: ConnectionHandoff = match self.handoff.try_recv() {
+            Ok(connection) => connection,
+            Err(e) => anyhow::bail!("connection should have been handed off: {:?}", e),
+        };
+        let worker_fd: QDesc = self.worker.tcp_adopt(connection)?;
+        Ok((bob_fd, worker_fd))
+    }
+
+    /// Returns the number of handed-off connections that the listening core forwards segments for.
+    fn get_num_forwarded(&mut self) -> Result<u64> {
+        let state: Value = serde_json::from_str(&self.listener.dump_state(false)?)?;
+        let listen_qd: u32 = self.listen_fd.into();
+        let socket: &Value = match state["sockets"]
+            .as_array()
+            .and_then(|sockets| sockets.iter().find(|socket| socket["qd"] == listen_qd))
+        {
+            Some(socket) => socket,
+            None => anyhow::bail!("listening socket should be in the dump (state={})", state),
+        };
+        match socket["listener"]["handed_off"].as_u64() {
+            Some(num_forwarded) => Ok(num_forwarded),
+            None => anyhow::bail!("dump should count handed-off connections (socket={})", socket),
+        }
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Waits for [qt] to complete on [engine] and returns the data that it popped.
+fn expect_data(engine: &SharedEngine, qt: QToken) -> Result<DemiBuffer> {
+    match engine.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Pop(_, buf, _)) => Ok(buf),
+        (_, result) => anyhow::bail!("pop should have succeeded (result={:?})", result),
+    }
+}
+
+/// Waits for the close [qt] to complete on [engine].
+fn expect_close(engine: &SharedEngine, qt: QToken) -> Result<()> {
+    match engine.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Close) => Ok(()),
+        (_, result) => anyhow::bail!("close should have succeeded (result={:?})", result),
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_handoff_forwards_segments() -> Result<()> {
+    let mut cores: Cores = Cores::new()?;
+    let (bob_fd, worker_fd): (QDesc, QDesc) = cores.connect()?;
+    crate::ensure_eq!(cores.get_num_forwarded()?, 1);
+
+    // Data from Bob reaches the worker through the listening core.
+    let data: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+    cores
+        .bob
+        .tcp_push(bob_fd, DemiBuffer::from_slice_with_headroom(&data, MAX_HEADER_SIZE)?)?;
+    cores.exchange_frames();
+    let pop_qt: QToken = cores.worker.tcp_pop(worker_fd)?;
+    crate::ensure_eq!(&expect_data(&cores.worker, pop_qt)?[..], &data[..]);
+
+    // The worker answers Bob on its own.
+    cores
+        .worker
+        .tcp_push(worker_fd, DemiBuffer::from_slice_with_headroom(&data, MAX_HEADER_SIZE)?)?;
+    cores.exchange_frames();
+    let pop_qt: QToken = cores.bob.tcp_pop(bob_fd)?;
+    crate::ensure_eq!(&expect_data(&cores.bob, pop_qt)?[..], &data[..]);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_handoff_stops_forwarding_closed_connections() -> Result<()> {
+    let mut cores: Cores = Cores::new()?;
+    let (bob_fd, worker_fd): (QDesc, QDesc) = cores.connect()?;
+
+    // Bob and the worker shut the connection down, with the ACKs of Bob going through the listening core.
+    let bob_close_qt: QToken = cores.bob.tcp_async_close(bob_fd)?;
+    cores.exchange_frames();
+    let worker_close_qt: QToken = cores.worker.tcp_async_close(worker_fd)?;
+    cores.exchange_frames();
+    expect_close(&cores.bob, bob_close_qt)?;
+    expect_close(&cores.worker, worker_close_qt)?;
+    cores.worker.poll();
+
+    // The listening core forgets the closed connection and only forwards segments for the next one.
+    cores.connect()?;
+    crate::ensure_eq!(cores.get_num_forwarded()?, 1);
+
+    Ok(())
+}
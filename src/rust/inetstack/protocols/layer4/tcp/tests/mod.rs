@@ -5,5 +5,135 @@
 // Exports
 //======================================================================================================================
 
+mod ack;
+mod bind;
+mod churn;
+mod close;
+mod dump;
+mod handoff;
+mod option;
+mod pop;
+mod push;
 #[cfg(debug_assertions)]
 mod simulator;
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    inetstack::test_helpers::{
+        self,
+        engine::{SharedEngine, TIMEOUT_SECONDS},
+    },
+    runtime::{
+        memory::DemiBuffer,
+        queue::{OperationResult, QDesc, QToken},
+    },
+};
+use ::anyhow::Result;
+use ::serde_json::Value;
+use ::std::{collections::VecDeque, net::SocketAddrV4};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Maximum number of rounds of frames that Bob and Carrie exchange to set up or tear down a connection.
+const MAX_ROUNDS: usize = 16;
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Moves frames between Bob and Carrie until neither of them has anything left to send. A coroutine that a poll wakes
+/// up (e.g. the background sender that sends a FIN) only runs in the next one, so we stop after two quiet rounds.
+fn exchange_frames(bob: &mut SharedEngine, carrie: &mut SharedEngine) {
+    let mut quiet_rounds: usize = 0;
+    for _ in 0..MAX_ROUNDS {
+        bob.poll();
+        carrie.poll();
+        let bob_frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+        let carrie_frames: VecDeque<DemiBuffer> = carrie.pop_all_frames();
+        if bob_frames.is_empty() && carrie_frames.is_empty() {
+            quiet_rounds += 1;
+            if quiet_rounds == 2 {
+                return;
+            }
+            continue;
+        }
+        quiet_rounds = 0;
+        for frame in bob_frames {
+            carrie.push_frame(frame);
+        }
+        for frame in carrie_frames {
+            bob.push_frame(frame);
+        }
+    }
+}
+
+/// Makes Carrie listen for connections on `local` and returns the listening socket.
+fn listen(carrie: &mut SharedEngine, local: SocketAddrV4) -> Result<QDesc> {
+    let listen_fd: QDesc = carrie.tcp_socket()?;
+    carrie.tcp_bind(listen_fd, local)?;
+    carrie.tcp_listen(listen_fd, 64)?;
+    Ok(listen_fd)
+}
+
+/// Connects `bob_fd` to `remote` and waits for the connection to be established.
+fn connect_to(bob: &mut SharedEngine, carrie: &mut SharedEngine, bob_fd: QDesc, remote: SocketAddrV4) -> Result<()> {
+    let connect_qt: QToken = bob.tcp_connect(bob_fd, remote)?;
+    exchange_frames(bob, carrie);
+    match bob.wait(connect_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Connect) => Ok(()),
+        (_, result) => anyhow::bail!("connect should have succeeded (result={:?})", result),
+    }
+}
+
+/// Connects `bob_fd` to `remote` and accepts the connection on `listen_fd`. Returns the socket of Carrie.
+fn connect_and_accept(
+    bob: &mut SharedEngine,
+    carrie: &mut SharedEngine,
+    bob_fd: QDesc,
+    listen_fd: QDesc,
+    remote: SocketAddrV4,
+) -> Result<QDesc> {
+    let accept_qt: QToken = carrie.tcp_accept(listen_fd)?;
+    connect_to(bob, carrie, bob_fd, remote)?;
+    match carrie.wait(accept_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Accept((carrie_fd, _))) => Ok(carrie_fd),
+        (_, result) => anyhow::bail!("accept should have succeeded (result={:?})", result),
+    }
+}
+
+/// Connects Bob to Carrie and returns the sockets of both ends.
+fn connect(bob: &mut SharedEngine, carrie: &mut SharedEngine) -> Result<(QDesc, QDesc)> {
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let listen_fd: QDesc = listen(carrie, remote)?;
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    let carrie_fd: QDesc = connect_and_accept(bob, carrie, bob_fd, listen_fd, remote)?;
+    Ok((bob_fd, carrie_fd))
+}
+
+/// Returns the description of [socket_fd] in the state dump of [engine], which leaves out queued data if
+/// [scrub_payload] is set.
+fn dump_socket_state(engine: &mut SharedEngine, socket_fd: QDesc, scrub_payload: bool) -> Result<Value> {
+    let state: Value = serde_json::from_str(&engine.dump_state(scrub_payload)?)?;
+    let qd: u32 = socket_fd.into();
+    match state["sockets"]
+        .as_array()
+        .and_then(|sockets| sockets.iter().find(|socket| socket["qd"] == qd))
+    {
+        Some(socket) => Ok(socket.clone()),
+        None => anyhow::bail!("socket should be in the dump (state={})", state),
+    }
+}
+
+/// Reads a counter from the connection statistics of [socket_fd] in the state dump of [engine].
+fn get_stat(engine: &mut SharedEngine, socket_fd: QDesc, name: &str) -> Result<u64> {
+    let socket: Value = dump_socket_state(engine, socket_fd, false)?;
+    match socket["connection"]["stats"][name].as_u64() {
+        Some(value) => Ok(value),
+        None => anyhow::bail!("dump should have the statistic (name={:?}, socket={})", name, socket),
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::tests::{connect, exchange_frames},
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{
+            self,
+            engine::{SharedEngine, TIMEOUT_SECONDS},
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::consts::TCP_ACK_DELAY_TIMEOUT,
+        queue::{OperationResult, QDesc, QToken},
+    },
+    SocketOption, SynBackoff, TcpInfo,
+};
+use ::anyhow::Result;
+use ::std::{
+    collections::VecDeque,
+    net::SocketAddrV4,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Moves the clock of [engine] forward by [delta] and runs its scheduler once. Returns the number of frames sent.
+fn advance(engine: &mut SharedEngine, delta: Duration) -> usize {
+    let now: Instant = engine.get_runtime().get_now();
+    engine.advance_clock(now + delta);
+    engine.poll();
+    engine.pop_all_frames().len()
+}
+
+/// Checks that [qt] failed because the connection timed out.
+fn expect_connect_timeout(engine: &SharedEngine, qt: QToken) -> Result<()> {
+    match engine.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Failed(e)) => crate::ensure_eq!(e.errno, libc::ETIMEDOUT),
+        (_, result) => anyhow::bail!("connect should have timed out (result={:?})", result),
+    }
+    Ok(())
+}
+
+/// Reads the connection statistics of [socket_fd].
+fn get_tcp_info(engine: &mut SharedEngine, socket_fd: QDesc) -> Result<TcpInfo> {
+    match engine.get_socket_option(socket_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => Ok(info),
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    }
+}
+
+/// Grows the congestion window of a paced connection from Bob to Carrie, lets the connection sit idle for [idle_rtos]
+/// RTOs and then sends again. Returns the congestion window before and after the idle period.
+fn get_cwnd_around_idle(keep_cwnd: bool, idle_rtos: f32) -> Result<(u32, u32)> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = SharedEngine::new(
+        test_helpers::BOB_PACED_CONFIG_PATH,
+        test_helpers::SharedTestPhysicalLayer::new_test(now),
+        now,
+    )?;
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, _): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+    bob.set_socket_option(bob_fd, SocketOption::KeepCwndAfterIdleWhenPaced(keep_cwnd))?;
+
+    // Slow start grows the window beyond the initial one while Carrie acknowledges our data.
+    let mss: usize = get_tcp_info(&mut bob, bob_fd)?.mss;
+    for _ in 0..4 {
+        bob.tcp_push(
+            bob_fd,
+            DemiBuffer::from_slice_with_headroom(&vec![1; 4 * mss], MAX_HEADER_SIZE)?,
+        )?;
+        exchange_frames(&mut bob, &mut carrie);
+    }
+    // Carrie acknowledges whatever her delayed ACK still holds back.
+    let carrie_now: Instant = carrie.get_runtime().get_now();
+    carrie.advance_clock(carrie_now + TCP_ACK_DELAY_TIMEOUT);
+    exchange_frames(&mut bob, &mut carrie);
+    let info: TcpInfo = get_tcp_info(&mut bob, bob_fd)?;
+    crate::ensure_eq!(info.bytes_in_flight, 0);
+    crate::ensure_eq!(info.congestion_window > 2 * mss as u32, true);
+
+    // Bob sends again after being idle for a while.
+    let bob_now: Instant = bob.get_runtime().get_now();
+    bob.advance_clock(bob_now + info.rto.mul_f32(idle_rtos));
+    bob.tcp_push(bob_fd, DemiBuffer::from_slice_with_headroom(&[1; 8], MAX_HEADER_SIZE)?)?;
+    bob.poll();
+
+    let cwnd_after_idle: u32 = get_tcp_info(&mut bob, bob_fd)?.congestion_window;
+
+    Ok((info.congestion_window, cwnd_after_idle))
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_choose_congestion_control() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let carrie_fd: QDesc = carrie.tcp_socket()?;
+
+    // New sockets use the algorithm from the configuration until they pick another one.
+    match carrie.get_socket_option(carrie_fd, SocketOption::CongestionControl(""))? {
+        SocketOption::CongestionControl(name) => crate::ensure_eq!(name, "none"),
+        option => anyhow::bail!("unexpected option {:?}", option),
+    }
+    match carrie.set_socket_option(carrie_fd, SocketOption::CongestionControl("reno")) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::ENOENT),
+        Ok(()) => anyhow::bail!("unknown algorithms should be rejected"),
+    }
+    carrie.set_socket_option(carrie_fd, SocketOption::CongestionControl("cubic"))?;
+    match carrie.get_socket_option(carrie_fd, SocketOption::CongestionControl(""))? {
+        SocketOption::CongestionControl(name) => crate::ensure_eq!(name, "cubic"),
+        option => anyhow::bail!("unexpected option {:?}", option),
+    }
+
+    // Once the socket listens, the algorithm is fixed.
+    carrie.tcp_bind(carrie_fd, SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80))?;
+    carrie.tcp_listen(carrie_fd, 8)?;
+    match carrie.set_socket_option(carrie_fd, SocketOption::CongestionControl("bbr")) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+        Ok(()) => anyhow::bail!("the algorithm should not change after listen()"),
+    }
+    match carrie.get_socket_option(carrie_fd, SocketOption::CongestionControl(""))? {
+        SocketOption::CongestionControl(name) => crate::ensure_eq!(name, "cubic"),
+        option => anyhow::bail!("unexpected option {:?}", option),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_syn_retries_with_backoff() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    match bob.set_socket_option(bob_fd, SocketOption::SynRetries(0)) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+        Ok(()) => anyhow::bail!("zero SYN retries should be rejected"),
+    }
+    bob.set_socket_option(bob_fd, SocketOption::SynRetries(2))?;
+    bob.set_socket_option(
+        bob_fd,
+        SocketOption::SynBackoff(SynBackoff {
+            initial_timeout: Duration::from_secs(1),
+            multiplier: 2,
+            max_timeout: Duration::from_secs(3),
+        }),
+    )?;
+
+    // Carrie never answers, so Bob retransmits the SYN after 1 and then 2 seconds, and gives up after 3 more seconds,
+    // as the backoff is capped there.
+    let connect_qt: QToken = bob.tcp_connect(bob_fd, remote)?;
+    crate::ensure_eq!(advance(&mut bob, Duration::ZERO), 1);
+    crate::ensure_eq!(advance(&mut bob, Duration::from_secs(1)), 1);
+    crate::ensure_eq!(advance(&mut bob, Duration::from_millis(1500)), 0);
+    crate::ensure_eq!(advance(&mut bob, Duration::from_millis(500)), 1);
+    crate::ensure_eq!(advance(&mut bob, Duration::from_secs(3)), 0);
+    expect_connect_timeout(&bob, connect_qt)
+}
+
+#[test]
+fn tcp_connect_timeout() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    match bob.set_socket_option(bob_fd, SocketOption::ConnectTimeout(Some(Duration::ZERO))) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+        Ok(()) => anyhow::bail!("a zero connect timeout should be rejected"),
+    }
+    bob.set_socket_option(bob_fd, SocketOption::ConnectTimeout(Some(Duration::from_secs(2))))?;
+
+    // The connect timeout cuts the handshake short, even though Bob would retransmit the SYN a few more times.
+    let connect_qt: QToken = bob.tcp_connect(bob_fd, remote)?;
+    crate::ensure_eq!(advance(&mut bob, Duration::ZERO), 1);
+    crate::ensure_eq!(advance(&mut bob, Duration::from_secs(2)), 0);
+    expect_connect_timeout(&bob, connect_qt)
+}
+
+#[test]
+fn tcp_last_error() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+    crate::ensure_eq!(bob.get_last_error(bob_fd)?.is_none(), true);
+
+    // Bob drops the segment of Carrie when it shows up for the second time, and keeps the reason around.
+    carrie.tcp_push(
+        carrie_fd,
+        DemiBuffer::from_slice_with_headroom(&[1; 8], MAX_HEADER_SIZE)?,
+    )?;
+    carrie.poll();
+    let mut frames: VecDeque<DemiBuffer> = carrie.pop_all_frames();
+    crate::ensure_eq!(frames.len(), 1);
+    let frame: DemiBuffer = frames.pop_front().unwrap();
+    bob.push_frame(frame.clone());
+    bob.push_frame(frame);
+    match bob.get_last_error(bob_fd)? {
+        Some(e) => crate::ensure_eq!(e.errno, libc::EBADMSG),
+        None => anyhow::bail!("the duplicate segment should have been reported"),
+    }
+
+    // Reading the error clears it.
+    crate::ensure_eq!(bob.get_last_error(bob_fd)?.is_none(), true);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_keep_cwnd_after_idle_when_paced() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, _): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Connections restart from the initial window after idle periods unless they opt out.
+    match bob.get_socket_option(bob_fd, SocketOption::KeepCwndAfterIdleWhenPaced(true))? {
+        SocketOption::KeepCwndAfterIdleWhenPaced(keep_cwnd) => crate::ensure_eq!(keep_cwnd, false),
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    }
+
+    // The option can be changed on established connections.
+    bob.set_socket_option(bob_fd, SocketOption::KeepCwndAfterIdleWhenPaced(true))?;
+    match bob.get_socket_option(bob_fd, SocketOption::KeepCwndAfterIdleWhenPaced(false))? {
+        SocketOption::KeepCwndAfterIdleWhenPaced(keep_cwnd) => crate::ensure_eq!(keep_cwnd, true),
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_keep_cwnd_after_idle_when_paced_keeps_window() -> Result<()> {
+    // Paced connections that opted in send again with the window that they had before going idle.
+    let (cwnd_before_idle, cwnd_after_idle): (u32, u32) = get_cwnd_around_idle(true, 4.0)?;
+    crate::ensure_eq!(cwnd_after_idle, cwnd_before_idle);
+
+    // All other connections shrink their window towards the restart window.
+    let (cwnd_before_idle, cwnd_after_idle): (u32, u32) = get_cwnd_around_idle(false, 4.0)?;
+    crate::ensure_eq!(cwnd_after_idle < cwnd_before_idle, true);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_restart_after_idle_waits_for_rto() -> Result<()> {
+    // The ACK clock is still considered running if the connection was idle for less than an RTO.
+    let (cwnd_before_idle, cwnd_after_idle): (u32, u32) = get_cwnd_around_idle(false, 0.5)?;
+    crate::ensure_eq!(cwnd_after_idle, cwnd_before_idle);
+
+    // Once a full RTO has passed, the window shrinks towards the restart window.
+    let (cwnd_before_idle, cwnd_after_idle): (u32, u32) = get_cwnd_around_idle(false, 1.5)?;
+    crate::ensure_eq!(cwnd_after_idle < cwnd_before_idle, true);
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::tests::{connect, exchange_frames},
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{
+            self,
+            engine::{SharedEngine, TIMEOUT_SECONDS},
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        queue::{OperationResult, QDesc, QToken},
+    },
+};
+use ::anyhow::Result;
+use ::std::time::{Duration, Instant};
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Waits for [qt] to complete on [engine] and returns the data that it popped.
+fn expect_data(engine: &SharedEngine, qt: QToken) -> Result<DemiBuffer> {
+    match engine.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Pop(_, buf, _)) => Ok(buf),
+        (_, result) => anyhow::bail!("pop should have succeeded (result={:?})", result),
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_peek() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    let data: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+    carrie.tcp_push(carrie_fd, DemiBuffer::from_slice_with_headroom(&data, MAX_HEADER_SIZE)?)?;
+    exchange_frames(&mut bob, &mut carrie);
+
+    // Peeking returns the data without consuming it, no matter how often Bob looks.
+    for _ in 0..2 {
+        let peek_qt: QToken = bob.tcp_peek(bob_fd)?;
+        crate::ensure_eq!(&expect_data(&bob, peek_qt)?[..], &data[..]);
+    }
+
+    // A pop consumes the data, so there is nothing left to peek at afterwards.
+    let pop_qt: QToken = bob.tcp_pop(bob_fd)?;
+    crate::ensure_eq!(&expect_data(&bob, pop_qt)?[..], &data[..]);
+    let peek_qt: QToken = bob.tcp_peek(bob_fd)?;
+    crate::ensure_eq!(bob.wait(peek_qt, Duration::ZERO).is_err(), true);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_pop_wait_all() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Half of the header that Bob waits for is not enough.
+    let header: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+    let body: [u8; 2] = [9, 10];
+    carrie.tcp_push(
+        carrie_fd,
+        DemiBuffer::from_slice_with_headroom(&header[..4], MAX_HEADER_SIZE)?,
+    )?;
+    exchange_frames(&mut bob, &mut carrie);
+    let pop_qt: QToken = bob.tcp_pop_all(bob_fd, header.len())?;
+    crate::ensure_eq!(bob.wait(pop_qt, Duration::ZERO).is_err(), true);
+
+    // The rest of the header completes the pop, and both segments come back as a single buffer. What follows the
+    // header stays in the queue.
+    carrie.tcp_push(
+        carrie_fd,
+        DemiBuffer::from_slice_with_headroom(&[&header[4..], &body[..]].concat(), MAX_HEADER_SIZE)?,
+    )?;
+    exchange_frames(&mut bob, &mut carrie);
+    crate::ensure_eq!(&expect_data(&bob, pop_qt)?[..], &header[..]);
+    let pop_qt: QToken = bob.tcp_pop(bob_fd)?;
+    crate::ensure_eq!(&expect_data(&bob, pop_qt)?[..], &body[..]);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_pop_buffered_bytes() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    let data: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+    carrie.tcp_push(carrie_fd, DemiBuffer::from_slice_with_headroom(&data, MAX_HEADER_SIZE)?)?;
+    exchange_frames(&mut bob, &mut carrie);
+
+    // A pop that takes half of the data tells Bob how much of it is left.
+    let pop_qt: QToken = bob.tcp_pop_all(bob_fd, 4)?;
+    match bob.wait(pop_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Pop(_, buf, buffered)) => {
+            crate::ensure_eq!(&buf[..], &data[..4]);
+            crate::ensure_eq!(buffered, 4);
+        },
+        (_, result) => anyhow::bail!("pop should have succeeded (result={:?})", result),
+    }
+
+    // Once the rest is popped, nothing is left.
+    let pop_qt: QToken = bob.tcp_pop(bob_fd)?;
+    match bob.wait(pop_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Pop(_, buf, buffered)) => {
+            crate::ensure_eq!(&buf[..], &data[4..]);
+            crate::ensure_eq!(buffered, 0);
+        },
+        (_, result) => anyhow::bail!("pop should have succeeded (result={:?})", result),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,250 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use crate::{
+    inetstack::{
+        protocols::{
+            layer4::tcp::tests::{connect, connect_and_accept, exchange_frames, get_stat, listen, MAX_ROUNDS},
+            MAX_HEADER_SIZE,
+        },
+        test_helpers::{
+            self,
+            engine::{SharedEngine, TIMEOUT_SECONDS},
+        },
+    },
+    runtime::{
+        memory::DemiBuffer,
+        network::consts::TCP_ACK_DELAY_TIMEOUT,
+        queue::{OperationResult, QDesc, QToken},
+    },
+    SocketOption, TcpInfo,
+};
+use ::anyhow::Result;
+use ::std::{
+    collections::VecDeque,
+    net::SocketAddrV4,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[test]
+fn tcp_push_chain() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Chain a header of odd length and a body that live in separate buffers, so that the checksum has to carry a byte
+    // across the segment boundary.
+    let header: [u8; 5] = [1, 2, 3, 4, 5];
+    let body: [u8; 8] = [6, 7, 8, 9, 10, 11, 12, 13];
+    let mut buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&header, MAX_HEADER_SIZE)?;
+    buf.append(DemiBuffer::from_slice(&body)?)?;
+    crate::ensure_eq!(buf.num_segments(), 2);
+
+    // Both segments go out in a single TCP segment that Bob accepts.
+    let push_qt: QToken = carrie.tcp_push(carrie_fd, buf)?;
+    let pop_qt: QToken = bob.tcp_pop(bob_fd)?;
+    exchange_frames(&mut bob, &mut carrie);
+    match bob.wait(pop_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Pop(_, buf, _)) => crate::ensure_eq!(&buf[..], &[&header[..], &body[..]].concat()[..]),
+        (_, result) => anyhow::bail!("pop should have succeeded (result={:?})", result),
+    }
+
+    // The push completes once Bob's delayed ACK arrives.
+    let bob_now: Instant = bob.get_runtime().get_now();
+    bob.advance_clock(bob_now + TCP_ACK_DELAY_TIMEOUT);
+    exchange_frames(&mut bob, &mut carrie);
+    match carrie.wait(push_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => (),
+        (_, result) => anyhow::bail!("push should have succeeded (result={:?})", result),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_push_multiple_segments() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Carrie's buffer spans a few segments. The headers of each segment must not end up in the data of the one before.
+    let mss: usize = match carrie.get_socket_option(carrie_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => info.mss,
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    };
+    let data: Vec<u8> = (0..3 * mss + mss / 2).map(|i| i as u8).collect();
+    carrie.tcp_push(carrie_fd, DemiBuffer::from_slice_with_headroom(&data, MAX_HEADER_SIZE)?)?;
+    let pop_qt: QToken = bob.tcp_pop_all(bob_fd, data.len())?;
+    exchange_frames(&mut bob, &mut carrie);
+    match bob.wait(pop_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Pop(_, buf, _)) => crate::ensure_eq!(&buf[..], &data[..]),
+        (_, result) => anyhow::bail!("pop should have succeeded (result={:?})", result),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_dsack_undoes_spurious_timeout() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let (bob_fd, carrie_fd): (QDesc, QDesc) = connect(&mut bob, &mut carrie)?;
+
+    // Carrie receives Bob's data, but her ACK gets lost.
+    let push_qt: QToken = bob.tcp_push(bob_fd, DemiBuffer::from_slice_with_headroom(&[1; 8], MAX_HEADER_SIZE)?)?;
+    bob.poll();
+    for frame in bob.pop_all_frames() {
+        carrie.push_frame(frame);
+    }
+    carrie.poll();
+    let carrie_now: Instant = carrie.get_runtime().get_now();
+    carrie.advance_clock(carrie_now + TCP_ACK_DELAY_TIMEOUT);
+    carrie.poll();
+    crate::ensure_eq!(carrie.pop_all_frames().is_empty(), false);
+
+    // Bob times out and retransmits, so Carrie reports the retransmission as a duplicate.
+    let rto: Duration = match bob.get_socket_option(bob_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => info.rto,
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    };
+    let bob_now: Instant = bob.get_runtime().get_now();
+    bob.advance_clock(bob_now + rto);
+    exchange_frames(&mut bob, &mut carrie);
+    match bob.wait(push_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => (),
+        (_, result) => anyhow::bail!("push should have succeeded (result={:?})", result),
+    }
+    crate::ensure_eq!(get_stat(&mut bob, bob_fd, "retransmission_timeouts")?, 1);
+    crate::ensure_eq!(get_stat(&mut carrie, carrie_fd, "duplicate_sacks_sent")?, 1);
+    crate::ensure_eq!(get_stat(&mut bob, bob_fd, "duplicate_sacks_received")?, 1);
+    crate::ensure_eq!(get_stat(&mut bob, bob_fd, "spurious_retransmission_timeouts")?, 1);
+
+    Ok(())
+}
+
+#[test]
+fn tcp_push_resumes_when_window_opens() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let listen_fd: QDesc = listen(&mut carrie, remote)?;
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    bob.set_socket_option(bob_fd, SocketOption::CongestionControl("cubic"))?;
+    connect_and_accept(&mut bob, &mut carrie, bob_fd, listen_fd, remote)?;
+
+    // Bob sends as much as his initial window allows and then goes quiet.
+    let mss: usize = match bob.get_socket_option(bob_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => info.mss,
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    };
+    let push_qt: QToken = bob.tcp_push(
+        bob_fd,
+        DemiBuffer::from_slice_with_headroom(&vec![1; 16 * mss], MAX_HEADER_SIZE)?,
+    )?;
+    let mut frames: VecDeque<DemiBuffer> = VecDeque::new();
+    for _ in 0..MAX_ROUNDS {
+        bob.poll();
+        frames.extend(bob.pop_all_frames());
+    }
+    crate::ensure_eq!(frames.is_empty(), false);
+    crate::ensure_eq!(frames.len() < 16, true);
+
+    // Carrie's ACKs open the window again, which wakes Bob up to send more.
+    for frame in frames {
+        carrie.push_frame(frame);
+    }
+    let carrie_now: Instant = carrie.get_runtime().get_now();
+    carrie.advance_clock(carrie_now + TCP_ACK_DELAY_TIMEOUT);
+    carrie.poll();
+    for frame in carrie.pop_all_frames() {
+        bob.push_frame(frame);
+    }
+    bob.poll();
+    let frames: VecDeque<DemiBuffer> = bob.pop_all_frames();
+    crate::ensure_eq!(frames.is_empty(), false);
+    for frame in frames {
+        carrie.push_frame(frame);
+    }
+
+    // Bob keeps going until all of his data was acknowledged.
+    for _ in 0..MAX_ROUNDS {
+        let carrie_now: Instant = carrie.get_runtime().get_now();
+        carrie.advance_clock(carrie_now + TCP_ACK_DELAY_TIMEOUT);
+        exchange_frames(&mut bob, &mut carrie);
+    }
+    match bob.wait(push_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => (),
+        (_, result) => anyhow::bail!("push should have succeeded (result={:?})", result),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tcp_writable_waits_for_window() -> Result<()> {
+    let now: Instant = Instant::now();
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let remote: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let listen_fd: QDesc = listen(&mut carrie, remote)?;
+    let bob_fd: QDesc = bob.tcp_socket()?;
+    bob.set_socket_option(bob_fd, SocketOption::CongestionControl("cubic"))?;
+    connect_and_accept(&mut bob, &mut carrie, bob_fd, listen_fd, remote)?;
+    let mss: usize = match bob.get_socket_option(bob_fd, SocketOption::TcpInfo(TcpInfo::default()))? {
+        SocketOption::TcpInfo(info) => info.mss,
+        option => anyhow::bail!("unexpected option (option={:?})", option),
+    };
+
+    // Waiting for no room at all is meaningless.
+    match bob.tcp_writable(bob_fd, 0) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::EINVAL),
+        Ok(_) => anyhow::bail!("waiting for zero bytes should be rejected"),
+    }
+
+    // An idle connection has room.
+    let writable_qt: QToken = bob.tcp_writable(bob_fd, mss)?;
+    match bob.wait(writable_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Writable) => (),
+        (_, result) => anyhow::bail!("writable should have succeeded (result={:?})", result),
+    }
+
+    // Once Bob queues more than his window allows, he has to wait for ACKs.
+    let push_qt: QToken = bob.tcp_push(
+        bob_fd,
+        DemiBuffer::from_slice_with_headroom(&vec![1; 16 * mss], MAX_HEADER_SIZE)?,
+    )?;
+    bob.poll();
+    let writable_qt: QToken = bob.tcp_writable(bob_fd, mss)?;
+    for _ in 0..MAX_ROUNDS {
+        bob.poll();
+    }
+    match bob.wait(writable_qt, Duration::ZERO) {
+        Err(e) => crate::ensure_eq!(e.errno, libc::ETIMEDOUT),
+        Ok(result) => anyhow::bail!("writable should still be waiting (result={:?})", result),
+    }
+
+    // Carrie's ACKs make room again.
+    for _ in 0..MAX_ROUNDS {
+        let carrie_now: Instant = carrie.get_runtime().get_now();
+        carrie.advance_clock(carrie_now + TCP_ACK_DELAY_TIMEOUT);
+        exchange_frames(&mut bob, &mut carrie);
+    }
+    match bob.wait(push_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => (),
+        (_, result) => anyhow::bail!("push should have succeeded (result={:?})", result),
+    }
+    match bob.wait(writable_qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Writable) => (),
+        (_, result) => anyhow::bail!("writable should have succeeded (result={:?})", result),
+    }
+
+    Ok(())
+}
@@ -585,7 +585,7 @@ impl Simulation {
                     info!("connection established as expected (qd={:?})", qd);
                     Ok(())
                 },
-                OperationResult::Pop(_sockaddr, _data) => {
+                OperationResult::Pop(_sockaddr, _data, _buffered) => {
                     info!("pop completed as expected (qd={:?})", qd);
                     Ok(())
                 },
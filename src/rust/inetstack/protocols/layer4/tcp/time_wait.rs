@@ -0,0 +1,469 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::{
+    inetstack::protocols::layer4::tcp::{
+        header::TcpHeader,
+        timestamps::{get_timestamps_option, Timestamps},
+        SeqNumber,
+    },
+    runtime::network::config::TimeWaitRecycle,
+};
+use ::std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    net::SocketAddrV4,
+    time::{Duration, Instant},
+};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// How long a connection in TIME_WAIT has to be idle before a new outgoing connection may take over its 4-tuple. By
+/// then, the timestamps of our peer have moved on far enough for PAWS to reject old duplicates of the old connection.
+const REUSE_IDLE_TIME: Duration = Duration::from_secs(1);
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// What a connection that entered TIME_WAIT needs to remember to answer the segments that our peer still sends.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeWaitEntry {
+    // Sequence number that follows our FIN. In RFC 793 terms, this is SND.NXT.
+    send_next: SeqNumber,
+    // Sequence number that follows the FIN of our peer. In RFC 793 terms, this is RCV.NXT.
+    receive_next: SeqNumber,
+    // Window that we last advertised, as it goes into the header.
+    window_size: u16,
+    // State of the timestamps option (RFC 7323), if the connection used it.
+    timestamps: Option<Timestamps>,
+    // For how long the connection stays in TIME_WAIT, which starts over when our peer retransmits its FIN.
+    duration: Duration,
+    expires_at: Instant,
+    // Time at which we last heard from our peer.
+    last_seen: Instant,
+}
+
+/// What to do with a segment that belongs to a connection in TIME_WAIT.
+#[derive(Debug)]
+pub enum TimeWaitReply {
+    /// Drop the segment.
+    Drop,
+    /// Drop the segment and answer it with this ACK.
+    Ack(TcpHeader),
+    /// The segment is a SYN that opens a new incarnation of the connection, so it goes to the listening socket.
+    Reopen,
+}
+
+/// Connections in TIME_WAIT. They do not keep a control block or a coroutine around, so that a host that closes many
+/// connections does not pay more for them than a table entry. Entries expire lazily, whenever the table is looked at.
+pub struct TimeWaitTable {
+    max_entries: usize,
+    // When new outgoing connections may take over the 4-tuple of an entry.
+    recycle: TimeWaitRecycle,
+    entries: HashMap<(SocketAddrV4, SocketAddrV4), TimeWaitEntry>,
+    // Expiry times of the entries, earliest first. Entries whose timer started over leave a stale item behind, which is
+    // skipped once it comes up.
+    expiry_queue: BinaryHeap<Reverse<(Instant, SocketAddrV4, SocketAddrV4)>>,
+    // Number of connections that were not kept in TIME_WAIT because the table was full.
+    overflows: u64,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl TimeWaitEntry {
+    pub fn new(
+        send_next: SeqNumber,
+        receive_next: SeqNumber,
+        window_size: u16,
+        timestamps: Option<Timestamps>,
+        duration: Duration,
+        now: Instant,
+    ) -> Self {
+        Self {
+            send_next,
+            receive_next,
+            window_size,
+            timestamps,
+            duration,
+            expires_at: now + duration,
+            last_seen: now,
+        }
+    }
+
+    /// Builds the ACK that answers a segment from `remote` to `local` at `now`.
+    fn ack(&mut self, local: SocketAddrV4, remote: SocketAddrV4, now: Instant) -> TcpHeader {
+        let mut header: TcpHeader = TcpHeader::new(local.port(), remote.port());
+        header.seq_num = self.send_next;
+        header.ack = true;
+        header.ack_num = self.receive_next;
+        header.window_size = self.window_size;
+        if let Some(timestamps) = self.timestamps.as_mut() {
+            header.push_option(timestamps.get_option(now));
+            timestamps.on_ack_sent(self.receive_next);
+        }
+        header
+    }
+
+    /// Checks whether the SYN `header` may open a new incarnation of the connection (RFC 6191). With timestamps, it has
+    /// to carry a timestamp that is newer than the last one of the old connection. Without them, its sequence number
+    /// has to lie beyond the sequence space of the old connection (RFC 1122 Section 4.2.2.13).
+    fn accepts_syn(&self, header: &TcpHeader) -> bool {
+        match (self.timestamps, get_timestamps_option(header)) {
+            (Some(timestamps), Some((ts_val, _))) => (ts_val.wrapping_sub(timestamps.get_recent()) as i32) > 0,
+            _ => header.seq_num > self.receive_next,
+        }
+    }
+}
+
+impl TimeWaitTable {
+    pub fn new(max_entries: usize, recycle: TimeWaitRecycle) -> Self {
+        Self {
+            max_entries,
+            recycle,
+            entries: HashMap::new(),
+            expiry_queue: BinaryHeap::new(),
+            overflows: 0,
+        }
+    }
+
+    /// Puts the connection from `local` to `remote` into TIME_WAIT. Connections that linger for no time at all and
+    /// connections that do not fit into the table anymore are forgotten right away.
+    pub fn insert(&mut self, local: SocketAddrV4, remote: SocketAddrV4, entry: TimeWaitEntry, now: Instant) {
+        self.expire(now);
+        if entry.duration.is_zero() {
+            return;
+        }
+        if self.entries.len() >= self.max_entries {
+            warn!(
+                "insert(): TIME_WAIT table is full, not keeping connection (local={:?}, remote={:?})",
+                local, remote
+            );
+            self.overflows += 1;
+            return;
+        }
+        self.expiry_queue.push(Reverse((entry.expires_at, local, remote)));
+        self.entries.insert((local, remote), entry);
+    }
+
+    /// Processes `header`, a segment from `remote` to `local` that carried `data_len` bytes. Returns `None` if the
+    /// segment does not belong to a connection in TIME_WAIT.
+    pub fn receive(
+        &mut self,
+        local: SocketAddrV4,
+        remote: SocketAddrV4,
+        header: &TcpHeader,
+        data_len: usize,
+        now: Instant,
+    ) -> Option<TimeWaitReply> {
+        self.expire(now);
+        let entry: &mut TimeWaitEntry = self.entries.get_mut(&(local, remote))?;
+
+        // Resets would cut TIME_WAIT short, which leaves the 4-tuple open to old duplicates (RFC 1337).
+        if header.rst {
+            return Some(TimeWaitReply::Drop);
+        }
+        if header.syn && !header.ack {
+            if entry.accepts_syn(header) {
+                debug!(
+                    "receive(): reopening connection (local={:?}, remote={:?})",
+                    local, remote
+                );
+                self.entries.remove(&(local, remote));
+                return Some(TimeWaitReply::Reopen);
+            }
+            return Some(TimeWaitReply::Ack(entry.ack(local, remote, now)));
+        }
+
+        entry.last_seen = now;
+        let timestamp: Option<(u32, u32)> = get_timestamps_option(header);
+        let acceptable: bool = match (entry.timestamps, timestamp) {
+            (Some(timestamps), Some((ts_val, _))) => timestamps.is_acceptable(ts_val),
+            _ => true,
+        };
+        if acceptable {
+            if let (Some(timestamps), Some((ts_val, _))) = (entry.timestamps.as_mut(), timestamp) {
+                timestamps.update_recent(ts_val, header.seq_num);
+            }
+        }
+
+        // Our peer did not get our ACK of its FIN, so we acknowledge it again and start TIME_WAIT over (RFC 793).
+        if header.fin && acceptable {
+            entry.expires_at = now + entry.duration;
+            self.expiry_queue.push(Reverse((entry.expires_at, local, remote)));
+            return Some(TimeWaitReply::Ack(entry.ack(local, remote, now)));
+        }
+        // Pure ACKs are dropped silently, so that we do not get into an ACK loop with our peer.
+        if data_len > 0 || header.fin || !acceptable {
+            return Some(TimeWaitReply::Ack(entry.ack(local, remote, now)));
+        }
+        Some(TimeWaitReply::Drop)
+    }
+
+    /// Checks whether a new outgoing connection from `local` to `remote` may use this 4-tuple. If a connection with the
+    /// same 4-tuple is still in TIME_WAIT, whether it is taken over depends on the recycling policy. By default, this
+    /// happens if it used timestamps and has been idle for long enough, as PAWS then protects the new connection from
+    /// the old one. Otherwise, the 4-tuple is still in use.
+    pub fn try_reuse(&mut self, local: SocketAddrV4, remote: SocketAddrV4, now: Instant) -> bool {
+        self.expire(now);
+        let entry: &TimeWaitEntry = match self.entries.get(&(local, remote)) {
+            Some(entry) => entry,
+            None => return true,
+        };
+        let reusable: bool = match self.recycle {
+            TimeWaitRecycle::Never => false,
+            TimeWaitRecycle::Timestamps => {
+                entry.timestamps.is_some() && now.saturating_duration_since(entry.last_seen) >= REUSE_IDLE_TIME
+            },
+            TimeWaitRecycle::Always => true,
+        };
+        if !reusable {
+            return false;
+        }
+        debug!(
+            "try_reuse(): reusing connection (local={:?}, remote={:?})",
+            local, remote
+        );
+        self.entries.remove(&(local, remote));
+        true
+    }
+
+    /// Returns the number of connections in TIME_WAIT.
+    pub fn get_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of connections that were not kept in TIME_WAIT because the table was full.
+    pub fn get_overflows(&self) -> u64 {
+        self.overflows
+    }
+
+    /// Removes the entries that expired by `now`.
+    fn expire(&mut self, now: Instant) {
+        while let Some(Reverse((expires_at, local, remote))) = self.expiry_queue.peek().copied() {
+            if expires_at > now {
+                break;
+            }
+            self.expiry_queue.pop();
+            if let Some(entry) = self.entries.get(&(local, remote)) {
+                if entry.expires_at <= now {
+                    self.entries.remove(&(local, remote));
+                }
+            }
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeWaitEntry, TimeWaitReply, TimeWaitTable, REUSE_IDLE_TIME};
+    use crate::{
+        inetstack::protocols::layer4::tcp::{
+            header::{TcpHeader, TcpOptions2},
+            timestamps::Timestamps,
+            SeqNumber,
+        },
+        runtime::network::config::TimeWaitRecycle,
+    };
+    use ::anyhow::Result;
+    use ::std::{
+        net::{Ipv4Addr, SocketAddrV4},
+        time::{Duration, Instant},
+    };
+
+    const DURATION: Duration = Duration::from_secs(4);
+
+    fn endpoints() -> (SocketAddrV4, SocketAddrV4) {
+        (
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 80),
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 50000),
+        )
+    }
+
+    fn segment(seq_num: u32, ts_val: Option<u32>) -> TcpHeader {
+        let mut header: TcpHeader = TcpHeader::new(50000, 80);
+        header.seq_num = SeqNumber::from(seq_num);
+        if let Some(ts_val) = ts_val {
+            header.push_option(TcpOptions2::Timestamp {
+                sender_timestamp: ts_val,
+                echo_timestamp: 0,
+            });
+        }
+        header
+    }
+
+    fn entry(timestamps: Option<Timestamps>, now: Instant) -> TimeWaitEntry {
+        TimeWaitEntry::new(
+            SeqNumber::from(500),
+            SeqNumber::from(1000),
+            1024,
+            timestamps,
+            DURATION,
+            now,
+        )
+    }
+
+    #[test]
+    fn test_time_wait_expiry() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote) = endpoints();
+        let mut table: TimeWaitTable = TimeWaitTable::new(16, TimeWaitRecycle::Timestamps);
+        table.insert(local, remote, entry(None, now), now);
+        crate::ensure_eq!(table.get_count(), 1);
+
+        // A retransmitted FIN is acknowledged again and starts TIME_WAIT over.
+        let mut fin: TcpHeader = segment(999, None);
+        fin.fin = true;
+        fin.ack = true;
+        let later: Instant = now + DURATION / 2;
+        match table.receive(local, remote, &fin, 0, later) {
+            Some(TimeWaitReply::Ack(reply)) => {
+                crate::ensure_eq!(reply.seq_num, SeqNumber::from(500));
+                crate::ensure_eq!(reply.ack_num, SeqNumber::from(1000));
+            },
+            reply => anyhow::bail!("unexpected reply: {:?}", reply),
+        }
+        table.expire(now + DURATION);
+        crate::ensure_eq!(table.get_count(), 1);
+        table.expire(later + DURATION);
+        crate::ensure_eq!(table.get_count(), 0);
+
+        // Connections that linger for no time are not kept at all.
+        table.insert(
+            local,
+            remote,
+            TimeWaitEntry::new(SeqNumber::from(0), SeqNumber::from(0), 0, None, Duration::ZERO, now),
+            now,
+        );
+        crate::ensure_eq!(table.get_count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_wait_limit() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote) = endpoints();
+        let mut table: TimeWaitTable = TimeWaitTable::new(1, TimeWaitRecycle::Timestamps);
+        table.insert(local, remote, entry(None, now), now);
+        let other: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 3), 50000);
+        table.insert(local, other, entry(None, now), now);
+        crate::ensure_eq!(table.get_count(), 1);
+        crate::ensure_eq!(table.get_overflows(), 1);
+        crate::ensure_eq!(table.receive(local, other, &segment(0, None), 0, now).is_none(), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_wait_ignores_reset() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote) = endpoints();
+        let mut table: TimeWaitTable = TimeWaitTable::new(16, TimeWaitRecycle::Timestamps);
+        table.insert(local, remote, entry(None, now), now);
+
+        let mut rst: TcpHeader = segment(1000, None);
+        rst.rst = true;
+        crate::ensure_eq!(
+            matches!(table.receive(local, remote, &rst, 0, now), Some(TimeWaitReply::Drop)),
+            true
+        );
+        crate::ensure_eq!(table.get_count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_wait_reopen() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote) = endpoints();
+
+        // Without timestamps, a SYN has to start beyond the old sequence space.
+        let mut table: TimeWaitTable = TimeWaitTable::new(16, TimeWaitRecycle::Timestamps);
+        table.insert(local, remote, entry(None, now), now);
+        let mut syn: TcpHeader = segment(900, None);
+        syn.syn = true;
+        crate::ensure_eq!(
+            matches!(table.receive(local, remote, &syn, 0, now), Some(TimeWaitReply::Ack(_))),
+            true
+        );
+        syn.seq_num = SeqNumber::from(2000);
+        crate::ensure_eq!(
+            matches!(table.receive(local, remote, &syn, 0, now), Some(TimeWaitReply::Reopen)),
+            true
+        );
+        crate::ensure_eq!(table.get_count(), 0);
+
+        // With timestamps, the timestamp of the SYN decides, whatever its sequence number.
+        let timestamps: Timestamps = Timestamps::new(now, 100, SeqNumber::from(1000));
+        table.insert(local, remote, entry(Some(timestamps), now), now);
+        let mut syn: TcpHeader = segment(2000, Some(100));
+        syn.syn = true;
+        crate::ensure_eq!(
+            matches!(table.receive(local, remote, &syn, 0, now), Some(TimeWaitReply::Ack(_))),
+            true
+        );
+        let mut syn: TcpHeader = segment(900, Some(101));
+        syn.syn = true;
+        crate::ensure_eq!(
+            matches!(table.receive(local, remote, &syn, 0, now), Some(TimeWaitReply::Reopen)),
+            true
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_wait_reuse() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote) = endpoints();
+        let mut table: TimeWaitTable = TimeWaitTable::new(16, TimeWaitRecycle::Timestamps);
+
+        // Connections without timestamps keep their 4-tuple until they expire.
+        table.insert(local, remote, entry(None, now), now);
+        crate::ensure_eq!(table.try_reuse(local, remote, now + REUSE_IDLE_TIME), false);
+        crate::ensure_eq!(table.try_reuse(local, remote, now + DURATION), true);
+
+        // Connections with timestamps give it up once they have been idle for long enough.
+        let timestamps: Timestamps = Timestamps::new(now, 100, SeqNumber::from(1000));
+        table.insert(local, remote, entry(Some(timestamps), now), now);
+        crate::ensure_eq!(table.try_reuse(local, remote, now), false);
+        crate::ensure_eq!(table.try_reuse(local, remote, now + REUSE_IDLE_TIME), true);
+        crate::ensure_eq!(table.get_count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_wait_recycle() -> Result<()> {
+        let now: Instant = Instant::now();
+        let (local, remote) = endpoints();
+        let timestamps: Timestamps = Timestamps::new(now, 100, SeqNumber::from(1000));
+
+        // Without recycling, even connections with timestamps keep their 4-tuple until they expire.
+        let mut table: TimeWaitTable = TimeWaitTable::new(16, TimeWaitRecycle::Never);
+        table.insert(local, remote, entry(Some(timestamps), now), now);
+        crate::ensure_eq!(table.try_reuse(local, remote, now + REUSE_IDLE_TIME), false);
+        crate::ensure_eq!(table.try_reuse(local, remote, now + DURATION), true);
+
+        // With recycling always on, the 4-tuple is free right away.
+        let mut table: TimeWaitTable = TimeWaitTable::new(16, TimeWaitRecycle::Always);
+        table.insert(local, remote, entry(None, now), now);
+        crate::ensure_eq!(table.try_reuse(local, remote, now), true);
+        crate::ensure_eq!(table.get_count(), 0);
+
+        Ok(())
+    }
+}
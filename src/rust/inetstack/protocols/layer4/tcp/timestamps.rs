@@ -0,0 +1,153 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::inetstack::protocols::layer4::tcp::{
+    header::{TcpHeader, TcpOptions2},
+    SeqNumber,
+};
+use ::std::time::{Duration, Instant};
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Number of header bytes that the timestamps option takes up in every segment, including the padding that aligns it.
+pub const TIMESTAMPS_OPTION_SIZE: usize = 12;
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// State of the timestamps option (RFC 7323) of a connection that negotiated it during the handshake.
+#[derive(Clone, Copy, Debug)]
+pub struct Timestamps {
+    // Time at which our timestamp clock read zero. Every connection starts its clock when it sends or receives its SYN,
+    // so the timestamps that we send do not reveal for how long the stack has been running.
+    base: Instant,
+    // Timestamp of our peer that we echo back. In RFC 7323 terms, this is TS.Recent.
+    recent: u32,
+    // Acknowledgement number of the last segment that we sent. In RFC 7323 terms, this is Last.ACK.sent.
+    last_ack_sent: SeqNumber,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl Timestamps {
+    pub fn new(base: Instant, recent: u32, last_ack_sent: SeqNumber) -> Self {
+        Self {
+            base,
+            recent,
+            last_ack_sent,
+        }
+    }
+
+    /// Reads a timestamp clock that started at `base`. It ticks once per millisecond, which is within the range that
+    /// RFC 7323 Section 5.4 asks for, and wraps around after about 49 days.
+    pub fn clock(base: Instant, now: Instant) -> u32 {
+        now.saturating_duration_since(base).as_millis() as u32
+    }
+
+    /// Builds the timestamps option for a segment that we send at `now`.
+    pub fn get_option(&self, now: Instant) -> TcpOptions2 {
+        TcpOptions2::Timestamp {
+            sender_timestamp: Self::clock(self.base, now),
+            echo_timestamp: self.recent,
+        }
+    }
+
+    /// Records the acknowledgement number of a segment that we sent.
+    pub fn on_ack_sent(&mut self, ack_num: SeqNumber) {
+        self.last_ack_sent = ack_num;
+    }
+
+    /// Runs the PAWS test (RFC 7323 Section 5.3) on the timestamp `ts_val` of a segment. Segments that carry a timestamp
+    /// older than the most recent one that we echo are old duplicates, even if their sequence numbers look valid.
+    /// TODO: TS.Recent should be considered invalid once the connection has been idle for more than 24 days.
+    pub fn is_acceptable(&self, ts_val: u32) -> bool {
+        (ts_val.wrapping_sub(self.recent) as i32) >= 0
+    }
+
+    /// Updates the timestamp that we echo with the timestamp `ts_val` of an acceptable segment that starts at `seq_num`
+    /// (RFC 7323 Section 4.3). Only segments that start at or before the last acknowledgement that we sent count, so
+    /// that we echo the timestamp of the segment that our ACK is for.
+    pub fn update_recent(&mut self, ts_val: u32, seq_num: SeqNumber) {
+        if seq_num <= self.last_ack_sent && self.is_acceptable(ts_val) {
+            self.recent = ts_val;
+        }
+    }
+
+    /// Takes a round-trip time sample from the timestamp `ts_ecr` that our peer echoed back to us at `now`. Returns
+    /// `None` if the echoed timestamp lies in the future, which means that it is bogus.
+    pub fn get_rtt_sample(&self, now: Instant, ts_ecr: u32) -> Option<Duration> {
+        let elapsed_ms: u32 = Self::clock(self.base, now).wrapping_sub(ts_ecr);
+        if (elapsed_ms as i32) < 0 {
+            return None;
+        }
+        Some(Duration::from_millis(elapsed_ms as u64))
+    }
+
+    /// Returns the timestamp of our peer that we echo back.
+    pub fn get_recent(&self) -> u32 {
+        self.recent
+    }
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Returns the timestamp (TSval) and echoed timestamp (TSecr) in the timestamps option of `header`, if any.
+pub fn get_timestamps_option(header: &TcpHeader) -> Option<(u32, u32)> {
+    header.iter_options().find_map(|option| match option {
+        TcpOptions2::Timestamp {
+            sender_timestamp,
+            echo_timestamp,
+        } => Some((*sender_timestamp, *echo_timestamp)),
+        _ => None,
+    })
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::Timestamps;
+    use crate::inetstack::protocols::layer4::tcp::SeqNumber;
+    use ::anyhow::Result;
+    use ::std::time::{Duration, Instant};
+
+    #[test]
+    fn test_paws_across_wraparound() -> Result<()> {
+        let mut timestamps: Timestamps = Timestamps::new(Instant::now(), u32::MAX - 1, SeqNumber::from(100));
+        crate::ensure_eq!(timestamps.is_acceptable(u32::MAX - 2), false);
+        crate::ensure_eq!(timestamps.is_acceptable(1), true);
+
+        // Segments that start after the last acknowledgement that we sent do not update the echoed timestamp.
+        timestamps.update_recent(1, SeqNumber::from(200));
+        crate::ensure_eq!(timestamps.get_recent(), u32::MAX - 1);
+        timestamps.update_recent(1, SeqNumber::from(100));
+        crate::ensure_eq!(timestamps.get_recent(), 1);
+        crate::ensure_eq!(timestamps.is_acceptable(u32::MAX - 1), false);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rtt_sample() -> Result<()> {
+        let base: Instant = Instant::now();
+        let timestamps: Timestamps = Timestamps::new(base, 0, SeqNumber::from(0));
+        let now: Instant = base + Duration::from_millis(250);
+        crate::ensure_eq!(timestamps.get_rtt_sample(now, 50), Some(Duration::from_millis(200)));
+        crate::ensure_eq!(timestamps.get_rtt_sample(now, 300), None);
+
+        Ok(())
+    }
+}
@@ -53,6 +53,18 @@ impl UdpHeader {
         self.dest_port
     }
 
+    /// Checks whether the checksum of the UDP datagram in [buf] matches its contents. Datagrams without a checksum always
+    /// match, while truncated ones never do.
+    pub fn verify_checksum(src_ipv4_addr: &Ipv4Addr, dst_ipv4_addr: &Ipv4Addr, buf: &[u8]) -> bool {
+        if buf.len() < UDP_HEADER_SIZE {
+            return false;
+        }
+        let (hdr_buf, payload_buf): (&[u8], &[u8]) = buf.split_at(UDP_HEADER_SIZE);
+        let checksum: u16 = u16::from_be_bytes([hdr_buf[6], hdr_buf[7]]);
+        // A checksum of zero means that the sender did not compute one.
+        checksum == 0 || checksum == Self::checksum(src_ipv4_addr, dst_ipv4_addr, hdr_buf, payload_buf)
+    }
+
     /// Parses and strips the UDP header off of the packet in [buf].
     pub fn parse_and_strip(
         src_ipv4_addr: &Ipv4Addr,
@@ -75,16 +87,8 @@ impl UdpHeader {
         }
 
         // Checksum payload.
-        if !checksum_offload {
-            let payload_buf: &[u8] = &buf[UDP_HEADER_SIZE..];
-            let checksum: u16 = u16::from_be_bytes([hdr_buf[6], hdr_buf[7]]);
-            // Check if we should skip checksum verification.
-            if checksum != 0 {
-                // No, so check if checksum value matches what we expect.
-                if checksum != Self::checksum(src_ipv4_addr, dst_ipv4_addr, hdr_buf, payload_buf) {
-                    return Err(Fail::new(EBADMSG, "UDP checksum mismatch"));
-                }
-            }
+        if !checksum_offload && !Self::verify_checksum(src_ipv4_addr, dst_ipv4_addr, &buf[..]) {
+            return Err(Fail::new(EBADMSG, "UDP checksum mismatch"));
         }
 
         let header: UdpHeader = Self::new(src_port, dest_port);
@@ -151,7 +155,7 @@ impl UdpHeader {
         state += u16::from_be_bytes([dst_octets[2], dst_octets[3]]) as u32;
 
         // Padding zeros (1 byte) and UDP protocol number (1 byte)
-        state += u16::from_be_bytes([0, IpProtocol::UDP as u8]) as u32;
+        state += u16::from_be_bytes([0, u8::from(IpProtocol::UDP)]) as u32;
 
         // UDP segment length (2 bytes)
         state += (udp_hdr.len() + data.len()) as u32;
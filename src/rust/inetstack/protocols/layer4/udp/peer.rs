@@ -9,10 +9,19 @@ use crate::{
     demikernel::config::Config,
     inetstack::protocols::{
         layer3::SharedLayer3Endpoint,
-        layer4::udp::{header::UdpHeader, socket::SharedUdpSocket},
+        layer4::{
+            checksum_sampler::{ChecksumSampler, ChecksumStats},
+            udp::{header::UdpHeader, socket::SharedUdpSocket},
+        },
+        MAX_HEADER_SIZE,
     },
-    runtime::{fail::Fail, memory::DemiBuffer, SharedDemiRuntime, SharedObject},
-    timer,
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::socket::{PopFlags, SocketId},
+        SharedDemiRuntime, SharedObject,
+    },
+    timer, SocketOption,
 };
 
 use ::std::{
@@ -28,12 +37,14 @@ use ::std::{
 
 /// UDP Peer
 pub struct UdpPeer {
+    /// Shared Demikernel runtime.
+    runtime: SharedDemiRuntime,
     /// Underlying transport.
     layer3_endpoint: SharedLayer3Endpoint,
-    /// Local IPv4 address.
-    local_ipv4_addr: Ipv4Addr,
     /// Offload checksum to hardware?
     checksum_offload: bool,
+    /// Validates the checksums of a sample of the received datagrams in software although the hardware did it already.
+    checksum_sampler: ChecksumSampler,
     /// Incoming routing table.
     addresses: HashMap<SocketAddrV4, SharedUdpSocket>,
 }
@@ -50,13 +61,14 @@ pub struct SharedUdpPeer(SharedObject<UdpPeer>);
 impl SharedUdpPeer {
     pub fn new(
         config: &Config,
-        _runtime: SharedDemiRuntime,
+        runtime: SharedDemiRuntime,
         layer3_endpoint: SharedLayer3Endpoint,
     ) -> Result<Self, Fail> {
         Ok(Self(SharedObject::<UdpPeer>::new(UdpPeer {
+            runtime,
             layer3_endpoint,
-            local_ipv4_addr: config.local_ipv4_addr()?,
             checksum_offload: config.udp_checksum_offload()?,
+            checksum_sampler: ChecksumSampler::new(config.rx_checksum_sample_interval().unwrap_or(0)),
             addresses: HashMap::<SocketAddrV4, SharedUdpSocket>::new(),
         })))
     }
@@ -64,12 +76,26 @@ impl SharedUdpPeer {
     /// Opens a UDP socket.
     pub fn socket(&mut self) -> Result<SharedUdpSocket, Fail> {
         SharedUdpSocket::new(
-            self.local_ipv4_addr,
+            self.runtime.clone(),
             self.layer3_endpoint.clone(),
             self.checksum_offload,
         )
     }
 
+    /// Sets an option on a UDP socket.
+    pub fn set_socket_option(&mut self, socket: &mut SharedUdpSocket, option: SocketOption) -> Result<(), Fail> {
+        socket.set_socket_option(option)
+    }
+
+    /// Gets an option on a UDP socket.
+    pub fn get_socket_option(
+        &mut self,
+        socket: &mut SharedUdpSocket,
+        option: SocketOption,
+    ) -> Result<SocketOption, Fail> {
+        socket.get_socket_option(option)
+    }
+
     /// Binds a UDP socket to a local endpoint address.
     pub fn bind(&mut self, socket: &mut SharedUdpSocket, addr: SocketAddrV4) -> Result<(), Fail> {
         if let Some(_) = socket.local() {
@@ -83,6 +109,54 @@ impl SharedUdpPeer {
         Ok(())
     }
 
+    /// Connects a UDP socket to a remote endpoint address. Afterwards, data may be pushed without a destination and only
+    /// datagrams that come from `remote` are received.
+    pub fn connect(&mut self, socket: &mut SharedUdpSocket, remote: SocketAddrV4) -> Result<(), Fail> {
+        // TODO: Allocate ephemeral port if not bound.
+        // FIXME: https://github.com/microsoft/demikernel/issues/973
+        if !socket.is_bound() {
+            let cause: String = format!("queue is not bound");
+            error!("connect(): {}", &cause);
+            return Err(Fail::new(libc::ENOTSUP, &cause));
+        }
+        socket.connect(remote);
+        Ok(())
+    }
+
+    /// Moves the sockets that are bound to the `old` local IPv4 address over to the `new` one. Sockets that are bound to
+    /// the wildcard address follow the local address on their own.
+    pub fn rebind(&mut self, old: Ipv4Addr, new: Ipv4Addr) {
+        let bound: Vec<SocketAddrV4> = self
+            .addresses
+            .keys()
+            .filter(|addr| *addr.ip() == old)
+            .cloned()
+            .collect();
+        for old_addr in bound {
+            let new_addr: SocketAddrV4 = SocketAddrV4::new(new, old_addr.port());
+            if let Some(mut socket) = self.addresses.remove(&old_addr) {
+                debug!("rebind(): moving socket from {:?} to {:?}", old_addr, new_addr);
+                // Binding an already bound UDP socket just replaces its address, so this cannot fail.
+                let _ = socket.bind(new_addr);
+                self.addresses.insert(new_addr, socket);
+                if let Some(qd) = self.runtime.remove_socket_id_to_qd(&SocketId::Passive(old_addr)) {
+                    self.runtime.insert_socket_id_to_qd(SocketId::Passive(new_addr), qd);
+                }
+            }
+        }
+    }
+
+    /// Returns the results of validating, in software, the checksums of a sample of the received datagrams whose checksum
+    /// the hardware validated.
+    pub fn get_checksum_stats(&self) -> ChecksumStats {
+        self.checksum_sampler.get_stats()
+    }
+
+    /// Returns the number of bytes in the datagrams that are waiting to be popped from `socket`.
+    pub fn get_buffered_bytes(&self, socket: &SharedUdpSocket) -> usize {
+        socket.get_buffered_bytes()
+    }
+
     /// Closes a UDP socket.
     pub fn hard_close(&mut self, socket: &mut SharedUdpSocket) -> Result<(), Fail> {
         if let Some(addr) = socket.local() {
@@ -110,8 +184,10 @@ impl SharedUdpPeer {
             error!("pushto(): {}", &cause);
             return Err(Fail::new(libc::ENOTSUP, &cause));
         }
+        // A datagram goes out in a single frame, with a checksum over all of its data, so gather the segments of a
+        // buffer chain first.
         // TODO: Remove copy once we actually use push coroutine for send.
-        socket.push(remote, buf.clone()).await?;
+        socket.push(remote, buf.clone().coalesce(MAX_HEADER_SIZE)?).await?;
         buf.trim(buf.len())
     }
 
@@ -120,8 +196,9 @@ impl SharedUdpPeer {
         &mut self,
         socket: &mut SharedUdpSocket,
         size: usize,
+        flags: PopFlags,
     ) -> Result<(Option<SocketAddr>, DemiBuffer), Fail> {
-        let (addr, buf) = socket.pop(size).await?;
+        let (addr, buf) = socket.pop(size, flags).await?;
         Ok((Some(addr.into()), buf))
     }
 
@@ -130,8 +207,16 @@ impl SharedUdpPeer {
         timer!("udp::receive");
         // Parse datagram. Safe to use the local IP address here because the lower IP layer would have discarded the
         // packet if the destination did not match the local IP.
+        let local_ipv4_addr: Ipv4Addr = self.layer3_endpoint.get_local_addr();
+        if self.checksum_offload
+            && !self
+                .checksum_sampler
+                .check(|| UdpHeader::verify_checksum(&src_ipv4_addr, &local_ipv4_addr, &buf[..]))
+        {
+            return;
+        }
         let hdr: UdpHeader =
-            match UdpHeader::parse_and_strip(&src_ipv4_addr, &self.local_ipv4_addr, &mut buf, self.checksum_offload) {
+            match UdpHeader::parse_and_strip(&src_ipv4_addr, &local_ipv4_addr, &mut buf, self.checksum_offload) {
                 Ok(header) => header,
                 Err(e) => {
                     let cause: String = format!("dropping packet: unable to parse UDP header");
@@ -141,7 +226,7 @@ impl SharedUdpPeer {
             };
         debug!("UDP received {:?}", hdr);
 
-        let local: SocketAddrV4 = SocketAddrV4::new(self.local_ipv4_addr, hdr.dest_port());
+        let local: SocketAddrV4 = SocketAddrV4::new(local_ipv4_addr, hdr.dest_port());
         let remote: SocketAddrV4 = SocketAddrV4::new(src_ipv4_addr, hdr.src_port());
 
         let socket: &mut SharedUdpSocket = match self.get_socket_from_addr(&local) {
@@ -7,13 +7,30 @@
 
 use crate::{
     collections::async_queue::AsyncQueue,
-    inetstack::protocols::{layer3::SharedLayer3Endpoint, layer4::udp::header::UdpHeader},
-    runtime::{fail::Fail, memory::DemiBuffer, network::unwrap_socketaddr, SharedObject},
+    inetstack::protocols::{
+        layer3::SharedLayer3Endpoint,
+        layer4::{dump_payload, pacer::Pacer, udp::header::UdpHeader},
+    },
+    runtime::{
+        fail::Fail,
+        memory::DemiBuffer,
+        network::{
+            socket::{
+                option::{PacingRate, PriorityClass},
+                PopFlags,
+            },
+            unwrap_socketaddr,
+        },
+        yield_with_timeout, SharedDemiRuntime, SharedObject,
+    },
+    SocketOption,
 };
+use ::serde_json::{json, Value};
 use ::std::{
     fmt::Debug,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     ops::{Deref, DerefMut},
+    time::Instant,
 };
 
 //======================================================================================================================
@@ -36,12 +53,18 @@ const SEND_QUEUE_MAX_SIZE: usize = 1024;
 
 /// Per-queue metadata for a UDP socket.
 pub struct UdpSocket {
-    local_ipv4_addr: Ipv4Addr,
     bound: Option<SocketAddrV4>,
+    // Remote address that this socket is connected to, if any.
+    connected: Option<SocketAddrV4>,
+    runtime: SharedDemiRuntime,
     layer3_endpoint: SharedLayer3Endpoint,
     // A queue of incoming packets as remote address and data buffer pairs.
     recv_queue: AsyncQueue<(SocketAddrV4, DemiBuffer)>,
     checksum_offload: bool,
+    // Shapes outgoing packets, if the application asked for pacing.
+    pacer: Option<Pacer>,
+    // Priority class of outgoing packets.
+    priority: PriorityClass,
 }
 #[derive(Clone)]
 pub struct SharedUdpSocket(SharedObject<UdpSocket>);
@@ -52,31 +75,93 @@ pub struct SharedUdpSocket(SharedObject<UdpSocket>);
 
 impl SharedUdpSocket {
     pub fn new(
-        local_ipv4_addr: Ipv4Addr,
+        runtime: SharedDemiRuntime,
         layer3_endpoint: SharedLayer3Endpoint,
         checksum_offload: bool,
     ) -> Result<Self, Fail> {
         Ok(Self(SharedObject::new(UdpSocket {
-            local_ipv4_addr,
             bound: None,
+            connected: None,
+            runtime,
             layer3_endpoint,
             recv_queue: AsyncQueue::<(SocketAddrV4, DemiBuffer)>::default(),
             checksum_offload,
+            pacer: None,
+            priority: PriorityClass::default(),
         })))
     }
 
+    /// Sets an option on the socket. Only pacing and priority are supported on UDP sockets.
+    pub fn set_socket_option(&mut self, option: SocketOption) -> Result<(), Fail> {
+        match option {
+            SocketOption::Pacing(Some(pacing)) => {
+                let rate: u64 = match pacing.rate {
+                    PacingRate::PacketsPerSecond(rate) | PacingRate::BytesPerSecond(rate) => rate,
+                };
+                if rate == 0 || pacing.burst == 0 {
+                    let cause: String = format!("pacing rate and burst must be positive (pacing={:?})", pacing);
+                    error!("set_socket_option(): {}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                }
+                self.pacer = Some(Pacer::new(pacing, self.runtime.get_now()));
+                Ok(())
+            },
+            SocketOption::Pacing(None) => {
+                self.pacer = None;
+                Ok(())
+            },
+            SocketOption::Priority(priority) => {
+                self.priority = priority;
+                Ok(())
+            },
+            _ => {
+                let cause: String = format!(
+                    "only pacing and priority are supported on UDP sockets (option={:?})",
+                    option
+                );
+                error!("set_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
+    /// Gets an option on the socket. The option should be passed in as [option] and the value is returned in
+    /// [option].
+    pub fn get_socket_option(&mut self, option: SocketOption) -> Result<SocketOption, Fail> {
+        match option {
+            SocketOption::Pacing(_) => Ok(SocketOption::Pacing(
+                self.pacer.as_ref().map(|pacer| pacer.get_pacing()),
+            )),
+            SocketOption::Priority(_) => Ok(SocketOption::Priority(self.priority)),
+            _ => {
+                let cause: String = format!(
+                    "only pacing and priority are supported on UDP sockets (option={:?})",
+                    option
+                );
+                error!("get_socket_option(): {}", cause);
+                Err(Fail::new(libc::ENOTSUP, &cause))
+            },
+        }
+    }
+
     pub fn bind(&mut self, local: SocketAddrV4) -> Result<(), Fail> {
         self.bound = Some(local);
         Ok(())
     }
 
+    pub fn connect(&mut self, remote: SocketAddrV4) {
+        self.connected = Some(remote);
+    }
+
     pub async fn push(&mut self, remote: Option<SocketAddr>, mut buf: DemiBuffer) -> Result<(), Fail> {
-        let remote: SocketAddrV4 = if let Some(remote) = remote {
-            unwrap_socketaddr(remote)?
-        } else {
-            let cause: String = format!("udp socket requires a remote address");
-            error!("pushto(): {}", &cause);
-            return Err(Fail::new(libc::ENOTSUP, &cause));
+        let remote: SocketAddrV4 = match (remote, self.connected) {
+            (Some(remote), _) => unwrap_socketaddr(remote)?,
+            (None, Some(connected)) => connected,
+            (None, None) => {
+                let cause: String = format!("udp socket requires a remote address");
+                error!("pushto(): {}", &cause);
+                return Err(Fail::new(libc::EDESTADDRREQ, &cause));
+            },
         };
         // Check that the socket is bound.
         let port: u16 = if let Some(addr) = self.local() {
@@ -86,21 +171,36 @@ impl SharedUdpSocket {
             error!("pushto(): {}", &cause);
             return Err(Fail::new(libc::ENOTSUP, &cause));
         };
+        // If the socket is paced, wait until the pacer lets this packet go out.
+        loop {
+            let now: Instant = self.runtime.get_now();
+            let len: usize = buf.len();
+            match self.pacer.as_mut().map(|pacer| pacer.try_consume(now, len)) {
+                Some(Err(delay)) => yield_with_timeout(delay).await,
+                Some(Ok(())) | None => break,
+            }
+        }
         let udp_header: UdpHeader = UdpHeader::new(port, remote.port());
         debug!("UDP send {:?}", udp_header);
-        udp_header.serialize_and_attach(&mut buf, &self.local_ipv4_addr, remote.ip(), self.checksum_offload);
+        let local_ipv4_addr: Ipv4Addr = self.layer3_endpoint.get_local_addr();
+        udp_header.serialize_and_attach(&mut buf, &local_ipv4_addr, remote.ip(), self.checksum_offload);
         // Send the packet to the lower layer.
+        let priority: PriorityClass = self.priority;
         self.layer3_endpoint
-            .transmit_udp_packet_blocking(remote.ip().clone(), buf)
+            .transmit_udp_packet_blocking(remote.ip().clone(), priority, buf)
             .await
     }
 
-    pub async fn pop(&mut self, size: usize) -> Result<(SocketAddrV4, DemiBuffer), Fail> {
+    pub async fn pop(&mut self, size: usize, flags: PopFlags) -> Result<(SocketAddrV4, DemiBuffer), Fail> {
         loop {
             match self.recv_queue.pop(None).await {
                 Ok(msg) => {
                     let remote: SocketAddrV4 = msg.0;
                     let mut buf: DemiBuffer = msg.1;
+                    // A peek leaves the datagram in place for the next pop.
+                    if flags.peek {
+                        self.recv_queue.push_front((remote, buf.clone()));
+                    }
                     // We got more bytes than expected, so we trim the buffer.
                     if size < buf.len() {
                         buf.trim(size - buf.len())?;
@@ -113,11 +213,26 @@ impl SharedUdpSocket {
     }
 
     pub fn receive(&mut self, remote: SocketAddrV4, buf: DemiBuffer) {
+        // Connected sockets only take datagrams from their peer.
+        if let Some(connected) = self.connected {
+            if connected != remote {
+                warn!(
+                    "dropping packet: socket is connected to {:?}, not {:?}",
+                    connected, remote
+                );
+                return;
+            }
+        }
         // Push data to the receiver-side shared queue. This will cause the
         // associated pool operation to be ready.
         self.recv_queue.push((remote, buf));
     }
 
+    /// Returns the number of bytes in the datagrams that are waiting to be popped.
+    pub fn get_buffered_bytes(&self) -> usize {
+        self.recv_queue.get_values().map(|(_, buf)| buf.len()).sum()
+    }
+
     pub fn is_bound(&self) -> bool {
         self.bound.is_some()
     }
@@ -128,9 +243,30 @@ impl SharedUdpSocket {
     }
 
     /// Returns the remote address to which the target queue is connected to.
-    /// TODO: Add later if we support connected UDP sockets.
     pub fn remote(&self) -> Option<SocketAddrV4> {
-        None
+        self.connected
+    }
+
+    /// Describes the socket for a state dump. Unless `scrub_payload` is set, this includes the datagrams that are
+    /// waiting to be read.
+    pub fn dump_state(&self, scrub_payload: bool) -> Value {
+        let recv_queue: Vec<Value> = self
+            .recv_queue
+            .get_values()
+            .map(|(remote, buf)| {
+                json!({
+                    "remote": remote.to_string(),
+                    "payload": dump_payload(buf, scrub_payload),
+                })
+            })
+            .collect();
+        json!({
+            "protocol": "udp",
+            "local": self.bound.map(|addr| addr.to_string()),
+            "remote": self.connected.map(|addr| addr.to_string()),
+            "priority": format!("{:?}", self.priority),
+            "recv_queue": recv_queue,
+        })
     }
 }
 
@@ -11,11 +11,13 @@ use crate::{
     },
     runtime::{
         memory::DemiBuffer,
+        network::socket::option::{Pacing, PacingRate, PriorityClass},
         queue::{OperationResult, QDesc, QToken},
     },
+    SocketOption,
 };
 use ::anyhow::Result;
-use ::libc::EBADF;
+use ::libc::{EADDRINUSE, EBADF};
 use ::std::{
     net::{Ipv4Addr, SocketAddrV4},
     time::{Duration, Instant},
@@ -96,7 +98,7 @@ fn udp_push_pop() -> Result<()> {
 
     let (remote_addr, received_buf): (Option<SocketAddrV4>, DemiBuffer) =
         match carrie.wait(carrie_qt, TIMEOUT_SECONDS)? {
-            (_, OperationResult::Pop(addr, buf)) => (addr, buf),
+            (_, OperationResult::Pop(addr, buf, _)) => (addr, buf),
             _ => anyhow::bail!("Pop failed"),
         };
     assert_eq!(remote_addr.unwrap(), bob_addr);
@@ -109,6 +111,52 @@ fn udp_push_pop() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn udp_pop_buffered_bytes() -> Result<()> {
+    let now: Instant = Instant::now();
+
+    // Setup Bob.
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let bob_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::BOB_IPV4, 80);
+    let bob_fd: QDesc = bob.udp_socket()?;
+    bob.udp_bind(bob_fd, bob_addr)?;
+
+    // Setup Carrie.
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let carrie_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+    let carrie_fd: QDesc = carrie.udp_socket()?;
+    carrie.udp_bind(carrie_fd, carrie_addr)?;
+
+    // Send two datagrams to Carrie.
+    for len in [32, 16] {
+        let buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&vec![0x5a; len][..], MAX_HEADER_SIZE)?;
+        let bob_qt: QToken = bob.udp_pushto(bob_fd, buf, carrie_addr)?;
+        match bob.wait(bob_qt, TIMEOUT_SECONDS)? {
+            (_, OperationResult::Push) => {},
+            _ => anyhow::bail!("Push failed"),
+        };
+        carrie.push_frame(bob.pop_frame());
+    }
+
+    // Each pop reports the bytes of the datagrams that are still waiting.
+    for (len, buffered) in [(32, 16), (16, 0)] {
+        let carrie_qt: QToken = carrie.udp_pop(carrie_fd)?;
+        match carrie.wait(carrie_qt, TIMEOUT_SECONDS)? {
+            (_, OperationResult::Pop(_, buf, remaining)) => {
+                crate::ensure_eq!(buf.len(), len);
+                crate::ensure_eq!(remaining, buffered);
+            },
+            _ => anyhow::bail!("Pop failed"),
+        };
+    }
+
+    // Close peers.
+    bob.udp_close(bob_fd)?;
+    carrie.udp_close(carrie_fd)?;
+
+    Ok(())
+}
+
 //======================================================================================================================
 // Push & Pop
 //======================================================================================================================
@@ -147,7 +195,7 @@ fn udp_push_pop_wildcard_address() -> Result<()> {
     let carrie_qt: QToken = carrie.udp_pop(carrie_fd)?;
     let (remote_addr, received_buf): (Option<SocketAddrV4>, DemiBuffer) =
         match carrie.wait(carrie_qt, TIMEOUT_SECONDS)? {
-            (_, OperationResult::Pop(addr, buf)) => (addr, buf),
+            (_, OperationResult::Pop(addr, buf, _)) => (addr, buf),
             _ => anyhow::bail!("Pop failed"),
         };
     assert_eq!(remote_addr.unwrap(), bob_addr);
@@ -159,6 +207,146 @@ fn udp_push_pop_wildcard_address() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn udp_push_pop_specific_over_wildcard_address() -> Result<()> {
+    let now: Instant = Instant::now();
+
+    // Setup Bob.
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let bob_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::BOB_IPV4, 80);
+    let bob_fd: QDesc = bob.udp_socket()?;
+    bob.udp_bind(bob_fd, bob_addr)?;
+
+    // Setup Carrie with one socket bound to the wildcard address and another one bound to her address, on the same port.
+    let mut carrie: SharedEngine = test_helpers::new_carrie(now);
+    let carrie_port: u16 = 80;
+    let carrie_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, carrie_port);
+    let wildcard_fd: QDesc = carrie.udp_socket()?;
+    carrie.udp_bind(wildcard_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, carrie_port))?;
+    let specific_fd: QDesc = carrie.udp_socket()?;
+    carrie.udp_bind(specific_fd, carrie_addr)?;
+
+    // Binding either address again fails.
+    let other_fd: QDesc = carrie.udp_socket()?;
+    match carrie.udp_bind(other_fd, carrie_addr) {
+        Err(e) if e.errno == EADDRINUSE => (),
+        _ => anyhow::bail!("bind() of an address that is in use should fail"),
+    }
+    match carrie.udp_bind(other_fd, SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, carrie_port)) {
+        Err(e) if e.errno == EADDRINUSE => (),
+        _ => anyhow::bail!("bind() of an address that is in use should fail"),
+    }
+    let wildcard_qt: QToken = carrie.udp_pop(wildcard_fd)?;
+    let specific_qt: QToken = carrie.udp_pop(specific_fd)?;
+
+    // The socket that is bound to Carrie's address gets the datagram.
+    let buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&vec![0x5a; 32][..], MAX_HEADER_SIZE)
+        .expect("slice should fit in DemiBuffer");
+    let qt: QToken = bob.udp_pushto(bob_fd, buf.clone(), carrie_addr)?;
+    match bob.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => {},
+        _ => anyhow::bail!("Push failed"),
+    };
+    carrie.push_frame(bob.pop_frame());
+    match carrie.wait(specific_qt, TIMEOUT_SECONDS)? {
+        (qd, OperationResult::Pop(_, received_buf, _)) if qd == specific_fd => assert_eq!(received_buf[..], buf[..]),
+        _ => anyhow::bail!("Pop failed"),
+    };
+
+    // Once it is closed, the socket that is bound to the wildcard address gets them.
+    carrie.udp_close(specific_fd)?;
+    let qt: QToken = bob.udp_pushto(bob_fd, buf.clone(), carrie_addr)?;
+    match bob.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => {},
+        _ => anyhow::bail!("Push failed"),
+    };
+    carrie.push_frame(bob.pop_frame());
+    match carrie.wait(wildcard_qt, TIMEOUT_SECONDS)? {
+        (qd, OperationResult::Pop(_, received_buf, _)) if qd == wildcard_fd => assert_eq!(received_buf[..], buf[..]),
+        _ => anyhow::bail!("Pop failed"),
+    };
+
+    // Close peers.
+    bob.udp_close(bob_fd)?;
+    carrie.udp_close(wildcard_fd)?;
+    carrie.udp_close(other_fd)?;
+
+    Ok(())
+}
+
+#[test]
+fn udp_push_paced() -> Result<()> {
+    let mut now: Instant = Instant::now();
+
+    // Setup Bob and pace his socket to one packet per second.
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let bob_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::BOB_IPV4, 80);
+    let bob_fd: QDesc = bob.udp_socket()?;
+    bob.udp_bind(bob_fd, bob_addr)?;
+    let pacing: Pacing = Pacing {
+        rate: PacingRate::PacketsPerSecond(1),
+        burst: 1,
+    };
+    bob.set_socket_option(bob_fd, SocketOption::Pacing(Some(pacing)))?;
+    let carrie_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+
+    // The first packet goes out right away.
+    let buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&vec![0x5a; 32][..], MAX_HEADER_SIZE)
+        .expect("slice should fit in DemiBuffer");
+    let qt: QToken = bob.udp_pushto(bob_fd, buf.clone(), carrie_addr)?;
+    match bob.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => {},
+        _ => anyhow::bail!("Push failed"),
+    };
+    crate::ensure_eq!(bob.pop_all_frames().len(), 1);
+
+    // The second packet waits for the pacer.
+    let qt: QToken = bob.udp_pushto(bob_fd, buf.clone(), carrie_addr)?;
+    bob.poll();
+    crate::ensure_eq!(bob.pop_all_frames().len(), 0);
+
+    now += Duration::from_secs(1);
+    bob.advance_clock(now);
+    match bob.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => {},
+        _ => anyhow::bail!("Push failed"),
+    };
+    crate::ensure_eq!(bob.pop_all_frames().len(), 1);
+
+    bob.udp_close(bob_fd)?;
+
+    Ok(())
+}
+
+#[test]
+fn udp_push_priority() -> Result<()> {
+    let now: Instant = Instant::now();
+
+    // Setup Bob and mark his socket as latency critical.
+    let mut bob: SharedEngine = test_helpers::new_bob(now);
+    let bob_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::BOB_IPV4, 80);
+    let bob_fd: QDesc = bob.udp_socket()?;
+    bob.udp_bind(bob_fd, bob_addr)?;
+    bob.set_socket_option(bob_fd, SocketOption::Priority(PriorityClass::LatencyCritical))?;
+    let carrie_addr: SocketAddrV4 = SocketAddrV4::new(test_helpers::CARRIE_IPV4, 80);
+
+    let buf: DemiBuffer = DemiBuffer::from_slice_with_headroom(&vec![0x5a; 32][..], MAX_HEADER_SIZE)
+        .expect("slice should fit in DemiBuffer");
+    let qt: QToken = bob.udp_pushto(bob_fd, buf, carrie_addr)?;
+    match bob.wait(qt, TIMEOUT_SECONDS)? {
+        (_, OperationResult::Push) => {},
+        _ => anyhow::bail!("Push failed"),
+    };
+
+    // The packet carries the DSCP code point of its class, right after the Ethernet header.
+    let frame: DemiBuffer = bob.pop_frame();
+    crate::ensure_eq!(frame[15] >> 2, PriorityClass::LatencyCritical.dscp());
+
+    bob.udp_close(bob_fd)?;
+
+    Ok(())
+}
+
 //======================================================================================================================
 // Ping Pong
 //======================================================================================================================
@@ -198,7 +386,7 @@ fn udp_ping_pong() -> Result<()> {
 
     let (remote_addr, received_buf_a): (Option<SocketAddrV4>, DemiBuffer) =
         match carrie.wait(carrie_qt, TIMEOUT_SECONDS)? {
-            (_, OperationResult::Pop(addr, buf)) => (addr, buf),
+            (_, OperationResult::Pop(addr, buf, _)) => (addr, buf),
             _ => anyhow::bail!("Pop failed"),
         };
     assert_eq!(remote_addr.unwrap(), bob_addr);
@@ -221,7 +409,7 @@ fn udp_ping_pong() -> Result<()> {
     bob.push_frame(carrie.pop_frame());
     let bob_qt: QToken = bob.udp_pop(bob_fd)?;
     let (remote_addr, received_buf_b): (Option<SocketAddrV4>, DemiBuffer) = match bob.wait(bob_qt, TIMEOUT_SECONDS)? {
-        (_, OperationResult::Pop(addr, buf)) => (addr, buf),
+        (_, OperationResult::Pop(addr, buf, _)) => (addr, buf),
         _ => anyhow::bail!("Pop failed"),
     };
     assert_eq!(remote_addr.unwrap(), carrie_addr);
@@ -332,7 +520,7 @@ fn udp_loop2_push_pop() -> Result<()> {
         let carrie_qt: QToken = carrie.udp_pop(carrie_fd)?;
         let (remote_addr, received_buf): (Option<SocketAddrV4>, DemiBuffer) =
             match carrie.wait(carrie_qt, TIMEOUT_SECONDS)? {
-                (_, OperationResult::Pop(addr, buf)) => (addr, buf),
+                (_, OperationResult::Pop(addr, buf, _)) => (addr, buf),
                 _ => anyhow::bail!("Pop failed"),
             };
         assert_eq!(remote_addr.unwrap(), bob_addr);
@@ -397,7 +585,7 @@ fn udp_loop2_ping_pong() -> Result<()> {
         let carrie_qt: QToken = carrie.udp_pop(carrie_fd)?;
         let (remote_addr, received_buf_a): (Option<SocketAddrV4>, DemiBuffer) =
             match carrie.wait(carrie_qt, TIMEOUT_SECONDS)? {
-                (_, OperationResult::Pop(addr, buf)) => (addr, buf),
+                (_, OperationResult::Pop(addr, buf, _)) => (addr, buf),
                 _ => anyhow::bail!("Pop failed"),
             };
         assert_eq!(remote_addr.unwrap(), bob_addr);
@@ -421,7 +609,7 @@ fn udp_loop2_ping_pong() -> Result<()> {
         let bob_qt: QToken = bob.udp_pop(bob_fd)?;
         let (remote_addr, received_buf_b): (Option<SocketAddrV4>, DemiBuffer) =
             match bob.wait(bob_qt, TIMEOUT_SECONDS)? {
-                (_, OperationResult::Pop(addr, buf)) => (addr, buf),
+                (_, OperationResult::Pop(addr, buf, _)) => (addr, buf),
                 _ => anyhow::bail!("Pop failed"),
             };
         assert_eq!(remote_addr.unwrap(), carrie_addr);
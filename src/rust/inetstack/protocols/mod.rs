@@ -14,6 +14,13 @@ pub mod layer4;
 // Imports
 //======================================================================================================================
 
+#[cfg(not(test))]
+use ::rand::rngs::OsRng;
+use ::rand::{
+    distributions::{Distribution, Standard},
+    prelude::SmallRng,
+    Rng,
+};
 use ::std::slice::ChunksExact;
 
 //======================================================================================================================
@@ -69,3 +76,23 @@ pub fn fold16(mut state: u32) -> u16 {
     }
     !state as u16
 }
+
+/// Draws a secret, like the key of a hash whose output others must not be able to predict, from the entropy of the
+/// operating system. Stacks are seeded with the same value, so drawing from their `rng` would give every one of them
+/// the same secret.
+#[cfg(not(test))]
+pub fn draw_secret<T>(_rng: &mut SmallRng) -> T
+where
+    Standard: Distribution<T>,
+{
+    OsRng.gen()
+}
+
+/// Draws a secret from `rng`, so that unit tests are reproducible.
+#[cfg(test)]
+pub fn draw_secret<T>(rng: &mut SmallRng) -> T
+where
+    Standard: Distribution<T>,
+{
+    rng.gen()
+}
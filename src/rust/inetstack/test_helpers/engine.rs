@@ -4,18 +4,23 @@
 use crate::{
     demi_sgarray_t,
     demikernel::{config::Config, libos::network::libos::SharedNetworkLibOS},
-    inetstack::{test_helpers::SharedTestPhysicalLayer, SharedInetStack},
+    inetstack::{
+        protocols::layer4::tcp::handoff::{ConnectionHandoff, HandoffSender},
+        test_helpers::SharedTestPhysicalLayer,
+        SharedInetStack,
+    },
     runtime::{
         fail::Fail,
         memory::{DemiBuffer, MemoryRuntime},
         network::types::MacAddress,
         OperationResult, QDesc, QToken, SharedDemiRuntime, SharedObject,
     },
+    PopFlags, SocketOption,
 };
 use ::socket2::{Domain, Protocol, Type};
 use ::std::{
     collections::{HashMap, VecDeque},
-    net::{Ipv4Addr, SocketAddrV4},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     ops::{Deref, DerefMut},
     time::{Duration, Instant},
 };
@@ -75,7 +80,7 @@ impl SharedEngine {
     }
 
     pub fn udp_pop(&mut self, qd: QDesc) -> Result<QToken, Fail> {
-        self.libos.pop(qd, None)
+        self.libos.pop(qd, None, PopFlags::default())
     }
 
     pub fn udp_socket(&mut self) -> Result<QDesc, Fail> {
@@ -86,6 +91,22 @@ impl SharedEngine {
         self.libos.bind(socket_fd, endpoint.into())
     }
 
+    pub fn set_socket_option(&mut self, socket_fd: QDesc, option: SocketOption) -> Result<(), Fail> {
+        self.libos.set_socket_option(socket_fd, option)
+    }
+
+    pub fn get_socket_option(&mut self, socket_fd: QDesc, option: SocketOption) -> Result<SocketOption, Fail> {
+        self.libos.get_socket_option(socket_fd, option)
+    }
+
+    pub fn get_last_error(&mut self, socket_fd: QDesc) -> Result<Option<Fail>, Fail> {
+        self.libos.get_last_error(socket_fd)
+    }
+
+    pub fn get_addresses(&self, socket_fd: QDesc) -> Result<(Option<SocketAddr>, Option<SocketAddr>), Fail> {
+        self.libos.get_addresses(socket_fd)
+    }
+
     pub fn udp_close(&mut self, socket_fd: QDesc) -> Result<(), Fail> {
         let qt = self.libos.async_close(socket_fd)?;
         match self.wait(qt, TIMEOUT_SECONDS)? {
@@ -115,8 +136,34 @@ impl SharedEngine {
         self.libos.push(socket_fd, &data)
     }
 
+    pub fn tcp_writable(&mut self, socket_fd: QDesc, min_bytes: usize) -> Result<QToken, Fail> {
+        self.libos.writable(socket_fd, min_bytes)
+    }
+
     pub fn tcp_pop(&mut self, socket_fd: QDesc) -> Result<QToken, Fail> {
-        self.libos.pop(socket_fd, None)
+        self.libos.pop(socket_fd, None, PopFlags::default())
+    }
+
+    pub fn tcp_peek(&mut self, socket_fd: QDesc) -> Result<QToken, Fail> {
+        self.libos.pop(
+            socket_fd,
+            None,
+            PopFlags {
+                peek: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn tcp_pop_all(&mut self, socket_fd: QDesc, size: usize) -> Result<QToken, Fail> {
+        self.libos.pop(
+            socket_fd,
+            Some(size),
+            PopFlags {
+                wait_all: true,
+                ..Default::default()
+            },
+        )
     }
 
     pub fn tcp_async_close(&mut self, socket_fd: QDesc) -> Result<QToken, Fail> {
@@ -127,6 +174,18 @@ impl SharedEngine {
         self.libos.listen(socket_fd, backlog)
     }
 
+    pub fn tcp_set_handoff(&mut self, socket_fd: QDesc, handoff: HandoffSender) -> Result<(), Fail> {
+        self.libos.set_tcp_handoff(socket_fd, handoff)
+    }
+
+    pub fn tcp_adopt(&mut self, handoff: ConnectionHandoff) -> Result<QDesc, Fail> {
+        self.libos.adopt_tcp_connection(handoff)
+    }
+
+    pub fn dump_state(&mut self, scrub_payload: bool) -> Result<String, Fail> {
+        self.libos.dump_state(scrub_payload)
+    }
+
     pub async fn arp_query(self, ipv4_addr: Ipv4Addr) -> Result<MacAddress, Fail> {
         self.libos.get_transport().arp_query(ipv4_addr).await
     }
@@ -135,8 +194,11 @@ impl SharedEngine {
         self.libos.get_transport().export_arp_cache()
     }
 
+    /// Polls every task that is ready. A single poll of the runtime skips tasks that became ready in the group that it
+    /// started with (e.g. a coroutine that we just inserted), so we poll twice.
     pub fn poll(&self) {
-        self.libos.get_runtime().poll()
+        self.libos.get_runtime().poll();
+        self.libos.get_runtime().poll();
     }
 
     pub fn wait(&self, qt: QToken, timeout: Duration) -> Result<(QDesc, OperationResult), Fail> {
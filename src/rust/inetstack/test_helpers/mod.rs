@@ -20,8 +20,19 @@ pub const BOB_IPV4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
 pub const CARRIE_MAC: MacAddress = MacAddress::new([0xef, 0xcd, 0xab, 0x89, 0x67, 0x45]);
 pub const CARRIE_IPV4: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 3);
 pub const ALICE_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/alice.yaml";
+pub const ALICE_PROBE_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/alice_probe.yaml";
 pub const BOB_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/bob.yaml";
+/// Bob reaches a foreign address through Carrie's link address, as if Carrie was his gateway.
+pub const BOB_GATEWAY_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/bob_gateway.yaml";
+/// Bob paces his segments and uses CUBIC, starting from a window of two segments.
+pub const BOB_PACED_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/bob_paced.yaml";
 pub const CARRIE_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/carrie.yaml";
+/// Carrie acknowledges every fourth segment that she receives.
+pub const CARRIE_ACK_STRETCH_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/carrie_ack_stretch.yaml";
+/// Carrie answers connections through the link address that their SYN came from.
+pub const CARRIE_LEARN_LINK_ADDR_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/carrie_learn_link_addr.yaml";
+/// Carrie intercepts connections to the 10.0.0.0/8 prefix.
+pub const CARRIE_TRANSPARENT_CONFIG_PATH: &str = "./src/rust/inetstack/test_helpers/carrie_transparent.yaml";
 
 //======================================================================================================================
 // Standalone Functions
@@ -6,21 +6,19 @@
 //======================================================================================================================
 
 use crate::{
-    demi_sgarray_t, demi_sgaseg_t,
+    demi_sgarray_t,
     inetstack::protocols::{layer1::PhysicalLayer, MAX_HEADER_SIZE},
     runtime::{
         fail::Fail,
         logging,
-        memory::{DemiBuffer, MemoryRuntime},
+        memory::{sgarray_from_buffer, DemiBuffer, MemoryRuntime},
         network::consts::RECEIVE_BATCH_SIZE,
         SharedDemiRuntime, SharedObject,
     },
 };
 use ::arrayvec::ArrayVec;
-use ::libc::c_void;
 use ::std::{
     collections::VecDeque,
-    mem,
     ops::{Deref, DerefMut},
     time::Instant,
 };
@@ -93,7 +91,8 @@ impl PhysicalLayer for SharedTestPhysicalLayer {
         // For this test harness, we 2^16 bytes (u16::MAX) as our limit.
         assert!(pkt.len() < u16::MAX as usize);
 
-        self.outgoing.push_back(pkt);
+        // Like a NIC, deliver a buffer chain as a single frame.
+        self.outgoing.push_back(pkt.coalesce(0)?);
         Ok(())
     }
 
@@ -144,19 +143,7 @@ impl MemoryRuntime for SharedTestPhysicalLayer {
         // First allocate the underlying DemiBuffer.
         let buf: DemiBuffer = DemiBuffer::new_with_headroom(size as u16, MAX_HEADER_SIZE as u16);
 
-        // Create a scatter-gather segment to expose the DemiBuffer to the user.
-        let data: *const u8 = buf.as_ptr();
-        let sga_seg: demi_sgaseg_t = demi_sgaseg_t {
-            sgaseg_buf: data as *mut c_void,
-            sgaseg_len: size as u32,
-        };
-
         // Create and return a new scatter-gather array (which inherits the DemiBuffer's reference).
-        Ok(demi_sgarray_t {
-            sga_buf: buf.into_raw().as_ptr() as *mut c_void,
-            sga_numsegs: 1,
-            sga_segs: [sga_seg],
-            sga_addr: unsafe { mem::zeroed() },
-        })
+        sgarray_from_buffer(buf)
     }
 }
@@ -15,7 +15,6 @@ pub mod inetstack;
 mod pal;
 pub mod runtime;
 
-#[cfg(feature = "profiler")]
 pub mod perftools;
 
 extern crate test;
@@ -32,10 +31,22 @@ mod catpowder;
 #[cfg(all(feature = "catnap-libos"))]
 mod catnap;
 
-pub use self::demikernel::libos::{name::LibOSName, LibOS};
+#[cfg(feature = "catnip-libos")]
+pub use self::catnip::runtime::diagnostics::{
+    CaptureDirection, CaptureRecord, DiagnosticsClient, PortStats, MAX_CAPTURE_RECORD_SIZE, MAX_SNAPLEN,
+};
+pub use self::demikernel::{
+    balancer::{BackendState, LoadBalancer},
+    libos::{name::LibOSName, LibOS},
+};
 pub use crate::runtime::{
     network::{
-        socket::option::SocketOption,
+        socket::{
+            option::{
+                DelayedAck, Pacing, PacingRate, PortRange, QuickAck, SocketOption, SynBackoff, TcpInfo, TcpState,
+            },
+            PopFlags,
+        },
         types::{MacAddress, Port16},
     },
     types::{demi_sgarray_t, demi_sgaseg_t},
@@ -210,6 +221,15 @@ macro_rules! timer {
     };
 }
 
+/// Accounts the cycles spent until the end of the enclosing block to a stage of the packet processing path. This does
+/// nothing unless stage timing is turned on at runtime.
+#[macro_export]
+macro_rules! stage_timer {
+    ($stage:expr) => {
+        let _stage_guard = $crate::perftools::stages::StageGuard::enter($stage);
+    };
+}
+
 #[cfg(feature = "profiler")]
 #[macro_export]
 macro_rules! async_timer {
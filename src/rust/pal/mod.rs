@@ -63,6 +63,42 @@ pub const SOL_SOCKET: i32 = WinSock::SOL_SOCKET;
 #[cfg(target_os = "windows")]
 pub const SO_LINGER: i32 = WinSock::SO_LINGER;
 
+#[cfg(target_os = "windows")]
+pub const SO_KEEPALIVE: i32 = WinSock::SO_KEEPALIVE;
+
+#[cfg(target_os = "windows")]
+pub const SO_ERROR: i32 = WinSock::SO_ERROR;
+
+#[cfg(target_os = "windows")]
+pub const SO_RCVLOWAT: i32 = WinSock::SO_RCVLOWAT;
+
+#[cfg(target_os = "windows")]
+pub const IPPROTO_IP: i32 = WinSock::IPPROTO_IP.0;
+
+#[cfg(target_os = "windows")]
+pub const IPPROTO_TCP: i32 = WinSock::IPPROTO_TCP.0;
+
+#[cfg(target_os = "windows")]
+pub const TCP_KEEPIDLE: i32 = WinSock::TCP_KEEPIDLE;
+
+#[cfg(target_os = "windows")]
+pub const TCP_KEEPINTVL: i32 = WinSock::TCP_KEEPINTVL;
+
+#[cfg(target_os = "windows")]
+pub const TCP_KEEPCNT: i32 = WinSock::TCP_KEEPCNT;
+
+#[cfg(target_os = "windows")]
+pub const SHUT_RD: i32 = WinSock::SD_RECEIVE.0;
+
+#[cfg(target_os = "windows")]
+pub const SHUT_WR: i32 = WinSock::SD_SEND.0;
+
+#[cfg(target_os = "windows")]
+pub const SHUT_RDWR: i32 = WinSock::SD_BOTH.0;
+
+#[cfg(target_os = "windows")]
+pub const MSG_PEEK: i32 = WinSock::MSG_PEEK.0;
+
 //======================================================================================================================
 // Linux constants
 //======================================================================================================================
@@ -82,6 +118,60 @@ pub const SOL_SOCKET: i32 = libc::SOL_SOCKET;
 #[cfg(target_os = "linux")]
 pub const SO_LINGER: i32 = libc::SO_LINGER;
 
+#[cfg(target_os = "linux")]
+pub const SO_KEEPALIVE: i32 = libc::SO_KEEPALIVE;
+
+#[cfg(target_os = "linux")]
+pub const SO_ERROR: i32 = libc::SO_ERROR;
+
+#[cfg(target_os = "linux")]
+pub const SO_RCVLOWAT: i32 = libc::SO_RCVLOWAT;
+
+#[cfg(target_os = "linux")]
+pub const IPPROTO_IP: i32 = libc::IPPROTO_IP;
+
+#[cfg(target_os = "linux")]
+pub const IP_TRANSPARENT: i32 = libc::IP_TRANSPARENT;
+
+#[cfg(target_os = "linux")]
+pub const IPPROTO_TCP: i32 = libc::IPPROTO_TCP;
+
+#[cfg(target_os = "linux")]
+pub const TCP_KEEPIDLE: i32 = libc::TCP_KEEPIDLE;
+
+#[cfg(target_os = "linux")]
+pub const TCP_KEEPINTVL: i32 = libc::TCP_KEEPINTVL;
+
+#[cfg(target_os = "linux")]
+pub const TCP_KEEPCNT: i32 = libc::TCP_KEEPCNT;
+
+#[cfg(target_os = "linux")]
+pub const TCP_QUICKACK: i32 = libc::TCP_QUICKACK;
+
+#[cfg(target_os = "linux")]
+pub const TCP_INFO: i32 = libc::TCP_INFO;
+
+#[cfg(target_os = "linux")]
+pub const TCP_CORK: i32 = libc::TCP_CORK;
+
+#[cfg(target_os = "linux")]
+pub const TCP_CONGESTION: i32 = libc::TCP_CONGESTION;
+
+#[cfg(target_os = "linux")]
+pub const TCP_SYNCNT: i32 = libc::TCP_SYNCNT;
+
+#[cfg(target_os = "linux")]
+pub const SHUT_RD: i32 = libc::SHUT_RD;
+
+#[cfg(target_os = "linux")]
+pub const SHUT_WR: i32 = libc::SHUT_WR;
+
+#[cfg(target_os = "linux")]
+pub const SHUT_RDWR: i32 = libc::SHUT_RDWR;
+
+#[cfg(target_os = "linux")]
+pub const MSG_PEEK: i32 = libc::MSG_PEEK;
+
 //======================================================================================================================
 // Windows data structures
 //======================================================================================================================
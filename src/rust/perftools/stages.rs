@@ -0,0 +1,248 @@
+// Copyright(c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! This module provides lightweight per-stage cycle counters for the packet processing path. Unlike the profiler,
+//! these counters are always compiled in and may be turned on and off at runtime, so they can be used on production
+//! builds to see where CPU time goes at line rate.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use ::std::{
+    cell::{Cell, RefCell},
+    io, thread,
+};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+thread_local!(
+    /// Whether stage timing is turned on for this thread.
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    /// Global thread-local instance of the stage counters.
+    static COUNTERS: RefCell<StageCounters> = RefCell::new(StageCounters::default());
+);
+
+/// Stages of the packet processing path that we keep track of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// Receiving a batch of frames from the network driver.
+    DriverPoll,
+    /// Parsing and validating IPv4 headers.
+    Layer3Parse,
+    /// Processing TCP segments.
+    TcpProcess,
+    /// Turning completed operations into results for the application.
+    AppCompletion,
+}
+
+/// Cycles spent in a single stage.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageCounter {
+    /// How often has this stage been entered?
+    pub num_calls: u64,
+    /// In total, how many cycles have been spent in this stage?
+    pub cycles: u64,
+}
+
+/// Cycles spent in each stage, along with the number of packets that went through them.
+#[derive(Clone, Debug, Default)]
+pub struct StageCounters {
+    pub stages: [StageCounter; Stage::COUNT],
+    pub num_packets: u64,
+}
+
+/// A guard that is created when entering a stage and dropped when leaving it. If stage timing was off when the guard
+/// was created, dropping it does nothing.
+pub struct StageGuard {
+    stage: Stage,
+    enter_time: Option<u64>,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+/// Turns stage timing on or off for the calling thread. Counters that were already collected are kept.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.with(|e| e.set(enabled));
+}
+
+/// Tells whether stage timing is turned on for the calling thread.
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+/// Accounts for packets that entered the packet processing path.
+#[inline]
+pub fn count_packets(num_packets: usize) {
+    if is_enabled() {
+        COUNTERS.with(|c| c.borrow_mut().num_packets += num_packets as u64);
+    }
+}
+
+/// Returns a snapshot of the counters of the calling thread.
+pub fn get_counters() -> StageCounters {
+    COUNTERS.with(|c| c.borrow().clone())
+}
+
+/// Resets the counters of the calling thread.
+pub fn reset() {
+    COUNTERS.with(|c| *c.borrow_mut() = StageCounters::default());
+}
+
+/// Prints the counters of the calling thread.
+pub fn write<W: io::Write>(out: &mut W) -> io::Result<()> {
+    COUNTERS.with(|c| c.borrow().write(out))
+}
+
+impl Stage {
+    /// Number of stages.
+    pub const COUNT: usize = 4;
+
+    /// Stages in the order in which packets go through them.
+    pub const ALL: [Stage; Stage::COUNT] = [
+        Stage::DriverPoll,
+        Stage::Layer3Parse,
+        Stage::TcpProcess,
+        Stage::AppCompletion,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::DriverPoll => "driver_poll",
+            Stage::Layer3Parse => "layer3_parse",
+            Stage::TcpProcess => "tcp_process",
+            Stage::AppCompletion => "app_completion",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl StageCounters {
+    pub fn get(&self, stage: Stage) -> &StageCounter {
+        &self.stages[stage.index()]
+    }
+
+    /// Average number of cycles spent in `stage` for each packet that entered the packet processing path.
+    pub fn cycles_per_packet(&self, stage: Stage) -> f64 {
+        match self.num_packets {
+            0 => 0.0,
+            num_packets => self.get(stage).cycles as f64 / num_packets as f64,
+        }
+    }
+
+    fn write<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        let thread_id: thread::ThreadId = thread::current().id();
+
+        writeln!(
+            out,
+            "thread_id,stage,num_calls,num_packets,cycles,cycles_per_call,cycles_per_packet"
+        )?;
+        for stage in Stage::ALL {
+            let counter: &StageCounter = self.get(stage);
+            let cycles_per_call: f64 = match counter.num_calls {
+                0 => 0.0,
+                num_calls => counter.cycles as f64 / num_calls as f64,
+            };
+            writeln!(
+                out,
+                "{:?},{},{},{},{},{:.2},{:.2}",
+                thread_id,
+                stage.name(),
+                counter.num_calls,
+                self.num_packets,
+                counter.cycles,
+                cycles_per_call,
+                self.cycles_per_packet(stage)
+            )?;
+        }
+
+        out.flush()
+    }
+}
+
+impl StageGuard {
+    #[inline]
+    pub fn enter(stage: Stage) -> Self {
+        let enter_time: Option<u64> = if is_enabled() {
+            let (now, _): (u64, u32) = unsafe { x86::time::rdtscp() };
+            Some(now)
+        } else {
+            None
+        };
+        Self { stage, enter_time }
+    }
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl Drop for StageGuard {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(enter_time) = self.enter_time {
+            let (now, _): (u64, u32) = unsafe { x86::time::rdtscp() };
+            COUNTERS.with(|c| {
+                let mut counters = c.borrow_mut();
+                let counter: &mut StageCounter = &mut counters.stages[self.stage.index()];
+                counter.num_calls += 1;
+                counter.cycles += now.saturating_sub(enter_time);
+            });
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{Stage, StageCounters};
+    use crate::{perftools::stages, stage_timer};
+    use ::anyhow::Result;
+
+    #[test]
+    fn stage_timer_only_counts_when_enabled() -> Result<()> {
+        stages::reset();
+
+        stages::set_enabled(false);
+        {
+            stage_timer!(Stage::DriverPoll);
+        }
+        stages::count_packets(4);
+        let counters: StageCounters = stages::get_counters();
+        crate::ensure_eq!(counters.get(Stage::DriverPoll).num_calls, 0);
+        crate::ensure_eq!(counters.num_packets, 0);
+
+        stages::set_enabled(true);
+        for _ in 0..3 {
+            stage_timer!(Stage::DriverPoll);
+        }
+        {
+            stage_timer!(Stage::TcpProcess);
+        }
+        stages::count_packets(4);
+        stages::set_enabled(false);
+
+        let counters: StageCounters = stages::get_counters();
+        crate::ensure_eq!(counters.get(Stage::DriverPoll).num_calls, 3);
+        crate::ensure_eq!(counters.get(Stage::TcpProcess).num_calls, 1);
+        crate::ensure_eq!(counters.get(Stage::Layer3Parse).num_calls, 0);
+        crate::ensure_eq!(counters.get(Stage::AppCompletion).num_calls, 0);
+        crate::ensure_eq!(counters.num_packets, 4);
+
+        stages::reset();
+        crate::ensure_eq!(stages::get_counters().get(Stage::DriverPoll).num_calls, 0);
+
+        Ok(())
+    }
+}
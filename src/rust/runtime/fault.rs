@@ -0,0 +1,227 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Fault injection for resilience testing. Parts of the stack fail on purpose with a configurable probability, so that
+//! applications can be tested against resource failures that are hard to provoke otherwise. Each module that faults
+//! are injected into is configured on its own. The faults follow a seeded random sequence, so that a run that went
+//! wrong can be reproduced.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::fail::Fail;
+use ::rand::{prelude::SmallRng, Rng, SeedableRng};
+use ::std::{cell::RefCell, thread, time::Duration};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Modules of the stack that faults can be injected into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultModule {
+    /// Buffer allocations fail with ENOMEM.
+    Memory,
+    /// Packet transmissions fail with ENOBUFS.
+    Network,
+    /// Polls of the scheduler are delayed.
+    Scheduler,
+}
+
+/// Configuration of the faults to inject.
+#[derive(Clone, Debug, Default)]
+pub struct FaultConfig {
+    /// Seed of the random sequence that decides which operations fail.
+    pub seed: u64,
+    /// Probability that a buffer allocation fails.
+    pub memory_failure_probability: f64,
+    /// Probability that a packet transmission fails.
+    pub network_failure_probability: f64,
+    /// Probability that a poll of the scheduler is delayed.
+    pub scheduler_delay_probability: f64,
+    /// How long a delayed poll of the scheduler waits before it runs.
+    pub scheduler_delay: Duration,
+}
+
+/// Injects faults as configured.
+struct FaultInjector {
+    config: FaultConfig,
+    rng: SmallRng,
+    // Number of faults injected so far, indexed by module.
+    num_injected: [u64; 3],
+}
+
+//======================================================================================================================
+// Thread Locals
+//======================================================================================================================
+
+thread_local! {
+    /// Faults are only injected on threads that enabled them, which are the threads that run a libOS.
+    static INJECTOR: RefCell<Option<FaultInjector>> = RefCell::new(None);
+}
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Starts injecting faults on the calling thread as [config] says. This replaces any previous configuration.
+pub fn configure(config: FaultConfig) -> Result<(), Fail> {
+    for (name, probability) in [
+        ("memory_failure_probability", config.memory_failure_probability),
+        ("network_failure_probability", config.network_failure_probability),
+        ("scheduler_delay_probability", config.scheduler_delay_probability),
+    ] {
+        if !(0.0..=1.0).contains(&probability) {
+            let cause: String = format!("invalid probability (name={:?}, probability={:?})", name, probability);
+            error!("configure(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+    }
+
+    warn!("configure(): injecting faults (config={:?})", config);
+    let rng: SmallRng = SmallRng::seed_from_u64(config.seed);
+    INJECTOR.with(|injector| {
+        *injector.borrow_mut() = Some(FaultInjector {
+            config,
+            rng,
+            num_injected: [0; 3],
+        })
+    });
+    Ok(())
+}
+
+/// Stops injecting faults on the calling thread.
+pub fn disable() {
+    INJECTOR.with(|injector| *injector.borrow_mut() = None);
+}
+
+/// Fails the operation of [module] that [caller] is about to carry out, if a fault is due. Delays of the scheduler are
+/// injected with [inject_delay] instead.
+pub fn inject_failure(module: FaultModule, caller: &str) -> Result<(), Fail> {
+    let errno: i32 = match module {
+        FaultModule::Memory => libc::ENOMEM,
+        FaultModule::Network => libc::ENOBUFS,
+        FaultModule::Scheduler => return Ok(()),
+    };
+    if !roll(module) {
+        return Ok(());
+    }
+    let cause: String = format!("injected fault (module={:?})", module);
+    debug!("{}(): {}", caller, cause);
+    Err(Fail::new(errno, &cause))
+}
+
+/// Delays the calling thread before the scheduler polls, if a delay is due.
+pub fn inject_delay() {
+    if roll(FaultModule::Scheduler) {
+        let delay: Duration = INJECTOR.with(|injector| {
+            injector
+                .borrow()
+                .as_ref()
+                .map_or(Duration::ZERO, |injector| injector.config.scheduler_delay)
+        });
+        debug!("inject_delay(): delaying poll (delay={:?})", delay);
+        thread::sleep(delay);
+    }
+}
+
+/// Returns the number of faults that were injected into [module] on the calling thread.
+pub fn get_injected_count(module: FaultModule) -> u64 {
+    INJECTOR.with(|injector| {
+        injector
+            .borrow()
+            .as_ref()
+            .map_or(0, |injector| injector.num_injected[module as usize])
+    })
+}
+
+/// Decides whether a fault is due in [module], and counts it if it is.
+fn roll(module: FaultModule) -> bool {
+    INJECTOR.with(|injector| match injector.borrow_mut().as_mut() {
+        Some(injector) => {
+            let probability: f64 = injector.config.get_probability(module);
+            if probability > 0.0 && injector.rng.gen_bool(probability) {
+                injector.num_injected[module as usize] += 1;
+                true
+            } else {
+                false
+            }
+        },
+        None => false,
+    })
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl FaultConfig {
+    /// Returns the probability that an operation of [module] is faulted.
+    fn get_probability(&self, module: FaultModule) -> f64 {
+        match module {
+            FaultModule::Memory => self.memory_failure_probability,
+            FaultModule::Network => self.network_failure_probability,
+            FaultModule::Scheduler => self.scheduler_delay_probability,
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::{configure, disable, get_injected_count, inject_failure, FaultConfig, FaultModule};
+    use ::anyhow::Result;
+
+    /// Counts how many of [num_operations] operations of [module] fail.
+    fn count_failures(module: FaultModule, num_operations: usize) -> usize {
+        (0..num_operations)
+            .filter(|_| inject_failure(module, "count_failures").is_err())
+            .count()
+    }
+
+    #[test]
+    fn test_inject_failure() -> Result<()> {
+        let config: FaultConfig = FaultConfig {
+            seed: 42,
+            memory_failure_probability: 1.0,
+            network_failure_probability: 0.5,
+            ..Default::default()
+        };
+        configure(config.clone())?;
+
+        // Every module fails as often as it is configured to.
+        match inject_failure(FaultModule::Memory, "test_inject_failure") {
+            Err(e) => crate::ensure_eq!(e.errno, libc::ENOMEM),
+            Ok(()) => anyhow::bail!("allocation should have failed"),
+        }
+        let num_failures: usize = count_failures(FaultModule::Network, 1000);
+        crate::ensure_eq!((400..600).contains(&num_failures), true);
+        crate::ensure_eq!(get_injected_count(FaultModule::Network), num_failures as u64);
+        crate::ensure_eq!(count_failures(FaultModule::Scheduler, 1000), 0);
+
+        // The same seed fails the same operations.
+        configure(config)?;
+        crate::ensure_eq!(count_failures(FaultModule::Memory, 1), 1);
+        crate::ensure_eq!(count_failures(FaultModule::Network, 1000), num_failures);
+
+        // Nothing fails once fault injection is disabled.
+        disable();
+        crate::ensure_eq!(count_failures(FaultModule::Memory, 1000), 0);
+        crate::ensure_eq!(get_injected_count(FaultModule::Memory), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_probability() -> Result<()> {
+        let config: FaultConfig = FaultConfig {
+            network_failure_probability: 1.5,
+            ..Default::default()
+        };
+        crate::ensure_eq!(configure(config).is_err(), true);
+        Ok(())
+    }
+}
@@ -8,3 +8,11 @@ pub const RECVBUF_SIZE_MAX: usize = 8192;
 /// Maximum size for a fixed-size pop operation.
 /// This is set to be at most `RECVBUF_SIZE_MAX`.
 pub const POP_SIZE_MAX: usize = RECVBUF_SIZE_MAX;
+
+/// Maximum number of operations in a linked chain.
+/// This keeps a single submission from filling up the scheduler.
+pub const LINKED_OPS_MAX: usize = 16;
+
+/// Maximum number of completions that a completion ring can hold.
+/// This keeps a ring within a few megabytes of memory.
+pub const COMPLETION_RING_CAPACITY_MAX: usize = 1 << 16;
@@ -5,8 +5,9 @@
 // Imports
 //======================================================================================================================
 
-use ::flexi_logger::{with_thread, Logger};
-use ::std::sync::Once;
+use crate::runtime::fail::Fail;
+use ::flexi_logger::{with_thread, Logger, LoggerHandle};
+use ::std::sync::{Mutex, Once};
 
 //======================================================================================================================
 // Static Variables
@@ -15,6 +16,9 @@ use ::std::sync::Once;
 /// Guardian to the logging initialize function.
 static INIT_LOG: Once = Once::new();
 
+/// Handle to the logger, which we need to change what gets logged at runtime.
+static LOGGER: Mutex<Option<LoggerHandle>> = Mutex::new(None);
+
 //======================================================================================================================
 // Standalone Functions
 //======================================================================================================================
@@ -22,6 +26,36 @@ static INIT_LOG: Once = Once::new();
 /// Initializes logging features.
 pub fn initialize() {
     INIT_LOG.call_once(|| {
-        Logger::try_with_env().unwrap().format(with_thread).start().unwrap();
+        let handle: LoggerHandle = Logger::try_with_env().unwrap().format(with_thread).start().unwrap();
+        if let Ok(mut logger) = LOGGER.lock() {
+            *logger = Some(handle);
+        }
     });
 }
+
+/// Replaces what gets logged with `spec`, which follows the same syntax as the RUST_LOG environment variable.
+pub fn set_log_spec(spec: &str) -> Result<(), Fail> {
+    let logger = match LOGGER.lock() {
+        Ok(logger) => logger,
+        Err(_) => {
+            let cause: String = format!("logger is poisoned");
+            error!("set_log_spec(): {}", cause);
+            return Err(Fail::new(libc::EIO, &cause));
+        },
+    };
+    match logger.as_ref() {
+        Some(handle) => match handle.parse_new_spec(spec) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let cause: String = format!("invalid log specification (spec={:?}): {:?}", spec, e);
+                error!("set_log_spec(): {}", cause);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        },
+        None => {
+            let cause: String = format!("logging is not initialized");
+            error!("set_log_spec(): {}", cause);
+            Err(Fail::new(libc::EAGAIN, &cause))
+        },
+    }
+}
@@ -13,10 +13,12 @@
 // Note: if compiled without the "libdpdk" feature defined, the DPDK-specific functionality won't be present.
 
 // Note on buffer chain support:
-// DPDK has a concept of MBuf chaining where multiple MBufs may be linked together to form a "packet".  While the
-// DemiBuffer routines for heap-allocated buffers also now support this functionality, it isn't yet exposed via the
-// DemiBuffer interface.
-// TODO: Expose buffer chain support once we have a solid use case.
+// DPDK has a concept of MBuf chaining where multiple MBufs may be linked together to form a "packet".  DemiBuffers of
+// either type support this as well: append() links buffers into a chain without copying their data, which is how
+// scatter-gather arrays with multiple segments are pushed.  The length of a chain covers all of its segments, and
+// split_front() and split_back() work across segment boundaries.  A chain cannot be dereferenced, as its data is not
+// contiguous, so code that looks at the data itself has to walk the chain with segments().  Code that only writes or
+// reads headers, which always live in the first segment, may use first_segment() and first_segment_mut() instead.
 
 // Note on intrusive queueing:
 // Since all DemiBuffer types keep the metadata for each "view" in a separate allocated region, they can be queued
@@ -45,12 +47,13 @@ use crate::{
 };
 use ::std::{
     alloc::{alloc, dealloc, handle_alloc_error, Layout},
+    iter::Peekable,
     marker::PhantomData,
     mem::{self, size_of, MaybeUninit},
     num::NonZeroUsize,
     ops::{BitOr, Deref, DerefMut},
     ptr::{self, null_mut, NonNull},
-    slice,
+    slice, vec,
 };
 use std::rc::Rc;
 
@@ -247,7 +250,7 @@ impl MetaData {
 // DemiBuffer type tags.
 // Since our MetaData structure is 64-byte aligned, the lower 6 bits of a pointer to it are guaranteed to be zero.
 // We currently only use the lower 2 of those bits to hold the type tag.
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 enum Tag {
     Heap = 1,
     #[cfg(feature = "libdpdk")]
@@ -301,6 +304,13 @@ pub struct DemiBuffer {
 // TODO: For now, this is here because some of our test infrastructure wants to send DemiBuffers to other threads.
 unsafe impl Send for DemiBuffer {}
 
+/// Iterator over the data of each segment in a `DemiBuffer` chain.
+#[derive(Clone)]
+pub struct Segments<'a> {
+    next: Option<NonNull<MetaData>>,
+    _phantom: PhantomData<&'a DemiBuffer>,
+}
+
 impl DemiBuffer {
     // ------------
     // Constructors
@@ -502,17 +512,126 @@ impl DemiBuffer {
         self.get_tag() == Tag::Dpdk
     }
 
-    /// Returns the length of the data stored in the `DemiBuffer`.
-    // Note that while we return a usize here (for convenience), the value is guaranteed to never exceed u16::MAX.
+    /// Returns the length of the data stored in the `DemiBuffer`, across all segments of a chain.
+    // Note that while we return a usize here (for convenience), the value only exceeds u16::MAX for chains.
     pub fn len(&self) -> usize {
-        self.as_metadata().data_len as usize
+        self.as_metadata().pkt_len as usize
+    }
+
+    /// Returns whether the `DemiBuffer` holds no data in any of its segments.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of segments in the `DemiBuffer` chain.
+    pub fn num_segments(&self) -> usize {
+        self.as_metadata().nb_segs as usize
+    }
+
+    /// Returns an iterator over the data of each segment in the `DemiBuffer` chain.
+    pub fn segments(&self) -> Segments<'_> {
+        Segments {
+            next: Some(self.get_ptr::<MetaData>()),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the data of the first segment of the `DemiBuffer` chain. Unlike dereferencing, this works on chains too.
+    pub fn first_segment(&self) -> &[u8] {
+        // If the buffer is empty, return an empty slice.
+        if self.segment_len() == 0 {
+            return &[];
+        }
+        // Safety: the call to from_raw_parts is safe, as its arguments refer to a valid readable memory region
+        // of the size specified (which is guaranteed to be smaller than isize::MAX) and is contained within
+        // a single allocated object.  Also, since the data type is u8, proper alignment is not an issue.
+        unsafe { slice::from_raw_parts(self.data_ptr(), self.segment_len()) }
+    }
+
+    /// Returns the data of the first segment of the `DemiBuffer` chain for writing. Unlike dereferencing, this works on
+    /// chains too.
+    pub fn first_segment_mut(&mut self) -> &mut [u8] {
+        // Safety: the call to from_raw_parts_mut is safe, as its args refer to a valid readable memory region
+        // of the size specified (which is guaranteed to be smaller than isize::MAX) and is contained within
+        // a single allocated object.  Also, since the data type is u8, proper alignment is not an issue.
+        unsafe { slice::from_raw_parts_mut(self.data_ptr(), self.segment_len()) }
+    }
+
+    /// Appends the segments of `tail` to the end of the `DemiBuffer` chain, without copying any data.
+    // Note: Both buffers must be of the same type, as a DPDK chain may only hold MBufs.
+    pub fn append(&mut self, tail: DemiBuffer) -> Result<(), Fail> {
+        if self.get_tag() != tail.get_tag() {
+            let cause: String = format!("cannot chain buffers of different types");
+            error!("append(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        let (nb_segs, pkt_len): (u16, u32) = {
+            let md_tail: &MetaData = tail.as_metadata();
+            (md_tail.nb_segs, md_tail.pkt_len)
+        };
+        let md_first: &mut MetaData = self.as_metadata();
+        if md_first.nb_segs as usize + nb_segs as usize > u16::MAX as usize {
+            let cause: String = format!("too many segments in buffer chain");
+            error!("append(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
+        }
+        md_first.nb_segs += nb_segs;
+        md_first.pkt_len += pkt_len;
+        md_first.get_last_segment().next = Some(tail.get_ptr::<MetaData>());
+
+        // The chain owns the segments of `tail` now.
+        mem::forget(tail);
+        Ok(())
+    }
+
+    /// Breaks the `DemiBuffer` chain up into its segments, without copying any data.
+    pub fn into_segments(self) -> Vec<DemiBuffer> {
+        let tag: Tag = self.get_tag();
+        let mut segments: Vec<DemiBuffer> = Vec::with_capacity(self.num_segments());
+        let mut next_entry: Option<NonNull<MetaData>> = Some(self.get_ptr::<MetaData>());
+        while let Some(mut entry) = next_entry {
+            // Safety: This is safe, as `entry` is aligned, dereferenceable, and the MetaData struct it points to is
+            // initialized.
+            let metadata: &mut MetaData = unsafe { entry.as_mut() };
+            next_entry = metadata.next.take();
+            metadata.nb_segs = 1;
+            metadata.pkt_len = metadata.data_len as u32;
+            segments.push(DemiBuffer {
+                tagged_ptr: entry.with_addr(entry.addr() | tag),
+                _phantom: PhantomData,
+            });
+        }
+
+        // Each segment owns itself now.
+        mem::forget(self);
+        segments
+    }
+
+    /// Copies the data of a `DemiBuffer` chain into a single new heap-allocated buffer with `headroom` bytes of headroom.
+    /// A `DemiBuffer` that has a single segment is returned as it is.
+    pub fn coalesce(self, headroom: usize) -> Result<Self, Fail> {
+        if !self.is_multi_segment() {
+            return Ok(self);
+        }
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.len());
+        for segment in self.segments() {
+            bytes.extend_from_slice(segment);
+        }
+        DemiBuffer::from_slice_with_headroom(&bytes, headroom)
     }
 
     /// Removes `nbytes` bytes from the beginning of the `DemiBuffer` chain.
-    // Note: If `nbytes` is greater than the length of the first segment in the chain, then this function will fail and
-    // return an error, rather than remove the remaining bytes from subsequent segments in the chain.  This is to match
-    // the behavior of DPDK's rte_pktmbuf_adj() routine.
+    // Note: Like DPDK's rte_pktmbuf_adj() routine, this only shrinks the first segment in the chain.  If `nbytes` is
+    // greater than the length of that segment, the leading segments are split off the chain instead.
     pub fn adjust(&mut self, nbytes: usize) -> Result<(), Fail> {
+        if self.is_multi_segment() && nbytes > self.segment_len() {
+            if nbytes > self.len() {
+                return Err(Fail::new(libc::EINVAL, "tried to remove more bytes than are present"));
+            }
+            drop(self.split_front(nbytes)?);
+            return Ok(());
+        }
+
         // TODO: Review having this "match", since MetaData and MBuf are laid out the same, these are equivalent cases.
         match self.get_tag() {
             Tag::Heap => {
@@ -549,10 +668,17 @@ impl DemiBuffer {
     }
 
     /// Removes `nbytes` bytes from the end of the `DemiBuffer` chain.
-    // Note: If `nbytes` is greater than the length of the last segment in the chain, then this function will fail and
-    // return an error, rather than remove the remaining bytes from subsequent segments in the chain.  This is to match
-    // the behavior of DPDK's rte_pktmbuf_trim() routine.
+    // Note: Like DPDK's rte_pktmbuf_trim() routine, this only shrinks the last segment in the chain.  If `nbytes` is
+    // greater than the length of that segment, the trailing segments are split off the chain instead.
     pub fn trim(&mut self, nbytes: usize) -> Result<(), Fail> {
+        if self.is_multi_segment() && nbytes > self.as_metadata().get_last_segment().data_len as usize {
+            if nbytes > self.len() {
+                return Err(Fail::new(libc::EINVAL, "tried to remove more bytes than are present"));
+            }
+            drop(self.split_back(self.len() - nbytes)?);
+            return Ok(());
+        }
+
         // TODO: Review having this "match", since MetaData and MBuf are laid out the same, these are equivalent cases.
         match self.get_tag() {
             Tag::Heap => {
@@ -642,7 +768,7 @@ impl DemiBuffer {
     ///
     /// **Notes**
     ///
-    /// - The target [DemiBuffer] must be a single buffer segment (not a chain).
+    /// - If the target [DemiBuffer] is a chain, only the segment that holds the split point is split.
     /// - The target [DemiBuffer] should be large enough to hold `offset`.
     ///
     pub fn split_back(&mut self, offset: usize) -> Result<Self, Fail> {
@@ -664,7 +790,7 @@ impl DemiBuffer {
     ///
     /// **Notes**
     ///
-    /// - The target [DemiBuffer] must be a single buffer segment (not a chain).
+    /// - If the target [DemiBuffer] is a chain, only the segment that holds the split point is split.
     /// - The target [DemiBuffer] should be large enough to hold `offset`.
     ///
     pub fn split_front(&mut self, offset: usize) -> Result<Self, Fail> {
@@ -683,13 +809,6 @@ impl DemiBuffer {
     /// On failure, a [Fail] structure encoding the failure condition is returned instead.
     ///
     fn split(&mut self, split_front: bool, offset: usize) -> Result<Self, Fail> {
-        // Check if this is a multi-segment buffer.
-        if self.is_multi_segment() {
-            let cause: String = format!("cannot split a multi-segment buffer");
-            error!("split_front(): {}", &cause);
-            return Err(Fail::new(libc::EINVAL, &cause));
-        }
-
         // Check if split offset is valid.
         if self.len() < offset {
             let cause: String = format!("cannot split buffer at given offset (offset={:?})", offset);
@@ -697,6 +816,11 @@ impl DemiBuffer {
             return Err(Fail::new(libc::EINVAL, &cause));
         }
 
+        // Check if this is a multi-segment buffer.
+        if self.is_multi_segment() {
+            return self.split_chain(split_front, offset);
+        }
+
         // Clone the target buffer before any changes are applied.
         let mut cloned_buf: DemiBuffer = self.clone();
 
@@ -726,6 +850,57 @@ impl DemiBuffer {
         Ok(cloned_buf)
     }
 
+    // Splits a multi-segment buffer at the given `offset`. The segment that holds the split point is split in two, while
+    // the segments before and after it move to the front half and to the back half as they are.
+    fn split_chain(&mut self, split_front: bool, offset: usize) -> Result<Self, Fail> {
+        let chain: DemiBuffer = mem::replace(self, DemiBuffer::new(0));
+        let mut front: Option<DemiBuffer> = None;
+        let mut back: Option<DemiBuffer> = None;
+        let mut remaining: usize = offset;
+        let mut segments: Peekable<vec::IntoIter<DemiBuffer>> = chain.into_segments().into_iter().peekable();
+        // A half that ends up without data still gets a zero-length piece of a segment, so that it has the same type
+        // as the chain. Splitting at the very front or back of the chain takes such a piece from its first or last
+        // segment.
+        while let Some(mut segment) = segments.next() {
+            if remaining == 0 {
+                if front.is_none() {
+                    front = Some(segment.split_front(0)?);
+                }
+                Self::append_to(&mut back, segment)?;
+            } else if segment.len() < remaining || (segment.len() == remaining && segments.peek().is_some()) {
+                remaining -= segment.len();
+                Self::append_to(&mut front, segment)?;
+            } else {
+                let piece: DemiBuffer = segment.split_front(remaining)?;
+                remaining = 0;
+                Self::append_to(&mut front, piece)?;
+                Self::append_to(&mut back, segment)?;
+            }
+        }
+        // These won't panic, as every split above puts data or a zero-length piece into both halves.
+        let front: DemiBuffer = front.expect("front half should not be empty");
+        let back: DemiBuffer = back.expect("back half should not be empty");
+
+        if split_front {
+            *self = back;
+            Ok(front)
+        } else {
+            *self = front;
+            Ok(back)
+        }
+    }
+
+    // Appends `segment` to `chain`, or makes it the chain if there is none yet.
+    fn append_to(chain: &mut Option<DemiBuffer>, segment: DemiBuffer) -> Result<(), Fail> {
+        match chain {
+            Some(chain) => chain.append(segment),
+            None => {
+                *chain = Some(segment);
+                Ok(())
+            },
+        }
+    }
+
     /// Consumes the `DemiBuffer`, returning a raw token (useful for FFI) that can be used with `from_raw()`.
     // Note the type of the token is arbitrary, it should be treated as an opaque value.
     pub fn into_raw(self) -> NonNull<u8> {
@@ -784,6 +959,12 @@ impl DemiBuffer {
         self.get_ptr::<rte_mbuf>().as_ptr()
     }
 
+    // Gets the length of the data in the first segment of the DemiBuffer.
+    #[inline]
+    fn segment_len(&self) -> usize {
+        self.as_metadata().data_len as usize
+    }
+
     // Gets a raw pointer to the DemiBuffer data.
     fn data_ptr(&self) -> *mut u8 {
         let metadata: &mut MetaData = self.as_metadata();
@@ -1038,29 +1219,28 @@ impl Clone for DemiBuffer {
     }
 }
 
-/// De-Reference Trait Implementation for `DemiBuffer`.
+/// De-Reference Trait Implementation for `DemiBuffer`. Chains cannot be dereferenced, as their data is not contiguous.
 impl Deref for DemiBuffer {
     type Target = [u8];
 
     fn deref(&self) -> &[u8] {
-        // If the buffer is empty, return an empty slice.
-        if self.len() == 0 {
-            return &[];
-        }
-        // Safety: the call to from_raw_parts is safe, as its arguments refer to a valid readable memory region
-        // of the size specified (which is guaranteed to be smaller than isize::MAX) and is contained within
-        // a single allocated object.  Also, since the data type is u8, proper alignment is not an issue.
-        unsafe { slice::from_raw_parts(self.data_ptr(), self.len()) }
+        assert!(
+            !self.is_multi_segment(),
+            "cannot dereference a buffer chain, walk its segments() instead"
+        );
+        self.first_segment()
     }
 }
 
-/// Mutable De-Reference Trait Implementation for `DemiBuffer`.
+/// Mutable De-Reference Trait Implementation for `DemiBuffer`. Chains cannot be dereferenced, as their data is not
+/// contiguous.
 impl DerefMut for DemiBuffer {
     fn deref_mut(&mut self) -> &mut [u8] {
-        // Safety: the call to from_raw_parts_mut is safe, as its args refer to a valid readable memory region
-        // of the size specified (which is guaranteed to be smaller than isize::MAX) and is contained within
-        // a single allocated object.  Also, since the data type is u8, proper alignment is not an issue.
-        unsafe { slice::from_raw_parts_mut(self.data_ptr(), self.len()) }
+        assert!(
+            !self.is_multi_segment(),
+            "cannot dereference a buffer chain, walk its segments() instead"
+        );
+        self.first_segment_mut()
     }
 }
 
@@ -1132,6 +1312,29 @@ impl Drop for DemiBuffer {
     }
 }
 
+/// Iterator Trait Implementation for `Segments`.
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Safety: This is safe, as the pointer is aligned, dereferenceable, and the MetaData struct it points to is
+        // initialized.  The segments outlive the iterator, as it borrows the `DemiBuffer` that holds them.
+        let metadata: &'a MetaData = unsafe { self.next?.as_ref() };
+        self.next = metadata.next;
+        if metadata.data_len == 0 {
+            return Some(&[]);
+        }
+        // Safety: the call to from_raw_parts is safe, as its arguments refer to a valid readable memory region of the
+        // size specified, which is contained within a single allocated object.
+        Some(unsafe {
+            slice::from_raw_parts(
+                metadata.buf_addr.offset(metadata.data_off as isize),
+                metadata.data_len as usize,
+            )
+        })
+    }
+}
+
 /// TryFrom Trait Implementation for `DemiBuffer`.
 impl TryFrom<&[u8]> for DemiBuffer {
     type Error = Fail;
@@ -1382,4 +1585,90 @@ mod tests {
 
         Ok(())
     }
+
+    // Test chaining buffers together and breaking chains up again.
+    #[test]
+    fn chain() -> Result<()> {
+        let mut buf: DemiBuffer = DemiBuffer::from_slice(b"word one ")?;
+        buf.append(DemiBuffer::from_slice(b"two three ")?)?;
+        buf.append(DemiBuffer::from_slice(b"four")?)?;
+        crate::ensure_eq!(buf.num_segments(), 3);
+        crate::ensure_eq!(buf.len(), 23);
+
+        // Only the first segment can be accessed directly, while iterating covers all of them.
+        crate::ensure_eq!(buf.first_segment(), b"word one ");
+        let segments: Vec<&[u8]> = buf.segments().collect();
+        crate::ensure_eq!(segments.concat(), b"word one two three four".to_vec());
+
+        // Cloning the chain shares all of its segments.
+        let clone: DemiBuffer = buf.clone();
+        crate::ensure_eq!(clone.num_segments(), 3);
+        crate::ensure_eq!(clone.len(), 23);
+
+        // Break the chain up into its segments.
+        let segments: Vec<DemiBuffer> = buf.into_segments();
+        crate::ensure_eq!(segments.len(), 3);
+        crate::ensure_eq!(&segments[0][..], b"word one ");
+        crate::ensure_eq!(&segments[1][..], b"two three ");
+        crate::ensure_eq!(&segments[2][..], b"four");
+        for segment in segments.iter() {
+            crate::ensure_eq!(segment.num_segments(), 1);
+        }
+        crate::ensure_eq!(clone.len(), 23);
+
+        Ok(())
+    }
+
+    // Test splitting a chain across and on segment boundaries.
+    #[test]
+    fn chain_split() -> Result<()> {
+        let mut buf: DemiBuffer = DemiBuffer::from_slice(b"word one ")?;
+        buf.append(DemiBuffer::from_slice(b"two three ")?)?;
+        buf.append(DemiBuffer::from_slice(b"four")?)?;
+
+        // Split in the middle of the second segment.
+        let front: DemiBuffer = buf.split_front(12)?;
+        crate::ensure_eq!(front.num_segments(), 2);
+        crate::ensure_eq!(front.len(), 12);
+        crate::ensure_eq!(
+            front.segments().collect::<Vec<&[u8]>>().concat(),
+            b"word one two".to_vec()
+        );
+        crate::ensure_eq!(buf.num_segments(), 2);
+        crate::ensure_eq!(buf.len(), 11);
+        crate::ensure_eq!(buf.segments().collect::<Vec<&[u8]>>().concat(), b" three four".to_vec());
+
+        // Split on the segment boundary.
+        let back: DemiBuffer = buf.split_back(7)?;
+        crate::ensure_eq!(buf.num_segments(), 1);
+        crate::ensure_eq!(&buf[..], b" three ");
+        crate::ensure_eq!(back.num_segments(), 1);
+        crate::ensure_eq!(&back[..], b"four");
+
+        // Split at the very front and back of a chain, which leaves one half without data.
+        let mut buf: DemiBuffer = DemiBuffer::from_slice(b"word")?;
+        buf.append(DemiBuffer::from_slice(b"one")?)?;
+        let front: DemiBuffer = buf.split_front(0)?;
+        crate::ensure_eq!(front.len(), 0);
+        crate::ensure_eq!(buf.len(), 7);
+        let back: DemiBuffer = buf.split_back(7)?;
+        crate::ensure_eq!(back.len(), 0);
+        crate::ensure_eq!(buf.num_segments(), 2);
+        crate::ensure_eq!(buf.len(), 7);
+
+        // Verify bad requests actually fail.
+        crate::ensure_eq!(buf.split_front(8).is_err(), true);
+        crate::ensure_eq!(buf.len(), 7);
+
+        Ok(())
+    }
+
+    // Test that a chain cannot be dereferenced, as its data is not contiguous.
+    #[test]
+    #[should_panic]
+    fn chain_deref() {
+        let mut buf: DemiBuffer = DemiBuffer::from_slice(b"word").unwrap();
+        buf.append(DemiBuffer::from_slice(b"one").unwrap()).unwrap();
+        let _ = buf[0];
+    }
 }
@@ -11,7 +11,7 @@ mod memory_pool;
 
 use crate::runtime::{
     fail::Fail,
-    types::{demi_sgarray_t, demi_sgaseg_t},
+    types::{demi_sgarray_t, demi_sgaseg_t, DEMI_SGARRAY_MAXLEN},
 };
 use ::libc::c_void;
 use ::std::{
@@ -36,20 +36,7 @@ pub use self::{buffer_pool::*, demibuffer::*};
 pub trait MemoryRuntime {
     /// Converts a buffer into a scatter-gather array.
     fn into_sgarray(&self, buf: DemiBuffer) -> Result<demi_sgarray_t, Fail> {
-        // Create a scatter-gather segment to expose the DemiBuffer to the user.
-        let data: *const u8 = buf.as_ptr();
-        let sga_seg: demi_sgaseg_t = demi_sgaseg_t {
-            sgaseg_buf: data as *mut c_void,
-            sgaseg_len: buf.len() as u32,
-        };
-
-        // Create and return a new scatter-gather array (which inherits the DemiBuffer's reference).
-        Ok(demi_sgarray_t {
-            sga_buf: buf.into_raw().as_ptr() as *mut c_void,
-            sga_numsegs: 1,
-            sga_segs: [sga_seg],
-            sga_addr: unsafe { mem::zeroed() },
-        })
+        sgarray_from_buffer(buf)
     }
 
     /// Allocates a scatter-gather array.
@@ -71,101 +58,176 @@ pub trait MemoryRuntime {
         // First allocate the underlying DemiBuffer.
         let buf: DemiBuffer = DemiBuffer::new(size as u16);
 
-        // Create a scatter-gather segment to expose the DemiBuffer to the user.
-        let data: *const u8 = buf.as_ptr();
-        let sga_seg: demi_sgaseg_t = demi_sgaseg_t {
-            sgaseg_buf: data as *mut c_void,
-            sgaseg_len: size as u32,
-        };
-
         // Create and return a new scatter-gather array (which inherits the DemiBuffer's reference).
-        Ok(demi_sgarray_t {
-            sga_buf: buf.into_raw().as_ptr() as *mut c_void,
-            sga_numsegs: 1,
-            sga_segs: [sga_seg],
-            sga_addr: unsafe { mem::zeroed() },
-        })
+        sgarray_from_buffer(buf)
     }
 
     /// Releases a scatter-gather array.
     fn sgafree(&self, sga: demi_sgarray_t) -> Result<(), Fail> {
-        // Check arguments.
-        // TODO: Drop this check once we support scatter-gather arrays with multiple segments.
-        if sga.sga_numsegs != 1 {
-            return Err(Fail::new(libc::EINVAL, "demi_sgarray_t has invalid segment count"));
-        }
+        free_sgarray(sga)
+    }
 
-        if sga.sga_buf == ptr::null_mut() {
-            return Err(Fail::new(libc::EINVAL, "demi_sgarray_t has invalid DemiBuffer token"));
+    /// Appends the segments of the `tail` scatter-gather array to `sga`, so that both can be pushed at once. This
+    /// takes over the segments of `tail`, which should not be released afterwards.
+    fn sgaappend(&self, sga: &mut demi_sgarray_t, tail: demi_sgarray_t) -> Result<(), Fail> {
+        let numsegs: usize = sga.sga_numsegs as usize + tail.sga_numsegs as usize;
+        if numsegs > DEMI_SGARRAY_MAXLEN {
+            let cause: String = format!("too many segments in scatter-gather array (numsegs={:?})", numsegs);
+            error!("sgaappend(): {}", cause);
+            return Err(Fail::new(libc::EINVAL, &cause));
         }
 
-        // Convert back to a DemiBuffer and drop it.
-        // Safety: The `NonNull::new_unchecked()` call is safe, as we verified `sga.sga_buf` is not null above.
-        let token: NonNull<u8> = unsafe { NonNull::new_unchecked(sga.sga_buf as *mut u8) };
-        // Safety: The `DemiBuffer::from_raw()` call *should* be safe, as the `sga_buf` field in the `demi_sgarray_t`
-        // contained a valid `DemiBuffer` token when we provided it to the user (and the user shouldn't change it).
-        let buf: DemiBuffer = unsafe { DemiBuffer::from_raw(token) };
-        drop(buf);
+        // Chain clones of both scatter-gather arrays first, so that neither is touched if this fails.
+        let mut buf: DemiBuffer = self.clone_sgarray(sga)?;
+        buf.append(self.clone_sgarray(&tail)?)?;
+        let appended: demi_sgarray_t = sgarray_from_buffer(buf)?;
+
+        // The chain holds its own references to the data, so release the ones of the original scatter-gather arrays.
+        self.sgafree(*sga)?;
+        self.sgafree(tail)?;
+        *sga = appended;
 
         Ok(())
     }
 
     /// Clones a scatter-gather array.
     fn clone_sgarray(&self, sga: &demi_sgarray_t) -> Result<DemiBuffer, Fail> {
-        // Check arguments.
-        // TODO: Drop this check once we support scatter-gather arrays with multiple segments.
-        if sga.sga_numsegs != 1 {
-            return Err(Fail::new(libc::EINVAL, "demi_sgarray_t has invalid segment count"));
-        }
+        clone_sgarray(sga)
+    }
+}
 
-        if sga.sga_buf == ptr::null_mut() {
-            return Err(Fail::new(libc::EINVAL, "demi_sgarray_t has invalid DemiBuffer token"));
-        }
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Creates a scatter-gather array that describes each segment of a buffer and inherits the buffer's reference.
+pub fn sgarray_from_buffer(buf: DemiBuffer) -> Result<demi_sgarray_t, Fail> {
+    if buf.num_segments() > DEMI_SGARRAY_MAXLEN {
+        let cause: String = format!("too many segments in buffer (num_segments={:?})", buf.num_segments());
+        error!("sgarray_from_buffer(): {}", cause);
+        return Err(Fail::new(libc::EINVAL, &cause));
+    }
+
+    // Create a scatter-gather segment for each segment of the DemiBuffer to expose it to the user.
+    let mut sga_segs: [demi_sgaseg_t; DEMI_SGARRAY_MAXLEN] = [demi_sgaseg_t {
+        sgaseg_buf: ptr::null_mut(),
+        sgaseg_len: 0,
+    }; DEMI_SGARRAY_MAXLEN];
+    for (sga_seg, segment) in sga_segs.iter_mut().zip(buf.segments()) {
+        sga_seg.sgaseg_buf = segment.as_ptr() as *mut c_void;
+        sga_seg.sgaseg_len = segment.len() as u32;
+    }
+
+    Ok(demi_sgarray_t {
+        sga_numsegs: buf.num_segments() as u32,
+        sga_buf: buf.into_raw().as_ptr() as *mut c_void,
+        sga_segs,
+        sga_addr: unsafe { mem::zeroed() },
+    })
+}
+
+/// Releases the reference to the buffer that a scatter-gather array inherited.
+pub fn free_sgarray(sga: demi_sgarray_t) -> Result<(), Fail> {
+    // Convert back to a DemiBuffer and drop it.
+    let buf: DemiBuffer = sgarray_buffer(&sga)?;
+    drop(buf);
+
+    Ok(())
+}
 
-        // Convert back to a DemiBuffer.
-        // Safety: The `NonNull::new_unchecked()` call is safe, as we verified `sga.sga_buf` is not null above.
-        let token: NonNull<u8> = unsafe { NonNull::new_unchecked(sga.sga_buf as *mut u8) };
-        // Safety: The `DemiBuffer::from_raw()` call *should* be safe, as the `sga_buf` field in the `demi_sgarray_t`
-        // contained a valid `DemiBuffer` token when we provided it to the user (and the user shouldn't change it).
-        let buf: DemiBuffer = unsafe { DemiBuffer::from_raw(token) };
-        let mut clone: DemiBuffer = buf.clone();
+/// Clones the buffer behind a scatter-gather array, fitting each of its segments to the data that the matching
+/// scatter-gather array segment describes.
+pub fn clone_sgarray(sga: &demi_sgarray_t) -> Result<DemiBuffer, Fail> {
+    // Convert back to a DemiBuffer.
+    let buf: DemiBuffer = sgarray_buffer(sga)?;
+    let clone: DemiBuffer = buf.clone();
 
-        // Don't drop buf, as it holds the same reference to the data as the sgarray (which should keep it).
+    // Don't drop buf, as it holds the same reference to the data as the sgarray (which should keep it).
+    mem::forget(buf);
+
+    // Return the clone.
+    fit_to_sgarray(clone, sga)
+}
+
+// Recovers the buffer behind a scatter-gather array. The caller is responsible for not dropping the same reference
+// twice.
+fn sgarray_buffer(sga: &demi_sgarray_t) -> Result<DemiBuffer, Fail> {
+    // Check arguments.
+    if sga.sga_numsegs == 0 || sga.sga_numsegs as usize > DEMI_SGARRAY_MAXLEN {
+        return Err(Fail::new(libc::EINVAL, "demi_sgarray_t has invalid segment count"));
+    }
+
+    if sga.sga_buf == ptr::null_mut() {
+        return Err(Fail::new(libc::EINVAL, "demi_sgarray_t has invalid DemiBuffer token"));
+    }
+
+    // Safety: The `NonNull::new_unchecked()` call is safe, as we verified `sga.sga_buf` is not null above.
+    let token: NonNull<u8> = unsafe { NonNull::new_unchecked(sga.sga_buf as *mut u8) };
+    // Safety: The `DemiBuffer::from_raw()` call *should* be safe, as the `sga_buf` field in the `demi_sgarray_t`
+    // contained a valid `DemiBuffer` token when we provided it to the user (and the user shouldn't change it).
+    let buf: DemiBuffer = unsafe { DemiBuffer::from_raw(token) };
+
+    if buf.num_segments() != sga.sga_numsegs as usize {
+        // Don't drop buf, as the sgarray still holds its reference.
         mem::forget(buf);
+        return Err(Fail::new(libc::EINVAL, "demi_sgarray_t has invalid segment count"));
+    }
+
+    Ok(buf)
+}
 
-        // Check to see if the user has reduced the size of the buffer described by the sgarray segment since we
-        // provided it to them.  They could have increased the starting address of the buffer (`sgaseg_buf`),
-        // decreased the ending address of the buffer (`sgaseg_buf + sgaseg_len`), or both.
-        let sga_data: *const u8 = sga.sga_segs[0].sgaseg_buf as *const u8;
-        let sga_len: usize = sga.sga_segs[0].sgaseg_len as usize;
-        let clone_data: *const u8 = clone.as_ptr();
-        let mut clone_len: usize = clone.len();
-        if sga_data != clone_data || sga_len != clone_len {
-            // We need to adjust the DemiBuffer to match the user's changes.
-
-            // First check that the user didn't do something non-sensical, like change the buffer description to
-            // reference address space outside of the DemiBuffer's allocated memory area.
-            if sga_data < clone_data || sga_data.addr() + sga_len > clone_data.addr() + clone_len {
-                return Err(Fail::new(
-                    libc::EINVAL,
-                    "demi_sgarray_t describes data outside backing buffer's allocated region",
-                ));
-            }
-
-            // Calculate the amount the new starting address is ahead of the old.  And then adjust `clone` to match.
-            let adjustment_amount: usize = sga_data.addr() - clone_data.addr();
-            clone.adjust(adjustment_amount)?;
-
-            // An adjustment above would have reduced clone.len() by the adjustment amount.
-            clone_len -= adjustment_amount;
-            debug_assert_eq!(clone_len, clone.len());
-
-            // Trim the clone down to size.
-            let trim_amount: usize = clone_len - sga_len;
-            clone.trim(trim_amount)?;
+// Fits each segment of a buffer to the data that the matching scatter-gather array segment describes.
+fn fit_to_sgarray(buf: DemiBuffer, sga: &demi_sgarray_t) -> Result<DemiBuffer, Fail> {
+    if buf.num_segments() == 1 {
+        return fit_to_sgaseg(buf, &sga.sga_segs[0]);
+    }
+
+    let mut fitted: Option<DemiBuffer> = None;
+    for (segment, sga_seg) in buf.into_segments().into_iter().zip(sga.sga_segs.iter()) {
+        let segment: DemiBuffer = fit_to_sgaseg(segment, sga_seg)?;
+        match fitted {
+            Some(ref mut fitted) => fitted.append(segment)?,
+            None => fitted = Some(segment),
+        }
+    }
+
+    // This unwrap won't panic, as the chain has at least one segment.
+    Ok(fitted.unwrap())
+}
+
+// Fits a single-segment buffer to the data that a scatter-gather array segment describes.
+fn fit_to_sgaseg(mut buf: DemiBuffer, sga_seg: &demi_sgaseg_t) -> Result<DemiBuffer, Fail> {
+    // Check to see if the user has reduced the size of the buffer described by the sgarray segment since we
+    // provided it to them.  They could have increased the starting address of the buffer (`sgaseg_buf`),
+    // decreased the ending address of the buffer (`sgaseg_buf + sgaseg_len`), or both.
+    let sga_data: *const u8 = sga_seg.sgaseg_buf as *const u8;
+    let sga_len: usize = sga_seg.sgaseg_len as usize;
+    let buf_data: *const u8 = buf.as_ptr();
+    let mut buf_len: usize = buf.len();
+    if sga_data != buf_data || sga_len != buf_len {
+        // We need to adjust the DemiBuffer to match the user's changes.
+
+        // First check that the user didn't do something non-sensical, like change the buffer description to
+        // reference address space outside of the DemiBuffer's allocated memory area.
+        if sga_data < buf_data || sga_data.addr() + sga_len > buf_data.addr() + buf_len {
+            return Err(Fail::new(
+                libc::EINVAL,
+                "demi_sgarray_t describes data outside backing buffer's allocated region",
+            ));
         }
 
-        // Return the clone.
-        Ok(clone)
+        // Calculate the amount the new starting address is ahead of the old.  And then adjust `buf` to match.
+        let adjustment_amount: usize = sga_data.addr() - buf_data.addr();
+        buf.adjust(adjustment_amount)?;
+
+        // An adjustment above would have reduced buf.len() by the adjustment amount.
+        buf_len -= adjustment_amount;
+        debug_assert_eq!(buf_len, buf.len());
+
+        // Trim the buffer down to size.
+        let trim_amount: usize = buf_len - sga_len;
+        buf.trim(trim_amount)?;
     }
+
+    Ok(buf)
 }
@@ -7,6 +7,8 @@
 
 pub mod condition_variable;
 pub mod fail;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
 pub mod limits;
 pub mod logging;
 pub mod memory;
@@ -51,8 +53,10 @@ use ::std::{
     collections::HashMap,
     net::SocketAddrV4,
     ops::{Deref, DerefMut},
+    panic::AssertUnwindSafe,
     pin::pin,
     rc::Rc,
+    sync::mpsc::{self, Receiver, Sender},
     time::{Duration, Instant, SystemTime},
 };
 use std::pin::Pin;
@@ -66,6 +70,11 @@ use std::pin::Pin;
 const TIMER_RESOLUTION: usize = 64;
 const TIMER_FINER_RESOLUTION: usize = 2;
 
+/// Whether panics in coroutines are contained unless configured otherwise. Debug builds let them unwind all the way
+/// up, so that they fail fast and with a full backtrace. Release builds abort upon panic, unless they were built with
+/// the `release-unwind` profile.
+pub const CONTAIN_PANICS_BY_DEFAULT: bool = !cfg!(debug_assertions) && cfg!(panic = "unwind");
+
 //======================================================================================================================
 // Structures
 //======================================================================================================================
@@ -78,6 +87,20 @@ pub struct DemiRuntime {
     ts_iters: usize,
     /// Tasks that have been completed and removed from the
     completed_tasks: HashMap<QToken, (QDesc, OperationResult)>,
+    /// Whether coroutines that are inserted from now on have their panics contained.
+    contain_panics: bool,
+    /// Diagnostics channel on which contained panics are reported.
+    panic_tx: Sender<CoroutinePanic>,
+    panic_rx: Receiver<CoroutinePanic>,
+}
+
+/// Report of a panic in a coroutine that was contained by the runtime, instead of taking the whole process down.
+#[derive(Clone, Debug)]
+pub struct CoroutinePanic {
+    pub task_name: &'static str,
+    /// Queue that the coroutine was operating on. Background coroutines are not associated with any queue.
+    pub qd: Option<QDesc>,
+    pub message: String,
 }
 
 #[derive(Clone)]
@@ -107,31 +130,98 @@ impl SharedDemiRuntime {
     #[cfg(test)]
     pub fn new(now: Instant) -> Self {
         timer::global_set_time(now);
+        let (panic_tx, panic_rx): (Sender<CoroutinePanic>, Receiver<CoroutinePanic>) = mpsc::channel();
         Self(SharedObject::<DemiRuntime>::new(DemiRuntime {
             qtable: IoQueueTable::default(),
             scheduler: SharedScheduler::default(),
             socket_id_to_qdesc_map: SocketIdToQDescMap::default(),
             ts_iters: 0,
             completed_tasks: HashMap::<QToken, (QDesc, OperationResult)>::new(),
+            contain_panics: CONTAIN_PANICS_BY_DEFAULT,
+            panic_tx,
+            panic_rx,
         }))
     }
 
-    /// Inserts the `coroutine` named `task_name` into the scheduler.
+    /// Inserts the `coroutine` named `task_name`, which operates on the queue `qd`, into the scheduler. If panics are
+    /// contained, a panic in the coroutine fails the operation instead.
     pub fn insert_io_coroutine<F: FusedFuture<Output = (QDesc, OperationResult)> + 'static>(
         &mut self,
         task_name: &'static str,
+        qd: QDesc,
         coroutine: Pin<Box<F>>,
     ) -> Result<QToken, Fail> {
-        self.insert_coroutine(task_name, coroutine)
+        if self.contain_panics {
+            let coroutine = self.contain_panic(task_name, Some(qd), coroutine, move |e| {
+                (qd, OperationResult::Failed(e))
+            });
+            self.insert_coroutine(task_name, coroutine)
+        } else {
+            self.insert_coroutine(task_name, coroutine)
+        }
     }
 
-    /// Inserts the background `coroutine` named `task_name` into the scheduler
+    /// Inserts the background `coroutine` named `task_name` into the scheduler. If panics are contained, a panic in
+    /// the coroutine just ends it.
     pub fn insert_background_coroutine<F: FusedFuture<Output = ()> + 'static>(
         &mut self,
         task_name: &'static str,
         coroutine: Pin<Box<F>>,
     ) -> Result<QToken, Fail> {
-        self.insert_coroutine(task_name, coroutine)
+        if self.contain_panics {
+            let coroutine = self.contain_panic(task_name, None, coroutine, |_| ());
+            self.insert_coroutine(task_name, coroutine)
+        } else {
+            self.insert_coroutine(task_name, coroutine)
+        }
+    }
+
+    /// Wraps `coroutine` so that a panic inside it does not unwind through the scheduler. Instead, the panic is
+    /// reported on the diagnostics channel and the coroutine completes with whatever `on_panic` makes of it.
+    fn contain_panic<F: FusedFuture + 'static>(
+        &self,
+        task_name: &'static str,
+        qd: Option<QDesc>,
+        coroutine: Pin<Box<F>>,
+        on_panic: impl FnOnce(Fail) -> F::Output + 'static,
+    ) -> Pin<Box<impl FusedFuture<Output = F::Output>>> {
+        let panic_tx: Sender<CoroutinePanic> = self.panic_tx.clone();
+        Box::pin(
+            async move {
+                match AssertUnwindSafe(coroutine).catch_unwind().await {
+                    Ok(output) => output,
+                    Err(payload) => {
+                        let message: String = if let Some(message) = payload.downcast_ref::<&str>() {
+                            message.to_string()
+                        } else if let Some(message) = payload.downcast_ref::<String>() {
+                            message.clone()
+                        } else {
+                            "unknown panic payload".to_string()
+                        };
+                        let cause: String = format!(
+                            "coroutine panicked (task_name={:?}, qd={:?}): {}",
+                            task_name, qd, message
+                        );
+                        error!("contain_panic(): {}", cause);
+                        // The receiving end lives in the runtime, so this only fails if the runtime is gone already.
+                        let _ = panic_tx.send(CoroutinePanic { task_name, qd, message });
+                        on_panic(Fail::new(libc::EIO, &cause))
+                    },
+                }
+            }
+            .fuse(),
+        )
+    }
+
+    /// Sets whether coroutines that are inserted from now on have their panics contained. This only has an effect if
+    /// panics unwind, that is, if the binary was not built with `panic = "abort"`.
+    pub fn set_contain_panics(&mut self, contain_panics: bool) {
+        self.contain_panics = contain_panics;
+    }
+
+    /// Takes the reports of all panics that were contained since the last call.
+    pub fn take_coroutine_panics(&mut self) -> Vec<CoroutinePanic> {
+        self.panic_rx.try_iter().collect()
     }
 
     /// Inserts a coroutine of type T and task
@@ -157,6 +247,18 @@ impl SharedDemiRuntime {
         }
     }
 
+    /// Removes the coroutine behind `qt` from the scheduler before it completes, so that it neither runs any further nor
+    /// leaves a result behind. Returns whether there was such a coroutine.
+    pub fn remove_coroutine(&mut self, qt: QToken) -> bool {
+        match self.scheduler.remove_task(TaskId::from(qt)) {
+            Some(boxed_task) => {
+                trace!("Removing coroutine: {:?}", boxed_task.get_name());
+                true
+            },
+            None => false,
+        }
+    }
+
     /// This is just a single-token convenience wrapper for wait_any().
     pub fn wait(&mut self, qt: QToken, timeout: Duration) -> Result<(usize, QToken, QDesc, OperationResult), Fail> {
         trace!("wait(): qt={:?}, timeout={:?}", qt, timeout);
@@ -347,6 +449,9 @@ impl SharedDemiRuntime {
 
     /// Performs a single pool on the underlying scheduler.
     pub fn poll(&mut self) {
+        #[cfg(feature = "fault-injection")]
+        fault::inject_delay();
+
         // For all ready tasks that were removed from the scheduler, add to our completed task list.
         for boxed_task in self.scheduler.poll_all() {
             trace!("Completed while polling coroutine: {:?}", boxed_task.get_name());
@@ -510,12 +615,16 @@ pub async fn poll_yield() {
 impl Default for SharedDemiRuntime {
     fn default() -> Self {
         timer::global_set_time(Instant::now());
+        let (panic_tx, panic_rx): (Sender<CoroutinePanic>, Receiver<CoroutinePanic>) = mpsc::channel();
         Self(SharedObject::<DemiRuntime>::new(DemiRuntime {
             qtable: IoQueueTable::default(),
             scheduler: SharedScheduler::default(),
             socket_id_to_qdesc_map: SocketIdToQDescMap::default(),
             ts_iters: 0,
             completed_tasks: HashMap::<QToken, (QDesc, OperationResult)>::new(),
+            contain_panics: CONTAIN_PANICS_BY_DEFAULT,
+            panic_tx,
+            panic_rx,
         }))
     }
 }
@@ -613,7 +722,8 @@ pub trait Runtime: Clone + Unpin + 'static {}
 
 #[cfg(test)]
 mod tests {
-    use crate::runtime::{poll_yield, OperationResult, QDesc, QToken, SharedDemiRuntime};
+    use crate::runtime::{poll_yield, CoroutinePanic, OperationResult, QDesc, QToken, SharedDemiRuntime};
+    use ::anyhow::Result;
     use ::std::time::Duration;
     use futures::FutureExt;
     use test::Bencher;
@@ -631,11 +741,43 @@ mod tests {
         }
     }
 
+    async fn panicking_coroutine() -> (QDesc, OperationResult) {
+        poll_yield().await;
+        panic!("something went wrong");
+    }
+
+    #[test]
+    fn contained_panic_fails_operation() -> Result<()> {
+        let mut runtime: SharedDemiRuntime = SharedDemiRuntime::default();
+        runtime.set_contain_panics(true);
+
+        let qt: QToken = runtime.insert_io_coroutine(
+            "panicking coroutine",
+            QDesc::from(7),
+            Box::pin(panicking_coroutine().fuse()),
+        )?;
+        let (_, _, qd, result) = runtime.wait(qt, Duration::from_secs(1))?;
+        crate::ensure_eq!(qd, QDesc::from(7));
+        match result {
+            OperationResult::Failed(e) => crate::ensure_eq!(e.errno, libc::EIO),
+            _ => anyhow::bail!("operation should have failed"),
+        }
+
+        let panics: Vec<CoroutinePanic> = runtime.take_coroutine_panics();
+        crate::ensure_eq!(panics.len(), 1);
+        crate::ensure_eq!(panics[0].task_name, "panicking coroutine");
+        crate::ensure_eq!(panics[0].qd, Some(QDesc::from(7)));
+        crate::ensure_eq!(panics[0].message.as_str(), "something went wrong");
+        crate::ensure_eq!(runtime.take_coroutine_panics().is_empty(), true);
+
+        Ok(())
+    }
+
     #[bench]
     fn benchmark_insert_io_coroutine(b: &mut Bencher) {
         let mut runtime: SharedDemiRuntime = SharedDemiRuntime::default();
 
-        b.iter(|| runtime.insert_io_coroutine("dummy coroutine", Box::pin(dummy_coroutine(10).fuse())));
+        b.iter(|| runtime.insert_io_coroutine("dummy coroutine", QDesc::from(0), Box::pin(dummy_coroutine(10).fuse())));
     }
 
     #[bench]
@@ -659,7 +801,11 @@ mod tests {
         for i in 0..NUM_TASKS {
             // Make the arg big enough that the coroutine doesn't exit.
             qts[i] = runtime
-                .insert_io_coroutine("dummy coroutine", Box::pin(dummy_coroutine(1000000000).fuse()))
+                .insert_io_coroutine(
+                    "dummy coroutine",
+                    QDesc::from(0),
+                    Box::pin(dummy_coroutine(1000000000).fuse()),
+                )
                 .expect("should be able to insert tasks");
         }
 
@@ -676,7 +822,11 @@ mod tests {
         for i in 0..NUM_TASKS {
             // Make the arg big enough that the coroutine doesn't exit.
             qts[i] = runtime
-                .insert_io_coroutine("dummy coroutine", Box::pin(dummy_coroutine(1000000000).fuse()))
+                .insert_io_coroutine(
+                    "dummy coroutine",
+                    QDesc::from(0),
+                    Box::pin(dummy_coroutine(1000000000).fuse()),
+                )
                 .expect("should be able to insert tasks");
         }
 
@@ -693,7 +843,11 @@ mod tests {
         for i in 0..NUM_TASKS {
             // Make the arg big enough that the coroutine doesn't exit.
             qts[i] = runtime
-                .insert_io_coroutine("dummy coroutine", Box::pin(dummy_coroutine(1000000000).fuse()))
+                .insert_io_coroutine(
+                    "dummy coroutine",
+                    QDesc::from(0),
+                    Box::pin(dummy_coroutine(1000000000).fuse()),
+                )
                 .expect("should be able to insert tasks");
         }
 
@@ -20,6 +20,8 @@ pub struct ArpConfig {
     cache_ttl: Duration,
     request_timeout: Duration,
     retry_count: usize,
+    /// Number of probes to send for the local IPv4 address before using it. Zero disables duplicate address detection.
+    probe_count: usize,
     initial_values: HashMap<Ipv4Addr, MacAddress>,
     is_enabled: bool,
 }
@@ -35,6 +37,8 @@ impl ArpConfig {
                 cache_ttl: config.arp_cache_ttl()?,
                 request_timeout: config.arp_request_timeout()?,
                 retry_count: config.arp_request_retries()?,
+                // Duplicate address detection is optional, so older configuration files keep working.
+                probe_count: config.arp_probe_count().unwrap_or(0),
                 initial_values,
                 is_enabled: true,
             })
@@ -44,6 +48,7 @@ impl ArpConfig {
                 cache_ttl: Duration::ZERO,
                 request_timeout: Duration::ZERO,
                 retry_count: 0,
+                probe_count: 0,
                 initial_values: HashMap::new(),
                 is_enabled: false,
             })
@@ -62,6 +67,10 @@ impl ArpConfig {
         self.retry_count
     }
 
+    pub fn get_probe_count(&self) -> usize {
+        self.probe_count
+    }
+
     pub fn get_initial_values(&self) -> &HashMap<Ipv4Addr, MacAddress> {
         &self.initial_values
     }
@@ -81,6 +90,7 @@ impl Default for ArpConfig {
             cache_ttl: Duration::from_secs(15),
             request_timeout: Duration::from_secs(20),
             retry_count: 5,
+            probe_count: 0,
             initial_values: HashMap::new(),
             is_enabled: true,
         }
@@ -103,6 +113,7 @@ mod tests {
         crate::ensure_eq!(config.get_cache_ttl(), Duration::from_secs(15));
         crate::ensure_eq!(config.get_request_timeout(), Duration::from_secs(20));
         crate::ensure_eq!(config.get_retry_count(), 5);
+        crate::ensure_eq!(config.get_probe_count(), 0);
         crate::ensure_eq!(config.get_initial_values(), &HashMap::new());
         crate::ensure_eq!(config.is_enabled(), true);
 
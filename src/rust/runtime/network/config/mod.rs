@@ -9,4 +9,8 @@ mod udp;
 // Exports
 //======================================================================================================================
 
-pub use self::{arp::ArpConfig, tcp::TcpConfig, udp::UdpConfig};
+pub use self::{
+    arp::ArpConfig,
+    tcp::{BacklogOverflow, SynCookies, TcpConfig, TimeWaitRecycle, UrgentData},
+    udp::UdpConfig,
+};
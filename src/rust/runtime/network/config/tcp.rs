@@ -7,29 +7,153 @@
 
 use crate::{
     demikernel::config::Config,
+    inetstack::protocols::{
+        layer3::ipv4::IPV4_HEADER_MIN_SIZE,
+        layer4::tcp::{
+            congestion_control::{self, CongestionControl, CongestionControlConstructor},
+            header::MIN_TCP_HEADER_SIZE,
+        },
+    },
     runtime::{
         fail::Fail,
-        network::consts::{DEFAULT_MSS, MAX_MSS, MIN_MSS, TCP_ACK_DELAY_TIMEOUT, TCP_HANDSHAKE_TIMEOUT},
+        network::{
+            consts::{
+                DEFAULT_INITIAL_CONGESTION_WINDOW, DEFAULT_MAX_TIME_WAIT, DEFAULT_MSS,
+                DEFAULT_TCP_ACK_STRETCH_SEGMENTS, MAX_MSS, MIN_MSS, MSL, TCP_ACK_DELAY_TIMEOUT, TCP_FIN_TIMEOUT,
+                TCP_HANDSHAKE_TIMEOUT,
+            },
+            socket::option::SynBackoff,
+        },
     },
 };
-use ::std::time::Duration;
+use ::std::{cmp, str::FromStr, time::Duration};
 
 //======================================================================================================================
 // Structures
 //======================================================================================================================
 
+/// When listening sockets answer SYN segments with SYN cookies instead of keeping state for the handshake.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SynCookies {
+    /// Never. SYN segments that do not fit into the queue of handshakes in progress are reset or dropped.
+    #[default]
+    Off,
+    /// Only for SYN segments that do not fit into the queue of handshakes in progress.
+    UnderPressure,
+    /// For every SYN segment.
+    Always,
+}
+
+/// What listening sockets do with SYN segments that do not fit into their queues.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BacklogOverflow {
+    /// Answer them with a RST, so that the connection attempt fails right away.
+    #[default]
+    Reset,
+    /// Drop them silently, so that our peer retransmits the SYN and may get in once there is room.
+    Drop,
+}
+
+/// When a new outgoing connection may take over the 4-tuple of a connection that is still in TIME_WAIT.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeWaitRecycle {
+    /// Never. The 4-tuple stays in use until TIME_WAIT is over.
+    Never,
+    /// If the old connection used timestamps and has been idle for a while, as PAWS then protects the new connection
+    /// from old duplicates.
+    #[default]
+    Timestamps,
+    /// Always. This is only safe in controlled environments, where no old duplicates linger in the network, but it
+    /// lets short-lived connections reuse their 4-tuples right away.
+    Always,
+}
+
+/// What established connections do with segments that have the URG flag set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UrgentData {
+    /// Ignore the urgent pointer and deliver urgent data in line with the rest of the stream, as RFC 6093 recommends.
+    #[default]
+    Inline,
+    /// Drop them without looking at their data, so that the application never sees urgent data.
+    Drop,
+    /// Reset the connection, so that peers that rely on urgent data find out right away.
+    Reset,
+}
+
 #[derive(Clone, Debug)]
 pub struct TcpConfig {
     /// mss = Maximum Segment Size
     advertised_mss: usize,
+    /// Largest MSS that fits into the MTU of the local interface.
+    mtu_mss: usize,
     handshake_retries: usize,
     handshake_timeout: Duration,
     receive_window_size: u16,
     /// Scaling Factor for Window Size
     window_scale: u8,
     ack_delay_timeout: Duration,
+    /// Number of received segments to acknowledge with a single ACK, as long as there is no loss or reordering.
+    ack_stretch_segments: usize,
     rx_checksum_offload: bool,
     tx_checksum_offload: bool,
+    /// Out of how many received segments we validate one in software although the hardware validated its checksum. Zero
+    /// turns this validation off.
+    rx_checksum_sample_interval: usize,
+    /// Congestion control algorithm used by new connections, unless their socket picks another one.
+    congestion_control: CongestionControlConstructor,
+    /// Name of the congestion control algorithm used by new connections.
+    congestion_control_name: &'static str,
+    /// Initial congestion window in segments.
+    initial_congestion_window: usize,
+    /// Whether passively opened connections send to the link address that their SYN came from. This keeps replies on
+    /// the same path as requests when the sender sits behind a bridge or router that ARP does not resolve to.
+    learn_remote_link_addr: bool,
+    /// Whether connections that use timestamps (RFC 7323) take round-trip time samples from the echoed timestamps. These
+    /// samples also cover retransmitted segments, so they keep the RTO accurate on lossy links where Karn's algorithm
+    /// would discard most samples.
+    timestamp_rtt_samples: bool,
+    /// Whether connections negotiate Explicit Congestion Notification (RFC 3168), so that routers can signal congestion
+    /// by marking packets instead of dropping them.
+    ecn: bool,
+    /// Size in bytes up to which connections grow their receive buffer as the application reads faster, so that a single
+    /// connection fills links with a high bandwidth-delay product. Zero keeps the receive buffer at its initial size.
+    max_receive_buffer_size: usize,
+    /// Size in bytes of the send buffer, which bounds the data that is unsent or unacknowledged. Pushes wait for room
+    /// while the send buffer is full. Zero leaves the send buffer unbounded.
+    send_buffer_size: usize,
+    /// Size in bytes up to which connections grow their send buffer with the congestion window.
+    max_send_buffer_size: usize,
+    /// Whether connections fall back to smaller segments after repeated retransmission timeouts, in case a router on the
+    /// path drops segments that are too large for it without sending ICMP Fragmentation Needed messages (RFC 2923).
+    pmtu_blackhole_detection: bool,
+    /// Whether connections space out their segments over the round-trip time, at a rate derived from cwnd/SRTT, unless
+    /// the congestion control algorithm supplies a pacing rate of its own. This avoids bursts that overflow the shallow
+    /// buffers of switches.
+    pacing: bool,
+    /// Maximum number of accepts that a listening socket completes in a single poll iteration. Bursts of connections are
+    /// spread over several iterations, so that they do not hold up traffic on established connections. Zero means no
+    /// limit.
+    max_accepts_per_poll: usize,
+    /// When listening sockets answer SYN segments with SYN cookies, so that a SYN flood does not fill their backlog.
+    syn_cookies: SynCookies,
+    /// Maximum number of handshakes that a listening socket keeps in progress. Zero means the backlog that was passed to
+    /// listen, which bounds the queue of connections that wait to be accepted.
+    max_syn_backlog: usize,
+    /// What listening sockets do with SYN segments when either of their queues is full.
+    backlog_overflow: BacklogOverflow,
+    /// Maximum number of connections that are kept in TIME_WAIT. Connections that close once the limit is reached are
+    /// forgotten right away, as if they had set a linger timeout of zero.
+    max_time_wait: usize,
+    /// Maximum segment lifetime. Connections stay in TIME_WAIT for twice as long, unless their socket sets a linger
+    /// timeout.
+    msl: Duration,
+    /// How long a connection that we closed waits in FIN_WAIT_2 for our peer to close its end. After that, we reset the
+    /// connection and release it.
+    fin_timeout: Duration,
+    /// When new outgoing connections may take over the 4-tuple of a connection in TIME_WAIT.
+    time_wait_recycle: TimeWaitRecycle,
+    /// What established connections do with segments that carry urgent data.
+    urgent_data: UrgentData,
 }
 
 //======================================================================================================================
@@ -45,16 +169,142 @@ impl TcpConfig {
             assert!(value <= MAX_MSS);
             options.advertised_mss = value;
         }
+        if let Ok(mtu) = config.mtu() {
+            let headers_size: usize = IPV4_HEADER_MIN_SIZE as usize + MIN_TCP_HEADER_SIZE;
+            match (mtu as usize).checked_sub(headers_size) {
+                Some(mtu_mss) if mtu_mss >= MIN_MSS => options.mtu_mss = mtu_mss,
+                _ => {
+                    let cause: String = format!("MTU is too small to carry a minimum-sized TCP segment (mtu={})", mtu);
+                    error!("new(): {}", cause);
+                    return Err(Fail::new(libc::EINVAL, &cause));
+                },
+            }
+        }
         if let Ok(value) = config.tcp_checksum_offload() {
             options.rx_checksum_offload = value;
             options.tx_checksum_offload = value;
         }
+        if let Ok(value) = config.rx_checksum_sample_interval() {
+            options.rx_checksum_sample_interval = value;
+        }
+        if let Ok(value) = config.tcp_initial_congestion_window() {
+            if value == 0 {
+                let cause: String = format!("initial congestion window must be at least one segment");
+                error!("new(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            }
+            options.initial_congestion_window = value;
+        }
+        if let Ok(value) = config.tcp_ack_stretch_segments() {
+            if value == 0 {
+                let cause: String = format!("ACK stretching must acknowledge at least one segment");
+                error!("new(): {}", cause);
+                return Err(Fail::new(libc::EINVAL, &cause));
+            }
+            options.ack_stretch_segments = value;
+        }
+        if let Ok(value) = config.tcp_learn_remote_link_addr() {
+            options.learn_remote_link_addr = value;
+        }
+        if let Ok(value) = config.tcp_timestamp_rtt_samples() {
+            options.timestamp_rtt_samples = value;
+        }
+        if let Ok(value) = config.tcp_ecn() {
+            options.ecn = value;
+        }
+        if let Ok(value) = config.tcp_max_receive_buffer_size() {
+            options.max_receive_buffer_size = value;
+        }
+        if let Ok(value) = config.tcp_send_buffer_size() {
+            options.send_buffer_size = value;
+        }
+        if let Ok(value) = config.tcp_max_send_buffer_size() {
+            options.max_send_buffer_size = value;
+        }
+        if let Ok(value) = config.tcp_pmtu_blackhole_detection() {
+            options.pmtu_blackhole_detection = value;
+        }
+        if let Ok(value) = config.tcp_pacing() {
+            options.pacing = value;
+        }
+        if let Ok(value) = config.tcp_max_accepts_per_poll() {
+            options.max_accepts_per_poll = value;
+        }
+        if let Ok(mode) = config.tcp_syn_cookies() {
+            options.syn_cookies = match SynCookies::from_str(&mode) {
+                Ok(syn_cookies) => syn_cookies,
+                Err(e) => {
+                    error!("new(): {}", e.cause);
+                    return Err(e);
+                },
+            };
+        }
+        if let Ok(value) = config.tcp_max_syn_backlog() {
+            options.max_syn_backlog = value;
+        }
+        if let Ok(value) = config.tcp_max_time_wait() {
+            options.max_time_wait = value;
+        }
+        if let Ok(value) = config.tcp_msl() {
+            options.msl = value;
+        }
+        if let Ok(value) = config.tcp_fin_timeout() {
+            options.fin_timeout = value;
+        }
+        if let Ok(policy) = config.tcp_time_wait_recycle() {
+            options.time_wait_recycle = match TimeWaitRecycle::from_str(&policy) {
+                Ok(time_wait_recycle) => time_wait_recycle,
+                Err(e) => {
+                    error!("new(): {}", e.cause);
+                    return Err(e);
+                },
+            };
+        }
+        if let Ok(policy) = config.tcp_urgent_data() {
+            options.urgent_data = match UrgentData::from_str(&policy) {
+                Ok(urgent_data) => urgent_data,
+                Err(e) => {
+                    error!("new(): {}", e.cause);
+                    return Err(e);
+                },
+            };
+        }
+        if let Ok(policy) = config.tcp_backlog_overflow() {
+            options.backlog_overflow = match BacklogOverflow::from_str(&policy) {
+                Ok(backlog_overflow) => backlog_overflow,
+                Err(e) => {
+                    error!("new(): {}", e.cause);
+                    return Err(e);
+                },
+            };
+        }
+        if let Ok(name) = config.tcp_congestion_control() {
+            (options.congestion_control_name, options.congestion_control) =
+                match congestion_control::get_algorithm(&name) {
+                    Some(algorithm) => algorithm,
+                    None => {
+                        let cause: String = format!("unknown congestion control algorithm (name={:?})", name);
+                        error!("new(): {}", cause);
+                        return Err(Fail::new(libc::EINVAL, &cause));
+                    },
+                };
+        }
 
         Ok(options)
     }
 
+    /// Returns the MSS that we advertise to peers. This is the configured MSS, unless it does not fit into the MTU.
     pub fn get_advertised_mss(&self) -> usize {
-        self.advertised_mss
+        cmp::min(self.advertised_mss, self.mtu_mss)
+    }
+
+    pub fn get_mtu_mss(&self) -> usize {
+        self.mtu_mss
+    }
+
+    /// Returns the MSS that we use when sending to a peer that advertised `remote_mss`.
+    pub fn get_send_mss(&self, remote_mss: usize) -> usize {
+        cmp::min(remote_mss, self.get_advertised_mss())
     }
 
     pub fn get_handshake_retries(&self) -> usize {
@@ -65,6 +315,21 @@ impl TcpConfig {
         self.handshake_timeout
     }
 
+    /// Returns how often connecting sockets retransmit their SYN, unless they choose otherwise.
+    pub fn get_syn_retries(&self) -> u32 {
+        self.handshake_retries.saturating_sub(1) as u32
+    }
+
+    /// Returns how long connecting sockets wait for an answer to each SYN, unless they choose otherwise. The wait does
+    /// not grow with retransmissions.
+    pub fn get_syn_backoff(&self) -> SynBackoff {
+        SynBackoff {
+            initial_timeout: self.handshake_timeout,
+            multiplier: 1,
+            max_timeout: self.handshake_timeout,
+        }
+    }
+
     pub fn get_receive_window_size(&self) -> u16 {
         self.receive_window_size
     }
@@ -77,6 +342,10 @@ impl TcpConfig {
         self.ack_delay_timeout
     }
 
+    pub fn get_ack_stretch_segments(&self) -> usize {
+        self.ack_stretch_segments
+    }
+
     pub fn get_tx_checksum_offload(&self) -> bool {
         self.tx_checksum_offload
     }
@@ -84,6 +353,103 @@ impl TcpConfig {
     pub fn get_rx_checksum_offload(&self) -> bool {
         self.rx_checksum_offload
     }
+
+    pub fn get_rx_checksum_sample_interval(&self) -> usize {
+        self.rx_checksum_sample_interval
+    }
+
+    pub fn get_congestion_control(&self) -> CongestionControlConstructor {
+        self.congestion_control
+    }
+
+    pub fn get_congestion_control_name(&self) -> &'static str {
+        self.congestion_control_name
+    }
+
+    /// Returns the constructor of the congestion control algorithm named [name], or of the default one if no algorithm
+    /// was picked.
+    pub fn get_congestion_control_by_name(&self, name: Option<&str>) -> CongestionControlConstructor {
+        match name.and_then(congestion_control::get_constructor) {
+            Some(constructor) => constructor,
+            None => self.congestion_control,
+        }
+    }
+
+    pub fn get_initial_congestion_window(&self) -> usize {
+        self.initial_congestion_window
+    }
+
+    pub fn get_learn_remote_link_addr(&self) -> bool {
+        self.learn_remote_link_addr
+    }
+
+    pub fn get_timestamp_rtt_samples(&self) -> bool {
+        self.timestamp_rtt_samples
+    }
+
+    pub fn get_ecn(&self) -> bool {
+        self.ecn
+    }
+
+    pub fn get_max_receive_buffer_size(&self) -> usize {
+        self.max_receive_buffer_size
+    }
+
+    pub fn get_send_buffer_size(&self) -> usize {
+        self.send_buffer_size
+    }
+
+    pub fn get_max_send_buffer_size(&self) -> usize {
+        self.max_send_buffer_size
+    }
+
+    pub fn get_pmtu_blackhole_detection(&self) -> bool {
+        self.pmtu_blackhole_detection
+    }
+
+    pub fn get_pacing(&self) -> bool {
+        self.pacing
+    }
+
+    pub fn get_max_accepts_per_poll(&self) -> usize {
+        self.max_accepts_per_poll
+    }
+
+    pub fn get_syn_cookies(&self) -> SynCookies {
+        self.syn_cookies
+    }
+
+    /// Returns the maximum number of handshakes that a listening socket with a backlog of `backlog` keeps in progress.
+    pub fn get_max_syn_backlog(&self, backlog: usize) -> usize {
+        match self.max_syn_backlog {
+            0 => backlog,
+            max_syn_backlog => max_syn_backlog,
+        }
+    }
+
+    pub fn get_backlog_overflow(&self) -> BacklogOverflow {
+        self.backlog_overflow
+    }
+
+    pub fn get_max_time_wait(&self) -> usize {
+        self.max_time_wait
+    }
+
+    pub fn get_msl(&self) -> Duration {
+        self.msl
+    }
+
+    pub fn get_fin_timeout(&self) -> Duration {
+        self.fin_timeout
+    }
+
+    pub fn get_time_wait_recycle(&self) -> TimeWaitRecycle {
+        self.time_wait_recycle
+    }
+
+    pub fn get_urgent_data(&self) -> UrgentData {
+        self.urgent_data
+    }
 }
 
 //======================================================================================================================
@@ -94,13 +460,99 @@ impl Default for TcpConfig {
     fn default() -> Self {
         TcpConfig {
             advertised_mss: DEFAULT_MSS,
+            mtu_mss: MAX_MSS,
             handshake_retries: 5,
             handshake_timeout: TCP_HANDSHAKE_TIMEOUT,
             receive_window_size: 0xffff,
             ack_delay_timeout: TCP_ACK_DELAY_TIMEOUT,
+            ack_stretch_segments: DEFAULT_TCP_ACK_STRETCH_SEGMENTS,
             window_scale: 0,
             rx_checksum_offload: false,
             tx_checksum_offload: false,
+            rx_checksum_sample_interval: 0,
+            congestion_control: <congestion_control::None as CongestionControl>::new,
+            congestion_control_name: "none",
+            initial_congestion_window: DEFAULT_INITIAL_CONGESTION_WINDOW,
+            learn_remote_link_addr: false,
+            timestamp_rtt_samples: true,
+            ecn: false,
+            max_receive_buffer_size: 0,
+            send_buffer_size: 0,
+            max_send_buffer_size: 0,
+            pmtu_blackhole_detection: false,
+            pacing: false,
+            max_accepts_per_poll: 0,
+            syn_cookies: SynCookies::Off,
+            max_syn_backlog: 0,
+            backlog_overflow: BacklogOverflow::Reset,
+            max_time_wait: DEFAULT_MAX_TIME_WAIT,
+            msl: MSL,
+            fin_timeout: TCP_FIN_TIMEOUT,
+            time_wait_recycle: TimeWaitRecycle::Timestamps,
+            urgent_data: UrgentData::Inline,
+        }
+    }
+}
+
+impl FromStr for BacklogOverflow {
+    type Err = Fail;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reset" => Ok(BacklogOverflow::Reset),
+            "drop" => Ok(BacklogOverflow::Drop),
+            _ => {
+                let cause: String = format!("unknown backlog overflow policy (policy={:?})", s);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+}
+
+impl FromStr for TimeWaitRecycle {
+    type Err = Fail;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(TimeWaitRecycle::Never),
+            "timestamps" => Ok(TimeWaitRecycle::Timestamps),
+            "always" => Ok(TimeWaitRecycle::Always),
+            _ => {
+                let cause: String = format!("unknown TIME_WAIT recycling policy (policy={:?})", s);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+}
+
+impl FromStr for UrgentData {
+    type Err = Fail;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inline" => Ok(UrgentData::Inline),
+            "drop" => Ok(UrgentData::Drop),
+            "reset" => Ok(UrgentData::Reset),
+            _ => {
+                let cause: String = format!("unknown urgent data policy (policy={:?})", s);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
+        }
+    }
+}
+
+impl FromStr for SynCookies {
+    type Err = Fail;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(SynCookies::Off),
+            "under_pressure" => Ok(SynCookies::UnderPressure),
+            "always" => Ok(SynCookies::Always),
+            _ => {
+                let cause: String = format!("unknown SYN cookies mode (mode={:?})", s);
+                Err(Fail::new(libc::EINVAL, &cause))
+            },
         }
     }
 }
@@ -111,21 +563,111 @@ impl Default for TcpConfig {
 
 #[cfg(test)]
 mod tests {
-    use crate::runtime::network::{config::TcpConfig, consts::DEFAULT_MSS};
+    use crate::runtime::network::{
+        config::{BacklogOverflow, SynCookies, TcpConfig, TimeWaitRecycle, UrgentData},
+        consts::{
+            DEFAULT_INITIAL_CONGESTION_WINDOW, DEFAULT_MAX_TIME_WAIT, DEFAULT_MSS, DEFAULT_TCP_ACK_STRETCH_SEGMENTS,
+            MAX_MSS, MSL, TCP_FIN_TIMEOUT,
+        },
+    };
     use ::anyhow::Result;
-    use ::std::time::Duration;
+    use ::std::{str::FromStr, time::Duration};
 
     #[test]
     fn test_tcp_config_default() -> Result<()> {
         let config: TcpConfig = TcpConfig::default();
         crate::ensure_eq!(config.get_advertised_mss(), DEFAULT_MSS);
+        crate::ensure_eq!(config.get_mtu_mss(), MAX_MSS);
         crate::ensure_eq!(config.get_handshake_retries(), 5);
         crate::ensure_eq!(config.get_handshake_timeout(), Duration::from_secs(3));
+        crate::ensure_eq!(config.get_syn_retries(), 4);
         crate::ensure_eq!(config.get_receive_window_size(), 0xffff);
         crate::ensure_eq!(config.get_window_scale(), 0);
         crate::ensure_eq!(config.get_rx_checksum_offload(), false);
         crate::ensure_eq!(config.get_tx_checksum_offload(), false);
+        crate::ensure_eq!(config.get_rx_checksum_sample_interval(), 0);
+        crate::ensure_eq!(config.get_ack_stretch_segments(), DEFAULT_TCP_ACK_STRETCH_SEGMENTS);
+        crate::ensure_eq!(config.get_congestion_control_name(), "none");
+        crate::ensure_eq!(
+            config.get_initial_congestion_window(),
+            DEFAULT_INITIAL_CONGESTION_WINDOW
+        );
+        crate::ensure_eq!(config.get_learn_remote_link_addr(), false);
+        crate::ensure_eq!(config.get_timestamp_rtt_samples(), true);
+        crate::ensure_eq!(config.get_ecn(), false);
+        crate::ensure_eq!(config.get_max_receive_buffer_size(), 0);
+        crate::ensure_eq!(config.get_send_buffer_size(), 0);
+        crate::ensure_eq!(config.get_max_send_buffer_size(), 0);
+        crate::ensure_eq!(config.get_pmtu_blackhole_detection(), false);
+        crate::ensure_eq!(config.get_pacing(), false);
+        crate::ensure_eq!(config.get_max_accepts_per_poll(), 0);
+        crate::ensure_eq!(config.get_syn_cookies(), SynCookies::Off);
+        crate::ensure_eq!(config.get_max_syn_backlog(16), 16);
+        crate::ensure_eq!(config.get_backlog_overflow(), BacklogOverflow::Reset);
+        crate::ensure_eq!(config.get_max_time_wait(), DEFAULT_MAX_TIME_WAIT);
+        crate::ensure_eq!(config.get_msl(), MSL);
+        crate::ensure_eq!(config.get_fin_timeout(), TCP_FIN_TIMEOUT);
+        crate::ensure_eq!(config.get_time_wait_recycle(), TimeWaitRecycle::Timestamps);
+        crate::ensure_eq!(config.get_urgent_data(), UrgentData::Inline);
+
+        Ok(())
+    }
+    #[test]
+    fn test_tcp_config_send_mss() -> Result<()> {
+        // The configured MSS is advertised as long as it fits into the MTU.
+        let mut config: TcpConfig = TcpConfig::default();
+        config.advertised_mss = 1200;
+        config.mtu_mss = 1460;
+        crate::ensure_eq!(config.get_advertised_mss(), 1200);
+        crate::ensure_eq!(config.get_send_mss(1460), 1200);
+        crate::ensure_eq!(config.get_send_mss(1000), 1000);
+
+        // Otherwise, the MTU wins.
+        config.advertised_mss = 1500;
+        crate::ensure_eq!(config.get_advertised_mss(), 1460);
+        crate::ensure_eq!(config.get_send_mss(9000), 1460);
+
+        Ok(())
+    }
 
+    #[test]
+    fn test_tcp_config_syn_cookies() -> Result<()> {
+        crate::ensure_eq!(SynCookies::from_str("off")?, SynCookies::Off);
+        crate::ensure_eq!(SynCookies::from_str("under_pressure")?, SynCookies::UnderPressure);
+        crate::ensure_eq!(SynCookies::from_str("always")?, SynCookies::Always);
+        crate::ensure_eq!(SynCookies::from_str("sometimes").is_err(), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_config_backlog() -> Result<()> {
+        let mut config: TcpConfig = TcpConfig::default();
+        config.max_syn_backlog = 256;
+        crate::ensure_eq!(config.get_max_syn_backlog(16), 256);
+
+        crate::ensure_eq!(BacklogOverflow::from_str("reset")?, BacklogOverflow::Reset);
+        crate::ensure_eq!(BacklogOverflow::from_str("drop")?, BacklogOverflow::Drop);
+        crate::ensure_eq!(BacklogOverflow::from_str("ignore").is_err(), true);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_config_time_wait_recycle() -> Result<()> {
+        crate::ensure_eq!(TimeWaitRecycle::from_str("never")?, TimeWaitRecycle::Never);
+        crate::ensure_eq!(TimeWaitRecycle::from_str("timestamps")?, TimeWaitRecycle::Timestamps);
+        crate::ensure_eq!(TimeWaitRecycle::from_str("always")?, TimeWaitRecycle::Always);
+        crate::ensure_eq!(TimeWaitRecycle::from_str("sometimes").is_err(), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_config_urgent_data() -> Result<()> {
+        crate::ensure_eq!(UrgentData::from_str("inline")?, UrgentData::Inline);
+        crate::ensure_eq!(UrgentData::from_str("drop")?, UrgentData::Drop);
+        crate::ensure_eq!(UrgentData::from_str("reset")?, UrgentData::Reset);
+        crate::ensure_eq!(UrgentData::from_str("oob").is_err(), true);
         Ok(())
     }
 }
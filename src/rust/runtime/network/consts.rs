@@ -24,13 +24,28 @@ pub const MAX_MSS: usize = u16::max_value() as usize;
 /// See: https://www.rfc-editor.org/rfc/rfc793.txt
 pub const MSL: Duration = Duration::from_secs(2);
 
+/// How long a connection that we closed waits in FIN_WAIT_2 for our peer to close its end, before we give up on it.
+/// This is the default of `tcp_fin_timeout` on Linux.
+pub const TCP_FIN_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Delay timeout for TCP ACKs.
 /// See: https://www.rfc-editor.org/rfc/rfc5681#section-4.2
 pub const TCP_ACK_DELAY_TIMEOUT: Duration = Duration::from_millis(500);
 
+/// Default number of segments that are acknowledged by a single ACK.
+/// See: https://www.rfc-editor.org/rfc/rfc5681#section-4.2
+pub const DEFAULT_TCP_ACK_STRETCH_SEGMENTS: usize = 2;
+
 /// Handshake timeout for tcp.
 pub const TCP_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
 
+/// Default initial congestion window for TCP, in segments.
+/// See: https://www.rfc-editor.org/rfc/rfc6928
+pub const DEFAULT_INITIAL_CONGESTION_WINDOW: usize = 10;
+
+/// Default maximum number of TCP connections that are kept in TIME_WAIT.
+pub const DEFAULT_MAX_TIME_WAIT: usize = 16384;
+
 /// Default MSS Parameter for TCP
 ///
 /// TODO: Auto-Discovery MTU Size
@@ -31,6 +31,8 @@ use ::std::{
 
 pub struct SocketIdToQDescMap {
     mappings: HashMap<SocketId, QDesc>,
+    // Number of mappings for each local address, so that checking whether an address is in use does not scan them all.
+    local_addresses: HashMap<SocketAddrV4, usize>,
 }
 
 //======================================================================================================================
@@ -43,21 +45,36 @@ impl SocketIdToQDescMap {
     }
 
     pub fn insert(&mut self, socket_id: SocketId, qd: QDesc) -> Option<QDesc> {
-        self.mappings.insert(socket_id, qd)
+        let local: SocketAddrV4 = Self::get_local(&socket_id);
+        let old_qd: Option<QDesc> = self.mappings.insert(socket_id, qd);
+        if old_qd.is_none() {
+            *self.local_addresses.entry(local).or_insert(0) += 1;
+        }
+        old_qd
     }
 
     pub fn remove(&mut self, socket_id: &SocketId) -> Option<QDesc> {
-        self.mappings.remove(socket_id)
+        let qd: QDesc = self.mappings.remove(socket_id)?;
+        let local: SocketAddrV4 = Self::get_local(socket_id);
+        if let Some(count) = self.local_addresses.get_mut(&local) {
+            *count -= 1;
+            if *count == 0 {
+                self.local_addresses.remove(&local);
+            }
+        }
+        Some(qd)
     }
 
+    /// Checks whether a socket is bound to exactly `socket_addrv4`. The wildcard address and a specific address on the
+    /// same port do not conflict, as segments go to the most specific binding first.
     pub fn is_in_use(&self, socket_addrv4: SocketAddrV4) -> bool {
-        for (socket_id, _) in &self.mappings {
-            match socket_id {
-                SocketId::Passive(addr) | SocketId::Active(addr, _) if *addr == socket_addrv4 => return true,
-                _ => continue,
-            }
+        self.local_addresses.contains_key(&socket_addrv4)
+    }
+
+    fn get_local(socket_id: &SocketId) -> SocketAddrV4 {
+        match socket_id {
+            SocketId::Passive(local) | SocketId::Active(local, _) => *local,
         }
-        false
     }
 }
 
@@ -69,6 +86,7 @@ impl Default for SocketIdToQDescMap {
     fn default() -> Self {
         Self {
             mappings: HashMap::<SocketId, QDesc>::new(),
+            local_addresses: HashMap::<SocketAddrV4, usize>::new(),
         }
     }
 }
@@ -24,3 +24,13 @@ pub enum SocketId {
     Active(SocketAddrV4, SocketAddrV4),
     Passive(SocketAddrV4),
 }
+
+/// Flags that change how a pop behaves, like the flags of recv() do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PopFlags {
+    /// Return the data without removing it from the queue, so that the next pop returns it again (MSG_PEEK).
+    pub peek: bool,
+    /// Wait until as many bytes as the pop asked for have arrived, returning fewer only if the connection is closed or
+    /// fails first (MSG_WAITALL). Peeks do not wait for more than they otherwise would.
+    pub wait_all: bool,
+}
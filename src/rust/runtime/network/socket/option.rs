@@ -26,6 +26,11 @@ const DEFAULT_KEEP_ALIVE: tcp_keepalive = tcp_keepalive {
     keepaliveinterval: 1000,
 };
 const DEFAULT_NO_DELAY: bool = true;
+// Keepalive timers of Linux (see tcp(7)).
+const DEFAULT_KEEP_ALIVE_IDLE: Duration = Duration::from_secs(7200);
+const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(75);
+const DEFAULT_KEEP_ALIVE_COUNT: u32 = 9;
+const DEFAULT_RECEIVE_LOW_WATERMARK: usize = 1;
 
 //======================================================================================================================
 // Structures
@@ -34,16 +39,239 @@ const DEFAULT_NO_DELAY: bool = true;
 /// A listing of the SO_* socket options.
 #[derive(Debug, Clone, Copy)]
 pub enum SocketOption {
+    /// How long the close of a TCP socket waits for unsent and unacknowledged data to be acknowledged before it resets
+    /// the connection (SO_LINGER). Zero resets the connection right away.
     Linger(Option<Duration>),
     KeepAlive(KeepAlive),
+    /// Time that a connection must be idle before we start sending keepalive probes (TCP_KEEPIDLE).
+    KeepAliveIdle(Duration),
+    /// Time between keepalive probes (TCP_KEEPINTVL).
+    KeepAliveInterval(Duration),
+    /// Number of unanswered keepalive probes after which we give up on the connection (TCP_KEEPCNT).
+    KeepAliveCount(u32),
     NoDelay(bool),
+    /// Holds back partial segments on a TCP socket, so that small pushes go out as full-sized segments, until the socket
+    /// is uncorked or for up to 200 ms (TCP_CORK).
+    Cork(bool),
+    /// Paces the packets that are sent on a UDP socket. Setting this to `None` turns pacing off.
+    Pacing(Option<Pacing>),
+    /// Priority class of the packets that are sent on the socket.
+    Priority(PriorityClass),
+    /// Acknowledges received segments right away instead of delaying the ACK (TCP_QUICKACK).
+    QuickAck(QuickAck),
+    /// How long and for how many segments a TCP socket delays the ACKs for received data.
+    DelayedAck(DelayedAck),
+    /// Number of bytes that have to be buffered before a pop completes, unless the connection is closed (SO_RCVLOWAT).
+    ReceiveLowWatermark(usize),
+    /// Statistics of the connection on a TCP socket (TCP_INFO). This option can only be read.
+    TcpInfo(TcpInfo),
+    /// Name of the congestion control algorithm of a TCP socket (TCP_CONGESTION). The algorithm can only be chosen
+    /// before the socket connects or listens.
+    CongestionControl(&'static str),
+    /// Number of times that a connecting TCP socket retransmits its SYN before the connect fails with ETIMEDOUT
+    /// (TCP_SYNCNT).
+    SynRetries(u32),
+    /// How long a connecting TCP socket waits for an answer to each of its SYNs.
+    SynBackoff(SynBackoff),
+    /// Time after which the connect of a TCP socket fails with ETIMEDOUT, no matter how many SYN retransmissions are
+    /// left. `None` leaves it to the SYN retransmissions alone.
+    ConnectTimeout(Option<Duration>),
+    /// Takes connections to the foreign addresses that the stack intercepts, as transparent proxies do (IP_TRANSPARENT).
+    /// Only listening TCP sockets that are bound to the wildcard address take such connections.
+    Transparent(bool),
+    /// Takes connections to every port in the range on a listening TCP socket, not only to the port that the socket is
+    /// bound to, which must fall into the range. Accepted connections keep the port that they were made to as their
+    /// local port. The range can only be chosen before the socket listens.
+    ListenPorts(Option<PortRange>),
+    /// Keeps the congestion window of a TCP socket when it starts sending again after being idle, instead of
+    /// restarting from the initial window, as long as its segments are paced. Pacing already keeps the socket from
+    /// sending the whole window in one burst. This has no effect on sockets that are not paced.
+    KeepCwndAfterIdleWhenPaced(bool),
+}
+
+/// Inclusive range of ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub first: u16,
+    pub last: u16,
+}
+
+/// How long a connecting TCP socket waits for an answer to its SYN before it retransmits the SYN. The wait starts at
+/// `initial_timeout` and is multiplied by `multiplier` with every retransmission, up to `max_timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SynBackoff {
+    pub initial_timeout: Duration,
+    pub multiplier: u32,
+    pub max_timeout: Duration,
+}
+
+/// Snapshot of the state of a TCP connection, so that operators can debug its performance without a debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TcpInfo {
+    /// State of the connection.
+    pub state: TcpState,
+    /// Smoothed round-trip time, or `None` if no round-trip time was measured yet.
+    pub smoothed_rtt: Option<Duration>,
+    /// Current retransmission timeout.
+    pub rto: Duration,
+    /// Congestion window in bytes.
+    pub congestion_window: u32,
+    /// Slow start threshold in bytes. This is `u32::MAX` until the first congestion event, and for congestion control
+    /// algorithms that do not use a threshold.
+    pub slow_start_threshold: u32,
+    /// Number of segments that were retransmitted on the connection.
+    pub retransmitted_segments: u64,
+    /// Number of bytes that were sent but not acknowledged yet.
+    pub bytes_in_flight: u32,
+    /// Receive window that we advertise to our peer, in bytes.
+    pub receive_window: u32,
+    /// Maximum segment size that we send.
+    pub mss: usize,
+}
+
+/// States of an established TCP connection, from RFC 793.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TcpState {
+    #[default]
+    Established,
+    FinWait1,
+    FinWait2,
+    Closing,
+    TimeWait,
+    CloseWait,
+    LastAck,
+    Closed,
+}
+
+/// When a TCP socket acknowledges received segments right away, instead of waiting for more segments or for the
+/// delayed ACK timer. Request/response protocols use this so that the ACK for a request is not held back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuickAck {
+    /// ACKs are delayed as usual.
+    #[default]
+    Off,
+    /// Every segment is acknowledged right away.
+    Always,
+    /// The given number of data segments is acknowledged right away whenever our peer starts sending after the
+    /// connection has been idle, including when it starts sending for the first time.
+    AfterIdle(u32),
+}
+
+/// How a TCP socket delays the ACKs for received data, in the hope of piggybacking them on data that goes the other
+/// way or of acknowledging several segments at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DelayedAck {
+    /// ACKs are delayed as configured for the whole stack.
+    #[default]
+    Default,
+    /// Every segment is acknowledged right away.
+    Disabled,
+    /// ACKs are held back for up to `timeout`, until `segments` segments or full-sized segments worth of data have
+    /// arrived. Two segments is the standard of RFC 5681, which keeps Reno-style senders growing their congestion
+    /// window at the expected rate.
+    Enabled { timeout: Duration, segments: u32 },
+}
+
+/// Priority classes of outgoing traffic. Packets carry their class in the DSCP field of the IPv4 header, so both the
+/// NIC and the network can tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum PriorityClass {
+    /// Bulk traffic that may be held back in favor of everything else (CS1).
+    Background,
+    /// Regular traffic (CS0).
+    #[default]
+    BestEffort,
+    /// Traffic that should be sent ahead of everything else (EF).
+    LatencyCritical,
+}
+
+/// Rate at which a paced socket may transmit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacingRate {
+    PacketsPerSecond(u64),
+    /// Counts the bytes of payload, excluding protocol headers.
+    BytesPerSecond(u64),
+}
+
+/// Pacing of the packets that are sent on a socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pacing {
+    pub rate: PacingRate,
+    /// Number of packets or bytes, depending on the rate, that may be sent back-to-back after the socket has been idle.
+    pub burst: u64,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct TcpSocketOptions {
     linger: Option<Duration>,
     keep_alive: KeepAlive,
+    keep_alive_idle: Duration,
+    keep_alive_interval: Duration,
+    keep_alive_count: u32,
     no_delay: bool,
+    cork: bool,
+    priority: PriorityClass,
+    quick_ack: QuickAck,
+    delayed_ack: DelayedAck,
+    receive_low_watermark: usize,
+    congestion_control: Option<&'static str>,
+    syn_retries: Option<u32>,
+    syn_backoff: Option<SynBackoff>,
+    connect_timeout: Option<Duration>,
+    transparent: bool,
+    listen_ports: Option<PortRange>,
+    keep_cwnd_after_idle_when_paced: bool,
+}
+
+//======================================================================================================================
+// Associated Functions
+//======================================================================================================================
+
+impl PriorityClass {
+    /// Number of priority classes.
+    pub const COUNT: usize = 3;
+
+    /// Returns the DSCP code point that marks packets of this class.
+    pub fn dscp(&self) -> u8 {
+        match self {
+            PriorityClass::Background => 8,
+            PriorityClass::BestEffort => 0,
+            PriorityClass::LatencyCritical => 46,
+        }
+    }
+
+    /// Returns the class of a packet that is marked with the `dscp` code point. Code points that we do not use
+    /// ourselves are treated as best effort.
+    pub fn from_dscp(dscp: u8) -> Self {
+        match dscp {
+            8 => PriorityClass::Background,
+            46 => PriorityClass::LatencyCritical,
+            _ => PriorityClass::BestEffort,
+        }
+    }
+
+    /// Returns the index of this class, from the lowest priority to the highest one.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl SynBackoff {
+    /// Returns how long to wait for an answer to the SYN that follows one that went unanswered for [timeout].
+    pub fn next_timeout(&self, timeout: Duration) -> Duration {
+        timeout.saturating_mul(self.multiplier).min(self.max_timeout)
+    }
+}
+
+impl PortRange {
+    pub fn contains(&self, port: u16) -> bool {
+        self.first <= port && port <= self.last
+    }
+
+    /// Checks whether this range and [other] have any port in common.
+    pub fn overlaps(&self, other: &PortRange) -> bool {
+        self.first <= other.last && other.first <= self.last
+    }
 }
 
 impl TcpSocketOptions {
@@ -51,7 +279,22 @@ impl TcpSocketOptions {
         Ok(Self {
             linger: config.linger().unwrap_or(DEFAULT_LINGER),
             keep_alive: config.tcp_keepalive().unwrap_or(DEFAULT_KEEP_ALIVE),
+            keep_alive_idle: DEFAULT_KEEP_ALIVE_IDLE,
+            keep_alive_interval: DEFAULT_KEEP_ALIVE_INTERVAL,
+            keep_alive_count: DEFAULT_KEEP_ALIVE_COUNT,
             no_delay: config.no_delay().unwrap_or(DEFAULT_NO_DELAY),
+            cork: false,
+            priority: PriorityClass::default(),
+            quick_ack: QuickAck::default(),
+            delayed_ack: DelayedAck::default(),
+            receive_low_watermark: DEFAULT_RECEIVE_LOW_WATERMARK,
+            congestion_control: None,
+            syn_retries: None,
+            syn_backoff: None,
+            connect_timeout: None,
+            transparent: false,
+            listen_ports: None,
+            keep_cwnd_after_idle_when_paced: false,
         })
     }
 
@@ -71,6 +314,38 @@ impl TcpSocketOptions {
         self.keep_alive = keep_alive;
     }
 
+    /// Checks whether keepalive probes are turned on.
+    pub fn is_keepalive_enabled(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        return self.keep_alive;
+        #[cfg(target_os = "windows")]
+        return self.keep_alive.onoff != 0;
+    }
+
+    pub fn get_keepalive_idle(&self) -> Duration {
+        self.keep_alive_idle
+    }
+
+    pub fn set_keepalive_idle(&mut self, idle: Duration) {
+        self.keep_alive_idle = idle;
+    }
+
+    pub fn get_keepalive_interval(&self) -> Duration {
+        self.keep_alive_interval
+    }
+
+    pub fn set_keepalive_interval(&mut self, interval: Duration) {
+        self.keep_alive_interval = interval;
+    }
+
+    pub fn get_keepalive_count(&self) -> u32 {
+        self.keep_alive_count
+    }
+
+    pub fn set_keepalive_count(&mut self, count: u32) {
+        self.keep_alive_count = count;
+    }
+
     pub fn get_nodelay(&self) -> bool {
         self.no_delay
     }
@@ -78,6 +353,106 @@ impl TcpSocketOptions {
     pub fn set_nodelay(&mut self, nodelay: bool) {
         self.no_delay = nodelay;
     }
+
+    pub fn get_cork(&self) -> bool {
+        self.cork
+    }
+
+    pub fn set_cork(&mut self, cork: bool) {
+        self.cork = cork;
+    }
+
+    pub fn get_priority(&self) -> PriorityClass {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: PriorityClass) {
+        self.priority = priority;
+    }
+
+    pub fn get_quickack(&self) -> QuickAck {
+        self.quick_ack
+    }
+
+    pub fn set_quickack(&mut self, quick_ack: QuickAck) {
+        self.quick_ack = quick_ack;
+    }
+
+    pub fn get_delayed_ack(&self) -> DelayedAck {
+        self.delayed_ack
+    }
+
+    pub fn set_delayed_ack(&mut self, delayed_ack: DelayedAck) {
+        self.delayed_ack = delayed_ack;
+    }
+
+    pub fn get_receive_low_watermark(&self) -> usize {
+        self.receive_low_watermark
+    }
+
+    /// Sets the number of bytes that pops wait for. Like on Linux, zero is taken as one.
+    pub fn set_receive_low_watermark(&mut self, bytes: usize) {
+        self.receive_low_watermark = bytes.max(1);
+    }
+
+    /// Returns the name of the congestion control algorithm that was chosen for the socket, if any.
+    pub fn get_congestion_control(&self) -> Option<&'static str> {
+        self.congestion_control
+    }
+
+    pub fn set_congestion_control(&mut self, name: &'static str) {
+        self.congestion_control = Some(name);
+    }
+
+    /// Returns the number of SYN retransmissions that was chosen for the socket, if any.
+    pub fn get_syn_retries(&self) -> Option<u32> {
+        self.syn_retries
+    }
+
+    pub fn set_syn_retries(&mut self, retries: u32) {
+        self.syn_retries = Some(retries);
+    }
+
+    /// Returns the SYN backoff that was chosen for the socket, if any.
+    pub fn get_syn_backoff(&self) -> Option<SynBackoff> {
+        self.syn_backoff
+    }
+
+    pub fn set_syn_backoff(&mut self, backoff: SynBackoff) {
+        self.syn_backoff = Some(backoff);
+    }
+
+    pub fn get_connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    pub fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect_timeout = timeout;
+    }
+
+    pub fn get_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+    }
+
+    pub fn get_listen_ports(&self) -> Option<PortRange> {
+        self.listen_ports
+    }
+
+    pub fn set_listen_ports(&mut self, ports: Option<PortRange>) {
+        self.listen_ports = ports;
+    }
+
+    pub fn get_keep_cwnd_after_idle_when_paced(&self) -> bool {
+        self.keep_cwnd_after_idle_when_paced
+    }
+
+    pub fn set_keep_cwnd_after_idle_when_paced(&mut self, keep_cwnd: bool) {
+        self.keep_cwnd_after_idle_when_paced = keep_cwnd;
+    }
 }
 
 impl Default for TcpSocketOptions {
@@ -85,7 +460,22 @@ impl Default for TcpSocketOptions {
         Self {
             linger: DEFAULT_LINGER,
             keep_alive: DEFAULT_KEEP_ALIVE,
+            keep_alive_idle: DEFAULT_KEEP_ALIVE_IDLE,
+            keep_alive_interval: DEFAULT_KEEP_ALIVE_INTERVAL,
+            keep_alive_count: DEFAULT_KEEP_ALIVE_COUNT,
             no_delay: DEFAULT_NO_DELAY,
+            cork: false,
+            priority: PriorityClass::default(),
+            quick_ack: QuickAck::default(),
+            delayed_ack: DelayedAck::default(),
+            receive_low_watermark: DEFAULT_RECEIVE_LOW_WATERMARK,
+            congestion_control: None,
+            syn_retries: None,
+            syn_backoff: None,
+            connect_timeout: None,
+            transparent: false,
+            listen_ports: None,
+            keep_cwnd_after_idle_when_paced: false,
         }
     }
 }
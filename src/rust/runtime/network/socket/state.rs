@@ -10,6 +10,7 @@ use crate::{
     runtime::{fail::Fail, network::socket::operation::SocketOp},
 };
 use ::socket2::Type;
+use ::std::net::SocketAddr;
 
 //======================================================================================================================
 // Structures
@@ -110,6 +111,34 @@ impl SocketStateMachine {
         Ok(())
     }
 
+    /// Checks whether data may be pushed to `remote`, or to the connected peer if `remote` is not given. Stream sockets
+    /// never take a destination, because we do not support connecting implicitly, and datagram sockets need one
+    /// unless they are connected.
+    pub fn may_push_to(&self, remote: Option<SocketAddr>) -> Result<(), Fail> {
+        self.ensure_not_closing()?;
+        self.ensure_not_closed()?;
+
+        let connected: bool = self.current.get() == SocketState::ActiveEstablished;
+        match (self.typ, remote) {
+            (Type::STREAM, Some(remote)) if connected => {
+                let cause: String = format!("socket is already connected, use push instead (remote={:?})", remote);
+                error!("may_push_to(): {}", cause);
+                Err(Fail::new(libc::EISCONN, &cause))
+            },
+            (Type::STREAM, Some(remote)) => {
+                let cause: String = format!("cannot connect implicitly on push (remote={:?})", remote);
+                error!("may_push_to(): {}", cause);
+                Err(Fail::new(libc::EOPNOTSUPP, &cause))
+            },
+            (Type::DGRAM, None) if !connected => {
+                let cause: String = format!("socket is not connected, use pushto instead");
+                error!("may_push_to(): {}", cause);
+                Err(Fail::new(libc::EDESTADDRREQ, &cause))
+            },
+            _ => self.may_push(),
+        }
+    }
+
     pub async fn while_may_push(&mut self) -> Fail {
         loop {
             match self.may_push() {
@@ -129,7 +158,7 @@ impl SocketStateMachine {
 
         if self.typ == Type::STREAM {
             self.ensure_established()?;
-        } else {
+        } else if self.current.get() != SocketState::ActiveEstablished {
             self.ensure_bound()?;
         }
 
@@ -151,6 +180,15 @@ impl SocketStateMachine {
         }
     }
 
+    /// Checks whether the connection of this socket may be shut down in one or both directions, which requires the
+    /// socket to be connected.
+    pub fn may_shutdown(&self) -> Result<(), Fail> {
+        self.ensure_not_closing()?;
+        self.ensure_not_closed()?;
+        self.ensure_established()?;
+        Ok(())
+    }
+
     pub fn commit(&mut self) {
         let current: SocketState = self.current.get();
         self.current.set(self.next.unwrap_or(current));
@@ -270,10 +308,11 @@ impl SocketStateMachine {
         }
     }
 
-    /// Attempts to transition from closing state.
+    /// Attempts to transition from closing state. A socket is closed once, so a second close fails right away instead of
+    /// handing out another queue token for the close that is already running.
     fn closing_state(&self, op: SocketOp) -> Result<SocketState, Fail> {
         match op {
-            SocketOp::Close => Ok(SocketState::Closing),
+            SocketOp::Close => Err(fail(op, &(format!("socket is already closing")), libc::EALREADY)),
             SocketOp::Closed => Ok(SocketState::Closed),
             _ => Err(fail(op, &(format!("socket is closing")), libc::EBADF)),
         }
@@ -8,13 +8,13 @@
 use crate::runtime::{
     fail::Fail,
     memory::{DemiBuffer, MemoryRuntime},
-    network::socket::option::SocketOption,
+    network::socket::{option::SocketOption, PopFlags},
     SharedDemiRuntime,
 };
 use ::socket2::{Domain, Type};
 use ::std::{
     fmt::Debug,
-    net::{SocketAddr, SocketAddrV4},
+    net::{Shutdown, SocketAddr, SocketAddrV4},
 };
 
 //======================================================================================================================
@@ -42,16 +42,36 @@ pub trait NetworkTransport: Clone + 'static + MemoryRuntime {
 
     fn getpeername(&mut self, sd: &mut Self::SocketDescriptor) -> Result<SocketAddrV4, Fail>;
 
+    /// Gets the local address of the socket, which is a foreign address for connections that transparent sockets
+    /// accept.
+    fn getsockname(&mut self, sd: &mut Self::SocketDescriptor) -> Result<SocketAddrV4, Fail>;
+
     /// Bind an address to the socket.
     fn bind(&mut self, sd: &mut Self::SocketDescriptor, local: SocketAddr) -> Result<(), Fail>;
 
     /// Listen on this socket in the network transport layer.
     fn listen(&mut self, sd: &mut Self::SocketDescriptor, backlog: usize) -> Result<(), Fail>;
 
+    /// Shut down the send direction, the receive direction or both directions of a connected socket, without closing
+    /// it.
+    fn shutdown(&mut self, sd: &mut Self::SocketDescriptor, how: Shutdown) -> Result<(), Fail>;
+
+    /// Close this socket right away, resetting its connection instead of shutting it down gracefully.
+    fn abort(&mut self, sd: &mut Self::SocketDescriptor) -> Result<(), Fail>;
+
     /// Forcibly close this socket in the network transport layer. This function should only be used in Drop and other
     /// internal functions, never exposed to the application.
     fn hard_close(&mut self, sd: &mut Self::SocketDescriptor) -> Result<(), Fail>;
 
+    /// Checks, without blocking, whether a listening socket has connections that are waiting to be accepted.
+    fn has_pending_connections(&mut self, sd: &mut Self::SocketDescriptor) -> Result<bool, Fail>;
+
+    /// Returns the most recent error that affected this socket and clears it, like SO_ERROR does.
+    fn get_last_error(&mut self, sd: &mut Self::SocketDescriptor) -> Result<Option<Fail>, Fail>;
+
+    /// Returns the number of bytes that can be popped from a connected socket without waiting, like FIONREAD does.
+    fn get_buffered_bytes(&mut self, sd: &mut Self::SocketDescriptor) -> Result<usize, Fail>;
+
     /// Asynchronously accept a new connection on a listening socket.
     fn accept(
         &mut self,
@@ -73,11 +93,19 @@ pub trait NetworkTransport: Clone + 'static + MemoryRuntime {
         addr: Option<SocketAddr>,
     ) -> impl std::future::Future<Output = Result<(), Fail>>;
 
-    /// Pop data from a connected socket.
+    /// Wait until at least [min_bytes] can be pushed to the socket without queueing behind flow or congestion control.
+    fn wait_for_writable(
+        &mut self,
+        sd: &mut Self::SocketDescriptor,
+        min_bytes: usize,
+    ) -> impl std::future::Future<Output = Result<(), Fail>>;
+
+    /// Pop data from a connected socket. [flags] may ask to leave the data in place for the next pop.
     fn pop(
         &mut self,
         sd: &mut Self::SocketDescriptor,
         size: usize,
+        flags: PopFlags,
     ) -> impl std::future::Future<Output = Result<(Option<SocketAddr>, DemiBuffer), Fail>>;
 
     /// Asynchronously close a socket.
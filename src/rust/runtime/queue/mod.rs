@@ -151,6 +151,13 @@ impl IoQueueTable {
         Ok(downcast_queue::<T>(self.table.remove(index as usize))?)
     }
 
+    /// Gets an iterator over the descriptors of all registered queues.
+    pub fn get_qds(&self) -> impl Iterator<Item = QDesc> + '_ {
+        self.table
+            .iter()
+            .map(|(index, _)| QDesc::from((index as u32) + Self::BASE_QD))
+    }
+
     /// Gets an iterator over all registered queues.
     pub fn get_values(&self) -> Iter<'_, Box<dyn IoQueue>> {
         self.table.iter()
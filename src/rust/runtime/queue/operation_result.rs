@@ -17,8 +17,11 @@ pub enum OperationResult {
     Connect,
     Accept((QDesc, SocketAddrV4)),
     Push,
-    Pop(Option<SocketAddrV4>, DemiBuffer),
+    /// The popped data, where it came from and how many bytes are still buffered on the queue.
+    Pop(Option<SocketAddrV4>, DemiBuffer, usize),
     Close,
+    /// The queue can take at least as many bytes as were asked for without queueing them.
+    Writable,
     Failed(Fail),
 }
 
@@ -34,6 +37,7 @@ impl fmt::Debug for OperationResult {
             OperationResult::Push => write!(f, "Push"),
             OperationResult::Pop(..) => write!(f, "Pop"),
             OperationResult::Close => write!(f, "Close"),
+            OperationResult::Writable => write!(f, "Writable"),
             OperationResult::Failed(ref e) => write!(f, "Failed({:?})", e),
         }
     }
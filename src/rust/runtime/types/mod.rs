@@ -6,6 +6,7 @@
 mod memory;
 mod ops;
 mod queue;
+mod socket;
 
 //======================================================================================================================
 // Exports
@@ -13,8 +14,9 @@ mod queue;
 
 pub use self::{
     memory::{demi_sgarray_t, demi_sgaseg_t, DEMI_SGARRAY_MAXLEN},
-    ops::{demi_accept_result_t, demi_opcode_t, demi_qr_value_t, demi_qresult_t},
+    ops::{demi_accept_result_t, demi_linked_op_t, demi_opcode_t, demi_qr_value_t, demi_qresult_t, DEMI_LINKED_QD},
     queue::demi_qtoken_t,
+    socket::demi_tcp_info_t,
 };
 
 //======================================================================================================================
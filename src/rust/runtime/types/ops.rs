@@ -7,6 +7,13 @@
 
 use crate::runtime::types::{memory::demi_sgarray_t, queue::demi_qtoken_t};
 
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+/// Queue descriptor of a linked operation that runs on the queue of the operation before it.
+pub const DEMI_LINKED_QD: i32 = -1;
+
 //======================================================================================================================
 // Structures
 //======================================================================================================================
@@ -22,6 +29,7 @@ pub enum demi_opcode_t {
     DEMI_OPC_CONNECT,
     DEMI_OPC_CLOSE,
     DEMI_OPC_FAILED,
+    DEMI_OPC_WRITABLE,
 }
 
 #[repr(C, packed)]
@@ -29,6 +37,7 @@ pub enum demi_opcode_t {
 pub struct demi_accept_result_t {
     pub qd: i32,
     pub addr: libc::sockaddr,
+    pub local: libc::sockaddr,
 }
 
 #[repr(C)]
@@ -37,6 +46,14 @@ pub union demi_qr_value_t {
     pub ares: demi_accept_result_t,
 }
 
+/// Operation of a linked chain
+#[repr(C)]
+pub struct demi_linked_op_t {
+    pub op_opcode: demi_opcode_t,
+    pub op_qd: i32,
+    pub op_sga: demi_sgarray_t,
+}
+
 /// Result
 #[repr(C)]
 pub struct demi_qresult_t {
@@ -65,7 +82,7 @@ mod test {
         // Size of a sockaddr structure.
         const ADDR_SIZE: usize = 16;
         // Size of a demi_accept_result_t structure.
-        crate::ensure_eq!(mem::size_of::<demi_accept_result_t>(), QD_SIZE + ADDR_SIZE);
+        crate::ensure_eq!(mem::size_of::<demi_accept_result_t>(), QD_SIZE + 2 * ADDR_SIZE);
         Ok(())
     }
 
@@ -94,10 +111,10 @@ mod test {
         const QR_RET_SIZE: usize = 8;
         // Size of a demi_qr_value_t structure.
         const QR_VALUE_SIZE: usize = mem::size_of::<demi_qr_value_t>();
-        // Size of a demi_qresult_t structure.
+        // Size of a demi_qresult_t structure, which is padded up to the alignment of its 64-bit fields.
         crate::ensure_eq!(
             mem::size_of::<demi_qresult_t>(),
-            QR_OPCODE_SIZE + QR_QD_SIZE + QR_QT_SIZE + QR_RET_SIZE + QR_VALUE_SIZE
+            (QR_OPCODE_SIZE + QR_QD_SIZE + QR_QT_SIZE + QR_RET_SIZE + QR_VALUE_SIZE).next_multiple_of(QR_QT_SIZE)
         );
         Ok(())
     }
@@ -0,0 +1,79 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use crate::runtime::network::socket::option::{TcpInfo, TcpState};
+
+//======================================================================================================================
+// Structures
+//======================================================================================================================
+
+/// Statistics of a TCP connection, as returned by demi_getsockopt(TCP_INFO).
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct demi_tcp_info_t {
+    /// State of the connection, numbered like the TCP_* states of Linux.
+    pub ti_state: u8,
+    /// Smoothed round-trip time in microseconds, or zero if no round-trip time was measured yet.
+    pub ti_srtt_us: u32,
+    pub ti_rto_us: u32,
+    pub ti_snd_cwnd: u32,
+    pub ti_snd_ssthresh: u32,
+    pub ti_total_retrans: u64,
+    pub ti_bytes_in_flight: u32,
+    pub ti_rcv_wnd: u32,
+    pub ti_snd_mss: u32,
+}
+
+//======================================================================================================================
+// Trait Implementations
+//======================================================================================================================
+
+impl From<TcpInfo> for demi_tcp_info_t {
+    fn from(info: TcpInfo) -> Self {
+        let state: u8 = match info.state {
+            TcpState::Established => 1,
+            TcpState::FinWait1 => 4,
+            TcpState::FinWait2 => 5,
+            TcpState::TimeWait => 6,
+            TcpState::Closed => 7,
+            TcpState::CloseWait => 8,
+            TcpState::LastAck => 9,
+            TcpState::Closing => 11,
+        };
+        Self {
+            ti_state: state,
+            ti_srtt_us: info
+                .smoothed_rtt
+                .map_or(0, |srtt| srtt.as_micros().min(u32::MAX as u128) as u32),
+            ti_rto_us: info.rto.as_micros().min(u32::MAX as u128) as u32,
+            ti_snd_cwnd: info.congestion_window,
+            ti_snd_ssthresh: info.slow_start_threshold,
+            ti_total_retrans: info.retransmitted_segments,
+            ti_bytes_in_flight: info.bytes_in_flight,
+            ti_rcv_wnd: info.receive_window,
+            ti_snd_mss: info.mss.min(u32::MAX as usize) as u32,
+        }
+    }
+}
+
+//======================================================================================================================
+// Unit Tests
+//======================================================================================================================
+
+#[cfg(test)]
+mod test {
+    use crate::runtime::types::socket::*;
+    use ::std::mem;
+
+    /// Tests if `demi_tcp_info_t` has the expected size.
+    #[test]
+    fn test_size_demi_tcp_info_t() -> Result<(), anyhow::Error> {
+        // Size of the state, of seven u32 fields and of the u64 retransmission count.
+        crate::ensure_eq!(mem::size_of::<demi_tcp_info_t>(), 1 + 7 * 4 + 8);
+        Ok(())
+    }
+}
@@ -25,6 +25,16 @@
  */
 #define MAX(a, b) (((a) > (b)) ? (a) : (b))
 
+/**
+ * @brief Rounds 'a' up to the next multiple of 'b'.
+ *
+ * @param a Value to round.
+ * @param b Alignment to round to.
+ *
+ * @returns 'a' rounded up to the next multiple of 'b'.
+ */
+#define ALIGN_UP(a, b) ((((a) + (b) - 1) / (b)) * (b))
+
 /**
  * @brief Asserts if 'a' and 'b' agree on size.
  *
@@ -55,13 +65,14 @@
 #define DEMI_SGARRAY_T_SIZE (SGA_BUF_SIZE + SGA_NUMSEGS_SIZE + SGA_SEGS_SIZE + SGA_ADDR_SIZE)
 #define QD_SIZE 4
 #define SADDR_SIZE 16
-#define DEMI_ACCEPT_RESULT_T_SIZE (QD_SIZE + SADDR_SIZE)
+#define DEMI_ACCEPT_RESULT_T_SIZE (QD_SIZE + 2 * SADDR_SIZE)
 #define QR_OPCODE_SIZE 4
 #define QR_QD_SIZE 4
 #define QR_QT_SIZE 8
 #define QR_RET_SIZE 8
 #define QR_VALUE_SIZE (MAX(DEMI_ACCEPT_RESULT_T_SIZE, DEMI_SGARRAY_T_SIZE))
-#define DEMI_QRESULT_T_SIZE (QR_OPCODE_SIZE + QR_QD_SIZE + QR_QT_SIZE + QR_RET_SIZE + QR_VALUE_SIZE)
+#define DEMI_QRESULT_T_SIZE \
+    ALIGN_UP(QR_OPCODE_SIZE + QR_QD_SIZE + QR_QT_SIZE + QR_RET_SIZE + QR_VALUE_SIZE, QR_QT_SIZE)
 #define DEMI_ARGS_ARGC_SIZE 4
 #define DEMI_ARGS_ARGV_SIZE 8
 #define DEMI_ARGS_CALLBACK_SIZE 8
@@ -89,6 +89,38 @@ static bool inval_close(void)
     return (demi_close(qd) != 0);
 }
 
+/**
+ * @brief Issues an invalid call to demi_async_close().
+ */
+static bool inval_async_close(void)
+{
+    demi_qtoken_t *qt = NULL;
+    int qd = -1;
+
+    return (demi_async_close(qt, qd) != 0);
+}
+
+/**
+ * @brief Issues an invalid call to demi_shutdown().
+ */
+static bool inval_shutdown(void)
+{
+    int qd = -1;
+    int how = -1;
+
+    return (demi_shutdown(qd, how) != 0);
+}
+
+/**
+ * @brief Issues an invalid call to demi_abort().
+ */
+static bool inval_abort(void)
+{
+    int qd = -1;
+
+    return (demi_abort(qd) != 0);
+}
+
 /**
  * @brief Issues an invalid call to demi_push().
  */
@@ -126,6 +158,41 @@ static bool inval_pop(void)
     return (demi_pop(qt, qd) != 0);
 }
 
+/**
+ * @brief Issues an invalid call to demi_pop_flags().
+ */
+static bool inval_pop_flags(void)
+{
+    demi_qtoken_t *qt = NULL;
+    int qd = -1;
+
+    return (demi_pop_flags(qt, qd, MSG_PEEK) != 0);
+}
+
+/**
+ * @brief Issues an invalid call to demi_splice().
+ */
+static bool inval_splice(void)
+{
+    demi_qtoken_t *qt = NULL;
+    int src_qd = -1;
+    int dst_qd = -1;
+
+    return (demi_splice(qt, src_qd, dst_qd) != 0);
+}
+
+/**
+ * @brief Issues an invalid call to demi_submit_linked().
+ */
+static bool inval_submit_linked(void)
+{
+    demi_qtoken_t *qts = NULL;
+    const demi_linked_op_t *ops = NULL;
+    int num_ops = -1;
+
+    return (demi_submit_linked(qts, ops, num_ops) != 0);
+}
+
 /**
  * @brief Issues an invalid call to demi_setsockopt().
  */
@@ -154,6 +221,17 @@ static bool inval_getsockopt(void)
     return (demi_getsockopt(qd, level, optname, optval, optlen) != 0);
 }
 
+/**
+ * @brief Issues an invalid call to demi_has_pending_connections().
+ */
+static bool inval_has_pending_connections(void)
+{
+    int qd = -1;
+    int pending = 0;
+
+    return (demi_has_pending_connections(qd, &pending) != 0);
+}
+
 /*
 * @brief Issues an invalid call to getpeername().
 */
@@ -219,6 +297,40 @@ static bool inval_wait_any(void)
     return (demi_wait_any(qr, ready_offset, qts, num_qts, timeout) != 0);
 }
 
+/**
+ * @brief Issues an invalid system call to demi_completion_ring_create().
+ */
+static bool inval_completion_ring_create(void)
+{
+    demi_completion_ring_t **ring_out = NULL;
+    size_t capacity = 3;
+
+    return (demi_completion_ring_create(ring_out, capacity) != 0);
+}
+
+/**
+ * @brief Issues an invalid system call to demi_wait_into_ring().
+ */
+static bool inval_wait_into_ring(void)
+{
+    demi_completion_ring_t *ring = NULL;
+    int *num_qrs_out = NULL;
+    struct timespec *timeout = NULL;
+
+    return (demi_wait_into_ring(ring, num_qrs_out, timeout) != 0);
+}
+
+/**
+ * @brief Issues an invalid system call to demi_completion_ring_pop().
+ */
+static bool inval_completion_ring_pop(void)
+{
+    demi_qresult_t *qr = NULL;
+    demi_completion_ring_t *ring = NULL;
+
+    return (demi_completion_ring_pop(qr, ring) != 0);
+}
+
 #pragma GCC diagnostic pop
 
 /*===================================================================================================================*
@@ -242,7 +354,14 @@ static struct test tests_libos[] = {{inval_socket, "invalid demi_socket()"},   {
                                     {inval_connect, "invalid demi_connect()"}, {inval_listen, "invalid demi_listen()"},
                                     {inval_pop, "invalid demi_pop()"},         {inval_push, "invalid demi_push()"},
                                     {inval_pushto, "invalid demi_pushto()"},   {inval_getpeername, "invalid demi_getpeername()"},
-                                    {inval_setsockopt, "invalid demi_setsockopt()"}, {inval_getsockopt, "invalid demi_getsockopt()}"}};
+                                    {inval_setsockopt, "invalid demi_setsockopt()"}, {inval_getsockopt, "invalid demi_getsockopt()}"},
+                                    {inval_has_pending_connections, "invalid demi_has_pending_connections()"},
+                                    {inval_shutdown, "invalid demi_shutdown()"},
+                                    {inval_abort, "invalid demi_abort()"},
+                                    {inval_async_close, "invalid demi_async_close()"},
+                                    {inval_pop_flags, "invalid demi_pop_flags()"},
+                                    {inval_splice, "invalid demi_splice()"},
+                                    {inval_submit_linked, "invalid demi_submit_linked()"}};
 
 /**
  * @brief Tests for system calls in demi/sga.h
@@ -253,7 +372,11 @@ static struct test tests_sga[] = {{inval_sgaalloc, "invalid demi_sgaalloc()"},
 /**
  * @brief Tests for system calls in demi/wait.h
  */
-static struct test tests_wait[] = {{inval_wait, "invalid demi_wait()"}, {inval_wait_any, "invalid demi_wait_any()"}};
+static struct test tests_wait[] = {{inval_wait, "invalid demi_wait()"},
+                                   {inval_wait_any, "invalid demi_wait_any()"},
+                                   {inval_completion_ring_create, "invalid demi_completion_ring_create()"},
+                                   {inval_wait_into_ring, "invalid demi_wait_into_ring()"},
+                                   {inval_completion_ring_pop, "invalid demi_completion_ring_pop()"}};
 
 /**
  * @brief Drives the application.
@@ -5,7 +5,7 @@
 // Imports
 //======================================================================================================================
 
-use crate::common::runtime::SharedDummyRuntime;
+use crate::common::runtime::{SharedDummyRuntime, TxBufferPool};
 use ::crossbeam_channel::{Receiver, Sender};
 use ::demikernel::{
     demi_sgarray_t,
@@ -54,6 +54,24 @@ impl DummyLibOS {
         Ok(Self(SharedNetworkLibOS::<SharedInetStack>::new(runtime, transport)))
     }
 
+    /// Initializes the libOS, taking the buffers for outgoing packets from `tx_pool`.
+    #[allow(dead_code)]
+    pub fn new_test_with_tx_pool(
+        config_path: &str,
+        tx: Sender<DemiBuffer>,
+        rx: Receiver<DemiBuffer>,
+        tx_pool: TxBufferPool,
+    ) -> Result<Self, Fail> {
+        let config: Config = Config::new(config_path.to_string())?;
+        let runtime: SharedDemiRuntime = SharedDemiRuntime::default();
+        let mut network: SharedDummyRuntime = SharedDummyRuntime::new(rx, tx);
+        network.set_tx_pool(tx_pool);
+
+        logging::initialize();
+        let transport = SharedInetStack::new_test(&config, runtime.clone(), network)?;
+        Ok(Self(SharedNetworkLibOS::<SharedInetStack>::new(runtime, transport)))
+    }
+
     pub fn prepare_dummy_buffer(&self, size: usize) -> Result<demi_sgarray_t, Fail> {
         let fill_char: u8 = b'a';
 
@@ -7,20 +7,22 @@
 
 use ::arrayvec::ArrayVec;
 use ::demikernel::{
-    demi_sgarray_t, demi_sgaseg_t,
+    demi_sgarray_t,
     inetstack::protocols::{layer1::PhysicalLayer, MAX_HEADER_SIZE},
     runtime::{
         fail::Fail,
-        memory::{DemiBuffer, MemoryRuntime},
+        memory::{sgarray_from_buffer, DemiBuffer, MemoryRuntime},
         network::consts::RECEIVE_BATCH_SIZE,
         SharedObject,
     },
 };
-use ::libc::c_void;
 use ::log::error;
 use ::std::{
-    mem,
     ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 //======================================================================================================================
@@ -35,8 +37,15 @@ pub struct DummyRuntime {
     incoming: crossbeam_channel::Receiver<DemiBuffer>,
     /// Outgoing Queue of Packets
     outgoing: crossbeam_channel::Sender<DemiBuffer>,
+    /// Pool of Transmit Buffers
+    tx_pool: Option<TxBufferPool>,
 }
 
+/// Pool of buffers for outgoing packets. Every transmitted packet takes one buffer out of the pool, so tests can
+/// exhaust it on purpose.
+#[derive(Clone)]
+pub struct TxBufferPool(Arc<AtomicUsize>);
+
 #[derive(Clone)]
 
 /// Shared Dummy Runtime
@@ -53,7 +62,42 @@ impl SharedDummyRuntime {
         incoming: crossbeam_channel::Receiver<DemiBuffer>,
         outgoing: crossbeam_channel::Sender<DemiBuffer>,
     ) -> Self {
-        Self(SharedObject::new(DummyRuntime { incoming, outgoing }))
+        Self(SharedObject::new(DummyRuntime {
+            incoming,
+            outgoing,
+            tx_pool: None,
+        }))
+    }
+
+    /// Takes the buffers for outgoing packets from `tx_pool`.
+    pub fn set_tx_pool(&mut self, tx_pool: TxBufferPool) {
+        self.tx_pool = Some(tx_pool);
+    }
+}
+
+/// Associate Functions for Transmit Buffer Pool
+#[allow(dead_code)]
+impl TxBufferPool {
+    /// Creates a pool with `size` buffers.
+    pub fn new(size: usize) -> Self {
+        Self(Arc::new(AtomicUsize::new(size)))
+    }
+
+    /// Takes all buffers out of the pool.
+    pub fn drain(&self) {
+        self.0.store(0, Ordering::SeqCst);
+    }
+
+    /// Puts `size` buffers back into the pool.
+    pub fn refill(&self, size: usize) {
+        self.0.fetch_add(size, Ordering::SeqCst);
+    }
+
+    /// Takes a buffer out of the pool, if there is any left.
+    fn try_take(&self) -> bool {
+        self.0
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |size| size.checked_sub(1))
+            .is_ok()
     }
 }
 
@@ -68,7 +112,14 @@ impl PhysicalLayer for SharedDummyRuntime {
         // For this test harness, we 2^16 bytes (u16::MAX) as our limit.
         assert!(pkt.len() < u16::MAX as usize);
 
-        match self.outgoing.try_send(pkt) {
+        if let Some(tx_pool) = self.tx_pool.as_ref() {
+            if !tx_pool.try_take() {
+                return Err(Fail::new(libc::ENOBUFS, "no buffers left for outgoing packet"));
+            }
+        }
+
+        // Like a NIC, deliver a buffer chain as a single frame.
+        match self.outgoing.try_send(pkt.coalesce(0)?) {
             Ok(_) => Ok(()),
             Err(_) => Err(Fail::new(
                 libc::EAGAIN,
@@ -107,20 +158,8 @@ impl MemoryRuntime for SharedDummyRuntime {
         // Always allocate with header space for now even if we do not need it.
         let buf: DemiBuffer = DemiBuffer::new_with_headroom(size as u16, MAX_HEADER_SIZE as u16);
 
-        // Create a scatter-gather segment to expose the DemiBuffer to the user.
-        let data: *const u8 = buf.as_ptr();
-        let sga_seg: demi_sgaseg_t = demi_sgaseg_t {
-            sgaseg_buf: data as *mut c_void,
-            sgaseg_len: size as u32,
-        };
-
         // Create and return a new scatter-gather array (which inherits the DemiBuffer's reference).
-        Ok(demi_sgarray_t {
-            sga_buf: buf.into_raw().as_ptr() as *mut c_void,
-            sga_numsegs: 1,
-            sga_segs: [sga_seg],
-            sga_addr: unsafe { mem::zeroed() },
-        })
+        sgarray_from_buffer(buf)
     }
 }
 
@@ -77,58 +77,49 @@ fn async_close_and_wait_twice_1(libos: &mut LibOS) -> Result<()> {
     }
 }
 
-/// Attempt to asynchronously close and wait on a TCP socket multiple times.
+/// Attempts to asynchronously close a TCP socket again while the first close is still running.
 fn async_close_and_wait_twice_2(libos: &mut LibOS) -> Result<()> {
     let sockqd: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
-    let qt1: QToken = libos.async_close(sockqd)?;
-    let qt2: Option<QToken> = match libos.async_close(sockqd) {
-        Ok(qt) => Some(qt),
-        Err(e) if e.errno == libc::EBADF => None,
-        Err(e) => anyhow::bail!("async_close() should not fail with {:?}", &e),
-    };
+    let qt: QToken = libos.async_close(sockqd)?;
+
+    // Fail to close the socket again, as it is already closing.
+    match libos.async_close(sockqd) {
+        Err(e) if e.errno == libc::EALREADY => {},
+        Err(e) => anyhow::bail!("async_close() failed with {}", e),
+        Ok(_) => anyhow::bail!("async_close() a closing socket should fail"),
+    }
 
     // wait() for the first close() qt.
-    match libos.wait(qt1, Some(Duration::from_micros(0))) {
+    match libos.wait(qt, Some(Duration::from_micros(0))) {
         Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_CLOSE && qr.qr_ret == 0 => {},
         _ => anyhow::bail!("wait() should succeed with async_close()"),
     }
 
-    // wait() for the second close() qt.
-    if let Some(qt2) = qt2 {
-        match libos.wait(qt2, Some(Duration::from_micros(0))) {
-            Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_FAILED && qr.qr_ret == libc::EBADF as i64 => {},
-            _ => anyhow::bail!("wait() should fail with async_close()"),
-        }
+    // Fail to close the socket once it is closed.
+    match libos.async_close(sockqd) {
+        Err(e) if e.errno == libc::EBADF => Ok(()),
+        Err(e) => anyhow::bail!("async_close() failed with {}", e),
+        Ok(_) => anyhow::bail!("async_close() a closed socket should fail"),
     }
-
-    Ok(())
 }
 
-/// Attempt to asynchronously close and wait on a TCP socket multiple times in reverse order.
+/// Attempts to synchronously close a TCP socket while an asynchronous close is still running.
 fn async_close_and_wait_twice_3(libos: &mut LibOS) -> Result<()> {
     let sockqd: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
-    let qt1: QToken = libos.async_close(sockqd)?;
-    let qt2: Option<QToken> = match libos.async_close(sockqd) {
-        Ok(qt) => Some(qt),
-        Err(e) if e.errno == libc::EBADF => None,
-        Err(e) => anyhow::bail!("async_close() should not fail with {:?}", &e),
-    };
-
-    // wait() for the second close() qt.
-    if let Some(qt2) = qt2 {
-        match libos.wait(qt2, Some(Duration::from_micros(0))) {
-            Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_FAILED && qr.qr_ret == libc::EBADF as i64 => {},
-            _ => anyhow::bail!("wait() should fail with async_close()"),
-        }
+    let qt: QToken = libos.async_close(sockqd)?;
+
+    // Fail to close the socket again, as it is already closing.
+    match libos.close(sockqd) {
+        Err(e) if e.errno == libc::EALREADY => {},
+        Err(e) => anyhow::bail!("close() failed with {}", e),
+        Ok(()) => anyhow::bail!("close() a closing socket should fail"),
     }
 
     // wait() for the first close() qt.
-    match libos.wait(qt1, Some(Duration::from_micros(0))) {
-        Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_CLOSE && qr.qr_ret == 0 => {},
+    match libos.wait(qt, Some(Duration::from_micros(0))) {
+        Ok(qr) if qr.qr_opcode == demi_opcode_t::DEMI_OPC_CLOSE && qr.qr_ret == 0 => Ok(()),
         _ => anyhow::bail!("wait() should succeed with async_close()"),
     }
-
-    Ok(())
 }
 
 /// Attempts to close a TCP socket that is not bound.
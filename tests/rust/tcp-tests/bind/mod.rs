@@ -45,6 +45,10 @@ pub fn run(libos: &mut LibOS, local: &IpAddr) -> Vec<(String, String, Result<(),
     crate::collect!(result, crate::test!(bind_to_private_ports(libos, local)));
     crate::collect!(result, crate::test!(bind_to_wildcard_port(libos, local)));
     crate::collect!(result, crate::test!(bind_to_wildcard_address(libos)));
+    crate::collect!(
+        result,
+        crate::test!(bind_wildcard_and_specific_address_to_same_port(libos, local))
+    );
     crate::collect!(result, crate::test!(bind_to_wildcard_address_and_port(libos)));
     crate::collect!(result, crate::test!(bind_to_non_local_address(libos)));
     crate::collect!(result, crate::test!(bind_to_closed_socket(libos, local)));
@@ -189,13 +193,8 @@ fn bind_to_wildcard_address(libos: &mut LibOS) -> Result<()> {
         SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)
     });
 
-    // Fail to bind socket.
-    // FIXME: https://github.com/demikernel/demikernel/issues/189
-    match libos.bind(sockqd, addr) {
-        Err(e) if e.errno == libc::ENOTSUP => (),
-        Err(e) => anyhow::bail!("bind() failed with {}", e),
-        Ok(()) => anyhow::bail!("bind() to the wildcard address should fail"),
-    };
+    // Bind socket.
+    libos.bind(sockqd, addr)?;
 
     // Close socket.
     libos.close(sockqd)?;
@@ -203,6 +202,37 @@ fn bind_to_wildcard_address(libos: &mut LibOS) -> Result<()> {
     Ok(())
 }
 
+/// Attempts to bind the wildcard address and a specific address on the same port to two sockets.
+fn bind_wildcard_and_specific_address_to_same_port(libos: &mut LibOS, local: &IpAddr) -> Result<()> {
+    // Create three TCP sockets.
+    let sockqd1: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
+    let sockqd2: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
+    let sockqd3: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
+
+    // Bind addresses.
+    let port: u16 = 8080;
+    let wildcard: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port);
+    let specific: SocketAddr = SocketAddr::new(*local, port);
+
+    // The wildcard address and a specific address do not conflict.
+    libos.bind(sockqd1, wildcard)?;
+    libos.bind(sockqd2, specific)?;
+
+    // But the wildcard address does conflict with itself.
+    match libos.bind(sockqd3, wildcard) {
+        Err(e) if e.errno == libc::EADDRINUSE => (),
+        Err(e) => anyhow::bail!("bind() failed with {}", e),
+        Ok(()) => anyhow::bail!("bind() the wildcard address to two sockets should fail"),
+    };
+
+    // Close sockets.
+    libos.close(sockqd1)?;
+    libos.close(sockqd2)?;
+    libos.close(sockqd3)?;
+
+    Ok(())
+}
+
 /// Attempts to bind to the wildcard address and port.
 fn bind_to_wildcard_address_and_port(libos: &mut LibOS) -> Result<()> {
     // Create a TCP socket.
@@ -14,6 +14,7 @@ mod bind;
 mod close;
 mod connect;
 mod listen;
+mod push;
 mod socket;
 mod wait;
 
@@ -84,6 +85,7 @@ fn main() -> Result<()> {
     crate::collect!(result, listen::run(&mut libos, &args.local(), &args.remote()));
     crate::collect!(result, accept::run(&mut libos, &args.local()));
     crate::collect!(result, connect::run(&mut libos, &args.local(), &args.remote()));
+    crate::collect!(result, push::run(&mut libos, &args.local(), &args.remote()));
     crate::collect!(result, close::run(&mut libos, &args.local()));
     crate::collect!(result, wait::run(&mut libos, &args.local()));
     crate::collect!(result, async_close::run(&mut libos, &args.local()));
@@ -111,5 +113,6 @@ pub fn check_for_network_error(qr: &demi_qresult_t) -> bool {
             || qr.qr_ret == (libc::ECANCELED as i64)
             || qr.qr_ret == (libc::ECONNREFUSED as i64)
             || qr.qr_ret == (libc::ECONNABORTED as i64)
+            || qr.qr_ret == (libc::ETIMEDOUT as i64)
             || qr.qr_ret == (libc::EADDRNOTAVAIL as i64))
 }
@@ -0,0 +1,100 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//======================================================================================================================
+// Imports
+//======================================================================================================================
+
+use anyhow::Result;
+use demikernel::{runtime::types::demi_sgarray_t, LibOS, QDesc};
+use std::net::SocketAddr;
+
+//======================================================================================================================
+// Constants
+//======================================================================================================================
+
+#[cfg(target_os = "windows")]
+pub const AF_INET: i32 = windows::Win32::Networking::WinSock::AF_INET.0 as i32;
+
+#[cfg(target_os = "windows")]
+pub const SOCK_STREAM: i32 = windows::Win32::Networking::WinSock::SOCK_STREAM.0 as i32;
+
+#[cfg(target_os = "linux")]
+pub const AF_INET: i32 = libc::AF_INET;
+
+#[cfg(target_os = "linux")]
+pub const SOCK_STREAM: i32 = libc::SOCK_STREAM;
+
+/// Size of the buffers that we push.
+const DATA_SIZE: usize = 64;
+
+//======================================================================================================================
+// Standalone Functions
+//======================================================================================================================
+
+/// Drives integration tests for push() and pushto() on TCP sockets.
+pub fn run(
+    libos: &mut LibOS,
+    local: &SocketAddr,
+    remote: &SocketAddr,
+) -> Vec<(String, String, Result<(), anyhow::Error>)> {
+    let mut result: Vec<(String, String, Result<(), anyhow::Error>)> = Vec::new();
+
+    crate::collect!(result, crate::test!(pushto_unbound_socket(libos, remote)));
+    crate::collect!(result, crate::test!(pushto_listening_socket(libos, local, remote)));
+    crate::collect!(result, crate::test!(push_unconnected_socket(libos, local)));
+
+    result
+}
+
+/// Attempts to pushto on a TCP socket that is not bound. We do not connect implicitly, so this must fail with
+/// EOPNOTSUPP.
+fn pushto_unbound_socket(libos: &mut LibOS, remote: &SocketAddr) -> Result<()> {
+    let sockqd: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
+
+    let sga: demi_sgarray_t = libos.sgaalloc(DATA_SIZE)?;
+    let result = libos.pushto(sockqd, &sga, remote.to_owned());
+    libos.sgafree(sga)?;
+    libos.close(sockqd)?;
+
+    match result {
+        Err(e) if e.errno == libc::EOPNOTSUPP => Ok(()),
+        Err(e) => anyhow::bail!("pushto() failed with {}", e),
+        Ok(_) => anyhow::bail!("pushto() on an unbound socket should fail"),
+    }
+}
+
+/// Attempts to pushto on a TCP socket that is listening. This must fail with EOPNOTSUPP.
+fn pushto_listening_socket(libos: &mut LibOS, local: &SocketAddr, remote: &SocketAddr) -> Result<()> {
+    let sockqd: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
+    libos.bind(sockqd, local.to_owned())?;
+    libos.listen(sockqd, 16)?;
+
+    let sga: demi_sgarray_t = libos.sgaalloc(DATA_SIZE)?;
+    let result = libos.pushto(sockqd, &sga, remote.to_owned());
+    libos.sgafree(sga)?;
+    libos.close(sockqd)?;
+
+    match result {
+        Err(e) if e.errno == libc::EOPNOTSUPP => Ok(()),
+        Err(e) => anyhow::bail!("pushto() failed with {}", e),
+        Ok(_) => anyhow::bail!("pushto() on a listening socket should fail"),
+    }
+}
+
+/// Attempts to push on a TCP socket that is bound but not connected. This must fail with ENOTCONN.
+fn push_unconnected_socket(libos: &mut LibOS, local: &SocketAddr) -> Result<()> {
+    let sockqd: QDesc = libos.socket(AF_INET, SOCK_STREAM, 0)?;
+    libos.bind(sockqd, local.to_owned())?;
+
+    let sga: demi_sgarray_t = libos.sgaalloc(DATA_SIZE)?;
+    let result = libos.push(sockqd, &sga);
+    libos.sgafree(sga)?;
+    libos.close(sockqd)?;
+
+    match result {
+        Err(e) if e.errno == libc::ENOTCONN => Ok(()),
+        Err(e) => anyhow::bail!("push() failed with {}", e),
+        Ok(_) => anyhow::bail!("push() on an unconnected socket should fail"),
+    }
+}
@@ -8,14 +8,18 @@ mod test {
     //======================================================================================================================
     // Imports
     //======================================================================================================================
-    use crate::common::{libos::*, ALICE_CONFIG_PATH, ALICE_IP, BOB_CONFIG_PATH, BOB_IP, PORT_NUMBER};
+    use crate::common::{
+        libos::*, runtime::TxBufferPool, ALICE_CONFIG_PATH, ALICE_IP, BOB_CONFIG_PATH, BOB_IP, PORT_NUMBER,
+    };
     use ::anyhow::Result;
     use ::demikernel::{
         demi_sgarray_t,
         runtime::{
             memory::{DemiBuffer, MemoryRuntime},
+            types::{demi_linked_op_t, demi_opcode_t, DEMI_LINKED_QD},
             OperationResult, QDesc, QToken,
         },
+        PopFlags,
     };
     use ::socket2::{Domain, Protocol, Type};
     use crossbeam_channel::{Receiver, Sender};
@@ -36,12 +40,14 @@ mod test {
     /// ensure most OS operations will complete.
     const TIMEOUT_MILLISECONDS: Duration = Duration::from_millis(100);
     const BAD_WAIT_TIMEOUT_MILLISECONDS: Duration = Duration::from_millis(1);
+    /// Number of transmit buffers for tests that exhaust them. This is plenty for a single connection.
+    const TX_POOL_SIZE: usize = 1024;
 
     use std::{
-        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV6},
         sync::{Arc, Barrier},
         thread::{self, JoinHandle},
-        time::Duration,
+        time::{Duration, Instant},
     };
     #[cfg(target_os = "windows")]
     use windows::Win32::Networking::WinSock;
@@ -287,7 +293,7 @@ mod test {
             let qt: QToken = safe_pop(&mut libos, qd)?;
             let (qd, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
             match qr {
-                OperationResult::Pop(_, _) => (),
+                OperationResult::Pop(_, _, _) => (),
                 _ => {
                     // Close socket on error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
@@ -349,6 +355,237 @@ mod test {
         Ok(())
     }
 
+    /// Tests if a push fails with ENOBUFS while the pool of transmit buffers is exhausted, and can be retried once it was
+    /// refilled.
+    #[test]
+    fn tcp_push_remote_no_buffers() -> Result<()> {
+        let (alice_tx, alice_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+        let (bob_tx, bob_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+
+        let bob_barrier: Arc<Barrier> = Arc::new(Barrier::new(2));
+        let alice_barrier: Arc<Barrier> = bob_barrier.clone();
+
+        let alice: JoinHandle<Result<()>> = thread::Builder::new().name(format!("alice")).spawn(move || {
+            let mut libos: DummyLibOS = match DummyLibOS::new_test(ALICE_CONFIG_PATH, alice_tx, bob_rx) {
+                Ok(libos) => libos,
+                Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+            };
+
+            let local: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+            // Open connection.
+            let sockqd: QDesc = safe_socket(&mut libos)?;
+            safe_bind(&mut libos, sockqd, local)?;
+            safe_listen(&mut libos, sockqd)?;
+            let qt: QToken = safe_accept(&mut libos, sockqd)?;
+            let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
+            let qd: QDesc = match qr {
+                OperationResult::Accept((qd, addr)) if addr.ip() == &BOB_IP => qd,
+                _ => {
+                    // Close socket on error.
+                    // FIXME: https://github.com/demikernel/demikernel/issues/633
+                    anyhow::bail!("accept() has failed")
+                },
+            };
+
+            // Pop data. Only the push that was retried should make it.
+            let qt: QToken = safe_pop(&mut libos, qd)?;
+            let (qd, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
+            match qr {
+                OperationResult::Pop(_, buf, _) if buf.len() == 32 => (),
+                _ => {
+                    // Close socket on error.
+                    // FIXME: https://github.com/demikernel/demikernel/issues/633
+                    anyhow::bail!("pop() has has failed {:?}", qr)
+                },
+            }
+
+            // Close connection.
+            safe_close_active(&mut libos, qd)?;
+            safe_close_passive(&mut libos, sockqd)?;
+            alice_barrier.wait();
+            Ok(())
+        })?;
+
+        let bob: JoinHandle<Result<()>> = thread::Builder::new().name(format!("bob")).spawn(move || {
+            let tx_pool: TxBufferPool = TxBufferPool::new(TX_POOL_SIZE);
+            let mut libos: DummyLibOS =
+                match DummyLibOS::new_test_with_tx_pool(BOB_CONFIG_PATH, bob_tx, alice_rx, tx_pool.clone()) {
+                    Ok(libos) => libos,
+                    Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+                };
+
+            let remote: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+            // Open connection.
+            let sockqd: QDesc = safe_socket(&mut libos)?;
+            let qt: QToken = safe_connect(&mut libos, sockqd, remote)?;
+            let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
+            match qr {
+                OperationResult::Connect => (),
+                _ => {
+                    // Close socket on error.
+                    // FIXME: https://github.com/demikernel/demikernel/issues/633
+                    anyhow::bail!("connect() has failed")
+                },
+            }
+
+            // Push data while the pool is exhausted.
+            tx_pool.drain();
+            let buf = libos.prepare_dummy_buffer(32)?;
+            let qt: QToken = safe_push(&mut libos, sockqd, buf)?;
+            let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
+            match qr {
+                OperationResult::Failed(e) if e.errno == libc::ENOBUFS => (),
+                _ => {
+                    // Close socket on error.
+                    // FIXME: https://github.com/demikernel/demikernel/issues/633
+                    anyhow::bail!("push() should fail with ENOBUFS, got {:?}", qr)
+                },
+            }
+
+            // Push data again once the pool was refilled.
+            tx_pool.refill(TX_POOL_SIZE);
+            let buf = libos.prepare_dummy_buffer(32)?;
+            let qt: QToken = safe_push(&mut libos, sockqd, buf)?;
+            let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
+            match qr {
+                OperationResult::Push => (),
+                _ => {
+                    // Close socket on error.
+                    // FIXME: https://github.com/demikernel/demikernel/issues/633
+                    anyhow::bail!("push() has failed {:?}", qr)
+                },
+            }
+
+            // Close connection.
+            safe_close_active(&mut libos, sockqd)?;
+            bob_barrier.wait();
+
+            Ok(())
+        })?;
+        // It is safe to use unwrap here because there should not be any reason that we can't join the thread and if there
+        // is, there is nothing to clean up here on the main thread.
+        alice.join().unwrap()?;
+        bob.join().unwrap()?;
+
+        Ok(())
+    }
+
+    //======================================================================================================================
+    // Splice
+    //======================================================================================================================
+
+    /// Tests if a splice forwards data from one connection to another and passes the end of the stream on.
+    #[test]
+    fn tcp_splice() -> Result<()> {
+        let (alice_tx, alice_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+        let (bob_tx, bob_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+
+        let bob_barrier: Arc<Barrier> = Arc::new(Barrier::new(2));
+        let alice_barrier: Arc<Barrier> = bob_barrier.clone();
+
+        // Alice relays the first connection of Bob to his second one.
+        let alice: JoinHandle<Result<()>> = thread::Builder::new().name(format!("alice")).spawn(move || {
+            let mut libos: DummyLibOS = match DummyLibOS::new_test(ALICE_CONFIG_PATH, alice_tx, bob_rx) {
+                Ok(libos) => libos,
+                Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+            };
+
+            let local: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+            // Open connections.
+            let sockqd: QDesc = safe_socket(&mut libos)?;
+            safe_bind(&mut libos, sockqd, local)?;
+            safe_listen(&mut libos, sockqd)?;
+            let mut qds: Vec<QDesc> = Vec::with_capacity(2);
+            for _ in 0..2 {
+                let qt: QToken = safe_accept(&mut libos, sockqd)?;
+                match safe_wait(&mut libos, qt)? {
+                    (_, OperationResult::Accept((qd, addr))) if addr.ip() == &BOB_IP => qds.push(qd),
+                    _ => anyhow::bail!("accept() has failed"),
+                }
+            }
+            let (src_qd, dst_qd): (QDesc, QDesc) = (qds[0], qds[1]);
+
+            // The splice completes once Bob is done sending on his first connection.
+            let qt: QToken = match libos.splice(src_qd, dst_qd, None) {
+                Ok(qt) => qt,
+                Err(e) => anyhow::bail!("splice() failed: {:?}", e),
+            };
+            match safe_wait(&mut libos, qt)? {
+                (qd, OperationResult::Push) if qd == dst_qd => (),
+                (_, qr) => anyhow::bail!("splice() has failed {:?}", qr),
+            }
+
+            // Close connections.
+            safe_close_active(&mut libos, dst_qd)?;
+            safe_close_active(&mut libos, src_qd)?;
+            safe_close_passive(&mut libos, sockqd)?;
+            alice_barrier.wait();
+
+            Ok(())
+        })?;
+
+        let bob: JoinHandle<Result<()>> = thread::Builder::new().name(format!("bob")).spawn(move || {
+            let mut libos: DummyLibOS = match DummyLibOS::new_test(BOB_CONFIG_PATH, bob_tx, alice_rx) {
+                Ok(libos) => libos,
+                Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+            };
+
+            let remote: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+            // Open connections, one after the other so that Alice accepts them in this order.
+            let mut qds: Vec<QDesc> = Vec::with_capacity(2);
+            for _ in 0..2 {
+                let sockqd: QDesc = safe_socket(&mut libos)?;
+                let qt: QToken = safe_connect(&mut libos, sockqd, remote)?;
+                match safe_wait(&mut libos, qt)? {
+                    (_, OperationResult::Connect) => qds.push(sockqd),
+                    _ => anyhow::bail!("connect() has failed"),
+                }
+            }
+            let (client_qd, server_qd): (QDesc, QDesc) = (qds[0], qds[1]);
+
+            // Send data on the first connection and shut it down for writing.
+            let buf: demi_sgarray_t = libos.prepare_dummy_buffer(32)?;
+            let qt: QToken = safe_push(&mut libos, client_qd, buf)?;
+            match safe_wait(&mut libos, qt)? {
+                (_, OperationResult::Push) => (),
+                _ => anyhow::bail!("push() has failed"),
+            }
+            if let Err(e) = libos.shutdown(client_qd, Shutdown::Write) {
+                anyhow::bail!("shutdown() failed: {:?}", e);
+            }
+
+            // The data comes out of the second connection, followed by the end of the stream.
+            let qt: QToken = safe_pop(&mut libos, server_qd)?;
+            match safe_wait(&mut libos, qt)? {
+                (_, OperationResult::Pop(_, buf, _)) if buf.len() == 32 => (),
+                (_, qr) => anyhow::bail!("pop() should return the spliced data {:?}", qr),
+            }
+            let qt: QToken = safe_pop(&mut libos, server_qd)?;
+            match safe_wait(&mut libos, qt)? {
+                (_, OperationResult::Pop(_, buf, _)) if buf.len() == 0 => (),
+                (_, qr) => anyhow::bail!("pop() should return the end of the stream {:?}", qr),
+            }
+
+            // Close connections.
+            safe_close_active(&mut libos, server_qd)?;
+            safe_close_active(&mut libos, client_qd)?;
+            bob_barrier.wait();
+
+            Ok(())
+        })?;
+
+        // It is safe to use unwrap here because there should not be any reason that we can't join the thread and if there
+        // is, there is nothing to clean up here on the main thread.
+        alice.join().unwrap()?;
+        bob.join().unwrap()?;
+
+        Ok(())
+    }
+
     //======================================================================================================================
     // Bad Socket
     //======================================================================================================================
@@ -603,6 +840,132 @@ mod test {
         Ok(())
     }
 
+    //======================================================================================================================
+    // Pending Connections
+    //======================================================================================================================
+
+    /// Tests calls for `has_pending_connections()`.
+    #[test]
+    fn tcp_has_pending_connections() -> Result<()> {
+        let (tx, rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+        let mut libos: DummyLibOS = match DummyLibOS::new_test(ALICE_CONFIG_PATH, tx, rx) {
+            Ok(libos) => libos,
+            Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+        };
+
+        let local: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+        // Invalid queue descriptor.
+        match libos.has_pending_connections(QDesc::from(0)) {
+            Err(e) if e.errno == libc::EBADF => (),
+            _ => anyhow::bail!("invalid call to has_pending_connections() should fail with EBADF"),
+        };
+
+        // Socket that is not listening.
+        let sockqd: QDesc = safe_socket(&mut libos)?;
+        safe_bind(&mut libos, sockqd, local)?;
+        match libos.has_pending_connections(sockqd) {
+            Err(e) if e.errno == libc::EINVAL => (),
+            _ => anyhow::bail!("has_pending_connections() on a socket that is not listening should fail with EINVAL"),
+        };
+
+        // Listening socket without any connection requests.
+        safe_listen(&mut libos, sockqd)?;
+        match libos.has_pending_connections(sockqd) {
+            Ok(false) => (),
+            _ => anyhow::bail!("has_pending_connections() should not report connections that never arrived"),
+        };
+        safe_close_passive(&mut libos, sockqd)?;
+
+        Ok(())
+    }
+
+    /// Tests if `has_pending_connections()` reports a connection that completed its handshake but was not accepted yet.
+    #[test]
+    fn tcp_has_pending_connections_queued() -> Result<()> {
+        let (alice_tx, alice_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+        let (bob_tx, bob_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+
+        let bob_barrier: Arc<Barrier> = Arc::new(Barrier::new(2));
+        let alice_barrier: Arc<Barrier> = bob_barrier.clone();
+
+        let alice: JoinHandle<Result<()>> = thread::Builder::new().name(format!("alice")).spawn(move || {
+            let mut libos: DummyLibOS = match DummyLibOS::new_test(ALICE_CONFIG_PATH, alice_tx, bob_rx) {
+                Ok(libos) => libos,
+                Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+            };
+
+            let local: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+            let sockqd: QDesc = safe_socket(&mut libos)?;
+            safe_bind(&mut libos, sockqd, local)?;
+            safe_listen(&mut libos, sockqd)?;
+            alice_barrier.wait();
+
+            // Run the stack, without accepting, until the handshake with Bob completes.
+            let deadline: Instant = Instant::now() + TIMEOUT_MILLISECONDS * 10;
+            loop {
+                libos.poll();
+                match libos.has_pending_connections(sockqd) {
+                    Ok(true) => break,
+                    Ok(false) if Instant::now() < deadline => continue,
+                    _ => anyhow::bail!("has_pending_connections() should report the connection that is queued"),
+                }
+            }
+
+            // Accepting the connection takes it off the queue.
+            let qt: QToken = safe_accept(&mut libos, sockqd)?;
+            let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
+            let qd: QDesc = match qr {
+                OperationResult::Accept((qd, addr)) if addr.ip() == &BOB_IP => qd,
+                _ => anyhow::bail!("accept() has failed"),
+            };
+            match libos.has_pending_connections(sockqd) {
+                Ok(false) => (),
+                _ => anyhow::bail!("has_pending_connections() should not report connections that were accepted"),
+            };
+
+            // Close connection.
+            safe_close_active(&mut libos, qd)?;
+            safe_close_passive(&mut libos, sockqd)?;
+            alice_barrier.wait();
+
+            Ok(())
+        })?;
+
+        let bob: JoinHandle<Result<()>> = thread::Builder::new().name(format!("bob")).spawn(move || {
+            let mut libos: DummyLibOS = match DummyLibOS::new_test(BOB_CONFIG_PATH, bob_tx, alice_rx) {
+                Ok(libos) => libos,
+                Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+            };
+
+            let remote: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+            // Wait for Alice to listen before connecting.
+            bob_barrier.wait();
+            let sockqd: QDesc = safe_socket(&mut libos)?;
+            let qt: QToken = safe_connect(&mut libos, sockqd, remote)?;
+            let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
+            match qr {
+                OperationResult::Connect => (),
+                _ => anyhow::bail!("connect() has failed"),
+            }
+
+            // Close connection.
+            safe_close_active(&mut libos, sockqd)?;
+            bob_barrier.wait();
+
+            Ok(())
+        })?;
+
+        // It is safe to use unwrap here because there should not be any reason that we can't join the thread and if there
+        // is, there is nothing to clean up here on the main thread.
+        alice.join().unwrap()?;
+        bob.join().unwrap()?;
+
+        Ok(())
+    }
+
     //======================================================================================================================
     // Bad Connect
     //======================================================================================================================
@@ -851,7 +1214,7 @@ mod test {
             let qt: QToken = safe_pop(&mut libos, qd)?;
             let (qd, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
             match qr {
-                OperationResult::Pop(_, _) => (),
+                OperationResult::Pop(_, _, _) => (),
                 _ => {
                     // Close socket if error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
@@ -980,7 +1343,7 @@ mod test {
             };
 
             // Pop from bad socket.
-            match libos.pop(QDesc::from(2), None) {
+            match libos.pop(QDesc::from(2), None, PopFlags::default()) {
                 Ok(_) => {
                     // Close socket if not error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
@@ -993,7 +1356,7 @@ mod test {
             let qt: QToken = safe_pop(&mut libos, qd)?;
             let (qd, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
             match qr {
-                OperationResult::Pop(_, _) => (),
+                OperationResult::Pop(_, _, _) => (),
                 _ => {
                     // Close socket if error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
@@ -1057,10 +1420,120 @@ mod test {
         Ok(())
     }
 
+    //======================================================================================================================
+    // Linked Operations
+    //======================================================================================================================
+
+    /// Tests that the operations after one that fails in the middle of a linked chain are cancelled.
+    #[test]
+    fn tcp_submit_linked_broken_chain() -> Result<()> {
+        let (alice_tx, alice_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+        let (bob_tx, bob_rx): (Sender<DemiBuffer>, Receiver<DemiBuffer>) = crossbeam_channel::unbounded();
+
+        let bob_barrier: Arc<Barrier> = Arc::new(Barrier::new(2));
+        let alice_barrier: Arc<Barrier> = bob_barrier.clone();
+
+        let alice: JoinHandle<Result<()>> = thread::Builder::new().name(format!("alice")).spawn(move || {
+            let mut libos: DummyLibOS = match DummyLibOS::new_test(ALICE_CONFIG_PATH, alice_tx, bob_rx) {
+                Ok(libos) => libos,
+                Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+            };
+
+            let local: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+            let sockqd: QDesc = safe_socket(&mut libos)?;
+            safe_bind(&mut libos, sockqd, local)?;
+            safe_listen(&mut libos, sockqd)?;
+
+            // Accept a connection and push on it, then break the chain with a pop on the listening socket. The push
+            // after that never runs.
+            let bytes: demi_sgarray_t = libos.prepare_dummy_buffer(32)?;
+            let ops: [demi_linked_op_t; 4] = [
+                linked_op(demi_opcode_t::DEMI_OPC_ACCEPT, sockqd.into(), bytes),
+                linked_op(demi_opcode_t::DEMI_OPC_PUSH, DEMI_LINKED_QD, bytes),
+                linked_op(demi_opcode_t::DEMI_OPC_POP, sockqd.into(), bytes),
+                linked_op(demi_opcode_t::DEMI_OPC_PUSH, DEMI_LINKED_QD, bytes),
+            ];
+            let qts: Vec<QToken> = match libos.submit_linked(&ops) {
+                Ok(qts) => qts,
+                Err(e) => anyhow::bail!("submit_linked() failed: {:?}", e),
+            };
+            let qd: QDesc = match safe_wait(&mut libos, qts[0])? {
+                (_, OperationResult::Accept((qd, addr))) if addr.ip() == &BOB_IP => qd,
+                (_, qr) => anyhow::bail!("accept() should have succeeded: {:?}", qr),
+            };
+            match safe_wait(&mut libos, qts[1])? {
+                (_, OperationResult::Push) => (),
+                (_, qr) => anyhow::bail!("push() should have succeeded: {:?}", qr),
+            }
+            match safe_wait(&mut libos, qts[2])? {
+                (_, OperationResult::Failed(_)) => (),
+                (_, qr) => anyhow::bail!("pop() on a listening socket should have failed: {:?}", qr),
+            }
+            match safe_wait(&mut libos, qts[3])? {
+                (_, OperationResult::Failed(e)) if e.errno == libc::ECANCELED => (),
+                (_, qr) => anyhow::bail!("push() after a failed operation should have been cancelled: {:?}", qr),
+            }
+            libos.sgafree(bytes)?;
+
+            // Close connection.
+            safe_close_active(&mut libos, qd)?;
+            safe_close_passive(&mut libos, sockqd)?;
+
+            alice_barrier.wait();
+            Ok(())
+        })?;
+
+        let bob: JoinHandle<Result<()>> = thread::Builder::new().name(format!("bob")).spawn(move || {
+            let mut libos: DummyLibOS = match DummyLibOS::new_test(BOB_CONFIG_PATH, bob_tx, alice_rx) {
+                Ok(libos) => libos,
+                Err(e) => anyhow::bail!("Could not create inetstack: {:?}", e),
+            };
+
+            let remote: SocketAddr = SocketAddr::new(ALICE_IP, PORT_NUMBER);
+
+            // Open connection.
+            let sockqd: QDesc = safe_socket(&mut libos)?;
+            let qt: QToken = safe_connect(&mut libos, sockqd, remote)?;
+            match safe_wait(&mut libos, qt)? {
+                (_, OperationResult::Connect) => (),
+                (_, qr) => anyhow::bail!("connect() has failed: {:?}", qr),
+            }
+
+            // Only the push before the break arrives.
+            let qt: QToken = safe_pop(&mut libos, sockqd)?;
+            match safe_wait(&mut libos, qt)? {
+                (_, OperationResult::Pop(_, buf, _)) if buf.len() == 32 => (),
+                (_, qr) => anyhow::bail!("pop() has failed: {:?}", qr),
+            }
+
+            // Close connection.
+            safe_close_active(&mut libos, sockqd)?;
+
+            bob_barrier.wait();
+            Ok(())
+        })?;
+
+        // It is safe to use unwrap here because there should not be any reason that we can't join the thread and if there
+        // is, there is nothing to clean up here on the main thread.
+        alice.join().unwrap()?;
+        bob.join().unwrap()?;
+
+        Ok(())
+    }
+
     //======================================================================================================================
     // Standalone Functions
     //======================================================================================================================
 
+    /// Builds an operation of a linked chain.
+    fn linked_op(op_opcode: demi_opcode_t, op_qd: i32, op_sga: demi_sgarray_t) -> demi_linked_op_t {
+        demi_linked_op_t {
+            op_opcode,
+            op_qd,
+            op_sga,
+        }
+    }
+
     /// Safe call to `socket()`.
     fn safe_socket(libos: &mut DummyLibOS) -> Result<QDesc> {
         match libos.socket(Domain::IPV4, Type::STREAM, Protocol::TCP) {
@@ -1119,7 +1592,7 @@ mod test {
 
     /// Safe call to `pop()`.
     fn safe_pop(libos: &mut DummyLibOS, qd: QDesc) -> Result<QToken> {
-        match libos.pop(qd, None) {
+        match libos.pop(qd, None, PopFlags::default()) {
             Ok(qt) => Ok(qt),
             Err(e) => {
                 // Close socket on error.
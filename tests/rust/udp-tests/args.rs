@@ -55,8 +55,6 @@ impl ProgramArguments {
         self.local_socket_addr
     }
 
-    // ToDo: Remove this `unused` annotation after remote_socket_addr is used (when new tests are added to this file).
-    #[allow(unused)]
     pub fn get_remote_socket_addr(&self) -> SocketAddr {
         self.remote_socket_addr
     }
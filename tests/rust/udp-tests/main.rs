@@ -6,6 +6,7 @@
 mod args;
 mod bind;
 mod close;
+mod push;
 
 use anyhow::Result;
 use args::ProgramArguments;
@@ -51,6 +52,15 @@ fn main() -> Result<()> {
         close::run_tests(&mut libos, &args.get_local_socket_addr().ip())
     );
 
+    append_test_result!(
+        test_results,
+        push::run_tests(
+            &mut libos,
+            &args.get_local_socket_addr().ip(),
+            &args.get_remote_socket_addr()
+        )
+    );
+
     for (test_name, test_status, test_result) in test_results {
         println!("[{}] {}", test_status, test_name);
         if let Err(e) = test_result {
@@ -0,0 +1,95 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use anyhow::Result;
+use demikernel::{
+    runtime::types::{demi_opcode_t, demi_qresult_t, demi_sgarray_t},
+    LibOS, QDesc, QToken,
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+#[cfg(target_os = "windows")]
+pub const AF_INET: i32 = windows::Win32::Networking::WinSock::AF_INET.0 as i32;
+
+#[cfg(target_os = "windows")]
+pub const SOCK_DGRAM: i32 = windows::Win32::Networking::WinSock::SOCK_DGRAM.0 as i32;
+
+#[cfg(target_os = "linux")]
+pub const AF_INET: i32 = libc::AF_INET;
+
+#[cfg(target_os = "linux")]
+pub const SOCK_DGRAM: i32 = libc::SOCK_DGRAM;
+
+/// Size of the datagrams that we push.
+const DATA_SIZE: usize = 64;
+
+pub fn run_tests(
+    libos: &mut LibOS,
+    local_ip_address: &IpAddr,
+    remote: &SocketAddr,
+) -> Vec<(String, String, Result<(), anyhow::Error>)> {
+    let mut test_results: Vec<(String, String, Result<(), anyhow::Error>)> = Vec::new();
+
+    crate::append_test_result!(
+        test_results,
+        crate::test!(push_unconnected_socket(libos, local_ip_address))
+    );
+
+    crate::append_test_result!(
+        test_results,
+        crate::test!(push_connected_socket(libos, local_ip_address, remote))
+    );
+
+    test_results
+}
+
+/// Pushing without a destination on a socket that is not connected must fail with EDESTADDRREQ.
+fn push_unconnected_socket(libos: &mut LibOS, local_ip_address: &IpAddr) -> Result<()> {
+    let bind_address: SocketAddr = SocketAddr::new(*local_ip_address, 8080);
+    let socket_qd: QDesc = libos.socket(AF_INET, SOCK_DGRAM, 0)?;
+    libos.bind(socket_qd, bind_address)?;
+
+    let sga: demi_sgarray_t = libos.sgaalloc(DATA_SIZE)?;
+    let result = libos.push(socket_qd, &sga);
+    libos.sgafree(sga)?;
+    libos.close(socket_qd)?;
+
+    match result {
+        Err(e) if e.errno == libc::EDESTADDRREQ => Ok(()),
+        Err(e) => anyhow::bail!("push() failed with {}", e),
+        Ok(_) => anyhow::bail!("push() on an unconnected socket should fail"),
+    }
+}
+
+/// Once connected, a socket takes both push and pushto.
+fn push_connected_socket(libos: &mut LibOS, local_ip_address: &IpAddr, remote: &SocketAddr) -> Result<()> {
+    let bind_address: SocketAddr = SocketAddr::new(*local_ip_address, 8080);
+    let socket_qd: QDesc = libos.socket(AF_INET, SOCK_DGRAM, 0)?;
+    libos.bind(socket_qd, bind_address)?;
+
+    // Connecting a datagram socket only records the remote, so it completes right away.
+    let qt: QToken = libos.connect(socket_qd, *remote)?;
+    let qr: demi_qresult_t = libos.wait(qt, Some(Duration::from_secs(1)))?;
+    if qr.qr_opcode != demi_opcode_t::DEMI_OPC_CONNECT {
+        libos.close(socket_qd)?;
+        anyhow::bail!("connect() should succeed (ret={:?})", qr.qr_ret);
+    }
+
+    // The remote may not exist, so we only check that the pushes get scheduled.
+    let sga: demi_sgarray_t = libos.sgaalloc(DATA_SIZE)?;
+    let push_result = libos.push(socket_qd, &sga);
+    let pushto_result = libos.pushto(socket_qd, &sga, *remote);
+    libos.sgafree(sga)?;
+    libos.close(socket_qd)?;
+
+    if let Err(e) = push_result {
+        anyhow::bail!("push() on a connected socket failed with {}", e);
+    }
+    if let Err(e) = pushto_result {
+        anyhow::bail!("pushto() on a connected socket failed with {}", e);
+    }
+    Ok(())
+}
@@ -11,9 +11,12 @@ mod test {
 
     use crate::common::{libos::*, ALICE_CONFIG_PATH, ALICE_IP, BOB_CONFIG_PATH, BOB_IP, PORT_NUMBER};
     use ::anyhow::Result;
-    use ::demikernel::runtime::{
-        memory::{DemiBuffer, MemoryRuntime},
-        OperationResult, QDesc, QToken,
+    use ::demikernel::{
+        runtime::{
+            memory::{DemiBuffer, MemoryRuntime},
+            OperationResult, QDesc, QToken,
+        },
+        PopFlags,
     };
     use crossbeam_channel::{Receiver, Sender};
 
@@ -216,7 +219,7 @@ mod test {
             }
 
             // Pop data.
-            let qt: QToken = match libos.pop(sockfd, None) {
+            let qt: QToken = match libos.pop(sockfd, None, PopFlags::default()) {
                 Ok(qt) => qt,
                 Err(e) => {
                     // Close socket on error.
@@ -226,7 +229,7 @@ mod test {
             };
             let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
             match qr {
-                OperationResult::Pop(_, _) => (),
+                OperationResult::Pop(_, _, _) => (),
                 _ => {
                     // Close socket on error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
@@ -266,7 +269,7 @@ mod test {
             };
 
             // Pop data.
-            let qt: QToken = match libos.pop(sockfd, None) {
+            let qt: QToken = match libos.pop(sockfd, None, PopFlags::default()) {
                 Ok(qt) => qt,
                 Err(e) => {
                     // Close socket on error.
@@ -276,7 +279,7 @@ mod test {
             };
             let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
             let bytes: DemiBuffer = match qr {
-                OperationResult::Pop(_, bytes) => bytes,
+                OperationResult::Pop(_, bytes, _) => bytes,
                 _ => {
                     // Close socket on error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
@@ -375,7 +378,7 @@ mod test {
                 },
             }
 
-            let qt: QToken = match libos.pop(sockfd, None) {
+            let qt: QToken = match libos.pop(sockfd, None, PopFlags::default()) {
                 Ok(qt) => qt,
                 Err(e) => {
                     // Close socket on error.
@@ -386,7 +389,7 @@ mod test {
 
             let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
             match qr {
-                OperationResult::Pop(_, _) => (),
+                OperationResult::Pop(_, _, _) => (),
                 _ => {
                     // Close socket on error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
@@ -424,7 +427,7 @@ mod test {
                 },
             };
             // Pop data.
-            let qt: QToken = match libos.pop(sockfd, None) {
+            let qt: QToken = match libos.pop(sockfd, None, PopFlags::default()) {
                 Ok(qt) => qt,
                 Err(e) => {
                     // Close socket on error.
@@ -434,7 +437,7 @@ mod test {
             };
             let (_, qr): (QDesc, OperationResult) = safe_wait(&mut libos, qt)?;
             let bytes: DemiBuffer = match qr {
-                OperationResult::Pop(_, bytes) => bytes,
+                OperationResult::Pop(_, bytes, _) => bytes,
                 _ => {
                     // Close socket on error.
                     // FIXME: https://github.com/demikernel/demikernel/issues/633
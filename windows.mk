@@ -26,6 +26,9 @@ XDP_PATH = $(USERPROFILE)\xdp
 #=======================================================================================================================
 
 BUILD = release
+!if "$(CONTAIN_PANICS)" == "yes"
+BUILD = release-unwind
+!endif
 !if "$(DEBUG)" == "yes"
 BUILD = dev
 !ifndef RUST_LOG
@@ -48,6 +51,9 @@ INCDIR = $(MAKEDIR)\include
 SRCDIR = $(MAKEDIR)\src
 
 BUILD_DIR = $(MAKEDIR)\target\release
+!if "$(BUILD)" == "release-unwind"
+BUILD_DIR = $(MAKEDIR)\target\release-unwind
+!endif
 !if "$(BUILD)" == "dev"
 BUILD_DIR = $(MAKEDIR)\target\debug
 !endif